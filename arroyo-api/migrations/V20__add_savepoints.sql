@@ -0,0 +1,26 @@
+create type savepoint_state as ENUM ('pending', 'inprogress', 'ready', 'failed');
+
+-- savepoints outlive the jobs they were taken from, so that new pipelines can be started from them
+CREATE TABLE savepoints (
+    id BIGSERIAL PRIMARY KEY,
+    pub_id VARCHAR UNIQUE NOT NULL,
+    organization_id VARCHAR NOT NULL,
+    job_id VARCHAR REFERENCES job_configs(id) ON DELETE SET NULL,
+    name TEXT NOT NULL,
+    created_by VARCHAR NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    finish_time TIMESTAMPTZ,
+    state savepoint_state NOT NULL DEFAULT 'pending',
+    state_backend TEXT,
+    epoch INT,
+    path TEXT,
+    operators JSONB DEFAULT '[]' NOT NULL,
+    failure_message TEXT,
+
+    UNIQUE(organization_id, name)
+);
+
+CREATE INDEX savepoints_job_id_idx ON savepoints (job_id);
+
+ALTER TABLE job_configs
+ADD COLUMN restore_savepoint_id BIGINT REFERENCES savepoints(id) ON DELETE SET NULL;
@@ -0,0 +1,3 @@
+-- the hash of the program that the job's pipeline was compiled from, so that the controller can
+-- recompile it after the pipeline is upgraded
+ALTER TABLE job_statuses ADD COLUMN program_hash TEXT;
@@ -0,0 +1,3 @@
+-- the program a savepoint was taken from, so that pipelines it is restored into can be checked
+-- against the layout of its state
+ALTER TABLE savepoints ADD COLUMN program BYTEA;
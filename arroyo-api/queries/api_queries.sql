@@ -157,6 +157,21 @@ FROM pipelines
     LEFT JOIN job_statuses ON job_configs.id = job_statuses.id
 WHERE pipelines.pub_id = :pub_id AND pipelines.organization_id = :organization_id;
 
+--! update_pipeline_program
+UPDATE pipelines
+SET
+    updated_at = :updated_at,
+    updated_by = :updated_by,
+    textual_repr = :textual_repr,
+    udfs = :udfs,
+    program = :program
+WHERE pub_id = :pub_id AND organization_id = :organization_id
+RETURNING id;
+
+--! delete_pipeline_connection_tables
+DELETE FROM connection_table_pipelines
+WHERE pipeline_id = :pipeline_id;
+
 --! add_pipeline_connection_table
 INSERT INTO connection_table_pipelines(pub_id, pipeline_id, connection_table_id)
 VALUES (:pub_id, :pipeline_id, :connection_table_id);
@@ -188,10 +203,10 @@ SET
    restart_mode = :mode
 WHERE id = :job_id AND organization_id = :organization_id;
 
---! create_job(ttl_micros?)
+--! create_job(ttl_micros?, restore_savepoint_id?)
 INSERT INTO job_configs
-(id, organization_id, pipeline_name, created_by, pipeline_id, checkpoint_interval_micros, ttl_micros)
-VALUES (:id, :organization_id, :pipeline_name, :created_by, :pipeline_id, :checkpoint_interval_micros, :ttl_micros);
+(id, organization_id, pipeline_name, created_by, pipeline_id, checkpoint_interval_micros, ttl_micros, restore_savepoint_id)
+VALUES (:id, :organization_id, :pipeline_name, :created_by, :pipeline_id, :checkpoint_interval_micros, :ttl_micros, :restore_savepoint_id);
 
 --! create_job_status
 INSERT INTO job_statuses (pub_id, id, organization_id) VALUES (:pub_id, :id, :organization_id);
@@ -263,6 +278,30 @@ WHERE job_id = :job_id
     AND epoch = :epoch
     AND state != 'failed';
 
+----------- savepoints -----------------
+
+--: DbSavepoint (job_id?, finish_time?, epoch?, failure_message?)
+
+--! create_savepoint
+INSERT INTO savepoints (pub_id, organization_id, job_id, name, created_by)
+VALUES (:pub_id, :organization_id, :job_id, :name, :created_by);
+
+--! get_job_savepoints: DbSavepoint
+SELECT pub_id, name, job_id, state, created_at, finish_time, epoch, failure_message
+FROM savepoints
+WHERE organization_id = :organization_id AND job_id = :job_id
+ORDER BY created_at DESC;
+
+--! get_savepoint: DbSavepoint
+SELECT pub_id, name, job_id, state, created_at, finish_time, epoch, failure_message
+FROM savepoints
+WHERE organization_id = :organization_id AND pub_id = :pub_id;
+
+--! get_savepoint_to_restore: (program?)
+SELECT id, state, operators, program
+FROM savepoints
+WHERE organization_id = :organization_id AND pub_id = :pub_id;
+
 --! delete_pipeline_for_job
 DELETE FROM pipelines WHERE pipelines.id = (
     SELECT pipeline_id
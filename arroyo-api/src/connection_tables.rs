@@ -21,13 +21,14 @@ use arroyo_rpc::api_types::connections::{
     ConnectionProfile, ConnectionSchema, ConnectionTable, ConnectionTablePost, SchemaDefinition,
 };
 use arroyo_rpc::api_types::{ConnectionTableCollection, PaginationQueryParams};
-use arroyo_rpc::formats::{AvroFormat, Format, JsonFormat};
+use arroyo_rpc::formats::{AvroFormat, Format, JsonFormat, ProtobufFormat};
 use arroyo_rpc::public_ids::{generate_id, IdTypes};
 use arroyo_rpc::schema_resolver::{
     ConfluentSchemaRegistry, ConfluentSchemaSubjectResponse, ConfluentSchemaType,
 };
 use arroyo_sql::avro;
 use arroyo_sql::json_schema::convert_json_schema;
+use arroyo_sql::protobuf;
 use arroyo_sql::types::{StructField, TypeDef};
 
 use crate::rest::AppState;
@@ -437,8 +438,12 @@ pub(crate) async fn expand_schema(
         Format::Avro(_) => {
             expand_avro_schema(name, connector, schema, table_config, profile_config).await
         }
+        Format::Protobuf(_) => {
+            expand_proto_schema(connector, schema, table_config, profile_config).await
+        }
         Format::Parquet(_) => Ok(schema),
         Format::RawString(_) => Ok(schema),
+        Format::Csv(_) => Ok(schema),
     }
 }
 
@@ -488,6 +493,57 @@ async fn expand_avro_schema(
     Ok(schema)
 }
 
+async fn expand_proto_schema(
+    connector: &str,
+    mut schema: ConnectionSchema,
+    table_config: &Value,
+    profile_config: &Value,
+) -> Result<ConnectionSchema, ErrorResp> {
+    if let Some(Format::Protobuf(ProtobufFormat {
+        confluent_schema_registry: true,
+        schema_id,
+        ..
+    })) = &mut schema.format
+    {
+        let schema_response = get_schema(connector, table_config, profile_config).await?;
+
+        if schema_response.schema_type != ConfluentSchemaType::Protobuf {
+            return Err(bad_request(format!(
+                "Format configured is protobuf, but confluent schema repository returned a {:?} schema",
+                schema_response.schema_type
+            )));
+        }
+        schema_id.replace(schema_response.id);
+
+        schema.definition = Some(SchemaDefinition::ProtobufSchema(schema_response.schema));
+    }
+
+    let Some(SchemaDefinition::ProtobufSchema(definition)) = schema.definition.as_ref() else {
+        return Err(bad_request(
+            "protobuf format requires a protobuf schema be set",
+        ));
+    };
+
+    let message_name = match &mut schema.format {
+        Some(Format::Protobuf(format)) => {
+            format.add_schema_def(definition.clone());
+            format.message_name.clone()
+        }
+        _ => None,
+    };
+
+    let fields: Result<_, String> =
+        protobuf::convert_proto_schema(&definition, message_name.as_deref())
+            .map_err(|e| bad_request(format!("Invalid protobuf schema: {}", e)))?
+            .into_iter()
+            .map(|f| f.try_into())
+            .collect();
+
+    schema.fields = fields.map_err(|e| bad_request(format!("Failed to convert schema: {}", e)))?;
+
+    Ok(schema)
+}
+
 async fn expand_json_schema(
     name: &str,
     connector: &str,
@@ -606,6 +662,17 @@ pub(crate) async fn test_schema(
                 Ok(())
             }
         }
+        SchemaDefinition::ProtobufSchema(schema) => {
+            let message_name = match &req.format {
+                Some(Format::Protobuf(format)) => format.message_name.as_deref(),
+                _ => None,
+            };
+            if let Err(e) = protobuf::convert_proto_schema(&schema, message_name) {
+                Err(bad_request(e.to_string()))
+            } else {
+                Ok(())
+            }
+        }
         _ => {
             // TODO: add testing for other schema types
             Ok(())
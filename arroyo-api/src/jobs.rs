@@ -1,14 +1,16 @@
 use crate::queries::api_queries::{
-    DbCheckpoint, DbLogMessage, DbPipelineJob, GetOperatorErrorsParams,
+    DbCheckpoint, DbLogMessage, DbPipelineJob, DbSavepoint, GetOperatorErrorsParams,
 };
 use arroyo_rpc::api_types::checkpoints::{
-    Checkpoint, CheckpointEventSpan, CheckpointSpanType, OperatorCheckpointGroup,
-    SubtaskCheckpointGroup,
+    Checkpoint, CheckpointEventSpan, CheckpointSpanType, CheckpointTable, OperatorCheckpointGroup,
+    OperatorCheckpointTables, Savepoint, SavepointPost, SavepointState, StateEntry, StateOperation,
+    StateQueryParams, StateValueType, SubtaskCheckpointGroup,
 };
 use arroyo_rpc::api_types::pipelines::{JobLogLevel, JobLogMessage, OutputData, StopType};
 use arroyo_rpc::api_types::{
     CheckpointCollection, JobCollection, JobLogMessageCollection,
-    OperatorCheckpointGroupCollection, PaginationQueryParams,
+    OperatorCheckpointGroupCollection, OperatorCheckpointTablesCollection, PaginationQueryParams,
+    SavepointCollection, StateEntryCollection,
 };
 use arroyo_rpc::grpc;
 use arroyo_rpc::grpc::api::{
@@ -17,9 +19,12 @@ use arroyo_rpc::grpc::api::{
 };
 use arroyo_rpc::grpc::controller_grpc_client::ControllerGrpcClient;
 use arroyo_rpc::public_ids::{generate_id, IdTypes};
+use arroyo_state::inspect;
+use arroyo_state::{BackingStore, DataOperation, StateBackend};
 use axum::extract::{Path, Query, State};
 use axum::response::sse::{Event, Sse};
 use axum::Json;
+use axum_extra::extract::WithRejection;
 use cornucopia_async::GenericClient;
 use cornucopia_async::Params;
 use deadpool_postgres::Transaction;
@@ -37,15 +42,16 @@ use crate::pipelines::{query_job_by_pub_id, query_pipeline_by_pub_id};
 use crate::rest::AppState;
 use crate::rest_utils::{
     authenticate, bad_request, client, log_and_map, not_found, paginate_results,
-    validate_pagination_params, BearerAuth, ErrorResp,
+    validate_pagination_params, ApiError, BearerAuth, ErrorResp,
 };
 use crate::types::public::LogLevel;
-use crate::{queries::api_queries, to_micros, types::public, AuthData};
+use crate::{handle_db_error, queries::api_queries, to_micros, types::public, AuthData};
 
 pub(crate) async fn create_job<'a>(
     request: CreateJobReq,
     pipeline_name: &str,
     pipeline_id: &i64,
+    restore_savepoint_id: Option<i64>,
     auth: &AuthData,
     client: &Transaction<'a>,
 ) -> Result<String, ErrorResp> {
@@ -94,6 +100,7 @@ pub(crate) async fn create_job<'a>(
             } else {
                 None
             }),
+            &restore_savepoint_id,
         )
         .await
         .map_err(log_and_map)?;
@@ -310,6 +317,98 @@ pub async fn get_job_checkpoints(
     Ok(Json(CheckpointCollection { data: checkpoints }))
 }
 
+/// Take a savepoint of a job
+#[utoipa::path(
+    post,
+    path = "/v1/pipelines/{pipeline_id}/jobs/{job_id}/savepoints",
+    tag = "jobs",
+    params(
+        ("pipeline_id" = String, Path, description = "Pipeline id"),
+        ("job_id" = String, Path, description = "Job id")
+    ),
+    request_body = SavepointPost,
+    responses(
+        (status = 200, description = "Requested savepoint", body = Savepoint),
+    ),
+)]
+pub async fn create_savepoint(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    Path((pipeline_pub_id, job_pub_id)): Path<(String, String)>,
+    WithRejection(Json(savepoint_post), _): WithRejection<Json<SavepointPost>, ApiError>,
+) -> Result<Json<Savepoint>, ErrorResp> {
+    let client = client(&state.pool).await?;
+    let auth_data = authenticate(&state.pool, bearer_auth).await?;
+
+    let job = query_job_by_pub_id(&pipeline_pub_id, &job_pub_id, &client, &auth_data).await?;
+    if job.state != "Running" {
+        return Err(bad_request(format!(
+            "Savepoints can only be taken of running jobs, but job '{}' is {}",
+            job_pub_id, job.state
+        )));
+    }
+
+    if savepoint_post.name.trim().is_empty() {
+        return Err(bad_request("Savepoint name must not be empty".to_string()));
+    }
+
+    let pub_id = generate_id(IdTypes::Savepoint);
+    api_queries::create_savepoint()
+        .bind(
+            &client,
+            &pub_id,
+            &auth_data.organization_id,
+            &job_pub_id,
+            &savepoint_post.name,
+            &auth_data.user_id,
+        )
+        .await
+        .map_err(|e| handle_db_error("savepoint", e))?;
+
+    let savepoint = api_queries::get_savepoint()
+        .bind(&client, &auth_data.organization_id, &pub_id)
+        .one()
+        .await
+        .map_err(log_and_map)?;
+
+    Ok(Json(savepoint.into()))
+}
+
+/// List a job's savepoints
+#[utoipa::path(
+    get,
+    path = "/v1/pipelines/{pipeline_id}/jobs/{job_id}/savepoints",
+    tag = "jobs",
+    params(
+        ("pipeline_id" = String, Path, description = "Pipeline id"),
+        ("job_id" = String, Path, description = "Job id")
+    ),
+    responses(
+        (status = 200, description = "Got job's savepoints", body = SavepointCollection),
+    ),
+)]
+pub async fn get_job_savepoints(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    Path((pipeline_pub_id, job_pub_id)): Path<(String, String)>,
+) -> Result<Json<SavepointCollection>, ErrorResp> {
+    let client = client(&state.pool).await?;
+    let auth_data = authenticate(&state.pool, bearer_auth).await?;
+
+    query_job_by_pub_id(&pipeline_pub_id, &job_pub_id, &client, &auth_data).await?;
+
+    let savepoints = api_queries::get_job_savepoints()
+        .bind(&client, &auth_data.organization_id, &job_pub_id)
+        .all()
+        .await
+        .map_err(log_and_map)?
+        .into_iter()
+        .map(|s| s.into())
+        .collect();
+
+    Ok(Json(SavepointCollection { data: savepoints }))
+}
+
 fn get_event_spans(subtask_details: &TaskCheckpointDetail) -> Vec<CheckpointEventSpan> {
     let alignment_started = subtask_details
         .events
@@ -455,6 +554,189 @@ pub async fn get_checkpoint_details(
     Ok(Json(OperatorCheckpointGroupCollection { data: operators }))
 }
 
+/// List the state tables of a checkpoint's operators
+#[utoipa::path(
+    get,
+    path = "/v1/pipelines/{pipeline_id}/jobs/{job_id}/checkpoints/{epoch}/tables",
+    tag = "jobs",
+    params(
+        ("pipeline_id" = String, Path, description = "Pipeline id"),
+        ("job_id" = String, Path, description = "Job id"),
+        ("epoch" = u32, Path, description = "Epoch")
+    ),
+    responses(
+        (status = 200, description = "Got checkpoint's tables", body = OperatorCheckpointTablesCollection),
+    ),
+)]
+pub async fn get_checkpoint_tables(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    Path((pipeline_pub_id, job_pub_id, epoch)): Path<(String, String, u32)>,
+) -> Result<Json<OperatorCheckpointTablesCollection>, ErrorResp> {
+    let client = client(&state.pool).await?;
+    let auth_data = authenticate(&state.pool, bearer_auth).await?;
+
+    query_job_by_pub_id(&pipeline_pub_id, &job_pub_id, &client, &auth_data).await?;
+
+    let metadata = StateBackend::load_checkpoint_metadata(&job_pub_id, epoch)
+        .await
+        .ok_or_else(|| {
+            not_found(&format!(
+                "Checkpoint with epoch {} for job '{}'",
+                epoch, job_pub_id
+            ))
+        })?;
+
+    let mut operators = vec![];
+    for operator_id in &metadata.operator_ids {
+        let Some(operator_metadata) =
+            StateBackend::load_operator_metadata(&job_pub_id, operator_id, epoch).await
+        else {
+            // operators without state don't write any metadata
+            continue;
+        };
+
+        let tables = inspect::operator_table_stats(&job_pub_id, operator_id, epoch)
+            .await
+            .map_err(log_and_map)?
+            .into_iter()
+            .map(|stats| CheckpointTable {
+                table_type: format!("{:?}", stats.table.table_type()),
+                name: stats.table.name,
+                description: stats.table.description,
+                files: stats.files as u64,
+                bytes: stats.bytes,
+                rows: stats.rows,
+            })
+            .collect();
+
+        operators.push(OperatorCheckpointTables {
+            operator_id: operator_id.clone(),
+            bytes: operator_metadata.bytes,
+            readable: !inspect::has_disk_state(&operator_metadata),
+            tables,
+        });
+    }
+
+    Ok(Json(OperatorCheckpointTablesCollection { data: operators }))
+}
+
+/// Get the rows of a table in a checkpoint
+#[utoipa::path(
+    get,
+    path = "/v1/pipelines/{pipeline_id}/jobs/{job_id}/checkpoints/{epoch}/operators/{operator_id}/tables/{table}",
+    tag = "jobs",
+    params(
+        ("pipeline_id" = String, Path, description = "Pipeline id"),
+        ("job_id" = String, Path, description = "Job id"),
+        ("epoch" = u32, Path, description = "Epoch"),
+        ("operator_id" = String, Path, description = "Operator id"),
+        ("table" = String, Path, description = "Table name"),
+        ("key" = Option<String>, Query, description = "Key to get the rows of, hex-encoded unless key_type is set"),
+        ("key_type" = Option<StateValueType>, Query, description = "Type to decode the keys of the rows as"),
+        ("value_type" = Option<StateValueType>, Query, description = "Type to decode the values of the rows as"),
+    ),
+    responses(
+        (status = 200, description = "Got table's rows", body = StateEntryCollection),
+    ),
+)]
+pub async fn get_checkpoint_table_state(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    Path((pipeline_pub_id, job_pub_id, epoch, operator_id, table)): Path<(
+        String,
+        String,
+        u32,
+        String,
+        String,
+    )>,
+    query_params: Query<StateQueryParams>,
+) -> Result<Json<StateEntryCollection>, ErrorResp> {
+    let client = client(&state.pool).await?;
+    let auth_data = authenticate(&state.pool, bearer_auth).await?;
+
+    query_job_by_pub_id(&pipeline_pub_id, &job_pub_id, &client, &auth_data).await?;
+
+    let mut chars = table.chars();
+    let (Some(table_char), None) = (chars.next(), chars.next()) else {
+        return Err(bad_request(format!("Invalid table name '{}'", table)));
+    };
+
+    let key = match (&query_params.key, query_params.key_type) {
+        (Some(key), Some(key_type)) => {
+            Some(inspect::encode_key(key, key_type).map_err(|e| {
+                bad_request(format!("Key '{}' is not a {:?}: {}", key, key_type, e))
+            })?)
+        }
+        (Some(key), None) => Some(
+            inspect::hex_decode(key)
+                .ok_or_else(|| bad_request(format!("Key '{}' is not hex-encoded", key)))?,
+        ),
+        (None, _) => None,
+    };
+
+    let operator_metadata = StateBackend::load_operator_metadata(&job_pub_id, &operator_id, epoch)
+        .await
+        .ok_or_else(|| {
+            not_found(&format!(
+                "State of operator '{}' in checkpoint with epoch {} for job '{}'",
+                operator_id, epoch, job_pub_id
+            ))
+        })?;
+    if inspect::has_disk_state(&operator_metadata) {
+        return Err(bad_request(format!(
+            "Operator '{}' was checkpointed by the disk state backend; the tables of disk state \
+            checkpoints can be listed, but their rows can't be read",
+            operator_id
+        )));
+    }
+
+    let decode = |bytes: &[u8], value_type: Option<StateValueType>, name: &str| {
+        value_type
+            .map(|value_type| {
+                inspect::decode(bytes, value_type).map_err(|e| {
+                    bad_request(format!(
+                        "Failed to decode {} as {:?}: {}",
+                        name, value_type, e
+                    ))
+                })
+            })
+            .transpose()
+    };
+
+    let mut entries = vec![];
+    for tuple in
+        inspect::read_blind_table(&job_pub_id, &operator_id, epoch, table_char, key.as_deref())
+            .await
+            .map_err(|e| bad_request(e.to_string()))?
+    {
+        let decoded_key = decode(&tuple.key, query_params.key_type, "key")?;
+        // only inserts have values
+        let decoded_value = match tuple.operation {
+            DataOperation::Insert => decode(&tuple.value, query_params.value_type, "value")?,
+            _ => None,
+        };
+
+        entries.push(StateEntry {
+            key_hash: tuple.key_hash,
+            timestamp: arroyo_types::to_micros(tuple.timestamp),
+            operation: match tuple.operation {
+                DataOperation::Insert => StateOperation::Insert,
+                DataOperation::DeleteTimeKey(_) => StateOperation::DeleteTimeKey,
+                DataOperation::DeleteKey(_) => StateOperation::DeleteKey,
+                DataOperation::DeleteValue(_) => StateOperation::DeleteValue,
+                DataOperation::DeleteTimeRange(_) => StateOperation::DeleteTimeRange,
+            },
+            key: inspect::hex_encode(&tuple.key),
+            value: inspect::hex_encode(&tuple.value),
+            decoded_key,
+            decoded_value,
+        });
+    }
+
+    Ok(Json(StateEntryCollection { data: entries }))
+}
+
 /// Subscribe to a job's output
 #[utoipa::path(
     get,
@@ -573,3 +855,23 @@ impl Into<Checkpoint> for DbCheckpoint {
         }
     }
 }
+
+impl Into<Savepoint> for DbSavepoint {
+    fn into(self) -> Savepoint {
+        Savepoint {
+            id: self.pub_id,
+            name: self.name,
+            job_id: self.job_id,
+            state: match self.state {
+                public::SavepointState::pending => SavepointState::Pending,
+                public::SavepointState::inprogress => SavepointState::InProgress,
+                public::SavepointState::ready => SavepointState::Ready,
+                public::SavepointState::failed => SavepointState::Failed,
+            },
+            created_at: to_micros(self.created_at),
+            finish_time: self.finish_time.map(to_micros),
+            epoch: self.epoch.map(|epoch| epoch as u32),
+            failure_message: self.failure_message,
+        }
+    }
+}
@@ -13,8 +13,9 @@ use crate::connection_tables::{
 };
 use crate::connectors::__path_get_connectors;
 use crate::jobs::{
-    __path_get_checkpoint_details, __path_get_job_checkpoints, __path_get_job_errors,
-    __path_get_job_output, __path_get_jobs,
+    __path_create_savepoint, __path_get_checkpoint_details, __path_get_checkpoint_table_state,
+    __path_get_checkpoint_tables, __path_get_job_checkpoints, __path_get_job_errors,
+    __path_get_job_output, __path_get_job_savepoints, __path_get_jobs,
 };
 use crate::metrics::__path_get_operator_metric_groups;
 use crate::pipelines::__path_get_pipelines;
@@ -152,6 +153,10 @@ pub(crate) fn to_micros(dt: OffsetDateTime) -> u64 {
         test_connection_table,
         test_schema,
         get_checkpoint_details,
+        get_checkpoint_tables,
+        get_checkpoint_table_state,
+        create_savepoint,
+        get_job_savepoints,
         create_udf,
         get_udfs,
         delete_udf
@@ -173,6 +178,10 @@ pub(crate) fn to_micros(dt: OffsetDateTime) -> u64 {
         JobLogLevel,
         Checkpoint,
         CheckpointCollection,
+        SavepointPost,
+        Savepoint,
+        SavepointState,
+        SavepointCollection,
         OutputData,
         MetricNames,
         Metric,
@@ -199,8 +208,10 @@ pub(crate) fn to_micros(dt: OffsetDateTime) -> u64 {
         TestSourceMessage,
         JsonFormat,
         AvroFormat,
+        ProtobufFormat,
         ParquetFormat,
         RawStringFormat,
+        CsvFormat,
         TimestampFormat,
         Framing,
         FramingMethod,
@@ -211,6 +222,13 @@ pub(crate) fn to_micros(dt: OffsetDateTime) -> u64 {
         OperatorCheckpointGroupCollection,
         SubtaskCheckpointGroup,
         OperatorCheckpointGroup,
+        CheckpointTable,
+        OperatorCheckpointTables,
+        OperatorCheckpointTablesCollection,
+        StateOperation,
+        StateValueType,
+        StateEntry,
+        StateEntryCollection,
         ValidateQueryPost,
         QueryValidationResult,
         ValidateUdfPost,
@@ -9,7 +9,7 @@ use deadpool_postgres::{Object, Transaction};
 use http::StatusCode;
 
 use petgraph::Direction;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use std::time::Duration;
 
@@ -49,7 +49,7 @@ use crate::rest_utils::{
     authenticate, bad_request, client, log_and_map, not_found, paginate_results, required_field,
     unauthorized, validate_pagination_params, ApiError, BearerAuth, ErrorResp,
 };
-use crate::types::public::{PipelineType, RestartMode, StopMode};
+use crate::types::public::{PipelineType, RestartMode, SavepointState, StopMode};
 use crate::{connection_tables, to_micros};
 use crate::{handle_db_error, optimizations, AuthData};
 use create_pipeline_req::Config::Sql;
@@ -142,6 +142,7 @@ where
         schema_provider,
         SqlConfig {
             default_parallelism: parallelism,
+            ..Default::default()
         },
     )
     .await
@@ -201,6 +202,21 @@ async fn try_register_confluent_schema(
                 config.format = Some(Format::Avro(avro))
             }
         }
+        Some(Format::Protobuf(mut proto)) => {
+            if proto.confluent_schema_registry && proto.schema_id.is_none() {
+                let Some(schema_def) = proto.schema_def.clone() else {
+                    bail!("protobuf sinks require a schema to register with the schema registry");
+                };
+
+                let id = schema_registry
+                    .write_schema(schema_def, ConfluentSchemaType::Protobuf)
+                    .await
+                    .map_err(|e| anyhow!("Failed to write schema to schema registry: {}", e))?;
+
+                proto.schema_id = Some(id as u32);
+                config.format = Some(Format::Protobuf(proto))
+            }
+        }
         Some(Format::Json(_)) => {
             // TODO: add json schema support
         }
@@ -241,6 +257,28 @@ async fn register_schemas(compiled_sql: &mut CompiledSql) -> anyhow::Result<()>
     Ok(())
 }
 
+fn optimize_and_validate(program: &mut Program, auth: &AuthData) -> Result<(), ErrorResp> {
+    optimizations::optimize(&mut program.graph);
+
+    if program.graph.node_count() > auth.org_metadata.max_operators as usize {
+        return Err(bad_request(
+            format!("This pipeline is too large to create under your plan, which only allows pipelines up to {} nodes;
+                contact support@arroyo.systems for an increase", auth.org_metadata.max_operators)));
+    }
+
+    let errors = program.validate_graph();
+    if !errors.is_empty() {
+        let errs: Vec<String> = errors.iter().map(|s| format!("  * {}\n", s)).collect();
+
+        return Err(bad_request(format!(
+            "Program validation failed:\n{}",
+            errs.join("")
+        )));
+    }
+
+    Ok(())
+}
+
 pub(crate) async fn create_pipeline<'a>(
     req: &CreatePipelineReq,
     pub_id: &str,
@@ -269,6 +307,7 @@ pub(crate) async fn create_pipeline<'a>(
                 connection_ids: vec![],
                 schemas: HashMap::new(),
             };
+            set_parallelism(&mut compiled.program, 1);
             text = None;
             udfs = None;
             is_preview = false;
@@ -285,40 +324,31 @@ pub(crate) async fn create_pipeline<'a>(
             let api_udfs = sql.udfs.into_iter().map(|t| t.into()).collect::<Vec<Udf>>();
 
             pipeline_type = PipelineType::sql;
-            compiled = compile_sql(
-                sql.query.clone(),
-                &api_udfs,
-                sql.parallelism as usize,
-                &auth,
-                tx,
-            )
-            .await
-            .map_err(|e| bad_request(e.to_string()))?;
+            // pipelines start at parallelism 1, except for operators with a parallelism set in
+            // the query
+            compiled = compile_sql(sql.query.clone(), &api_udfs, 1, &auth, tx)
+                .await
+                .map_err(|e| bad_request(e.to_string()))?;
+
+            if compiled
+                .program
+                .graph
+                .node_weights()
+                .any(|node| node.parallelism > auth.org_metadata.max_parallelism as usize)
+            {
+                return Err(bad_request(format!(
+                    "Your plan allows you to run pipelines up to parallelism {};
+                    contact support@arroyo.systems for an increase",
+                    auth.org_metadata.max_parallelism
+                )));
+            }
             text = Some(sql.query);
             udfs = Some(api_udfs);
             is_preview = sql.preview;
         }
     };
 
-    optimizations::optimize(&mut compiled.program.graph);
-
-    if compiled.program.graph.node_count() > auth.org_metadata.max_operators as usize {
-        return Err(bad_request(
-            format!("This pipeline is too large to create under your plan, which only allows pipelines up to {} nodes;
-                contact support@arroyo.systems for an increase", auth.org_metadata.max_operators)));
-    }
-
-    let errors = compiled.program.validate_graph();
-    if !errors.is_empty() {
-        let errs: Vec<String> = errors.iter().map(|s| format!("  * {}\n", s)).collect();
-
-        return Err(bad_request(format!(
-            "Program validation failed:\n{}",
-            errs.join("")
-        )));
-    }
-
-    set_parallelism(&mut compiled.program, 1);
+    optimize_and_validate(&mut compiled.program, &auth)?;
 
     if is_preview {
         for node in compiled.program.graph.node_weights_mut() {
@@ -382,6 +412,61 @@ pub(crate) async fn create_pipeline<'a>(
     Ok((pipeline_id, compiled.program))
 }
 
+/// Checks that a savepoint can be restored into the program, returning its id
+async fn savepoint_to_restore<'a>(
+    savepoint_pub_id: &str,
+    program: &Program,
+    auth: &AuthData,
+    tx: &Transaction<'a>,
+) -> Result<i64, ErrorResp> {
+    let savepoint = api_queries::get_savepoint_to_restore()
+        .bind(tx, &auth.organization_id, &savepoint_pub_id)
+        .opt()
+        .await
+        .map_err(log_and_map)?
+        .ok_or_else(|| not_found("Savepoint"))?;
+
+    if savepoint.state != SavepointState::ready {
+        return Err(bad_request(format!(
+            "Savepoint '{}' is not ready to be restored",
+            savepoint_pub_id
+        )));
+    }
+
+    let operators: HashSet<String> =
+        serde_json::from_value(savepoint.operators).map_err(log_and_map)?;
+    if let Some(node) = program
+        .graph
+        .node_weights()
+        .find(|node| !operators.contains(&node.operator_id))
+    {
+        return Err(bad_request(format!(
+            "Savepoint '{}' does not contain state for operator {}; savepoints can only be \
+            restored into pipelines with the same operators",
+            savepoint_pub_id, node.operator_id
+        )));
+    }
+
+    // the kind of each operator and the types of its inputs must also match those of the operator
+    // that wrote its state; its tables are determined by those, and are checked against the
+    // restored ones by the workers
+    if let Some(savepoint_program) = savepoint.program {
+        let savepoint_program: Program = PipelineProgram::decode(&savepoint_program[..])
+            .map_err(log_and_map)?
+            .try_into()
+            .map_err(log_and_map)?;
+        let compatibility = savepoint_program.state_compatibility(program);
+        if !compatibility.is_compatible() {
+            return Err(bad_request(format!(
+                "Savepoint '{}' is not compatible with the pipeline:\n{}",
+                savepoint_pub_id, compatibility
+            )));
+        }
+    }
+
+    Ok(savepoint.id)
+}
+
 impl TryInto<Pipeline> for DbPipeline {
     type Error = ErrorResp;
 
@@ -565,6 +650,13 @@ pub async fn post_pipeline(
     )
     .await?;
 
+    let restore_savepoint_id = match &pipeline_post.savepoint_id {
+        Some(savepoint_id) => {
+            Some(savepoint_to_restore(savepoint_id, &program, &auth_data, &transaction).await?)
+        }
+        None => None,
+    };
+
     let create_job = CreateJobReq {
         pipeline_id: format!("{}", pipeline_id),
         checkpoint_interval_micros: DEFAULT_CHECKPOINT_INTERVAL.as_micros() as u64,
@@ -575,6 +667,7 @@ pub async fn post_pipeline(
         create_job,
         &pipeline_post.name,
         &pipeline_id,
+        restore_savepoint_id,
         &auth_data,
         &transaction,
     )
@@ -600,6 +693,149 @@ pub async fn post_pipeline(
     Ok(Json(pipeline))
 }
 
+/// Replaces the query of a pipeline. Operators are matched between the current and new programs
+/// by their ids, and the upgrade is rejected if the new program can't read the state of the
+/// operators it shares with the current one. Jobs that are running are restarted with the new
+/// program, which restores that state from their final checkpoint.
+async fn upgrade_pipeline<'a>(
+    pipeline_pub_id: &str,
+    job_id: &str,
+    query: String,
+    udfs: Vec<Udf>,
+    auth: &AuthData,
+    tx: &Transaction<'a>,
+) -> Result<(), ErrorResp> {
+    let job = api_queries::get_job_details()
+        .bind(tx, &auth.organization_id, &job_id)
+        .opt()
+        .await
+        .map_err(log_and_map)?
+        .ok_or_else(|| not_found("Job"))?;
+
+    if job.textual_repr.is_none() {
+        return Err(bad_request(
+            "Only SQL pipelines can be upgraded to a new query".to_string(),
+        ));
+    }
+
+    let current: Program = PipelineProgram::decode(&job.program[..])
+        .map_err(log_and_map)?
+        .try_into()
+        .map_err(log_and_map)?;
+
+    let mut compiled = compile_sql(query.clone(), &udfs, 1, auth, tx)
+        .await
+        .map_err(|e| bad_request(e.to_string()))?;
+    optimize_and_validate(&mut compiled.program, auth)?;
+
+    let compatibility = current.state_compatibility(&compiled.program);
+    if !compatibility.is_compatible() {
+        return Err(bad_request(format!(
+            "The new query is not compatible with the state of the pipeline:\n{}",
+            compatibility
+        )));
+    }
+
+    // pipelines created before operator ids were derived from the query share no operators with
+    // their upgraded versions, and would silently lose all of their state
+    if compatibility.restored.is_empty() && !compatibility.dropped.is_empty() {
+        return Err(bad_request(format!(
+            "None of the state of the pipeline can be restored into the new query:\n{}",
+            compatibility
+        )));
+    }
+
+    register_schemas(&mut compiled).await.map_err(|e| {
+        bad_request(format!(
+            "Failed to register schemas with the schema registry: {}",
+            e
+        ))
+    })?;
+
+    let proto_program: PipelineProgram =
+        compiled.program.clone().try_into().map_err(log_and_map)?;
+
+    let pipeline_id = api_queries::update_pipeline_program()
+        .bind(
+            tx,
+            &OffsetDateTime::now_utc(),
+            &auth.user_id,
+            &query,
+            &serde_json::to_value(&udfs).map_err(log_and_map)?,
+            &proto_program.encode_to_vec(),
+            &pipeline_pub_id,
+            &auth.organization_id,
+        )
+        .one()
+        .await
+        .map_err(log_and_map)?;
+
+    api_queries::delete_pipeline_connection_tables()
+        .bind(tx, &pipeline_id)
+        .await
+        .map_err(log_and_map)?;
+    for connection in &compiled.connection_ids {
+        api_queries::add_pipeline_connection_table()
+            .bind(
+                tx,
+                &generate_id(IdTypes::ConnectionTablePipeline),
+                &pipeline_id,
+                connection,
+            )
+            .await
+            .map_err(log_and_map)?;
+    }
+
+    // parallelism overrides apply to every operator, so carry them over to the new operators
+    let parallelism = job
+        .parallelism_overrides
+        .as_object()
+        .and_then(|overrides| overrides.values().filter_map(|p| p.as_u64()).max());
+    if let Some(parallelism) = parallelism {
+        let overrides: HashMap<String, u64> = compiled
+            .program
+            .graph
+            .node_weights()
+            .map(|node| (node.operator_id.clone(), parallelism))
+            .collect();
+
+        api_queries::update_job()
+            .bind(
+                tx,
+                &OffsetDateTime::now_utc(),
+                &auth.user_id,
+                &None,
+                &None,
+                &Some(serde_json::to_value(overrides).map_err(log_and_map)?),
+                &job_id,
+                &auth.organization_id,
+            )
+            .await
+            .map_err(log_and_map)?;
+    }
+
+    // jobs that aren't running pick up the new program when they are next started
+    let state = job.state.unwrap_or_else(|| "Created".to_string());
+    if !matches!(
+        state.as_str(),
+        "Created" | "Stopping" | "CheckpointStopping" | "Stopped" | "Finishing" | "Finished"
+    ) {
+        api_queries::restart_job()
+            .bind(
+                tx,
+                &OffsetDateTime::now_utc(),
+                &auth.user_id,
+                &RestartMode::safe,
+                &job_id,
+                &auth.organization_id,
+            )
+            .await
+            .map_err(log_and_map)?;
+    }
+
+    Ok(())
+}
+
 /// Update a pipeline
 #[utoipa::path(
     patch,
@@ -619,17 +855,31 @@ pub async fn patch_pipeline(
     Path(pipeline_pub_id): Path<String>,
     WithRejection(Json(pipeline_patch), _): WithRejection<Json<PipelinePatch>, ApiError>,
 ) -> Result<Json<Pipeline>, ErrorResp> {
-    let client = client(&state.pool).await?;
+    let mut client = client(&state.pool).await?;
     let auth_data = authenticate(&state.pool, bearer_auth).await?;
 
+    let transaction = client.transaction().await.map_err(log_and_map)?;
+
     // this assumes there is just one job for the pipeline
     let job_id = api_queries::get_pipeline_jobs()
-        .bind(&client, &auth_data.organization_id, &pipeline_pub_id)
+        .bind(&transaction, &auth_data.organization_id, &pipeline_pub_id)
         .one()
         .await
         .map_err(log_and_map)?
         .id;
 
+    if let Some(query) = pipeline_patch.query.clone() {
+        upgrade_pipeline(
+            &pipeline_pub_id,
+            &job_id,
+            query,
+            pipeline_patch.udfs.clone().unwrap_or_default(),
+            &auth_data,
+            &transaction,
+        )
+        .await?;
+    }
+
     let interval = pipeline_patch
         .checkpoint_interval_micros
         .map(Duration::from_micros);
@@ -652,7 +902,7 @@ pub async fn patch_pipeline(
 
     let parallelism_overrides = if let Some(parallelism) = pipeline_patch.parallelism {
         let res = api_queries::get_job_details()
-            .bind(&client, &auth_data.organization_id, &job_id)
+            .bind(&transaction, &auth_data.organization_id, &job_id)
             .opt()
             .await
             .map_err(log_and_map)?
@@ -672,7 +922,7 @@ pub async fn patch_pipeline(
 
     let res = api_queries::update_job()
         .bind(
-            &client,
+            &transaction,
             &OffsetDateTime::now_utc(),
             &auth_data.user_id,
             &stop,
@@ -688,6 +938,8 @@ pub async fn patch_pipeline(
         return Err(not_found("Job"));
     }
 
+    transaction.commit().await.map_err(log_and_map)?;
+
     let pipeline = query_pipeline_by_pub_id(&pipeline_pub_id, &client, &auth_data).await?;
     Ok(Json(pipeline))
 }
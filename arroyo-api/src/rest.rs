@@ -24,7 +24,8 @@ use crate::connection_tables::{
 };
 use crate::connectors::get_connectors;
 use crate::jobs::{
-    get_checkpoint_details, get_job_checkpoints, get_job_errors, get_job_output, get_jobs,
+    create_savepoint, get_checkpoint_details, get_checkpoint_table_state, get_checkpoint_tables,
+    get_job_checkpoints, get_job_errors, get_job_output, get_job_savepoints, get_jobs,
 };
 use crate::metrics::get_operator_metric_groups;
 use crate::pipelines::{
@@ -98,6 +99,16 @@ pub fn create_rest_app(pool: Pool, controller_addr: &str) -> Router {
             "/:job_id/checkpoints/:checkpoint_id/operator_checkpoint_groups",
             get(get_checkpoint_details),
         )
+        .route(
+            "/:job_id/checkpoints/:checkpoint_id/tables",
+            get(get_checkpoint_tables),
+        )
+        .route(
+            "/:job_id/checkpoints/:checkpoint_id/operators/:operator_id/tables/:table",
+            get(get_checkpoint_table_state),
+        )
+        .route("/:job_id/savepoints", get(get_job_savepoints))
+        .route("/:job_id/savepoints", post(create_savepoint))
         .route("/:job_id/output", get(get_job_output))
         .route(
             "/:job_id/operator_metric_groups",
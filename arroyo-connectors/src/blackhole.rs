@@ -97,6 +97,7 @@ impl Connector for BlackholeConnector {
             rate_limit: None,
             format: None,
             framing: None,
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
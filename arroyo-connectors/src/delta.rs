@@ -10,7 +10,8 @@ use arroyo_rpc::api_types::connections::{
 use arroyo_rpc::OperatorConfig;
 
 use crate::filesystem::{
-    file_system_sink_from_options, CommitStyle, FileSystemTable, FormatSettings, TableType,
+    file_system_sink_from_options, file_system_source_from_options, table_source_connection,
+    CommitStyle, FileSystemTable, FormatSettings, TableFormat, TableType,
 };
 use crate::{Connection, EmptyConfig};
 
@@ -34,9 +35,9 @@ impl Connector for DeltaLakeConnector {
             id: "delta".to_string(),
             name: "Delta Lake".to_string(),
             icon: "".to_string(),
-            description: "Write to a Delta Lake table".to_string(),
+            description: "Read from or write to a Delta Lake table".to_string(),
             enabled: true,
-            source: false,
+            source: true,
             sink: true,
             testing: false,
             hidden: true,
@@ -66,8 +67,11 @@ impl Connector for DeltaLakeConnector {
         });
     }
 
-    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
-        return ConnectionType::Source;
+    fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
+        match table.table_type {
+            TableType::Source { .. } => ConnectionType::Source,
+            TableType::Sink { .. } => ConnectionType::Sink,
+        }
     }
 
     fn from_config(
@@ -78,6 +82,10 @@ impl Connector for DeltaLakeConnector {
         table: Self::TableT,
         schema: Option<&ConnectionSchema>,
     ) -> anyhow::Result<crate::Connection> {
+        if let TableType::Source { .. } = &table.table_type {
+            return table_source_connection(id, name, config, table, schema, TableFormat::Delta);
+        }
+
         let TableType::Sink {
             write_path,
             file_settings,
@@ -85,7 +93,7 @@ impl Connector for DeltaLakeConnector {
             ..
         } = &table.table_type
         else {
-            bail!("Delta Lake connector only supports sink tables");
+            unreachable!("source tables are handled above");
         };
         // confirm commit style is DeltaLake
         if let Some(CommitStyle::DeltaLake) = file_settings
@@ -131,6 +139,7 @@ impl Connector for DeltaLakeConnector {
             rate_limit: None,
             format: Some(format),
             framing: schema.framing.clone(),
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
@@ -151,7 +160,13 @@ impl Connector for DeltaLakeConnector {
         schema: Option<&ConnectionSchema>,
         _profile: Option<&ConnectionProfile>,
     ) -> anyhow::Result<Connection> {
-        let table = file_system_sink_from_options(options, schema, CommitStyle::DeltaLake)?;
+        let table = match options.remove("type").as_deref() {
+            Some("source") => file_system_source_from_options(options, Some(TableFormat::Delta))?,
+            Some("sink") | None => {
+                file_system_sink_from_options(options, schema, CommitStyle::DeltaLake)?
+            }
+            Some(t) => bail!("unknown type: {}", t),
+        };
 
         self.from_config(None, name, EmptyConfig {}, table, schema)
     }
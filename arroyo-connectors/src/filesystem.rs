@@ -127,6 +127,14 @@ impl Connector for FileSystemConnector {
                         "FileSystem<JSON>".to_string(),
                         "connectors::filesystem::JsonFileSystemSink::<#in_k, #in_t>"
                     ),
+                    (Some(FormatSettings::Csv { .. }), true) => (
+                        "LocalFileSystem<CSV>".to_string(),
+                        "connectors::filesystem::LocalCsvFileSystemSink::<#in_k, #in_t>"
+                    ),
+                    (Some(FormatSettings::Csv { .. }), false) => (
+                        "FileSystem<CSV>".to_string(),
+                        "connectors::filesystem::CsvFileSystemSink::<#in_k, #in_t>"
+                    ),
                     (None, _) => bail!("have to have some format settings"),
                 };
                 (description, operator, ConnectionType::Sink)
@@ -143,12 +151,23 @@ impl Connector for FileSystemConnector {
             .map(|t| t.to_owned())
             .ok_or_else(|| anyhow!("'format' must be set for FileSystem connection"))?;
 
+        if matches!(connection_type, ConnectionType::Source) {
+            match &format {
+                Format::Protobuf(_) => bail!("FileSystem sources do not support protobuf"),
+                Format::Avro(avro) if avro.raw_datums || avro.confluent_schema_registry => {
+                    bail!("FileSystem sources only support Avro object container files")
+                }
+                _ => {}
+            }
+        }
+
         let config = OperatorConfig {
             connection: serde_json::to_value(config).unwrap(),
             table: serde_json::to_value(table).unwrap(),
             rate_limit: None,
             format: Some(format),
             framing: schema.framing.clone(),
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
@@ -171,27 +190,9 @@ impl Connector for FileSystemConnector {
     ) -> anyhow::Result<Connection> {
         match options.remove("type") {
             Some(t) if t == "source" => {
-                let (storage_url, storage_options) = get_storage_url_and_options(options)?;
-                let compression_format = options
-                    .remove("compression_format")
-                    .map(|format| format.as_str().try_into().map_err(|err: &str| anyhow!(err)))
-                    .transpose()?
-                    .unwrap_or(CompressionFormat::None);
-                let matching_pattern = options.remove("source.regex-pattern");
-                self.from_config(
-                    None,
-                    name,
-                    EmptyConfig {},
-                    FileSystemTable {
-                        table_type: TableType::Source {
-                            path: storage_url,
-                            storage_options,
-                            compression_format: Some(compression_format),
-                            regex_pattern: matching_pattern,
-                        },
-                    },
-                    schema,
-                )
+                let table = file_system_source_from_options(options, None)?;
+
+                self.from_config(None, name, EmptyConfig {}, table, schema)
             }
             Some(t) if t == "sink" => {
                 let table = file_system_sink_from_options(options, schema, CommitStyle::Direct)?;
@@ -218,6 +219,104 @@ fn get_storage_url_and_options(
     Ok((storage_url, storage_options))
 }
 
+pub fn file_system_source_from_options(
+    opts: &mut HashMap<String, String>,
+    table_format: Option<TableFormat>,
+) -> Result<FileSystemTable> {
+    let (storage_url, storage_options) = get_storage_url_and_options(opts)?;
+    let compression_format = opts
+        .remove("compression_format")
+        .map(|format| format.as_str().try_into().map_err(|err: &str| anyhow!(err)))
+        .transpose()?
+        .unwrap_or(CompressionFormat::None);
+    let matching_pattern = opts.remove("source.regex-pattern");
+
+    // tables are read from their log, which may be polled for new versions
+    let poll_interval_seconds = match table_format {
+        Some(_) => pull_option_to_i64("poll_interval_seconds", opts)?,
+        None => None,
+    };
+    if let Some(interval) = poll_interval_seconds {
+        if interval <= 0 {
+            bail!("poll_interval_seconds must be positive");
+        }
+    }
+
+    Ok(FileSystemTable {
+        table_type: TableType::Source {
+            path: storage_url,
+            storage_options,
+            compression_format: Some(compression_format),
+            regex_pattern: matching_pattern,
+            table_format,
+            poll_interval_seconds,
+            iceberg_settings: None,
+        },
+    })
+}
+
+/// Builds the connection for a source that reads the data files of a Delta Lake or Iceberg
+/// table.
+pub fn table_source_connection(
+    id: Option<i64>,
+    name: &str,
+    config: EmptyConfig,
+    table: FileSystemTable,
+    schema: Option<&ConnectionSchema>,
+    table_format: TableFormat,
+) -> Result<Connection> {
+    let TableType::Source {
+        table_format: source_format,
+        iceberg_settings,
+        ..
+    } = &table.table_type
+    else {
+        bail!("expected a source table");
+    };
+    let (format_name, description) = match table_format {
+        TableFormat::Delta => ("Delta Lake", "DeltaLakeSource"),
+        TableFormat::Iceberg => ("Iceberg", "IcebergSource"),
+    };
+    if source_format.as_ref() != Some(&table_format) {
+        bail!("{} sources must have a matching table_format", format_name);
+    }
+    if iceberg_settings.is_some() && table_format != TableFormat::Iceberg {
+        bail!("iceberg_settings can only be set for Iceberg sources");
+    }
+
+    let schema = schema
+        .map(|s| s.to_owned())
+        .ok_or_else(|| anyhow!("no schema defined for {} source", format_name))?;
+
+    let format = schema
+        .format
+        .as_ref()
+        .map(|t| t.to_owned())
+        .ok_or_else(|| anyhow!("'format' must be set for {} source", format_name))?;
+    if !matches!(format, Format::Parquet(_)) {
+        bail!("{} sources only support the parquet format", format_name);
+    }
+
+    let config = OperatorConfig {
+        connection: serde_json::to_value(config).unwrap(),
+        table: serde_json::to_value(table).unwrap(),
+        rate_limit: None,
+        format: Some(format),
+        framing: schema.framing.clone(),
+        metadata_fields: vec![],
+    };
+
+    Ok(Connection {
+        id,
+        name: name.to_string(),
+        connection_type: ConnectionType::Source,
+        schema,
+        operator: "connectors::filesystem::source::FileSystemSourceFunc".to_string(),
+        config: serde_json::to_string(&config).unwrap(),
+        description: description.to_string(),
+    })
+}
+
 pub fn file_system_sink_from_options(
     opts: &mut std::collections::HashMap<String, String>,
     schema: Option<&ConnectionSchema>,
@@ -304,6 +403,14 @@ pub fn file_system_sink_from_options(
         Format::Json(..) => Some(FormatSettings::Json {
             json_format: JsonFormat::Json,
         }),
+        Format::Csv(csv) => Some(FormatSettings::Csv {
+            csv_format: CsvFormat::Csv,
+            delimiter: Some(csv.delimiter.to_string()),
+            quote: Some(csv.quote.to_string()),
+            escape: csv.escape.map(|c| c.to_string()),
+            header: Some(csv.header),
+            null_literal: Some(csv.null_literal.clone()),
+        }),
         other => bail!("Unsupported format: {:?}", other),
     };
     Ok(FileSystemTable {
@@ -312,6 +419,7 @@ pub fn file_system_sink_from_options(
             format_settings,
             write_path: storage_url,
             storage_options,
+            iceberg_settings: None,
         },
     })
 }
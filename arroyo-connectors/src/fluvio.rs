@@ -151,6 +151,7 @@ impl Connector for FluvioConnector {
             rate_limit: None,
             format: Some(format),
             framing: schema.framing.clone(),
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use anyhow::{anyhow, bail};
+use arroyo_rpc::OperatorConfig;
+
+use axum::response::sse::Event;
+use tokio::sync::mpsc::Sender;
+use typify::import_types;
+
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{construct_http_client, pull_opt, pull_option_to_u64, Connection, EmptyConfig};
+
+use super::Connector;
+
+const TABLE_SCHEMA: &str = include_str!("../../connector-schemas/http_lookup/table.json");
+
+import_types!(schema = "../connector-schemas/http_lookup/table.json");
+const ICON: &str = include_str!("../resources/webhook.svg");
+
+const KEY_PLACEHOLDER: &str = "{key}";
+
+pub struct HttpLookupConnector {}
+
+impl HttpLookupConnector {
+    fn validate(table: &HttpLookupTable) -> anyhow::Result<()> {
+        if !table.endpoint.contains(KEY_PLACEHOLDER) {
+            bail!(
+                "endpoint '{}' must contain a {} placeholder to substitute the lookup key into",
+                table.endpoint,
+                KEY_PLACEHOLDER
+            );
+        }
+
+        construct_http_client(
+            &table.endpoint.replace(KEY_PLACEHOLDER, "key"),
+            table.headers.as_ref().map(|t| &t.0),
+        )?;
+
+        Ok(())
+    }
+}
+
+impl Connector for HttpLookupConnector {
+    type ProfileT = EmptyConfig;
+
+    type TableT = HttpLookupTable;
+
+    fn name(&self) -> &'static str {
+        "http_lookup"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "http_lookup".to_string(),
+            name: "HTTP Lookup".to_string(),
+            icon: ICON.to_string(),
+            description: "Enrich streams with values fetched from an HTTP endpoint".to_string(),
+            enabled: true,
+            source: false,
+            sink: false,
+            testing: false,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: None,
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: Sender<Result<Event, Infallible>>,
+    ) {
+        tokio::task::spawn(async move {
+            let message = match Self::validate(&table) {
+                Ok(_) => TestSourceMessage::done("Successfully validated HTTP lookup table"),
+                Err(err) => TestSourceMessage::fail(format!("{:?}", err)),
+            };
+
+            tx.send(Ok(Event::default().json_data(message).unwrap()))
+                .await
+                .unwrap();
+        });
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Lookup
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<crate::Connection> {
+        Self::validate(&table)?;
+
+        let description = format!("HttpLookup<{}>", table.endpoint);
+
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for HTTP lookup connection"))?;
+
+        let format = schema
+            .format
+            .as_ref()
+            .map(|t| t.to_owned())
+            .ok_or_else(|| anyhow!("'format' must be set for HTTP lookup connection"))?;
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            framing: schema.framing.clone(),
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            name: name.to_string(),
+            connection_type: ConnectionType::Lookup,
+            schema,
+            operator: "connectors::http_lookup::HttpLookup".to_string(),
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        _profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let endpoint = pull_opt("endpoint", options)?;
+
+        let headers = options
+            .remove("headers")
+            .map(|s| s.try_into())
+            .transpose()
+            .map_err(|e| anyhow!("invalid value for 'headers' config: {:?}", e))?;
+
+        let max_concurrency = pull_option_to_u64("max_concurrency", options)?
+            .map(|t| t.try_into())
+            .transpose()
+            .map_err(|_| anyhow!("max_concurrency must be greater than 0"))?;
+
+        let table = HttpLookupTable {
+            endpoint,
+            headers,
+            max_concurrency,
+        };
+
+        self.from_config(None, name, EmptyConfig {}, table, schema)
+    }
+}
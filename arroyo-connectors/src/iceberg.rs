@@ -0,0 +1,334 @@
+use anyhow::{anyhow, bail, Result};
+use arroyo_storage::BackendConfig;
+use axum::response::sse::Event;
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, FieldType, PrimitiveType,
+    TestSourceMessage,
+};
+use arroyo_rpc::OperatorConfig;
+
+use crate::filesystem::{
+    file_system_sink_from_options, file_system_source_from_options, table_source_connection,
+    CommitStyle, FileSystemTable, FormatSettings, IcebergCatalog, IcebergSettings,
+    IcebergSourceSettings, TableFormat, TableType,
+};
+use crate::{pull_opt, Connection, EmptyConfig};
+
+use super::Connector;
+
+const TABLE_SCHEMA: &str = include_str!("../../connector-schemas/filesystem/table.json");
+
+pub struct IcebergConnector {}
+
+/// Returns the Iceberg time transform (hour, day, month or year) for the finest field of a
+/// strftime-style time partition pattern.
+pub fn time_transform(pattern: &str) -> Result<&'static str> {
+    let specifiers: Vec<char> = pattern
+        .split('%')
+        .skip(1)
+        .filter_map(|s| s.chars().next())
+        .collect();
+    let has = |chars: &[char]| specifiers.iter().any(|c| chars.contains(c));
+
+    if has(&['H', 'k', 'I', 'l', 'M', 'S', 'R', 'T', 'X', 'c', 's']) {
+        Ok("hour")
+    } else if has(&['d', 'e', 'j', 'F', 'D', 'x']) {
+        Ok("day")
+    } else if has(&['m', 'b', 'B', 'h']) {
+        Ok("month")
+    } else if has(&['Y', 'y', 'C', 'G']) {
+        Ok("year")
+    } else {
+        bail!(
+            "time_partition_pattern '{}' does not contain a date or time field to partition on",
+            pattern
+        )
+    }
+}
+
+impl Connector for IcebergConnector {
+    type ProfileT = EmptyConfig;
+
+    type TableT = FileSystemTable;
+
+    fn name(&self) -> &'static str {
+        "iceberg"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "iceberg".to_string(),
+            name: "Apache Iceberg".to_string(),
+            icon: "".to_string(),
+            description: "Read from or write to an Apache Iceberg table".to_string(),
+            enabled: true,
+            source: true,
+            sink: true,
+            testing: false,
+            hidden: true,
+            custom_schemas: true,
+            connection_config: None,
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<Result<Event, Infallible>>,
+    ) {
+        tokio::task::spawn(async move {
+            let message = TestSourceMessage {
+                error: false,
+                done: true,
+                message: "Successfully validated connection".to_string(),
+            };
+            tx.send(Ok(Event::default().json_data(message).unwrap()))
+                .await
+                .unwrap();
+        });
+    }
+
+    fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
+        match table.table_type {
+            TableType::Source { .. } => ConnectionType::Source,
+            TableType::Sink { .. } => ConnectionType::Sink,
+        }
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<crate::Connection> {
+        if let TableType::Source {
+            iceberg_settings, ..
+        } = &table.table_type
+        {
+            if let Some(settings) = iceberg_settings {
+                check_rest_catalog(
+                    &settings.catalog,
+                    &settings.catalog_uri,
+                    &settings.namespace,
+                    &settings.table_name,
+                )?;
+            }
+            return table_source_connection(id, name, config, table, schema, TableFormat::Iceberg);
+        }
+
+        let TableType::Sink {
+            write_path,
+            file_settings,
+            format_settings,
+            iceberg_settings,
+            ..
+        } = &table.table_type
+        else {
+            unreachable!("source tables are handled above");
+        };
+
+        let file_settings = file_settings
+            .as_ref()
+            .ok_or_else(|| anyhow!("no file_settings"))?;
+        if file_settings.commit_style != Some(CommitStyle::Iceberg) {
+            bail!("commit_style must be Iceberg");
+        }
+
+        let iceberg_settings = iceberg_settings
+            .as_ref()
+            .ok_or_else(|| anyhow!("no iceberg_settings for Iceberg sink"))?;
+        check_rest_catalog(
+            &iceberg_settings.catalog,
+            &iceberg_settings.catalog_uri,
+            &iceberg_settings.namespace,
+            &iceberg_settings.table_name,
+        )?;
+
+        let backend_config = BackendConfig::parse_url(&write_path, true)?;
+        let is_local = match &backend_config {
+            BackendConfig::Local { .. } => true,
+            _ => false,
+        };
+        let (description, operator) = match (&format_settings, is_local) {
+            (Some(FormatSettings::Parquet { .. }), true) => (
+                "LocalIceberg<Parquet>".to_string(),
+                "connectors::filesystem::LocalParquetFileSystemSink::<#in_k, #in_t, #in_tRecordBatchBuilder>"
+            ),
+            (Some(FormatSettings::Parquet { .. }), false) => (
+                "Iceberg<Parquet>".to_string(),
+                "connectors::filesystem::ParquetFileSystemSink::<#in_k, #in_t, #in_tRecordBatchBuilder>"
+            ),
+            _ => bail!("Iceberg sink only supports Parquet format"),
+        };
+
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for Iceberg sink"))?;
+
+        let partitioning = file_settings.partitioning.as_ref();
+        if let Some(pattern) = partitioning.and_then(|p| p.time_partition_pattern.as_ref()) {
+            time_transform(pattern)?;
+            let field = iceberg_settings.time_partition_field.as_ref().ok_or_else(|| {
+                anyhow!("iceberg.time_partition_field must be set to partition on time_partition_pattern")
+            })?;
+            let field = schema
+                .fields
+                .iter()
+                .find(|f| &f.field_name == field)
+                .ok_or_else(|| anyhow!("time partition field '{}' is not in the schema", field))?;
+            if !matches!(
+                field.field_type.r#type,
+                FieldType::Primitive(
+                    PrimitiveType::DateTime
+                        | PrimitiveType::UnixMillis
+                        | PrimitiveType::UnixMicros
+                        | PrimitiveType::UnixNanos
+                )
+            ) {
+                bail!(
+                    "time partition field '{}' must be a timestamp",
+                    field.field_name
+                );
+            }
+        }
+        for field in partitioning.iter().flat_map(|p| &p.partition_fields) {
+            if !schema.fields.iter().any(|f| &f.field_name == field) {
+                bail!("partition field '{}' is not in the schema", field);
+            }
+        }
+
+        let format = schema
+            .format
+            .as_ref()
+            .map(|t| t.to_owned())
+            .ok_or_else(|| anyhow!("'format' must be set for Iceberg connection"))?;
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            framing: schema.framing.clone(),
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            name: name.to_string(),
+            connection_type: ConnectionType::Sink,
+            schema,
+            operator: operator.to_string(),
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        _profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        match options.remove("type").as_deref() {
+            Some("source") => {
+                let settings = catalog_settings_from_options(options)?;
+                let mut table =
+                    file_system_source_from_options(options, Some(TableFormat::Iceberg))?;
+                if let TableType::Source {
+                    iceberg_settings, ..
+                } = &mut table.table_type
+                {
+                    *iceberg_settings = Some(settings);
+                }
+                return self.from_config(None, name, EmptyConfig {}, table, schema);
+            }
+            Some("sink") | None => {}
+            Some(t) => bail!("unknown type: {}", t),
+        }
+
+        let mut table = file_system_sink_from_options(options, schema, CommitStyle::Iceberg)?;
+
+        let catalog = catalog_settings_from_options(options)?;
+        let settings = IcebergSettings {
+            catalog: catalog.catalog,
+            catalog_uri: catalog.catalog_uri,
+            warehouse: catalog.warehouse,
+            namespace: catalog.namespace,
+            table_name: catalog.table_name,
+            token: catalog.token,
+            time_partition_field: options.remove("iceberg.time_partition_field"),
+        };
+
+        if let TableType::Sink {
+            iceberg_settings, ..
+        } = &mut table.table_type
+        {
+            *iceberg_settings = Some(settings);
+        }
+
+        self.from_config(None, name, EmptyConfig {}, table, schema)
+    }
+}
+
+/// Reads the `iceberg.*` options that locate the table's catalog, which are shared by sources
+/// and sinks.
+fn catalog_settings_from_options(
+    options: &mut HashMap<String, String>,
+) -> Result<IcebergSourceSettings> {
+    let catalog = match options.remove("iceberg.catalog").as_deref() {
+        None | Some("hadoop") => IcebergCatalog::Hadoop,
+        Some("rest") => IcebergCatalog::Rest,
+        Some(other) => bail!(
+            "unknown iceberg.catalog '{}'; expected 'hadoop' or 'rest'",
+            other
+        ),
+    };
+
+    let (catalog_uri, namespace, table_name) = match catalog {
+        IcebergCatalog::Hadoop => (None, None, None),
+        IcebergCatalog::Rest => (
+            Some(pull_opt("iceberg.catalog_uri", options)?),
+            Some(pull_opt("iceberg.namespace", options)?),
+            Some(pull_opt("iceberg.table", options)?),
+        ),
+    };
+
+    Ok(IcebergSourceSettings {
+        catalog,
+        catalog_uri,
+        warehouse: options.remove("iceberg.warehouse"),
+        namespace,
+        table_name,
+        token: options.remove("iceberg.token"),
+    })
+}
+
+fn check_rest_catalog(
+    catalog: &IcebergCatalog,
+    catalog_uri: &Option<String>,
+    namespace: &Option<String>,
+    table_name: &Option<String>,
+) -> Result<()> {
+    if *catalog == IcebergCatalog::Rest {
+        for (option, value) in [
+            ("iceberg.catalog_uri", catalog_uri),
+            ("iceberg.namespace", namespace),
+            ("iceberg.table", table_name),
+        ] {
+            if value.is_none() {
+                bail!("{} must be set for the rest catalog", option);
+            }
+        }
+    }
+    Ok(())
+}
@@ -29,6 +29,7 @@ pub fn impulse_schema() -> ConnectionSchema {
         ],
         definition: None,
         inferred: None,
+        primary_keys: vec![],
     }
 }
 
@@ -162,6 +163,7 @@ impl Connector for ImpulseConnector {
             rate_limit: None,
             format: None,
             framing: None,
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
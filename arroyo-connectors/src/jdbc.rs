@@ -0,0 +1,286 @@
+use anyhow::{anyhow, bail};
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::formats::{Format, JsonFormat, TimestampFormat};
+use arroyo_rpc::OperatorConfig;
+use axum::response::sse::Event;
+use mysql_async::prelude::Queryable;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::str::FromStr;
+use typify::import_types;
+
+use crate::{pull_opt, Connection, Connector};
+
+pub struct JdbcConnector {}
+
+const CONFIG_SCHEMA: &str = include_str!("../../connector-schemas/jdbc/connection.json");
+const TABLE_SCHEMA: &str = include_str!("../../connector-schemas/jdbc/table.json");
+const ICON: &str = include_str!("../resources/postgres.svg");
+
+import_types!(schema = "../connector-schemas/jdbc/connection.json",);
+import_types!(schema = "../connector-schemas/jdbc/table.json");
+
+enum Database {
+    Postgres(tokio_postgres::Config),
+    MySql(mysql_async::Opts),
+}
+
+fn database(config: &JdbcConfig) -> anyhow::Result<Database> {
+    let scheme = config
+        .url
+        .split_once("://")
+        .map(|(scheme, _)| scheme)
+        .ok_or_else(|| anyhow!("invalid database url '{}'", config.url))?;
+
+    match scheme {
+        "postgres" | "postgresql" => {
+            let mut pg_config = tokio_postgres::Config::from_str(&config.url)
+                .map_err(|e| anyhow!("invalid Postgres url: {}", e))?;
+            if let Some(username) = &config.username {
+                pg_config.user(username);
+            }
+            if let Some(password) = &config.password {
+                pg_config.password(password);
+            }
+            Ok(Database::Postgres(pg_config))
+        }
+        "mysql" => {
+            let opts = mysql_async::Opts::from_url(&config.url)
+                .map_err(|e| anyhow!("invalid MySQL url: {}", e))?;
+            let mut builder = mysql_async::OptsBuilder::from_opts(opts);
+            if let Some(username) = &config.username {
+                builder = builder.user(Some(username));
+            }
+            if let Some(password) = &config.password {
+                builder = builder.pass(Some(password));
+            }
+            Ok(Database::MySql(builder.into()))
+        }
+        other => bail!(
+            "unsupported database '{}'; expected a postgres:// or mysql:// url",
+            other
+        ),
+    }
+}
+
+async fn test_inner(
+    c: JdbcConfig,
+    t: JdbcTable,
+    tx: tokio::sync::mpsc::Sender<Result<Event, Infallible>>,
+) -> anyhow::Result<String> {
+    tx.send(Ok(Event::default()
+        .json_data(TestSourceMessage::info("Connecting to database"))
+        .unwrap()))
+        .await
+        .unwrap();
+
+    // selecting no rows checks both that the table exists and that we can read it, with the table
+    // quoted as the sink quotes it
+    let query = |quote: char| {
+        let table = t
+            .table
+            .split('.')
+            .map(|part| {
+                let escaped = part.replace(quote, &format!("{}{}", quote, quote));
+                format!("{}{}{}", quote, escaped, quote)
+            })
+            .collect::<Vec<_>>()
+            .join(".");
+        format!("SELECT * FROM {} WHERE 1 = 0", table)
+    };
+
+    match database(&c)? {
+        Database::Postgres(config) => {
+            let (client, connection) = config
+                .connect(tokio_postgres::NoTls)
+                .await
+                .map_err(|e| anyhow!("Failed to connect to Postgres: {}", e))?;
+            tokio::spawn(connection);
+
+            client
+                .batch_execute(&query('"'))
+                .await
+                .map_err(|e| anyhow!("Failed to query table '{}': {}", t.table, e))?;
+        }
+        Database::MySql(opts) => {
+            let mut conn = mysql_async::Conn::new(opts)
+                .await
+                .map_err(|e| anyhow!("Failed to connect to MySQL: {}", e))?;
+
+            conn.query_drop(query('`'))
+                .await
+                .map_err(|e| anyhow!("Failed to query table '{}': {}", t.table, e))?;
+            let _ = conn.disconnect().await;
+        }
+    }
+
+    Ok(format!("Successfully validated table '{}'", t.table))
+}
+
+impl Connector for JdbcConnector {
+    type ProfileT = JdbcConfig;
+    type TableT = JdbcTable;
+
+    fn name(&self) -> &'static str {
+        "jdbc"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "jdbc".to_string(),
+            name: "JDBC".to_string(),
+            icon: ICON.to_string(),
+            description: "Upsert results into Postgres or MySQL tables".to_string(),
+            enabled: true,
+            source: false,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: Some(CONFIG_SCHEMA.to_string()),
+            table_config: TABLE_SCHEMA.to_string(),
+        }
+    }
+
+    fn config_description(&self, config: Self::ProfileT) -> String {
+        // strip any credentials from the url
+        match config.url.split_once('@') {
+            Some((prefix, rest)) => match prefix.split_once("://") {
+                Some((scheme, _)) => format!("{}://{}", scheme, rest),
+                None => rest.to_string(),
+            },
+            None => config.url,
+        }
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Sink
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        c: Self::ProfileT,
+        t: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<Result<Event, Infallible>>,
+    ) {
+        tokio::task::spawn(async move {
+            let resp = match test_inner(c, t, tx.clone()).await {
+                Ok(c) => TestSourceMessage::done(c),
+                Err(e) => TestSourceMessage::fail(e.to_string()),
+            };
+
+            tx.send(Ok(Event::default().json_data(resp).unwrap()))
+                .await
+                .unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let connection = match profile {
+            Some(connection_profile) => {
+                serde_json::from_value(connection_profile.config.clone())
+                    .map_err(|e| anyhow!("Failed to parse connection config: {:?}", e))?
+            }
+            None => JdbcConfig {
+                url: pull_opt("url", options)?,
+                username: options.remove("username"),
+                password: options.remove("password"),
+            },
+        };
+
+        let table = JdbcTable {
+            table: pull_opt("table", options)?,
+            primary_keys: vec![],
+        };
+
+        Self::from_config(&self, None, name, connection, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: JdbcConfig,
+        mut table: JdbcTable,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        database(&config)?;
+
+        let mut schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for JDBC connection"))?;
+
+        match (
+            table.primary_keys.is_empty(),
+            schema.primary_keys.is_empty(),
+        ) {
+            (true, true) => bail!("JDBC tables must declare a PRIMARY KEY to upsert on"),
+            (true, false) => table.primary_keys = schema.primary_keys.clone(),
+            (false, true) => schema.primary_keys = table.primary_keys.clone(),
+            (false, false) => {
+                if table.primary_keys != schema.primary_keys {
+                    bail!("the primaryKeys of the table don't match the primary key of its schema");
+                }
+            }
+        }
+
+        for key in &table.primary_keys {
+            if !schema.fields.iter().any(|f| &f.field_name == key) {
+                bail!("primary key '{}' is not a field of the table", key);
+            }
+        }
+
+        if schema.format.is_some() {
+            bail!("JDBC tables don't take a format; rows are written as columns of the table");
+        }
+
+        // the sink consumes Debezium-shaped changes, so that updating queries can be written
+        let format = Format::Json(JsonFormat {
+            debezium: true,
+            timestamp_format: TimestampFormat::RFC3339,
+            ..Default::default()
+        });
+        schema.format = Some(format.clone());
+
+        let description = format!("JdbcSink<{}>", table.table);
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            name: name.to_string(),
+            connection_type: ConnectionType::Sink,
+            schema,
+            operator: "connectors::jdbc::JdbcSink::<#in_k, #in_t>".to_string(),
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+}
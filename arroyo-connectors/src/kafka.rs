@@ -5,7 +5,9 @@ use std::collections::HashMap;
 use std::convert::Infallible;
 use typify::import_types;
 
-use arroyo_rpc::api_types::connections::{ConnectionProfile, ConnectionSchema, TestSourceMessage};
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, FieldType, PrimitiveType, SourceField, TestSourceMessage,
+};
 use axum::response::sse::Event;
 use rdkafka::{
     consumer::{BaseConsumer, Consumer},
@@ -90,12 +92,39 @@ impl Connector for KafkaConnector {
             .map(|t| t.to_owned())
             .ok_or_else(|| anyhow!("'format' must be set for Kafka connection"))?;
 
+        let metadata_fields = match &table.type_ {
+            TableType::Source { .. } => {
+                for field in &schema.fields {
+                    validate_metadata_field(field)?;
+                }
+                schema.metadata_fields()
+            }
+            TableType::Sink {
+                key_field,
+                headers_field,
+                ..
+            } => {
+                if !schema.metadata_fields().is_empty() {
+                    bail!("metadata fields can't be used in Kafka sinks; set sink.key_field or sink.headers_field to write a field as message metadata");
+                }
+                validate_sink_field("key_field", key_field.as_ref(), &schema, None)?;
+                validate_sink_field(
+                    "headers_field",
+                    headers_field.as_ref(),
+                    &schema,
+                    Some(&[PrimitiveType::String, PrimitiveType::Json]),
+                )?;
+                vec![]
+            }
+        };
+
         let config = OperatorConfig {
             connection: serde_json::to_value(config).unwrap(),
             table: serde_json::to_value(table).unwrap(),
             rate_limit: None,
             format: Some(format),
             framing: schema.framing.clone(),
+            metadata_fields,
         };
 
         Ok(Connection {
@@ -202,6 +231,8 @@ impl Connector for KafkaConnector {
                         Some("exactly_once") => Some(SinkCommitMode::ExactlyOnce),
                         Some(other) => bail!("invalid value for commit_mode '{}'", other),
                     },
+                    key_field: options.remove("sink.key_field"),
+                    headers_field: options.remove("sink.headers_field"),
                 }
             }
             _ => {
@@ -218,6 +249,80 @@ impl Connector for KafkaConnector {
     }
 }
 
+// the types that a field read from each kind of message metadata may have
+fn metadata_types(key: &str) -> Option<&'static [PrimitiveType]> {
+    match key {
+        "key" => Some(&[PrimitiveType::Bytes, PrimitiveType::String]),
+        "headers" => Some(&[PrimitiveType::String, PrimitiveType::Json]),
+        "topic" => Some(&[PrimitiveType::String]),
+        "partition" => Some(&[PrimitiveType::Int32, PrimitiveType::Int64]),
+        "offset" => Some(&[PrimitiveType::Int64]),
+        "timestamp" => Some(&[
+            PrimitiveType::UnixMillis,
+            PrimitiveType::UnixMicros,
+            PrimitiveType::UnixNanos,
+            PrimitiveType::DateTime,
+        ]),
+        _ => None,
+    }
+}
+
+fn validate_metadata_field(field: &SourceField) -> anyhow::Result<()> {
+    let Some(key) = &field.metadata_key else {
+        return Ok(());
+    };
+
+    let types = metadata_types(key).ok_or_else(|| {
+        anyhow!(
+            "unknown Kafka metadata '{}' for field '{}'; expected one of key, headers, topic, partition, offset or timestamp",
+            key,
+            field.field_name
+        )
+    })?;
+
+    if !matches!(&field.field_type.r#type, FieldType::Primitive(p) if types.contains(p)) {
+        bail!(
+            "field '{}' can't hold Kafka metadata '{}' as it has type {}",
+            field.field_name,
+            key,
+            field.field_type.sql_name.as_deref().unwrap_or("STRUCT")
+        );
+    }
+
+    Ok(())
+}
+
+fn validate_sink_field(
+    option: &str,
+    field: Option<&String>,
+    schema: &ConnectionSchema,
+    types: Option<&[PrimitiveType]>,
+) -> anyhow::Result<()> {
+    let Some(field) = field else {
+        return Ok(());
+    };
+
+    // the fields of the table may not be known until the query is planned
+    if schema.fields.is_empty() {
+        return Ok(());
+    }
+
+    let source_field = schema
+        .fields
+        .iter()
+        .find(|f| f.field_name == *field)
+        .ok_or_else(|| anyhow!("{} '{}' is not a field of the table", option, field))?;
+
+    if let Some(types) = types {
+        if !matches!(&source_field.field_type.r#type, FieldType::Primitive(p) if types.contains(p))
+        {
+            bail!("{} '{}' must be a TEXT field", option, field);
+        }
+    }
+
+    Ok(())
+}
+
 struct KafkaTester {
     connection: KafkaConfig,
     table: KafkaTable,
@@ -110,6 +110,7 @@ impl Connector for KinesisConnector {
             rate_limit: None,
             format: Some(format),
             framing: schema.framing.clone(),
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
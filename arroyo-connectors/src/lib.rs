@@ -25,11 +25,17 @@ pub mod blackhole;
 pub mod delta;
 pub mod filesystem;
 pub mod fluvio;
+pub mod http_lookup;
+pub mod iceberg;
 pub mod impulse;
+pub mod jdbc;
 pub mod kafka;
 pub mod kinesis;
+pub mod mqtt;
+pub mod nats;
 pub mod nexmark;
 pub mod polling_http;
+pub mod postgres_cdc;
 pub mod redis;
 pub mod single_file;
 pub mod sse;
@@ -42,14 +48,23 @@ pub fn connectors() -> HashMap<&'static str, Box<dyn ErasedConnector>> {
     m.insert("delta", Box::new(delta::DeltaLakeConnector {}));
     m.insert("filesystem", Box::new(filesystem::FileSystemConnector {}));
     m.insert("fluvio", Box::new(FluvioConnector {}));
+    m.insert("http_lookup", Box::new(http_lookup::HttpLookupConnector {}));
+    m.insert("iceberg", Box::new(iceberg::IcebergConnector {}));
     m.insert("impulse", Box::new(ImpulseConnector {}));
+    m.insert("jdbc", Box::new(jdbc::JdbcConnector {}));
     m.insert("kafka", Box::new(KafkaConnector {}));
     m.insert("kinesis", Box::new(kinesis::KinesisConnector {}));
+    m.insert("mqtt", Box::new(mqtt::MqttConnector {}));
+    m.insert("nats", Box::new(nats::NatsConnector {}));
     m.insert("nexmark", Box::new(NexmarkConnector {}));
     m.insert(
         "polling_http",
         Box::new(polling_http::PollingHTTPConnector {}),
     );
+    m.insert(
+        "postgres_cdc",
+        Box::new(postgres_cdc::PostgresCdcConnector {}),
+    );
     m.insert("redis", Box::new(redis::RedisConnector {}));
     m.insert("single_file", Box::new(single_file::SingleFileConnector {}));
     m.insert("sse", Box::new(SSEConnector {}));
@@ -320,6 +335,7 @@ pub(crate) fn source_field(name: &str, field_type: FieldType) -> SourceField {
             r#type: field_type,
         },
         nullable: false,
+        metadata_key: None,
     }
 }
 
@@ -328,6 +344,7 @@ pub(crate) fn nullable_field(name: &str, field_type: SourceFieldType) -> SourceF
         field_name: name.to_string(),
         field_type,
         nullable: true,
+        metadata_key: None,
     }
 }
 
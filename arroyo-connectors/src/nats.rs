@@ -0,0 +1,263 @@
+use anyhow::{anyhow, bail};
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::OperatorConfig;
+use async_nats::ServerAddr;
+use axum::response::sse::Event;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use typify::import_types;
+
+use crate::{pull_opt, Connection, Connector};
+
+pub struct NatsConnector {}
+
+const CONFIG_SCHEMA: &str = include_str!("../../connector-schemas/nats/connection.json");
+const TABLE_SCHEMA: &str = include_str!("../../connector-schemas/nats/table.json");
+const ICON: &str = include_str!("../resources/nats.svg");
+
+import_types!(schema = "../connector-schemas/nats/connection.json",);
+import_types!(schema = "../connector-schemas/nats/table.json");
+
+fn server_addrs(servers: &str) -> anyhow::Result<Vec<ServerAddr>> {
+    servers
+        .split(',')
+        .map(|s| {
+            s.trim()
+                .parse::<ServerAddr>()
+                .map_err(|e| anyhow!("invalid NATS server '{}': {:?}", s, e))
+        })
+        .collect()
+}
+
+async fn test_inner(
+    c: NatsConfig,
+    t: NatsTable,
+    tx: tokio::sync::mpsc::Sender<Result<Event, Infallible>>,
+) -> anyhow::Result<String> {
+    tx.send(Ok(Event::default()
+        .json_data(TestSourceMessage::info("Connecting to NATS"))
+        .unwrap()))
+        .await
+        .unwrap();
+
+    let options = match c.authentication {
+        NatsConfigAuthentication::None {} => async_nats::ConnectOptions::new(),
+        NatsConfigAuthentication::Credentials { username, password } => {
+            async_nats::ConnectOptions::with_user_and_password(username, password)
+        }
+        NatsConfigAuthentication::Token { token } => async_nats::ConnectOptions::with_token(token),
+    };
+
+    let client = options
+        .connect(server_addrs(&c.servers)?)
+        .await
+        .map_err(|e| anyhow!("Failed to connect to NATS: {:?}", e))?;
+
+    let TableType::Source { stream, .. } = t.type_ else {
+        return Ok("Successfully connected to NATS".to_string());
+    };
+
+    tx.send(Ok(Event::default()
+        .json_data(TestSourceMessage::info(
+            "Connected successfully, fetching stream",
+        ))
+        .unwrap()))
+        .await
+        .unwrap();
+
+    async_nats::jetstream::new(client)
+        .get_stream(&stream)
+        .await
+        .map_err(|e| anyhow!("Failed to fetch stream '{}': {:?}", stream, e))?;
+
+    Ok(format!("Successfully fetched stream '{}'", stream))
+}
+
+impl Connector for NatsConnector {
+    type ProfileT = NatsConfig;
+    type TableT = NatsTable;
+
+    fn name(&self) -> &'static str {
+        "nats"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "nats".to_string(),
+            name: "NATS".to_string(),
+            icon: ICON.to_string(),
+            description: "Read or write from NATS JetStream".to_string(),
+            enabled: true,
+            source: true,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: Some(CONFIG_SCHEMA.to_string()),
+            table_config: TABLE_SCHEMA.to_string(),
+        }
+    }
+
+    fn config_description(&self, config: Self::ProfileT) -> String {
+        config.servers
+    }
+
+    fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
+        match table.type_ {
+            TableType::Source { .. } => ConnectionType::Source,
+            TableType::Sink { .. } => ConnectionType::Sink,
+        }
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        c: Self::ProfileT,
+        t: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<Result<Event, Infallible>>,
+    ) {
+        tokio::task::spawn(async move {
+            let resp = match test_inner(c, t, tx.clone()).await {
+                Ok(c) => TestSourceMessage::done(c),
+                Err(e) => TestSourceMessage::fail(e.to_string()),
+            };
+
+            tx.send(Ok(Event::default().json_data(resp).unwrap()))
+                .await
+                .unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let connection = match profile {
+            Some(connection_profile) => {
+                serde_json::from_value(connection_profile.config.clone())
+                    .map_err(|e| anyhow!("Failed to parse connection config: {:?}", e))?
+            }
+            None => {
+                let authentication = match options.remove("auth.type").as_deref() {
+                    Some("none") | None => NatsConfigAuthentication::None {},
+                    Some("credentials") => NatsConfigAuthentication::Credentials {
+                        username: pull_opt("auth.username", options)?,
+                        password: pull_opt("auth.password", options)?,
+                    },
+                    Some("token") => NatsConfigAuthentication::Token {
+                        token: pull_opt("auth.token", options)?,
+                    },
+                    Some(other) => bail!("unknown auth type '{}'", other),
+                };
+
+                NatsConfig {
+                    servers: pull_opt("servers", options)?,
+                    authentication,
+                }
+            }
+        };
+
+        let typ = pull_opt("type", options)?;
+        let table_type = match typ.as_str() {
+            "source" => TableType::Source {
+                stream: pull_opt("stream", options)?,
+                subject: options.remove("subject"),
+                offset: match options.remove("source.offset").as_deref() {
+                    Some("earliest") => SourceOffset::Earliest,
+                    None | Some("latest") => SourceOffset::Latest,
+                    Some(other) => bail!("invalid value for source.offset '{}'", other),
+                },
+            },
+            "sink" => TableType::Sink {
+                subject: pull_opt("subject", options)?,
+            },
+            _ => {
+                bail!("type must be one of 'source' or 'sink'")
+            }
+        };
+
+        Self::from_config(
+            &self,
+            None,
+            name,
+            connection,
+            NatsTable { type_: table_type },
+            schema,
+        )
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: NatsConfig,
+        table: NatsTable,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        server_addrs(&config.servers)?;
+
+        let (typ, operator, desc) = match &table.type_ {
+            TableType::Source { stream, .. } => (
+                ConnectionType::Source,
+                "connectors::nats::source::NatsSourceFunc",
+                format!("NatsSource<{}>", stream),
+            ),
+            TableType::Sink { subject } => {
+                if subject.contains(['*', '>']) {
+                    bail!("sink subject '{}' can't contain wildcards", subject);
+                }
+
+                (
+                    ConnectionType::Sink,
+                    "connectors::nats::sink::NatsSinkFunc::<#in_k, #in_t>",
+                    format!("NatsSink<{}>", subject),
+                )
+            }
+        };
+
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for NATS connection"))?;
+
+        let format = schema
+            .format
+            .as_ref()
+            .map(|t| t.to_owned())
+            .ok_or_else(|| anyhow!("'format' must be set for NATS connection"))?;
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            framing: schema.framing.clone(),
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            name: name.to_string(),
+            connection_type: typ,
+            schema,
+            operator: operator.to_string(),
+            config: serde_json::to_string(&config).unwrap(),
+            description: desc,
+        })
+    }
+}
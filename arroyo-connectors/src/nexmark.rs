@@ -87,6 +87,7 @@ pub fn nexmark_schema() -> ConnectionSchema {
         ],
         definition: None,
         inferred: None,
+        primary_keys: vec![],
     }
 }
 
@@ -208,6 +209,7 @@ impl Connector for NexmarkConnector {
             rate_limit: None,
             format: None,
             framing: None,
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
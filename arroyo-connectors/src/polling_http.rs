@@ -211,6 +211,7 @@ impl Connector for PollingHTTPConnector {
             rate_limit: None,
             format: Some(format),
             framing: schema.framing.clone(),
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
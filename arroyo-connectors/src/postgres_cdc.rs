@@ -0,0 +1,272 @@
+use anyhow::{anyhow, bail};
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::formats::{Format, JsonFormat, TimestampFormat};
+use arroyo_rpc::OperatorConfig;
+use axum::response::sse::Event;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use tokio_postgres::NoTls;
+use typify::import_types;
+
+use crate::{pull_opt, pull_option_to_i64, Connection, Connector};
+
+pub struct PostgresCdcConnector {}
+
+const CONFIG_SCHEMA: &str = include_str!("../../connector-schemas/postgres_cdc/connection.json");
+const TABLE_SCHEMA: &str = include_str!("../../connector-schemas/postgres_cdc/table.json");
+const ICON: &str = include_str!("../resources/postgres.svg");
+
+import_types!(schema = "../connector-schemas/postgres_cdc/connection.json",);
+import_types!(schema = "../connector-schemas/postgres_cdc/table.json");
+
+fn postgres_config(config: &PostgresCdcConfig) -> anyhow::Result<tokio_postgres::Config> {
+    let port = u16::try_from(config.port.unwrap_or(5432))
+        .map_err(|_| anyhow!("invalid port {}", config.port.unwrap_or_default()))?;
+
+    let mut pg_config = tokio_postgres::Config::new();
+    pg_config
+        .host(&config.host)
+        .port(port)
+        .dbname(&config.database)
+        .user(&config.user)
+        .application_name("arroyo");
+
+    if let Some(password) = &config.password {
+        pg_config.password(password);
+    }
+
+    Ok(pg_config)
+}
+
+// splits a table name into its schema and name, defaulting to the public schema
+fn split_table_name(table: &str) -> anyhow::Result<(&str, &str)> {
+    match table.split('.').collect::<Vec<_>>().as_slice() {
+        [name] if !name.is_empty() => Ok(("public", name)),
+        [schema, name] if !schema.is_empty() && !name.is_empty() => Ok((schema, name)),
+        _ => bail!(
+            "invalid table '{}'; expected a table name, optionally qualified by its schema",
+            table
+        ),
+    }
+}
+
+async fn test_inner(
+    c: PostgresCdcConfig,
+    t: PostgresCdcTable,
+    tx: tokio::sync::mpsc::Sender<Result<Event, Infallible>>,
+) -> anyhow::Result<String> {
+    tx.send(Ok(Event::default()
+        .json_data(TestSourceMessage::info("Connecting to Postgres"))
+        .unwrap()))
+        .await
+        .unwrap();
+
+    let (client, connection) = postgres_config(&c)?
+        .connect(NoTls)
+        .await
+        .map_err(|e| anyhow!("Failed to connect to Postgres: {}", e))?;
+
+    tokio::spawn(connection);
+
+    let wal_level: String = client.query_one("SHOW wal_level", &[]).await?.get(0);
+    if wal_level != "logical" {
+        bail!(
+            "wal_level must be set to 'logical' for change data capture, but is '{}'",
+            wal_level
+        );
+    }
+
+    let can_replicate: bool = client
+        .query_one(
+            "SELECT rolreplication OR rolsuper FROM pg_roles WHERE rolname = current_user",
+            &[],
+        )
+        .await?
+        .get(0);
+    if !can_replicate {
+        bail!("user '{}' does not have the REPLICATION attribute", c.user);
+    }
+
+    tx.send(Ok(Event::default()
+        .json_data(TestSourceMessage::info(
+            "Connected successfully, checking table",
+        ))
+        .unwrap()))
+        .await
+        .unwrap();
+
+    let (schema, name) = split_table_name(&t.table)?;
+    let exists = client
+        .query_opt(
+            "SELECT 1 FROM pg_tables WHERE schemaname = $1 AND tablename = $2",
+            &[&schema, &name],
+        )
+        .await?
+        .is_some();
+    if !exists {
+        bail!("table '{}' does not exist", t.table);
+    }
+
+    Ok(format!("Successfully validated table '{}'", t.table))
+}
+
+impl Connector for PostgresCdcConnector {
+    type ProfileT = PostgresCdcConfig;
+    type TableT = PostgresCdcTable;
+
+    fn name(&self) -> &'static str {
+        "postgres_cdc"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "postgres_cdc".to_string(),
+            name: "Postgres CDC".to_string(),
+            icon: ICON.to_string(),
+            description: "Capture changes from Postgres tables through logical replication"
+                .to_string(),
+            enabled: true,
+            source: true,
+            sink: false,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: Some(CONFIG_SCHEMA.to_string()),
+            table_config: TABLE_SCHEMA.to_string(),
+        }
+    }
+
+    fn config_description(&self, config: Self::ProfileT) -> String {
+        format!(
+            "{}:{}/{}",
+            config.host,
+            config.port.unwrap_or(5432),
+            config.database
+        )
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Source
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        c: Self::ProfileT,
+        t: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<Result<Event, Infallible>>,
+    ) {
+        tokio::task::spawn(async move {
+            let resp = match test_inner(c, t, tx.clone()).await {
+                Ok(c) => TestSourceMessage::done(c),
+                Err(e) => TestSourceMessage::fail(e.to_string()),
+            };
+
+            tx.send(Ok(Event::default().json_data(resp).unwrap()))
+                .await
+                .unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let connection = match profile {
+            Some(connection_profile) => {
+                serde_json::from_value(connection_profile.config.clone())
+                    .map_err(|e| anyhow!("Failed to parse connection config: {:?}", e))?
+            }
+            None => PostgresCdcConfig {
+                host: pull_opt("host", options)?,
+                port: pull_option_to_i64("port", options)?,
+                database: pull_opt("database", options)?,
+                user: pull_opt("user", options)?,
+                password: options.remove("password"),
+            },
+        };
+
+        let table = PostgresCdcTable {
+            table: pull_opt("table", options)?,
+            slot: pull_opt("slot", options)?,
+            publication: pull_opt("publication", options)?,
+            snapshot: match options.remove("snapshot").as_deref() {
+                Some("true") => Some(true),
+                Some("false") => Some(false),
+                None => None,
+                Some(other) => bail!("invalid value for snapshot '{}'", other),
+            },
+        };
+
+        Self::from_config(&self, None, name, connection, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: PostgresCdcConfig,
+        table: PostgresCdcTable,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        postgres_config(&config)?;
+        split_table_name(&table.table)?;
+
+        let mut schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for Postgres CDC connection"))?;
+
+        if schema.fields.is_empty() {
+            bail!("Postgres CDC tables must define the columns to capture");
+        }
+
+        if schema.format.is_some() {
+            bail!("Postgres CDC tables don't take a format; they always produce updates");
+        }
+
+        // the source produces Debezium-shaped changes, which makes the table updating
+        let format = Format::Json(JsonFormat {
+            debezium: true,
+            timestamp_format: TimestampFormat::RFC3339,
+            ..Default::default()
+        });
+        schema.format = Some(format.clone());
+
+        let description = format!("PostgresCdcSource<{}>", table.table);
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            name: name.to_string(),
+            connection_type: ConnectionType::Source,
+            schema,
+            operator: "connectors::postgres_cdc::source::PostgresCdcSourceFunc".to_string(),
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+}
@@ -3,9 +3,11 @@ use arroyo_rpc::api_types::connections::{
     ConnectionProfile, ConnectionSchema, ConnectionType, FieldType, PrimitiveType,
     TestSourceMessage,
 };
+use arroyo_rpc::formats::Format;
 use arroyo_rpc::OperatorConfig;
 use axum::response::sse::Event;
-use redis::cluster::ClusterClient;
+use redis::cluster::{ClusterClient, ClusterClientBuilder};
+use redis::IntoConnectionInfo;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::convert::Infallible;
@@ -46,6 +48,35 @@ fn validate_column(
     Ok(column)
 }
 
+fn standard_client(config: &RedisConfig, address: &str) -> anyhow::Result<redis::Client> {
+    let mut info = address
+        .into_connection_info()
+        .map_err(|e| anyhow!("Invalid Redis address {}: {:?}", address, e))?;
+    if let Some(username) = &config.username {
+        info.redis.username = Some(username.clone());
+    }
+    if let Some(password) = &config.password {
+        info.redis.password = Some(password.clone());
+    }
+
+    redis::Client::open(info)
+        .map_err(|e| anyhow!("Failed to construct Redis client for {}: {:?}", address, e))
+}
+
+fn cluster_client(config: &RedisConfig, addresses: &[Address]) -> anyhow::Result<ClusterClient> {
+    let mut builder = ClusterClientBuilder::new(addresses.iter().map(|a| a.0.clone()).collect());
+    if let Some(username) = &config.username {
+        builder = builder.username(username.clone());
+    }
+    if let Some(password) = &config.password {
+        builder = builder.password(password.clone());
+    }
+
+    builder
+        .build()
+        .map_err(|e| anyhow!("Failed to construct Redis Cluster client: {:?}", e))
+}
+
 async fn test_inner(
     c: RedisConfig,
     tx: tokio::sync::mpsc::Sender<Result<Event, Infallible>>,
@@ -56,15 +87,9 @@ async fn test_inner(
         .await
         .unwrap();
 
-    match c.connection {
+    match &c.connection {
         RedisConfigConnection::Address(address) => {
-            let client = redis::Client::open(address.0.clone()).map_err(|e| {
-                anyhow!(
-                    "Failed to construct Redis client for {}: {:?}",
-                    address.0,
-                    e
-                )
-            })?;
+            let client = standard_client(&c, &address.0)?;
 
             let mut connection = client
                 .get_async_connection()
@@ -85,8 +110,7 @@ async fn test_inner(
                 .map_err(|e| anyhow!("Received error sending PING command: {:?}", e))?;
         }
         RedisConfigConnection::Addresses(addresses) => {
-            let client = ClusterClient::new(addresses.into_iter().map(|a| a.0).collect())
-                .map_err(|e| anyhow!("Failed to construct Redis Cluster client: {:?}", e))?;
+            let client = cluster_client(&c, addresses)?;
 
             let mut connection = client
                 .get_async_connection()
@@ -124,9 +148,9 @@ impl Connector for RedisConnector {
             id: "redis".to_string(),
             name: "Redis".to_string(),
             icon: ICON.to_string(),
-            description: "Write results to Redis".to_string(),
+            description: "Read from Redis Streams or Pub/Sub, write results to Redis, or use it as a lookup table".to_string(),
             enabled: true,
-            source: false,
+            source: true,
             sink: true,
             testing: false,
             hidden: false,
@@ -136,8 +160,12 @@ impl Connector for RedisConnector {
         }
     }
 
-    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
-        return ConnectionType::Source;
+    fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
+        match table.connector_type {
+            TableType::Source(_) => ConnectionType::Source,
+            TableType::Target(_) => ConnectionType::Sink,
+            TableType::Lookup { .. } => ConnectionType::Lookup,
+        }
     }
 
     fn get_schema(
@@ -178,10 +206,8 @@ impl Connector for RedisConnector {
     ) -> anyhow::Result<Connection> {
         let connection_config = match profile {
             Some(connection_profile) => {
-                let config: RedisConfigConnection =
-                    serde_json::from_value(connection_profile.config.clone())
-                        .map_err(|e| anyhow!("Failed to parse connection config: {:?}", e))?;
-                RedisConfig { connection: config }
+                serde_json::from_value(connection_profile.config.clone())
+                    .map_err(|e| anyhow!("Failed to parse connection config: {:?}", e))?
             }
             None => {
                 let address = options.remove("address");
@@ -206,6 +232,8 @@ impl Connector for RedisConnector {
                 };
                 RedisConfig {
                     connection: connection_config,
+                    username: options.remove("username"),
+                    password: options.remove("password"),
                 }
             }
         };
@@ -238,6 +266,38 @@ impl Connector for RedisConnector {
         }
 
         let sink = match typ.as_str() {
+            "source" => TableType::Source(match pull_opt("source", options)?.as_str() {
+                "stream" => Source::Stream {
+                    stream: pull_opt("source.stream", options)?,
+                    group: pull_opt("source.group", options)?,
+                    consumer: options.remove("source.consumer"),
+                    offset: match options.remove("source.offset").as_deref() {
+                        Some("latest") | None => StreamOffset::Latest,
+                        Some("earliest") => StreamOffset::Earliest,
+                        Some(offset) => {
+                            bail!("'{}' is not a valid value for source.offset; must be one of 'earliest' or 'latest'", offset);
+                        }
+                    },
+                    field: options.remove("source.field"),
+                },
+                "pubsub" => Source::PubSub {
+                    channels: pull_opt("source.channels", options)?,
+                    pattern: options
+                        .remove("source.pattern")
+                        .map(|p| {
+                            p.parse::<bool>().map_err(|_| {
+                                anyhow!("'{}' is not a valid value for source.pattern; must be 'true' or 'false'", p)
+                            })
+                        })
+                        .transpose()?,
+                },
+                s => {
+                    bail!(
+                        "'{}' is not a valid redis source; must be one of `stream` or `pubsub`",
+                        s
+                    );
+                }
+            }),
             "sink" => TableType::Target(match pull_opt("target", options)?.as_str() {
                 "string" => Target::StringTable {
                     key_prefix: pull_opt("target.key_prefix", options)?,
@@ -288,8 +348,14 @@ impl Connector for RedisConnector {
                     bail!("'{}' is not a valid redis target", s);
                 }
             }),
+            "lookup" => TableType::Lookup {
+                key_prefix: options.remove("lookup.key_prefix"),
+            },
             s => {
-                bail!("'{}' is not a valid type; must be `sink`", s);
+                bail!(
+                    "'{}' is not a valid type; must be one of `source`, `sink` or `lookup`",
+                    s
+                );
             }
         };
 
@@ -324,21 +390,33 @@ impl Connector for RedisConnector {
 
         match &config.connection {
             RedisConfigConnection::Address(address) => {
-                let _ = redis::Client::open(address.0.clone()).map_err(|e| {
-                    anyhow!(
-                        "Failed to construct Redis client for {}: {:?}",
-                        address.0,
-                        e
-                    )
-                })?;
+                standard_client(&config, &address.0)?;
             }
             RedisConfigConnection::Addresses(addresses) => {
-                let _ = ClusterClient::new(addresses.into_iter().map(|a| a.0.clone()).collect())
-                    .map_err(|e| anyhow!("Failed to construct Redis Cluster client: {:?}", e))?;
+                cluster_client(&config, addresses)?;
             }
         }
 
         match &table.connector_type {
+            TableType::Source(Source::Stream {
+                stream,
+                group,
+                field,
+                ..
+            }) => {
+                if stream.is_empty() || group.is_empty() {
+                    bail!("source.stream and source.group must not be empty");
+                }
+
+                if field.is_none() && !matches!(format, Format::Json(_)) {
+                    bail!("source.field must be set for Redis Stream sources unless the format is json");
+                }
+            }
+            TableType::Source(Source::PubSub { channels, .. }) => {
+                if channels.split(',').any(|c| c.trim().is_empty()) {
+                    bail!("invalid value '{}' for source.channels, must be a comma-separated list of channels", channels);
+                }
+            }
             TableType::Target(Target::StringTable { key_column, .. }) => {
                 if let Some(key_column) = key_column {
                     validate_column(&schema, key_column.clone(), "connector_type.key_column")?;
@@ -366,6 +444,30 @@ impl Connector for RedisConnector {
                     "connector_type.hash_field_column",
                 )?;
             }
+            TableType::Lookup { .. } => {}
+        };
+
+        let (connection_type, operator, description) = match &table.connector_type {
+            TableType::Source(Source::Stream { stream, .. }) => (
+                ConnectionType::Source,
+                "connectors::redis::source::RedisStreamSourceFunc",
+                format!("RedisStreamSource<{}>", stream),
+            ),
+            TableType::Source(Source::PubSub { channels, .. }) => (
+                ConnectionType::Source,
+                "connectors::redis::source::RedisPubSubSourceFunc",
+                format!("RedisPubSubSource<{}>", channels),
+            ),
+            TableType::Target(_) => (
+                ConnectionType::Sink,
+                "connectors::redis::sink::RedisSinkFunc::<#in_k, #in_t>",
+                "RedisSink".to_string(),
+            ),
+            TableType::Lookup { .. } => (
+                ConnectionType::Lookup,
+                "connectors::redis::lookup::RedisLookup",
+                "RedisLookup".to_string(),
+            ),
         };
 
         let config = OperatorConfig {
@@ -374,16 +476,17 @@ impl Connector for RedisConnector {
             rate_limit: None,
             format: Some(format),
             framing: schema.framing.clone(),
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
             id,
             name: name.to_string(),
-            connection_type: ConnectionType::Sink,
+            connection_type,
             schema,
-            operator: "connectors::redis::sink::RedisSinkFunc::<#in_k, #in_t>".to_string(),
+            operator: operator.to_string(),
             config: serde_json::to_string(&config).unwrap(),
-            description: "RedisSink".to_string(),
+            description,
         })
     }
 }
@@ -98,6 +98,9 @@ impl Connector for SingleFileConnector {
             ConnectionType::Sink => {
                 "connectors::filesystem::single_file::sink::FileSink::<#in_k, #in_t>".to_string()
             }
+            ConnectionType::Lookup => {
+                bail!("Single File connections cannot be used as lookup tables")
+            }
         };
 
         let config = OperatorConfig {
@@ -106,6 +109,7 @@ impl Connector for SingleFileConnector {
             rate_limit: None,
             format: Some(format),
             framing: schema.framing.clone(),
+            metadata_fields: vec![],
         };
 
         Ok(Connection {
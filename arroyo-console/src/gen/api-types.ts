@@ -244,6 +244,7 @@ export interface components {
       format?: components["schemas"]["Format"] | null;
       framing?: components["schemas"]["Framing"] | null;
       inferred?: boolean | null;
+      primaryKeys?: (string)[];
       structName?: string | null;
     };
     ConnectionTable: {
@@ -271,7 +272,7 @@ export interface components {
       schema?: components["schemas"]["ConnectionSchema"] | null;
     };
     /** @enum {string} */
-    ConnectionType: "source" | "sink";
+    ConnectionType: "source" | "sink" | "lookup";
     Connector: {
       connectionConfig?: string | null;
       customSchemas: boolean;
@@ -289,6 +290,13 @@ export interface components {
     ConnectorCollection: {
       data: (components["schemas"]["Connector"])[];
     };
+    CsvFormat: {
+      delimiter?: string;
+      escape?: string | null;
+      header?: boolean;
+      nullLiteral?: string;
+      quote?: string;
+    };
     FieldType: OneOf<[{
       primitive: components["schemas"]["PrimitiveType"];
     }, {
@@ -302,6 +310,8 @@ export interface components {
       parquet: components["schemas"]["ParquetFormat"];
     }, {
       raw_string: components["schemas"]["RawStringFormat"];
+    }, {
+      csv: components["schemas"]["CsvFormat"];
     }]>;
     Framing: {
       method: components["schemas"]["FramingMethod"];
@@ -485,6 +495,7 @@ export interface components {
     SourceField: {
       fieldName: string;
       fieldType: components["schemas"]["SourceFieldType"];
+      metadataKey?: string | null;
       nullable: boolean;
     };
     SourceFieldType: {
@@ -1,4 +1,4 @@
---! all_jobs : Job(ttl_micros?, state?, start_time?, finish_time?, tasks?, failure_message?, run_id?, pipeline_path?, wasm_path?)
+--! all_jobs : Job(ttl_micros?, state?, start_time?, finish_time?, tasks?, failure_message?, run_id?, pipeline_path?, wasm_path?, program_hash?, pending_savepoint?, restore_savepoint_path?)
 SELECT
     job_configs.id as id,
     job_configs.organization_id as org_id,
@@ -17,13 +17,21 @@ SELECT
     run_id,
     pipeline_path,
     wasm_path,
+    program_hash,
     job_configs.restart_nonce as config_restart_nonce,
     job_statuses.restart_nonce as status_restart_nonce,
-    restart_mode
+    restart_mode,
+    (SELECT pub_id FROM savepoints
+     WHERE savepoints.job_id = job_configs.id AND savepoints.state IN ('pending', 'inprogress')
+     ORDER BY savepoints.id
+     LIMIT 1) as pending_savepoint,
+    restore_savepoints.path as restore_savepoint_path
 FROM job_configs
-LEFT JOIN job_statuses ON job_configs.id = job_statuses.id;
+LEFT JOIN job_statuses ON job_configs.id = job_statuses.id
+LEFT JOIN savepoints restore_savepoints ON job_configs.restore_savepoint_id = restore_savepoints.id
+    AND restore_savepoints.state = 'ready';
 
---! update_job_status (start_time?, finish_time?, tasks?, failure_message?, pipeline_path?, wasm_path?)
+--! update_job_status (start_time?, finish_time?, tasks?, failure_message?, pipeline_path?, wasm_path?, program_hash?)
 UPDATE job_statuses
 SET state = :state,
     start_time = :start_time,
@@ -33,6 +41,7 @@ SET state = :state,
     restarts = :restarts,
     pipeline_path = :pipeline_path,
     wasm_path = :wasm_path,
+    program_hash = :program_hash,
     run_id = :run_id,
     restart_nonce = :restart_nonce
 WHERE id = :job_id;
@@ -89,3 +98,37 @@ LIMIT 1;
 INSERT INTO job_log_messages (pub_id, job_id, operator_id, task_index, log_level, message, details)
 VALUES (:pub_id, :job_id, :operator_id, :task_index, :log_level, :message, :details)
 RETURNING id;
+
+--! start_savepoint
+UPDATE savepoints
+SET
+    state = 'inprogress',
+    state_backend = :state_backend,
+    epoch = :epoch
+WHERE pub_id = :pub_id AND state = 'pending';
+
+--! finish_savepoint
+UPDATE savepoints
+SET
+    state = 'ready',
+    path = :path,
+    operators = :operators,
+    program = :program,
+    finish_time = :finish_time
+WHERE pub_id = :pub_id;
+
+--! fail_savepoint
+UPDATE savepoints
+SET
+    state = 'failed',
+    failure_message = :failure_message,
+    finish_time = :finish_time
+WHERE pub_id = :pub_id;
+
+--! fail_interrupted_savepoints
+UPDATE savepoints
+SET
+    state = 'failed',
+    failure_message = :failure_message,
+    finish_time = :finish_time
+WHERE job_id = :job_id AND state = 'inprogress';
@@ -7,6 +7,7 @@ use std::{
 use crate::types::public::StopMode as SqlStopMode;
 use anyhow::bail;
 use arroyo_datastream::Program;
+use arroyo_rpc::grpc::api::PipelineProgram;
 use arroyo_rpc::grpc::{
     worker_grpc_client::WorkerGrpcClient, CheckpointReq, CommitReq, JobFinishedReq,
     LoadCompactedDataReq, StopExecutionReq, StopMode, TaskCheckpointEventType,
@@ -15,11 +16,11 @@ use arroyo_state::{BackingStore, StateBackend};
 use arroyo_types::{to_micros, WorkerId};
 
 use deadpool_postgres::Pool;
+use prost::Message;
 use time::OffsetDateTime;
 
 use arroyo_rpc::public_ids::{generate_id, IdTypes};
 use arroyo_state::checkpoint_state::CheckpointState;
-use arroyo_state::parquet::ParquetBackend;
 use tokio::{sync::mpsc::Receiver, task::JoinHandle};
 use tonic::{transport::Channel, Request};
 use tracing::{error, info, warn};
@@ -36,6 +37,11 @@ const CHECKPOINTS_TO_KEEP: u32 = 4;
 const COMPACT_EVERY: u32 = 2;
 const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(30);
 
+/// The location of a savepoint in the checkpoint storage
+pub fn savepoint_path(pub_id: &str) -> String {
+    format!("savepoints/{}", pub_id)
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum WorkerState {
     Running,
@@ -374,7 +380,7 @@ impl RunningJobModel {
             self.workers.values().map(|w| w.connect.clone()).collect();
         for (operator_id, parallelism) in self.operator_parallelism.clone() {
             // compact the operator's state and notify the workers to load the new files
-            if let Ok(Some(compaction_result)) = ParquetBackend::compact_operator(
+            if let Ok(Some(compaction_result)) = StateBackend::compact_operator(
                 parallelism,
                 self.job_id.clone(),
                 operator_id.clone(),
@@ -523,6 +529,11 @@ pub struct JobController {
     config: JobConfig,
     model: RunningJobModel,
     cleanup_task: Option<JoinHandle<anyhow::Result<u32>>>,
+    // a savepoint that has been requested but whose checkpoint hasn't started yet
+    pending_savepoint: Option<String>,
+    // the savepoint being checkpointed, with the epoch of its checkpoint
+    savepoint: Option<(String, u32)>,
+    savepoint_task: Option<(String, JoinHandle<()>)>,
 }
 
 impl std::fmt::Debug for JobController {
@@ -531,6 +542,7 @@ impl std::fmt::Debug for JobController {
             .field("config", &self.config)
             .field("model", &self.model)
             .field("cleaning", &self.cleanup_task.is_some())
+            .field("savepoint", &self.savepoint)
             .finish()
     }
 }
@@ -597,6 +609,9 @@ impl JobController {
             },
             config,
             cleanup_task: None,
+            pending_savepoint: None,
+            savepoint: None,
+            savepoint_task: None,
         }
     }
 
@@ -651,8 +666,25 @@ impl JobController {
             }
         }
 
+        // check on savepoints
+        if self.savepoint_task.is_some() && self.savepoint_task.as_ref().unwrap().1.is_finished() {
+            let (pub_id, task) = self.savepoint_task.take().unwrap();
+
+            if let Err(e) = task.await {
+                error!(
+                    message = "savepoint panicked",
+                    job_id = self.config.id,
+                    savepoint = pub_id,
+                    error = format!("{:?}", e)
+                );
+            }
+        }
+
         if let Some(new_epoch) = self.model.cleanup_needed() {
-            if self.cleanup_task.is_none() && self.model.checkpoint_state.is_none() {
+            if self.cleanup_task.is_none()
+                && self.model.checkpoint_state.is_none()
+                && self.savepoint_task.is_none()
+            {
                 self.cleanup_task = Some(self.start_cleanup(new_epoch));
             }
         }
@@ -660,6 +692,12 @@ impl JobController {
         // check on checkpointing
         if self.model.checkpoint_state.is_some() {
             self.model.finish_checkpoint_if_done(&self.pool).await?;
+        } else if self.pending_savepoint.is_some()
+            && self.cleanup_task.is_none()
+            && self.savepoint_task.is_none()
+        {
+            // savepoints are taken with their own checkpoint
+            self.start_savepoint().await?;
         } else if self.model.last_checkpoint.elapsed() > self.config.checkpoint_interval
             && self.cleanup_task.is_none()
         {
@@ -667,6 +705,13 @@ impl JobController {
             self.checkpoint(false).await?;
         }
 
+        if self.model.checkpoint_state.is_none() {
+            if let Some((pub_id, epoch)) = self.savepoint.take() {
+                let task = self.start_savepoint_copy(pub_id.clone(), epoch);
+                self.savepoint_task = Some((pub_id, task));
+            }
+        }
+
         Ok(ControllerProgress::Continue)
     }
 
@@ -693,6 +738,112 @@ impl JobController {
         }
     }
 
+    /// Requests a savepoint, which is taken once any in-progress checkpoint has finished
+    pub fn savepoint(&mut self, pub_id: &str) {
+        let in_progress = self.savepoint.as_ref().map(|(id, _)| id.as_str()) == Some(pub_id)
+            || self.savepoint_task.as_ref().map(|(id, _)| id.as_str()) == Some(pub_id);
+        if !in_progress {
+            self.pending_savepoint = Some(pub_id.to_string());
+        }
+    }
+
+    async fn start_savepoint(&mut self) -> anyhow::Result<()> {
+        let pub_id = self.pending_savepoint.take().unwrap();
+        let epoch = self.model.epoch + 1;
+
+        let c = self.pool.get().await?;
+        let updated = controller_queries::start_savepoint()
+            .bind(
+                &c,
+                &StateBackend::name().to_string(),
+                &(epoch as i32),
+                &pub_id,
+            )
+            .await?;
+        if updated == 0 {
+            // the savepoint has already been taken
+            return Ok(());
+        }
+
+        info!(
+            message = "Starting savepoint",
+            job_id = self.config.id,
+            savepoint = pub_id,
+            epoch
+        );
+        self.checkpoint(false).await?;
+        self.savepoint = Some((pub_id, epoch));
+        Ok(())
+    }
+
+    fn start_savepoint_copy(&mut self, pub_id: String, epoch: u32) -> JoinHandle<()> {
+        let job_id = self.config.id.clone();
+        let pool = self.pool.clone();
+        // the program is kept with the savepoint, so that pipelines it's restored into can be
+        // checked against the layout of its state
+        let program = PipelineProgram::try_from(self.model.program.clone());
+
+        tokio::spawn(async move {
+            let path = savepoint_path(&pub_id);
+            let result: anyhow::Result<()> = async {
+                let program = program?.encode_to_vec();
+                let metadata = StateBackend::write_savepoint(&job_id, epoch, &path).await?;
+                let c = pool.get().await?;
+                controller_queries::finish_savepoint()
+                    .bind(
+                        &c,
+                        &path,
+                        &serde_json::to_value(&metadata.operator_ids).unwrap(),
+                        &program,
+                        &OffsetDateTime::now_utc(),
+                        &pub_id,
+                    )
+                    .await?;
+                Ok(())
+            }
+            .await;
+
+            match result {
+                Ok(()) => {
+                    info!(
+                        message = "Finished savepoint",
+                        job_id,
+                        savepoint = pub_id,
+                        epoch,
+                        path
+                    );
+                }
+                Err(e) => {
+                    error!(
+                        message = "savepoint failed",
+                        job_id,
+                        savepoint = pub_id,
+                        error = format!("{:?}", e)
+                    );
+                    let failed = async {
+                        controller_queries::fail_savepoint()
+                            .bind(
+                                &pool.get().await?,
+                                &e.to_string(),
+                                &OffsetDateTime::now_utc(),
+                                &pub_id,
+                            )
+                            .await?;
+                        Ok::<_, anyhow::Error>(())
+                    };
+                    if let Err(e) = failed.await {
+                        error!(
+                            message = "failed to mark savepoint as failed",
+                            job_id,
+                            savepoint = pub_id,
+                            error = format!("{:?}", e)
+                        );
+                    }
+                }
+            }
+        })
+    }
+
     pub fn finished(&self) -> bool {
         self.model.all_tasks_finished()
     }
@@ -84,6 +84,10 @@ pub struct JobConfig {
     parallelism_overrides: HashMap<String, usize>,
     restart_nonce: i32,
     restart_mode: RestartMode,
+    // the savepoint requested for the job that hasn't been taken yet
+    pending_savepoint: Option<String>,
+    // the savepoint to restore the job from if it doesn't have any checkpoints
+    restore_savepoint_path: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -98,6 +102,8 @@ pub struct JobStatus {
     restarts: i32,
     pipeline_path: Option<String>,
     wasm_path: Option<String>,
+    // hash of the program that pipeline_path was compiled from
+    program_hash: Option<String>,
     restart_nonce: i32,
 }
 
@@ -115,6 +121,7 @@ impl JobStatus {
                 &self.restarts,
                 &self.pipeline_path,
                 &self.wasm_path,
+                &self.program_hash,
                 &self.run_id,
                 &self.restart_nonce,
                 &self.id,
@@ -623,6 +630,8 @@ impl ControllerServer {
                             .collect(),
                         restart_nonce: p.config_restart_nonce,
                         restart_mode: p.restart_mode,
+                        pending_savepoint: p.pending_savepoint,
+                        restore_savepoint_path: p.restore_savepoint_path,
                     };
 
                     let mut jobs = jobs.lock().await;
@@ -638,6 +647,7 @@ impl ControllerServer {
                         restarts: p.restarts,
                         pipeline_path: p.pipeline_path,
                         wasm_path: p.wasm_path,
+                        program_hash: p.program_hash,
                         restart_nonce: p.status_restart_nonce,
                     };
 
@@ -657,6 +667,9 @@ impl ControllerServer {
     }
 
     pub async fn start(self, addr: SocketAddr) -> Result<(), Box<dyn std::error::Error>> {
+        // the backend is recorded in checkpoints, so make sure that it's one we know
+        arroyo_state::backend_type()?;
+
         let reflection = tonic_reflection::server::Builder::configure()
             .register_encoded_file_descriptor_set(arroyo_rpc::grpc::API_FILE_DESCRIPTOR_SET)
             .build()?;
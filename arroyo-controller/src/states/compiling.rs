@@ -1,7 +1,7 @@
 use tokio::sync::oneshot;
 use tracing::info;
 
-use crate::states::{fatal, stop_if_desired_non_running, StateError};
+use crate::states::{fatal, load_program, stop_if_desired_non_running, StateError};
 use crate::{compiler::ProgramCompiler, JobMessage};
 
 use super::{scheduling::Scheduling, JobContext, State, Transition};
@@ -16,7 +16,16 @@ impl State for Compiling {
     }
 
     async fn next(self: Box<Self>, ctx: &mut JobContext) -> Result<Transition, StateError> {
-        if ctx.status.pipeline_path.is_some() {
+        // the program may have been replaced by an upgrade of the pipeline since it was loaded
+        match load_program(&ctx.pool, ctx.config.pipeline_id).await {
+            Ok(program) => *ctx.program = program,
+            Err(e) => {
+                return Err(ctx.retryable(self, "failed to load pipeline program", e, 10));
+            }
+        }
+        let hash = ctx.program.get_hash();
+
+        if ctx.status.pipeline_path.is_some() && ctx.status.program_hash.as_ref() == Some(&hash) {
             info!(
                 message = "Pipeline already compiled",
                 job_id = ctx.config.id,
@@ -24,11 +33,7 @@ impl State for Compiling {
             return Ok(Transition::next(*self, Scheduling {}));
         }
 
-        info!(
-            message = "Compiling pipeline",
-            job_id = ctx.config.id,
-            hash = ctx.program.get_hash()
-        );
+        info!(message = "Compiling pipeline", job_id = ctx.config.id, hash,);
 
         let pc = ProgramCompiler::new(
             ctx.config.pipeline_name.clone(),
@@ -46,6 +51,7 @@ impl State for Compiling {
                     Ok(res) => {
                         ctx.status.pipeline_path = Some(res.pipeline_path);
                         ctx.status.wasm_path = Some(res.wasm_path);
+                        ctx.status.program_hash = Some(hash.clone());
                         return Ok(Transition::next(*self, Scheduling {}));
                     }
                     Err(e) => return Err(e
@@ -257,13 +257,7 @@ impl TransitionTo<Restarting> for Running {
     }
 }
 impl TransitionTo<Restarting> for Restarting {}
-impl TransitionTo<Scheduling> for Restarting {
-    fn update_status(&self) -> TransitionFn {
-        Box::new(|ctx| {
-            ctx.status.run_id += 1;
-        })
-    }
-}
+impl TransitionTo<Compiling> for Restarting {}
 impl TransitionTo<Stopping> for Restarting {}
 impl TransitionTo<CheckpointStopping> for Restarting {}
 
@@ -533,6 +527,17 @@ async fn execute_state<'a>(
     (next, ctx)
 }
 
+/// Loads the current program of the pipeline, which is replaced when the pipeline is upgraded
+pub(crate) async fn load_program(pool: &Pool, pipeline_id: i64) -> Result<Program> {
+    let c = pool.get().await?;
+    let res = controller_queries::get_program()
+        .bind(&c, &pipeline_id)
+        .one()
+        .await?;
+
+    PipelineProgram::decode(&res[..])?.try_into()
+}
+
 pub async fn run_to_completion(
     config: Arc<RwLock<JobConfig>>,
     mut status: JobStatus,
@@ -541,20 +546,8 @@ pub async fn run_to_completion(
     mut rx: Receiver<JobMessage>,
     scheduler: Arc<dyn Scheduler>,
 ) {
-    let c = pool.get().await.unwrap();
     let id = config.read().unwrap().pipeline_id;
-    let mut program: Program = {
-        let res = controller_queries::get_program()
-            .bind(&c, &id)
-            .one()
-            .await
-            .unwrap();
-
-        PipelineProgram::decode(&res[..])
-            .unwrap()
-            .try_into()
-            .unwrap()
-    };
+    let mut program = load_program(&pool, id).await.unwrap();
 
     let mut ctx = JobContext {
         config: config.read().unwrap().clone(),
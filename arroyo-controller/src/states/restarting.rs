@@ -1,5 +1,5 @@
+use crate::states::compiling::Compiling;
 use crate::states::recovering::Recovering;
-use crate::states::scheduling::Scheduling;
 use crate::states::stop_if_desired_non_running;
 use crate::types::public::RestartMode;
 use crate::JobMessage;
@@ -30,7 +30,7 @@ impl State for Restarting {
                     match job_controller.checkpoint_finished().await {
                         Ok(done) => {
                             if done && job_controller.finished() {
-                                return Ok(Transition::next(*self, Scheduling {}));
+                                return Ok(Transition::next(*self, Compiling {}));
                             }
                         }
                         Err(e) => {
@@ -76,7 +76,7 @@ impl State for Restarting {
                     return Err(ctx.retryable(self, "failed to tear down existing cluster", e, 10));
                 }
 
-                Ok(Transition::next(*self, Scheduling {}))
+                Ok(Transition::next(*self, Compiling {}))
             }
         }
     }
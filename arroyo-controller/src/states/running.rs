@@ -35,6 +35,10 @@ impl State for Running {
     async fn next(mut self: Box<Self>, ctx: &mut JobContext) -> Result<Transition, StateError> {
         stop_if_desired_running!(self, ctx.config);
 
+        if let Some(savepoint) = &ctx.config.pending_savepoint {
+            ctx.job_controller.as_mut().unwrap().savepoint(savepoint);
+        }
+
         let running_start = Instant::now();
 
         let mut log_interval = tokio::time::interval(Duration::from_secs(60));
@@ -62,7 +66,11 @@ impl State for Running {
                                 }));
                             }
 
-                            let job_controller = ctx.job_controller.as_ref().unwrap();
+                            let job_controller = ctx.job_controller.as_mut().unwrap();
+                            if let Some(savepoint) = &c.pending_savepoint {
+                                job_controller.savepoint(savepoint);
+                            }
+
                             for (op, p) in &c.parallelism_overrides {
                                 if let Some(actual) = job_controller.operator_parallelism(op){
                                     if actual != *p {
@@ -8,14 +8,17 @@ use arroyo_datastream::Program;
 use arroyo_rpc::grpc::{
     worker_grpc_client::WorkerGrpcClient, StartExecutionReq, TableWriteBehavior, TaskAssignment,
 };
+use arroyo_rpc::public_ids::{generate_id, IdTypes};
 use arroyo_types::WorkerId;
+use time::OffsetDateTime;
 use tokio::{sync::Mutex, task::JoinHandle};
 use tonic::{transport::Channel, Request};
 use tracing::{error, info, warn};
 
 use anyhow::anyhow;
 use arroyo_state::{
-    committing_state::CommittingState, parquet::get_storage_env_vars, BackingStore, StateBackend,
+    check_checkpoint_backend, committing_state::CommittingState, parquet::get_storage_env_vars,
+    BackingStore, StateBackend,
 };
 
 use crate::{
@@ -309,7 +312,7 @@ impl State for Scheduling {
             needs_commits: bool,
         }
 
-        let checkpoint_info = controller_queries::last_successful_checkpoint()
+        let mut checkpoint_info = controller_queries::last_successful_checkpoint()
             .bind(&c, &ctx.config.id)
             .opt()
             .await
@@ -330,6 +333,70 @@ impl State for Scheduling {
                 }
             });
 
+        // a job started from a savepoint restores it on its first run
+        let restore_path = ctx
+            .config
+            .restore_savepoint_path
+            .clone()
+            .filter(|_| checkpoint_info.is_none());
+        if let Some(path) = restore_path {
+            info!(
+                message = "restoring savepoint",
+                job_id = ctx.config.id,
+                path
+            );
+
+            let metadata = match StateBackend::restore_savepoint(&path, &ctx.config.id).await {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    return Err(ctx.retryable(self, "failed to restore savepoint", e, 10));
+                }
+            };
+
+            let id = match controller_queries::create_checkpoint()
+                .bind(
+                    &c,
+                    &generate_id(IdTypes::Checkpoint),
+                    &ctx.config.organization_id,
+                    &ctx.config.id,
+                    &StateBackend::name().to_string(),
+                    &(metadata.epoch as i32),
+                    &(metadata.min_epoch as i32),
+                    &OffsetDateTime::now_utc(),
+                )
+                .one()
+                .await
+            {
+                Ok(id) => id,
+                Err(e) => {
+                    return Err(ctx.retryable(
+                        self,
+                        "failed to record the restored savepoint",
+                        e.into(),
+                        10,
+                    ));
+                }
+            };
+            if let Err(e) = controller_queries::commit_checkpoint()
+                .bind(&c, &OffsetDateTime::now_utc(), &id)
+                .await
+            {
+                return Err(ctx.retryable(
+                    self,
+                    "failed to record the restored savepoint",
+                    e.into(),
+                    10,
+                ));
+            }
+
+            checkpoint_info = Some(CheckpointInfo {
+                epoch: metadata.epoch,
+                min_epoch: metadata.min_epoch,
+                id,
+                needs_commits: false,
+            });
+        }
+
         {
             // mark in-progress checkpoints as failed
             let last_epoch = checkpoint_info
@@ -342,6 +409,25 @@ impl State for Scheduling {
                 .unwrap();
         }
 
+        // savepoints are taken by the run that started them, so any that were in progress have
+        // been interrupted
+        if let Err(e) = controller_queries::fail_interrupted_savepoints()
+            .bind(
+                &c,
+                &"the job restarted before the savepoint finished".to_string(),
+                &OffsetDateTime::now_utc(),
+                &ctx.config.id,
+            )
+            .await
+        {
+            return Err(ctx.retryable(
+                self,
+                "failed to mark interrupted savepoints as failed",
+                e.into(),
+                10,
+            ));
+        }
+
         let mut committing_state = None;
 
         // clear all of the epochs after the one we're loading so that we don't read in-progress data
@@ -364,6 +450,11 @@ impl State for Scheduling {
                     )
                 })?;
 
+            // retrying can't help if the checkpoint was written by another backend
+            if let Err(e) = check_checkpoint_backend(&metadata) {
+                return Err(fatal("Failed to restore job from checkpoint.", e));
+            }
+
             if let Err(e) = StateBackend::prepare_checkpoint_load(&metadata).await {
                 return Err(ctx.retryable(self, "failed to prepare checkpoint for loading", e, 10));
             }
@@ -372,6 +463,16 @@ impl State for Scheduling {
                 let mut commit_subtasks = HashSet::new();
                 let mut committing_data = HashMap::new();
                 for operator_id in &metadata.operator_ids {
+                    // find the node with matching operator id; operators that were removed by an
+                    // upgrade of the pipeline have nothing left to commit to
+                    let Some(program_node) = ctx
+                        .program
+                        .graph
+                        .node_weights()
+                        .find(|node| node.operator_id == *operator_id)
+                    else {
+                        continue;
+                    };
                     let operator_metadata =
                         StateBackend::load_operator_metadata(&ctx.config.id, operator_id, epoch)
                             .await;
@@ -399,13 +500,6 @@ impl State for Scheduling {
                             .iter()
                             .any(|table| TableWriteBehavior::CommitWrites == table.write_behavior())
                     {
-                        // find the node with matching operator id
-                        let program_node = ctx
-                            .program
-                            .graph
-                            .node_weights()
-                            .find(|node| node.operator_id == *operator_id)
-                            .unwrap();
                         for subtask_index in 0..program_node.parallelism {
                             commit_subtasks.insert((operator_id.clone(), subtask_index as u32));
                         }
@@ -4,7 +4,7 @@
 use std::cell::RefCell;
 use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashMap, HashSet};
-use std::fmt::{Debug, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 use std::hash::Hasher;
 use std::marker::PhantomData;
 use std::ops::Add;
@@ -103,10 +103,22 @@ pub struct WasmUDF {
 
 #[derive(Clone, Encode, Decode, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd)]
 pub enum WindowType {
-    Tumbling { width: Duration },
-    Sliding { width: Duration, slide: Duration },
+    Tumbling {
+        width: Duration,
+    },
+    Sliding {
+        width: Duration,
+        slide: Duration,
+    },
     Instant,
-    Session { gap: Duration },
+    Session {
+        gap: Duration,
+        max_length: Option<Duration>,
+    },
+    Cumulating {
+        step: Duration,
+        max_size: Duration,
+    },
 }
 
 fn format_duration(duration: Duration) -> String {
@@ -144,9 +156,31 @@ impl Debug for WindowType {
             Self::Instant => {
                 write!(f, "InstantWindow")
             }
-            Self::Session { gap } => {
+            Self::Session {
+                gap,
+                max_length: None,
+            } => {
                 write!(f, "SessionWindow({})", format_duration(*gap))
             }
+            Self::Session {
+                gap,
+                max_length: Some(max_length),
+            } => {
+                write!(
+                    f,
+                    "SessionWindow(gap: {}, max length: {})",
+                    format_duration(*gap),
+                    format_duration(*max_length)
+                )
+            }
+            Self::Cumulating { step, max_size } => {
+                write!(
+                    f,
+                    "CumulatingWindow(step: {}, max size: {})",
+                    format_duration(*step),
+                    format_duration(*max_size)
+                )
+            }
         }
     }
 }
@@ -232,6 +266,32 @@ pub struct TumblingWindowAggregator {
     pub bin_type: String,
 }
 
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SessionWindowAggregator {
+    pub gap: Duration,
+    pub max_length: Option<Duration>,
+    // fn(&K, Window, &BinA) -> OutT
+    pub aggregator: String,
+    // fn(&T, Option<&BinA>) -> BinA
+    pub bin_merger: String,
+    // fn(&BinA, Option<&BinA>) -> BinA
+    pub bin_combiner: String,
+    pub bin_type: String,
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CumulatingWindowAggregator {
+    pub step: Duration,
+    pub max_size: Duration,
+    // fn(&K, Window, &BinA) -> OutT
+    pub aggregator: String,
+    // fn(&T, Option<&BinA>) -> BinA
+    pub bin_merger: String,
+    // fn(&BinA, Option<&BinA>) -> BinA
+    pub bin_combiner: String,
+    pub bin_type: String,
+}
+
 #[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize, PartialEq, Eq)]
 pub struct TumblingTopN {
     pub width: Duration,
@@ -274,13 +334,54 @@ pub struct NonWindowAggregator {
     pub bin_type: String,
 }
 
+#[derive(Clone, Encode, Decode, Serialize, Deserialize, PartialEq)]
+pub struct LookupJoin {
+    // the connector that values are fetched from; its operator is the LookupConnector implementation
+    pub connector: ConnectorOp,
+    pub join_type: JoinType,
+    // the type that looked-up values are deserialized into
+    pub lookup_type: String,
+    // fn(&T, Option<&LookupT>) -> Option<OutT>
+    pub merge: String,
+    pub cache_ttl: Option<Duration>,
+    pub max_cache_entries: usize,
+    pub max_batch_size: usize,
+    pub max_batch_wait: Duration,
+}
+
+#[derive(Clone, Encode, Decode, Serialize, Deserialize, PartialEq)]
+pub struct IntervalJoin {
+    // rows match when the left row's timestamp minus the right row's is within
+    // [lower_bound, upper_bound]
+    pub lower_bound_micros: i64,
+    pub upper_bound_micros: i64,
+}
+
+#[derive(Clone, Encode, Decode, Serialize, Deserialize, PartialEq, Debug)]
+pub struct PatternElement {
+    // index of the pattern variable
+    pub variable: usize,
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+#[derive(Clone, Encode, Decode, Serialize, Deserialize, PartialEq)]
+pub struct MatchRecognize {
+    pub pattern: Vec<PatternElement>,
+    pub within: Option<Duration>,
+    // fn(&T, usize) -> bool, whether a row satisfies the condition of a pattern variable
+    pub define: String,
+    // fn(&K, &[(usize, T)]) -> OutT, computes the output row from the rows of a match
+    pub measures: String,
+}
+
 #[derive(Copy, Clone, Debug, Encode, Decode, Serialize, Deserialize, PartialEq)]
 pub enum ImpulseSpec {
     Delay(Duration),
     EventsPerSecond(f32),
 }
 
-#[derive(Clone, Encode, Decode, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Encode, Decode, Serialize, Deserialize, PartialEq)]
 pub struct ConnectorOp {
     // path of the operator that this will compile into (like `crate::sources::kafka::KafkaSource`)
     pub operator: String,
@@ -359,6 +460,8 @@ pub enum Operator {
     },
     SlidingWindowAggregator(SlidingWindowAggregator),
     TumblingWindowAggregator(TumblingWindowAggregator),
+    SessionWindowAggregator(SessionWindowAggregator),
+    CumulatingWindowAggregator(CumulatingWindowAggregator),
     TumblingTopN(TumblingTopN),
     SlidingAggregatingTopN(SlidingAggregatingTopN),
     JoinWithExpiration {
@@ -375,6 +478,12 @@ pub enum Operator {
         name: String,
         expression: String,
     },
+    LookupJoin(LookupJoin),
+    IntervalJoin(IntervalJoin),
+    TemporalJoin {
+        join_type: JoinType,
+    },
+    MatchRecognize(MatchRecognize),
 }
 
 #[derive(Clone, Encode, Decode, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -462,6 +571,28 @@ impl Debug for Operator {
                 "TumblingWindowAggregator<{:?}>",
                 WindowType::Tumbling { width: *width }
             ),
+            Operator::SessionWindowAggregator(SessionWindowAggregator {
+                gap, max_length, ..
+            }) => write!(
+                f,
+                "SessionWindowAggregator<{:?}>",
+                WindowType::Session {
+                    gap: *gap,
+                    max_length: *max_length
+                }
+            ),
+            Operator::CumulatingWindowAggregator(CumulatingWindowAggregator {
+                step,
+                max_size,
+                ..
+            }) => write!(
+                f,
+                "CumulatingWindowAggregator<{:?}>",
+                WindowType::Cumulating {
+                    step: *step,
+                    max_size: *max_size
+                }
+            ),
             Operator::TumblingTopN(TumblingTopN {
                 width,
                 max_elements,
@@ -500,10 +631,144 @@ impl Debug for Operator {
                 name,
                 expression: _,
             } => write!(f, "updating_key<{}>", name),
+            Operator::LookupJoin(LookupJoin {
+                connector,
+                join_type,
+                ..
+            }) => write!(
+                f,
+                "LookupJoin<{}, join_type: {:?}>",
+                connector.description, join_type
+            ),
+            Operator::IntervalJoin(IntervalJoin {
+                lower_bound_micros,
+                upper_bound_micros,
+                ..
+            }) => write!(
+                f,
+                "IntervalJoin<lower: {}us, upper: {}us>",
+                lower_bound_micros, upper_bound_micros
+            ),
+            Operator::TemporalJoin { join_type } => {
+                write!(f, "TemporalJoin<join_type: {:?}>", join_type)
+            }
+            Operator::MatchRecognize(MatchRecognize {
+                pattern, within, ..
+            }) => write!(
+                f,
+                "MatchRecognize<pattern: {:?}, within: {:?}>",
+                pattern, within
+            ),
         }
     }
 }
 
+impl ConnectorOp {
+    // the tables a connector keeps and what is stored in them (like the topic partitions that
+    // offsets are tracked for, or whether writes are committed transactionally) depend on its
+    // table config, unlike its connection
+    fn state_config(&self) -> String {
+        serde_json::from_str::<serde_json::Value>(&self.config)
+            .ok()
+            .and_then(|config| config.get("table").map(|table| table.to_string()))
+            .unwrap_or_else(|| self.config.clone())
+    }
+}
+
+impl Operator {
+    /// Describes the layout of the state kept by the operator, or None if it is stateless. State
+    /// can only be restored into an operator with the same signature.
+    pub fn state_signature(&self) -> Option<String> {
+        Some(match self {
+            Operator::ConnectorSource(c) => format!("source<{}, {}>", c.operator, c.state_config()),
+            Operator::ConnectorSink(c) => format!("sink<{}, {}>", c.operator, c.state_config()),
+            Operator::Window { typ, .. } => format!("window<{:?}>", typ),
+            Operator::Count => "count".to_string(),
+            Operator::Aggregate(behavior) => format!("aggregate<{:?}>", behavior),
+            Operator::Watermark(_) => "watermark".to_string(),
+            Operator::WindowJoin { window } => format!("window_join<{:?}>", window),
+            Operator::SlidingWindowAggregator(agg) => format!(
+                "sliding_window_aggregator<{:?}, {:?}, {}>",
+                agg.width, agg.slide, agg.bin_type
+            ),
+            Operator::TumblingWindowAggregator(agg) => {
+                format!(
+                    "tumbling_window_aggregator<{:?}, {}>",
+                    agg.width, agg.bin_type
+                )
+            }
+            Operator::SessionWindowAggregator(agg) => {
+                format!("session_window_aggregator<{:?}, {}>", agg.gap, agg.bin_type)
+            }
+            Operator::CumulatingWindowAggregator(agg) => format!(
+                "cumulating_window_aggregator<{:?}, {:?}, {}>",
+                agg.step, agg.max_size, agg.bin_type
+            ),
+            Operator::TumblingTopN(top_n) => format!(
+                "tumbling_top_n<{:?}, {}>",
+                top_n.width, top_n.partition_key_type
+            ),
+            Operator::SlidingAggregatingTopN(top_n) => format!(
+                "sliding_aggregating_top_n<{:?}, {:?}, {}>",
+                top_n.width, top_n.slide, top_n.bin_type
+            ),
+            Operator::JoinWithExpiration { .. } => "join_with_expiration".to_string(),
+            Operator::NonWindowAggregator(agg) => {
+                format!("non_window_aggregator<{}>", agg.bin_type)
+            }
+            Operator::IntervalJoin(_) => "interval_join".to_string(),
+            Operator::TemporalJoin { .. } => "temporal_join".to_string(),
+            Operator::MatchRecognize(m) => format!("match_recognize<{:?}>", m.pattern),
+            Operator::FusedWasmUDFs { .. }
+            | Operator::GlobalKey
+            | Operator::ExpressionOperator { .. }
+            | Operator::FlattenOperator { .. }
+            | Operator::ArrayMapOperator { .. }
+            | Operator::FlatMapOperator { .. }
+            | Operator::UpdatingOperator { .. }
+            | Operator::UpdatingKeyOperator { .. }
+            | Operator::LookupJoin(_) => return None,
+        })
+    }
+}
+
+/// Describes how the state of a running program carries over to a new version of it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateCompatibility {
+    /// stateful operators whose state will be restored into the new program
+    pub restored: Vec<String>,
+    /// stateful operators that are not in the new program, whose state will be dropped
+    pub dropped: Vec<String>,
+    /// stateful operators of the new program that will start without state
+    pub added: Vec<String>,
+    /// operators whose state cannot be read by the new program, with the reason why
+    pub incompatible: Vec<(String, String)>,
+}
+
+impl StateCompatibility {
+    pub fn is_compatible(&self) -> bool {
+        self.incompatible.is_empty()
+    }
+}
+
+impl Display for StateCompatibility {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (operator, reason) in &self.incompatible {
+            writeln!(f, "  * {}: {}", operator, reason)?;
+        }
+        for (label, operators) in [
+            ("restored", &self.restored),
+            ("dropped", &self.dropped),
+            ("started without state", &self.added),
+        ] {
+            if !operators.is_empty() {
+                writeln!(f, "  {}: {}", label, operators.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Encode, Decode, serde::Serialize, serde::Deserialize, PartialEq)]
 pub struct StreamNode {
     pub operator_id: String,
@@ -1142,6 +1407,12 @@ impl Program {
                 Operator::TumblingWindowAggregator(_) => {
                     s.insert(format!("tumbling window aggregator"));
                 }
+                Operator::SessionWindowAggregator(_) => {
+                    s.insert(format!("session window aggregator"));
+                }
+                Operator::CumulatingWindowAggregator(_) => {
+                    s.insert(format!("cumulating window aggregator"));
+                }
                 Operator::TumblingTopN(_) => {
                     s.insert(format!("tumbling top n"));
                 }
@@ -1154,6 +1425,23 @@ impl Program {
                 Operator::NonWindowAggregator(_) => {
                     s.insert(format!("non-window aggregator"));
                 }
+                Operator::IntervalJoin(_) => {
+                    s.insert(format!("interval join"));
+                }
+                Operator::TemporalJoin { .. } => {
+                    s.insert(format!("temporal join"));
+                }
+                Operator::MatchRecognize(_) => {
+                    s.insert(format!("match recognize"));
+                }
+                Operator::LookupJoin(LookupJoin { connector, .. }) => {
+                    s.insert(format!(
+                        "lookup join {}",
+                        Regex::new("::<.*>$")
+                            .unwrap()
+                            .replace(&connector.operator, "")
+                    ));
+                }
                 _ => {}
             }
         }
@@ -1285,6 +1573,79 @@ impl Program {
             .collect()
     }
 
+    /// Matches the stateful operators of this program to those of `new` by operator id, and
+    /// checks that the state of each matched operator can be read by its new version.
+    pub fn state_compatibility(&self, new: &Program) -> StateCompatibility {
+        let mut compatibility = StateCompatibility::default();
+
+        for index in self.graph.node_indices() {
+            let node = &self.graph[index];
+            let Some(signature) = node.operator.state_signature() else {
+                continue;
+            };
+
+            let Some(new_index) = new
+                .graph
+                .node_indices()
+                .find(|i| new.graph[*i].operator_id == node.operator_id)
+            else {
+                compatibility.dropped.push(node.operator_id.clone());
+                continue;
+            };
+
+            let new_signature = new.graph[new_index].operator.state_signature();
+            if new_signature.as_ref() != Some(&signature) {
+                compatibility.incompatible.push((
+                    node.operator_id.clone(),
+                    format!(
+                        "state layout changed from {} to {}",
+                        signature,
+                        new_signature.as_deref().unwrap_or("stateless")
+                    ),
+                ));
+            } else if !matches!(node.operator, Operator::ConnectorSink(_))
+                && self.input_types(index) != new.input_types(new_index)
+            {
+                // sinks track the state of the writes they have made, not the rows they received,
+                // so only other operators depend on the types of their inputs
+                compatibility.incompatible.push((
+                    node.operator_id.clone(),
+                    "the key or value types of its inputs changed".to_string(),
+                ));
+            } else {
+                compatibility.restored.push(node.operator_id.clone());
+            }
+        }
+
+        compatibility.added = new
+            .graph
+            .node_weights()
+            .filter(|node| node.operator.state_signature().is_some())
+            .filter(|node| {
+                !self
+                    .graph
+                    .node_weights()
+                    .any(|old| old.operator_id == node.operator_id)
+            })
+            .map(|node| node.operator_id.clone())
+            .collect();
+
+        compatibility
+    }
+
+    fn input_types(&self, index: NodeIndex) -> Vec<(EdgeType, &str, &str)> {
+        let mut types: Vec<_> = self
+            .graph
+            .edges_directed(index, Direction::Incoming)
+            .map(|edge| {
+                let edge = edge.weight();
+                (edge.typ.clone(), edge.key.as_str(), edge.value.as_str())
+            })
+            .collect();
+        types.sort();
+        types
+    }
+
     pub fn get_hash(&self) -> String {
         let mut hasher = DefaultHasher::new();
         let bs = bincode::encode_to_vec(self, bincode::config::standard()).unwrap();
@@ -1454,14 +1815,30 @@ impl Program {
                                     instant_window(#agg))
                             }
                         }
-                        WindowType::Session { gap } => {
+                        WindowType::Session { gap, max_length } => {
                             let gap = duration_to_syn_expr(*gap);
+                            let max_length = match max_length {
+                                Some(max_length) => {
+                                    let max_length = duration_to_syn_expr(*max_length);
+                                    quote! { Some(#max_length) }
+                                }
+                                None => quote! { None },
+                            };
                             quote! {
                                 Box::new(SessionWindowFunc::<#in_k, #in_t, #out_t>::new(
-                                    #agg, #gap
+                                    #agg, #gap, #max_length
                                 ))
                             }
                         }
+                        WindowType::Cumulating { step, max_size } => {
+                            let step = duration_to_syn_expr(*step);
+                            let max_size = duration_to_syn_expr(*max_size);
+
+                            quote! {
+                                Box::new(KeyedWindowFunc::<#in_k, #in_t, #out_t, CumulatingWindowAssigner>::
+                                    cumulating_window(#step, #max_size, #agg))
+                            }
+                        }
                     }
                 }
                 Operator::Watermark(watermark) => {
@@ -1542,6 +1919,9 @@ impl Program {
                         WindowType::Session { .. } => {
                             unimplemented!("Session windows are not supported in joins")
                         }
+                        WindowType::Cumulating { .. } => {
+                            unimplemented!("Cumulating windows are not supported in joins")
+                        }
                     }
                 }
                 Operator::Count => {
@@ -1706,6 +2086,52 @@ impl Program {
                             #bin_merger))
                     }
                 },
+                Operator::SessionWindowAggregator(SessionWindowAggregator { gap, max_length, aggregator, bin_merger, bin_combiner, bin_type }) => {
+                    let in_k = parse_type(&input.unwrap().weight().key);
+                    let in_t = parse_type(&input.unwrap().weight().value);
+                    let out_t = parse_type(&output.unwrap().weight().value);
+                    let bin_t = parse_type(bin_type);
+                    let gap = duration_to_syn_expr(*gap);
+                    let max_length = match max_length {
+                        Some(max_length) => {
+                            let max_length = duration_to_syn_expr(*max_length);
+                            quote! { Some(#max_length) }
+                        }
+                        None => quote! { None },
+                    };
+                    let aggregator: syn::ExprClosure = parse_str(aggregator).unwrap();
+                    let bin_merger: syn::ExprClosure = parse_str(bin_merger).unwrap();
+                    let bin_combiner: syn::ExprClosure = parse_str(bin_combiner).unwrap();
+                    quote!{
+                        Box::new(arroyo_worker::operators::session_aggregating_window::
+                            SessionAggregatingWindowFunc::<#in_k, #in_t, #bin_t, #out_t>::
+                        new(#gap,
+                            #max_length,
+                            #aggregator,
+                            #bin_merger,
+                            #bin_combiner))
+                    }
+                },
+                Operator::CumulatingWindowAggregator(CumulatingWindowAggregator { step, max_size, aggregator, bin_merger, bin_combiner, bin_type }) => {
+                    let in_k = parse_type(&input.unwrap().weight().key);
+                    let in_t = parse_type(&input.unwrap().weight().value);
+                    let out_t = parse_type(&output.unwrap().weight().value);
+                    let bin_t = parse_type(bin_type);
+                    let step = duration_to_syn_expr(*step);
+                    let max_size = duration_to_syn_expr(*max_size);
+                    let aggregator: syn::ExprClosure = parse_str(aggregator).unwrap();
+                    let bin_merger: syn::ExprClosure = parse_str(bin_merger).unwrap();
+                    let bin_combiner: syn::ExprClosure = parse_str(bin_combiner).unwrap();
+                    quote!{
+                        Box::new(arroyo_worker::operators::cumulating_aggregating_window::
+                            CumulatingAggregatingWindowFunc::<#in_k, #in_t, #bin_t, #out_t>::
+                        new(#step,
+                            #max_size,
+                            #aggregator,
+                            #bin_merger,
+                            #bin_combiner))
+                    }
+                },
                 Operator::TumblingTopN(
                         TumblingTopN {
                             width,
@@ -1827,6 +2253,51 @@ impl Program {
                     }
 
                 },
+                Operator::IntervalJoin(IntervalJoin { lower_bound_micros, upper_bound_micros }) => {
+                    let mut inputs: Vec<_> = self.graph.edges_directed(idx, Direction::Incoming)
+                        .collect();
+                    inputs.sort_by_key(|e| e.weight().typ.clone());
+                    assert_eq!(2, inputs.len(), "IntervalJoin should have 2 inputs, but has {}", inputs.len());
+                    assert_eq!(inputs[0].weight().key, inputs[1].weight().key, "IntervalJoin inputs must have the same key type");
+
+                    let in_k = parse_type(&inputs[0].weight().key);
+                    let in_t1 = parse_type(&inputs[0].weight().value);
+                    let in_t2 = parse_type(&inputs[1].weight().value);
+
+                    quote!{
+                        Box::new(arroyo_worker::operators::interval_join::
+                            IntervalJoin::<#in_k, #in_t1, #in_t2>::new(#lower_bound_micros, #upper_bound_micros))
+                    }
+                },
+                Operator::TemporalJoin { join_type } => {
+                    let mut inputs: Vec<_> = self.graph.edges_directed(idx, Direction::Incoming)
+                        .collect();
+                    inputs.sort_by_key(|e| e.weight().typ.clone());
+                    assert_eq!(2, inputs.len(), "TemporalJoin should have 2 inputs, but has {}", inputs.len());
+                    assert_eq!(inputs[0].weight().key, inputs[1].weight().key, "TemporalJoin inputs must have the same key type");
+
+                    let in_k = parse_type(&inputs[0].weight().key);
+                    let in_t1 = parse_type(&inputs[0].weight().value);
+                    let in_t2 = parse_type(&inputs[1].weight().value);
+
+                    let (t2_updating, in_t2_inner) = match extract_container_type("UpdatingData", &in_t2) {
+                        Some(t) => (true, t),
+                        None => (false, in_t2.clone()),
+                    };
+
+                    let join_fn_head = match join_type {
+                        JoinType::Inner => "inner_join",
+                        JoinType::Left => "left_join",
+                        JoinType::Right | JoinType::Full => unreachable!("temporal joins must be inner or left joins"),
+                    };
+                    let join_fn_tail = if t2_updating { "_right_updating" } else { "" };
+                    let join_fn_name = format_ident!("{}{}", join_fn_head, join_fn_tail);
+
+                    quote!{
+                        Box::new(arroyo_worker::operators::temporal_join::
+                            #join_fn_name::<#in_k, #in_t1, #in_t2_inner>())
+                    }
+                },
                 Operator::UpdatingOperator { name, expression } => {
                     let expr : syn::Expr = parse_str(expression).expect(expression);
                     let in_k = parse_type(&input.unwrap().weight().key);
@@ -1867,6 +2338,60 @@ impl Program {
                         new(#name.to_string(), #expr))
                     }
                 },
+                Operator::LookupJoin(LookupJoin { connector, join_type: _, lookup_type, merge, cache_ttl, max_cache_entries, max_batch_size, max_batch_wait }) => {
+                    let in_k = parse_type(&input.unwrap().weight().key);
+                    let in_t = parse_type(&input.unwrap().weight().value);
+                    let out_t = parse_type(&output.unwrap().weight().value);
+                    let lookup_t = parse_type(lookup_type);
+                    let connector_t = parse_type(&connector.operator);
+                    let config = &connector.config;
+                    let merge: syn::ExprClosure = parse_str(merge).unwrap();
+                    let cache_ttl = match cache_ttl {
+                        Some(ttl) => {
+                            let ttl = duration_to_syn_expr(*ttl);
+                            quote! { Some(#ttl) }
+                        }
+                        None => quote! { None },
+                    };
+                    let max_batch_wait = duration_to_syn_expr(*max_batch_wait);
+                    quote! {
+                        Box::new(arroyo_worker::operators::lookup_join::
+                            LookupJoinFunc::<#in_k, #in_t, #lookup_t, #out_t, #connector_t>::
+                        new(#config, #merge, #cache_ttl, #max_cache_entries, #max_batch_size, #max_batch_wait))
+                    }
+                },
+                Operator::MatchRecognize(MatchRecognize { pattern, within, define, measures }) => {
+                    let in_k = parse_type(&input.unwrap().weight().key);
+                    let in_t = parse_type(&input.unwrap().weight().value);
+                    let out_t = parse_type(&output.unwrap().weight().value);
+                    let pattern = pattern.iter().map(|PatternElement { variable, min, max }| {
+                        let max = match max {
+                            Some(max) => quote! { Some(#max) },
+                            None => quote! { None },
+                        };
+                        quote! {
+                            arroyo_worker::operators::match_recognize::PatternElement {
+                                variable: #variable,
+                                min: #min,
+                                max: #max,
+                            }
+                        }
+                    });
+                    let within = match within {
+                        Some(within) => {
+                            let within = duration_to_syn_expr(*within);
+                            quote! { Some(#within) }
+                        }
+                        None => quote! { None },
+                    };
+                    let define: syn::ExprClosure = parse_str(define).expect(define);
+                    let measures: syn::ExprClosure = parse_str(measures).expect(measures);
+                    quote! {
+                        Box::new(arroyo_worker::operators::match_recognize::
+                            MatchRecognize::<#in_k, #in_t, #out_t>::
+                        new(vec![#(#pattern),*], #within, #define, #measures))
+                    }
+                },
             };
 
             (node.operator_id.clone(), description, body, node.parallelism)
@@ -2138,6 +2663,36 @@ impl From<Operator> for GrpcApi::operator::Operator {
                 bin_merger,
                 bin_type,
             }),
+            Operator::SessionWindowAggregator(SessionWindowAggregator {
+                gap,
+                max_length,
+                aggregator,
+                bin_merger,
+                bin_combiner,
+                bin_type,
+            }) => GrpcOperator::SessionWindowAggregator(GrpcApi::SessionWindowAggregator {
+                gap_micros: gap.as_micros() as u64,
+                max_length_micros: max_length.map(|max_length| max_length.as_micros() as u64),
+                aggregator,
+                bin_merger,
+                bin_combiner,
+                bin_type,
+            }),
+            Operator::CumulatingWindowAggregator(CumulatingWindowAggregator {
+                step,
+                max_size,
+                aggregator,
+                bin_merger,
+                bin_combiner,
+                bin_type,
+            }) => GrpcOperator::CumulatingWindowAggregator(GrpcApi::CumulatingWindowAggregator {
+                step_micros: step.as_micros() as u64,
+                max_size_micros: max_size.as_micros() as u64,
+                aggregator,
+                bin_merger,
+                bin_combiner,
+                bin_type,
+            }),
             Operator::TumblingTopN(TumblingTopN {
                 width,
                 max_elements,
@@ -2210,6 +2765,67 @@ impl From<Operator> for GrpcApi::operator::Operator {
             Operator::UpdatingKeyOperator { name, expression } => {
                 GrpcOperator::UpdatingKeyOperator(GrpcApi::UpdatingKeyOperator { name, expression })
             }
+            Operator::LookupJoin(LookupJoin {
+                connector,
+                join_type,
+                lookup_type,
+                merge,
+                cache_ttl,
+                max_cache_entries,
+                max_batch_size,
+                max_batch_wait,
+            }) => GrpcOperator::LookupJoin(GrpcApi::LookupJoin {
+                connector: Some(connector.into()),
+                join_type: match join_type {
+                    JoinType::Inner => GrpcApi::JoinType::Inner,
+                    JoinType::Left => GrpcApi::JoinType::Left,
+                    JoinType::Right => GrpcApi::JoinType::Right,
+                    JoinType::Full => GrpcApi::JoinType::Full,
+                }
+                .into(),
+                lookup_type,
+                merge,
+                cache_ttl_micros: cache_ttl.map(|ttl| ttl.as_micros() as u64),
+                max_cache_entries: max_cache_entries as u64,
+                max_batch_size: max_batch_size as u64,
+                max_batch_wait_micros: max_batch_wait.as_micros() as u64,
+            }),
+            Operator::IntervalJoin(IntervalJoin {
+                lower_bound_micros,
+                upper_bound_micros,
+            }) => GrpcOperator::IntervalJoin(GrpcApi::IntervalJoin {
+                lower_bound_micros,
+                upper_bound_micros,
+            }),
+            Operator::TemporalJoin { join_type } => {
+                GrpcOperator::TemporalJoin(GrpcApi::TemporalJoin {
+                    join_type: match join_type {
+                        JoinType::Inner => GrpcApi::JoinType::Inner,
+                        JoinType::Left => GrpcApi::JoinType::Left,
+                        JoinType::Right => GrpcApi::JoinType::Right,
+                        JoinType::Full => GrpcApi::JoinType::Full,
+                    }
+                    .into(),
+                })
+            }
+            Operator::MatchRecognize(MatchRecognize {
+                pattern,
+                within,
+                define,
+                measures,
+            }) => GrpcOperator::MatchRecognize(GrpcApi::MatchRecognize {
+                pattern: pattern
+                    .into_iter()
+                    .map(|element| GrpcApi::PatternElement {
+                        variable: element.variable as u64,
+                        min: element.min,
+                        max: element.max,
+                    })
+                    .collect(),
+                within_micros: within.map(|within| within.as_micros() as u64),
+                define,
+                measures,
+            }),
         }
     }
 }
@@ -2270,9 +2886,16 @@ impl From<WindowType> for GrpcApi::window::Window {
             WindowType::Instant => {
                 GrpcApi::window::Window::InstantWindow(GrpcApi::InstantWindow {})
             }
-            WindowType::Session { gap } => {
+            WindowType::Session { gap, max_length } => {
                 GrpcApi::window::Window::SessionWindow(GrpcApi::SessionWindow {
                     gap_micros: gap.as_micros() as u64,
+                    max_length_micros: max_length.map(|max_length| max_length.as_micros() as u64),
+                })
+            }
+            WindowType::Cumulating { step, max_size } => {
+                GrpcApi::window::Window::CumulatingWindow(GrpcApi::CumulatingWindow {
+                    step_micros: step.as_micros() as u64,
+                    max_size_micros: max_size.as_micros() as u64,
                 })
             }
         }
@@ -2445,6 +3068,36 @@ impl TryFrom<arroyo_rpc::grpc::api::Operator> for Operator {
                     bin_merger,
                     bin_type,
                 }),
+                GrpcOperator::SessionWindowAggregator(GrpcApi::SessionWindowAggregator {
+                    gap_micros,
+                    max_length_micros,
+                    aggregator,
+                    bin_merger,
+                    bin_combiner,
+                    bin_type,
+                }) => Operator::SessionWindowAggregator(SessionWindowAggregator {
+                    gap: Duration::from_micros(gap_micros),
+                    max_length: max_length_micros.map(Duration::from_micros),
+                    aggregator,
+                    bin_merger,
+                    bin_combiner,
+                    bin_type,
+                }),
+                GrpcOperator::CumulatingWindowAggregator(GrpcApi::CumulatingWindowAggregator {
+                    step_micros,
+                    max_size_micros,
+                    aggregator,
+                    bin_merger,
+                    bin_combiner,
+                    bin_type,
+                }) => Operator::CumulatingWindowAggregator(CumulatingWindowAggregator {
+                    step: Duration::from_micros(step_micros),
+                    max_size: Duration::from_micros(max_size_micros),
+                    aggregator,
+                    bin_merger,
+                    bin_combiner,
+                    bin_type,
+                }),
                 GrpcOperator::TumblingTopN(GrpcApi::TumblingTopN {
                     width_micros,
                     max_elements,
@@ -2518,6 +3171,63 @@ impl TryFrom<arroyo_rpc::grpc::api::Operator> for Operator {
                     name,
                     expression,
                 }) => Operator::UpdatingKeyOperator { name, expression },
+                GrpcOperator::LookupJoin(GrpcApi::LookupJoin {
+                    connector,
+                    join_type,
+                    lookup_type,
+                    merge,
+                    cache_ttl_micros,
+                    max_cache_entries,
+                    max_batch_size,
+                    max_batch_wait_micros,
+                }) => Operator::LookupJoin(LookupJoin {
+                    connector: connector
+                        .ok_or_else(|| anyhow!("lookup join is missing its connector"))?
+                        .into(),
+                    join_type: match GrpcApi::JoinType::from_i32(join_type) {
+                        Some(GrpcApi::JoinType::Left) => JoinType::Left,
+                        _ => JoinType::Inner,
+                    },
+                    lookup_type,
+                    merge,
+                    cache_ttl: cache_ttl_micros.map(Duration::from_micros),
+                    max_cache_entries: max_cache_entries as usize,
+                    max_batch_size: max_batch_size as usize,
+                    max_batch_wait: Duration::from_micros(max_batch_wait_micros),
+                }),
+                GrpcOperator::IntervalJoin(GrpcApi::IntervalJoin {
+                    lower_bound_micros,
+                    upper_bound_micros,
+                }) => Operator::IntervalJoin(IntervalJoin {
+                    lower_bound_micros,
+                    upper_bound_micros,
+                }),
+                GrpcOperator::TemporalJoin(GrpcApi::TemporalJoin { join_type }) => {
+                    Operator::TemporalJoin {
+                        join_type: match GrpcApi::JoinType::from_i32(join_type) {
+                            Some(GrpcApi::JoinType::Left) => JoinType::Left,
+                            _ => JoinType::Inner,
+                        },
+                    }
+                }
+                GrpcOperator::MatchRecognize(GrpcApi::MatchRecognize {
+                    pattern,
+                    within_micros,
+                    define,
+                    measures,
+                }) => Operator::MatchRecognize(MatchRecognize {
+                    pattern: pattern
+                        .into_iter()
+                        .map(|element| PatternElement {
+                            variable: element.variable as usize,
+                            min: element.min,
+                            max: element.max,
+                        })
+                        .collect(),
+                    within: within_micros.map(Duration::from_micros),
+                    define,
+                    measures,
+                }),
             },
             None => bail!("unset on operator {:?}", operator),
         };
@@ -2571,6 +3281,13 @@ impl From<arroyo_rpc::grpc::api::Window> for WindowType {
             Some(arroyo_rpc::grpc::api::window::Window::SessionWindow(session)) => {
                 WindowType::Session {
                     gap: Duration::from_micros(session.gap_micros),
+                    max_length: session.max_length_micros.map(Duration::from_micros),
+                }
+            }
+            Some(arroyo_rpc::grpc::api::window::Window::CumulatingWindow(cumulating)) => {
+                WindowType::Cumulating {
+                    step: Duration::from_micros(cumulating.step_micros),
+                    max_size: Duration::from_micros(cumulating.max_size_micros),
                 }
             }
             None => todo!(),
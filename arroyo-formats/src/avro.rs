@@ -70,27 +70,36 @@ pub async fn deserialize_slice_avro<'a, T: DeserializeOwned>(
     };
 
     let into_json = format.into_unstructured_json;
-    Ok(messages.into_iter().map(move |record| {
-        let value = record.map_err(|e| {
+    Ok(messages
+        .into_iter()
+        .map(move |record| from_avro_value(record, into_json)))
+}
+
+/// Converts a record read from Avro into the output type, either as its fields or, with
+/// `into_json`, as a single JSON `value` field.
+pub fn from_avro_value<T: DeserializeOwned>(
+    record: Result<AvroValue, apache_avro::Error>,
+    into_json: bool,
+) -> Result<T, UserError> {
+    let value = record.map_err(|e| {
+        UserError::new(
+            "Deserialization failed",
+            format!("Failed to deserialize from avro: {:?}", e),
+        )
+    })?;
+
+    if into_json {
+        Ok(serde_json::from_value(json!({"value": avro_to_json(value).to_string()})).unwrap())
+    } else {
+        // for now round-trip through json in order to handle unsupported avro features
+        // as that allows us to rely on raw json deserialization
+        serde_json::from_value(avro_to_json(value)).map_err(|e| {
             UserError::new(
                 "Deserialization failed",
-                format!("Failed to deserialize from avro: {:?}", e),
+                format!("Failed to convert avro message into struct type: {:?}", e),
             )
-        })?;
-
-        if into_json {
-            Ok(serde_json::from_value(json!({"value": avro_to_json(value).to_string()})).unwrap())
-        } else {
-            // for now round-trip through json in order to handle unsupported avro features
-            // as that allows us to rely on raw json deserialization
-            serde_json::from_value(avro_to_json(value)).map_err(|e| {
-                UserError::new(
-                    "Deserialization failed",
-                    format!("Failed to convert avro message into struct type: {:?}", e),
-                )
-            })
-        }
-    }))
+        })
+    }
 }
 
 pub fn to_vec<T: SchemaData>(
@@ -127,7 +136,7 @@ pub fn to_vec<T: SchemaData>(
     }
 }
 
-fn convert_float(f: f64) -> JsonValue {
+pub(crate) fn convert_float(f: f64) -> JsonValue {
     match serde_json::Number::from_f64(f) {
         Some(n) => JsonValue::Number(n),
         None => JsonValue::String(
@@ -227,9 +236,14 @@ fn arrow_to_avro(name: &str, dt: &DataType) -> serde_json::value::Value {
         DataType::Binary | DataType::FixedSizeBinary(_) | DataType::LargeBinary => "bytes",
         DataType::Utf8 | DataType::LargeUtf8 => "string",
         DataType::List(t) | DataType::FixedSizeList(t, _) | DataType::LargeList(t) => {
+            let mut items = arrow_to_avro(name, t.data_type());
+            if t.is_nullable() {
+                items = json!(["null", items]);
+            }
+
             return json!({
                 "type": "array",
-                "items": arrow_to_avro(name, t.data_type())
+                "items": items
             });
         }
         DataType::Struct(fields) => {
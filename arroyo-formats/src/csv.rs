@@ -0,0 +1,222 @@
+use arrow::datatypes::{DataType, Field, Fields};
+use arroyo_rpc::formats::CsvFormat;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Number, Value};
+
+/// Returns a reader for the CSV dialect of the format. Header rows are not expected, as they are
+/// only present in files; readers of files should enable them with `has_headers`.
+pub fn reader_builder(format: &CsvFormat) -> csv::ReaderBuilder {
+    let mut builder = csv::ReaderBuilder::new();
+    builder
+        .delimiter(format.delimiter as u8)
+        .quote(format.quote as u8)
+        .escape(format.escape.map(|c| c as u8))
+        .double_quote(format.escape.is_none())
+        .has_headers(false)
+        .flexible(true);
+    builder
+}
+
+pub fn deserialize_slice_csv<T: DeserializeOwned>(
+    format: &CsvFormat,
+    fields: &Fields,
+    msg: &[u8],
+) -> Result<T, String> {
+    let mut reader = reader_builder(format).from_reader(msg);
+    let mut record = csv::StringRecord::new();
+    if !reader
+        .read_record(&mut record)
+        .map_err(|e| format!("Failed to read CSV record: {}", e))?
+    {
+        return Err("message does not contain a CSV record".to_string());
+    }
+
+    deserialize_record(format, fields, None, &record)
+}
+
+/// Converts a CSV record into the output type. Columns are matched to fields by the names in the
+/// header row if there is one, and otherwise by their position.
+pub fn deserialize_record<T: DeserializeOwned>(
+    format: &CsvFormat,
+    fields: &Fields,
+    headers: Option<&csv::StringRecord>,
+    record: &csv::StringRecord,
+) -> Result<T, String> {
+    let mut object = Map::new();
+    match headers {
+        Some(headers) => {
+            for (name, value) in headers.iter().zip(record.iter()) {
+                if let Some((_, field)) = fields.find(name) {
+                    object.insert(name.to_string(), parse_value(format, field, value)?);
+                }
+            }
+        }
+        None => {
+            for (field, value) in fields.iter().zip(record.iter()) {
+                object.insert(field.name().clone(), parse_value(format, field, value)?);
+            }
+        }
+    }
+
+    serde_json::from_value(Value::Object(object))
+        .map_err(|e| format!("Failed to deserialize CSV into schema: {:?}", e))
+}
+
+fn parse_value(format: &CsvFormat, field: &Field, value: &str) -> Result<Value, String> {
+    let is_string = matches!(field.data_type(), DataType::Utf8 | DataType::LargeUtf8);
+    // an empty string is still a value for a required text field
+    if value == format.null_literal && (field.is_nullable() || !is_string) {
+        return Ok(Value::Null);
+    }
+
+    let invalid = || format!("invalid value '{}' for field '{}'", value, field.name());
+    Ok(match field.data_type() {
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Timestamp(_, _) => {
+            Value::String(value.to_string())
+        }
+        DataType::Boolean => Value::Bool(value.to_lowercase().parse().map_err(|_| invalid())?),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+            Value::from(value.trim().parse::<i64>().map_err(|_| invalid())?)
+        }
+        DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+            Value::from(value.trim().parse::<u64>().map_err(|_| invalid())?)
+        }
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => Value::Number(
+            value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .ok_or_else(invalid)?,
+        ),
+        // nested and other values are written as JSON
+        _ => serde_json::from_str(value).map_err(|_| invalid())?,
+    })
+}
+
+fn write_row<I, V>(format: &CsvFormat, row: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = V>,
+    V: AsRef<[u8]>,
+{
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(format.delimiter as u8)
+        .quote(format.quote as u8)
+        .escape(format.escape.unwrap_or(format.quote) as u8)
+        .double_quote(format.escape.is_none())
+        .terminator(csv::Terminator::Any(b'\n'))
+        .from_writer(Vec::with_capacity(128));
+    writer
+        .write_record(row)
+        .expect("writing to a vec cannot fail");
+
+    let mut buf = writer.into_inner().expect("writing to a vec cannot fail");
+    buf.pop();
+    buf
+}
+
+/// Serializes a record as a CSV row, without a trailing newline
+pub fn to_vec<T: Serialize>(format: &CsvFormat, fields: &Fields, record: &T) -> Vec<u8> {
+    let value = serde_json::to_value(record).unwrap();
+    write_row(
+        format,
+        fields.iter().map(|field| match value.get(field.name()) {
+            None | Some(Value::Null) => format.null_literal.clone(),
+            Some(Value::String(s)) => s.clone(),
+            Some(v) => v.to_string(),
+        }),
+    )
+}
+
+/// The header row naming the columns of the schema, without a trailing newline
+pub fn header(format: &CsvFormat, fields: &Fields) -> Vec<u8> {
+    write_row(format, fields.iter().map(|field| field.name()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::TimeUnit;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Row {
+        id: i64,
+        name: String,
+        score: Option<f64>,
+        active: bool,
+    }
+
+    fn fields() -> Fields {
+        vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("score", DataType::Float64, true),
+            Field::new("active", DataType::Boolean, false),
+        ]
+        .into()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let format = CsvFormat {
+            delimiter: '|',
+            null_literal: "NULL".to_string(),
+            ..Default::default()
+        };
+        let row = Row {
+            id: 5,
+            name: "a \"quoted\" | name".to_string(),
+            score: None,
+            active: true,
+        };
+
+        let bytes = to_vec(&format, &fields(), &row);
+        assert_eq!(
+            String::from_utf8(bytes.clone()).unwrap(),
+            "5|\"a \"\"quoted\"\" | name\"|NULL|true"
+        );
+        assert_eq!(
+            deserialize_slice_csv::<Row>(&format, &fields(), &bytes).unwrap(),
+            row
+        );
+        assert_eq!(header(&format, &fields()), b"id|name|score|active");
+    }
+
+    #[test]
+    fn test_escape_and_headers() {
+        let format = CsvFormat {
+            escape: Some('\\'),
+            header: true,
+            ..Default::default()
+        };
+        let mut reader = reader_builder(&format)
+            .has_headers(true)
+            .from_reader("active,id,extra,name,score\nTRUE,1,x,\"say \\\"hi\\\"\",\n".as_bytes());
+        let headers = reader.headers().unwrap().clone();
+        let record = reader.records().next().unwrap().unwrap();
+
+        assert_eq!(
+            deserialize_record::<Row>(&format, &fields(), Some(&headers), &record).unwrap(),
+            Row {
+                id: 1,
+                name: "say \"hi\"".to_string(),
+                score: None,
+                active: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let format = CsvFormat::default();
+        let err = deserialize_slice_csv::<Row>(&format, &fields(), b"one,a,,true").unwrap_err();
+        assert_eq!(err, "invalid value 'one' for field 'id'");
+
+        let field = Field::new("t", DataType::Timestamp(TimeUnit::Microsecond, None), false);
+        assert_eq!(
+            parse_value(&format, &field, "2023-10-05T12:00:00Z").unwrap(),
+            Value::String("2023-10-05T12:00:00Z".to_string())
+        );
+    }
+}
@@ -1,12 +1,13 @@
 extern crate core;
 
 use anyhow::bail;
-use arrow::datatypes::{DataType, Field, Schema};
+use arrow::datatypes::{DataType, Field, Fields, Schema};
 use arrow_array::cast::AsArray;
 use arrow_array::{RecordBatch, StringArray};
 use arroyo_rpc::formats::{AvroFormat, Format, Framing, FramingMethod};
 use arroyo_rpc::schema_resolver::{FailingSchemaResolver, FixedSchemaResolver, SchemaResolver};
 use arroyo_types::{Data, Debezium, RawJson, UserError};
+use prost_reflect::MessageDescriptor;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::{json, Value};
@@ -16,7 +17,12 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 pub mod avro;
+pub mod csv;
 pub mod json;
+pub mod metadata;
+pub mod protobuf;
+
+pub use metadata::{FromMetadata, Metadata};
 
 pub trait SchemaData: Data + Serialize + DeserializeOwned {
     fn name() -> &'static str;
@@ -36,6 +42,12 @@ pub trait SchemaData: Data + Serialize + DeserializeOwned {
     fn to_raw_string(&self) -> Option<Vec<u8>>;
 
     fn to_avro(&self, schema: &apache_avro::Schema) -> apache_avro::types::Value;
+
+    /// Sets a field that's read from the metadata of a message rather than its payload, like the
+    /// key of a Kafka message
+    fn set_metadata(&mut self, field: &str, _value: Metadata) -> Result<(), String> {
+        Err(format!("{} has no metadata field {}", Self::name(), field))
+    }
 }
 
 fn get_subschema<'a>(schema: &'a apache_avro::Schema, field: &str) -> &'a apache_avro::Schema {
@@ -254,6 +266,8 @@ pub struct DataDeserializer<T: SchemaData> {
     framing: Option<Arc<Framing>>,
     schema_registry: Arc<Mutex<HashMap<u32, apache_avro::schema::Schema>>>,
     schema_resolver: Arc<dyn SchemaResolver + Sync>,
+    proto_descriptor: Option<Result<MessageDescriptor, String>>,
+    csv_fields: Option<Fields>,
     _t: PhantomData<T>,
 }
 
@@ -278,11 +292,27 @@ impl<T: SchemaData> DataDeserializer<T> {
         framing: Option<Framing>,
         schema_resolver: Arc<dyn SchemaResolver + Sync>,
     ) -> Self {
+        // the schema is validated when the connection is created, so an error here is reported
+        // for each message rather than failing the operator
+        let proto_descriptor = match &format {
+            Format::Protobuf(proto) => {
+                Some(protobuf::message_for_format(proto).map_err(|e| e.to_string()))
+            }
+            _ => None,
+        };
+
+        let csv_fields = match &format {
+            Format::Csv(_) => Some(T::schema().fields().clone()),
+            _ => None,
+        };
+
         Self {
             format: Arc::new(format),
             framing: framing.map(|f| Arc::new(f)),
             schema_registry: Arc::new(Mutex::new(HashMap::new())),
             schema_resolver,
+            proto_descriptor,
+            csv_fields,
             _t: PhantomData,
         }
     }
@@ -323,8 +353,23 @@ impl<T: SchemaData> DataDeserializer<T> {
         match &*self.format {
             Format::Json(json) => json::deserialize_slice_json(json, msg),
             Format::Avro(_) => unreachable!("avro should be handled by here"),
+            Format::Protobuf(proto) => match self
+                .proto_descriptor
+                .as_ref()
+                .expect("protobuf descriptor should be set for protobuf format")
+            {
+                Ok(descriptor) => protobuf::deserialize_slice_proto(proto, descriptor, msg),
+                Err(e) => Err(e.clone()),
+            },
             Format::Parquet(_) => todo!("parquet is not supported as an input format"),
             Format::RawString(_) => deserialize_raw_string(msg),
+            Format::Csv(format) => csv::deserialize_slice_csv(
+                format,
+                self.csv_fields
+                    .as_ref()
+                    .expect("fields should be set for csv format"),
+                msg,
+            ),
         }
         .map_err(|e| {
             UserError::new(
@@ -344,7 +389,9 @@ pub struct DataSerializer<T: SchemaData> {
     #[allow(unused)]
     json_schema: Value,
     avro_schema: apache_avro::schema::Schema,
+    proto_descriptor: Option<Result<MessageDescriptor, String>>,
     schema_id: Option<u32>,
+    fields: Fields,
     format: Format,
     _t: PhantomData<T>,
 }
@@ -355,17 +402,26 @@ impl<T: SchemaData> DataSerializer<T> {
             kafka_schema: json::arrow_to_kafka_json(T::name(), T::schema().fields()),
             json_schema: json::arrow_to_json_schema(T::schema().fields()),
             avro_schema: avro::arrow_to_avro_schema(T::name(), T::schema().fields()),
+            proto_descriptor: match &format {
+                Format::Protobuf(proto) => {
+                    Some(protobuf::message_for_format(proto).map_err(|e| e.to_string()))
+                }
+                _ => None,
+            },
             schema_id: match &format {
                 Format::Avro(avro) => avro.schema_id,
                 _ => None,
             },
+            fields: T::schema().fields().clone(),
             format,
             _t: PhantomData,
         }
     }
 
-    pub fn to_vec(&self, record: &T) -> Option<Vec<u8>> {
-        match &self.format {
+    /// Serializes a record in the sink's format; returns `Ok(None)` for records that produce no
+    /// output, like null raw strings
+    pub fn to_vec(&self, record: &T) -> Result<Option<Vec<u8>>, UserError> {
+        Ok(match &self.format {
             Format::Json(json) => {
                 let mut writer: Vec<u8> = Vec::with_capacity(128);
                 if json.confluent_schema_registry {
@@ -388,9 +444,22 @@ impl<T: SchemaData> DataSerializer<T> {
                 Some(writer)
             }
             Format::Avro(f) => Some(avro::to_vec(record, f, &self.avro_schema, self.schema_id)),
+            Format::Protobuf(f) => {
+                let descriptor = self
+                    .proto_descriptor
+                    .as_ref()
+                    .expect("protobuf descriptor should be set for protobuf format")
+                    .as_ref()
+                    .map_err(|e| UserError::new("Serialization failed", e.clone()))?;
+                Some(
+                    protobuf::to_vec(record, f, descriptor)
+                        .map_err(|e| UserError::new("Serialization failed", e))?,
+                )
+            }
             Format::Parquet(_) => todo!(),
             Format::RawString(_) => record.to_raw_string(),
-        }
+            Format::Csv(f) => Some(csv::to_vec(f, &self.fields, record)),
+        })
     }
 }
 
@@ -0,0 +1,126 @@
+use std::time::SystemTime;
+
+/// A value from the metadata of a message that a source can expose as a field, like the key or
+/// partition of a Kafka message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Metadata {
+    Bytes(Option<Vec<u8>>),
+    Text(Option<String>),
+    Int(i64),
+    Timestamp(SystemTime),
+}
+
+impl Metadata {
+    fn is_null(&self) -> bool {
+        matches!(self, Metadata::Bytes(None) | Metadata::Text(None))
+    }
+}
+
+/// Types that fields populated from message metadata can have.
+pub trait FromMetadata: Sized {
+    fn from_metadata(value: Metadata) -> Result<Self, String>;
+
+    /// The value of the field until the source sets it, as it isn't part of the payload.
+    fn placeholder() -> Self;
+}
+
+impl FromMetadata for Vec<u8> {
+    fn from_metadata(value: Metadata) -> Result<Self, String> {
+        match value {
+            Metadata::Bytes(Some(bytes)) => Ok(bytes),
+            Metadata::Text(Some(text)) => Ok(text.into_bytes()),
+            value => Err(format!("can't read {:?} as bytes", value)),
+        }
+    }
+
+    fn placeholder() -> Self {
+        vec![]
+    }
+}
+
+impl FromMetadata for String {
+    fn from_metadata(value: Metadata) -> Result<Self, String> {
+        match value {
+            Metadata::Text(Some(text)) => Ok(text),
+            Metadata::Bytes(Some(bytes)) => Ok(String::from_utf8_lossy(&bytes).to_string()),
+            value => Err(format!("can't read {:?} as text", value)),
+        }
+    }
+
+    fn placeholder() -> Self {
+        String::new()
+    }
+}
+
+impl FromMetadata for i64 {
+    fn from_metadata(value: Metadata) -> Result<Self, String> {
+        match value {
+            Metadata::Int(i) => Ok(i),
+            value => Err(format!("can't read {:?} as an integer", value)),
+        }
+    }
+
+    fn placeholder() -> Self {
+        0
+    }
+}
+
+impl FromMetadata for i32 {
+    fn from_metadata(value: Metadata) -> Result<Self, String> {
+        let i = i64::from_metadata(value)?;
+        i32::try_from(i).map_err(|_| format!("{} is too large for an INT field", i))
+    }
+
+    fn placeholder() -> Self {
+        0
+    }
+}
+
+impl FromMetadata for SystemTime {
+    fn from_metadata(value: Metadata) -> Result<Self, String> {
+        match value {
+            Metadata::Timestamp(t) => Ok(t),
+            value => Err(format!("can't read {:?} as a timestamp", value)),
+        }
+    }
+
+    fn placeholder() -> Self {
+        SystemTime::UNIX_EPOCH
+    }
+}
+
+impl<T: FromMetadata> FromMetadata for Option<T> {
+    fn from_metadata(value: Metadata) -> Result<Self, String> {
+        if value.is_null() {
+            Ok(None)
+        } else {
+            T::from_metadata(value).map(Some)
+        }
+    }
+
+    fn placeholder() -> Self {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_metadata() {
+        assert_eq!(
+            Some("key".to_string()),
+            Option::<String>::from_metadata(Metadata::Bytes(Some(b"key".to_vec()))).unwrap()
+        );
+        assert_eq!(
+            None,
+            Option::<Vec<u8>>::from_metadata(Metadata::Bytes(None)).unwrap()
+        );
+        assert_eq!(3, i32::from_metadata(Metadata::Int(3)).unwrap());
+
+        assert!(Vec::<u8>::from_metadata(Metadata::Bytes(None)).is_err());
+        assert!(i32::from_metadata(Metadata::Int(i64::MAX)).is_err());
+        assert!(SystemTime::from_metadata(Metadata::Int(0)).is_err());
+    }
+}
@@ -0,0 +1,640 @@
+use crate::avro::convert_float;
+use anyhow::{anyhow, bail};
+use arroyo_rpc::formats::ProtobufFormat;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use prost::Message;
+use prost_reflect::{
+    DescriptorPool, DynamicMessage, FieldDescriptor, Kind, MapKey, MessageDescriptor,
+    ReflectMessage, Value,
+};
+use protox::file::{ChainFileResolver, File, FileResolver, GoogleFileResolver};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use std::collections::HashMap;
+
+/// The name under which the user-provided schema is compiled; imports other than the
+/// google/protobuf well-known types are not supported
+pub const SCHEMA_FILE_NAME: &str = "arroyo_schema.proto";
+
+pub const TIMESTAMP_MESSAGE: &str = "google.protobuf.Timestamp";
+
+struct SchemaFileResolver {
+    schema: String,
+}
+
+impl FileResolver for SchemaFileResolver {
+    fn open_file(&self, name: &str) -> Result<File, protox::Error> {
+        if name == SCHEMA_FILE_NAME {
+            File::from_source(name, &self.schema)
+        } else {
+            Err(protox::Error::file_not_found(name))
+        }
+    }
+}
+
+pub fn compile_schema(schema: &str) -> anyhow::Result<DescriptorPool> {
+    let mut resolver = ChainFileResolver::new();
+    resolver.add(SchemaFileResolver {
+        schema: schema.to_string(),
+    });
+    resolver.add(GoogleFileResolver::new());
+
+    let mut compiler = protox::Compiler::with_file_resolver(resolver);
+    compiler
+        .open_file(SCHEMA_FILE_NAME)
+        .map_err(|e| anyhow!("protobuf schema is not valid: {}", e))?;
+
+    Ok(compiler.descriptor_pool())
+}
+
+/// Finds the message that records are read from and written to. If no message name is
+/// configured, the first message defined in the schema is used.
+pub fn root_message(
+    pool: &DescriptorPool,
+    message_name: Option<&str>,
+) -> anyhow::Result<MessageDescriptor> {
+    let file = pool
+        .get_file_by_name(SCHEMA_FILE_NAME)
+        .ok_or_else(|| anyhow!("protobuf schema was not compiled"))?;
+
+    match message_name {
+        Some(name) => pool
+            .get_message_by_name(name)
+            .or_else(|| {
+                // also allow the name to be specified without its package
+                pool.get_message_by_name(&format!("{}.{}", file.package_name(), name))
+            })
+            .ok_or_else(|| anyhow!("message '{}' not found in protobuf schema", name)),
+        None => {
+            let Some(message) = file.messages().next() else {
+                bail!("protobuf schema does not define any messages");
+            };
+            Ok(message)
+        }
+    }
+}
+
+pub fn message_for_format(format: &ProtobufFormat) -> anyhow::Result<MessageDescriptor> {
+    let schema = format
+        .schema_def
+        .as_ref()
+        .ok_or_else(|| anyhow!("protobuf format requires a protobuf schema"))?;
+
+    root_message(&compile_schema(schema)?, format.message_name.as_deref())
+}
+
+pub fn deserialize_slice_proto<T: DeserializeOwned>(
+    format: &ProtobufFormat,
+    descriptor: &MessageDescriptor,
+    mut msg: &[u8],
+) -> Result<T, String> {
+    let descriptor = if format.confluent_schema_registry {
+        if msg.len() < 5 || msg[0] != 0 {
+            return Err(format!(
+                "data was not encoded with schema registry wire format; magic byte has unexpected value: {:?}",
+                msg.get(0)
+            ));
+        }
+
+        msg = &msg[5..];
+        let indices = read_message_indices(&mut msg)?;
+        resolve_message(descriptor, &indices)?
+    } else {
+        descriptor.clone()
+    };
+
+    let message = DynamicMessage::decode(descriptor, msg)
+        .map_err(|e| format!("Failed to deserialize protobuf message: {:?}", e))?;
+
+    let value = proto_to_json(&message);
+
+    if format.into_unstructured_json {
+        Ok(serde_json::from_value(json!({"value": value.to_string()})).unwrap())
+    } else {
+        serde_json::from_value(value).map_err(|e| {
+            format!(
+                "Failed to convert protobuf message into struct type: {:?}",
+                e
+            )
+        })
+    }
+}
+
+pub fn to_vec<T: Serialize>(
+    record: &T,
+    format: &ProtobufFormat,
+    descriptor: &MessageDescriptor,
+) -> Result<Vec<u8>, String> {
+    let value = serde_json::to_value(record).unwrap();
+    let message = json_to_proto(descriptor, &value)
+        .map_err(|e| format!("Failed to convert record into protobuf message: {}", e))?;
+
+    let mut buf = Vec::with_capacity(message.encoded_len() + 6);
+    if format.confluent_schema_registry {
+        buf.push(0);
+        let schema_id = format
+            .schema_id
+            .ok_or_else(|| "no schema id for confluent schema registry protobuf".to_string())?;
+        buf.extend(schema_id.to_be_bytes());
+        write_message_indices(descriptor, &mut buf);
+    }
+
+    message
+        .encode(&mut buf)
+        .map_err(|e| format!("Failed to encode protobuf message: {}", e))?;
+    Ok(buf)
+}
+
+// The confluent wire format follows the schema id with the path to the message within the schema,
+// encoded as an array of zig-zag varints; the common case of the first message is encoded as a
+// single 0 byte.
+fn read_varint(buf: &mut &[u8]) -> Result<i64, String> {
+    let mut result: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let Some((b, rest)) = buf.split_first() else {
+            return Err("message index in schema registry header is truncated".to_string());
+        };
+        *buf = rest;
+
+        result |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok(((result >> 1) as i64) ^ -((result & 1) as i64));
+        }
+    }
+
+    Err("message index in schema registry header is not a valid varint".to_string())
+}
+
+fn write_varint(buf: &mut Vec<u8>, v: i64) {
+    let mut v = ((v << 1) ^ (v >> 63)) as u64;
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn read_message_indices(buf: &mut &[u8]) -> Result<Vec<i64>, String> {
+    let count = read_varint(buf)?;
+    if count == 0 {
+        return Ok(vec![0]);
+    }
+
+    (0..count).map(|_| read_varint(buf)).collect()
+}
+
+fn message_indices(descriptor: &MessageDescriptor) -> Vec<i64> {
+    let mut indices = vec![];
+    let mut current = descriptor.clone();
+    loop {
+        match current.parent_message() {
+            Some(parent) => {
+                indices.push(
+                    parent
+                        .child_messages()
+                        .position(|m| m.full_name() == current.full_name())
+                        .unwrap() as i64,
+                );
+                current = parent;
+            }
+            None => {
+                indices.push(
+                    current
+                        .parent_file()
+                        .messages()
+                        .position(|m| m.full_name() == current.full_name())
+                        .unwrap() as i64,
+                );
+                break;
+            }
+        }
+    }
+
+    indices.reverse();
+    indices
+}
+
+fn write_message_indices(descriptor: &MessageDescriptor, buf: &mut Vec<u8>) {
+    let indices = message_indices(descriptor);
+    if indices == [0] {
+        buf.push(0);
+    } else {
+        write_varint(buf, indices.len() as i64);
+        for i in indices {
+            write_varint(buf, i);
+        }
+    }
+}
+
+fn resolve_message(
+    descriptor: &MessageDescriptor,
+    indices: &[i64],
+) -> Result<MessageDescriptor, String> {
+    if indices == message_indices(descriptor) {
+        return Ok(descriptor.clone());
+    }
+
+    let not_found = || {
+        format!(
+            "message with index {:?} not found in protobuf schema",
+            indices
+        )
+    };
+
+    let mut indices = indices.iter().map(|i| *i as usize);
+    let mut message = descriptor
+        .parent_file()
+        .messages()
+        .nth(indices.next().ok_or_else(not_found)?)
+        .ok_or_else(not_found)?;
+
+    for i in indices {
+        let child = message.child_messages().nth(i).ok_or_else(not_found)?;
+        message = child;
+    }
+
+    Ok(message)
+}
+
+fn map_key_to_string(key: &MapKey) -> String {
+    match key {
+        MapKey::Bool(b) => b.to_string(),
+        MapKey::I32(i) => i.to_string(),
+        MapKey::I64(i) => i.to_string(),
+        MapKey::U32(i) => i.to_string(),
+        MapKey::U64(i) => i.to_string(),
+        MapKey::String(s) => s.clone(),
+    }
+}
+
+fn timestamp_to_json(message: &DynamicMessage) -> JsonValue {
+    let seconds = message
+        .get_field_by_name("seconds")
+        .and_then(|v| v.as_i64())
+        .unwrap_or_default();
+    let nanos = message
+        .get_field_by_name("nanos")
+        .and_then(|v| v.as_i32())
+        .unwrap_or_default();
+
+    match NaiveDateTime::from_timestamp_opt(seconds, nanos as u32) {
+        Some(dt) => {
+            JsonValue::String(DateTime::<Utc>::from_naive_utc_and_offset(dt, Utc).to_rfc3339())
+        }
+        None => JsonValue::Null,
+    }
+}
+
+fn proto_to_json(message: &DynamicMessage) -> JsonValue {
+    if message.descriptor().full_name() == TIMESTAMP_MESSAGE {
+        return timestamp_to_json(message);
+    }
+
+    JsonValue::Object(
+        message
+            .descriptor()
+            .fields()
+            .map(|field| {
+                let value = if field.supports_presence() && !message.has_field(&field) {
+                    JsonValue::Null
+                } else {
+                    value_to_json(&field.kind(), &message.get_field(&field))
+                };
+
+                (field.name().to_string(), value)
+            })
+            .collect(),
+    )
+}
+
+fn value_to_json(kind: &Kind, value: &Value) -> JsonValue {
+    match value {
+        Value::Bool(b) => JsonValue::Bool(*b),
+        Value::I32(i) => JsonValue::Number((*i).into()),
+        Value::I64(i) => JsonValue::Number((*i).into()),
+        Value::U32(i) => JsonValue::Number((*i).into()),
+        Value::U64(i) => JsonValue::Number((*i).into()),
+        Value::F32(f) => convert_float(*f as f64),
+        Value::F64(f) => convert_float(*f),
+        Value::String(s) => JsonValue::String(s.clone()),
+        Value::Bytes(b) => {
+            JsonValue::Array(b.iter().map(|b| JsonValue::Number((*b).into())).collect())
+        }
+        Value::EnumNumber(n) => JsonValue::String(
+            kind.as_enum()
+                .and_then(|e| e.get_value(*n))
+                .map(|v| v.name().to_string())
+                .unwrap_or_else(|| n.to_string()),
+        ),
+        Value::Message(m) => proto_to_json(m),
+        Value::List(values) => {
+            JsonValue::Array(values.iter().map(|v| value_to_json(kind, v)).collect())
+        }
+        Value::Map(entries) => {
+            let value_kind = kind
+                .as_message()
+                .expect("map fields must have a map entry type")
+                .map_entry_value_field()
+                .kind();
+
+            JsonValue::Object(
+                entries
+                    .iter()
+                    .map(|(k, v)| (map_key_to_string(k), value_to_json(&value_kind, v)))
+                    .collect(),
+            )
+        }
+    }
+}
+
+fn json_to_timestamp(
+    descriptor: &MessageDescriptor,
+    value: &JsonValue,
+) -> Result<DynamicMessage, String> {
+    let JsonValue::String(s) = value else {
+        return Err(format!("expected an RFC 3339 timestamp, found {}", value));
+    };
+
+    let dt =
+        DateTime::parse_from_rfc3339(s).map_err(|e| format!("invalid timestamp '{}': {}", s, e))?;
+
+    let mut message = DynamicMessage::new(descriptor.clone());
+    message.set_field_by_name("seconds", Value::I64(dt.timestamp()));
+    message.set_field_by_name("nanos", Value::I32(dt.timestamp_subsec_nanos() as i32));
+    Ok(message)
+}
+
+fn json_to_proto(
+    descriptor: &MessageDescriptor,
+    value: &JsonValue,
+) -> Result<DynamicMessage, String> {
+    if descriptor.full_name() == TIMESTAMP_MESSAGE {
+        return json_to_timestamp(descriptor, value);
+    }
+
+    let JsonValue::Object(fields) = value else {
+        return Err(format!(
+            "expected an object for message {}, found {}",
+            descriptor.full_name(),
+            value
+        ));
+    };
+
+    let mut message = DynamicMessage::new(descriptor.clone());
+    for field in descriptor.fields() {
+        match fields.get(field.name()) {
+            None | Some(JsonValue::Null) => {}
+            Some(v) => {
+                message.set_field(&field, field_from_json(&field, v)?);
+            }
+        }
+    }
+
+    Ok(message)
+}
+
+fn field_from_json(field: &FieldDescriptor, value: &JsonValue) -> Result<Value, String> {
+    // repeated and map fields are represented as json-encoded strings in SQL
+    if field.is_list() || field.is_map() {
+        if let JsonValue::String(s) = value {
+            let parsed: JsonValue = serde_json::from_str(s)
+                .map_err(|e| format!("field '{}' is not valid json: {}", field.name(), e))?;
+            return field_from_json(field, &parsed);
+        }
+    }
+
+    if field.is_list() {
+        let JsonValue::Array(values) = value else {
+            return Err(format!("expected an array for field '{}'", field.name()));
+        };
+
+        return Ok(Value::List(
+            values
+                .iter()
+                .map(|v| value_from_json(&field.kind(), v))
+                .collect::<Result<_, _>>()?,
+        ));
+    }
+
+    if field.is_map() {
+        let JsonValue::Object(entries) = value else {
+            return Err(format!("expected an object for field '{}'", field.name()));
+        };
+
+        let entry = field.kind();
+        let entry = entry.as_message().unwrap();
+        let key_kind = entry.map_entry_key_field().kind();
+        let value_kind = entry.map_entry_value_field().kind();
+
+        let map: Result<HashMap<_, _>, String> = entries
+            .iter()
+            .map(|(k, v)| {
+                Ok((
+                    map_key_from_string(&key_kind, k)?,
+                    value_from_json(&value_kind, v)?,
+                ))
+            })
+            .collect();
+
+        return Ok(Value::Map(map?));
+    }
+
+    value_from_json(&field.kind(), value)
+        .map_err(|e| format!("invalid value for field '{}': {}", field.name(), e))
+}
+
+fn map_key_from_string(kind: &Kind, key: &str) -> Result<MapKey, String> {
+    let invalid = || format!("invalid map key '{}' for key type {:?}", key, kind);
+    Ok(match kind {
+        Kind::Bool => MapKey::Bool(key.parse().map_err(|_| invalid())?),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => {
+            MapKey::I32(key.parse().map_err(|_| invalid())?)
+        }
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => {
+            MapKey::I64(key.parse().map_err(|_| invalid())?)
+        }
+        Kind::Uint32 | Kind::Fixed32 => MapKey::U32(key.parse().map_err(|_| invalid())?),
+        Kind::Uint64 | Kind::Fixed64 => MapKey::U64(key.parse().map_err(|_| invalid())?),
+        Kind::String => MapKey::String(key.to_string()),
+        _ => return Err(invalid()),
+    })
+}
+
+fn value_from_json(kind: &Kind, value: &JsonValue) -> Result<Value, String> {
+    let mismatch = || format!("expected a value of type {:?}, found {}", kind, value);
+
+    Ok(match kind {
+        Kind::Double => Value::F64(value.as_f64().ok_or_else(mismatch)?),
+        Kind::Float => Value::F32(value.as_f64().ok_or_else(mismatch)? as f32),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => {
+            Value::I32(value.as_i64().ok_or_else(mismatch)? as i32)
+        }
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => {
+            Value::I64(value.as_i64().ok_or_else(mismatch)?)
+        }
+        Kind::Uint32 | Kind::Fixed32 => Value::U32(value.as_u64().ok_or_else(mismatch)? as u32),
+        Kind::Uint64 | Kind::Fixed64 => Value::U64(value.as_u64().ok_or_else(mismatch)?),
+        Kind::Bool => Value::Bool(value.as_bool().ok_or_else(mismatch)?),
+        Kind::String => Value::String(value.as_str().ok_or_else(mismatch)?.to_string()),
+        Kind::Bytes => match value {
+            JsonValue::Array(bytes) => Value::Bytes(
+                bytes
+                    .iter()
+                    .map(|b| b.as_u64().map(|b| b as u8).ok_or_else(mismatch))
+                    .collect::<Result<Vec<u8>, _>>()?
+                    .into(),
+            ),
+            JsonValue::String(s) => Value::Bytes(s.as_bytes().to_vec().into()),
+            _ => return Err(mismatch()),
+        },
+        Kind::Enum(e) => match value {
+            JsonValue::String(s) => Value::EnumNumber(
+                e.get_value_by_name(s)
+                    .map(|v| v.number())
+                    .or_else(|| s.parse().ok())
+                    .ok_or_else(|| format!("'{}' is not a value of enum {}", s, e.full_name()))?,
+            ),
+            JsonValue::Number(n) => Value::EnumNumber(n.as_i64().ok_or_else(mismatch)? as i32),
+            _ => return Err(mismatch()),
+        },
+        Kind::Message(m) => Value::Message(json_to_proto(m, value)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        compile_schema, deserialize_slice_proto, message_indices, read_message_indices,
+        root_message, to_vec,
+    };
+    use arroyo_rpc::formats::ProtobufFormat;
+    use serde::{Deserialize, Serialize};
+    use std::time::SystemTime;
+
+    const SCHEMA: &str = r#"
+        syntax = "proto3";
+        package test.orders;
+
+        import "google/protobuf/timestamp.proto";
+
+        enum Status {
+            PENDING = 0;
+            SHIPPED = 1;
+        }
+
+        message Order {
+            message Address {
+                string city = 1;
+                optional int32 zipcode = 2;
+            }
+
+            int64 order_id = 1;
+            string item = 2;
+            double price = 3;
+            Status status = 4;
+            Address address = 5;
+            repeated string tags = 6;
+            google.protobuf.Timestamp created_at = 7;
+            optional bytes payload = 8;
+        }
+    "#;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Address {
+        city: String,
+        zipcode: Option<i32>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Order {
+        order_id: i64,
+        item: String,
+        price: f64,
+        status: String,
+        address: Option<Address>,
+        #[serde(deserialize_with = "crate::deserialize_raw_json")]
+        tags: String,
+        #[serde(default)]
+        #[serde(with = "crate::json::opt_timestamp_as_rfc3339")]
+        created_at: Option<SystemTime>,
+        payload: Option<Vec<u8>>,
+    }
+
+    fn order() -> Order {
+        Order {
+            order_id: 4,
+            item: "pizza".to_string(),
+            price: 12.5,
+            status: "SHIPPED".to_string(),
+            address: Some(Address {
+                city: "Oakland".to_string(),
+                zipcode: None,
+            }),
+            tags: "[\"hot\",\"large\"]".to_string(),
+            created_at: Some(arroyo_types::from_millis(1_700_000_000_123)),
+            payload: Some(vec![1, 2, 3]),
+        }
+    }
+
+    fn format(confluent_schema_registry: bool) -> ProtobufFormat {
+        let mut format = ProtobufFormat::new(confluent_schema_registry, false, None);
+        format.add_schema_def(SCHEMA);
+        format.schema_id = Some(7);
+        format
+    }
+
+    #[test]
+    fn test_root_message() {
+        let pool = compile_schema(SCHEMA).unwrap();
+        assert_eq!(
+            root_message(&pool, None).unwrap().full_name(),
+            "test.orders.Order"
+        );
+        assert_eq!(
+            root_message(&pool, Some("Order.Address"))
+                .unwrap()
+                .full_name(),
+            "test.orders.Order.Address"
+        );
+        assert!(root_message(&pool, Some("Missing")).is_err());
+        assert!(compile_schema("message {").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let format = format(false);
+        let descriptor = super::message_for_format(&format).unwrap();
+
+        let bytes = to_vec(&order(), &format, &descriptor).unwrap();
+        let result: Order = deserialize_slice_proto(&format, &descriptor, &bytes).unwrap();
+
+        assert_eq!(order(), result);
+    }
+
+    #[test]
+    fn test_confluent_round_trip() {
+        let format = format(true);
+        let descriptor = super::message_for_format(&format).unwrap();
+
+        let bytes = to_vec(&order(), &format, &descriptor).unwrap();
+        assert_eq!(&bytes[0..6], &[0, 0, 0, 0, 7, 0]);
+
+        let result: Order = deserialize_slice_proto(&format, &descriptor, &bytes).unwrap();
+        assert_eq!(order(), result);
+    }
+
+    #[test]
+    fn test_message_indices() {
+        let pool = compile_schema(SCHEMA).unwrap();
+        let address = root_message(&pool, Some("test.orders.Order.Address")).unwrap();
+        assert_eq!(message_indices(&address), vec![0, 0]);
+
+        let mut buf = vec![];
+        super::write_message_indices(&address, &mut buf);
+        assert_eq!(buf, vec![4, 0, 0]);
+        assert_eq!(read_message_indices(&mut &buf[..]).unwrap(), vec![0, 0]);
+
+        assert_eq!(read_message_indices(&mut &[0u8][..]).unwrap(), vec![0]);
+    }
+}
@@ -720,7 +720,10 @@ fn impl_stream_node_type(
             crate::process_fn::ProcessFnUtils::send_checkpoint_event(checkpoint_barrier, ctx, arroyo_rpc::grpc::TaskCheckpointEventType::FinishedOperatorSetup).await;
 
             let watermark = ctx.watermarks.last_present_watermark();
-            ctx.state.checkpoint(checkpoint_barrier, watermark).await;
+            if let Err(e) = ctx.state.checkpoint(checkpoint_barrier, watermark).await {
+                panic!("failed to checkpoint the state of operator {} for epoch {}: {:?}",
+                    ctx.task_info.operator_id, checkpoint_barrier.epoch, e);
+            }
 
             crate::process_fn::ProcessFnUtils::send_checkpoint_event(checkpoint_barrier, ctx, arroyo_rpc::grpc::TaskCheckpointEventType::FinishedSync).await;
 
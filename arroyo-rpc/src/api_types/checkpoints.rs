@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -10,6 +10,34 @@ pub struct Checkpoint {
     pub finish_time: Option<u64>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SavepointPost {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SavepointState {
+    Pending,
+    InProgress,
+    Ready,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Savepoint {
+    pub id: String,
+    pub name: String,
+    pub job_id: Option<String>,
+    pub state: SavepointState,
+    pub created_at: u64,
+    pub finish_time: Option<u64>,
+    pub epoch: Option<u32>,
+    pub failure_message: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum CheckpointSpanType {
@@ -43,3 +71,77 @@ pub struct OperatorCheckpointGroup {
     pub bytes: u64,
     pub subtasks: Vec<SubtaskCheckpointGroup>,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointTable {
+    pub name: String,
+    pub description: String,
+    pub table_type: String,
+    pub files: u64,
+    pub bytes: Option<u64>,
+    pub rows: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorCheckpointTables {
+    pub operator_id: String,
+    pub bytes: u64,
+    /// false for operators checkpointed by the disk state backend, whose tables can be listed
+    /// but not read
+    pub readable: bool,
+    pub tables: Vec<CheckpointTable>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StateOperation {
+    Insert,
+    DeleteTimeKey,
+    DeleteKey,
+    DeleteValue,
+    DeleteTimeRange,
+}
+
+/// The primitive types that the keys and values of a table can be decoded as
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StateValueType {
+    Bool,
+    I32,
+    I64,
+    U32,
+    U64,
+    F32,
+    F64,
+    String,
+    Bytes,
+}
+
+/// A row of a table's state, with its key and value hex-encoded as they are stored
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StateEntry {
+    pub key_hash: u64,
+    pub timestamp: u64,
+    pub operation: StateOperation,
+    pub key: String,
+    pub value: String,
+    /// the key decoded as the requested key type
+    pub decoded_key: Option<serde_json::Value>,
+    /// the value decoded as the requested value type, for rows that have a value
+    pub decoded_value: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "snake_case")]
+pub struct StateQueryParams {
+    /// only return the rows for this key, which is hex-encoded unless a key type is given
+    pub key: Option<String>,
+    /// decode the keys of the rows as this type
+    pub key_type: Option<StateValueType>,
+    /// decode the values of the rows as this type
+    pub value_type: Option<StateValueType>,
+}
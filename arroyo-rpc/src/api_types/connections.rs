@@ -1,4 +1,5 @@
 use crate::formats::{Format, Framing};
+use crate::MetadataField;
 use anyhow::bail;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
@@ -44,6 +45,7 @@ pub struct ConnectionProfilePost {
 pub enum ConnectionType {
     Source,
     Sink,
+    Lookup,
 }
 
 impl Display for ConnectionType {
@@ -51,6 +53,7 @@ impl Display for ConnectionType {
         match self {
             ConnectionType::Source => write!(f, "SOURCE"),
             ConnectionType::Sink => write!(f, "SINK"),
+            ConnectionType::Lookup => write!(f, "LOOKUP"),
         }
     }
 }
@@ -62,6 +65,7 @@ impl TryFrom<String> for ConnectionType {
         match value.to_lowercase().as_str() {
             "source" => Ok(ConnectionType::Source),
             "sink" => Ok(ConnectionType::Sink),
+            "lookup" => Ok(ConnectionType::Lookup),
             _ => Err(format!("Invalid connection type: {}", value)),
         }
     }
@@ -113,6 +117,10 @@ pub struct SourceField {
     pub field_name: String,
     pub field_type: SourceFieldType,
     pub nullable: bool,
+    // set for fields that are read from the metadata of a message, like its Kafka key, rather
+    // than from its payload
+    #[serde(default)]
+    pub metadata_key: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -133,6 +141,9 @@ pub struct ConnectionSchema {
     pub fields: Vec<SourceField>,
     pub definition: Option<SchemaDefinition>,
     pub inferred: Option<bool>,
+    // the columns that make up the primary key of the table, used by sinks that upsert
+    #[serde(default)]
+    pub primary_keys: Vec<String>,
 }
 
 impl ConnectionSchema {
@@ -151,6 +162,7 @@ impl ConnectionSchema {
             fields,
             definition,
             inferred,
+            primary_keys: vec![],
         };
 
         s.validate()
@@ -159,8 +171,13 @@ impl ConnectionSchema {
     pub fn validate(self) -> anyhow::Result<Self> {
         match &self.format {
             Some(Format::RawString(_)) => {
-                if self.fields.len() != 1
-                    || self.fields.get(0).unwrap().field_type.r#type
+                let payload_fields: Vec<_> = self
+                    .fields
+                    .iter()
+                    .filter(|f| f.metadata_key.is_none())
+                    .collect();
+                if payload_fields.len() != 1
+                    || payload_fields[0].field_type.r#type
                         != FieldType::Primitive(PrimitiveType::String)
                 {
                     bail!("raw_string format requires a schema with a single field of type TEXT");
@@ -171,6 +188,18 @@ impl ConnectionSchema {
 
         Ok(self)
     }
+
+    pub fn metadata_fields(&self) -> Vec<MetadataField> {
+        self.fields
+            .iter()
+            .filter_map(|f| {
+                Some(MetadataField {
+                    field_name: f.field_name.clone(),
+                    key: f.metadata_key.clone()?,
+                })
+            })
+            .collect()
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema, IntoParams)]
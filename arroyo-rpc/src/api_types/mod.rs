@@ -31,6 +31,9 @@ pub struct PaginatedCollection<T> {
     JobCollection = NonPaginatedCollection<Job>,
     OperatorCheckpointGroupCollection = NonPaginatedCollection<OperatorCheckpointGroup>,
     CheckpointCollection = NonPaginatedCollection<Checkpoint>,
+    SavepointCollection = NonPaginatedCollection<Savepoint>,
+    OperatorCheckpointTablesCollection = NonPaginatedCollection<OperatorCheckpointTables>,
+    StateEntryCollection = NonPaginatedCollection<StateEntry>,
     OperatorMetricGroupCollection = NonPaginatedCollection<OperatorMetricGroup>,
     ConnectorCollection = NonPaginatedCollection<Connector>,
     ConnectionProfileCollection = NonPaginatedCollection<ConnectionProfile>,
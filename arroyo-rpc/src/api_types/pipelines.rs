@@ -26,6 +26,8 @@ pub struct PipelinePost {
     pub udfs: Option<Vec<Udf>>,
     pub preview: Option<bool>,
     pub parallelism: u64,
+    /// id of a savepoint to restore the pipeline's state from
+    pub savepoint_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -34,6 +36,10 @@ pub struct PipelinePatch {
     pub parallelism: Option<u64>,
     pub checkpoint_interval_micros: Option<u64>,
     pub stop: Option<StopType>,
+    /// new query for the pipeline; operators it shares with the current query keep their state
+    pub query: Option<String>,
+    /// UDFs for the new query, replacing the current ones
+    pub udfs: Option<Vec<Udf>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
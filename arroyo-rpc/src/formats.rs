@@ -103,6 +103,85 @@ impl JsonFormat {
 #[serde(rename_all = "camelCase")]
 pub struct RawStringFormat {}
 
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvFormat {
+    #[serde(default = "CsvFormat::default_delimiter")]
+    pub delimiter: char,
+
+    #[serde(default = "CsvFormat::default_quote")]
+    pub quote: char,
+
+    /// The character that escapes quotes within quoted fields; if not set, quotes are escaped
+    /// by doubling them
+    #[serde(default)]
+    pub escape: Option<char>,
+
+    /// Whether files start with a header row naming the columns; messages never have one
+    #[serde(default)]
+    pub header: bool,
+
+    /// The value of fields that are null
+    #[serde(default)]
+    pub null_literal: String,
+}
+
+impl Default for CsvFormat {
+    fn default() -> Self {
+        Self {
+            delimiter: Self::default_delimiter(),
+            quote: Self::default_quote(),
+            escape: None,
+            header: false,
+            null_literal: String::new(),
+        }
+    }
+}
+
+impl CsvFormat {
+    fn default_delimiter() -> char {
+        ','
+    }
+
+    fn default_quote() -> char {
+        '"'
+    }
+
+    fn char_opt(opts: &mut HashMap<String, String>, name: &str) -> Result<Option<char>, String> {
+        let Some(value) = opts.remove(name) else {
+            return Ok(None);
+        };
+
+        let c = match value.as_str() {
+            "\\t" => '\t',
+            v => {
+                let mut chars = v.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => return Err(format!("{} must be a single character", name)),
+                }
+            }
+        };
+
+        if !c.is_ascii() {
+            return Err(format!("{} must be an ASCII character", name));
+        }
+
+        Ok(Some(c))
+    }
+
+    pub fn from_opts(opts: &mut HashMap<String, String>) -> Result<Self, String> {
+        Ok(Self {
+            delimiter: Self::char_opt(opts, "csv.delimiter")?
+                .unwrap_or_else(Self::default_delimiter),
+            quote: Self::char_opt(opts, "csv.quote")?.unwrap_or_else(Self::default_quote),
+            escape: Self::char_opt(opts, "csv.escape")?,
+            header: opts.remove("csv.header").filter(|t| t == "true").is_some(),
+            null_literal: opts.remove("csv.null_literal").unwrap_or_default(),
+        })
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
 pub struct ConfluentSchemaRegistryConfig {
     endpoint: String,
@@ -222,6 +301,64 @@ impl AvroFormat {
     }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtobufFormat {
+    #[serde(default)]
+    pub confluent_schema_registry: bool,
+
+    #[serde(default)]
+    pub into_unstructured_json: bool,
+
+    /// The fully-qualified name of the message to read or write; if not set, the first
+    /// message defined in the schema is used
+    #[serde(default)]
+    pub message_name: Option<String>,
+
+    #[serde(default)]
+    #[schema(read_only)]
+    pub schema_def: Option<String>,
+
+    #[serde(default)]
+    #[schema(read_only)]
+    pub schema_id: Option<u32>,
+}
+
+impl ProtobufFormat {
+    pub fn new(
+        confluent_schema_registry: bool,
+        into_unstructured_json: bool,
+        message_name: Option<String>,
+    ) -> Self {
+        Self {
+            confluent_schema_registry,
+            into_unstructured_json,
+            message_name,
+            schema_def: None,
+            schema_id: None,
+        }
+    }
+
+    pub fn from_opts(opts: &mut HashMap<String, String>) -> Result<Self, String> {
+        let mut format = Self::new(
+            opts.remove("protobuf.confluent_schema_registry")
+                .filter(|t| t == "true")
+                .is_some(),
+            opts.remove("protobuf.into_unstructured_json")
+                .filter(|t| t == "true")
+                .is_some(),
+            opts.remove("protobuf.message_name"),
+        );
+        // the source of a .proto file, for tables created in SQL
+        format.schema_def = opts.remove("protobuf.schema");
+        Ok(format)
+    }
+
+    pub fn add_schema_def(&mut self, schema: impl Into<String>) {
+        self.schema_def = Some(schema.into());
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ParquetFormat {}
@@ -231,8 +368,10 @@ pub struct ParquetFormat {}
 pub enum Format {
     Json(JsonFormat),
     Avro(AvroFormat),
+    Protobuf(ProtobufFormat),
     Parquet(ParquetFormat),
     RawString(RawStringFormat),
+    Csv(CsvFormat),
 }
 
 impl Format {
@@ -244,10 +383,11 @@ impl Format {
         Ok(Some(match name.as_str() {
             "json" => Format::Json(JsonFormat::from_opts(false, opts)?),
             "debezium_json" => Format::Json(JsonFormat::from_opts(true, opts)?),
-            "protobuf" => return Err("protobuf is not yet supported".to_string()),
+            "protobuf" => Format::Protobuf(ProtobufFormat::from_opts(opts)?),
             "avro" => Format::Avro(AvroFormat::from_opts(opts)?),
             "raw_string" => Format::RawString(RawStringFormat {}),
             "parquet" => Format::Parquet(ParquetFormat {}),
+            "csv" => Format::Csv(CsvFormat::from_opts(opts)?),
             f => return Err(format!("Unknown format '{}'", f)),
         }))
     }
@@ -255,7 +395,12 @@ impl Format {
     pub fn is_updating(&self) -> bool {
         match self {
             Format::Json(JsonFormat { debezium: true, .. }) => true,
-            Format::Json(_) | Format::Avro(_) | Format::Parquet(_) | Format::RawString(_) => false,
+            Format::Json(_)
+            | Format::Avro(_)
+            | Format::Protobuf(_)
+            | Format::Parquet(_)
+            | Format::RawString(_)
+            | Format::Csv(_) => false,
         }
     }
 }
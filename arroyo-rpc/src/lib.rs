@@ -159,6 +159,12 @@ pub struct RateLimit {
     pub messages_per_second: u32,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MetadataField {
+    pub field_name: String,
+    pub key: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct OperatorConfig {
     pub connection: Value,
@@ -166,6 +172,8 @@ pub struct OperatorConfig {
     pub format: Option<Format>,
     pub framing: Option<Framing>,
     pub rate_limit: Option<RateLimit>,
+    #[serde(default)]
+    pub metadata_fields: Vec<MetadataField>,
 }
 
 impl Default for OperatorConfig {
@@ -176,6 +184,7 @@ impl Default for OperatorConfig {
             format: None,
             framing: None,
             rate_limit: None,
+            metadata_fields: vec![],
         }
     }
 }
@@ -22,6 +22,7 @@ pub enum IdTypes {
     ConnectionTable,
     ConnectionTablePipeline,
     Udf,
+    Savepoint,
 }
 
 pub fn generate_id(id_type: IdTypes) -> String {
@@ -38,6 +39,7 @@ pub fn generate_id(id_type: IdTypes) -> String {
         IdTypes::ConnectionTable => "ct",
         IdTypes::ConnectionTablePipeline => "ctp",
         IdTypes::Udf => "udf",
+        IdTypes::Savepoint => "sp",
     };
     let id = nanoid!(ID_LENGTH, &ALPHABET);
     format!("{}_{}", prefix, id)
@@ -161,13 +161,13 @@ fn get_pipeline_module(
     let file = syn::parse_file(&udfs.unwrap_or_default()).unwrap();
     for item in file.items.into_iter() {
         match item {
-            syn::Item::Fn(_) => {
+            syn::Item::Fn(_) | syn::Item::Mod(_) => {
                 schema_provider
                     .add_rust_udf(&item.to_token_stream().to_string())
                     .unwrap();
             }
             _ => {
-                panic!("Expected only functions.")
+                panic!("Expected only functions and UDAF modules.")
             }
         }
     }
@@ -178,6 +178,7 @@ fn get_pipeline_module(
         schema_provider,
         SqlConfig {
             default_parallelism: 1,
+            ..Default::default()
         },
     )
     .unwrap()
@@ -179,6 +179,18 @@ full_pipeline_codegen! {"session_window",
 from nexmark
 group by window, auction.id; "}
 
+full_pipeline_codegen! {"session_window_max_length",
+"SELECT count(*), avg(bid.price), session(INTERVAL '10' SECOND, INTERVAL '1' HOUR) AS window
+from nexmark
+where bid is not null
+group by window, bid.auction; "}
+
+full_pipeline_codegen! {"cumulating_window",
+"SELECT count(*), sum(bid.price), cumulate(INTERVAL '1' MINUTE, INTERVAL '1' HOUR) AS window
+from nexmark
+where bid is not null
+group by window, bid.auction; "}
+
 full_pipeline_codegen! {"virtual_field_implicit_cast",
 "create table demo_stream (
   timestamp BIGINT NOT NULL,
@@ -345,6 +357,58 @@ SELECT bid.auction as auction,
   GROUP BY 1, 2)) WHERE row_number < 4
 "}
 
+full_pipeline_codegen! {"rank_with_filter",
+"SELECT * FROM (
+  SELECT *, RANK() OVER (
+      PARTITION BY window
+      ORDER BY count DESC) as rank
+  FROM (
+    SELECT bid.auction as auction,
+           tumble(INTERVAL '1' minute) as window,
+           count(*) as count
+      FROM nexmark
+      GROUP BY 1, 2)) where rank <= 3
+"}
+
+full_pipeline_codegen! {"dense_rank_and_ntile",
+"SELECT *, DENSE_RANK() OVER (
+      PARTITION BY window
+      ORDER BY price DESC) as dense_rank
+  FROM (
+    SELECT *, NTILE(4) OVER (
+        PARTITION BY window
+        ORDER BY price) as quartile
+    FROM (
+      SELECT bid.auction as auction,
+             tumble(INTERVAL '1' minute) as window,
+             sum(bid.price) as price
+        FROM nexmark
+        GROUP BY 1, 2))
+"}
+
+full_pipeline_codegen! {"lag_lead_per_key_delta",
+"SELECT auction, delta,
+    LEAD(price, 2, 0) OVER (PARTITION BY window, auction ORDER BY datetime) as next_price
+  FROM (
+    SELECT *, price - LAG(price) OVER (PARTITION BY window, auction ORDER BY datetime) as delta
+    FROM (
+      SELECT bid.auction as auction, bid.price as price, bid.datetime as datetime,
+             tumble(INTERVAL '10' second) as window
+        FROM nexmark WHERE bid is not null))
+"}
+
+full_pipeline_codegen! {"first_and_last_value",
+"SELECT *, LAST_VALUE(price) OVER (PARTITION BY window ORDER BY auction) as last_price
+  FROM (
+    SELECT *, FIRST_VALUE(auction) OVER (PARTITION BY window ORDER BY price DESC) as top_auction
+    FROM (
+      SELECT bid.auction as auction,
+             tumble(INTERVAL '1' minute) as window,
+             max(bid.price) as price
+        FROM nexmark
+        GROUP BY 1, 2))
+"}
+
 full_pipeline_codegen! {"top_n_offset",
 "SELECT * FROM (
   SELECT *, ROW_NUMBER()  OVER (
@@ -572,3 +636,476 @@ full_pipeline_codegen! {
 SELECT * from logs;
 "
 }
+
+full_pipeline_codegen! {
+  "redis_lookup_join",
+  "create table auctions (
+    seller BIGINT PRIMARY KEY,
+    category BIGINT
+) with (
+    connector = 'redis',
+    address = 'redis://localhost:6379',
+    type = 'lookup',
+    'lookup.key_prefix' = 'auction:',
+    'lookup.cache.ttl_secs' = '60',
+    format = 'json'
+);
+
+SELECT bid.auction, bid.price, a.seller, a.category
+FROM nexmark
+LEFT JOIN auctions FOR SYSTEM_TIME AS OF PROCTIME() AS a
+  ON bid.auction = a.seller
+WHERE bid is not null;
+"
+}
+
+full_pipeline_codegen! {
+  "redis_stream_source",
+  "create table orders (
+    id BIGINT,
+    customer TEXT
+) with (
+    connector = 'redis',
+    address = 'redis://localhost:6379',
+    type = 'source',
+    source = 'stream',
+    'source.stream' = 'orders',
+    'source.group' = 'arroyo',
+    'source.offset' = 'earliest',
+    format = 'json'
+);
+
+SELECT customer, count(*) FROM orders GROUP BY customer, tumble(interval '1 minute');
+"
+}
+
+full_pipeline_codegen! {
+  "redis_pubsub_source",
+  "create table notifications (
+    value TEXT
+) with (
+    connector = 'redis',
+    address = 'redis://localhost:6379',
+    type = 'source',
+    source = 'pubsub',
+    'source.channels' = 'notifications.*',
+    'source.pattern' = 'true',
+    format = 'raw_string'
+);
+
+SELECT value FROM notifications;
+"
+}
+
+full_pipeline_codegen! {
+  "http_lookup_join",
+  "create table users (
+    id TEXT NOT NULL,
+    name TEXT
+) with (
+    connector = 'http_lookup',
+    endpoint = 'http://localhost:8080/users/{key}',
+    format = 'json'
+);
+
+SELECT bid.bidder, u.name
+FROM nexmark
+JOIN users u ON CAST(bid.bidder as TEXT) = u.id
+WHERE bid is not null;
+"
+}
+
+full_pipeline_codegen! {
+  "aggregate_filter_two_phase",
+  "SELECT count(*) FILTER (WHERE bid.price > 100) as expensive_bids,
+  sum(bid.price) FILTER (WHERE bid.channel = 'Google') as google_volume,
+  avg(bid.price) FILTER (WHERE bid.auction % 2 = 0) as even_average
+FROM nexmark
+WHERE bid is not null
+GROUP BY hop(interval '2 seconds', interval '10 seconds')"
+}
+
+full_pipeline_codegen! {
+  "aggregate_filter_and_order_by",
+  "SELECT bid.bidder,
+  count(distinct bid.auction) FILTER (WHERE bid.price > 100) as expensive_auctions,
+  max(bid.price) FILTER (WHERE bid.price < 1000) as max_cheap_bid,
+  array_agg(bid.auction ORDER BY bid.datetime DESC) as auctions,
+  array_agg(bid.url) FILTER (WHERE bid.channel IS NOT NULL) as urls
+FROM nexmark
+WHERE bid is not null
+GROUP BY 1, tumble(interval '1 second')"
+}
+
+full_pipeline_codegen! {
+  "updating_aggregate_filter",
+  "SELECT bid.auction,
+  count(*) FILTER (WHERE bid.price > 100) as expensive_bids,
+  min(bid.price) FILTER (WHERE bid.channel = 'Google') as min_google_bid
+FROM nexmark
+WHERE bid is not null
+GROUP BY 1"
+}
+
+full_pipeline_codegen! {
+  "updating_ordered_array_agg",
+  "SELECT bid.bidder,
+  array_agg(bid.auction ORDER BY bid.price DESC, bid.datetime) as auctions,
+  array_agg(bid.url) FILTER (WHERE bid.price > 100) as expensive_urls
+FROM nexmark
+WHERE bid is not null
+GROUP BY 1"
+}
+
+full_pipeline_codegen! {
+  "sliding_ordered_array_agg",
+  "SELECT array_agg(bid.auction ORDER BY bid.datetime DESC) as auctions
+FROM nexmark
+WHERE bid is not null
+GROUP BY hop(interval '2 seconds', interval '10 seconds')"
+}
+
+full_pipeline_codegen! {
+  "sliding_statistical_aggregates",
+  "SELECT corr(bid.price, bid.auction) as correlation,
+  covar_pop(bid.price, bid.auction) as covariance,
+  arg_max(bid.auction, bid.price) as top_auction
+FROM nexmark
+WHERE bid is not null
+GROUP BY hop(interval '2 seconds', interval '10 seconds')"
+}
+
+full_pipeline_codegen! {
+  "updating_statistical_aggregates",
+  "SELECT bid.bidder, covar_samp(bid.price, bid.auction) as covariance,
+  arg_min(bid.auction, bid.price) FILTER (WHERE bid.channel = 'Google') as cheapest_google_auction
+FROM nexmark
+WHERE bid is not null
+GROUP BY 1"
+}
+
+full_pipeline_codegen! {"temporal_join",
+"CREATE TABLE bids (
+  auction BIGINT,
+  price BIGINT,
+  datetime TIMESTAMP
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  topic = 'bids',
+  type = 'source',
+  format = 'json',
+  event_time_field = 'datetime'
+);
+
+CREATE TABLE auction_categories (
+  id BIGINT,
+  category BIGINT
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  type = 'source',
+  topic = 'auction_categories',
+  format = 'debezium_json'
+);
+
+SELECT bids.auction, bids.price, c.category
+FROM bids
+JOIN auction_categories FOR SYSTEM_TIME AS OF bids.datetime AS c
+  ON bids.auction = c.id
+"}
+
+full_pipeline_codegen! {"match_recognize",
+"CREATE VIEW bids AS
+SELECT bid.auction as auction, bid.price as price
+FROM nexmark WHERE bid is not null;
+
+SELECT * FROM bids MATCH_RECOGNIZE (
+  PARTITION BY auction
+  MEASURES
+    FIRST(UP.price) AS start_price,
+    MAX(price) AS max_price,
+    COUNT(*) AS row_count
+  PATTERN (UP+ DOWN)
+  WITHIN INTERVAL '10' MINUTE
+  DEFINE
+    UP AS UP.price > 100,
+    DOWN AS DOWN.price <= 100
+)
+"}
+
+full_pipeline_codegen! {"interval_join",
+"CREATE TABLE bids (
+  auction BIGINT,
+  price BIGINT,
+  datetime TIMESTAMP
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  topic = 'bids',
+  type = 'source',
+  format = 'json',
+  event_time_field = 'datetime'
+);
+CREATE TABLE auctions (
+  id BIGINT,
+  seller BIGINT,
+  datetime TIMESTAMP
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  topic = 'auctions',
+  type = 'source',
+  format = 'json',
+  event_time_field = 'datetime'
+);
+SELECT bids.auction, bids.price, auctions.seller
+FROM bids JOIN auctions ON bids.auction = auctions.id
+  AND bids.datetime >= auctions.datetime
+  AND bids.datetime < auctions.datetime + INTERVAL '10' MINUTE
+"}
+
+full_pipeline_codegen! {"kafka_metadata_fields", "
+CREATE TABLE events (
+  user_id TEXT,
+  key BYTEA METADATA FROM 'key',
+  headers TEXT METADATA FROM 'headers',
+  partition INT METADATA FROM 'partition',
+  event_time TIMESTAMP METADATA FROM 'timestamp'
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  type = 'source',
+  topic = 'events',
+  format = 'json'
+);
+
+CREATE TABLE keyed_sink (
+  user_id TEXT,
+  headers TEXT,
+  partition INT
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  type = 'sink',
+  topic = 'keyed_events',
+  format = 'json',
+  'sink.key_field' = 'user_id',
+  'sink.headers_field' = 'headers'
+);
+
+INSERT INTO keyed_sink
+SELECT user_id, headers, partition FROM events;
+"}
+
+full_pipeline_codegen! {"mqtt_source_and_sink", "
+CREATE TABLE telemetry (
+  device_id TEXT,
+  temperature DOUBLE
+) WITH (
+  connector = 'mqtt',
+  url = 'tcp://localhost:1883',
+  type = 'source',
+  topic = 'devices/+/telemetry',
+  qos = 'at_least_once',
+  format = 'json'
+);
+
+CREATE TABLE alerts (
+  device_id TEXT,
+  temperature DOUBLE
+) WITH (
+  connector = 'mqtt',
+  url = 'tcp://localhost:1883',
+  type = 'sink',
+  topic = 'alerts',
+  'sink.retain' = 'true',
+  format = 'json'
+);
+
+INSERT INTO alerts
+SELECT device_id, temperature FROM telemetry WHERE temperature > 100;
+"}
+
+full_pipeline_codegen! {"nats_source_and_sink", "
+CREATE TABLE orders (
+  order_id BIGINT,
+  amount DOUBLE
+) WITH (
+  connector = 'nats',
+  servers = 'nats://localhost:4222',
+  type = 'source',
+  stream = 'ORDERS',
+  subject = 'orders.>',
+  'source.offset' = 'earliest',
+  format = 'json'
+);
+
+CREATE TABLE large_orders (
+  order_id BIGINT,
+  amount DOUBLE
+) WITH (
+  connector = 'nats',
+  servers = 'nats://localhost:4222',
+  type = 'sink',
+  subject = 'orders.large',
+  format = 'json'
+);
+
+INSERT INTO large_orders
+SELECT order_id, amount FROM orders WHERE amount > 1000;
+"}
+
+full_pipeline_codegen! {"postgres_cdc_source", "
+CREATE TABLE orders (
+  id INT,
+  customer_id BIGINT,
+  amount DOUBLE,
+  created_at TIMESTAMP
+) WITH (
+  connector = 'postgres_cdc',
+  host = 'localhost',
+  database = 'shop',
+  user = 'arroyo',
+  table = 'public.orders',
+  slot = 'arroyo_orders',
+  publication = 'arroyo_orders'
+);
+
+SELECT customer_id, sum(amount) as total FROM orders GROUP BY customer_id;
+"}
+
+full_pipeline_codegen! {"jdbc_upsert_sink", "
+CREATE TABLE auction_bids (
+  auction BIGINT PRIMARY KEY,
+  bids BIGINT
+) WITH (
+  connector = 'jdbc',
+  url = 'postgres://localhost:5432/arroyo',
+  table = 'auction_bids'
+);
+
+INSERT INTO auction_bids SELECT bid.auction, count(*) FROM nexmark GROUP BY 1;
+"}
+
+full_pipeline_codegen! {"iceberg_sink", "
+CREATE TABLE bids (
+  auction BIGINT,
+  price BIGINT,
+  bid_time TIMESTAMP
+) WITH (
+  connector = 'iceberg',
+  path = 's3://warehouse/bids',
+  format = 'parquet',
+  'iceberg.catalog' = 'rest',
+  'iceberg.catalog_uri' = 'http://localhost:8181',
+  'iceberg.namespace' = 'arroyo',
+  'iceberg.table' = 'bids',
+  'iceberg.time_partition_field' = 'bid_time',
+  time_partition_pattern = '%Y/%m/%d',
+  partition_fields = 'auction'
+);
+
+INSERT INTO bids SELECT bid.auction, bid.price, bid.datetime FROM nexmark WHERE bid IS NOT NULL;
+"}
+
+full_pipeline_codegen! {"delta_source", "
+CREATE TABLE bids (
+  auction BIGINT,
+  price BIGINT
+) WITH (
+  connector = 'delta',
+  type = 'source',
+  path = 's3://warehouse/bids',
+  format = 'parquet',
+  poll_interval_seconds = '30'
+);
+
+SELECT auction, max(price) FROM bids GROUP BY 1;
+"}
+
+full_pipeline_codegen! {"iceberg_rest_source", "
+CREATE TABLE bids (
+  auction BIGINT,
+  price BIGINT
+) WITH (
+  connector = 'iceberg',
+  type = 'source',
+  path = 's3://warehouse/bids',
+  format = 'parquet',
+  'iceberg.catalog' = 'rest',
+  'iceberg.catalog_uri' = 'http://localhost:8181',
+  'iceberg.namespace' = 'arroyo',
+  'iceberg.table' = 'bids'
+);
+
+SELECT auction, max(price) FROM bids GROUP BY 1;
+"}
+
+full_pipeline_codegen! {"filesystem_avro_source", "
+CREATE TABLE events (
+  id BIGINT,
+  name TEXT
+) WITH (
+  connector = 'filesystem',
+  type = 'source',
+  path = 's3://archive/events',
+  format = 'avro',
+  compression_format = 'gzip'
+);
+
+SELECT id, name FROM events;
+"}
+
+full_pipeline_codegen! {"filesystem_raw_string_source", "
+CREATE TABLE logs (
+  value TEXT
+) WITH (
+  connector = 'filesystem',
+  type = 'source',
+  path = 's3://archive/logs',
+  format = 'raw_string',
+  compression_format = 'zstd'
+);
+
+SELECT value FROM logs WHERE value LIKE '%ERROR%';
+"}
+
+full_pipeline_codegen! {"kafka_csv_to_filesystem_csv", "
+CREATE TABLE orders (
+  id BIGINT,
+  customer TEXT,
+  amount DOUBLE,
+  created_at TIMESTAMP
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  type = 'source',
+  topic = 'orders',
+  format = 'csv',
+  'csv.delimiter' = ';',
+  'csv.null_literal' = 'NULL'
+);
+
+CREATE TABLE order_export (
+  id BIGINT,
+  customer TEXT,
+  amount DOUBLE,
+  created_at TIMESTAMP
+) WITH (
+  connector = 'filesystem',
+  path = 's3://exports/orders',
+  format = 'csv',
+  'csv.header' = 'true',
+  'csv.quote' = '|'
+);
+
+INSERT INTO order_export SELECT * FROM orders;
+"}
+
+full_pipeline_codegen! {"set_state_options",
+"SET state.ttl = '2 hours';
+SET watermark.max_lateness = '5 seconds';
+SET parallelism.nexmark = 2;
+
+SELECT bid.auction, count(*) FROM nexmark WHERE bid IS NOT NULL GROUP BY bid.auction"}
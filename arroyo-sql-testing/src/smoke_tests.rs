@@ -1,5 +1,5 @@
 #![allow(warnings)]
-use arroyo_state::parquet::ParquetBackend;
+use arroyo_state::{BackingStore, StateBackend};
 use std::collections::HashMap;
 use std::{env, fmt::Debug, time::SystemTime};
 use tokio::sync::mpsc::Receiver;
@@ -99,7 +99,7 @@ async fn compact(
     let operator_controls = running_engine.operator_controls();
     for (operator, parallelism) in tasks_per_operator {
         if let Ok(Some(compacted)) =
-            ParquetBackend::compact_operator(parallelism, job_id.clone(), operator.clone(), epoch)
+            StateBackend::compact_operator(parallelism, job_id.clone(), operator.clone(), epoch)
                 .await
         {
             let operator_controls = operator_controls.get(&operator).unwrap();
@@ -388,6 +388,41 @@ FROM cars
 GROUP BY 1);
 "}
 
+correctness_run_codegen! {"filtered_aggregates", 200,
+"CREATE TABLE cars(
+  timestamp TIMESTAMP,
+  driver_id BIGINT,
+  event_type TEXT,
+  location TEXT
+) WITH (
+  connector = 'single_file',
+  path = '$input_dir/cars.json',
+  format = 'json',
+  type = 'source',
+  event_time_field = 'timestamp'
+);
+CREATE TABLE filtered_aggregates (
+  hour TIMESTAMP,
+  pickups BIGINT,
+  fidi_dropoffs BIGINT,
+  max_fidi_driver BIGINT
+) WITH (
+  connector = 'single_file',
+  path = '$output_path',
+  format = 'json',
+  type = 'sink'
+);
+INSERT INTO filtered_aggregates
+SELECT window.start as hour, pickups, fidi_dropoffs, max_fidi_driver
+FROM (
+SELECT TUMBLE(INTERVAL '1' HOUR) as window,
+  COUNT(*) FILTER (WHERE event_type = 'pickup') as pickups,
+  COUNT(*) FILTER (WHERE event_type = 'dropoff' AND location = 'FiDi') as fidi_dropoffs,
+  MAX(driver_id) FILTER (WHERE location = 'FiDi') as max_fidi_driver
+FROM cars
+GROUP BY 1);
+"}
+
 correctness_run_codegen! {"tight_watermark", 200,
 "CREATE TABLE cars(
   timestamp TIMESTAMP,
@@ -737,6 +772,51 @@ pub fn max_product(first_arg: Vec<u64>, second_arg: Vec<u64>) -> u64 {
   pairs.map(|(x, y)| x * y).max().unwrap()
 }"}
 
+// test incremental UDAFs and multi-argument aggregates
+correctness_run_codegen! {"incremental_udaf", 10,
+"CREATE TABLE impulse_source (
+  timestamp TIMESTAMP,
+  counter bigint unsigned not null,
+  subtask_index bigint unsigned not null
+) WITH (
+  connector = 'single_file',
+  path = '$input_dir/impulse.json',
+  format = 'json',
+  type = 'source'
+);
+CREATE TABLE incremental_udaf (
+  average double,
+  variance double,
+  last_counter bigint
+) WITH (
+  connector = 'single_file',
+  path = '$output_path',
+  format = 'json',
+  type = 'sink'
+);
+INSERT INTO incremental_udaf SELECT average, variance, last_counter FROM (
+  SELECT tumble(interval '1' month) as window, my_average(counter) as average,
+  covar_samp(counter, counter) as variance, arg_max(counter, timestamp) as last_counter
+FROM impulse_source
+GROUP BY 1)",
+"pub mod my_average {
+  pub fn accumulate(state: &mut (u64, u64), value: u64) {
+    state.0 += 1;
+    state.1 += value;
+  }
+  pub fn merge(state: &mut (u64, u64), other: &(u64, u64)) {
+    state.0 += other.0;
+    state.1 += other.1;
+  }
+  pub fn retract(state: &mut (u64, u64), other: &(u64, u64)) {
+    state.0 -= other.0;
+    state.1 -= other.1;
+  }
+  pub fn finish(state: &(u64, u64)) -> Option<f64> {
+    (state.0 > 0).then(|| state.1 as f64 / state.0 as f64)
+  }
+}"}
+
 // filter updating aggregates
 correctness_run_codegen! {"filter_updating_aggregates", 10,
 "CREATE TABLE impulse_source (
@@ -25,17 +25,19 @@ pub fn get_defs(name: &str, schema: &str) -> anyhow::Result<String> {
     let fields = convert_avro_schema(name, schema)?;
 
     let sd = StructDef::new(Some(ROOT_NAME.to_string()), true, fields, None);
-    let defs: Vec<_> = sd
+    let defs = sd
         .all_structs_including_named()
         .iter()
         .map(|p| {
-            vec![
+            Ok(vec![
                 syn::parse_str(&p.def(false)).unwrap(),
-                p.generate_serializer_items(),
-            ]
+                p.generate_serializer_items()?,
+            ])
         })
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .into_iter()
         .flatten()
-        .collect();
+        .collect::<Vec<_>>();
 
     let mod_ident: Ident = syn::parse_str(name).unwrap();
     Ok(quote! {
@@ -218,8 +220,17 @@ pub fn generate_serializer_item(
                 Interval(_) => todo!("interval is not supported"),
                 Binary | FixedSizeBinary(_) | LargeBinary => quote! { Bytes(v.clone()) },
                 Utf8 | LargeUtf8 => quote! { String(v.clone()) },
-                List(_) | FixedSizeList(_, _) | LargeList(_) => {
-                    todo!("lists are not supported")
+                List(item) => {
+                    let item_serializer = generate_serializer_item(
+                        &format_ident!("__item"),
+                        None,
+                        None,
+                        &TypeDef::DataType(item.data_type().clone(), item.is_nullable()),
+                    );
+                    quote! { Array(v.iter().cloned().map(|__item| #item_serializer).collect()) }
+                }
+                FixedSizeList(_, _) | LargeList(_) => {
+                    todo!("fixed size and large lists are not supported")
                 }
                 Struct(_) => unreachable!("typedefs should not contain structs"),
                 Union(_, _) => unimplemented!("unions are not supported"),
@@ -1,8 +1,10 @@
 use arrow_schema::DataType;
 use quote::ToTokens;
 use quote::{format_ident, quote};
-use syn::parse_quote;
+use syn::{parse_quote, parse_str};
 
+use crate::operators::Projection;
+use crate::pipeline::JoinType;
 use crate::types::{data_type_as_syn_type, StructDef, TypeDef};
 
 pub trait CodeGenerator<Context, OutputValue, OutputType: ToTokens> {
@@ -254,6 +256,49 @@ impl JoinPairContext {
             Some(#merge_expr)
         })
     }
+
+    pub(crate) fn compile_lookup_merge_closure(
+        &self,
+        join_type: &JoinType,
+        lookup_struct: &StructDef,
+        right_projections: &[Projection],
+    ) -> syn::ExprClosure {
+        let merge_expr = join_type.generate(self);
+        let left_ident = self.left_ident();
+        let right_ident = self.right_ident();
+        let left_type = self.left_struct.get_type();
+        let lookup_type = lookup_struct.get_type();
+
+        // apply any projections to the looked-up value, so that it matches the right struct
+        let project_right = if right_projections.is_empty() {
+            None
+        } else {
+            let value_context = ValuePointerContext::new();
+            let arg_ident = value_context.variable_ident();
+            let projections = right_projections
+                .iter()
+                .map(|projection| projection.generate(&value_context));
+            Some(quote! {
+                let #right_ident = #right_ident.map(|#arg_ident| {
+                    #(let #arg_ident = &#projections;)*
+                    #arg_ident.clone()
+                });
+                let #right_ident = #right_ident.as_ref();
+            })
+        };
+
+        let unwrap_right = if join_type.right_nullable() {
+            None
+        } else {
+            Some(quote!(let #right_ident = #right_ident?;))
+        };
+
+        parse_quote!(|#left_ident: &#left_type, #right_ident: Option<&#lookup_type>| {
+            #project_right
+            #unwrap_right
+            Some(#merge_expr)
+        })
+    }
 }
 
 pub struct JoinListsContext {
@@ -335,6 +380,8 @@ pub enum BinType {
     Option(Box<BinType>),
     Tuple(Vec<BinType>),
     BTreeMap(Box<BinType>, Box<BinType>),
+    // the state of an accumulator, as the string form of its rust type
+    Accumulator(String),
 }
 
 impl BinType {
@@ -362,6 +409,7 @@ impl BinType {
                 parse_quote!(std::collections::BTreeMap<#key_ident, #value_ident>)
             }
             BinType::Usize => parse_quote!(usize),
+            BinType::Accumulator(state_type) => parse_str(state_type).unwrap(),
         }
     }
 }
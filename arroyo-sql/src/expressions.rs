@@ -24,6 +24,7 @@ use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use regex::Regex;
 use std::hash::Hash;
+use std::iter::once;
 use std::{fmt::Debug, sync::Arc, time::Duration};
 use syn::{parse_quote, parse_str, Ident, Path};
 
@@ -465,7 +466,7 @@ impl Expression {
                 (&mut *e.struct_expression).traverse_mut(context, f);
             }
             Expression::Aggregation(e) => {
-                (&mut *e.producing_expression).traverse_mut(context, f);
+                e.expressions().for_each(|e| e.traverse_mut(context, f));
             }
             Expression::Cast(e) => {
                 (&mut *e.input).traverse_mut(context, f);
@@ -658,30 +659,9 @@ impl<'a> ExpressionContext<'a> {
             Expr::GetIndexedField(datafusion_expr::GetIndexedField { expr, field }) => {
                 StructFieldExpression::new(Box::new(self.compile_expr(expr)?), field)
             }
-            Expr::AggregateFunction(datafusion_expr::expr::AggregateFunction {
-                fun,
-                args,
-                distinct,
-                filter,
-                order_by,
-            }) => {
-                if args.len() != 1 {
-                    bail!("multiple aggregation parameters is not yet supported");
-                }
-
-                if filter.is_some() {
-                    bail!("filters in aggregations is not yet supported");
-                }
-                if order_by.is_some() {
-                    bail!("order by in aggregations is not yet supported");
-                }
-
-                Ok(AggregationExpression::new(
-                    Box::new(self.compile_expr(&args[0])?),
-                    fun.clone(),
-                    *distinct,
-                )?)
-            }
+            Expr::AggregateFunction(aggregate_function) => Ok(Expression::Aggregation(
+                AggregationExpression::try_from_aggregate_function(self, aggregate_function)?,
+            )),
             Expr::AggregateUDF { .. } => bail!("aggregate UDFs not supported"),
             Expr::Case(datafusion_expr::Case {
                 expr,
@@ -944,11 +924,26 @@ impl<'a> ExpressionContext<'a> {
                     Ok(Expression::WindowUDF(WindowType::Tumbling { width }))
                 }
                 "session" => {
-                    if args.len() != 1 {
-                        bail!("wrong number of arguments for session(), expected one");
+                    if args.is_empty() || args.len() > 2 {
+                        bail!("wrong number of arguments for session(), expected one or two");
                     }
                     let gap = Expression::get_duration(&args[0])?;
-                    Ok(Expression::WindowUDF(WindowType::Session { gap }))
+                    let max_length = args.get(1).map(Expression::get_duration).transpose()?;
+                    Ok(Expression::WindowUDF(WindowType::Session {
+                        gap,
+                        max_length,
+                    }))
+                }
+                "cumulate" => {
+                    if args.len() != 2 {
+                        bail!("wrong number of arguments for cumulate(), expected two");
+                    }
+                    let step = Expression::get_duration(&args[0])?;
+                    let max_size = Expression::get_duration(&args[1])?;
+                    Ok(Expression::WindowUDF(WindowType::Cumulating {
+                        step,
+                        max_size,
+                    }))
                 }
                 "unnest" => {
                     if args.len() != 1 {
@@ -1092,6 +1087,10 @@ impl ColumnExpression {
         )?;
         Ok(ColumnExpression { column_field })
     }
+
+    pub fn name(&self) -> &str {
+        &self.column_field.name
+    }
 }
 
 impl CodeGenerator<ValuePointerContext, TypeDef, syn::Expr> for ColumnExpression {
@@ -1531,12 +1530,20 @@ pub enum AggregateComputation {
         column: Column,
         computation: RustUdafExpression,
     },
+    Accumulator {
+        column: Column,
+        computation: AccumulatorExpression,
+    },
 }
 impl AggregateComputation {
     pub fn allows_two_phase(&self) -> bool {
         match self {
-            AggregateComputation::Builtin { computation, .. } => computation.allows_two_phase(),
+            // ARRAY_AGG is computed in two phases by an accumulator
+            AggregateComputation::Builtin { computation, .. } => {
+                computation.allows_two_phase() || computation.aggregator == Aggregator::ArrayAgg
+            }
             AggregateComputation::UDAF { .. } => false,
+            AggregateComputation::Accumulator { .. } => true,
         }
     }
     pub fn try_from_expression(
@@ -1547,6 +1554,14 @@ impl AggregateComputation {
         match expr {
             Expr::Alias(Alias { expr, .. }) => Self::try_from_expression(ctx, column, expr),
             Expr::AggregateFunction(aggregate_function) => {
+                if let Some(computation) =
+                    AccumulatorExpression::try_from_aggregate_function(ctx, aggregate_function)?
+                {
+                    return Ok(Self::Accumulator {
+                        column: Column::convert(column),
+                        computation,
+                    });
+                }
                 let computation =
                     AggregationExpression::try_from_aggregate_function(ctx, aggregate_function)?;
                 Ok(Self::Builtin {
@@ -1555,6 +1570,14 @@ impl AggregateComputation {
                 })
             }
             Expr::AggregateUDF(aggregate_udf) => {
+                if let Some(computation) =
+                    AccumulatorExpression::try_from_aggregate_udf(ctx, aggregate_udf)?
+                {
+                    return Ok(Self::Accumulator {
+                        column: Column::convert(column),
+                        computation,
+                    });
+                }
                 let computation = RustUdafExpression::try_from_aggregate_udf(ctx, aggregate_udf)?;
                 Ok(Self::UDAF {
                     column: Column::convert(column),
@@ -1568,7 +1591,8 @@ impl AggregateComputation {
     pub(crate) fn column(&self) -> Column {
         match self {
             AggregateComputation::Builtin { column, .. }
-            | AggregateComputation::UDAF { column, .. } => column.clone(),
+            | AggregateComputation::UDAF { column, .. }
+            | AggregateComputation::Accumulator { column, .. } => column.clone(),
         }
     }
 }
@@ -1580,6 +1604,9 @@ impl CodeGenerator<VecOfPointersContext, TypeDef, syn::Expr> for AggregateComput
                 computation.generate(input_context)
             }
             AggregateComputation::UDAF { computation, .. } => computation.generate(input_context),
+            AggregateComputation::Accumulator { computation, .. } => {
+                computation.generate(input_context)
+            }
         }
     }
 
@@ -1591,6 +1618,9 @@ impl CodeGenerator<VecOfPointersContext, TypeDef, syn::Expr> for AggregateComput
             AggregateComputation::UDAF { computation, .. } => {
                 computation.expression_type(input_context)
             }
+            AggregateComputation::Accumulator { computation, .. } => {
+                computation.expression_type(input_context)
+            }
         }
     }
 }
@@ -1603,6 +1633,7 @@ pub enum Aggregator {
     Max,
     Avg,
     CountDistinct,
+    ArrayAgg,
 }
 
 impl Aggregator {
@@ -1617,13 +1648,20 @@ impl Aggregator {
             (datafusion_expr::AggregateFunction::Max, false) => Ok(Self::Max),
             (datafusion_expr::AggregateFunction::Avg, false) => Ok(Self::Avg),
             (datafusion_expr::AggregateFunction::Count, true) => Ok(Self::CountDistinct),
+            (datafusion_expr::AggregateFunction::ArrayAgg, false) => Ok(Self::ArrayAgg),
             (aggregator, true) => bail!("distinct not supported for {:?}", aggregator),
             (aggregator, false) => bail!("aggregator {:?} not supported yet", aggregator),
         }
     }
 
+    /// Whether the result depends on the order in which values are aggregated, and so whether an
+    /// ORDER BY within the aggregate needs to be respected.
+    pub fn order_sensitive(&self) -> bool {
+        matches!(self, Aggregator::ArrayAgg)
+    }
+
     pub fn return_data_type(&self, input_type: TypeDef) -> DataType {
-        let (input_type, _) = match input_type {
+        let (input_type, input_nullable) = match input_type {
             TypeDef::StructDef(_, _) => unreachable!("aggregates over structs not supported"),
             TypeDef::DataType(arg_type, nullable) => (arg_type, nullable),
         };
@@ -1638,6 +1676,9 @@ impl Aggregator {
                 avg_return_type(&input_type).expect("data fusion should've validated types")
             }
             Aggregator::CountDistinct => DataType::Int64,
+            Aggregator::ArrayAgg => {
+                DataType::List(Arc::new(Field::new("item", input_type, input_nullable)))
+            }
         }
     }
 }
@@ -1646,6 +1687,10 @@ impl Aggregator {
 pub struct AggregationExpression {
     pub producing_expression: Box<Expression>,
     pub aggregator: Aggregator,
+    // only rows for which the filter is true are aggregated
+    pub filter: Option<Box<Expression>>,
+    // the order in which rows are aggregated, for order-sensitive aggregators
+    pub order_by: Vec<SortExpression>,
 }
 
 impl TryFrom<AggregationExpression> for TwoPhaseAggregation {
@@ -1653,8 +1698,18 @@ impl TryFrom<AggregationExpression> for TwoPhaseAggregation {
 
     fn try_from(aggregation_expression: AggregationExpression) -> Result<Self> {
         if aggregation_expression.allows_two_phase() {
+            // two phase aggregators all ignore nulls, so a filter can be applied by nulling out
+            // the rows that don't pass it
+            let incoming_expression = match aggregation_expression.filter {
+                Some(filter) => Expression::Case(CaseExpression::new(
+                    None,
+                    vec![(filter, aggregation_expression.producing_expression)],
+                    None,
+                )),
+                None => *aggregation_expression.producing_expression,
+            };
             Ok(TwoPhaseAggregation {
-                incoming_expression: *aggregation_expression.producing_expression,
+                incoming_expression,
                 aggregator: aggregation_expression.aggregator,
             })
         } else {
@@ -1666,19 +1721,27 @@ impl TryFrom<AggregationExpression> for TwoPhaseAggregation {
     }
 }
 
-impl AggregationExpression {
-    fn new(
-        producing_expression: Box<Expression>,
-        aggregator: aggregate_function::AggregateFunction,
-        distinct: bool,
-    ) -> Result<Expression> {
-        let aggregator = Aggregator::from_datafusion(aggregator, distinct)?;
-        Ok(Expression::Aggregation(Self {
-            producing_expression,
-            aggregator,
-        }))
-    }
+/// Compiles the FILTER clause of an aggregate, which must be a boolean expression
+fn compile_aggregate_filter(
+    ctx: &ExpressionContext,
+    filter: &Option<Box<Expr>>,
+) -> Result<Option<Box<Expression>>> {
+    filter
+        .as_ref()
+        .map(|filter| {
+            let filter = ctx.compile_expr(filter)?;
+            if !matches!(
+                filter.expression_type(&ValuePointerContext::new()),
+                TypeDef::DataType(DataType::Boolean, _)
+            ) {
+                bail!("aggregate filter must be a boolean expression");
+            }
+            Ok(Box::new(filter))
+        })
+        .transpose()
+}
 
+impl AggregationExpression {
     pub(crate) fn allows_two_phase(&self) -> bool {
         match self.aggregator {
             Aggregator::Count
@@ -1686,34 +1749,88 @@ impl AggregationExpression {
             | Aggregator::Min
             | Aggregator::Avg
             | Aggregator::Max => true,
-            Aggregator::CountDistinct => false,
+            Aggregator::CountDistinct | Aggregator::ArrayAgg => false,
         }
     }
 
+    pub fn expressions(&mut self) -> impl Iterator<Item = &mut Expression> {
+        once(&mut *self.producing_expression)
+            .chain(self.filter.iter_mut().map(|filter| &mut **filter))
+            .chain(self.order_by.iter_mut().map(|sort| sort.expression()))
+    }
+
     pub fn try_from_aggregate_function(
-        ctx: &mut ExpressionContext,
+        ctx: &ExpressionContext,
         aggregate_function: &datafusion_expr::expr::AggregateFunction,
     ) -> Result<Self> {
-        if aggregate_function.filter.is_some() {
-            bail!("Not supporting aggregate filters right now");
-        }
-        if aggregate_function.order_by.is_some() {
-            bail!("Not supporting aggregate sorts right now");
-        }
         let fun = &aggregate_function.fun;
         let distinct = aggregate_function.distinct;
         let args = &aggregate_function.args;
 
         if args.len() != 1 {
-            bail!("unexpected arg length");
+            bail!("multiple aggregation parameters is not yet supported");
         }
         let producing_expression = Box::new(ctx.compile_expr(&args[0])?);
         let aggregator = Aggregator::from_datafusion(fun.clone(), distinct)?;
+
+        let filter = compile_aggregate_filter(ctx, &aggregate_function.filter)?;
+
+        // ordering only affects the result of order-sensitive aggregators, so it can be dropped
+        // for everything else
+        let order_by = match &aggregate_function.order_by {
+            Some(order_by) if aggregator.order_sensitive() => order_by
+                .iter()
+                .map(|expr| {
+                    if let Expr::Sort(sort) = expr {
+                        SortExpression::from_expression(ctx, sort)
+                    } else {
+                        bail!(
+                            "expected aggregate ordering to be a sort expression, not {}",
+                            expr
+                        )
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?,
+            _ => vec![],
+        };
+
         Ok(AggregationExpression {
             producing_expression,
             aggregator,
+            filter,
+            order_by,
         })
     }
+
+    /// The rows of the input vector that should be aggregated, in aggregation order.
+    fn values(&self, input_context: &VecOfPointersContext) -> syn::Expr {
+        let vec_ident = input_context.variable_ident();
+        let single_value_context = ValuePointerContext::new();
+        let single_value_ident = single_value_context.variable_ident();
+
+        let mut values: syn::Expr = parse_quote!(#vec_ident.iter());
+        if let Some(filter) = &self.filter {
+            let filter_expr = filter.generate(&single_value_context);
+            let filter_expr: syn::Expr =
+                if filter.expression_type(&single_value_context).is_optional() {
+                    parse_quote!(#filter_expr.unwrap_or(false))
+                } else {
+                    filter_expr
+                };
+            values = parse_quote!(#values.filter(|#single_value_ident| #filter_expr));
+        }
+
+        if !self.order_by.is_empty() {
+            let sort_key = SortExpression::sort_tuple_expression(&self.order_by);
+            values = parse_quote!({
+                let mut values: Vec<_> = #values.collect();
+                values.sort_by_key(|#single_value_ident| #sort_key);
+                values.into_iter()
+            });
+        }
+
+        values
+    }
 }
 
 impl CodeGenerator<VecOfPointersContext, TypeDef, syn::Expr> for AggregationExpression {
@@ -1722,6 +1839,7 @@ impl CodeGenerator<VecOfPointersContext, TypeDef, syn::Expr> for AggregationExpr
         let sub_expr = self.producing_expression.generate(&single_value_context);
         let single_value_ident = single_value_context.variable_ident();
         let vec_ident = input_context.variable_ident();
+        let values = self.values(input_context);
         let producing_expression_is_optional = self
             .producing_expression
             .expression_type(&single_value_context)
@@ -1731,7 +1849,7 @@ impl CodeGenerator<VecOfPointersContext, TypeDef, syn::Expr> for AggregationExpr
         } else {
             quote!(map)
         };
-        let unwrap = if producing_expression_is_optional {
+        let unwrap = if self.expression_type(input_context).is_optional() {
             None
         } else {
             Some(quote!(.unwrap()))
@@ -1741,34 +1859,36 @@ impl CodeGenerator<VecOfPointersContext, TypeDef, syn::Expr> for AggregationExpr
             Aggregator::Count => {
                 if producing_expression_is_optional {
                     parse_quote!({
-                        #vec_ident.iter()
+                        #values
                             .filter_map(|#single_value_ident| #sub_expr)
                             .count() as i64
                     })
+                } else if self.filter.is_some() {
+                    parse_quote!((#values.count() as i64))
                 } else {
                     parse_quote!((#vec_ident.len() as i64))
                 }
             }
             Aggregator::Sum => parse_quote!({
-                #vec_ident.iter()
+                #values
                     .#map_type(|#single_value_ident| #sub_expr)
                     .reduce(|left, right| left + right)
                     #unwrap
             }),
             Aggregator::Min => parse_quote!({
-                #vec_ident.iter()
+                #values
                     .#map_type(|#single_value_ident| #sub_expr)
                     .reduce( |left, right| left.min(right))
                     #unwrap
             }),
             Aggregator::Max => parse_quote!({
-                #vec_ident.iter()
+                #values
                     .#map_type(|#single_value_ident| #sub_expr)
                     .reduce(|left, right| left.max(right))
                     #unwrap
             }),
             Aggregator::Avg => parse_quote!({
-                #vec_ident.iter()
+                #values
                     .#map_type(|#single_value_ident| #sub_expr)
                     .map(|val| (1, val))
                     .reduce(|left, right| (left.0 + right.0, left.1+right.1))
@@ -1776,11 +1896,16 @@ impl CodeGenerator<VecOfPointersContext, TypeDef, syn::Expr> for AggregationExpr
                     #unwrap
             }),
             Aggregator::CountDistinct => parse_quote! ({
-                #vec_ident.iter()
+                #values
                     .#map_type(|#single_value_ident| #sub_expr)
                     .collect::<std::collections::HashSet<_>>()
                     .len() as i64
             }),
+            Aggregator::ArrayAgg => parse_quote!({
+                #values
+                    .map(|#single_value_ident| #sub_expr)
+                    .collect::<Vec<_>>()
+            }),
         }
     }
 
@@ -1789,12 +1914,20 @@ impl CodeGenerator<VecOfPointersContext, TypeDef, syn::Expr> for AggregationExpr
             Aggregator::Count | Aggregator::CountDistinct => {
                 TypeDef::DataType(DataType::Int64, false)
             }
+            Aggregator::ArrayAgg => {
+                let single_value_context = ValuePointerContext::new();
+                let input_type = self
+                    .producing_expression
+                    .expression_type(&single_value_context);
+                TypeDef::DataType(self.aggregator.return_data_type(input_type), false)
+            }
             aggregator => {
                 let single_value_context = ValuePointerContext::new();
                 let input_type = self
                     .producing_expression
                     .expression_type(&single_value_context);
-                let is_optional = input_type.is_optional();
+                // if a filter is present there may be no rows to aggregate
+                let is_optional = input_type.is_optional() || self.filter.is_some();
                 TypeDef::DataType(aggregator.return_data_type(input_type), is_optional)
             }
         }
@@ -2096,7 +2229,7 @@ impl SortExpression {
         &mut self.value
     }
 
-    pub fn from_expression(ctx: &mut ExpressionContext, sort: &Sort) -> Result<Self> {
+    pub fn from_expression(ctx: &ExpressionContext, sort: &Sort) -> Result<Self> {
         let value = ctx.compile_expr(&sort.expr)?;
 
         let direction = if sort.asc {
@@ -2182,7 +2315,26 @@ impl SortExpression {
             self.value.clone()
         };
 
-        let value_expr = value.generate(input_context);
+        self.sort_key(value.generate(input_context), input_context)
+    }
+
+    /// The sort key for a value of the expression that was computed earlier, like one held in
+    /// the state of an aggregate
+    fn sort_key_for_value(
+        &self,
+        value: syn::Expr,
+        input_context: &ValuePointerContext,
+    ) -> syn::Expr {
+        let value_type = self.value.expression_type(input_context);
+        let value = match (value_type.is_float(), value_type.is_optional()) {
+            (true, true) => parse_quote!(#value.map(arroyo_worker::OrderedFloat)),
+            (true, false) => parse_quote!(arroyo_worker::OrderedFloat(#value)),
+            (false, _) => value,
+        };
+        self.sort_key(value, input_context)
+    }
+
+    fn sort_key(&self, value_expr: syn::Expr, input_context: &ValuePointerContext) -> syn::Expr {
         match (
             self.value.expression_type(input_context).is_optional(),
             &self.direction,
@@ -3836,6 +3988,391 @@ impl CodeGenerator<VecOfPointersContext, TypeDef, syn::Expr> for RustUdafExpress
     }
 }
 
+/// Aggregates that are computed incrementally, by folding rows into a state that can be merged
+/// with (and, for some, retracted from) the states of other bins. The hooks are the same for the
+/// built-ins in `arroyo_worker::operators::accumulators` and for user-defined aggregates.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd)]
+pub enum Accumulator {
+    Udaf {
+        name: String,
+        state_type: String,
+        ret_type: TypeDef,
+        retractable: bool,
+    },
+    CovarianceSample,
+    CovariancePopulation,
+    Correlation,
+    ArgMax,
+    ArgMin,
+    // ARRAY_AGG, which is otherwise computed over all of the values in a window at once
+    ArrayAgg,
+}
+
+impl Accumulator {
+    fn from_datafusion(aggregator: &aggregate_function::AggregateFunction) -> Option<Self> {
+        match aggregator {
+            aggregate_function::AggregateFunction::Covariance => Some(Self::CovarianceSample),
+            aggregate_function::AggregateFunction::CovariancePop => {
+                Some(Self::CovariancePopulation)
+            }
+            aggregate_function::AggregateFunction::Correlation => Some(Self::Correlation),
+            _ => None,
+        }
+    }
+
+    fn from_udaf(ctx: &ExpressionContext, name: &str) -> Option<Self> {
+        match name {
+            "arg_max" => Some(Self::ArgMax),
+            "arg_min" => Some(Self::ArgMin),
+            udaf => {
+                let def = ctx.schema_provider.udf_defs.get(udaf)?;
+                let accumulator = def.accumulator.as_ref()?;
+                Some(Self::Udaf {
+                    name: udaf.to_string(),
+                    state_type: accumulator.state_type.clone(),
+                    ret_type: def.ret.clone(),
+                    retractable: accumulator.retractable,
+                })
+            }
+        }
+    }
+
+    fn path(&self) -> Path {
+        match self {
+            Accumulator::Udaf { name, .. } => {
+                let name = format_ident!("{}", name);
+                parse_quote!(udfs::#name)
+            }
+            Accumulator::CovarianceSample => {
+                parse_quote!(arroyo_worker::operators::accumulators::covar_samp)
+            }
+            Accumulator::CovariancePopulation => {
+                parse_quote!(arroyo_worker::operators::accumulators::covar_pop)
+            }
+            Accumulator::Correlation => parse_quote!(arroyo_worker::operators::accumulators::corr),
+            Accumulator::ArgMax => parse_quote!(arroyo_worker::operators::accumulators::arg_max),
+            Accumulator::ArgMin => parse_quote!(arroyo_worker::operators::accumulators::arg_min),
+            Accumulator::ArrayAgg => {
+                parse_quote!(arroyo_worker::operators::accumulators::array_agg)
+            }
+        }
+    }
+
+    fn is_statistical(&self) -> bool {
+        matches!(
+            self,
+            Accumulator::CovarianceSample
+                | Accumulator::CovariancePopulation
+                | Accumulator::Correlation
+        )
+    }
+
+    pub(crate) fn retractable(&self) -> bool {
+        match self {
+            Accumulator::Udaf { retractable, .. } => *retractable,
+            Accumulator::CovarianceSample
+            | Accumulator::CovariancePopulation
+            | Accumulator::Correlation
+            | Accumulator::ArrayAgg => true,
+            Accumulator::ArgMax | Accumulator::ArgMin => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd)]
+pub struct AccumulatorExpression {
+    pub accumulator: Accumulator,
+    // the parameter types of the accumulate hook, along with the expressions passed to them
+    pub args: Vec<(TypeDef, Expression)>,
+    // only rows for which the filter is true are accumulated
+    pub filter: Option<Box<Expression>>,
+    // the order of the accumulated values, for ARRAY_AGG
+    pub order_by: Vec<SortExpression>,
+}
+
+impl AccumulatorExpression {
+    fn new(
+        ctx: &ExpressionContext,
+        accumulator: Accumulator,
+        args: &[Expr],
+        filter: &Option<Box<Expr>>,
+    ) -> Result<Self> {
+        let args = args
+            .iter()
+            .map(|arg| ctx.compile_expr(arg))
+            .collect::<Result<Vec<_>>>()?;
+
+        let params = match &accumulator {
+            Accumulator::Udaf { name, .. } => ctx
+                .schema_provider
+                .udf_defs
+                .get(name)
+                .ok_or_else(|| anyhow!("no UDAF with name '{}'", name))?
+                .args
+                .clone(),
+            accumulator if accumulator.is_statistical() => {
+                vec![TypeDef::DataType(DataType::Float64, false); 2]
+            }
+            Accumulator::ArgMax | Accumulator::ArgMin => {
+                for arg in &args {
+                    if matches!(
+                        arg.expression_type(&ValuePointerContext::new()),
+                        TypeDef::StructDef(_, _)
+                    ) {
+                        bail!("arguments to arg_max and arg_min can't be structs");
+                    }
+                }
+                // rows where either the value or the ordering is null are skipped
+                args.iter()
+                    .map(|arg| {
+                        arg.expression_type(&ValuePointerContext::new())
+                            .with_nullity(false)
+                    })
+                    .collect()
+            }
+            _ => unreachable!(),
+        };
+
+        if args.len() != params.len() {
+            bail!(
+                "wrong number of arguments for aggregate {:?} (found {}, expected {})",
+                accumulator,
+                args.len(),
+                params.len()
+            );
+        }
+
+        let filter = compile_aggregate_filter(ctx, filter)?;
+
+        Ok(Self {
+            accumulator,
+            args: params.into_iter().zip(args).collect(),
+            filter,
+            order_by: vec![],
+        })
+    }
+
+    /// ARRAY_AGG keeps every value along with its sort key, so that it can be computed
+    /// incrementally and values can be retracted.
+    pub(crate) fn array_agg(aggregation: AggregationExpression) -> Self {
+        let input_type = aggregation
+            .producing_expression
+            .expression_type(&ValuePointerContext::new());
+        Self {
+            accumulator: Accumulator::ArrayAgg,
+            args: vec![(input_type, *aggregation.producing_expression)],
+            filter: aggregation.filter,
+            order_by: aggregation.order_by,
+        }
+    }
+
+    pub(crate) fn try_from_aggregate_function(
+        ctx: &ExpressionContext,
+        aggregate_function: &datafusion_expr::expr::AggregateFunction,
+    ) -> Result<Option<Self>> {
+        let Some(accumulator) = Accumulator::from_datafusion(&aggregate_function.fun) else {
+            return Ok(None);
+        };
+        if aggregate_function.distinct {
+            bail!("distinct not supported for {:?}", aggregate_function.fun);
+        }
+        Ok(Some(Self::new(
+            ctx,
+            accumulator,
+            &aggregate_function.args,
+            &aggregate_function.filter,
+        )?))
+    }
+
+    pub(crate) fn try_from_aggregate_udf(
+        ctx: &ExpressionContext,
+        aggregate_udf: &AggregateUDF,
+    ) -> Result<Option<Self>> {
+        let Some(accumulator) = Accumulator::from_udaf(ctx, &aggregate_udf.fun.name) else {
+            return Ok(None);
+        };
+        if aggregate_udf.order_by.is_some() {
+            bail!("Not supporting UDAF sorts right now, as datafusion doesn't");
+        }
+        Ok(Some(Self::new(
+            ctx,
+            accumulator,
+            &aggregate_udf.args,
+            &aggregate_udf.filter,
+        )?))
+    }
+
+    pub(crate) fn name(&self) -> Option<String> {
+        match &self.accumulator {
+            Accumulator::Udaf { name, .. } => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn expressions(&mut self) -> impl Iterator<Item = &mut Expression> {
+        self.args
+            .iter_mut()
+            .map(|(_, e)| e)
+            .chain(self.filter.iter_mut().map(|filter| &mut **filter))
+            .chain(self.order_by.iter_mut().map(|sort| sort.expression()))
+    }
+
+    pub(crate) fn retractable(&self) -> bool {
+        self.accumulator.retractable()
+    }
+
+    pub(crate) fn state_type(&self) -> syn::Type {
+        match &self.accumulator {
+            Accumulator::Udaf { state_type, .. } => parse_str(state_type).unwrap(),
+            Accumulator::ArgMax | Accumulator::ArgMin => {
+                let value_type = self.args[0].0.return_type();
+                let by_type = self.args[1].0.return_type();
+                parse_quote!(Option<(#by_type, #value_type)>)
+            }
+            Accumulator::ArrayAgg => {
+                let value_type = self.args[0].0.return_type();
+                let key_types = self.order_by.iter().map(|sort| {
+                    sort.value
+                        .expression_type(&ValuePointerContext::new())
+                        .return_type()
+                });
+                parse_quote!(Vec<((#(#key_types,)*), #value_type)>)
+            }
+            _ => parse_quote!(arroyo_worker::operators::accumulators::CovarianceState),
+        }
+    }
+
+    pub(crate) fn return_type(&self) -> TypeDef {
+        match &self.accumulator {
+            Accumulator::Udaf { ret_type, .. } => ret_type.clone(),
+            Accumulator::ArgMax | Accumulator::ArgMin => self.args[0].0.with_nullity(true),
+            Accumulator::ArrayAgg => TypeDef::DataType(
+                Aggregator::ArrayAgg.return_data_type(self.args[0].0.clone()),
+                false,
+            ),
+            _ => TypeDef::DataType(DataType::Float64, true),
+        }
+    }
+
+    /// Folds the row in the value context into the accumulator state held in `state_ident`.
+    pub(crate) fn accumulate(
+        &self,
+        state_ident: &Ident,
+        input_context: &ValuePointerContext,
+    ) -> syn::Expr {
+        let path = self.accumulator.path();
+        let mut patterns: Vec<TokenStream> = vec![];
+        let mut values: Vec<syn::Expr> = vec![];
+        let mut call_args: Vec<syn::Expr> = vec![];
+        let mut refutable = false;
+        for (i, (param, expr)) in self.args.iter().enumerate() {
+            let ident = format_ident!("accumulator_arg_{}", i);
+            let value = expr.generate(input_context);
+            match (
+                param.is_optional(),
+                expr.expression_type(input_context).is_optional(),
+            ) {
+                (false, true) => {
+                    refutable = true;
+                    patterns.push(quote!(Some(#ident)));
+                    values.push(value);
+                }
+                (true, false) => {
+                    patterns.push(quote!(#ident));
+                    values.push(parse_quote!(Some(#value)));
+                }
+                _ => {
+                    patterns.push(quote!(#ident));
+                    values.push(value);
+                }
+            }
+            if self.accumulator.is_statistical() {
+                call_args.push(parse_quote!(#ident as f64));
+            } else {
+                call_args.push(parse_quote!(#ident));
+            }
+        }
+
+        if matches!(self.accumulator, Accumulator::ArrayAgg) {
+            // the values are kept along with their sort keys, which are only compared once the
+            // array is produced
+            let keys = self
+                .order_by
+                .iter()
+                .map(|sort| sort.value.generate(input_context));
+            call_args.push(parse_quote!((#(#keys,)*)));
+        }
+
+        let call = quote!(#path::accumulate(&mut #state_ident, #(#call_args),*));
+        let accumulate: syn::Expr = if refutable {
+            parse_quote!(if let (#(#patterns,)*) = (#(#values,)*) { #call; })
+        } else {
+            parse_quote!({ let (#(#patterns,)*) = (#(#values,)*); #call; })
+        };
+
+        match &self.filter {
+            Some(filter) => {
+                let filter_expr = filter.generate(input_context);
+                let filter_expr: syn::Expr = if filter.expression_type(input_context).is_optional()
+                {
+                    parse_quote!(#filter_expr.unwrap_or(false))
+                } else {
+                    filter_expr
+                };
+                parse_quote!(if #filter_expr { #accumulate })
+            }
+            None => accumulate,
+        }
+    }
+
+    pub(crate) fn merge(&self, state_ident: &Ident, other: &Ident) -> syn::Expr {
+        let path = self.accumulator.path();
+        parse_quote!(#path::merge(&mut #state_ident, &#other))
+    }
+
+    pub(crate) fn retract(&self, state_ident: &Ident, other: &Ident) -> syn::Expr {
+        let path = self.accumulator.path();
+        parse_quote!(#path::retract(&mut #state_ident, &#other))
+    }
+
+    pub(crate) fn finish(&self, state: &Ident) -> syn::Expr {
+        let path = self.accumulator.path();
+        if matches!(self.accumulator, Accumulator::ArrayAgg) {
+            let input_context = ValuePointerContext::new();
+            let sort_keys = self.order_by.iter().enumerate().map(|(i, sort)| {
+                let i = syn::Index::from(i);
+                sort.sort_key_for_value(parse_quote!(keys.#i.clone()), &input_context)
+            });
+            return parse_quote!(#path::finish(#state, |keys| (#(#sort_keys,)*)));
+        }
+        parse_quote!(#path::finish(#state))
+    }
+}
+
+impl CodeGenerator<VecOfPointersContext, TypeDef, syn::Expr> for AccumulatorExpression {
+    fn generate(&self, input_context: &VecOfPointersContext) -> syn::Expr {
+        let vec_ident = input_context.variable_ident();
+        let single_value_context = ValuePointerContext::new();
+        let single_value_ident = single_value_context.variable_ident();
+        let state_ident = format_ident!("state");
+        let state_type = self.state_type();
+        let accumulate = self.accumulate(&state_ident, &single_value_context);
+        let finish = self.finish(&state_ident);
+        parse_quote!({
+            let mut #state_ident: #state_type = Default::default();
+            for #single_value_ident in #vec_ident.iter() {
+                #accumulate;
+            }
+            let #state_ident = &#state_ident;
+            #finish
+        })
+    }
+
+    fn expression_type(&self, _input_context: &VecOfPointersContext) -> TypeDef {
+        self.return_type()
+    }
+}
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd)]
 pub struct WrapTypeExpression {
     name: String,
@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use arroyo_datastream::Operator;
+use arroyo_datastream::{ConnectorOp, Operator};
 
 use crate::types::StructDef;
 
@@ -27,6 +27,36 @@ pub struct SqlSink {
     pub updating_type: SinkUpdateType,
 }
 
+#[derive(Clone, Debug)]
+pub struct SqlLookup {
+    pub id: Option<i64>,
+    pub name: String,
+    pub struct_def: StructDef,
+    pub connector: ConnectorOp,
+    /// the primary key of the lookup table, which joins must be on
+    pub key_column: String,
+    pub options: LookupOptions,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupOptions {
+    pub cache_ttl: Option<Duration>,
+    pub max_cache_entries: usize,
+    pub max_batch_size: usize,
+    pub max_batch_wait: Duration,
+}
+
+impl Default for LookupOptions {
+    fn default() -> Self {
+        Self {
+            cache_ttl: None,
+            max_cache_entries: 100_000,
+            max_batch_size: 100,
+            max_batch_wait: Duration::from_millis(10),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SinkUpdateType {
     Allow,
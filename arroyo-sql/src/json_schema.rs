@@ -65,8 +65,13 @@ pub fn convert_json_schema(name: &str, schema: &str) -> Result<Vec<StructField>,
 }
 
 pub fn get_defs(source_name: &str, schema: &str) -> Result<String, String> {
-    fn add_defs(source_name: &str, name: &str, fields: &Vec<StructField>, defs: &mut Vec<String>) {
-        let struct_fields: Vec<_> = fields.iter().map(|f| {
+    fn add_defs(
+        source_name: &str,
+        name: &str,
+        fields: &Vec<StructField>,
+        defs: &mut Vec<String>,
+    ) -> Result<(), String> {
+        let struct_fields = fields.iter().map(|f| {
             let mut serde_opts = vec![];
             if let Some(opt) = match (&f.data_type, f.original_type.as_ref().map(|s| s.as_str())) {
                 (TypeDef::DataType(DataType::Utf8, nullable), Some("json")) => {
@@ -109,7 +114,7 @@ pub fn get_defs(source_name: &str, schema: &str) -> Result<String, String> {
                 TypeDef::StructDef(sd, _) => {
                     let name = sd.name.as_ref().unwrap().replace(&format!("{}::", source_name), "");
 
-                    add_defs(source_name, &name, &sd.fields, defs);
+                    add_defs(source_name, &name, &sd.fields, defs)?;
 
                     name
                 },
@@ -123,14 +128,15 @@ pub fn get_defs(source_name: &str, schema: &str) -> Result<String, String> {
                 quote! { #typ }
             };
 
-            quote! {
+            Ok(quote! {
                 #(#serde_opts) *
                 pub #ident: #typ
-            }
-        }).collect();
+            })
+        }).collect::<Result<Vec<_>, String>>()?;
 
         let serializer_items = StructDef::new(Some(name.to_string()), true, fields.clone(), None)
-            .generate_serializer_items();
+            .generate_serializer_items()
+            .map_err(|e| e.to_string())?;
         let name = format_ident!("{}", name);
         defs.push(quote!{
             #[derive(Clone, Debug, bincode::Encode, bincode::Decode, PartialEq,  PartialOrd, serde::Serialize, serde::Deserialize)]
@@ -141,13 +147,15 @@ pub fn get_defs(source_name: &str, schema: &str) -> Result<String, String> {
 
             #serializer_items
         }.to_string());
+
+        Ok(())
     }
 
     let fields = convert_json_schema(source_name, schema)?;
 
     let mut defs: Vec<String> = vec![];
 
-    add_defs(source_name, ROOT_NAME, &fields, &mut defs);
+    add_defs(source_name, ROOT_NAME, &fields, &mut defs)?;
 
     Ok(format!(
         "mod {} {{\nuse super::*;\n{}\n}}",
@@ -13,18 +13,23 @@ pub(crate) mod code_gen;
 pub mod expressions;
 pub mod external;
 pub mod json_schema;
+mod match_recognize;
 mod operators;
 mod optimizations;
 mod pipeline;
 mod plan_graph;
+pub mod protobuf;
 pub mod schemas;
 mod tables;
 pub mod types;
 
 use datafusion::prelude::create_udf;
 
+use datafusion::sql::sqlparser::ast::{Expr as SqlExpr, Statement, Value as SqlValue};
 use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use datafusion::sql::sqlparser::keywords::{Keyword, RESERVED_FOR_TABLE_ALIAS};
 use datafusion::sql::sqlparser::parser::Parser;
+use datafusion::sql::sqlparser::tokenizer::{Token, TokenWithLocation, Tokenizer};
 use datafusion::sql::{planner::ContextProvider, TableReference};
 
 use datafusion_expr::{
@@ -32,9 +37,10 @@ use datafusion_expr::{
 };
 use datafusion_expr::{
     AccumulatorFactoryFunction, LogicalPlan, ReturnTypeFunction, Signature, StateTypeFunction,
-    Volatility, WindowUDF,
+    TypeSignature, Volatility, WindowUDF,
 };
 use expressions::{Expression, ExpressionContext};
+use match_recognize::{extract_match_recognize, resolve_match_recognize};
 use pipeline::{SqlOperator, SqlPipelineBuilder};
 use plan_graph::{get_program, PlanGraph};
 use schemas::window_arrow_struct;
@@ -46,13 +52,14 @@ use arroyo_rpc::api_types::connections::{ConnectionProfile, ConnectionSchema, Co
 use arroyo_rpc::formats::{Format, JsonFormat};
 use datafusion_common::DataFusionError;
 use prettyplease::unparse;
+use quote::ToTokens;
 use regex::Regex;
 use std::collections::HashSet;
 
 use arroyo_rpc::OperatorConfig;
 use std::time::{Duration, SystemTime};
 use std::{collections::HashMap, sync::Arc};
-use syn::{parse_file, parse_quote, parse_str, FnArg, Item, ReturnType, Visibility};
+use syn::{parse_file, parse_quote, parse_str, FnArg, Item, ItemFn, ReturnType, Visibility};
 use tracing::warn;
 use unicase::UniCase;
 
@@ -67,6 +74,15 @@ pub struct UdfDef {
     ret: TypeDef,
     def: String,
     dependencies: String,
+    accumulator: Option<UdafAccumulatorDef>,
+}
+
+/// The accumulator of a UDAF defined as a module of `accumulate`, `merge`, `retract` and `finish`
+/// functions, which lets it be computed incrementally instead of over a vector of all inputs.
+#[derive(Clone, Debug)]
+pub struct UdafAccumulatorDef {
+    state_type: String,
+    retractable: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -86,6 +102,10 @@ pub struct ArroyoSchemaProvider {
     profiles: HashMap<String, ConnectionProfile>,
     pub udf_defs: HashMap<String, UdfDef>,
     config_options: datafusion::config::ConfigOptions,
+    // tables joined `FOR SYSTEM_TIME AS OF` an event-time column, which are planned as temporal
+    // joins, keyed by the name they are referenced by (their alias, if they have one) and mapped
+    // to the parts of the name of that column
+    pub temporal_tables: HashMap<String, Vec<String>>,
 }
 
 impl ArroyoSchemaProvider {
@@ -120,15 +140,39 @@ impl ArroyoSchemaProvider {
             )),
         );
         functions.insert(
-            "session".to_string(),
+            "cumulate".to_string(),
             Arc::new(create_udf(
-                "session",
-                vec![DataType::Interval(datatypes::IntervalUnit::MonthDayNano)],
-                window_return_type,
+                "cumulate",
+                vec![
+                    DataType::Interval(datatypes::IntervalUnit::MonthDayNano),
+                    DataType::Interval(datatypes::IntervalUnit::MonthDayNano),
+                ],
+                window_return_type.clone(),
                 Volatility::Volatile,
                 make_scalar_function(fn_impl),
             )),
         );
+        functions.insert(
+            "session".to_string(),
+            Arc::new({
+                // session(gap) or session(gap, max_length)
+                let interval = DataType::Interval(datatypes::IntervalUnit::MonthDayNano);
+                let return_type: ReturnTypeFunction =
+                    Arc::new(move |_| Ok(window_return_type.clone()));
+                ScalarUDF::new(
+                    "session",
+                    &Signature::one_of(
+                        vec![
+                            TypeSignature::Exact(vec![interval.clone()]),
+                            TypeSignature::Exact(vec![interval.clone(), interval]),
+                        ],
+                        Volatility::Volatile,
+                    ),
+                    &return_type,
+                    &make_scalar_function(fn_impl),
+                )
+            }),
+        );
         functions.insert(
             "unnest".to_string(),
             Arc::new({
@@ -201,15 +245,37 @@ impl ArroyoSchemaProvider {
             )),
         );
 
+        let mut aggregate_functions = HashMap::new();
+        for name in ["arg_max", "arg_min"] {
+            let return_type: ReturnTypeFunction = Arc::new(move |args| {
+                Ok(Arc::new(args.get(0).cloned().ok_or_else(|| {
+                    DataFusionError::Plan(format!("{} takes two arguments", name))
+                })?))
+            });
+            let accumulator: AccumulatorFactoryFunction = Arc::new(|_| unreachable!());
+            let state_type: StateTypeFunction = Arc::new(|_| unreachable!());
+            aggregate_functions.insert(
+                name.to_string(),
+                Arc::new(AggregateUDF::new(
+                    name,
+                    &Signature::any(2, Volatility::Immutable),
+                    &return_type,
+                    &accumulator,
+                    &state_type,
+                )),
+            );
+        }
+
         Self {
             tables,
             functions,
-            aggregate_functions: HashMap::new(),
+            aggregate_functions,
             source_defs: HashMap::new(),
             connections: HashMap::new(),
             profiles: HashMap::new(),
             udf_defs: HashMap::new(),
             config_options: datafusion::config::ConfigOptions::new(),
+            temporal_tables: HashMap::new(),
         }
     }
 
@@ -260,6 +326,7 @@ impl ArroyoSchemaProvider {
 
     pub fn add_rust_udf(&mut self, body: &str) -> Result<String> {
         let mut file = parse_file(body)?;
+        let defines_module = file.items.iter().any(|item| matches!(item, Item::Mod(_)));
 
         let mut functions = file.items.iter_mut().filter_map(|item| match item {
             Item::Fn(function) => Some(function),
@@ -268,6 +335,9 @@ impl ArroyoSchemaProvider {
 
         let function = match (functions.next(), functions.next()) {
             (Some(function), None) => function,
+            (None, None) if defines_module => {
+                return self.add_rust_udaf(body);
+            }
             _ => bail!("UDF definition must contain exactly 1 function."),
         };
 
@@ -318,21 +388,7 @@ impl ArroyoSchemaProvider {
             bail!("Function {} arguments must be vectors or none", name);
         }
         if vec_arguments > 0 {
-            let return_type = Arc::new(ret.as_datatype().unwrap().clone());
-            let name = function.sig.ident.to_string();
-            let signature = Signature::exact(
-                args.iter()
-                    .map(|t| t.as_datatype().unwrap().clone())
-                    .collect(),
-                Volatility::Volatile,
-            );
-            let return_type: ReturnTypeFunction = Arc::new(move |_| Ok(return_type.clone()));
-            let accumulator: AccumulatorFactoryFunction = Arc::new(|_| unreachable!());
-            let state_type: StateTypeFunction = Arc::new(|_| unreachable!());
-            let udaf =
-                AggregateUDF::new(&name, &signature, &return_type, &accumulator, &state_type);
-            self.aggregate_functions
-                .insert(function.sig.ident.to_string(), Arc::new(udaf));
+            self.add_aggregate_udf(&function.sig.ident.to_string(), &args, &ret);
         } else {
             let fn_impl = |args: &[ArrayRef]| Ok(Arc::new(args[0].clone()) as ArrayRef);
 
@@ -368,11 +424,289 @@ impl ArroyoSchemaProvider {
                 ret,
                 def: unparse(&file.clone()),
                 dependencies: parse_dependencies(&body)?,
+                accumulator: None,
             },
         );
 
         Ok(name)
     }
+
+    /// Adds a UDAF defined as a module containing the functions
+    ///
+    /// * `accumulate(state: &mut S, args...)`, which folds a row into the state
+    /// * `merge(state: &mut S, other: &S)`, which combines two partial states
+    /// * `retract(state: &mut S, other: &S)` (optional), which removes a merged partial state
+    /// * `finish(state: &S) -> T`, which computes the result
+    ///
+    /// The state `S` must implement `Default`, and is stored in the aggregator's bins, so it should be
+    /// composed of primitive types. Without `retract` the UDAF can't be used in sliding windows or
+    /// over updating inputs.
+    fn add_rust_udaf(&mut self, body: &str) -> Result<String> {
+        let mut file = parse_file(body)?;
+
+        let mut modules = file.items.iter_mut().filter_map(|item| match item {
+            Item::Mod(module) => Some(module),
+            _ => None,
+        });
+
+        let module = match (modules.next(), modules.next()) {
+            (Some(module), None) => module,
+            _ => bail!("UDAF definition must contain exactly 1 module."),
+        };
+
+        let name = module.ident.to_string();
+        let Some((_, items)) = &mut module.content else {
+            bail!("UDAF module {} must be defined inline", name);
+        };
+
+        let mut hooks: HashMap<String, &mut ItemFn> = items
+            .iter_mut()
+            .filter_map(|item| match item {
+                Item::Fn(function) => Some((function.sig.ident.to_string(), function)),
+                _ => None,
+            })
+            .collect();
+
+        for hook in ["accumulate", "merge", "finish"] {
+            if !hooks.contains_key(hook) {
+                bail!("UDAF {} must define a '{}' function", name, hook);
+            }
+        }
+        let retractable = hooks.contains_key("retract");
+
+        let accumulate = &hooks["accumulate"];
+        let mut inputs = accumulate.sig.inputs.iter();
+        let state_type = match inputs.next() {
+            Some(FnArg::Typed(t)) => match &*t.ty {
+                syn::Type::Reference(r) if r.mutability.is_some() => {
+                    r.elem.to_token_stream().to_string()
+                }
+                _ => bail!(
+                    "the first argument of {}::accumulate must be a mutable reference to the state",
+                    name
+                ),
+            },
+            _ => bail!(
+                "the first argument of {}::accumulate must be a mutable reference to the state",
+                name
+            ),
+        };
+
+        let args = inputs
+            .enumerate()
+            .map(|(i, arg)| match arg {
+                FnArg::Typed(t) => TypeDef::try_from(&*t.ty).map_err(|_| {
+                    anyhow!(
+                        "Could not convert UDAF {} accumulate arg {} into a SQL data type",
+                        name,
+                        i + 1
+                    )
+                }),
+                FnArg::Receiver(_) => bail!(
+                    "Function {}::accumulate has a 'self' argument, which is not allowed",
+                    name
+                ),
+            })
+            .collect::<Result<Vec<TypeDef>>>()?;
+
+        let ret: TypeDef = match &hooks["finish"].sig.output {
+            ReturnType::Default => bail!("Function {}::finish return type must be specified", name),
+            ReturnType::Type(_, t) => (&**t).try_into().map_err(|_| {
+                anyhow!(
+                    "Could not convert UDAF {} return type into a SQL data type",
+                    name
+                )
+            })?,
+        };
+
+        for function in hooks.values_mut() {
+            function.vis = Visibility::Public(Default::default());
+        }
+        module.vis = Visibility::Public(Default::default());
+
+        self.add_aggregate_udf(&name, &args, &ret);
+
+        self.udf_defs.insert(
+            name.clone(),
+            UdfDef {
+                args,
+                ret,
+                def: unparse(&file.clone()),
+                dependencies: parse_dependencies(&body)?,
+                accumulator: Some(UdafAccumulatorDef {
+                    state_type,
+                    retractable,
+                }),
+            },
+        );
+
+        Ok(name)
+    }
+
+    fn add_aggregate_udf(&mut self, name: &str, args: &[TypeDef], ret: &TypeDef) {
+        let return_type = Arc::new(ret.as_datatype().unwrap().clone());
+        let signature = Signature::exact(
+            args.iter()
+                .map(|t| t.as_datatype().unwrap().clone())
+                .collect(),
+            Volatility::Volatile,
+        );
+        let return_type: ReturnTypeFunction = Arc::new(move |_| Ok(return_type.clone()));
+        let accumulator: AccumulatorFactoryFunction = Arc::new(|_| unreachable!());
+        let state_type: StateTypeFunction = Arc::new(|_| unreachable!());
+        let udaf = AggregateUDF::new(name, &signature, &return_type, &accumulator, &state_type);
+        self.aggregate_functions
+            .insert(name.to_string(), Arc::new(udaf));
+    }
+}
+
+/// The byte offset in the query at which each of its tokens starts
+pub(crate) fn token_offsets(query: &str, tokens: &[TokenWithLocation]) -> Vec<usize> {
+    // byte offsets of the start of each line, to map token locations back into the query
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(query.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    tokens
+        .iter()
+        .map(|token| {
+            let line_start = line_starts[token.location.line as usize - 1];
+            query[line_start..]
+                .char_indices()
+                .nth(token.location.column as usize - 1)
+                .map(|(i, _)| line_start + i)
+                .unwrap_or(query.len())
+        })
+        .collect()
+}
+
+/// Joins may be written with the standard `FOR SYSTEM_TIME AS OF` clause on the right-hand table,
+/// which the Postgres dialect can't parse, so the clause is found in the query's tokens and removed
+/// before parsing. Lookups (`AS OF PROCTIME()`) are always made against the current value of the
+/// table, while an event-time column asks for an event-time temporal join; those table references
+/// are returned by the name they're referenced by, along with the column.
+fn strip_system_time(query: &str) -> Result<(String, HashMap<String, Vec<String>>)> {
+    let dialect = PostgreSqlDialect {};
+    let tokens = Tokenizer::new(&dialect, query).tokenize_with_location()?;
+    let offsets = token_offsets(query, &tokens);
+
+    let is_keyword = |token: &Token, keyword: Keyword| matches!(token, Token::Word(w) if w.keyword == keyword && w.quote_style.is_none());
+    // identifiers are case-insensitive unless they're quoted
+    let normalize = |value: &str, quote_style: Option<char>| match quote_style {
+        Some(_) => value.to_string(),
+        None => value.to_lowercase(),
+    };
+    let next_token = |start: usize| {
+        (start..tokens.len()).find(|j| !matches!(tokens[*j].token, Token::Whitespace(_)))
+    };
+
+    let mut removed = vec![];
+    let mut temporal_tables = HashMap::new();
+    // the last table name in the query, and the index of the token after it
+    let mut table: Option<(String, usize)> = None;
+    let mut i = 0;
+    while i < tokens.len() {
+        let clause: Vec<usize> = (i..tokens.len())
+            .filter(|j| !matches!(tokens[*j].token, Token::Whitespace(_)))
+            .take(4)
+            .collect();
+        let is_clause = clause.len() == 4
+            && [Keyword::FOR, Keyword::SYSTEM_TIME, Keyword::AS, Keyword::OF]
+                .into_iter()
+                .zip(&clause)
+                .all(|(keyword, j)| is_keyword(&tokens[*j].token, keyword));
+
+        if !is_clause {
+            match &tokens[i].token {
+                Token::Whitespace(_) => {}
+                Token::Word(w) => table = Some((normalize(&w.value, w.quote_style), i + 1)),
+                // the parts of a qualified table name
+                Token::Period => {}
+                _ => table = None,
+            }
+            i += 1;
+            continue;
+        }
+
+        let Some((table, table_end)) = table.take() else {
+            bail!("FOR SYSTEM_TIME AS OF must follow the name of a table");
+        };
+
+        let expr_start = clause[3] + 1;
+        let mut parser =
+            Parser::new(&dialect).with_tokens_with_locations(tokens[expr_start..].to_vec());
+        let as_of = parser.parse_expr()?;
+        let end = expr_start + parser.index();
+
+        let as_of_column: Vec<String> = match &as_of {
+            SqlExpr::Function(f)
+                if f.name.to_string().eq_ignore_ascii_case("proctime") && f.args.is_empty() =>
+            {
+                vec![]
+            }
+            SqlExpr::Identifier(ident) => vec![normalize(&ident.value, ident.quote_style)],
+            SqlExpr::CompoundIdentifier(idents) => idents
+                .iter()
+                .map(|ident| normalize(&ident.value, ident.quote_style))
+                .collect(),
+            _ => bail!(
+                "FOR SYSTEM_TIME AS OF must be PROCTIME() or the event time column of the other side of the join, not {}",
+                as_of
+            ),
+        };
+
+        if !as_of_column.is_empty() {
+            // the table is referenced by its alias, if it has one
+            let alias = next_token(end)
+                .and_then(|j| {
+                    if is_keyword(&tokens[j].token, Keyword::AS) {
+                        next_token(j + 1)
+                    } else {
+                        Some(j)
+                    }
+                })
+                .and_then(|j| match &tokens[j].token {
+                    Token::Word(w) if !RESERVED_FOR_TABLE_ALIAS.contains(&w.keyword) => {
+                        Some(normalize(&w.value, w.quote_style))
+                    }
+                    _ => None,
+                });
+            let reference = alias.unwrap_or_else(|| table.clone());
+            if temporal_tables
+                .insert(reference.clone(), as_of_column)
+                .is_some()
+            {
+                bail!(
+                    "{} is joined FOR SYSTEM_TIME AS OF more than once; give each reference a distinct alias",
+                    reference
+                );
+            }
+        }
+
+        removed.push((
+            offsets[table_end],
+            offsets.get(end).copied().unwrap_or(query.len()),
+        ));
+        i = end;
+    }
+
+    let mut stripped = String::with_capacity(query.len());
+    let mut last = 0;
+    for (start, end) in removed {
+        stripped.push_str(&query[last..start]);
+        last = end;
+    }
+    stripped.push_str(&query[last..]);
+
+    Ok((stripped, temporal_tables))
+}
+
+/// Connector tables may declare columns that are read from the metadata of each message, like
+/// `key BYTEA METADATA FROM 'key'`, which the Postgres dialect can't parse, so they're rewritten to
+/// be generated by a `metadata('key')` expression that the table definition recognizes.
+fn rewrite_metadata_columns(query: &str) -> String {
+    let re = Regex::new(r"(?i)\bMETADATA\s+FROM\s+('[^']*')").unwrap();
+    re.replace_all(query, "GENERATED ALWAYS AS (metadata($1))")
+        .to_string()
 }
 
 pub fn parse_dependencies(definition: &str) -> Result<String> {
@@ -436,16 +770,154 @@ impl ContextProvider for ArroyoSchemaProvider {
 #[derive(Clone, Debug)]
 pub struct SqlConfig {
     pub default_parallelism: usize,
+    /// Parallelism of operators by their names, like the name of a source table or
+    /// `sink_<table>`, set with `SET parallelism.<operator> = <n>`
+    pub operator_parallelism: HashMap<String, usize>,
+    /// How long the state of non-windowed aggregates and joins is kept after it was last updated
+    pub state_ttl: Duration,
+    /// Overrides `state_ttl` for the left side of non-windowed joins
+    pub left_join_expiration: Option<Duration>,
+    /// Overrides `state_ttl` for the right side of non-windowed joins
+    pub right_join_expiration: Option<Duration>,
+    /// How far behind the latest event time the watermark of sources without a watermark
+    /// column is held
+    pub watermark_max_lateness: Duration,
+    /// How often sources emit watermarks
+    pub watermark_period: Duration,
 }
 
 impl Default for SqlConfig {
     fn default() -> Self {
         Self {
             default_parallelism: 4,
+            operator_parallelism: HashMap::new(),
+            state_ttl: Duration::from_secs(24 * 60 * 60),
+            left_join_expiration: None,
+            right_join_expiration: None,
+            watermark_max_lateness: Duration::from_secs(1),
+            watermark_period: Duration::from_secs(1),
         }
     }
 }
 
+impl SqlConfig {
+    /// Applies an option set by a `SET <name> = <value>` statement
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        match name {
+            "parallelism" => self.default_parallelism = parse_parallelism(name, value)?,
+            "state.ttl" => self.state_ttl = parse_positive_duration(name, value)?,
+            "join.expiration" => {
+                let expiration = parse_positive_duration(name, value)?;
+                self.left_join_expiration = Some(expiration);
+                self.right_join_expiration = Some(expiration);
+            }
+            "join.left_expiration" => {
+                self.left_join_expiration = Some(parse_positive_duration(name, value)?)
+            }
+            "join.right_expiration" => {
+                self.right_join_expiration = Some(parse_positive_duration(name, value)?)
+            }
+            "watermark.max_lateness" => self.watermark_max_lateness = parse_duration(value)?,
+            "watermark.period" => self.watermark_period = parse_positive_duration(name, value)?,
+            _ => {
+                let Some(operator) = name.strip_prefix("parallelism.") else {
+                    bail!("unknown option '{}' in SET statement", name);
+                };
+                self.operator_parallelism
+                    .insert(operator.to_string(), parse_parallelism(name, value)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// The expiration of the left and right sides of non-windowed joins
+    pub fn join_expiration(&self) -> (Duration, Duration) {
+        (
+            self.left_join_expiration.unwrap_or(self.state_ttl),
+            self.right_join_expiration.unwrap_or(self.state_ttl),
+        )
+    }
+
+    fn apply_statement(&mut self, statement: &Statement) -> Result<bool> {
+        let Statement::SetVariable {
+            variable, value, ..
+        } = statement
+        else {
+            return Ok(false);
+        };
+
+        let name = variable.to_string().to_lowercase();
+        let value = match value.as_slice() {
+            [SqlExpr::Value(
+                SqlValue::SingleQuotedString(s)
+                | SqlValue::DoubleQuotedString(s)
+                | SqlValue::Number(s, _),
+            )] => s.clone(),
+            [SqlExpr::Identifier(ident)] => ident.value.clone(),
+            _ => bail!("SET {} must be set to a single literal value", name),
+        };
+
+        self.set(&name, &value)?;
+        Ok(true)
+    }
+}
+
+fn parse_parallelism(name: &str, value: &str) -> Result<usize> {
+    match value.trim().parse::<usize>() {
+        Ok(parallelism) if parallelism > 0 => Ok(parallelism),
+        _ => bail!("{} must be a positive integer, not '{}'", name, value),
+    }
+}
+
+fn parse_positive_duration(name: &str, value: &str) -> Result<Duration> {
+    let duration = parse_duration(value)?;
+    if duration.is_zero() {
+        bail!("{} must be greater than zero", name);
+    }
+    Ok(duration)
+}
+
+// the longest duration that can be set, which keeps arithmetic on event times and durations in
+// microseconds from overflowing
+const MAX_DURATION_DAYS: u64 = 10 * 365;
+
+/// Parses durations like '30 seconds', '2 hours' or '500ms'
+fn parse_duration(value: &str) -> Result<Duration> {
+    let trimmed = value.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (amount, unit) = trimmed.split_at(split);
+    let invalid = || {
+        anyhow!(
+            "invalid duration '{}'; expected a number followed by a unit, like '30 seconds'",
+            value
+        )
+    };
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+
+    let millis = match unit.trim().to_lowercase().as_str() {
+        "ms" | "millisecond" | "milliseconds" => 1,
+        "s" | "sec" | "secs" | "second" | "seconds" => 1000,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60 * 1000,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60 * 1000,
+        "d" | "day" | "days" => 24 * 60 * 60 * 1000,
+        _ => return Err(invalid()),
+    };
+    let duration = amount
+        .checked_mul(millis)
+        .map(Duration::from_millis)
+        .filter(|d| *d <= Duration::from_secs(MAX_DURATION_DAYS * 24 * 60 * 60))
+        .ok_or_else(|| {
+            anyhow!(
+                "duration '{}' is too large; durations can be at most {} days",
+                value,
+                MAX_DURATION_DAYS
+            )
+        })?;
+    Ok(duration)
+}
+
 pub async fn parse_and_get_program(
     query: &str,
     schema_provider: ArroyoSchemaProvider,
@@ -465,11 +937,19 @@ pub async fn parse_and_get_program(
 pub fn parse_and_get_program_sync(
     query: String,
     mut schema_provider: ArroyoSchemaProvider,
-    config: SqlConfig,
+    mut config: SqlConfig,
 ) -> Result<CompiledSql> {
     let dialect = PostgreSqlDialect {};
+    let query = rewrite_metadata_columns(&query);
+    let (query, temporal_tables) = strip_system_time(&query)?;
+    schema_provider.temporal_tables = temporal_tables;
+    let (query, mut match_recognize) = extract_match_recognize(&query)?;
     let mut inserts = vec![];
     for statement in Parser::parse_sql(&dialect, &query)? {
+        resolve_match_recognize(&mut match_recognize, &mut schema_provider)?;
+        if config.apply_statement(&statement)? {
+            continue;
+        }
         if let Some(table) = Table::try_from_statement(&statement, &schema_provider)? {
             schema_provider.insert_table(table);
         } else {
@@ -479,6 +959,12 @@ pub fn parse_and_get_program_sync(
             )?);
         };
     }
+    if let Some(clause) = match_recognize.first() {
+        bail!(
+            "MATCH_RECOGNIZE input table {} does not exist",
+            clause.input
+        );
+    }
 
     let mut sql_pipeline_builder = SqlPipelineBuilder::new(&mut schema_provider);
     for insert in inserts {
@@ -517,6 +1003,8 @@ pub fn parse_and_get_program_sync(
             watermark_field: None,
             idle_time: DEFAULT_IDLE_TIME,
             inferred_fields: None,
+            lookup_options: Default::default(),
+            primary_keys: vec![],
         });
 
         plan_graph.add_sql_operator(sink.as_sql_sink(insert)?);
@@ -693,6 +1181,7 @@ pub fn get_test_expression(
             .collect(),
         definition: None,
         inferred: None,
+        primary_keys: vec![],
     };
 
     let mut schema_provider = ArroyoSchemaProvider::new();
@@ -837,4 +1326,52 @@ pub fn my_udf() -> i64 {
         "#;
         assert!(parse_dependencies(definition).is_err());
     }
+
+    #[test]
+    fn test_strip_system_time() {
+        let (query, temporal_tables) = strip_system_time(
+            "SELECT * FROM orders o
+            JOIN rates FOR SYSTEM_TIME AS OF o.order_time AS r ON o.currency = r.currency
+            LEFT JOIN customers FOR system_time AS OF PROCTIME() c ON o.customer = c.id
+            WHERE o.note != 'rates FOR SYSTEM_TIME AS OF now'",
+        )
+        .unwrap();
+
+        assert_eq!(
+            query,
+            "SELECT * FROM orders o
+            JOIN rates AS r ON o.currency = r.currency
+            LEFT JOIN customers c ON o.customer = c.id
+            WHERE o.note != 'rates FOR SYSTEM_TIME AS OF now'"
+        );
+        assert_eq!(
+            temporal_tables,
+            HashMap::from([(
+                "r".to_string(),
+                vec!["o".to_string(), "order_time".to_string()]
+            )])
+        );
+    }
+
+    #[test]
+    fn test_strip_system_time_without_alias() {
+        let (query, temporal_tables) = strip_system_time(
+            "SELECT * FROM orders JOIN Rates FOR SYSTEM_TIME AS OF order_time ON currency = id",
+        )
+        .unwrap();
+
+        assert_eq!(query, "SELECT * FROM orders JOIN Rates ON currency = id");
+        assert_eq!(
+            temporal_tables,
+            HashMap::from([("rates".to_string(), vec!["order_time".to_string()])])
+        );
+    }
+
+    #[test]
+    fn test_strip_system_time_requires_table() {
+        assert!(strip_system_time(
+            "SELECT * FROM orders JOIN (SELECT * FROM rates) FOR SYSTEM_TIME AS OF PROCTIME() r"
+        )
+        .is_err());
+    }
 }
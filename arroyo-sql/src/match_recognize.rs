@@ -0,0 +1,1002 @@
+//! Support for `MATCH_RECOGNIZE`, which the SQL parser doesn't understand. Each clause is found in
+//! the query's tokens, cut out before parsing and replaced by a placeholder table whose rows are
+//! the matches.
+//! Once the clause's input table has been defined, the placeholder is planned as a projection of
+//! the input, computing the DEFINE conditions and measured values of each row, followed by the
+//! match_recognize operator.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use arrow_schema::DataType;
+use arroyo_datastream::{MatchRecognize, Operator, PatternElement};
+use datafusion::sql::planner::{PlannerContext, SqlToRel};
+use datafusion::sql::sqlparser::tokenizer::{Token, TokenWithLocation, Tokenizer, Word};
+use datafusion::sql::sqlparser::{dialect::PostgreSqlDialect, parser::Parser};
+use datafusion_common::DFSchema;
+use datafusion_expr::LogicalPlan;
+use quote::quote;
+use regex::Regex;
+
+use crate::code_gen::{CodeGenerator, ValuePointerContext};
+use crate::expressions::{Column, ColumnExpression, Expression};
+use crate::operators::Projection;
+use crate::pipeline::{SqlOperator, SqlPipelineBuilder};
+use crate::tables::{produce_optimized_plan, Table};
+use crate::types::{StructDef, StructField, TypeDef};
+use crate::{token_offsets, ArroyoSchemaProvider};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeasureFunction {
+    First,
+    Last,
+    Count,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone)]
+struct MeasureClause {
+    name: String,
+    function: MeasureFunction,
+    variable: Option<String>,
+    // the SQL of the measured value, or `None` to count rows
+    argument: Option<String>,
+}
+
+/// A `MATCH_RECOGNIZE` clause that has been extracted from a query.
+#[derive(Debug, Clone)]
+pub(crate) struct MatchRecognizeClause {
+    // the placeholder table that replaces the clause in the query
+    name: String,
+    pub(crate) input: String,
+    // the input table as it's written in the query
+    input_sql: String,
+    partition_by: Vec<String>,
+    measures: Vec<MeasureClause>,
+    // pattern variables, with their minimum and maximum repetitions
+    pattern: Vec<(String, u64, Option<u64>)>,
+    within: Option<String>,
+    define: Vec<(String, String)>,
+}
+
+/// The table that a `MATCH_RECOGNIZE` clause is replaced by.
+#[derive(Debug, Clone)]
+pub struct MatchRecognizeTable {
+    pub name: String,
+    // the input, projected to the partition columns, conditions and measured values
+    logical_plan: LogicalPlan,
+    partition_by: Vec<String>,
+    // the condition column of each pattern variable, if it has one
+    define: Vec<Option<String>>,
+    // the name, function, variable index and argument column of each measure
+    measures: Vec<(String, MeasureFunction, Option<usize>, Option<String>)>,
+    pattern: Vec<PatternElement>,
+    within: Option<Duration>,
+    pub fields: Vec<StructField>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Measure {
+    pub name: String,
+    pub function: MeasureFunction,
+    pub variable: Option<usize>,
+    pub value: Option<Expression>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchRecognizeOperator {
+    pub partition_key: Projection,
+    pub pattern: Vec<PatternElement>,
+    pub within: Option<Duration>,
+    // the conditions of the pattern variables that have one
+    pub define: Vec<(usize, Expression)>,
+    pub measures: Vec<Measure>,
+}
+
+/// The tokens of a query, along with the byte offset in the query at which each of them starts.
+struct QueryTokens<'a> {
+    query: &'a str,
+    tokens: Vec<TokenWithLocation>,
+    offsets: Vec<usize>,
+}
+
+impl<'a> QueryTokens<'a> {
+    fn new(query: &'a str) -> Result<Self> {
+        let tokens = Tokenizer::new(&PostgreSqlDialect {}, query).tokenize_with_location()?;
+        let offsets = token_offsets(query, &tokens);
+        Ok(Self {
+            query,
+            tokens,
+            offsets,
+        })
+    }
+
+    fn token(&self, i: usize) -> &Token {
+        &self.tokens[i].token
+    }
+
+    fn offset(&self, i: usize) -> usize {
+        self.offsets.get(i).copied().unwrap_or(self.query.len())
+    }
+
+    // the indices of the tokens that aren't whitespace or comments
+    fn significant(&self) -> Vec<usize> {
+        (0..self.tokens.len())
+            .filter(|i| !matches!(self.token(*i), Token::Whitespace(_)))
+            .collect()
+    }
+
+    // the SQL of the tokens, as written in the query, with whitespace and comments between them
+    // collapsed into single spaces
+    fn render(&self, indices: impl Iterator<Item = usize>) -> String {
+        let mut text = String::new();
+        for i in indices {
+            if matches!(self.token(i), Token::Whitespace(_)) {
+                if !text.is_empty() && !text.ends_with(' ') {
+                    text.push(' ');
+                }
+            } else {
+                text.push_str(&self.query[self.offset(i)..self.offset(i + 1)]);
+            }
+        }
+        text.trim_end().to_string()
+    }
+
+    // the SQL spanned by a run of significant tokens
+    fn text(&self, items: &[usize]) -> String {
+        match (items.first(), items.last()) {
+            (Some(first), Some(last)) => self.render(*first..*last + 1),
+            _ => String::new(),
+        }
+    }
+}
+
+fn is_keyword(token: &Token, keyword: &str) -> bool {
+    matches!(token, Token::Word(w) if w.quote_style.is_none() && w.value.eq_ignore_ascii_case(keyword))
+}
+
+// identifiers are case-insensitive unless they're quoted
+fn normalize(word: &Word) -> String {
+    match word.quote_style {
+        Some(_) => word.value.clone(),
+        None => word.value.to_lowercase(),
+    }
+}
+
+/// Replaces each `<table> MATCH_RECOGNIZE (...)` in the query with a placeholder table, returning
+/// the rewritten query and the extracted clauses.
+pub(crate) fn extract_match_recognize(query: &str) -> Result<(String, Vec<MatchRecognizeClause>)> {
+    let mut query = query.to_string();
+    let mut clauses = vec![];
+    loop {
+        let tokens = QueryTokens::new(&query)?;
+        let significant = tokens.significant();
+        let Some(keyword) = significant
+            .iter()
+            .position(|i| is_keyword(tokens.token(*i), "MATCH_RECOGNIZE"))
+        else {
+            break;
+        };
+
+        // the input is the (possibly qualified) table name before the clause
+        if keyword == 0 || !matches!(tokens.token(significant[keyword - 1]), Token::Word(_)) {
+            bail!("MATCH_RECOGNIZE must follow the name of a table");
+        }
+        let mut start = keyword - 1;
+        while start >= 2
+            && matches!(tokens.token(significant[start - 1]), Token::Period)
+            && matches!(tokens.token(significant[start - 2]), Token::Word(_))
+        {
+            start -= 2;
+        }
+        let input_name = &significant[start..keyword];
+
+        if !matches!(
+            significant.get(keyword + 1).map(|i| tokens.token(*i)),
+            Some(Token::LParen)
+        ) {
+            bail!("MATCH_RECOGNIZE must be followed by a parenthesized clause");
+        }
+        let close = closing_paren(&tokens, &significant, keyword + 1)
+            .ok_or_else(|| anyhow!("unbalanced parentheses in MATCH_RECOGNIZE"))?;
+
+        let name = format!("__match_recognize_{}", clauses.len());
+        let input = input_name
+            .iter()
+            .filter_map(|i| match tokens.token(*i) {
+                Token::Word(w) => Some(w.value.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(".");
+        clauses.push(MatchRecognizeClause::parse(
+            name.clone(),
+            input,
+            tokens.text(input_name),
+            &tokens,
+            &significant[keyword + 2..close],
+        )?);
+
+        let range = tokens.offset(significant[start])..tokens.offset(significant[close] + 1);
+        query.replace_range(range, &name);
+    }
+    Ok((query, clauses))
+}
+
+/// Turns the clauses whose input table has been defined into tables.
+pub(crate) fn resolve_match_recognize(
+    clauses: &mut Vec<MatchRecognizeClause>,
+    schema_provider: &mut ArroyoSchemaProvider,
+) -> Result<()> {
+    let (ready, pending): (Vec<_>, Vec<_>) = clauses
+        .drain(..)
+        .partition(|clause| schema_provider.get_table(&clause.input).is_some());
+    *clauses = pending;
+    for clause in ready {
+        let table = clause.into_table(schema_provider)?;
+        schema_provider.insert_table(Table::MatchRecognize(table));
+    }
+    Ok(())
+}
+
+// the position in `items` of the parenthesis closing the one at `open`
+fn closing_paren(tokens: &QueryTokens, items: &[usize], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (position, i) in items.iter().enumerate().skip(open) {
+        match tokens.token(*i) {
+            Token::LParen => depth += 1,
+            Token::RParen => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(position);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// splits on commas that aren't nested in parentheses
+fn split_top_level<'i>(tokens: &QueryTokens, items: &'i [usize]) -> Vec<&'i [usize]> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (position, i) in items.iter().enumerate() {
+        match tokens.token(*i) {
+            Token::LParen => depth += 1,
+            Token::RParen => depth -= 1,
+            Token::Comma if depth == 0 => {
+                parts.push(&items[start..position]);
+                start = position + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&items[start..]);
+    parts.into_iter().filter(|part| !part.is_empty()).collect()
+}
+
+const SECTIONS: &[&[&str]] = &[
+    &["PARTITION", "BY"],
+    &["ORDER", "BY"],
+    &["MEASURES"],
+    &["ONE", "ROW", "PER", "MATCH"],
+    &["ALL", "ROWS", "PER", "MATCH"],
+    &["AFTER", "MATCH", "SKIP"],
+    &["PATTERN"],
+    &["WITHIN"],
+    &["DEFINE"],
+];
+
+// splits the body of the clause into its sections, keyed by their (space-joined) keywords
+fn sections<'i>(tokens: &QueryTokens, body: &'i [usize]) -> Result<Vec<(String, &'i [usize])>> {
+    let mut headers = vec![];
+    let mut depth = 0;
+    let mut position = 0;
+    while position < body.len() {
+        let section = SECTIONS.iter().find(|section| {
+            depth == 0
+                && section.len() <= body.len() - position
+                && section
+                    .iter()
+                    .zip(&body[position..])
+                    .all(|(keyword, i)| is_keyword(tokens.token(*i), keyword))
+        });
+        match section {
+            Some(section) => {
+                headers.push((section.join(" "), position, position + section.len()));
+                position += section.len();
+            }
+            None => {
+                match tokens.token(body[position]) {
+                    Token::LParen => depth += 1,
+                    Token::RParen => depth -= 1,
+                    _ => {}
+                }
+                position += 1;
+            }
+        }
+    }
+
+    let Some((_, first_start, _)) = headers.first() else {
+        bail!("MATCH_RECOGNIZE requires a PATTERN");
+    };
+    if *first_start > 0 {
+        bail!(
+            "unexpected '{}' in MATCH_RECOGNIZE",
+            tokens.text(&body[..*first_start])
+        );
+    }
+
+    Ok(headers
+        .iter()
+        .enumerate()
+        .map(|(i, (section, _, end))| {
+            let next = headers.get(i + 1).map(|(_, start, _)| *start);
+            (section.clone(), &body[*end..next.unwrap_or(body.len())])
+        })
+        .collect())
+}
+
+fn parse_pattern(text: &str) -> Result<Vec<(String, u64, Option<u64>)>> {
+    let unsupported = || {
+        anyhow!(
+            "unsupported PATTERN '{}'; only sequences of variables with quantifiers are supported",
+            text
+        )
+    };
+    let inner = text
+        .strip_prefix('(')
+        .and_then(|t| t.strip_suffix(')'))
+        .ok_or_else(unsupported)?;
+    let re = Regex::new(r"^\s*([A-Za-z_]\w*)\s*(\*|\+|\?|\{\s*(\d*)\s*(,)?\s*(\d*)\s*\})?(\?)?")
+        .unwrap();
+
+    let mut elements = vec![];
+    let mut rest = inner.trim();
+    while !rest.is_empty() {
+        let captures = re.captures(rest).ok_or_else(unsupported)?;
+        if captures.get(6).is_some() {
+            bail!("reluctant quantifiers aren't supported in MATCH_RECOGNIZE");
+        }
+        let variable = captures[1].to_lowercase();
+        let (min, max) = match captures.get(2).map(|q| q.as_str()) {
+            None => (1, Some(1)),
+            Some("*") => (0, None),
+            Some("+") => (1, None),
+            Some("?") => (0, Some(1)),
+            Some(_) => {
+                let number = |i: usize| -> Result<Option<u64>> {
+                    match captures
+                        .get(i)
+                        .map(|m| m.as_str())
+                        .filter(|s| !s.is_empty())
+                    {
+                        Some(n) => Ok(Some(n.parse()?)),
+                        None => Ok(None),
+                    }
+                };
+                let min = number(3)?;
+                match (captures.get(4).is_some(), number(5)?) {
+                    (false, _) => {
+                        let n = min.ok_or_else(unsupported)?;
+                        (n, Some(n))
+                    }
+                    (true, max) => (min.unwrap_or(0), max),
+                }
+            }
+        };
+        if max == Some(0) || max.map_or(false, |max| max < min) {
+            bail!("invalid quantifier for pattern variable {}", variable);
+        }
+        elements.push((variable, min, max));
+        rest = rest[captures.get(0).unwrap().end()..].trim_start();
+    }
+
+    if elements.is_empty() {
+        return Err(unsupported());
+    }
+    Ok(elements)
+}
+
+// removes the `variable.` prefixes from an expression, returning its SQL and the variable they
+// referred to. Only the first part of a qualified name can be a variable, and strings and comments
+// are left alone.
+fn strip_variables(
+    tokens: &QueryTokens,
+    items: &[usize],
+    variables: &[String],
+) -> Result<(String, Option<String>)> {
+    let mut referenced: Option<String> = None;
+    let mut stripped = vec![];
+    for (position, i) in items.iter().enumerate() {
+        let Token::Word(word) = tokens.token(*i) else {
+            continue;
+        };
+        let qualifies = matches!(
+            items.get(position + 1).map(|i| tokens.token(*i)),
+            Some(Token::Period)
+        );
+        let qualified = position > 0 && matches!(tokens.token(items[position - 1]), Token::Period);
+        let name = normalize(word);
+        if qualifies && !qualified && variables.contains(&name) {
+            if referenced.as_ref().map_or(false, |r| *r != name) {
+                bail!(
+                    "'{}' references more than one pattern variable, which isn't supported",
+                    tokens.text(items)
+                );
+            }
+            referenced = Some(name);
+            stripped.extend(*i..items[position + 1] + 1);
+        }
+    }
+
+    let text = match (items.first(), items.last()) {
+        (Some(first), Some(last)) => {
+            tokens.render((*first..*last + 1).filter(|i| !stripped.contains(i)))
+        }
+        _ => String::new(),
+    };
+    Ok((text, referenced))
+}
+
+fn measure_function(token: &Token) -> Option<MeasureFunction> {
+    [
+        ("FIRST", MeasureFunction::First),
+        ("LAST", MeasureFunction::Last),
+        ("COUNT", MeasureFunction::Count),
+        ("MIN", MeasureFunction::Min),
+        ("MAX", MeasureFunction::Max),
+    ]
+    .into_iter()
+    .find(|(keyword, _)| is_keyword(token, keyword))
+    .map(|(_, function)| function)
+}
+
+fn parse_measure(
+    tokens: &QueryTokens,
+    item: &[usize],
+    variables: &[String],
+) -> Result<MeasureClause> {
+    let (expr, name) = match item {
+        [expr @ .., as_, name] if !expr.is_empty() && is_keyword(tokens.token(*as_), "AS") => {
+            match tokens.token(*name) {
+                Token::Word(name) => (expr, name.value.clone()),
+                _ => bail!(
+                    "invalid name for MATCH_RECOGNIZE measure '{}'",
+                    tokens.text(item)
+                ),
+            }
+        }
+        _ => bail!(
+            "MATCH_RECOGNIZE measure '{}' must be named, as in 'LAST(A.price) AS last_price'",
+            tokens.text(item)
+        ),
+    };
+
+    let function = expr
+        .first()
+        .and_then(|i| measure_function(tokens.token(*i)))
+        .filter(|_| {
+            matches!(expr.get(1).map(|i| tokens.token(*i)), Some(Token::LParen))
+                && closing_paren(tokens, expr, 1) == Some(expr.len() - 1)
+        });
+    let (function, argument) = match function {
+        Some(function) => (function, &expr[2..expr.len() - 1]),
+        // a plain value is taken from the last row
+        None => (MeasureFunction::Last, expr),
+    };
+
+    let row = match argument
+        .iter()
+        .map(|i| tokens.token(*i))
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [Token::Mul] => Some(None),
+        [Token::Word(variable), Token::Period, Token::Mul] => Some(Some(normalize(variable))),
+        _ => None,
+    };
+    if let Some(variable) = row {
+        if function != MeasureFunction::Count {
+            bail!("only COUNT can be applied to rows in MATCH_RECOGNIZE measures");
+        }
+        if let Some(variable) = &variable {
+            if !variables.contains(variable) {
+                bail!("unknown pattern variable {}", variable);
+            }
+        }
+        return Ok(MeasureClause {
+            name,
+            function,
+            variable,
+            argument: None,
+        });
+    }
+
+    if argument.is_empty() {
+        bail!(
+            "MATCH_RECOGNIZE measure '{}' requires an argument",
+            tokens.text(item)
+        );
+    }
+    let (argument, variable) = strip_variables(tokens, argument, variables)?;
+    Ok(MeasureClause {
+        name,
+        function,
+        variable,
+        argument: Some(argument),
+    })
+}
+
+impl MatchRecognizeClause {
+    fn parse(
+        name: String,
+        input: String,
+        input_sql: String,
+        tokens: &QueryTokens,
+        body: &[usize],
+    ) -> Result<Self> {
+        let sections = sections(tokens, body)?;
+        let section = |name: &str| {
+            sections
+                .iter()
+                .find(|(section, _)| section == name)
+                .map(|(_, content)| *content)
+        };
+
+        if section("ALL ROWS PER MATCH").is_some() {
+            bail!("only ONE ROW PER MATCH is supported in MATCH_RECOGNIZE");
+        }
+        if let Some(skip) = section("AFTER MATCH SKIP") {
+            let skip = tokens.text(skip);
+            if !skip.eq_ignore_ascii_case("PAST LAST ROW") {
+                bail!("only AFTER MATCH SKIP PAST LAST ROW is supported in MATCH_RECOGNIZE");
+            }
+        }
+        if let Some(order_by) = section("ORDER BY") {
+            // rows are always processed in event-time order
+            if order_by
+                .iter()
+                .any(|i| is_keyword(tokens.token(*i), "DESC"))
+            {
+                bail!("MATCH_RECOGNIZE can only be ordered by ascending event time");
+            }
+        }
+
+        let pattern = parse_pattern(&tokens.text(
+            section("PATTERN").ok_or_else(|| anyhow!("MATCH_RECOGNIZE requires a PATTERN"))?,
+        ))?;
+        let variables: Vec<String> = pattern.iter().map(|(v, _, _)| v.clone()).collect();
+
+        // qualified names refer to pattern variables, so the input can't be referred to by a
+        // name that is also a variable
+        let input_table = input.rsplit('.').next().unwrap_or_default().to_lowercase();
+        if variables.contains(&input_table) {
+            bail!(
+                "pattern variable {} has the same name as the MATCH_RECOGNIZE input table",
+                input_table
+            );
+        }
+
+        let partition_by = split_top_level(tokens, section("PARTITION BY").unwrap_or_default())
+            .into_iter()
+            .map(|column| match column {
+                [i] if matches!(tokens.token(*i), Token::Word(_)) => Ok(tokens.text(column)),
+                _ => bail!(
+                    "MATCH_RECOGNIZE can only be partitioned by columns, not '{}'",
+                    tokens.text(column)
+                ),
+            })
+            .collect::<Result<_>>()?;
+
+        let measures = split_top_level(tokens, section("MEASURES").unwrap_or_default())
+            .into_iter()
+            .map(|item| parse_measure(tokens, item, &variables))
+            .collect::<Result<_>>()?;
+
+        let define = split_top_level(tokens, section("DEFINE").unwrap_or_default())
+            .into_iter()
+            .map(|item| {
+                let (variable, condition) = match item {
+                    [variable, as_, condition @ ..]
+                        if !condition.is_empty() && is_keyword(tokens.token(*as_), "AS") =>
+                    {
+                        match tokens.token(*variable) {
+                            Token::Word(variable) => (normalize(variable), condition),
+                            _ => bail!(
+                                "DEFINE items must be of the form '<variable> AS <condition>'"
+                            ),
+                        }
+                    }
+                    _ => bail!("DEFINE items must be of the form '<variable> AS <condition>'"),
+                };
+                if !variables.contains(&variable) {
+                    bail!("DEFINE refers to {}, which isn't in the PATTERN", variable);
+                }
+                let (condition, referenced) = strip_variables(tokens, condition, &variables)?;
+                if referenced.map_or(false, |r| r != variable) {
+                    bail!(
+                        "the DEFINE condition of {} can only reference the current row",
+                        variable
+                    );
+                }
+                Ok((variable, condition))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            name,
+            input,
+            input_sql,
+            partition_by,
+            measures,
+            pattern,
+            within: section("WITHIN").map(|within| tokens.text(within)),
+            define,
+        })
+    }
+
+    fn variables(&self) -> Vec<String> {
+        let mut variables: Vec<String> = vec![];
+        for (variable, _, _) in &self.pattern {
+            if !variables.contains(variable) {
+                variables.push(variable.clone());
+            }
+        }
+        variables
+    }
+
+    fn within(&self, schema_provider: &ArroyoSchemaProvider) -> Result<Option<Duration>> {
+        let Some(within) = &self.within else {
+            return Ok(None);
+        };
+        let expr = Parser::new(&PostgreSqlDialect {})
+            .try_with_sql(within)?
+            .parse_expr()?;
+        let expr = SqlToRel::new(schema_provider).sql_to_expr(
+            expr,
+            &DFSchema::empty(),
+            &mut PlannerContext::new(),
+        )?;
+        SqlPipelineBuilder::get_duration(&expr)
+            .map(Some)
+            .map_err(|_| anyhow!("WITHIN must be an interval, not '{}'", within))
+    }
+
+    fn into_table(self, schema_provider: &ArroyoSchemaProvider) -> Result<MatchRecognizeTable> {
+        let variables = self.variables();
+
+        let mut select = self.partition_by.clone();
+        let mut define = vec![];
+        for (i, variable) in variables.iter().enumerate() {
+            match self.define.iter().find(|(v, _)| v == variable) {
+                Some((_, condition)) => {
+                    select.push(format!("({}) AS __define_{}", condition, i));
+                    define.push(Some(format!("__define_{}", i)));
+                }
+                // variables without a condition match every row
+                None => define.push(None),
+            }
+        }
+        for (i, measure) in self.measures.iter().enumerate() {
+            if let Some(argument) = &measure.argument {
+                select.push(format!("{} AS __measure_{}", argument, i));
+            }
+        }
+        if select.is_empty() {
+            select.push("true AS __row".to_string());
+        }
+
+        let sql = format!("SELECT {} FROM {}", select.join(", "), self.input_sql);
+        let statements = Parser::parse_sql(&PostgreSqlDialect {}, &sql)?;
+        let logical_plan = produce_optimized_plan(&statements[0], schema_provider)
+            .map_err(|e| anyhow!("failed to plan MATCH_RECOGNIZE: {}", e))?;
+
+        let schema = logical_plan.schema();
+        let mut fields = vec![];
+        let mut partition_by = vec![];
+        for field in schema.fields().iter().take(self.partition_by.len()) {
+            partition_by.push(field.name().clone());
+            fields.push(StructField::new(
+                field.name().clone(),
+                None,
+                TypeDef::DataType(field.data_type().clone(), field.is_nullable()),
+            ));
+        }
+
+        let mut measures = vec![];
+        for (i, measure) in self.measures.iter().enumerate() {
+            let argument = measure
+                .argument
+                .as_ref()
+                .map(|_| format!("__measure_{}", i));
+            let data_type = match (&argument, measure.function) {
+                (_, MeasureFunction::Count) => TypeDef::DataType(DataType::Int64, false),
+                (Some(argument), _) => TypeDef::DataType(
+                    schema
+                        .field_with_unqualified_name(argument)?
+                        .data_type()
+                        .clone(),
+                    true,
+                ),
+                (None, _) => unreachable!("only COUNT measures rows"),
+            };
+            fields.push(StructField::new(measure.name.clone(), None, data_type));
+            measures.push((
+                measure.name.clone(),
+                measure.function,
+                measure
+                    .variable
+                    .as_ref()
+                    .map(|v| variables.iter().position(|variable| variable == v).unwrap()),
+                argument,
+            ));
+        }
+
+        let pattern = self
+            .pattern
+            .iter()
+            .map(|(variable, min, max)| PatternElement {
+                variable: variables.iter().position(|v| v == variable).unwrap(),
+                min: *min,
+                max: *max,
+            })
+            .collect();
+
+        Ok(MatchRecognizeTable {
+            within: self.within(schema_provider)?,
+            name: self.name,
+            logical_plan,
+            partition_by,
+            define,
+            measures,
+            pattern,
+            fields,
+        })
+    }
+}
+
+impl MatchRecognizeTable {
+    pub(crate) fn as_sql_source(&self, builder: &mut SqlPipelineBuilder) -> Result<SqlOperator> {
+        let input = builder.insert_sql_plan(&self.logical_plan)?;
+        if input.is_updating() {
+            bail!("MATCH_RECOGNIZE doesn't support updating inputs");
+        }
+        if input.has_window() {
+            bail!("MATCH_RECOGNIZE doesn't support windowed inputs");
+        }
+
+        let input_struct = input.return_type();
+        let column = |name: &str| -> Result<Expression> {
+            let field = input_struct
+                .fields
+                .iter()
+                .find(|f| f.name() == name)
+                .ok_or_else(|| anyhow!("MATCH_RECOGNIZE input is missing column {}", name))?;
+            Ok(Expression::Column(ColumnExpression::new(field.clone())))
+        };
+
+        let partition_key = Projection::new(
+            self.partition_by
+                .iter()
+                .map(|name| {
+                    Ok((
+                        Column {
+                            relation: None,
+                            name: name.clone(),
+                        },
+                        column(name)?,
+                    ))
+                })
+                .collect::<Result<_>>()?,
+        );
+
+        let define = self
+            .define
+            .iter()
+            .enumerate()
+            .filter_map(|(i, condition)| condition.as_ref().map(|c| Ok((i, column(c)?))))
+            .collect::<Result<_>>()?;
+
+        let measures = self
+            .measures
+            .iter()
+            .map(|(name, function, variable, argument)| {
+                Ok(Measure {
+                    name: name.clone(),
+                    function: *function,
+                    variable: *variable,
+                    value: argument.as_ref().map(|a| column(a)).transpose()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(SqlOperator::MatchRecognize(
+            Box::new(input),
+            MatchRecognizeOperator {
+                partition_key,
+                pattern: self.pattern.clone(),
+                within: self.within,
+                define,
+                measures,
+            },
+        ))
+    }
+}
+
+impl Measure {
+    fn return_type(&self) -> TypeDef {
+        match (self.function, &self.value) {
+            (MeasureFunction::Count, _) => TypeDef::DataType(DataType::Int64, false),
+            (_, Some(value)) => value
+                .expression_type(&ValuePointerContext::new())
+                .as_nullable(),
+            (_, None) => unreachable!("only COUNT measures rows"),
+        }
+    }
+}
+
+impl MatchRecognizeOperator {
+    pub fn output_struct(&self) -> StructDef {
+        let mut fields = self.partition_key.output_struct().fields;
+        fields.extend(
+            self.measures
+                .iter()
+                .map(|m| StructField::new(m.name.clone(), None, m.return_type())),
+        );
+        StructDef::for_fields(fields)
+    }
+
+    pub fn expressions(&mut self) -> impl Iterator<Item = &mut Expression> {
+        self.define
+            .iter_mut()
+            .map(|(_, condition)| condition)
+            .chain(self.measures.iter_mut().filter_map(|m| m.value.as_mut()))
+    }
+
+    // |arg, variable| whether the row satisfies the condition of the variable
+    fn define_closure(&self) -> String {
+        let ctx = ValuePointerContext::new();
+        let arms = self.define.iter().map(|(variable, condition)| {
+            let value = condition.generate(&ctx);
+            let value = if condition.expression_type(&ctx).is_optional() {
+                quote!((#value).unwrap_or(false))
+            } else {
+                quote!(#value)
+            };
+            quote!(#variable => #value,)
+        });
+        quote!(|arg, variable| match variable {
+            #(#arms)*
+            _ => true,
+        })
+        .to_string()
+    }
+
+    // |key, rows| the output row for the rows of a match, each tagged with its variable
+    fn measures_closure(&self) -> String {
+        let ctx = ValuePointerContext::new();
+        let output_struct = self.output_struct();
+        let output_type = output_struct.get_type();
+
+        let mut setup = vec![];
+        let mut steps = vec![];
+        let mut values = vec![];
+        for (i, measure) in self.measures.iter().enumerate() {
+            let acc = quote::format_ident!("measure_{}", i);
+            let nullable = measure
+                .value
+                .as_ref()
+                .map_or(false, |v| v.expression_type(&ctx).is_optional());
+            let value = measure.value.as_ref().map(|v| {
+                let value = v.generate(&ctx);
+                quote!((#value).clone())
+            });
+
+            let (init, step, result) = match (measure.function, value) {
+                (MeasureFunction::Count, None) => (quote!(0i64), quote!(#acc += 1;), quote!(#acc)),
+                (MeasureFunction::Count, Some(value)) if nullable => (
+                    quote!(0i64),
+                    quote!(if #value.is_some() { #acc += 1; }),
+                    quote!(#acc),
+                ),
+                (MeasureFunction::Count, Some(_)) => {
+                    (quote!(0i64), quote!(#acc += 1;), quote!(#acc))
+                }
+                (MeasureFunction::First, Some(value)) => (
+                    quote!(None),
+                    quote!(if #acc.is_none() { #acc = Some(#value); }),
+                    if nullable {
+                        quote!(#acc.flatten())
+                    } else {
+                        quote!(#acc)
+                    },
+                ),
+                (MeasureFunction::Last, Some(value)) => (
+                    quote!(None),
+                    quote!(#acc = Some(#value);),
+                    if nullable {
+                        quote!(#acc.flatten())
+                    } else {
+                        quote!(#acc)
+                    },
+                ),
+                (function @ (MeasureFunction::Min | MeasureFunction::Max), Some(value)) => {
+                    let keep = if function == MeasureFunction::Min {
+                        quote!(current <= value)
+                    } else {
+                        quote!(current >= value)
+                    };
+                    let update = quote! {
+                        #acc = Some(match #acc.take() {
+                            Some(current) if #keep => current,
+                            _ => value,
+                        });
+                    };
+                    let step = if nullable {
+                        quote!(if let Some(value) = #value { #update })
+                    } else {
+                        quote!({ let value = #value; #update })
+                    };
+                    (quote!(None), step, quote!(#acc))
+                }
+                (_, None) => unreachable!("only COUNT measures rows"),
+            };
+
+            setup.push(quote!(let mut #acc = #init;));
+            steps.push(match measure.variable {
+                Some(variable) => quote!(if *variable == #variable { #step }),
+                None => step,
+            });
+            values.push(result);
+        }
+
+        let key_fields = self.partition_key.output_struct().fields;
+        let assignments = key_fields
+            .iter()
+            .map(|f| {
+                let ident = f.field_ident();
+                quote!(#ident: key.#ident.clone())
+            })
+            .chain(
+                output_struct
+                    .fields
+                    .iter()
+                    .skip(key_fields.len())
+                    .zip(values)
+                    .map(|(f, value)| {
+                        let ident = f.field_ident();
+                        quote!(#ident: #value)
+                    }),
+            );
+
+        let row = if self.measures.iter().any(|m| m.variable.is_some()) {
+            quote!((variable, arg))
+        } else {
+            quote!((_, arg))
+        };
+
+        quote!(|key, rows| {
+            #(#setup)*
+            for #row in rows.iter() {
+                #(#steps)*
+            }
+            #output_type {
+                #(#assignments,)*
+            }
+        })
+        .to_string()
+    }
+
+    pub fn to_operator(&self) -> Operator {
+        Operator::MatchRecognize(MatchRecognize {
+            pattern: self.pattern.clone(),
+            within: self.within,
+            define: self.define_closure(),
+            measures: self.measures_closure(),
+        })
+    }
+}
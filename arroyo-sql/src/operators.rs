@@ -5,7 +5,8 @@ use crate::{
         ValuePointerContext, VecAggregationContext,
     },
     expressions::{
-        AggregateComputation, AggregateResultExtraction, Aggregator, Column, Expression,
+        AccumulatorExpression, AggregateComputation, AggregateResultExtraction, Aggregator, Column,
+        Expression,
     },
     types::{data_type_as_syn_type, StructDef, StructField, TypeDef},
 };
@@ -14,7 +15,7 @@ use arrow_schema::DataType;
 use arroyo_rpc::formats::Format;
 use datafusion_expr::type_coercion::aggregates::{avg_return_type, sum_return_type};
 use proc_macro2::TokenStream;
-use quote::quote;
+use quote::{format_ident, quote, ToTokens};
 use syn::{parse_quote, parse_str};
 
 #[derive(Debug, Clone)]
@@ -171,6 +172,13 @@ impl AggregateProjection {
             .iter()
             .all(|computation| computation.allows_two_phase())
     }
+
+    pub(crate) fn supports_retraction(&self) -> bool {
+        self.aggregates.iter().all(|computation| match computation {
+            AggregateComputation::Accumulator { computation, .. } => computation.retractable(),
+            _ => true,
+        })
+    }
 }
 
 impl CodeGenerator<VecAggregationContext, StructDef, syn::Expr> for AggregateProjection {
@@ -227,7 +235,7 @@ impl CodeGenerator<VecAggregationContext, StructDef, syn::Expr> for AggregatePro
 
 #[derive(Debug, Clone)]
 pub struct TwoPhaseAggregateProjection {
-    pub aggregates: Vec<(Column, TwoPhaseAggregation)>,
+    pub aggregates: Vec<(Column, TwoPhaseComputation)>,
     pub group_bys: Vec<(StructField, AggregateResultExtraction)>,
 }
 
@@ -235,7 +243,26 @@ impl TwoPhaseAggregateProjection {
     pub fn expressions(&mut self) -> impl Iterator<Item = &mut Expression> {
         self.aggregates
             .iter_mut()
-            .map(|(_, computation)| &mut computation.incoming_expression)
+            .flat_map(|(_, computation)| computation.expressions())
+    }
+
+    pub(crate) fn udafs(&self) -> impl Iterator<Item = String> + '_ {
+        self.aggregates
+            .iter()
+            .filter_map(|(_, computation)| match computation {
+                TwoPhaseComputation::Builtin(_) => None,
+                TwoPhaseComputation::Accumulator(accumulator) => accumulator.name(),
+            })
+    }
+
+    // sliding windows and updating inputs need to remove bins from the in-memory aggregate
+    pub(crate) fn supports_retraction(&self) -> bool {
+        self.aggregates
+            .iter()
+            .all(|(_, computation)| match computation {
+                TwoPhaseComputation::Builtin(_) => true,
+                TwoPhaseComputation::Accumulator(accumulator) => accumulator.retractable(),
+            })
     }
 }
 
@@ -250,12 +277,26 @@ impl TryFrom<AggregateProjection> for TwoPhaseAggregateProjection {
                 AggregateComputation::Builtin {
                     column,
                     computation,
-                } => Ok((column, computation.try_into()?)),
+                } if computation.aggregator == Aggregator::ArrayAgg => Ok((
+                    column,
+                    TwoPhaseComputation::Accumulator(AccumulatorExpression::array_agg(computation)),
+                )),
+                AggregateComputation::Builtin {
+                    column,
+                    computation,
+                } => Ok((
+                    column,
+                    TwoPhaseComputation::Builtin(computation.try_into()?),
+                )),
+                AggregateComputation::Accumulator {
+                    column,
+                    computation,
+                } => Ok((column, TwoPhaseComputation::Accumulator(computation))),
                 AggregateComputation::UDAF { .. } => {
-                    bail!("UDAFs not supported in two phase aggregation")
+                    bail!("UDAFs without an accumulator not supported in two phase aggregation")
                 }
             })
-            .collect::<Result<Vec<(Column, TwoPhaseAggregation)>>>()?;
+            .collect::<Result<Vec<(Column, TwoPhaseComputation)>>>()?;
 
         Ok(Self {
             aggregates,
@@ -530,6 +571,171 @@ impl TwoPhaseAggregateProjection {
     }
 }
 
+#[derive(Debug, Clone)]
+pub enum TwoPhaseComputation {
+    Builtin(TwoPhaseAggregation),
+    Accumulator(AccumulatorExpression),
+}
+
+impl TwoPhaseComputation {
+    fn expressions(&mut self) -> Box<dyn Iterator<Item = &mut Expression> + '_> {
+        match self {
+            TwoPhaseComputation::Builtin(aggregation) => {
+                Box::new(std::iter::once(&mut aggregation.incoming_expression))
+            }
+            TwoPhaseComputation::Accumulator(accumulator) => Box::new(accumulator.expressions()),
+        }
+    }
+
+    fn output_type_def(&self, input_context: &ValuePointerContext) -> TypeDef {
+        match self {
+            TwoPhaseComputation::Builtin(aggregation) => aggregation.output_type_def(input_context),
+            TwoPhaseComputation::Accumulator(accumulator) => accumulator.return_type(),
+        }
+    }
+
+    fn bin_type(&self, input_context: &ValuePointerContext) -> BinType {
+        match self {
+            TwoPhaseComputation::Builtin(aggregation) => aggregation.bin_type(input_context),
+            TwoPhaseComputation::Accumulator(accumulator) => {
+                BinType::Accumulator(accumulator.state_type().into_token_stream().to_string())
+            }
+        }
+    }
+
+    fn mem_type(&self, input_context: &ValuePointerContext) -> BinType {
+        match self {
+            TwoPhaseComputation::Builtin(aggregation) => aggregation.mem_type(input_context),
+            // the in-memory state is the merge of the states of every bin in the window
+            TwoPhaseComputation::Accumulator(_) => self.bin_type(input_context),
+        }
+    }
+}
+
+impl CodeGenerator<ValueBinMergingContext, BinType, syn::Expr> for TwoPhaseComputation {
+    fn generate(&self, input_context: &ValueBinMergingContext) -> syn::Expr {
+        match self {
+            TwoPhaseComputation::Builtin(aggregation) => aggregation.generate(input_context),
+            TwoPhaseComputation::Accumulator(accumulator) => {
+                let current_bin_ident = input_context.bin_context.current_bin_ident();
+                let state_ident = format_ident!("state");
+                let accumulate = accumulator.accumulate(&state_ident, &input_context.value_context);
+                parse_quote!({
+                    let mut #state_ident = #current_bin_ident.unwrap_or_default();
+                    #accumulate;
+                    #state_ident
+                })
+            }
+        }
+    }
+
+    fn expression_type(&self, input_context: &ValueBinMergingContext) -> BinType {
+        self.bin_type(&input_context.value_context)
+    }
+}
+
+impl CodeGenerator<CombiningContext, BinType, syn::Expr> for TwoPhaseComputation {
+    fn generate(&self, input_context: &CombiningContext) -> syn::Expr {
+        match self {
+            TwoPhaseComputation::Builtin(aggregation) => aggregation.generate(input_context),
+            TwoPhaseComputation::Accumulator(accumulator) => {
+                let current_bin_ident = input_context.current_bin_ident();
+                let new_bin_ident = input_context.new_bin_ident();
+                let state_ident = format_ident!("state");
+                let merge = accumulator.merge(&state_ident, &new_bin_ident);
+                parse_quote!({
+                    let mut #state_ident = #current_bin_ident;
+                    #merge;
+                    #state_ident
+                })
+            }
+        }
+    }
+
+    fn expression_type(&self, input_context: &CombiningContext) -> BinType {
+        self.bin_type(&input_context.value_context)
+    }
+}
+
+impl CodeGenerator<MemoryAddingContext, BinType, syn::Expr> for TwoPhaseComputation {
+    fn generate(&self, input_context: &MemoryAddingContext) -> syn::Expr {
+        match self {
+            TwoPhaseComputation::Builtin(aggregation) => aggregation.generate(input_context),
+            TwoPhaseComputation::Accumulator(accumulator) => {
+                let memory_ident = input_context.memory_value_ident();
+                let bin_value_ident = input_context.bin_value_ident();
+                let state_ident = format_ident!("state");
+                let merge = accumulator.merge(&state_ident, &bin_value_ident);
+                parse_quote!({
+                    let mut #state_ident = #memory_ident.unwrap_or_default();
+                    #merge;
+                    #state_ident
+                })
+            }
+        }
+    }
+
+    fn expression_type(&self, input_context: &MemoryAddingContext) -> BinType {
+        self.mem_type(&input_context.value_context)
+    }
+}
+
+impl CodeGenerator<MemoryRemovingContext, BinType, syn::Expr> for TwoPhaseComputation {
+    fn generate(&self, input_context: &MemoryRemovingContext) -> syn::Expr {
+        match self {
+            TwoPhaseComputation::Builtin(aggregation) => aggregation.generate(input_context),
+            TwoPhaseComputation::Accumulator(accumulator) => {
+                if !accumulator.retractable() {
+                    unreachable!("non-retractable accumulators are never removed from memory");
+                }
+                let memory_ident = input_context.memory_value_ident();
+                let bin_value_ident = input_context.bin_value_ident();
+                let state_ident = format_ident!("state");
+                let retract = accumulator.retract(&state_ident, &bin_value_ident);
+                parse_quote!({
+                    let mut #state_ident = #memory_ident;
+                    #retract;
+                    Some(#state_ident)
+                })
+            }
+        }
+    }
+
+    fn expression_type(&self, input_context: &MemoryRemovingContext) -> BinType {
+        self.mem_type(&input_context.value_context)
+    }
+}
+
+impl CodeGenerator<BinAggregatingContext, TypeDef, syn::Expr> for TwoPhaseComputation {
+    fn generate(&self, input_context: &BinAggregatingContext) -> syn::Expr {
+        match self {
+            TwoPhaseComputation::Builtin(aggregation) => aggregation.generate(input_context),
+            TwoPhaseComputation::Accumulator(accumulator) => {
+                accumulator.finish(&input_context.bin_name())
+            }
+        }
+    }
+
+    fn expression_type(&self, input_context: &BinAggregatingContext) -> TypeDef {
+        self.output_type_def(&input_context.value_context)
+    }
+}
+
+impl CodeGenerator<MemoryAggregatingContext, TypeDef, syn::Expr> for TwoPhaseComputation {
+    fn generate(&self, input_context: &MemoryAggregatingContext) -> syn::Expr {
+        match self {
+            TwoPhaseComputation::Builtin(aggregation) => aggregation.generate(input_context),
+            TwoPhaseComputation::Accumulator(accumulator) => {
+                accumulator.finish(&input_context.bin_name())
+            }
+        }
+    }
+
+    fn expression_type(&self, input_context: &MemoryAggregatingContext) -> TypeDef {
+        self.output_type_def(&input_context.value_context)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TwoPhaseAggregation {
     pub incoming_expression: Expression,
@@ -615,7 +821,9 @@ impl CodeGenerator<ValueBinMergingContext, BinType, syn::Expr> for TwoPhaseAggre
                     None => (1, #expr as #aggregate_type)
                 }
             }),
-            (Aggregator::CountDistinct, _) => unreachable!("no two phase for count distinct"),
+            (Aggregator::CountDistinct | Aggregator::ArrayAgg, _) => {
+                unreachable!("no two phase for count distinct or array_agg")
+            }
         }
     }
 
@@ -674,7 +882,9 @@ impl CodeGenerator<CombiningContext, BinType, syn::Expr> for TwoPhaseAggregation
             (Aggregator::Avg, false) => {
                 parse_quote!({ (#current_bin_ident.0 + #new_bin_ident.0, #current_bin_ident.1 + #new_bin_ident.1) })
             }
-            (Aggregator::CountDistinct, _) => unreachable!("no two phase for count distinct"),
+            (Aggregator::CountDistinct | Aggregator::ArrayAgg, _) => {
+                unreachable!("no two phase for count distinct or array_agg")
+            }
         }
     }
 
@@ -726,6 +936,7 @@ impl CodeGenerator<MemoryAddingContext, BinType, syn::Expr> for TwoPhaseAggregat
             }),
             (Aggregator::CountDistinct, true) => todo!(),
             (Aggregator::CountDistinct, false) => todo!(),
+            (Aggregator::ArrayAgg, _) => unreachable!("ARRAY_AGG is not two-phase"),
         }
     }
 
@@ -771,6 +982,7 @@ impl CodeGenerator<MemoryRemovingContext, BinType, syn::Expr> for TwoPhaseAggreg
             }),
             (Aggregator::CountDistinct, true) => todo!(),
             (Aggregator::CountDistinct, false) => todo!(),
+            (Aggregator::ArrayAgg, _) => unreachable!("ARRAY_AGG is not two-phase"),
         }
     }
 
@@ -800,6 +1012,7 @@ impl CodeGenerator<BinAggregatingContext, TypeDef, syn::Expr> for TwoPhaseAggreg
             }
             (Aggregator::CountDistinct, true) => unimplemented!(),
             (Aggregator::CountDistinct, false) => unimplemented!(),
+            (Aggregator::ArrayAgg, _) => unreachable!("ARRAY_AGG is not two-phase"),
         }
     }
 
@@ -849,6 +1062,7 @@ impl CodeGenerator<MemoryAggregatingContext, TypeDef, syn::Expr> for TwoPhaseAgg
             }
             (Aggregator::CountDistinct, true) => unimplemented!(),
             (Aggregator::CountDistinct, false) => unimplemented!(),
+            (Aggregator::ArrayAgg, _) => unreachable!("ARRAY_AGG is not two-phase"),
         }
     }
 
@@ -874,6 +1088,7 @@ impl TwoPhaseAggregation {
             }
             Aggregator::Min | Aggregator::Max => data_type,
             Aggregator::CountDistinct => unimplemented!(),
+            Aggregator::ArrayAgg => unreachable!("ARRAY_AGG is not two-phase"),
         };
         TypeDef::DataType(aggregate_type, nullable)
     }
@@ -892,6 +1107,7 @@ impl TwoPhaseAggregation {
             }
             Aggregator::Min | Aggregator::Max => data_type,
             Aggregator::CountDistinct => unimplemented!(),
+            Aggregator::ArrayAgg => unreachable!("ARRAY_AGG is not two-phase"),
         };
         TypeDef::DataType(aggregate_type, nullable)
     }
@@ -921,6 +1137,7 @@ impl TwoPhaseAggregation {
                 BinType::DataType(aggregate_type),
             ]),
             (Aggregator::CountDistinct, _) => unimplemented!(),
+            (Aggregator::ArrayAgg, _) => unreachable!("ARRAY_AGG is not two-phase"),
         }
     }
 
@@ -970,6 +1187,7 @@ impl TwoPhaseAggregation {
                 BinType::DataType(aggregate_data_type),
             ]),
             (Aggregator::CountDistinct, _) => unimplemented!(),
+            (Aggregator::ArrayAgg, _) => unreachable!("ARRAY_AGG is not two-phase"),
         }
     }
 }
@@ -15,7 +15,7 @@ use tracing::debug;
 
 use crate::code_gen::ValueBinMergingContext;
 use crate::operators::{AggregateProjection, Projection, TwoPhaseAggregateProjection};
-use crate::pipeline::RecordTransform;
+use crate::pipeline::{RecordTransform, WindowFunction};
 use crate::plan_graph::{
     FusedRecordTransform, PlanEdge, PlanNode, PlanOperator, PlanType, WindowFunctionOperator,
 };
@@ -262,14 +262,48 @@ impl Optimizer for TwoPhaseOptimization {
             WindowType::Tumbling { width } => (width, width),
             WindowType::Sliding { width, slide } => (width, slide),
             WindowType::Instant => (Duration::ZERO, Duration::ZERO),
-            WindowType::Session { .. } => return false,
+            WindowType::Session { gap, max_length } => {
+                let Ok(projection): Result<TwoPhaseAggregateProjection> = projection.try_into()
+                else {
+                    return false;
+                };
+                let current_weight = graph.node_weight_mut(node_index).unwrap();
+                *current_weight = PlanNode {
+                    operator: PlanOperator::SessionWindowTwoPhaseAggregator {
+                        gap,
+                        max_length,
+                        projection,
+                    },
+                    output_type: node.output_type.clone(),
+                };
+                return true;
+            }
+            WindowType::Cumulating { step, max_size } => {
+                let Ok(projection): Result<TwoPhaseAggregateProjection> = projection.try_into()
+                else {
+                    return false;
+                };
+                let current_weight = graph.node_weight_mut(node_index).unwrap();
+                *current_weight = PlanNode {
+                    operator: PlanOperator::CumulatingWindowTwoPhaseAggregator {
+                        step,
+                        max_size,
+                        projection,
+                    },
+                    output_type: node.output_type.clone(),
+                };
+                return true;
+            }
         };
         if !slide.is_zero() && width.as_micros() % slide.as_micros() != 0 {
             return false;
         }
-        let Ok(projection) = projection.try_into() else {
+        let Ok(projection): Result<TwoPhaseAggregateProjection> = projection.try_into() else {
             return false;
         };
+        if width != slide && !projection.supports_retraction() {
+            return false;
+        }
         let operator = if width == slide {
             PlanOperator::TumblingWindowTwoPhaseAggregator {
                 tumble_width: width,
@@ -370,6 +404,11 @@ impl Optimizer for WindowTopNOptimization {
             }
             SearchTarget::WindowFunctionOperator => {
                 if let PlanOperator::WindowFunction(window_function_operator) = node.operator {
+                    // only ROW_NUMBER can be computed by keeping the top n rows
+                    if window_function_operator.window_function != WindowFunction::RowNumber {
+                        self.clear();
+                        return false;
+                    }
                     let _field_name = window_function_operator.field_name.clone();
                     self.window_function_operator = Some(window_function_operator);
                     self.nodes.push(node_index);
@@ -418,7 +457,7 @@ impl Optimizer for WindowTopNOptimization {
                             WindowType::Tumbling { width } => (width, width),
                             WindowType::Sliding { width, slide } => (width, slide),
                             WindowType::Instant => (Duration::ZERO, Duration::ZERO),
-                            WindowType::Session { .. } => {
+                            WindowType::Session { .. } | WindowType::Cumulating { .. } => {
                                 return false;
                             }
                         };
@@ -432,6 +471,10 @@ impl Optimizer for WindowTopNOptimization {
                             self.clear();
                             return false;
                         };
+                        if !two_phase_projection.supports_retraction() {
+                            self.clear();
+                            return false;
+                        }
                         let tumbling_local_operator = PlanOperator::TumblingLocalAggregator {
                             width: slide,
                             projection: two_phase_projection.clone(),
@@ -9,17 +9,22 @@ use anyhow::{anyhow, bail};
 use anyhow::{Ok, Result};
 use arrow_schema::DataType;
 use arroyo_datastream::{Operator, WindowType};
-use datafusion_common::{DFField, ScalarValue};
+use arroyo_rpc::api_types::connections::ConnectionType;
+use datafusion::optimizer::utils::split_conjunction;
+use datafusion_common::{DFField, ScalarValue, TableReference};
+use datafusion_expr::expr::Between;
 use datafusion_expr::expr::ScalarUDF;
 use datafusion_expr::{
-    BinaryExpr, BuiltInWindowFunction, Expr, JoinConstraint, LogicalPlan, Window, WriteOp,
+    BinaryExpr, BuiltInWindowFunction, Expr, ExprSchemable, JoinConstraint, LogicalPlan, Window,
+    WindowFrame, WindowFrameBound, WindowFrameUnits, WriteOp,
 };
 
 use quote::quote;
 
 use crate::code_gen::{CodeGenerator, ValuePointerContext, VecAggregationContext};
 use crate::expressions::{AggregateComputation, AggregateResultExtraction, ExpressionContext};
-use crate::external::{ProcessingMode, SqlSink, SqlSource};
+use crate::external::{ProcessingMode, SqlLookup, SqlSink, SqlSource};
+use crate::match_recognize::MatchRecognizeOperator;
 use crate::operators::{UnnestFieldType, UnnestProjection};
 use crate::schemas::window_type_def;
 use crate::tables::{Insert, Table};
@@ -35,6 +40,8 @@ pub enum SqlOperator {
     Source(SourceOperator),
     Aggregator(Box<SqlOperator>, AggregateOperator),
     JoinOperator(Box<SqlOperator>, Box<SqlOperator>, JoinOperator),
+    LookupJoin(Box<SqlOperator>, LookupJoinOperator),
+    MatchRecognize(Box<SqlOperator>, MatchRecognizeOperator),
     Window(Box<SqlOperator>, SqlWindowOperator),
     RecordTransform(Box<SqlOperator>, RecordTransform),
     Union(Vec<SqlOperator>),
@@ -195,9 +202,69 @@ impl AggregateOperator {
     }
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd)]
 pub enum WindowFunction {
     RowNumber,
+    Rank,
+    DenseRank,
+    Ntile(u64),
+    Lag {
+        expression: Expression,
+        offset: usize,
+        default: Option<Expression>,
+    },
+    Lead {
+        expression: Expression,
+        offset: usize,
+        default: Option<Expression>,
+    },
+    FirstValue(Expression),
+    LastValue(Expression),
+}
+
+impl WindowFunction {
+    pub fn result_type(&self) -> TypeDef {
+        let ctx = ValuePointerContext::new();
+        match self {
+            WindowFunction::RowNumber
+            | WindowFunction::Rank
+            | WindowFunction::DenseRank
+            | WindowFunction::Ntile(_) => TypeDef::DataType(DataType::UInt64, false),
+            WindowFunction::Lag {
+                expression,
+                default,
+                ..
+            }
+            | WindowFunction::Lead {
+                expression,
+                default,
+                ..
+            } => {
+                // rows without a neighbor are null unless there is a non-null default
+                let expression_type = expression.expression_type(&ctx);
+                match default {
+                    Some(default)
+                        if !expression_type.is_optional()
+                            && !default.expression_type(&ctx).is_optional() =>
+                    {
+                        expression_type
+                    }
+                    _ => expression_type.as_nullable(),
+                }
+            }
+            WindowFunction::FirstValue(expression) | WindowFunction::LastValue(expression) => {
+                expression.expression_type(&ctx)
+            }
+        }
+    }
+
+    // whether the function needs to compare the sort keys of neighboring rows to find peers
+    pub fn uses_peers(&self) -> bool {
+        matches!(
+            self,
+            WindowFunction::Rank | WindowFunction::DenseRank | WindowFunction::LastValue(_)
+        )
+    }
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -220,6 +287,50 @@ pub struct JoinOperator {
     pub left_key: Projection,
     pub right_key: Projection,
     pub join_type: JoinType,
+    // set for joins that bound the difference between the times of the two sides
+    pub interval: Option<JoinInterval>,
+    // set for joins against the version of the right side valid at the time of each left row
+    pub temporal: bool,
+}
+
+/// A time-range join predicate, matching rows where the difference between the event times of
+/// the left and right rows is between the lower and upper bound (inclusive).
+#[derive(Debug, Clone)]
+pub struct JoinInterval {
+    pub lower_bound_micros: i64,
+    pub upper_bound_micros: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JoinSide {
+    Left,
+    Right,
+}
+
+// a single bound on left_time - right_time, as produced by one comparison in a join filter
+struct TimeBound {
+    left_time: Expr,
+    right_time: Expr,
+    lower_micros: Option<i64>,
+    upper_micros: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LookupJoinOperator {
+    pub left_key: Projection,
+    pub join_type: JoinType,
+    pub lookup: SqlLookup,
+    // projections applied to looked-up values, for example to alias the lookup table
+    pub right_projections: Vec<Projection>,
+}
+
+impl LookupJoinOperator {
+    pub fn right_struct(&self) -> StructDef {
+        self.right_projections
+            .last()
+            .map(|p| p.output_struct())
+            .unwrap_or_else(|| self.lookup.struct_def.clone())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -331,12 +442,16 @@ impl SqlOperator {
             SqlOperator::JoinOperator(left, right, operator) => operator
                 .join_type
                 .output_struct(&left.return_type(), &right.return_type()),
+            SqlOperator::LookupJoin(left, operator) => operator
+                .join_type
+                .output_struct(&left.return_type(), &operator.right_struct()),
+            SqlOperator::MatchRecognize(_, operator) => operator.output_struct(),
             SqlOperator::Window(input, window) => {
                 let mut input_struct = input.return_type();
                 input_struct.fields.push(StructField::new(
                     window.field_name.clone(),
                     None,
-                    TypeDef::DataType(DataType::UInt64, false),
+                    window.window_fn.result_type(),
                 ));
                 input_struct
             }
@@ -356,6 +471,8 @@ impl SqlOperator {
                 !matches!(aggregator.window, WindowType::Instant) || input.has_window()
             }
             SqlOperator::JoinOperator(left, right, _) => left.has_window() || right.has_window(),
+            SqlOperator::LookupJoin(left, _) => left.has_window(),
+            SqlOperator::MatchRecognize(_, _) => false,
             SqlOperator::Window(_, _) => true,
             SqlOperator::RecordTransform(input, _) => input.has_window(),
             SqlOperator::Sink(_, _, input) => input.has_window(),
@@ -375,6 +492,10 @@ impl SqlOperator {
                         && !aggregate_operator.aggregating.aggregates.is_empty())
             }
             SqlOperator::JoinOperator(left, right, join_operator) => {
+                if join_operator.temporal {
+                    // each left row is joined exactly once, when the watermark passes it
+                    return left.is_updating();
+                }
                 // the join will be updating if one of the sides is updating or if a non-window side is nullable.
                 left.is_updating()
                     || right.is_updating()
@@ -385,6 +506,9 @@ impl SqlOperator {
                 input.is_updating() // TODO: figure out when this second case is supposed to be triggered.
                     || (!input.has_window() && sql_window_operator.window_type == WindowType::Instant)
             }
+            SqlOperator::LookupJoin(left, _) => left.is_updating(),
+            // each match is emitted once, when the watermark passes its last row
+            SqlOperator::MatchRecognize(_, _) => false,
             SqlOperator::RecordTransform(input, _) => input.is_updating(),
             SqlOperator::Sink(_, _, input) => input.is_updating(),
             SqlOperator::NamedTable(_, table_operator) => table_operator.is_updating(),
@@ -392,16 +516,53 @@ impl SqlOperator {
         }
     }
 
+    /// The name of the output column that holds the event time of each record, if the
+    /// `event_time_field` of a source is passed through unchanged.
+    pub(crate) fn event_time_column(&self) -> Option<String> {
+        match self {
+            SqlOperator::Source(source) => match &source.timestamp_override {
+                Some(Expression::Column(column)) => Some(column.name().to_string()),
+                _ => None,
+            },
+            SqlOperator::RecordTransform(input, transform) => match transform {
+                RecordTransform::ValueProjection(projection) => {
+                    let input_column = input.event_time_column()?;
+                    projection
+                        .fields
+                        .iter()
+                        .find(|(_, expr)| {
+                            matches!(expr, Expression::Column(column) if column.name() == input_column)
+                        })
+                        .map(|(column, _)| column.name.clone())
+                }
+                RecordTransform::KeyProjection(_) | RecordTransform::Filter(_) => {
+                    input.event_time_column()
+                }
+                RecordTransform::TimestampAssignment(Expression::Column(column)) => {
+                    Some(column.name().to_string())
+                }
+                RecordTransform::TimestampAssignment(_) | RecordTransform::UnnestProjection(_) => {
+                    None
+                }
+            },
+            SqlOperator::NamedTable(_, input) => input.event_time_column(),
+            _ => None,
+        }
+    }
+
     pub(crate) fn get_window(&self) -> Option<WindowType> {
         match self {
             SqlOperator::Source(_) => None,
             SqlOperator::Aggregator(input, aggregator) => match &aggregator.window {
                 WindowType::Tumbling { .. }
                 | WindowType::Sliding { .. }
-                | WindowType::Session { .. } => Some(aggregator.window.clone()),
+                | WindowType::Session { .. }
+                | WindowType::Cumulating { .. } => Some(aggregator.window.clone()),
                 WindowType::Instant => input.get_window(),
             },
             SqlOperator::JoinOperator(left, _, _) => left.get_window(),
+            SqlOperator::LookupJoin(left, _) => left.get_window(),
+            SqlOperator::MatchRecognize(_, _) => None,
             SqlOperator::Window(_, sql_window_operator) => {
                 Some(sql_window_operator.window_type.clone())
             }
@@ -699,6 +860,10 @@ impl<'a> SqlPipelineBuilder<'a> {
             bail!("updating aggregates only support two phase aggregations. Currently count distinct is not supported");
         }
 
+        if source.is_updating() && !aggregating.supports_retraction() {
+            bail!("aggregates over updating inputs must be retractable, which arg_max, arg_min and UDAFs without a retract function are not");
+        }
+
         Ok(SqlOperator::Aggregator(
             Box::new(source),
             AggregateOperator {
@@ -755,7 +920,7 @@ impl<'a> SqlPipelineBuilder<'a> {
     fn is_window(expression: &Expr) -> bool {
         match expression {
             Expr::ScalarUDF(ScalarUDF { fun, args: _ }) => {
-                matches!(fun.name.as_str(), "hop" | "tumble" | "session")
+                matches!(fun.name.as_str(), "hop" | "tumble" | "session" | "cumulate")
             }
             Expr::Alias(datafusion_expr::expr::Alias { expr, name: _ }) => Self::is_window(expr),
             _ => false,
@@ -781,11 +946,28 @@ impl<'a> SqlPipelineBuilder<'a> {
                     Ok(Some(WindowType::Tumbling { width }))
                 }
                 "session" => {
-                    if args.len() != 1 {
-                        unreachable!("wrong number of arguments for session(), expected one");
+                    if args.is_empty() || args.len() > 2 {
+                        unreachable!(
+                            "wrong number of arguments for session(), expected one or two"
+                        );
                     }
                     let gap = Self::get_duration(&args[0])?;
-                    Ok(Some(WindowType::Session { gap }))
+                    let max_length = args.get(1).map(Self::get_duration).transpose()?;
+                    if max_length.map_or(false, |max_length| max_length < gap) {
+                        bail!("the maximum length of a session can't be less than its gap");
+                    }
+                    Ok(Some(WindowType::Session { gap, max_length }))
+                }
+                "cumulate" => {
+                    if args.len() != 2 {
+                        unreachable!("wrong number of arguments for cumulate(), expected two");
+                    }
+                    let step = Self::get_duration(&args[0])?;
+                    let max_size = Self::get_duration(&args[1])?;
+                    if step.is_zero() || max_size.as_nanos() % step.as_nanos() != 0 {
+                        bail!("the maximum size of a cumulating window must be a multiple of its step");
+                    }
+                    Ok(Some(WindowType::Cumulating { step, max_size }))
                 }
                 _ => Ok(None),
             },
@@ -793,7 +975,7 @@ impl<'a> SqlPipelineBuilder<'a> {
             _ => Ok(None),
         }
     }
-    fn get_duration(expression: &Expr) -> Result<Duration> {
+    pub(crate) fn get_duration(expression: &Expr) -> Result<Duration> {
         match expression {
             Expr::Literal(ScalarValue::IntervalDayTime(Some(val))) => {
                 Ok(Duration::from_millis(*val as u64))
@@ -809,6 +991,10 @@ impl<'a> SqlPipelineBuilder<'a> {
     }
 
     fn insert_join(&mut self, join: &datafusion_expr::logical_plan::Join) -> Result<SqlOperator> {
+        if let Some((lookup, right_projections)) = self.insert_lookup_plan(&join.right)? {
+            return self.insert_lookup_join(join, lookup, right_projections);
+        }
+
         let left_input = self.insert_sql_plan(&join.left)?;
         let right_input = self.insert_sql_plan(&join.right)?;
         match join.join_constraint {
@@ -824,52 +1010,77 @@ impl<'a> SqlPipelineBuilder<'a> {
             _ => {}
         }
         let mut join_pairs = join.on.clone();
-        if let Some(Expr::BinaryExpr(BinaryExpr { left, op, right })) = &join.filter {
+        let mut time_bounds = vec![];
+        for predicate in join.filter.iter().flat_map(split_conjunction) {
+            if let Some(bound) = Self::join_time_bound(join, predicate)? {
+                time_bounds.push(bound);
+                continue;
+            }
+            let Expr::BinaryExpr(BinaryExpr { left, op, right }) = predicate else {
+                bail!(
+                    "only equality joins are supported, not filter {:?}",
+                    predicate
+                );
+            };
             if *op != datafusion_expr::Operator::Eq {
                 bail!("only equality joins are supported");
             }
-            // check which side each column comes from. Assumes there's at least one field
-            let left_relation = join
-                .schema
-                .fields()
-                .first()
-                .unwrap()
-                .qualifier()
-                .as_ref()
-                .unwrap()
-                .to_string();
-            let right_relation = join
-                .schema
-                .fields()
-                .last()
-                .unwrap()
-                .qualifier()
-                .as_ref()
-                .unwrap()
-                .to_string();
-            let left_table = Column::convert_expr(left)?.relation.unwrap();
-            let right_table = Column::convert_expr(right)?.relation.unwrap();
-            let pair = if right_table == right_relation && left_table == left_relation {
-                (left.as_ref().clone(), right.as_ref().clone())
-            } else if left_table == right_relation && right_table == left_relation {
-                ((right.as_ref()).clone(), left.as_ref().clone())
-            } else {
-                bail!("join filter must contain at least one column from each side of the join");
+            let pair = match (Self::join_side(join, left)?, Self::join_side(join, right)?) {
+                (Some(JoinSide::Left), Some(JoinSide::Right)) => {
+                    (left.as_ref().clone(), right.as_ref().clone())
+                }
+                (Some(JoinSide::Right), Some(JoinSide::Left)) => {
+                    (right.as_ref().clone(), left.as_ref().clone())
+                }
+                _ => {
+                    bail!("join filter must contain at least one column from each side of the join")
+                }
             };
             join_pairs.push(pair);
-        } else if join.filter.is_some() {
-            bail!(
-                "only equality joins are supported, not filter {:?}",
-                join.filter
-            );
         }
 
+        let temporal = match self.temporal_reference(&join.right) {
+            Some(as_of) => {
+                Self::check_temporal_time(join, &left_input, as_of)?;
+                true
+            }
+            None => false,
+        };
+        if temporal {
+            if !time_bounds.is_empty() {
+                bail!("temporal joins can't also have time-range conditions");
+            }
+            if left_input.has_window() {
+                bail!("temporal joins don't support windowed inputs");
+            }
+            if !matches!(join_type, JoinType::Inner | JoinType::Left) {
+                bail!("temporal joins only support inner and left joins");
+            }
+            if left_input.is_updating() {
+                bail!("the left side of a temporal join can't be updating");
+            }
+        }
+
+        let interval = if time_bounds.is_empty() {
+            None
+        } else {
+            if left_input.has_window() {
+                bail!("windowed joins can't also have time-range conditions");
+            }
+            if join_type != JoinType::Inner {
+                bail!("joins with time-range conditions only support inner joins");
+            }
+            if left_input.is_updating() || right_input.is_updating() {
+                bail!("joins with time-range conditions don't support updating inputs");
+            }
+            Some(self.join_interval(&left_input, &right_input, time_bounds)?)
+        };
+
         let join_projection_field_names: Vec<_> = join_pairs
             .iter()
             .map(|(left, _right)| Column::convert_expr(left))
             .collect::<Result<Vec<_>>>()?;
-        let (left_computations, right_computations): (Vec<_>, Vec<_>) = join
-            .on
+        let (left_computations, right_computations): (Vec<_>, Vec<_>) = join_pairs
             .iter()
             .map(|(left, right)| {
                 Ok((
@@ -909,10 +1120,399 @@ impl<'a> SqlPipelineBuilder<'a> {
                 left_key,
                 right_key,
                 join_type,
+                interval,
+                temporal,
             },
         ))
     }
 
+    // if the plan reads a table reference joined `FOR SYSTEM_TIME AS OF` an event-time column,
+    // the parts of the name of that column
+    fn temporal_reference(&self, plan: &LogicalPlan) -> Option<&Vec<String>> {
+        let temporal_tables = &self.schema_provider.temporal_tables;
+        match plan {
+            // an aliased table is only referenced by its alias
+            LogicalPlan::SubqueryAlias(alias) => temporal_tables.get(alias.alias.table()),
+            LogicalPlan::TableScan(table_scan) => {
+                temporal_tables.get(table_scan.table_name.table())
+            }
+            LogicalPlan::Projection(_) | LogicalPlan::Filter(_) => plan
+                .inputs()
+                .into_iter()
+                .find_map(|input| self.temporal_reference(input)),
+            _ => None,
+        }
+    }
+
+    // temporal joins read the version of the right side as of the event time of each left row,
+    // so the AS OF column has to be the left side's event time
+    fn check_temporal_time(
+        join: &datafusion_expr::logical_plan::Join,
+        left_input: &SqlOperator,
+        as_of: &[String],
+    ) -> Result<()> {
+        let (qualifier, name) = match as_of {
+            [name] => (None, name),
+            [qualifier, name] => (Some(TableReference::bare(qualifier.as_str())), name),
+            _ => bail!(
+                "FOR SYSTEM_TIME AS OF {} must be the event time column of the left side of the join",
+                as_of.join(".")
+            ),
+        };
+        if join
+            .left
+            .schema()
+            .field_with_name(qualifier.as_ref(), name)
+            .is_err()
+        {
+            bail!(
+                "FOR SYSTEM_TIME AS OF {} must be a column of the left side of the join",
+                as_of.join(".")
+            );
+        }
+
+        let Some(event_time_column) = left_input.event_time_column() else {
+            bail!("temporal joins require the left side of the join to have an event time, set with the event_time_field option of its source");
+        };
+        if *name != event_time_column {
+            bail!(
+                "FOR SYSTEM_TIME AS OF {} must be the event time column of the left side of the join, {}",
+                as_of.join("."),
+                event_time_column
+            );
+        }
+        Ok(())
+    }
+
+    // which side of the join all of the columns in the expression come from, if any
+    fn join_side(
+        join: &datafusion_expr::logical_plan::Join,
+        expr: &Expr,
+    ) -> Result<Option<JoinSide>> {
+        let columns = expr.to_columns()?;
+        if columns.is_empty() {
+            return Ok(None);
+        }
+        if columns
+            .iter()
+            .all(|c| join.left.schema().field_from_column(c).is_ok())
+        {
+            Ok(Some(JoinSide::Left))
+        } else if columns
+            .iter()
+            .all(|c| join.right.schema().field_from_column(c).is_ok())
+        {
+            Ok(Some(JoinSide::Right))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // splits `expr [+-] interval` into the expression and the offset in microseconds
+    fn time_offset(expr: &Expr) -> (&Expr, i64) {
+        if let Expr::BinaryExpr(BinaryExpr { left, op, right }) = expr {
+            if let std::result::Result::Ok(duration) = Self::get_duration(right) {
+                match op {
+                    datafusion_expr::Operator::Plus => {
+                        return (left, duration.as_micros() as i64);
+                    }
+                    datafusion_expr::Operator::Minus => {
+                        return (left, -(duration.as_micros() as i64));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        (expr, 0)
+    }
+
+    // recognizes comparisons between timestamps on either side of the join, like
+    // `a.ts >= b.ts - INTERVAL '5' MINUTE` or `a.ts BETWEEN b.ts AND b.ts + INTERVAL '1' MINUTE`
+    fn join_time_bound(
+        join: &datafusion_expr::logical_plan::Join,
+        predicate: &Expr,
+    ) -> Result<Option<TimeBound>> {
+        if let Expr::Between(Between {
+            expr,
+            negated: false,
+            low,
+            high,
+        }) = predicate
+        {
+            let lower = Expr::BinaryExpr(BinaryExpr::new(
+                expr.clone(),
+                datafusion_expr::Operator::GtEq,
+                low.clone(),
+            ));
+            let upper = Expr::BinaryExpr(BinaryExpr::new(
+                expr.clone(),
+                datafusion_expr::Operator::LtEq,
+                high.clone(),
+            ));
+            let (Some(lower), Some(upper)) = (
+                Self::join_time_bound(join, &lower)?,
+                Self::join_time_bound(join, &upper)?,
+            ) else {
+                return Ok(None);
+            };
+            if lower.left_time != upper.left_time || lower.right_time != upper.right_time {
+                return Ok(None);
+            }
+            return Ok(Some(TimeBound {
+                lower_micros: lower.lower_micros.or(upper.lower_micros),
+                upper_micros: lower.upper_micros.or(upper.upper_micros),
+                ..lower
+            }));
+        }
+
+        let Expr::BinaryExpr(BinaryExpr { left, op, right }) = predicate else {
+            return Ok(None);
+        };
+        if !matches!(
+            op,
+            datafusion_expr::Operator::Gt
+                | datafusion_expr::Operator::GtEq
+                | datafusion_expr::Operator::Lt
+                | datafusion_expr::Operator::LtEq
+        ) {
+            return Ok(None);
+        }
+
+        let (left_base, left_offset) = Self::time_offset(left);
+        let (right_base, right_offset) = Self::time_offset(right);
+
+        // normalize to `left_time - right_time <op> difference`
+        let (left_time, right_time, op, difference) = match (
+            Self::join_side(join, left_base)?,
+            Self::join_side(join, right_base)?,
+        ) {
+            (Some(JoinSide::Left), Some(JoinSide::Right)) => {
+                (left_base, right_base, *op, right_offset - left_offset)
+            }
+            (Some(JoinSide::Right), Some(JoinSide::Left)) => {
+                let flipped = match op {
+                    datafusion_expr::Operator::Gt => datafusion_expr::Operator::Lt,
+                    datafusion_expr::Operator::GtEq => datafusion_expr::Operator::LtEq,
+                    datafusion_expr::Operator::Lt => datafusion_expr::Operator::Gt,
+                    _ => datafusion_expr::Operator::GtEq,
+                };
+                (right_base, left_base, flipped, left_offset - right_offset)
+            }
+            _ => return Ok(None),
+        };
+
+        let is_timestamp = |expr: &Expr, schema: &datafusion_common::DFSchema| {
+            matches!(
+                expr.get_type(schema),
+                std::result::Result::Ok(DataType::Timestamp(_, _))
+            )
+        };
+        if !is_timestamp(left_time, join.left.schema())
+            || !is_timestamp(right_time, join.right.schema())
+        {
+            return Ok(None);
+        }
+
+        let (lower_micros, upper_micros) = match op {
+            datafusion_expr::Operator::Gt => (Some(difference + 1), None),
+            datafusion_expr::Operator::GtEq => (Some(difference), None),
+            datafusion_expr::Operator::Lt => (None, Some(difference - 1)),
+            _ => (None, Some(difference)),
+        };
+
+        Ok(Some(TimeBound {
+            left_time: left_time.clone(),
+            right_time: right_time.clone(),
+            lower_micros,
+            upper_micros,
+        }))
+    }
+
+    fn join_interval(
+        &self,
+        left_input: &SqlOperator,
+        right_input: &SqlOperator,
+        time_bounds: Vec<TimeBound>,
+    ) -> Result<JoinInterval> {
+        let first = &time_bounds[0];
+        if time_bounds
+            .iter()
+            .any(|b| b.left_time != first.left_time || b.right_time != first.right_time)
+        {
+            bail!("all time-range join conditions must compare the same pair of timestamps");
+        }
+
+        let lower_bound_micros = time_bounds.iter().filter_map(|b| b.lower_micros).max();
+        let upper_bound_micros = time_bounds.iter().filter_map(|b| b.upper_micros).min();
+        let (Some(lower_bound_micros), Some(upper_bound_micros)) =
+            (lower_bound_micros, upper_bound_micros)
+        else {
+            bail!("time-range join conditions must bound the time difference from above and below, for example a.ts BETWEEN b.ts - INTERVAL '1' MINUTE AND b.ts");
+        };
+        if lower_bound_micros > upper_bound_micros {
+            bail!("time-range join conditions can never be satisfied");
+        }
+
+        // the join matches and evicts rows by their event time, so the conditions must be on the
+        // columns that event time is read from
+        let left_time = self
+            .ctx(&left_input.return_type())
+            .compile_expr(&first.left_time)?;
+        let right_time = self
+            .ctx(&right_input.return_type())
+            .compile_expr(&first.right_time)?;
+        for (time, input) in [(left_time, left_input), (right_time, right_input)] {
+            let Some(event_time_column) = input.event_time_column() else {
+                bail!("time-range join conditions require both sides of the join to have an event time, set with the event_time_field option of their sources");
+            };
+            if !matches!(&time, Expression::Column(column) if column.name() == event_time_column) {
+                bail!(
+                    "time-range join conditions must compare the event time columns of the two sides, but {:?} is not the event time column {}",
+                    time,
+                    event_time_column
+                );
+            }
+        }
+
+        Ok(JoinInterval {
+            lower_bound_micros,
+            upper_bound_micros,
+        })
+    }
+
+    // If the plan reads from a lookup table, returns the lookup along with the projections that
+    // should be applied to looked-up values.
+    fn insert_lookup_plan(
+        &mut self,
+        plan: &LogicalPlan,
+    ) -> Result<Option<(SqlLookup, Vec<Projection>)>> {
+        match plan {
+            LogicalPlan::TableScan(table_scan) => {
+                let Some(Table::ConnectorTable(table)) = self
+                    .schema_provider
+                    .get_table(&table_scan.table_name.to_string())
+                else {
+                    return Ok(None);
+                };
+
+                if !matches!(table.connection_type, ConnectionType::Lookup) {
+                    return Ok(None);
+                }
+
+                let lookup = table
+                    .as_sql_lookup()
+                    .map_err(|e| anyhow!("failed to plan {}: {}", table_scan.table_name, e))?;
+
+                let mut projections = vec![];
+                if let Some(projection) = table_scan.projection.as_ref() {
+                    let fields = projection
+                        .iter()
+                        .map(|i| lookup.struct_def.fields[*i].clone())
+                        .map(|t| {
+                            (
+                                Column {
+                                    relation: Some(table_scan.table_name.to_string()),
+                                    name: t.name.clone(),
+                                },
+                                Expression::Column(ColumnExpression::new(t.clone())),
+                            )
+                        })
+                        .collect();
+                    projections.push(Projection::new(fields));
+                }
+
+                Ok(Some((lookup, projections)))
+            }
+            LogicalPlan::SubqueryAlias(subquery_alias) => {
+                let Some((lookup, mut projections)) =
+                    self.insert_lookup_plan(&subquery_alias.input)?
+                else {
+                    return Ok(None);
+                };
+
+                let input_type = projections
+                    .last()
+                    .map(|p| p.output_struct())
+                    .unwrap_or_else(|| lookup.struct_def.clone());
+
+                let field_computations = input_type
+                    .fields
+                    .iter()
+                    .map(|field| Expression::Column(ColumnExpression::new(field.clone())));
+
+                let field_names = subquery_alias
+                    .schema
+                    .fields()
+                    .iter()
+                    .map(|field| Column::convert(&field.qualified_column()));
+
+                projections.push(Projection::new(
+                    field_names.zip(field_computations).collect(),
+                ));
+
+                Ok(Some((lookup, projections)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn insert_lookup_join(
+        &mut self,
+        join: &datafusion_expr::logical_plan::Join,
+        lookup: SqlLookup,
+        right_projections: Vec<Projection>,
+    ) -> Result<SqlOperator> {
+        let left_input = self.insert_sql_plan(&join.left)?;
+        if left_input.is_updating() {
+            bail!("lookup joins are not supported on updating inputs");
+        }
+
+        let join_type: JoinType = join.join_type.try_into()?;
+        if !matches!(join_type, JoinType::Inner | JoinType::Left) {
+            bail!(
+                "{:?} joins are not supported against lookup tables; use an inner or left join",
+                join_type
+            );
+        }
+
+        if join.filter.is_some() || join.on.len() != 1 {
+            bail!("lookup joins must have a single equality condition on a column of the lookup table");
+        }
+
+        let (left, right) = &join.on[0];
+
+        let mut operator = LookupJoinOperator {
+            left_key: Projection::new(vec![]),
+            join_type,
+            lookup,
+            right_projections,
+        };
+
+        let right_struct = operator.right_struct();
+        let right_expr = self.ctx(&right_struct).compile_expr(right)?;
+        match &right_expr {
+            Expression::Column(column) if column.name() == operator.lookup.key_column => {}
+            _ => bail!(
+                "lookup joins must be on the primary key of the lookup table ({}), not {}",
+                operator.lookup.key_column,
+                right
+            ),
+        }
+
+        let left_expr = self.ctx(&left_input.return_type()).compile_expr(left)?;
+        Self::assert_no_unnest("join", &left_expr)?;
+        // the key may be an arbitrary expression over the left side, so it gets a fixed name
+        operator.left_key = Projection::new(vec![(
+            Column {
+                relation: None,
+                name: "lookup_key".to_string(),
+            },
+            left_expr,
+        )]);
+
+        Ok(SqlOperator::LookupJoin(Box::new(left_input), operator))
+    }
+
     fn insert_table_scan(
         &mut self,
         table_scan: &datafusion::logical_expr::TableScan,
@@ -957,6 +1557,10 @@ impl<'a> SqlPipelineBuilder<'a> {
             bail!("don't support window functions over updating inputs");
         }
 
+        if window.window_expr.len() > 1 {
+            bail!("only a single window function is supported per window");
+        }
+
         if let Some(expr) = window.window_expr.get(0) {
             let w = match expr {
                 Expr::Alias(datafusion_expr::expr::Alias { expr, name: _ }) => match **expr {
@@ -966,15 +1570,22 @@ impl<'a> SqlPipelineBuilder<'a> {
                 Expr::WindowFunction(window_function) => window_function,
                 _ => bail!("expected window function"),
             };
+            if !Self::is_default_window_frame(&w.window_frame, !w.order_by.is_empty()) {
+                bail!(
+                    "window frames are not supported, found {}; remove the frame clause",
+                    w.window_frame
+                );
+            }
+
+            let input_struct = input.return_type();
+            let mut ctx = self.ctx(&input_struct);
+
             let window_fn = match &w.fun {
                 datafusion_expr::WindowFunction::AggregateFunction(_) => {
                     bail!("window aggregate functions not yet supported")
                 }
-                datafusion_expr::WindowFunction::BuiltInWindowFunction(
-                    BuiltInWindowFunction::RowNumber,
-                ) => WindowFunction::RowNumber,
-                datafusion_expr::WindowFunction::BuiltInWindowFunction(w) => {
-                    bail!("Window function {} not yet supported", w);
+                datafusion_expr::WindowFunction::BuiltInWindowFunction(function) => {
+                    Self::window_function(&ctx, function, &w.args)?
                 }
                 datafusion_expr::WindowFunction::AggregateUDF(_) => {
                     bail!("Window UDAFs not yet supported");
@@ -984,9 +1595,6 @@ impl<'a> SqlPipelineBuilder<'a> {
                 }
             };
 
-            let input_struct = input.return_type();
-            let mut ctx = self.ctx(&input_struct);
-
             let order_by: Vec<_> = w
                 .order_by
                 .iter()
@@ -1048,6 +1656,113 @@ impl<'a> SqlPipelineBuilder<'a> {
         bail!("no expression for window");
     }
 
+    fn window_function(
+        ctx: &ExpressionContext,
+        function: &BuiltInWindowFunction,
+        args: &[Expr],
+    ) -> Result<WindowFunction> {
+        let argument = |index: usize| -> Result<Expression> {
+            let arg = args
+                .get(index)
+                .ok_or_else(|| anyhow!("{} requires an argument", function))?;
+            let expr = ctx.compile_expr(arg)?;
+            Self::assert_no_unnest("window function", &expr)?;
+            Ok(expr)
+        };
+
+        Ok(match function {
+            BuiltInWindowFunction::RowNumber => WindowFunction::RowNumber,
+            BuiltInWindowFunction::Rank => WindowFunction::Rank,
+            BuiltInWindowFunction::DenseRank => WindowFunction::DenseRank,
+            BuiltInWindowFunction::Ntile => {
+                let buckets = args
+                    .get(0)
+                    .map(|arg| Self::window_function_literal(function, arg))
+                    .transpose()?
+                    .ok_or_else(|| anyhow!("NTILE requires the number of buckets"))?;
+                if buckets == 0 {
+                    bail!("NTILE requires a positive number of buckets");
+                }
+                WindowFunction::Ntile(buckets)
+            }
+            BuiltInWindowFunction::Lag | BuiltInWindowFunction::Lead => {
+                let expression = argument(0)?;
+                let offset = args
+                    .get(1)
+                    .map(|arg| Self::window_function_literal(function, arg))
+                    .transpose()?
+                    .unwrap_or(1) as usize;
+                let default = match args.get(2) {
+                    None | Some(Expr::Literal(ScalarValue::Null)) => None,
+                    Some(_) => {
+                        let default = argument(2)?;
+                        let value_ctx = ValuePointerContext::new();
+                        if expression.expression_type(&value_ctx).as_nullable()
+                            != default.expression_type(&value_ctx).as_nullable()
+                        {
+                            bail!(
+                                "the default value of {} must have the same type as its argument",
+                                function
+                            );
+                        }
+                        Some(default)
+                    }
+                };
+                if *function == BuiltInWindowFunction::Lag {
+                    WindowFunction::Lag {
+                        expression,
+                        offset,
+                        default,
+                    }
+                } else {
+                    WindowFunction::Lead {
+                        expression,
+                        offset,
+                        default,
+                    }
+                }
+            }
+            BuiltInWindowFunction::FirstValue => WindowFunction::FirstValue(argument(0)?),
+            BuiltInWindowFunction::LastValue => WindowFunction::LastValue(argument(0)?),
+            _ => bail!("Window function {} not yet supported", function),
+        })
+    }
+
+    /// Whether a window frame is the one implied when the OVER clause has no frame clause, which is
+    /// the only frame that window functions are computed over
+    fn is_default_window_frame(frame: &WindowFrame, has_order_by: bool) -> bool {
+        // unbounded frame bounds may have been coerced to a typed null
+        let unbounded_preceding =
+            matches!(&frame.start_bound, WindowFrameBound::Preceding(v) if v.is_null());
+        if has_order_by {
+            frame.units == WindowFrameUnits::Range
+                && unbounded_preceding
+                && frame.end_bound == WindowFrameBound::CurrentRow
+        } else {
+            unbounded_preceding
+                && matches!(&frame.end_bound, WindowFrameBound::Following(v) if v.is_null())
+        }
+    }
+
+    fn window_function_literal(function: &BuiltInWindowFunction, expr: &Expr) -> Result<u64> {
+        let value = match expr {
+            Expr::Literal(ScalarValue::Int8(Some(v))) => *v as i64,
+            Expr::Literal(ScalarValue::Int16(Some(v))) => *v as i64,
+            Expr::Literal(ScalarValue::Int32(Some(v))) => *v as i64,
+            Expr::Literal(ScalarValue::Int64(Some(v))) => *v,
+            Expr::Literal(ScalarValue::UInt8(Some(v))) => *v as i64,
+            Expr::Literal(ScalarValue::UInt16(Some(v))) => *v as i64,
+            Expr::Literal(ScalarValue::UInt32(Some(v))) => *v as i64,
+            Expr::Literal(ScalarValue::UInt64(Some(v))) => i64::try_from(*v)?,
+            _ => bail!(
+                "{} requires an integer literal argument, found {}",
+                function,
+                expr
+            ),
+        };
+        u64::try_from(value).map_err(|_| anyhow!("{} requires a non-negative argument", function))
+    }
+
     fn insert_subquery_alias(
         &mut self,
         subquery_alias: &datafusion_expr::logical_plan::SubqueryAlias,
@@ -1118,6 +1833,7 @@ impl<'a> SqlPipelineBuilder<'a> {
                         name: _,
                         logical_plan: _,
                     } => todo!(),
+                    Table::MatchRecognize(m) => bail!("can't insert into {}", m.name),
                 }
             }
             Insert::Anonymous { logical_plan } => {
@@ -3,18 +3,23 @@ use std::{
     time::Duration,
 };
 
-use arrow_schema::DataType;
 use arroyo_datastream::{
-    EdgeType, ExpressionReturnType, NonWindowAggregator, Operator, PeriodicWatermark, Program,
-    ProgramUdf, SlidingAggregatingTopN, SlidingWindowAggregator, StreamEdge, StreamNode,
-    TumblingTopN, TumblingWindowAggregator, WindowAgg, WindowType,
+    CumulatingWindowAggregator, EdgeType, ExpressionReturnType, IntervalJoin, LookupJoin,
+    NonWindowAggregator, Operator, PeriodicWatermark, Program, ProgramUdf, SessionWindowAggregator,
+    SlidingAggregatingTopN, SlidingWindowAggregator, StreamEdge, StreamNode, TumblingTopN,
+    TumblingWindowAggregator, WindowAgg, WindowType,
 };
 
+use petgraph::algo::toposort;
 use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::unionfind::UnionFind;
+use petgraph::visit::EdgeRef;
 use quote::{quote, ToTokens};
+use sha2::{Digest, Sha256};
 use syn::{parse_quote, parse_str, Type};
 
 use crate::expressions::AggregateComputation;
+use crate::match_recognize::MatchRecognizeOperator;
 use crate::pipeline::InputsUpdating;
 use crate::{
     code_gen::{
@@ -23,16 +28,17 @@ use crate::{
         ValueBinMergingContext, ValuePointerContext, VecAggregationContext,
     },
     expressions::{Column, ColumnExpression, Expression, SortExpression},
-    external::{ProcessingMode, SinkUpdateType, SqlSink, SqlSource},
+    external::{ProcessingMode, SinkUpdateType, SqlLookup, SqlSink, SqlSource},
     operators::{AggregateProjection, Projection, TwoPhaseAggregateProjection},
     optimizations::optimize,
     pipeline::{
-        JoinType, MethodCompiler, RecordTransform, SourceOperator, SqlOperator, WindowFunction,
+        JoinInterval, JoinType, MethodCompiler, RecordTransform, SourceOperator, SqlOperator,
+        WindowFunction,
     },
-    types::{StructDef, StructField, StructPair, TypeDef},
+    types::{StructDef, StructField, StructPair},
     ArroyoSchemaProvider, CompiledSql, SqlConfig,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use petgraph::Direction;
 
 #[derive(Debug, Clone)]
@@ -55,6 +61,16 @@ pub enum PlanOperator {
         tumble_width: Duration,
         projection: TwoPhaseAggregateProjection,
     },
+    SessionWindowTwoPhaseAggregator {
+        gap: Duration,
+        max_length: Option<Duration>,
+        projection: TwoPhaseAggregateProjection,
+    },
+    CumulatingWindowTwoPhaseAggregator {
+        step: Duration,
+        max_size: Duration,
+        projection: TwoPhaseAggregateProjection,
+    },
     SlidingWindowTwoPhaseAggregator {
         width: Duration,
         slide: Duration,
@@ -66,8 +82,19 @@ pub enum PlanOperator {
         right_expiration: Duration,
         join_type: JoinType,
     },
+    IntervalJoin(JoinInterval),
+    TemporalJoin(JoinType),
     JoinListMerge(JoinType, StructPair),
     JoinPairMerge(JoinType, StructPair, InputsUpdating),
+    // merges the pairs of joins that only emit appends, even for outer joins
+    AppendingJoinMerge(JoinType, StructPair),
+    LookupJoin {
+        join_type: JoinType,
+        left_struct: StructDef,
+        lookup: SqlLookup,
+        right_projections: Vec<Projection>,
+    },
+    MatchRecognize(MatchRecognizeOperator),
     Flatten,
     // TODO: figure out naming of various things called 'window'
     WindowFunction(WindowFunctionOperator),
@@ -106,6 +133,139 @@ pub struct WindowFunctionOperator {
     pub field_name: String,
 }
 
+impl WindowFunctionOperator {
+    /// Generates an expression that computes the window function over the rows of a partition,
+    /// which have already been sorted and are bound to `rows`. It returns the statements to run
+    /// before iterating, the statements to run for each row (bound to `arg`, at `index`) and
+    /// the value for that row.
+    fn window_value(
+        &self,
+    ) -> (
+        proc_macro2::TokenStream,
+        proc_macro2::TokenStream,
+        proc_macro2::TokenStream,
+    ) {
+        let ctx = ValuePointerContext::new();
+        let mut setup = if self.window_function.uses_peers() {
+            let sort_tokens = SortExpression::sort_tuple_expression(&self.order_by);
+            quote!(let keys: Vec<_> = rows.iter().map(|arg| #sort_tokens).collect();)
+        } else {
+            quote!()
+        };
+        let nullable = self.window_function.result_type().is_optional();
+
+        let (step, value) = match &self.window_function {
+            WindowFunction::RowNumber => (quote!(), quote!(i as u64)),
+            WindowFunction::Rank => {
+                setup.extend(quote!(let mut rank = 0u64;));
+                (
+                    quote!(if index == 0 || keys[index] != keys[index - 1] {
+                        rank = i as u64;
+                    }),
+                    quote!(rank),
+                )
+            }
+            WindowFunction::DenseRank => {
+                setup.extend(quote!(let mut dense_rank = 0u64;));
+                (
+                    quote!(if index == 0 || keys[index] != keys[index - 1] {
+                        dense_rank += 1;
+                    }),
+                    quote!(dense_rank),
+                )
+            }
+            WindowFunction::Ntile(buckets) => {
+                // the first (len % buckets) buckets hold one more row than the rest
+                let buckets = *buckets as usize;
+                setup.extend(quote! {
+                    let ntile_size = rows.len() / #buckets;
+                    let ntile_remainder = rows.len() % #buckets;
+                    let ntile_large = ntile_remainder * (ntile_size + 1);
+                });
+                (
+                    quote!(),
+                    quote!(
+                        (if index < ntile_large {
+                            index / (ntile_size + 1)
+                        } else {
+                            ntile_remainder + (index - ntile_large) / ntile_size
+                        }) as u64
+                            + 1
+                    ),
+                )
+            }
+            WindowFunction::Lag {
+                expression,
+                offset,
+                default,
+            }
+            | WindowFunction::Lead {
+                expression,
+                offset,
+                default,
+            } => {
+                let neighbor = if matches!(self.window_function, WindowFunction::Lag { .. }) {
+                    quote!(index.checked_sub(#offset))
+                } else {
+                    quote!(index.checked_add(#offset).filter(|j| *j < rows.len()))
+                };
+                let mut value = expression.generate(&ctx).to_token_stream();
+                if nullable && !expression.expression_type(&ctx).is_optional() {
+                    value = quote!(Some(#value));
+                }
+                let default = match default {
+                    Some(default) => {
+                        let default_value = default.generate(&ctx);
+                        if nullable && !default.expression_type(&ctx).is_optional() {
+                            quote!(Some(#default_value))
+                        } else {
+                            quote!(#default_value)
+                        }
+                    }
+                    None => quote!(None),
+                };
+                (
+                    quote!(),
+                    quote!(match #neighbor {
+                        Some(j) => {
+                            let arg = &rows[j];
+                            #value
+                        }
+                        None => #default,
+                    }),
+                )
+            }
+            WindowFunction::FirstValue(expression) => {
+                let value = expression.generate(&ctx);
+                (
+                    quote!(),
+                    quote!({
+                        let arg = &rows[0];
+                        #value
+                    }),
+                )
+            }
+            WindowFunction::LastValue(expression) => {
+                // the frame ends at the last peer of the current row
+                let value = expression.generate(&ctx);
+                (
+                    quote! {
+                        let mut end = index;
+                        while end + 1 < rows.len() && keys[end + 1] == keys[index] {
+                            end += 1;
+                        }
+                    },
+                    quote!({
+                        let arg = &rows[end];
+                        #value
+                    }),
+                )
+            }
+        };
+        (setup, step, value)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FusedRecordTransform {
     pub expressions: Vec<RecordTransform>,
@@ -281,12 +441,11 @@ pub struct PlanNode {
 }
 
 impl PlanNode {
-    fn into_stream_node(&self, index: usize, sql_config: &SqlConfig) -> StreamNode {
-        let name = format!("{}_{}", self.prefix(), index);
+    fn into_stream_node(&self, operator_id: String, parallelism: usize) -> StreamNode {
         let operator = self.to_operator();
         StreamNode {
-            operator_id: name,
-            parallelism: sql_config.default_parallelism,
+            operator_id,
+            parallelism,
             operator,
         }
     }
@@ -322,13 +481,24 @@ impl PlanNode {
             PlanOperator::TumblingWindowTwoPhaseAggregator { .. } => {
                 "tumbling_window_two_phase_aggregator".to_string()
             }
+            PlanOperator::SessionWindowTwoPhaseAggregator { .. } => {
+                "session_window_two_phase_aggregator".to_string()
+            }
+            PlanOperator::CumulatingWindowTwoPhaseAggregator { .. } => {
+                "cumulating_window_two_phase_aggregator".to_string()
+            }
             PlanOperator::SlidingWindowTwoPhaseAggregator { .. } => {
                 "sliding_window_two_phase_aggregator".to_string()
             }
             PlanOperator::InstantJoin => "instant_join".to_string(),
             PlanOperator::JoinWithExpiration { .. } => "join_with_expiration".to_string(),
+            PlanOperator::IntervalJoin(_) => "interval_join".to_string(),
+            PlanOperator::TemporalJoin(_) => "temporal_join".to_string(),
             PlanOperator::JoinListMerge(_, _) => "join_list_merge".to_string(),
             PlanOperator::JoinPairMerge(_, _, _) => "join_pair_merge".to_string(),
+            PlanOperator::AppendingJoinMerge(_, _) => "appending_join_merge".to_string(),
+            PlanOperator::LookupJoin { .. } => "lookup_join".to_string(),
+            PlanOperator::MatchRecognize(_) => "match_recognize".to_string(),
             PlanOperator::Flatten => "flatten".to_string(),
             PlanOperator::WindowFunction { .. } => "window_function".to_string(),
             PlanOperator::StreamOperator(name, _) => name.to_string(),
@@ -390,6 +560,76 @@ impl PlanNode {
                     bin_type,
                 })
             }
+            PlanOperator::SessionWindowTwoPhaseAggregator {
+                gap,
+                max_length,
+                projection,
+            } => {
+                let value_bin_merging_context = ValueBinMergingContext::new();
+                let bin_type = value_bin_merging_context
+                    .bin_syn_type(projection)
+                    .into_token_stream()
+                    .to_string();
+                let bin_merger = value_bin_merging_context
+                    .compile_closure(projection)
+                    .into_token_stream()
+                    .to_string();
+                // sessions are merged when a row bridges them
+                let bin_combiner = CombiningContext::new()
+                    .compile_closure(projection)
+                    .into_token_stream()
+                    .to_string();
+
+                let aggregator = BinAggregatingContext::new()
+                    .compile_closure(projection)
+                    .into_token_stream()
+                    .to_string();
+
+                arroyo_datastream::Operator::SessionWindowAggregator(SessionWindowAggregator {
+                    gap: *gap,
+                    max_length: *max_length,
+                    aggregator,
+                    bin_merger,
+                    bin_combiner,
+                    bin_type,
+                })
+            }
+            PlanOperator::CumulatingWindowTwoPhaseAggregator {
+                step,
+                max_size,
+                projection,
+            } => {
+                let value_bin_merging_context = ValueBinMergingContext::new();
+                let bin_type = value_bin_merging_context
+                    .bin_syn_type(projection)
+                    .into_token_stream()
+                    .to_string();
+                let bin_merger = value_bin_merging_context
+                    .compile_closure(projection)
+                    .into_token_stream()
+                    .to_string();
+                // each bin is combined into the running aggregate of its window
+                let bin_combiner = CombiningContext::new()
+                    .compile_closure(projection)
+                    .into_token_stream()
+                    .to_string();
+
+                let aggregator = BinAggregatingContext::new()
+                    .compile_closure(projection)
+                    .into_token_stream()
+                    .to_string();
+
+                arroyo_datastream::Operator::CumulatingWindowAggregator(
+                    CumulatingWindowAggregator {
+                        step: *step,
+                        max_size: *max_size,
+                        aggregator,
+                        bin_merger,
+                        bin_combiner,
+                        bin_type,
+                    },
+                )
+            }
             PlanOperator::SlidingWindowTwoPhaseAggregator {
                 width,
                 slide,
@@ -450,6 +690,13 @@ impl PlanNode {
                 right_expiration: *right_expiration,
                 join_type: join_type.clone().into(),
             },
+            PlanOperator::IntervalJoin(interval) => Operator::IntervalJoin(IntervalJoin {
+                lower_bound_micros: interval.lower_bound_micros,
+                upper_bound_micros: interval.upper_bound_micros,
+            }),
+            PlanOperator::TemporalJoin(join_type) => Operator::TemporalJoin {
+                join_type: join_type.clone().into(),
+            },
             PlanOperator::JoinListMerge(join_type, struct_pair) => {
                 let context =
                     JoinListsContext::new(struct_pair.left.clone(), struct_pair.right.clone());
@@ -472,14 +719,46 @@ impl PlanNode {
                     MethodCompiler::record_expression_operator("join_merge", record_expression)
                 }
             }
-
-            PlanOperator::WindowFunction(WindowFunctionOperator {
-                window_function,
-                order_by,
-                window_type,
-                result_struct,
-                field_name: _,
-            }) => {
+            PlanOperator::AppendingJoinMerge(join_type, struct_pair) => {
+                let context =
+                    JoinPairContext::new(struct_pair.left.clone(), struct_pair.right.clone());
+                let record_expression = context.compile_pair_merge_record_expression(join_type);
+                MethodCompiler::record_expression_operator("join_merge", record_expression)
+            }
+            PlanOperator::LookupJoin {
+                join_type,
+                left_struct,
+                lookup,
+                right_projections,
+            } => {
+                let right_struct = right_projections
+                    .last()
+                    .map(|p| p.output_struct())
+                    .unwrap_or_else(|| lookup.struct_def.clone());
+                let merge = JoinPairContext::new(left_struct.clone(), right_struct)
+                    .compile_lookup_merge_closure(join_type, &lookup.struct_def, right_projections);
+
+                Operator::LookupJoin(LookupJoin {
+                    connector: lookup.connector.clone(),
+                    join_type: join_type.clone().into(),
+                    lookup_type: lookup.struct_def.get_type().into_token_stream().to_string(),
+                    merge: merge.into_token_stream().to_string(),
+                    cache_ttl: lookup.options.cache_ttl,
+                    max_cache_entries: lookup.options.max_cache_entries,
+                    max_batch_size: lookup.options.max_batch_size,
+                    max_batch_wait: lookup.options.max_batch_wait,
+                })
+            }
+            PlanOperator::MatchRecognize(match_recognize) => match_recognize.to_operator(),
+
+            PlanOperator::WindowFunction(window_function_operator) => {
+                let WindowFunctionOperator {
+                    window_function: _,
+                    order_by,
+                    window_type,
+                    result_struct,
+                    field_name: _,
+                } = window_function_operator;
                 let window_field = result_struct.fields.last().unwrap().field_ident();
                 let result_struct_name = result_struct.get_type();
                 let mut field_assignments: Vec<_> = result_struct
@@ -492,13 +771,10 @@ impl PlanNode {
                     })
                     .collect();
 
-                match window_function {
-                    WindowFunction::RowNumber => {
-                        field_assignments.push(quote! {
-                            #window_field: i as u64
-                        });
-                    }
-                }
+                let (setup, step, value) = window_function_operator.window_value();
+                field_assignments.push(quote! {
+                    #window_field: #value
+                });
 
                 let output_expression = quote!(#result_struct_name {
                     #(#field_assignments, )*
@@ -517,9 +793,12 @@ impl PlanNode {
                         expression: quote! {
                             {
                                 #sort
+                                let rows = &arg;
+                                #setup
                                 let mut result = vec![];
-                                for (index, arg) in arg.iter().enumerate() {
+                                for (index, arg) in rows.iter().enumerate() {
                                     let i = index + 1;
+                                    #step
                                     result.push(#output_expression);
                                 }
                                 result
@@ -679,13 +958,10 @@ impl PlanNode {
                     })
                     .collect();
 
-                match window_function.window_function {
-                    WindowFunction::RowNumber => {
-                        field_assignments.push(quote! {
-                            #window_field: i as u64
-                        });
-                    }
-                }
+                // top-n is only planned for ROW_NUMBER
+                field_assignments.push(quote! {
+                    #window_field: i as u64
+                });
                 let output_expression = quote!(#output_struct {
                     #(#field_assignments, )*
                 });
@@ -847,6 +1123,7 @@ impl PlanNode {
         // TODO: populate types only created within operators.
         match &self.operator {
             PlanOperator::JoinPairMerge(join_type, StructPair { left, right }, ..)
+            | PlanOperator::AppendingJoinMerge(join_type, StructPair { left, right })
             | PlanOperator::JoinListMerge(join_type, StructPair { left, right }) => {
                 output_types.insert(join_type.join_struct_type(left, right));
             }
@@ -855,6 +1132,16 @@ impl PlanNode {
                     output_types.extend(t.get_all_types());
                 });
             }
+            PlanOperator::LookupJoin {
+                lookup,
+                right_projections,
+                ..
+            } => {
+                output_types.extend(lookup.struct_def.all_structs());
+                right_projections.iter().for_each(|p| {
+                    output_types.extend(p.output_struct().all_structs());
+                });
+            }
             PlanOperator::SlidingAggregatingTopN {
                 width: _,
                 slide: _,
@@ -1160,6 +1447,8 @@ pub struct PlanGraph {
     pub named_tables: HashMap<String, NodeIndex>,
     pub sql_config: SqlConfig,
     pub saved_connections_used: Vec<i64>,
+    // parallelism of the operators configured with `SET parallelism.<operator>`
+    pub parallelism: HashMap<NodeIndex, usize>,
 }
 
 impl PlanGraph {
@@ -1172,9 +1461,90 @@ impl PlanGraph {
             named_tables: HashMap::new(),
             sql_config,
             saved_connections_used: vec![],
+            parallelism: HashMap::new(),
         }
     }
 
+    /// Assigns the parallelism set for operators by name. Operators connected by forward edges
+    /// must run with the same parallelism, so the setting applies to all operators chained with
+    /// the named one.
+    fn assign_parallelism(&mut self) -> Result<()> {
+        let mut chains = UnionFind::new(self.graph.node_count());
+        for edge in self.graph.edge_references() {
+            if matches!(edge.weight().edge_type, EdgeType::Forward) {
+                chains.union(edge.source().index(), edge.target().index());
+            }
+        }
+
+        let mut chain_parallelism: HashMap<usize, (&String, usize)> = HashMap::new();
+        for (operator, parallelism) in &self.sql_config.operator_parallelism {
+            let nodes: Vec<_> = self
+                .graph
+                .node_indices()
+                .filter(|idx| self.graph[*idx].prefix() == *operator)
+                .collect();
+            if nodes.is_empty() {
+                bail!(
+                    "SET parallelism.{} does not match any operator in the pipeline",
+                    operator
+                );
+            }
+
+            for node in nodes {
+                let chain = chains.find(node.index());
+                if let Some((other, other_parallelism)) = chain_parallelism.get(&chain) {
+                    if other_parallelism != parallelism {
+                        bail!(
+                            "operators {} and {} are chained together, so they must have the same parallelism",
+                            other,
+                            operator
+                        );
+                    }
+                }
+                chain_parallelism.insert(chain, (operator, *parallelism));
+            }
+        }
+
+        let parallelism = self
+            .graph
+            .node_indices()
+            .filter_map(|idx| {
+                let (_, parallelism) = chain_parallelism.get(&chains.find(idx.index()))?;
+                Some((idx, *parallelism))
+            })
+            .collect();
+        self.parallelism = parallelism;
+        Ok(())
+    }
+
+    /// Derives the operator id of each node from its kind and the ids of its inputs, rather than
+    /// from its position in the graph, so that an operator keeps its id (and with it, its state)
+    /// when other parts of the query change. The ids are sha256 digests, which unlike the std
+    /// hashers are stable across builds.
+    fn stable_operator_ids(&self) -> HashMap<NodeIndex, String> {
+        let mut ids: HashMap<NodeIndex, String> = HashMap::new();
+        let mut occurrences: HashMap<String, usize> = HashMap::new();
+        for index in toposort(&self.graph, None).expect("plan graph must be acyclic") {
+            let prefix = self.graph[index].prefix();
+            let mut inputs: Vec<_> = self
+                .graph
+                .edges_directed(index, Direction::Incoming)
+                .map(|edge| format!("{} {:?}", ids[&edge.source()], edge.weight().edge_type))
+                .collect();
+            inputs.sort();
+            let description = format!("{}\n{}", prefix, inputs.join("\n"));
+
+            // identical operators reading from the same inputs are told apart by their order
+            let occurrence = occurrences.entry(description.clone()).or_default();
+            let digest = Sha256::digest(format!("{}\n{}", description, occurrence));
+            *occurrence += 1;
+
+            let hash: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+            ids.insert(index, format!("{}_{}", prefix, hash));
+        }
+        ids
+    }
+
     pub fn find_used_udfs(&mut self, used_udfs: &mut HashSet<String>) {
         let accumulate_udfs = |ctx: &mut HashSet<String>, e: &mut Expression| match e {
             Expression::RustUdf(r) => {
@@ -1207,8 +1577,8 @@ impl PlanGraph {
                             ..
                         } => {
                             computation
-                                .producing_expression
-                                .traverse_mut(used_udfs, &accumulate_udfs);
+                                .expressions()
+                                .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs));
                         }
                         AggregateComputation::UDAF {
                             ref mut computation,
@@ -1219,27 +1589,69 @@ impl PlanGraph {
                                 .expressions()
                                 .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs));
                         }
+                        AggregateComputation::Accumulator {
+                            ref mut computation,
+                            ..
+                        } => {
+                            used_udfs.extend(computation.name());
+                            computation
+                                .expressions()
+                                .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs));
+                        }
                     });
                 }
                 PlanOperator::NonWindowAggregate {
                     ref mut projection, ..
-                } => projection
-                    .expressions()
-                    .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs)),
+                } => {
+                    used_udfs.extend(projection.udafs());
+                    projection
+                        .expressions()
+                        .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs));
+                }
                 PlanOperator::TumblingWindowTwoPhaseAggregator {
                     ref mut projection, ..
-                } => projection
-                    .expressions()
-                    .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs)),
+                } => {
+                    used_udfs.extend(projection.udafs());
+                    projection
+                        .expressions()
+                        .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs));
+                }
+                PlanOperator::SessionWindowTwoPhaseAggregator {
+                    ref mut projection, ..
+                }
+                | PlanOperator::CumulatingWindowTwoPhaseAggregator {
+                    ref mut projection, ..
+                } => {
+                    used_udfs.extend(projection.udafs());
+                    projection
+                        .expressions()
+                        .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs));
+                }
                 PlanOperator::SlidingWindowTwoPhaseAggregator {
                     ref mut projection, ..
-                } => projection
-                    .expressions()
-                    .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs)),
+                } => {
+                    used_udfs.extend(projection.udafs());
+                    projection
+                        .expressions()
+                        .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs));
+                }
                 PlanOperator::InstantJoin => {}
                 PlanOperator::JoinWithExpiration { .. } => {}
+                PlanOperator::IntervalJoin(_) => {}
+                PlanOperator::TemporalJoin(_) => {}
                 PlanOperator::JoinListMerge(_, _) => {}
                 PlanOperator::JoinPairMerge(_, _, _) => {}
+                PlanOperator::AppendingJoinMerge(_, _) => {}
+                PlanOperator::LookupJoin {
+                    ref mut right_projections,
+                    ..
+                } => right_projections.iter_mut().for_each(|p| {
+                    p.expressions()
+                        .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs))
+                }),
+                PlanOperator::MatchRecognize(ref mut match_recognize) => match_recognize
+                    .expressions()
+                    .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs)),
                 PlanOperator::Flatten => {}
                 PlanOperator::WindowFunction(w) => {
                     w.order_by
@@ -1249,9 +1661,12 @@ impl PlanGraph {
                 }
                 PlanOperator::TumblingLocalAggregator {
                     ref mut projection, ..
-                } => projection
-                    .expressions()
-                    .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs)),
+                } => {
+                    used_udfs.extend(projection.udafs());
+                    projection
+                        .expressions()
+                        .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs));
+                }
                 PlanOperator::SlidingAggregatingTopN {
                     ref mut aggregating_projection,
                     ref mut order_by,
@@ -1259,6 +1674,7 @@ impl PlanGraph {
                     ref mut converting_projection,
                     ..
                 } => {
+                    used_udfs.extend(aggregating_projection.udafs());
                     aggregating_projection
                         .expressions()
                         .for_each(|e| e.traverse_mut(used_udfs, &accumulate_udfs));
@@ -1289,6 +1705,12 @@ impl PlanGraph {
             SqlOperator::JoinOperator(left, right, join_operator) => {
                 self.add_join(left, right, join_operator)
             }
+            SqlOperator::LookupJoin(left, lookup_join_operator) => {
+                self.add_lookup_join(left, lookup_join_operator)
+            }
+            SqlOperator::MatchRecognize(input, match_recognize_operator) => {
+                self.add_match_recognize(input, match_recognize_operator)
+            }
             SqlOperator::Window(input, window_operator) => self.add_window(input, window_operator),
             SqlOperator::RecordTransform(input, transform) => {
                 self.add_record_transform(input, transform)
@@ -1404,12 +1826,12 @@ impl PlanGraph {
             }
         } else {
             arroyo_datastream::WatermarkStrategy::FixedLateness {
-                max_lateness: Duration::from_secs(1),
+                max_lateness: self.sql_config.watermark_max_lateness,
             }
         };
 
         let watermark_operator = PlanOperator::Watermark(arroyo_datastream::PeriodicWatermark {
-            period: Duration::from_secs(1),
+            period: self.sql_config.watermark_period,
             idle_time: source_operator.source.idle_time,
             strategy,
         });
@@ -1497,6 +1919,8 @@ impl PlanGraph {
         // right now left and right either both have or don't have windows.
         let has_window = left.has_window();
         let join_type = join_operator.join_type;
+        let interval = join_operator.interval;
+        let temporal = join_operator.temporal;
         let left_updating = left.is_updating();
         let right_updating = right.is_updating();
         let left_index = self.add_sql_operator(*left);
@@ -1535,7 +1959,25 @@ impl PlanGraph {
             .add_edge(left_index, left_key_index, left_key_edge);
         self.graph
             .add_edge(right_index, right_key_index, right_key_edge);
-        if has_window {
+        if let Some(interval) = interval {
+            self.add_interval_join(
+                left_key_index,
+                right_key_index,
+                key_struct,
+                left_type,
+                right_type,
+                interval,
+            )
+        } else if temporal {
+            self.add_temporal_join(
+                left_key_index,
+                right_key_index,
+                key_struct,
+                left_type,
+                right_type,
+                join_type,
+            )
+        } else if has_window {
             self.add_post_window_join(
                 left_key_index,
                 right_key_index,
@@ -1557,6 +1999,96 @@ impl PlanGraph {
         }
     }
 
+    fn add_lookup_join(
+        &mut self,
+        left: Box<SqlOperator>,
+        lookup_join_operator: crate::pipeline::LookupJoinOperator,
+    ) -> NodeIndex {
+        let left_type = left.return_type();
+        let right_type = lookup_join_operator.right_struct();
+        let join_type = lookup_join_operator.join_type;
+        let lookup = lookup_join_operator.lookup;
+        let left_index = self.add_sql_operator(*left);
+
+        if let Some(id) = lookup.id {
+            self.saved_connections_used.push(id);
+        }
+
+        let key_struct = lookup_join_operator.left_key.output_struct();
+        let key_operator = PlanOperator::RecordTransform(RecordTransform::KeyProjection(
+            lookup_join_operator.left_key,
+        ));
+        let key_index = self.insert_operator(
+            key_operator,
+            PlanType::Keyed {
+                key: key_struct,
+                value: left_type.clone(),
+            },
+        );
+
+        let key_edge = PlanEdge {
+            edge_type: EdgeType::Forward,
+        };
+        self.graph.add_edge(left_index, key_index, key_edge);
+
+        let merge_type = join_type.output_struct(&left_type, &right_type);
+        let lookup_name = lookup.name.clone();
+        let lookup_operator = PlanOperator::LookupJoin {
+            join_type,
+            left_struct: left_type,
+            lookup,
+            right_projections: lookup_join_operator.right_projections,
+        };
+        let lookup_index = self.insert_operator(lookup_operator, PlanType::Unkeyed(merge_type));
+        self.connections.insert(lookup_name, lookup_index);
+
+        // shuffle by key so that each subtask's cache sees a subset of the keys
+        let lookup_edge = PlanEdge {
+            edge_type: EdgeType::Shuffle,
+        };
+        self.graph.add_edge(key_index, lookup_index, lookup_edge);
+
+        lookup_index
+    }
+
+    fn add_match_recognize(
+        &mut self,
+        input: Box<SqlOperator>,
+        match_recognize_operator: MatchRecognizeOperator,
+    ) -> NodeIndex {
+        let input_type = input.return_type();
+        let input_index = self.add_sql_operator(*input);
+
+        let key_struct = match_recognize_operator.partition_key.output_struct();
+        let key_operator = PlanOperator::RecordTransform(RecordTransform::KeyProjection(
+            match_recognize_operator.partition_key.clone(),
+        ));
+        let key_index = self.insert_operator(
+            key_operator,
+            PlanType::Keyed {
+                key: key_struct,
+                value: input_type,
+            },
+        );
+        let key_edge = PlanEdge {
+            edge_type: EdgeType::Forward,
+        };
+        self.graph.add_edge(input_index, key_index, key_edge);
+
+        let output_type = match_recognize_operator.output_struct();
+        let match_index = self.insert_operator(
+            PlanOperator::MatchRecognize(match_recognize_operator),
+            PlanType::Unkeyed(output_type),
+        );
+        // each partition's rows must all reach the same subtask
+        let match_edge = PlanEdge {
+            edge_type: EdgeType::Shuffle,
+        };
+        self.graph.add_edge(key_index, match_index, match_edge);
+
+        match_index
+    }
+
     fn add_post_window_join(
         &mut self,
         left_index: NodeIndex,
@@ -1614,6 +2146,107 @@ impl PlanGraph {
         flatten_index
     }
 
+    fn add_interval_join(
+        &mut self,
+        left_index: NodeIndex,
+        right_index: NodeIndex,
+        key_struct: StructDef,
+        left_struct: StructDef,
+        right_struct: StructDef,
+        interval: JoinInterval,
+    ) -> NodeIndex {
+        let join_node = PlanOperator::IntervalJoin(interval);
+        let join_node_output_type = PlanType::KeyedPair {
+            key: key_struct,
+            left_value: left_struct.clone(),
+            right_value: right_struct.clone(),
+            join_type: JoinType::Inner,
+        };
+        let join_node_index = self.insert_operator(join_node, join_node_output_type);
+
+        let left_join_edge = PlanEdge {
+            edge_type: EdgeType::ShuffleJoin(0),
+        };
+        let right_join_edge = PlanEdge {
+            edge_type: EdgeType::ShuffleJoin(1),
+        };
+        self.graph
+            .add_edge(left_index, join_node_index, left_join_edge);
+        self.graph
+            .add_edge(right_index, join_node_index, right_join_edge);
+
+        // interval joins only emit appends, so the merge is the same as for a non-updating inner join
+        let merge_type = JoinType::Inner.output_struct(&left_struct, &right_struct);
+        let merge_operator = PlanOperator::JoinPairMerge(
+            JoinType::Inner,
+            StructPair {
+                left: left_struct,
+                right: right_struct,
+            },
+            InputsUpdating {
+                left: false,
+                right: false,
+            },
+        );
+        let merge_index = self.insert_operator(merge_operator, PlanType::Unkeyed(merge_type));
+
+        let merge_edge = PlanEdge {
+            edge_type: EdgeType::Forward,
+        };
+
+        self.graph
+            .add_edge(join_node_index, merge_index, merge_edge);
+        merge_index
+    }
+
+    fn add_temporal_join(
+        &mut self,
+        left_index: NodeIndex,
+        right_index: NodeIndex,
+        key_struct: StructDef,
+        left_struct: StructDef,
+        right_struct: StructDef,
+        join_type: JoinType,
+    ) -> NodeIndex {
+        let join_node = PlanOperator::TemporalJoin(join_type.clone());
+        let join_node_output_type = PlanType::KeyedPair {
+            key: key_struct,
+            left_value: left_struct.clone(),
+            right_value: right_struct.clone(),
+            join_type: join_type.clone(),
+        };
+        let join_node_index = self.insert_operator(join_node, join_node_output_type);
+
+        let left_join_edge = PlanEdge {
+            edge_type: EdgeType::ShuffleJoin(0),
+        };
+        let right_join_edge = PlanEdge {
+            edge_type: EdgeType::ShuffleJoin(1),
+        };
+        self.graph
+            .add_edge(left_index, join_node_index, left_join_edge);
+        self.graph
+            .add_edge(right_index, join_node_index, right_join_edge);
+
+        let merge_type = join_type.output_struct(&left_struct, &right_struct);
+        let merge_operator = PlanOperator::AppendingJoinMerge(
+            join_type,
+            StructPair {
+                left: left_struct,
+                right: right_struct,
+            },
+        );
+        let merge_index = self.insert_operator(merge_operator, PlanType::Unkeyed(merge_type));
+
+        let merge_edge = PlanEdge {
+            edge_type: EdgeType::Forward,
+        };
+
+        self.graph
+            .add_edge(join_node_index, merge_index, merge_edge);
+        merge_index
+    }
+
     fn add_join_with_expiration(
         &mut self,
         left_index: NodeIndex,
@@ -1624,9 +2257,10 @@ impl PlanGraph {
         join_type: JoinType,
         inputs_updating: InputsUpdating,
     ) -> NodeIndex {
+        let (left_expiration, right_expiration) = self.sql_config.join_expiration();
         let join_node = PlanOperator::JoinWithExpiration {
-            left_expiration: Duration::from_secs(24 * 60 * 60),
-            right_expiration: Duration::from_secs(24 * 60 * 60),
+            left_expiration,
+            right_expiration,
             join_type: join_type.clone(),
         };
         let join_node_output_type = PlanType::KeyedPair {
@@ -1694,7 +2328,7 @@ impl PlanGraph {
         result_type.fields.push(StructField::new(
             window_operator.field_name.clone(),
             None,
-            TypeDef::DataType(DataType::UInt64, false),
+            window_operator.window_fn.result_type(),
         ));
         let partition_struct = window_operator.partition.output_struct();
 
@@ -1860,7 +2494,7 @@ impl PlanGraph {
         let aggregate_struct = aggregate_projection.expression_type(&VecAggregationContext::new());
         let aggregate_operator = PlanOperator::NonWindowAggregate {
             input_is_update: input_updating,
-            expiration: Duration::from_secs(60 * 60 * 24),
+            expiration: self.sql_config.state_ttl,
             projection: aggregate_projection.clone().try_into().unwrap(),
         };
 
@@ -1960,8 +2594,16 @@ impl PlanGraph {
 
 impl From<PlanGraph> for DiGraph<StreamNode, StreamEdge> {
     fn from(val: PlanGraph) -> Self {
+        let mut operator_ids = val.stable_operator_ids();
         val.graph.map(
-            |index: NodeIndex, node| node.into_stream_node(index.index(), &val.sql_config),
+            |index: NodeIndex, node| {
+                let parallelism = val
+                    .parallelism
+                    .get(&index)
+                    .copied()
+                    .unwrap_or(val.sql_config.default_parallelism);
+                node.into_stream_node(operator_ids.remove(&index).unwrap(), parallelism)
+            },
             |index, edge| {
                 let source_index = val.graph.edge_endpoints(index).unwrap().0;
                 let source_node = val.graph.node_weight(source_index).unwrap();
@@ -1978,6 +2620,7 @@ pub fn get_program(
     schema_provider: ArroyoSchemaProvider,
 ) -> Result<CompiledSql> {
     optimize(&mut plan_graph.graph);
+    plan_graph.assign_parallelism()?;
 
     let mut key_structs = HashSet::new();
     let connection_ids = plan_graph.saved_connections_used.clone();
@@ -1989,7 +2632,17 @@ pub fn get_program(
     let mut used_udfs = HashSet::new();
     plan_graph.find_used_udfs(&mut used_udfs);
 
-    // find all types that are produced by a source or consumed by a sink
+    // find all types that are produced by a source or consumed by a sink, as well as the
+    // values read from lookup tables
+    let lookup_types: Vec<_> = plan_graph
+        .graph
+        .node_weights()
+        .filter_map(|node| match &node.operator {
+            PlanOperator::LookupJoin { lookup, .. } => Some(lookup.struct_def.clone()),
+            _ => None,
+        })
+        .collect();
+
     let connector_types: HashSet<_> = plan_graph
         .graph
         .externals(Direction::Incoming)
@@ -2004,6 +2657,7 @@ pub fn get_program(
                 }),
         )
         .flat_map(|node| plan_graph.graph.node_weight(node).unwrap().get_all_types())
+        .chain(lookup_types)
         .flat_map(|s| s.all_structs_including_named())
         .map(|t| t.struct_name())
         .collect();
@@ -2024,12 +2678,12 @@ pub fn get_program(
         .flat_map(|s| s.all_structs_including_named())
         .collect();
 
-    other_defs.extend(
-        all_types
-            .iter()
-            .filter(|t| connector_types.contains(&t.struct_name()))
-            .map(|s| s.generate_serializer_items().to_string()),
-    );
+    for s in all_types
+        .iter()
+        .filter(|t| connector_types.contains(&t.struct_name()))
+    {
+        other_defs.push(s.generate_serializer_items()?.to_string());
+    }
 
     other_defs.extend(
         schema_provider
@@ -0,0 +1,184 @@
+use crate::types::{StructDef, StructField, TypeDef};
+use anyhow::bail;
+use arrow_schema::{DataType, TimeUnit};
+use arroyo_formats::protobuf::{compile_schema, root_message, TIMESTAMP_MESSAGE};
+use proc_macro2::Ident;
+use prost_reflect::{FieldDescriptor, Kind, MessageDescriptor};
+use quote::quote;
+
+pub const ROOT_NAME: &str = "ArroyoProtoRoot";
+
+pub fn convert_proto_schema(
+    schema: &str,
+    message_name: Option<&str>,
+) -> anyhow::Result<Vec<StructField>> {
+    let pool = compile_schema(schema)?;
+    let message = root_message(&pool, message_name)?;
+
+    match to_typedef(&message, &mut vec![]) {
+        TypeDef::StructDef(sd, _) => Ok(sd.fields),
+        TypeDef::DataType(_, _) => {
+            bail!("top-level message must be a struct")
+        }
+    }
+}
+
+pub fn get_defs(name: &str, schema: &str, message_name: Option<&str>) -> anyhow::Result<String> {
+    let fields = convert_proto_schema(schema, message_name)?;
+
+    let sd = StructDef::new(Some(ROOT_NAME.to_string()), true, fields, None);
+    let defs = sd
+        .all_structs_including_named()
+        .iter()
+        .map(|p| {
+            Ok(vec![
+                syn::parse_str(&p.def(false)).unwrap(),
+                p.generate_serializer_items()?,
+            ])
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    let mod_ident: Ident = syn::parse_str(name).unwrap();
+    Ok(quote! {
+        mod #mod_ident {
+            use super::*;
+            #(#defs)
+            *
+        }
+    }
+    .to_string())
+}
+
+fn to_typedef(message: &MessageDescriptor, parents: &mut Vec<String>) -> TypeDef {
+    parents.push(message.full_name().to_string());
+    let fields = message
+        .fields()
+        .map(|f| {
+            let (ft, original) = field_typedef(&f, parents);
+            StructField::with_rename(f.name().to_string(), None, ft, None, original)
+        })
+        .collect();
+    parents.pop();
+
+    TypeDef::StructDef(
+        StructDef::for_name(Some(message.name().to_string()), fields),
+        false,
+    )
+}
+
+fn field_typedef(field: &FieldDescriptor, parents: &mut Vec<String>) -> (TypeDef, Option<String>) {
+    if field.is_list() || field.is_map() {
+        // repeated and map fields are exposed as json, which can be accessed with the json functions
+        return (
+            TypeDef::DataType(DataType::Utf8, false),
+            Some("json".to_string()),
+        );
+    }
+
+    let nullable = field.supports_presence();
+
+    let dt = match field.kind() {
+        Kind::Double => DataType::Float64,
+        Kind::Float => DataType::Float32,
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => DataType::Int32,
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => DataType::Int64,
+        Kind::Uint32 | Kind::Fixed32 => DataType::UInt32,
+        Kind::Uint64 | Kind::Fixed64 => DataType::UInt64,
+        Kind::Bool => DataType::Boolean,
+        Kind::String | Kind::Enum(_) => DataType::Utf8,
+        Kind::Bytes => DataType::Binary,
+        Kind::Message(m) if m.full_name() == TIMESTAMP_MESSAGE => {
+            DataType::Timestamp(TimeUnit::Microsecond, None)
+        }
+        Kind::Message(m) => {
+            if parents.iter().any(|p| p == m.full_name()) {
+                // recursive messages can't be represented as structs
+                return (
+                    TypeDef::DataType(DataType::Utf8, true),
+                    Some("json".to_string()),
+                );
+            }
+
+            let typedef = to_typedef(&m, parents);
+            return (
+                if nullable {
+                    typedef.to_optional()
+                } else {
+                    typedef
+                },
+                None,
+            );
+        }
+    };
+
+    (TypeDef::DataType(dt, nullable), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::convert_proto_schema;
+    use crate::types::TypeDef;
+    use arrow_schema::DataType;
+
+    #[test]
+    fn test_convert_proto_schema() {
+        let schema = r#"
+            syntax = "proto3";
+
+            import "google/protobuf/timestamp.proto";
+
+            message Event {
+                message Location {
+                    double lat = 1;
+                    double lon = 2;
+                }
+
+                string id = 1;
+                optional uint64 count = 2;
+                Location location = 3;
+                repeated string tags = 4;
+                map<string, int32> attributes = 5;
+                google.protobuf.Timestamp time = 6;
+                Event parent = 7;
+            }
+        "#;
+
+        let fields = convert_proto_schema(schema, None).unwrap();
+        let types: Vec<_> = fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.original_type.as_deref()))
+            .collect();
+
+        assert_eq!(
+            types,
+            vec![
+                ("id", None),
+                ("count", None),
+                ("location", None),
+                ("tags", Some("json")),
+                ("attributes", Some("json")),
+                ("time", None),
+                ("parent", Some("json")),
+            ]
+        );
+
+        assert_eq!(
+            fields[0].data_type,
+            TypeDef::DataType(DataType::Utf8, false)
+        );
+        assert_eq!(
+            fields[1].data_type,
+            TypeDef::DataType(DataType::UInt64, true)
+        );
+
+        let TypeDef::StructDef(location, true) = &fields[2].data_type else {
+            panic!("expected a nullable struct for location");
+        };
+        assert_eq!(location.fields.len(), 2);
+
+        assert!(convert_proto_schema(schema, Some("Missing")).is_err());
+    }
+}
@@ -9,26 +9,34 @@ use arroyo_rpc::api_types::connections::{
     ConnectionProfile, ConnectionSchema, ConnectionType, SchemaDefinition, SourceField,
 };
 use arroyo_rpc::formats::{Format, Framing};
+use arroyo_rpc::OperatorConfig;
 use datafusion::sql::sqlparser::ast::Query;
 use datafusion::{
     optimizer::{analyzer::Analyzer, optimizer::Optimizer, OptimizerContext},
     sql::{
         planner::{PlannerContext, SqlToRel},
-        sqlparser::ast::{ColumnDef, ColumnOption, Statement, Value},
+        sqlparser::ast::{
+            ColumnDef, ColumnOption, Expr as SqlExpr, FunctionArg, FunctionArgExpr, Statement,
+            TableConstraint, Value,
+        },
     },
 };
+use datafusion_common::tree_node::{Transformed, TreeNode};
 use datafusion_common::{config::ConfigOptions, DFField, DFSchema};
+use datafusion_expr::expr::AggregateFunction;
 use datafusion_expr::{
-    CreateMemoryTable, CreateView, DdlStatement, DmlStatement, LogicalPlan, WriteOp,
+    Aggregate, CreateMemoryTable, CreateView, DdlStatement, DmlStatement, Expr, LogicalPlan,
+    WriteOp,
 };
 
 use crate::code_gen::{CodeGenerator, ValuePointerContext};
 use crate::expressions::CastExpression;
 use crate::external::SinkUpdateType;
-use crate::{avro, DEFAULT_IDLE_TIME};
+use crate::match_recognize::MatchRecognizeTable;
+use crate::{avro, protobuf, DEFAULT_IDLE_TIME};
 use crate::{
     expressions::{Column, ColumnExpression, Expression, ExpressionContext},
-    external::{ProcessingMode, SqlSink, SqlSource},
+    external::{LookupOptions, ProcessingMode, SqlLookup, SqlSink, SqlSource},
     json_schema,
     operators::Projection,
     pipeline::{SourceOperator, SqlOperator, SqlPipelineBuilder},
@@ -50,6 +58,8 @@ pub struct ConnectorTable {
     pub event_time_field: Option<String>,
     pub watermark_field: Option<String>,
     pub idle_time: Option<Duration>,
+    pub lookup_options: LookupOptions,
+    pub primary_keys: Vec<String>,
 
     pub inferred_fields: Option<Vec<DFField>>,
 }
@@ -61,19 +71,28 @@ pub enum FieldSpec {
         field: StructField,
         expression: Expression,
     },
+    // a field populated from the metadata of a message, like its Kafka key, rather than its payload
+    MetadataField {
+        field: StructField,
+        key: String,
+    },
 }
 
 impl FieldSpec {
     fn is_virtual(&self) -> bool {
         match self {
-            FieldSpec::StructField(_) => false,
+            FieldSpec::StructField(_) | FieldSpec::MetadataField { .. } => false,
             FieldSpec::VirtualField { .. } => true,
         }
     }
+    fn is_metadata(&self) -> bool {
+        matches!(self, FieldSpec::MetadataField { .. })
+    }
     fn struct_field(&self) -> &StructField {
         match self {
             FieldSpec::StructField(f) => f,
             FieldSpec::VirtualField { field, .. } => field,
+            FieldSpec::MetadataField { field, .. } => field,
         }
     }
 }
@@ -91,7 +110,9 @@ fn schema_type(name: &str, schema: &ConnectionSchema) -> Option<String> {
             SchemaDefinition::JsonSchema(_) => {
                 Some(format!("{}::{}", name, json_schema::ROOT_NAME))
             }
-            SchemaDefinition::ProtobufSchema(_) => todo!(),
+            SchemaDefinition::ProtobufSchema(_) => {
+                Some(format!("{}::{}", name, protobuf::ROOT_NAME))
+            }
             SchemaDefinition::AvroSchema(_) => Some(format!("{}::{}", name, avro::ROOT_NAME)),
             SchemaDefinition::RawSchema(_) => Some("arroyo_types::RawJson".to_string()),
         }
@@ -103,13 +124,46 @@ pub fn schema_defs(name: &str, schema: &ConnectionSchema) -> Option<String> {
 
     match def {
         SchemaDefinition::JsonSchema(s) => Some(json_schema::get_defs(&name, &s).unwrap()),
-        SchemaDefinition::ProtobufSchema(_) => todo!(),
+        SchemaDefinition::ProtobufSchema(s) => {
+            let message_name = match &schema.format {
+                Some(Format::Protobuf(proto)) => proto.message_name.as_deref(),
+                _ => None,
+            };
+            Some(protobuf::get_defs(&name, &s, message_name).unwrap())
+        }
         SchemaDefinition::AvroSchema(s) => Some(avro::get_defs(&name, &s).unwrap()),
         SchemaDefinition::RawSchema(_) => None,
     }
 }
 
-fn produce_optimized_plan(
+fn lookup_options(options: &mut HashMap<String, String>) -> Result<LookupOptions> {
+    fn pull_usize(name: &str, options: &mut HashMap<String, String>) -> Result<Option<usize>> {
+        options
+            .remove(name)
+            .map(|v| {
+                usize::from_str(&v)
+                    .ok()
+                    .filter(|v| *v > 0)
+                    .ok_or_else(|| anyhow!("{} must be a positive integer", name))
+            })
+            .transpose()
+    }
+
+    let defaults = LookupOptions::default();
+    Ok(LookupOptions {
+        cache_ttl: pull_usize("lookup.cache.ttl_secs", options)?
+            .map(|t| Duration::from_secs(t as u64)),
+        max_cache_entries: pull_usize("lookup.cache.max_entries", options)?
+            .unwrap_or(defaults.max_cache_entries),
+        max_batch_size: pull_usize("lookup.max_batch_size", options)?
+            .unwrap_or(defaults.max_batch_size),
+        max_batch_wait: pull_usize("lookup.max_batch_wait_ms", options)?
+            .map(|t| Duration::from_millis(t as u64))
+            .unwrap_or(defaults.max_batch_wait),
+    })
+}
+
+pub(crate) fn produce_optimized_plan(
     statement: &Statement,
     schema_provider: &ArroyoSchemaProvider,
 ) -> Result<LogicalPlan> {
@@ -123,10 +177,55 @@ fn produce_optimized_plan(
     let optimizer = Optimizer::new();
     let analyzed_plan =
         analyzer.execute_and_check(&plan, &ConfigOptions::default(), |_plan, _rule| {})?;
+
+    let analyzed_plan = alias_filtered_aggregates(analyzed_plan)?;
+
     let plan = optimizer.optimize(&analyzed_plan, &optimizer_config, |_plan, _rule| {})?;
     Ok(plan)
 }
 
+// Common subexpression elimination rewrites the arguments and FILTER clauses of aggregates,
+// which changes their display names and breaks the projections that reference them. Aliasing
+// each filtered aggregate to its original name keeps the aggregate's output schema stable.
+fn alias_filtered_aggregates(plan: LogicalPlan) -> Result<LogicalPlan> {
+    Ok(plan.transform_up(&|plan| {
+        let LogicalPlan::Aggregate(aggregate) = &plan else {
+            return Ok(Transformed::No(plan));
+        };
+        if !aggregate.aggr_expr.iter().any(|expr| {
+            matches!(
+                expr,
+                Expr::AggregateFunction(AggregateFunction {
+                    filter: Some(_),
+                    ..
+                })
+            )
+        }) {
+            return Ok(Transformed::No(plan));
+        }
+
+        let aggr_expr = aggregate
+            .aggr_expr
+            .iter()
+            .map(|expr| match expr {
+                Expr::AggregateFunction(AggregateFunction {
+                    filter: Some(_), ..
+                }) => Ok(expr.clone().alias(expr.display_name()?)),
+                expr => Ok(expr.clone()),
+            })
+            .collect::<datafusion_common::Result<Vec<_>>>()?;
+
+        Ok(Transformed::Yes(LogicalPlan::Aggregate(
+            Aggregate::try_new_with_schema(
+                aggregate.input.clone(),
+                aggregate.group_expr.clone(),
+                aggr_expr,
+                aggregate.schema.clone(),
+            )?,
+        )))
+    })?)
+}
+
 impl From<Connection> for ConnectorTable {
     fn from(value: Connection) -> Self {
         ConnectorTable {
@@ -139,7 +238,13 @@ impl From<Connection> for ConnectorTable {
                 .iter()
                 .map(|f| {
                     let struct_field: StructField = f.clone().into();
-                    struct_field.into()
+                    match &f.metadata_key {
+                        Some(key) => FieldSpec::MetadataField {
+                            key: key.clone(),
+                            field: struct_field,
+                        },
+                        None => struct_field.into(),
+                    }
                 })
                 .collect(),
             type_name: schema_type(&value.name, &value.schema),
@@ -150,6 +255,8 @@ impl From<Connection> for ConnectorTable {
             event_time_field: None,
             watermark_field: None,
             idle_time: DEFAULT_IDLE_TIME,
+            lookup_options: LookupOptions::default(),
+            primary_keys: value.schema.primary_keys.clone(),
             inferred_fields: None,
         }
     }
@@ -160,6 +267,7 @@ impl ConnectorTable {
         name: &str,
         connector: &str,
         mut fields: Vec<FieldSpec>,
+        primary_keys: Vec<String>,
         options: &mut HashMap<String, String>,
         connection_profile: Option<&ConnectionProfile>,
     ) -> Result<Self> {
@@ -182,6 +290,7 @@ impl ConnectorTable {
                     FieldSpec::VirtualField { .. } => {
                         unreachable!("delta lake is only a sink, can't have virtual fields")
                     }
+                    FieldSpec::MetadataField { .. } => field_spec,
                 })
                 .collect();
         }
@@ -190,6 +299,24 @@ impl ConnectorTable {
 
         let format = Format::from_opts(options).map_err(|e| anyhow!("invalid format: '{e}'"))?;
 
+        if let Some(Format::Protobuf(proto)) = &format {
+            let Some(schema_def) = &proto.schema_def else {
+                bail!("protobuf tables require a schema, which can be set with the 'protobuf.schema' option");
+            };
+            let proto_fields =
+                protobuf::convert_proto_schema(schema_def, proto.message_name.as_deref())
+                    .map_err(|e| anyhow!("invalid protobuf schema: {}", e))?;
+
+            if fields.iter().any(|f| !f.is_virtual()) {
+                bail!("the columns of protobuf tables are defined by their schema; only virtual columns may be declared");
+            }
+            fields = proto_fields
+                .into_iter()
+                .map(FieldSpec::StructField)
+                .chain(fields)
+                .collect();
+        }
+
         let framing = Framing::from_opts(options).map_err(|e| anyhow!("invalid framing: '{e}'"))?;
 
         let schema_fields: Result<Vec<SourceField>> = fields
@@ -207,7 +334,7 @@ impl ConnectorTable {
             })
             .collect();
 
-        let schema = ConnectionSchema::try_new(
+        let mut schema = ConnectionSchema::try_new(
             format,
             framing,
             None,
@@ -215,10 +342,22 @@ impl ConnectorTable {
             None,
             Some(fields.is_empty()),
         )?;
+        schema.primary_keys = primary_keys;
 
         let connection =
             connector.from_options(name, options, Some(&schema), connection_profile)?;
 
+        let metadata_fields = fields.iter().filter(|f| f.is_metadata()).count();
+        if metadata_fields > 0 {
+            let config: OperatorConfig = serde_json::from_str(&connection.config)?;
+            if config.metadata_fields.len() != metadata_fields {
+                bail!(
+                    "connector {} does not support metadata fields",
+                    connector.name()
+                );
+            }
+        }
+
         let mut table: ConnectorTable = connection.into();
         if !fields.is_empty() {
             table.fields = fields;
@@ -236,6 +375,10 @@ impl ConnectorTable {
             .filter(|t| *t <= 0)
             .map(|t| Duration::from_micros(t as u64));
 
+        if matches!(table.connection_type, ConnectionType::Lookup) {
+            table.lookup_options = lookup_options(options)?;
+        }
+
         if !options.is_empty() {
             let keys: Vec<String> = options.keys().map(|s| format!("'{}'", s)).collect();
             bail!(
@@ -247,10 +390,60 @@ impl ConnectorTable {
         Ok(table)
     }
 
+    pub fn as_sql_lookup(&self) -> Result<SqlLookup> {
+        if !matches!(self.connection_type, ConnectionType::Lookup) {
+            bail!("table {} is not a lookup table", self.name);
+        }
+
+        if self.fields.is_empty() {
+            bail!("lookup table {} must have a defined schema", self.name);
+        }
+
+        if self.has_virtual_fields() {
+            bail!("virtual fields are not supported in lookup tables");
+        }
+
+        if self.has_metadata_fields() {
+            bail!("metadata fields are not supported in lookup tables");
+        }
+
+        if self.is_update() {
+            bail!("lookup tables cannot use an updating format");
+        }
+
+        let [key_column] = self.primary_keys.as_slice() else {
+            bail!(
+                "lookup table {} must declare the column that it is looked up by as its PRIMARY KEY",
+                self.name
+            );
+        };
+
+        Ok(SqlLookup {
+            id: self.id,
+            name: self.name.clone(),
+            struct_def: StructDef::new(
+                self.type_name.clone(),
+                self.type_name.is_none(),
+                self.fields
+                    .iter()
+                    .map(|field| field.struct_field().clone())
+                    .collect(),
+                self.format.clone(),
+            ),
+            connector: self.connector_op(),
+            key_column: key_column.clone(),
+            options: self.lookup_options.clone(),
+        })
+    }
+
     fn has_virtual_fields(&self) -> bool {
         self.fields.iter().any(|f| f.is_virtual())
     }
 
+    fn has_metadata_fields(&self) -> bool {
+        self.fields.iter().any(|f| f.is_metadata())
+    }
+
     fn is_update(&self) -> bool {
         self.format
             .as_ref()
@@ -265,7 +458,7 @@ impl ConnectorTable {
                 .iter()
                 .map(|field| {
                     match field {
-                        FieldSpec::StructField(struct_field) => Ok((Column{relation: None, name: struct_field.name.clone()}, Expression::Column(ColumnExpression::new(struct_field.clone())))),
+                        FieldSpec::StructField(struct_field) | FieldSpec::MetadataField { field: struct_field, .. } => Ok((Column{relation: None, name: struct_field.name.clone()}, Expression::Column(ColumnExpression::new(struct_field.clone())))),
                         FieldSpec::VirtualField { field, expression } => {
                             let expression_type_def = expression.expression_type(&ValuePointerContext::new());
                             let expression_return_type = expression_type_def.as_datatype().expect("virtual fields shouldn't return structs");
@@ -385,12 +578,24 @@ impl ConnectorTable {
             ConnectionType::Sink => {
                 bail!("cannot read from sink")
             }
+            ConnectionType::Lookup => {
+                bail!("lookup tables can only be used on the right side of a join")
+            }
         };
 
         if self.is_update() && self.has_virtual_fields() {
             bail!("can't read from a source with virtual fields and update mode.")
         }
 
+        if self.has_metadata_fields() {
+            if self.is_update() {
+                bail!("can't read from a source with metadata fields and update mode.")
+            }
+            if self.type_name.is_some() {
+                bail!("metadata fields are only supported in tables with schemas defined in SQL");
+            }
+        }
+
         let virtual_field_projection = self.virtual_field_projection()?;
         let timestamp_override = self.timestamp_override()?;
         let watermark_column = self.watermark_column()?;
@@ -403,7 +608,11 @@ impl ConnectorTable {
                 self.fields
                     .iter()
                     .filter_map(|field| match field {
-                        FieldSpec::StructField(struct_field) => Some(struct_field.clone()),
+                        FieldSpec::StructField(struct_field)
+                        | FieldSpec::MetadataField {
+                            field: struct_field,
+                            ..
+                        } => Some(struct_field.clone()),
                         FieldSpec::VirtualField { .. } => None,
                     })
                     .collect(),
@@ -428,6 +637,9 @@ impl ConnectorTable {
             ConnectionType::Source => {
                 bail!("inserting into a source is not allowed")
             }
+            ConnectionType::Lookup => {
+                bail!("inserting into a lookup table is not allowed")
+            }
             ConnectionType::Sink => {}
         }
 
@@ -436,6 +648,10 @@ impl ConnectorTable {
             bail!("virtual fields are not currently supported in sinks");
         }
 
+        if self.has_metadata_fields() {
+            bail!("metadata fields are not supported in sinks");
+        }
+
         let updating_type = if self.is_update() {
             SinkUpdateType::Force
         } else {
@@ -500,6 +716,7 @@ pub enum Table {
         name: String,
         logical_plan: LogicalPlan,
     },
+    MatchRecognize(MatchRecognizeTable),
 }
 
 fn value_to_inner_string(value: &Value) -> Result<String> {
@@ -511,6 +728,23 @@ fn value_to_inner_string(value: &Value) -> Result<String> {
     }
 }
 
+// columns declared with `METADATA FROM 'key'` are rewritten to be generated by `metadata('key')`
+// before parsing, as the SQL parser doesn't support that syntax
+fn metadata_key(expr: &SqlExpr) -> Option<String> {
+    let SqlExpr::Function(function) = expr else {
+        return None;
+    };
+    if !function.name.to_string().eq_ignore_ascii_case("metadata") {
+        return None;
+    }
+    match function.args.as_slice() {
+        [FunctionArg::Unnamed(FunctionArgExpr::Expr(SqlExpr::Value(Value::SingleQuotedString(
+            key,
+        ))))] => Some(key.clone()),
+        _ => None,
+    }
+}
+
 fn qualified_field(f: &DFField) -> Field {
     Field::new(f.qualified_name(), f.data_type().clone(), f.is_nullable())
 }
@@ -552,8 +786,8 @@ impl Table {
                 .iter()
                 .filter_map(
                     |(field, generating_expression)| match generating_expression {
-                        Some(_) => None,
-                        None => Some(field.clone()),
+                        Some(expr) if metadata_key(expr).is_none() => None,
+                        _ => Some(field.clone()),
                     },
                 )
                 .collect(),
@@ -582,7 +816,12 @@ impl Table {
         struct_field_pairs
             .into_iter()
             .map(|(struct_field, generating_expression)| {
-                if let Some(generating_expression) = generating_expression {
+                if let Some(key) = generating_expression.as_ref().and_then(metadata_key) {
+                    Ok(FieldSpec::MetadataField {
+                        field: struct_field.with_metadata_key(Some(key.clone())),
+                        key,
+                    })
+                } else if let Some(generating_expression) = generating_expression {
                     // TODO: Implement automatic type coercion here, as we have elsewhere.
                     // It is done by calling the Analyzer which inserts CAST operators where necessary.
 
@@ -603,6 +842,58 @@ impl Table {
             .collect::<Result<Vec<_>>>()
     }
 
+    fn primary_keys(columns: &[ColumnDef], constraints: &[TableConstraint]) -> Result<Vec<String>> {
+        let column_keys: Vec<String> = columns
+            .iter()
+            .filter(|column| {
+                column.options.iter().any(|option| {
+                    matches!(
+                        option.option,
+                        ColumnOption::Unique {
+                            is_primary: true,
+                            ..
+                        }
+                    )
+                })
+            })
+            .map(|column| column.name.value.to_string())
+            .collect();
+
+        let mut table_keys = constraints
+            .iter()
+            .filter_map(|constraint| match constraint {
+                TableConstraint::Unique {
+                    columns,
+                    is_primary: true,
+                    ..
+                } => Some(columns),
+                _ => None,
+            });
+
+        let keys = match table_keys.next() {
+            Some(keys) => {
+                if !column_keys.is_empty() || table_keys.next().is_some() {
+                    bail!("a table can only have one primary key");
+                }
+                keys.iter().map(|key| key.value.to_string()).collect()
+            }
+            None => {
+                if column_keys.len() > 1 {
+                    bail!("a table can only have one primary key; use a PRIMARY KEY (...) constraint for keys with multiple columns");
+                }
+                column_keys
+            }
+        };
+
+        for key in &keys {
+            if !columns.iter().any(|c| &c.name.value == key) {
+                bail!("primary key column '{}' is not a column of the table", key);
+            }
+        }
+
+        Ok(keys)
+    }
+
     pub fn try_from_statement(
         statement: &Statement,
         schema_provider: &ArroyoSchemaProvider,
@@ -610,6 +901,7 @@ impl Table {
         if let Statement::CreateTable {
             name,
             columns,
+            constraints,
             with_options,
             query: None,
             ..
@@ -626,6 +918,7 @@ impl Table {
 
             let connector = with_map.remove("connector");
             let fields = Self::schema_from_columns(columns, schema_provider)?;
+            let primary_keys = Self::primary_keys(columns, constraints)?;
 
             match connector.as_ref().map(|c| c.as_str()) {
                 Some("memory") | None => {
@@ -633,6 +926,14 @@ impl Table {
                         bail!("Virtual fields are not supported in memory tables; instead write a query");
                     }
 
+                    if fields.iter().any(|f| f.is_metadata()) {
+                        bail!("Metadata fields are not supported in memory tables");
+                    }
+
+                    if !primary_keys.is_empty() {
+                        bail!("Primary keys are not supported in memory tables");
+                    }
+
                     if !with_map.is_empty() {
                         if connector.is_some() {
                             bail!("Memory tables do not allow with options");
@@ -669,6 +970,7 @@ impl Table {
                             &name,
                             connector,
                             fields,
+                            primary_keys,
                             &mut with_map,
                             connection_profile,
                         )
@@ -702,6 +1004,7 @@ impl Table {
         match self {
             Table::MemoryTable { name, .. } | Table::TableFromQuery { name, .. } => name.as_str(),
             Table::ConnectorTable(c) => c.name.as_str(),
+            Table::MatchRecognize(m) => m.name.as_str(),
         }
     }
 
@@ -755,6 +1058,7 @@ impl Table {
                 .iter()
                 .map(qualified_field)
                 .collect(),
+            Table::MatchRecognize(m) => m.fields.iter().map(|field| field.clone().into()).collect(),
         }
     }
 
@@ -774,6 +1078,7 @@ impl Table {
             Table::TableFromQuery { logical_plan, .. } => {
                 builder.insert_sql_plan(&logical_plan.clone())
             }
+            Table::MatchRecognize(m) => m.as_sql_source(builder),
         }
     }
 
@@ -784,6 +1089,7 @@ impl Table {
                 Ok(SqlOperator::NamedTable(name.clone(), Box::new(input)))
             }
             Table::TableFromQuery { .. } => todo!(),
+            Table::MatchRecognize(m) => bail!("can't insert into {}", m.name),
         }
     }
 }
@@ -3,6 +3,7 @@ use arroyo_connectors::{
     nexmark::{NexmarkConnector, NexmarkTable},
     Connector, EmptyConfig,
 };
+use arroyo_datastream::Operator;
 
 use crate::{parse_and_get_program, types::TypeDef, ArroyoSchemaProvider, SqlConfig};
 
@@ -117,6 +118,62 @@ async fn test_window_function() {
         .unwrap();
 }
 
+#[tokio::test]
+async fn test_lag_window_function() {
+    let schema_provider = get_test_schema_provider();
+
+    let sql = "SELECT auction, price - LAG(price, 1, price) OVER (
+        PARTITION BY window, auction
+        ORDER BY datetime) as delta
+    FROM (SELECT bid.auction as auction, bid.price as price, bid.datetime as datetime,
+        tumble(interval '10 seconds') as window
+        FROM nexmark WHERE bid is not null)";
+
+    parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_no_window_frames() {
+    let schema_provider = get_test_schema_provider();
+
+    let sql = "SELECT auction, LAST_VALUE(price) OVER (
+        PARTITION BY window, auction
+        ORDER BY datetime
+        ROWS BETWEEN UNBOUNDED PRECEDING AND UNBOUNDED FOLLOWING) as last_price
+    FROM (SELECT bid.auction as auction, bid.price as price, bid.datetime as datetime,
+        tumble(interval '10 seconds') as window
+        FROM nexmark WHERE bid is not null)";
+
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("window frames are not supported"));
+}
+
+#[tokio::test]
+async fn test_ntile_requires_literal() {
+    let schema_provider = get_test_schema_provider();
+
+    let sql = "SELECT *, NTILE(count) OVER (
+        PARTITION BY window
+        ORDER BY count DESC) as bucket
+    FROM (SELECT count(*) as count,
+        tumble(interval '10 seconds') as window
+            FROM nexmark
+            group by window)";
+
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("NTILE requires an integer literal argument"));
+}
+
 #[tokio::test]
 async fn test_no_updating_window_functions() {
     let schema_provider = get_test_schema_provider();
@@ -217,3 +274,1108 @@ async fn test_udf() {
         .await
         .unwrap();
 }
+
+#[tokio::test]
+async fn test_incremental_udaf() {
+    let mut schema_provider = get_test_schema_provider();
+
+    schema_provider
+        .add_rust_udf(
+            "mod my_sum {
+                fn accumulate(state: &mut (i64,), x: i64) { state.0 += x; }
+                fn merge(state: &mut (i64,), other: &(i64,)) { state.0 += other.0; }
+                fn finish(state: &(i64,)) -> i64 { state.0 }
+            }",
+        )
+        .unwrap();
+
+    let def = schema_provider.udf_defs.get("my_sum").unwrap();
+    assert_eq!(def.args, vec![TypeDef::DataType(DataType::Int64, false)]);
+    assert!(!def.accumulator.as_ref().unwrap().retractable);
+
+    let sql =
+        "SELECT my_sum(bid.price), arg_max(bid.auction, bid.price), corr(bid.price, bid.auction)
+    FROM nexmark
+    GROUP BY hop(interval '2 seconds', interval '10 seconds')";
+    parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+
+    let sql = "SELECT my_sum(bid.price) FROM nexmark GROUP BY bid.auction";
+    parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_aggregate_filter_must_be_boolean() {
+    let schema_provider = get_test_schema_provider();
+    for aggregate in ["max(bid.price)", "arg_max(bid.auction, bid.price)"] {
+        let sql = format!(
+            "SELECT {} FILTER (WHERE bid.price) FROM nexmark
+            GROUP BY hop(interval '2 seconds', interval '10 seconds')",
+            aggregate
+        );
+        let err = parse_and_get_program(&sql, schema_provider.clone(), SqlConfig::default())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "aggregate filter must be a boolean expression"
+        );
+    }
+}
+
+#[test]
+fn test_udaf_requires_hooks() {
+    let mut schema_provider = get_test_schema_provider();
+    let err = schema_provider
+        .add_rust_udf(
+            "mod my_sum {
+                fn accumulate(state: &mut (i64,), x: i64) { state.0 += x; }
+                fn finish(state: &(i64,)) -> i64 { state.0 }
+            }",
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "UDAF my_sum must define a 'merge' function"
+    );
+}
+
+#[tokio::test]
+async fn test_session_window_two_phase() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "SELECT count(*), max(bid.price),
+        session(INTERVAL '10' SECOND, INTERVAL '1' HOUR) AS window
+      FROM nexmark
+      WHERE bid is not null
+      GROUP BY window, bid.auction";
+    let compiled = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+
+    assert!(compiled.program.graph.node_weights().any(|node| matches!(
+        &node.operator,
+        Operator::SessionWindowAggregator(aggregator)
+            if aggregator.max_length == Some(std::time::Duration::from_secs(3600))
+    )));
+}
+
+#[tokio::test]
+async fn test_session_max_length_less_than_gap() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "SELECT count(*), session(INTERVAL '1' HOUR, INTERVAL '10' SECOND) AS window
+      FROM nexmark
+      GROUP BY window, bid.auction";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "the maximum length of a session can't be less than its gap"
+    );
+}
+
+#[tokio::test]
+async fn test_cumulating_window() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "SELECT count(*), max(bid.price),
+        cumulate(INTERVAL '1' MINUTE, INTERVAL '1' HOUR) AS window
+      FROM nexmark
+      WHERE bid is not null
+      GROUP BY window, bid.auction";
+    let compiled = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+
+    assert!(compiled.program.graph.node_weights().any(|node| matches!(
+        &node.operator,
+        Operator::CumulatingWindowAggregator(aggregator)
+            if aggregator.step == std::time::Duration::from_secs(60)
+                && aggregator.max_size == std::time::Duration::from_secs(3600)
+    )));
+}
+
+#[tokio::test]
+async fn test_cumulating_window_size_not_multiple_of_step() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "SELECT count(*), cumulate(INTERVAL '7' SECOND, INTERVAL '1' MINUTE) AS window
+      FROM nexmark
+      GROUP BY window, bid.auction";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "the maximum size of a cumulating window must be a multiple of its step"
+    );
+}
+
+const INTERVAL_JOIN_TABLES: &str = "CREATE TABLE bids (
+        auction BIGINT,
+        price BIGINT,
+        datetime TIMESTAMP,
+        ingested TIMESTAMP
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        topic = 'bids',
+        type = 'source',
+        format = 'json',
+        event_time_field = 'datetime'
+      );
+
+      CREATE TABLE auctions (
+        id BIGINT,
+        datetime TIMESTAMP
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        topic = 'auctions',
+        type = 'source',
+        format = 'json',
+        event_time_field = 'datetime'
+      );";
+
+#[tokio::test]
+async fn test_interval_join() {
+    let schema_provider = get_test_schema_provider();
+    let sql = format!(
+        "{}
+      SELECT bids.auction, bids.price
+      FROM bids JOIN auctions ON bids.auction = auctions.id
+        AND bids.datetime BETWEEN auctions.datetime - INTERVAL '5' MINUTE
+          AND auctions.datetime + INTERVAL '1' MINUTE",
+        INTERVAL_JOIN_TABLES
+    );
+    let compiled = parse_and_get_program(&sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+
+    let interval_join = compiled
+        .program
+        .graph
+        .node_weights()
+        .find_map(|node| match &node.operator {
+            Operator::IntervalJoin(join) => Some(join.clone()),
+            _ => None,
+        })
+        .expect("expected an interval join");
+    assert_eq!(-5 * 60 * 1_000_000, interval_join.lower_bound_micros);
+    assert_eq!(60 * 1_000_000, interval_join.upper_bound_micros);
+}
+
+#[tokio::test]
+async fn test_interval_join_requires_both_bounds() {
+    let schema_provider = get_test_schema_provider();
+    let sql = format!(
+        "{}
+      SELECT bids.auction
+      FROM bids JOIN auctions ON bids.auction = auctions.id
+        AND bids.datetime > auctions.datetime",
+        INTERVAL_JOIN_TABLES
+    );
+    let err = parse_and_get_program(&sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("time-range join conditions must bound the time difference"));
+}
+
+#[tokio::test]
+async fn test_interval_join_requires_event_time() {
+    let schema_provider = get_test_schema_provider();
+    let sql = format!(
+        "{}
+      SELECT bids.auction
+      FROM bids JOIN auctions ON bids.auction = auctions.id
+        AND bids.ingested BETWEEN auctions.datetime AND auctions.datetime + INTERVAL '1' MINUTE",
+        INTERVAL_JOIN_TABLES
+    );
+    let err = parse_and_get_program(&sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("time-range join conditions must compare the event time columns"));
+}
+
+const TEMPORAL_JOIN_TABLES: &str = "CREATE TABLE bids (
+        auction BIGINT,
+        price BIGINT,
+        datetime TIMESTAMP,
+        ingested TIMESTAMP
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        topic = 'bids',
+        type = 'source',
+        format = 'json',
+        event_time_field = 'datetime'
+      );
+
+      CREATE TABLE auction_categories (
+        id BIGINT,
+        category BIGINT
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'auction_categories',
+        format = 'debezium_json'
+      );";
+
+#[tokio::test]
+async fn test_temporal_join() {
+    let schema_provider = get_test_schema_provider();
+    let sql = format!(
+        "{}
+      SELECT bids.auction, bids.price, c.category
+      FROM bids
+      LEFT JOIN auction_categories FOR SYSTEM_TIME AS OF bids.datetime AS c
+        ON bids.auction = c.id",
+        TEMPORAL_JOIN_TABLES
+    );
+    let compiled = parse_and_get_program(&sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+
+    assert!(compiled
+        .program
+        .graph
+        .node_weights()
+        .any(|node| matches!(node.operator, Operator::TemporalJoin { .. })));
+    assert!(!compiled
+        .program
+        .graph
+        .node_weights()
+        .any(|node| matches!(node.operator, Operator::JoinWithExpiration { .. })));
+}
+
+#[tokio::test]
+async fn test_temporal_join_requires_inner_or_left() {
+    let schema_provider = get_test_schema_provider();
+    let sql = format!(
+        "{}
+      SELECT bids.auction, c.category
+      FROM bids
+      FULL JOIN auction_categories FOR SYSTEM_TIME AS OF bids.datetime AS c
+        ON bids.auction = c.id",
+        TEMPORAL_JOIN_TABLES
+    );
+    let err = parse_and_get_program(&sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "temporal joins only support inner and left joins"
+    );
+}
+
+#[tokio::test]
+async fn test_temporal_join_requires_event_time() {
+    let schema_provider = get_test_schema_provider();
+    let sql = format!(
+        "{}
+      SELECT bids.auction, c.category
+      FROM bids
+      JOIN auction_categories FOR SYSTEM_TIME AS OF bids.ingested AS c
+        ON bids.auction = c.id",
+        TEMPORAL_JOIN_TABLES
+    );
+    let err = parse_and_get_program(&sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "FOR SYSTEM_TIME AS OF bids.ingested must be the event time column of the left side of the join, datetime"
+    );
+}
+
+#[tokio::test]
+async fn test_match_recognize() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE VIEW bids AS
+      SELECT bid.auction as auction, bid.price as price
+      FROM nexmark WHERE bid is not null;
+
+      SELECT * FROM bids MATCH_RECOGNIZE (
+        PARTITION BY auction
+        ORDER BY _timestamp
+        MEASURES
+          FIRST(UP.price) AS start_price,
+          LAST(UP.price) AS end_price,
+          COUNT(UP.*) AS rises
+        ONE ROW PER MATCH
+        AFTER MATCH SKIP PAST LAST ROW
+        PATTERN (START UP{2,} DOWN)
+        WITHIN INTERVAL '1' HOUR
+        DEFINE
+          UP AS UP.price > 100,
+          DOWN AS DOWN.price <= 100
+      ) AS m";
+    let compiled = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+
+    assert!(compiled
+        .program
+        .graph
+        .node_weights()
+        .any(|node| matches!(node.operator, Operator::MatchRecognize(_))));
+}
+
+#[tokio::test]
+async fn test_match_recognize_unsupported_pattern() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE VIEW bids AS
+      SELECT bid.auction as auction, bid.price as price
+      FROM nexmark WHERE bid is not null;
+
+      SELECT * FROM bids MATCH_RECOGNIZE (
+        PARTITION BY auction
+        MEASURES LAST(price) AS price
+        PATTERN (A | B)
+        DEFINE A AS A.price > 100
+      )";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unsupported PATTERN '(A | B)'; only sequences of variables with quantifiers are supported"
+    );
+}
+
+#[tokio::test]
+async fn test_match_recognize_comments_and_strings() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE VIEW bids AS
+      SELECT bid.auction as auction, bid.price as price, bid.channel as channel
+      FROM nexmark WHERE bid is not null;
+
+      SELECT * FROM bids MATCH_RECOGNIZE ( -- MATCH_RECOGNIZE (
+        PARTITION BY auction
+        MEASURES LAST(UP.channel) AS last_channel /* ) */
+        PATTERN (UP+)
+        DEFINE UP AS UP.channel <> 'up.)' AND UP.price > 100
+      )";
+    let compiled = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+
+    assert!(compiled
+        .program
+        .graph
+        .node_weights()
+        .any(|node| matches!(node.operator, Operator::MatchRecognize(_))));
+}
+
+#[tokio::test]
+async fn test_match_recognize_variable_named_like_input() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE VIEW bids AS
+      SELECT bid.auction as auction, bid.price as price
+      FROM nexmark WHERE bid is not null;
+
+      SELECT * FROM bids MATCH_RECOGNIZE (
+        MEASURES LAST(bids.price) AS price
+        PATTERN (bids+)
+        DEFINE bids AS bids.price > 100
+      )";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "pattern variable bids has the same name as the MATCH_RECOGNIZE input table"
+    );
+}
+
+#[tokio::test]
+async fn test_lookup_join() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE auctions (
+        seller BIGINT PRIMARY KEY,
+        category BIGINT
+      ) WITH (
+        connector = 'redis',
+        address = 'redis://localhost:6379',
+        type = 'lookup',
+        'lookup.key_prefix' = 'auction:',
+        'lookup.cache.ttl_secs' = '60',
+        format = 'json'
+      );
+
+      SELECT bid.auction, bid.price, a.seller, a.category
+      FROM nexmark
+      LEFT JOIN auctions FOR SYSTEM_TIME AS OF PROCTIME() AS a
+        ON bid.auction = a.seller
+      WHERE bid is not null";
+    parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_lookup_join_requires_primary_key() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE auctions (
+        seller BIGINT PRIMARY KEY,
+        category BIGINT
+      ) WITH (
+        connector = 'redis',
+        address = 'redis://localhost:6379',
+        type = 'lookup',
+        format = 'json'
+      );
+
+      SELECT bid.auction, a.seller
+      FROM nexmark
+      JOIN auctions FOR SYSTEM_TIME AS OF PROCTIME() AS a
+        ON bid.auction = a.category
+      WHERE bid is not null";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("lookup joins must be on the primary key of the lookup table (seller)"));
+}
+
+#[tokio::test]
+async fn test_no_lookup_table_as_source() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE auctions (
+        seller BIGINT
+      ) WITH (
+        connector = 'redis',
+        address = 'redis://localhost:6379',
+        type = 'lookup',
+        format = 'json'
+      );
+
+      SELECT * FROM auctions";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "failed to plan auctions: lookup tables can only be used on the right side of a join"
+    );
+}
+
+#[tokio::test]
+async fn test_redis_stream_source() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE orders (
+        id BIGINT,
+        customer TEXT
+      ) WITH (
+        connector = 'redis',
+        address = 'redis://localhost:6379',
+        type = 'source',
+        source = 'stream',
+        'source.stream' = 'orders',
+        'source.group' = 'arroyo',
+        'source.offset' = 'earliest',
+        format = 'json'
+      );
+
+      SELECT customer, count(*) FROM orders GROUP BY customer, tumble(interval '1 minute')";
+    parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_redis_stream_source_requires_field() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE orders (
+        value TEXT
+      ) WITH (
+        connector = 'redis',
+        address = 'redis://localhost:6379',
+        type = 'source',
+        source = 'stream',
+        'source.stream' = 'orders',
+        'source.group' = 'arroyo',
+        format = 'raw_string'
+      );
+
+      SELECT * FROM orders";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("source.field must be set"));
+}
+
+#[tokio::test]
+async fn test_kafka_metadata_fields() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE events (
+        value TEXT,
+        key BYTEA METADATA FROM 'key',
+        partition INT METADATA FROM 'partition',
+        offset_id BIGINT METADATA FROM 'offset'
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'events',
+        format = 'json'
+      );
+
+      SELECT value, key, partition, offset_id FROM events";
+    let compiled = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+
+    let config = compiled
+        .program
+        .graph
+        .node_weights()
+        .find_map(|node| match &node.operator {
+            Operator::ConnectorSource(op) => Some(op.config.clone()),
+            _ => None,
+        })
+        .unwrap();
+    let config: arroyo_rpc::OperatorConfig = serde_json::from_str(&config).unwrap();
+    assert_eq!(
+        vec![
+            ("key", "key"),
+            ("partition", "partition"),
+            ("offset_id", "offset")
+        ],
+        config
+            .metadata_fields
+            .iter()
+            .map(|f| (f.field_name.as_str(), f.key.as_str()))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_kafka_metadata() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE events (
+        value TEXT,
+        leader INT METADATA FROM 'leader'
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'events',
+        format = 'json'
+      );
+
+      SELECT * FROM events";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("unknown Kafka metadata 'leader' for field 'leader'"));
+}
+
+#[tokio::test]
+async fn test_postgres_cdc_is_updating() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE orders (
+        id INT,
+        amount DOUBLE
+      ) WITH (
+        connector = 'postgres_cdc',
+        host = 'localhost',
+        database = 'shop',
+        user = 'arroyo',
+        table = 'orders',
+        slot = 'arroyo_orders',
+        publication = 'arroyo_orders'
+      );
+
+      CREATE TABLE sink (
+        id INT,
+        amount DOUBLE
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'sink',
+        topic = 'sink',
+        format = 'json'
+      );
+
+      INSERT INTO sink SELECT * FROM orders";
+    let _ = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+}
+
+#[tokio::test]
+async fn test_postgres_cdc_format() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE orders (
+        id INT
+      ) WITH (
+        connector = 'postgres_cdc',
+        host = 'localhost',
+        database = 'shop',
+        user = 'arroyo',
+        table = 'orders',
+        slot = 'arroyo_orders',
+        publication = 'arroyo_orders',
+        format = 'json'
+      );
+
+      SELECT * FROM orders";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("don't take a format"));
+}
+
+#[tokio::test]
+async fn test_jdbc_requires_primary_key() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE totals (
+        id BIGINT,
+        total BIGINT
+      ) WITH (
+        connector = 'jdbc',
+        url = 'postgres://localhost:5432/arroyo',
+        table = 'totals'
+      );
+
+      INSERT INTO totals SELECT bid.auction, count(*) FROM nexmark GROUP BY 1";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("PRIMARY KEY"));
+}
+
+#[tokio::test]
+async fn test_jdbc_primary_key() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE totals (
+        id BIGINT PRIMARY KEY,
+        total BIGINT
+      ) WITH (
+        connector = 'jdbc',
+        url = 'postgres://localhost:5432/arroyo',
+        table = 'totals'
+      );
+
+      INSERT INTO totals SELECT bid.auction, count(*) FROM nexmark GROUP BY 1";
+    let _ = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_memory_table_primary_key() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE totals (
+        id BIGINT,
+        total BIGINT,
+        PRIMARY KEY (id)
+      );
+
+      SELECT * FROM totals";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Primary keys are not supported in memory tables"));
+}
+
+#[tokio::test]
+async fn test_iceberg_sink_requires_time_partition_field() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE bids (
+        auction BIGINT,
+        bid_time TIMESTAMP
+      ) WITH (
+        connector = 'iceberg',
+        path = '/tmp/iceberg/bids',
+        format = 'parquet',
+        time_partition_pattern = '%Y/%m/%d'
+      );
+
+      INSERT INTO bids SELECT bid.auction, bid.datetime FROM nexmark WHERE bid IS NOT NULL";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("iceberg.time_partition_field must be set"));
+}
+
+#[tokio::test]
+async fn test_iceberg_sink() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE bids (
+        auction BIGINT,
+        bid_time TIMESTAMP
+      ) WITH (
+        connector = 'iceberg',
+        path = '/tmp/iceberg/bids',
+        format = 'parquet',
+        'iceberg.time_partition_field' = 'bid_time',
+        time_partition_pattern = '%Y/%m/%d/%H'
+      );
+
+      INSERT INTO bids SELECT bid.auction, bid.datetime FROM nexmark WHERE bid IS NOT NULL";
+    parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_delta_source() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE bids (
+        auction BIGINT,
+        price BIGINT
+      ) WITH (
+        connector = 'delta',
+        type = 'source',
+        path = '/tmp/delta/bids',
+        format = 'parquet',
+        poll_interval_seconds = '10'
+      );
+
+      SELECT auction, sum(price) FROM bids GROUP BY 1";
+    parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_table_source_requires_parquet() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE bids (
+        auction BIGINT,
+        price BIGINT
+      ) WITH (
+        connector = 'iceberg',
+        type = 'source',
+        path = '/tmp/iceberg/bids',
+        format = 'json'
+      );
+
+      SELECT * FROM bids";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Iceberg sources only support the parquet format"));
+}
+
+#[tokio::test]
+async fn test_filesystem_source_requires_avro_container_files() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE events (
+        id BIGINT,
+        name TEXT
+      ) WITH (
+        connector = 'filesystem',
+        type = 'source',
+        path = '/tmp/events',
+        format = 'avro',
+        'avro.raw_datums' = 'true'
+      );
+
+      SELECT * FROM events";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("FileSystem sources only support Avro object container files"));
+}
+
+#[tokio::test]
+async fn test_csv_delimiter_must_be_a_character() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE orders (
+        id BIGINT,
+        customer TEXT
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        type = 'source',
+        topic = 'orders',
+        format = 'csv',
+        'csv.delimiter' = '||'
+      );
+
+      SELECT * FROM orders";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("csv.delimiter must be a single character"));
+}
+
+#[tokio::test]
+async fn test_set_state_options() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "SET state.ttl = '2 hours';
+      SET join.left_expiration = '30 minutes';
+      SET watermark.max_lateness = '10 seconds';
+
+      WITH bids as (
+        SELECT bid.auction as auction, bid.price as price FROM nexmark where bid is not null),
+      auctions as (
+        SELECT auction.id as id, auction.seller as seller FROM nexmark where auction is not null)
+      SELECT bids.auction, bids.price, auctions.seller
+      FROM bids JOIN auctions ON bids.auction = auctions.id";
+    let compiled = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+
+    assert!(compiled.program.graph.node_weights().any(|node| matches!(
+        &node.operator,
+        Operator::JoinWithExpiration { left_expiration, right_expiration, .. }
+            if *left_expiration == std::time::Duration::from_secs(30 * 60)
+                && *right_expiration == std::time::Duration::from_secs(2 * 60 * 60)
+    )));
+    assert!(compiled.program.graph.node_weights().any(|node| matches!(
+        &node.operator,
+        Operator::Watermark(watermark) if matches!(
+            watermark.strategy,
+            arroyo_datastream::WatermarkStrategy::FixedLateness { max_lateness }
+                if max_lateness == std::time::Duration::from_secs(10)
+        )
+    )));
+}
+
+#[tokio::test]
+async fn test_set_unknown_option() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "SET state.expiration = '1 hour';
+      SELECT bid.price FROM nexmark";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unknown option 'state.expiration' in SET statement"
+    );
+}
+
+#[tokio::test]
+async fn test_set_duration_overflow() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "SET state.ttl = '18446744073709551615 days';
+      SELECT bid.price FROM nexmark";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "duration '18446744073709551615 days' is too large; durations can be at most 3650 days"
+    );
+
+    // large enough to overflow event time arithmetic, though not the duration itself
+    let schema_provider = get_test_schema_provider();
+    let sql = "SET join.expiration = '1000000 days';
+      SELECT bid.price FROM nexmark";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "duration '1000000 days' is too large; durations can be at most 3650 days"
+    );
+}
+
+#[tokio::test]
+async fn test_set_operator_parallelism() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "SET parallelism.nexmark = 2;
+      SELECT count(*), tumble(INTERVAL '1' MINUTE) AS window
+      FROM nexmark
+      GROUP BY window";
+    let compiled = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+
+    let parallelism = |prefix: &str| {
+        compiled
+            .program
+            .graph
+            .node_weights()
+            .find(|node| node.operator_id.starts_with(prefix))
+            .unwrap()
+            .parallelism
+    };
+    assert_eq!(parallelism("nexmark"), 2);
+    assert_eq!(parallelism("watermark"), 2);
+    assert_eq!(parallelism("sink_"), 4);
+
+    let schema_provider = get_test_schema_provider();
+    let sql = "SET parallelism.bids = 2;
+      SELECT bid.price FROM nexmark";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "SET parallelism.bids does not match any operator in the pipeline"
+    );
+}
+
+#[tokio::test]
+async fn test_stable_operator_ids() {
+    let compile = |sql: &'static str| async move {
+        parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+            .await
+            .unwrap()
+            .program
+    };
+
+    let original = compile(
+        "SELECT count(*) as c, tumble(INTERVAL '1' MINUTE) AS window
+        FROM nexmark
+        GROUP BY window",
+    )
+    .await;
+
+    // changing the projection of the aggregate's output keeps its state
+    let upgraded = compile(
+        "SELECT count(*) + 1 as c, tumble(INTERVAL '1' MINUTE) AS window
+        FROM nexmark
+        GROUP BY window",
+    )
+    .await;
+    let compatibility = original.state_compatibility(&upgraded);
+    assert!(compatibility.is_compatible(), "{}", compatibility);
+    assert!(compatibility.dropped.is_empty());
+    assert!(compatibility
+        .restored
+        .iter()
+        .any(|id| id.contains("aggregator")));
+
+    // a different aggregate can't read the existing state
+    let changed = compile(
+        "SELECT max(bid.price) as c, tumble(INTERVAL '1' MINUTE) AS window
+        FROM nexmark
+        GROUP BY window",
+    )
+    .await;
+    assert!(!original.state_compatibility(&changed).is_compatible());
+
+    // operator ids are full-width digests
+    for node in original.graph.node_weights() {
+        let (_, hash) = node.operator_id.rsplit_once('_').unwrap();
+        assert_eq!(hash.len(), 64, "{}", node.operator_id);
+    }
+}
+
+#[tokio::test]
+async fn test_sink_commit_mode_changes_state() {
+    let compile = |commit_mode: &'static str| async move {
+        let sql = format!(
+            "CREATE TABLE sink (
+                auction BIGINT
+            ) WITH (
+                connector = 'kafka',
+                bootstrap_servers = 'localhost:9092',
+                type = 'sink',
+                topic = 'sink',
+                format = 'json',
+                'sink.commit_mode' = '{}'
+            );
+
+            INSERT INTO sink SELECT bid.auction FROM nexmark",
+            commit_mode
+        );
+        parse_and_get_program(&sql, get_test_schema_provider(), SqlConfig::default())
+            .await
+            .unwrap()
+            .program
+    };
+
+    let at_least_once = compile("at_least_once").await;
+    assert!(at_least_once
+        .state_compatibility(&compile("at_least_once").await)
+        .is_compatible());
+
+    // exactly-once sinks keep the transactions they are committing in their state
+    let exactly_once = compile("exactly_once").await;
+    assert!(!at_least_once
+        .state_compatibility(&exactly_once)
+        .is_compatible());
+}
+
+#[tokio::test]
+async fn test_protobuf_table_in_ddl() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE orders WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        topic = 'orders',
+        type = 'source',
+        format = 'protobuf',
+        'protobuf.schema' = '
+          syntax = \"proto3\";
+          message Order {
+            int64 id = 1;
+            string customer = 2;
+            int64 amount = 3;
+          }'
+      );
+
+      SELECT customer, sum(amount) FROM orders GROUP BY customer, tumble(interval '1 minute')";
+    parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_protobuf_table_requires_valid_schema() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE orders WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        topic = 'orders',
+        type = 'source',
+        format = 'protobuf',
+        'protobuf.schema' = 'message Order { int64 id = 1'
+      );
+
+      SELECT * FROM orders";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("invalid protobuf schema"));
+}
+
+#[tokio::test]
+async fn test_timestamp_lists_in_connector_tables() {
+    let schema_provider = get_test_schema_provider();
+    let sql = "CREATE TABLE events (
+        id BIGINT,
+        times TIMESTAMP[]
+      ) WITH (
+        connector = 'kafka',
+        bootstrap_servers = 'localhost:9092',
+        topic = 'events',
+        type = 'source',
+        format = 'json'
+      );
+
+      SELECT * FROM events";
+    let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("lists of timestamps are not yet supported"));
+}
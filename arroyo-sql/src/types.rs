@@ -217,19 +217,19 @@ impl StructDef {
         parse_str(&reader_name).expect(&reader_name)
     }
 
-    pub fn generate_builder_items(&self) -> TokenStream {
+    pub fn generate_builder_items(&self) -> Result<TokenStream> {
         let struct_type = self.get_type();
         let builder_ident = self.builder_ident();
 
         let fields = &self.fields;
-        let field_definitions: Vec<TokenStream> = fields
+        let field_definitions = fields
             .iter()
             .map(|field| {
-                let field_type = field.to_array_builder_type();
+                let field_type = field.to_array_builder_type()?;
                 let field_name = field.field_array_ident();
-                quote! { #field_name: #field_type }
+                Ok(quote! { #field_name: #field_type })
             })
-            .collect();
+            .collect::<Result<Vec<_>>>()?;
 
         let schema_initializations: Vec<_> = fields
             .iter()
@@ -239,14 +239,14 @@ impl StructDef {
             })
             .collect();
 
-        let field_initializations: Vec<TokenStream> = fields
+        let field_initializations = fields
             .iter()
             .map(|field| {
                 let field_name = field.field_array_ident();
-                let field_initialization = field.create_array_builder();
-                quote! { #field_name : #field_initialization}
+                let field_initialization = field.create_array_builder()?;
+                Ok(quote! { #field_name : #field_initialization})
             })
-            .collect();
+            .collect::<Result<Vec<_>>>()?;
 
         let field_appends: Vec<TokenStream> =
             fields.iter().map(|field| field.field_append()).collect();
@@ -266,7 +266,7 @@ impl StructDef {
             })
             .collect();
 
-        quote! {
+        Ok(quote! {
             #[derive(Debug)]
             pub struct #builder_ident {
                 schema: std::sync::Arc<arrow::datatypes::Schema>,
@@ -310,7 +310,7 @@ impl StructDef {
                     self.schema.clone()
                 }
             }
-        }
+        })
     }
 
     fn generate_avro_writer(&self) -> TokenStream {
@@ -372,6 +372,36 @@ impl StructDef {
 
         let avro_writer = self.generate_avro_writer();
 
+        let metadata_fields: Vec<_> = self
+            .fields
+            .iter()
+            .filter(|field| field.metadata_key.is_some())
+            .map(|field| {
+                let name = field.name();
+                let ident = field.field_ident();
+                quote! {
+                    #name => {
+                        self.#ident = arroyo_formats::FromMetadata::from_metadata(value)
+                            .map_err(|e| format!("invalid value for field {}: {}", #name, e))?;
+                    }
+                }
+            })
+            .collect();
+
+        let metadata_setter = if metadata_fields.is_empty() {
+            quote!()
+        } else {
+            quote! {
+                fn set_metadata(&mut self, field: &str, value: arroyo_formats::Metadata) -> Result<(), String> {
+                    match field {
+                        #(#metadata_fields)*
+                        _ => return Err(format!("{} has no metadata field {}", #name, field)),
+                    }
+                    Ok(())
+                }
+            }
+        };
+
         Some(quote! {
             impl arroyo_formats::SchemaData for #struct_type {
                 fn name() -> &'static str {
@@ -389,6 +419,8 @@ impl StructDef {
 
                 #avro_writer
 
+                #metadata_setter
+
                 fn iterator_from_record_batch(
                     record_batch: arrow_array::RecordBatch,
                 ) -> anyhow::Result<Box<dyn Iterator<Item = Self> + Send>> {
@@ -532,19 +564,19 @@ impl StructDef {
         )
     }
 
-    pub fn generate_serializer_items(&self) -> TokenStream {
+    pub fn generate_serializer_items(&self) -> Result<TokenStream> {
         let schema_data_impl = self.generate_schema_data();
-        let builder_items = self.generate_builder_items();
+        let builder_items = self.generate_builder_items()?;
 
         let parquet_reader_items = self.generate_parquet_reader_items();
 
-        quote! {
+        Ok(quote! {
             #schema_data_impl
 
             #builder_items
 
             #parquet_reader_items
-        }
+        })
     }
 
     pub(crate) fn field_types_match(&self, other: &StructDef) -> bool {
@@ -567,6 +599,8 @@ pub struct StructField {
     pub data_type: TypeDef,
     pub renamed_from: Option<String>,
     pub original_type: Option<String>,
+    // the key of the message metadata that a source populates this field from
+    pub metadata_key: Option<String>,
 }
 
 impl StructField {
@@ -596,6 +630,7 @@ impl StructField {
             ident,
             renamed_from,
             original_type: None,
+            metadata_key: None,
         }
     }
 
@@ -614,104 +649,138 @@ impl StructField {
             data_type,
             renamed_from,
             original_type,
+            metadata_key: None,
         }
     }
 
+    pub fn with_metadata_key(mut self, metadata_key: Option<String>) -> Self {
+        self.metadata_key = metadata_key;
+        self
+    }
+
     fn parquet_read_assigmment(&self) -> TokenStream {
         let field_name = self.field_ident();
         match &self.data_type {
             TypeDef::StructDef(_, _) => {
                 quote!(self.#field_name.get(index))
             }
-            TypeDef::DataType(data_type, true) => match data_type {
+            TypeDef::DataType(data_type, nullable) => {
+                Self::arrow_read_expression(quote!(&self.#field_name), data_type, *nullable)
+            }
+        }
+    }
+
+    fn arrow_read_expression(
+        array: TokenStream,
+        data_type: &DataType,
+        nullable: bool,
+    ) -> TokenStream {
+        match (data_type, nullable) {
+            (DataType::List(item), _) => {
+                let item_read = Self::arrow_read_expression(
+                    quote!(&__list),
+                    item.data_type(),
+                    item.is_nullable(),
+                );
+                let list_read = quote!({
+                    let __list = arrow_array::cast::AsArray::as_list::<i32>(#array).value(index);
+                    (0..__list.len()).map(|index| #item_read).collect()
+                });
+                if nullable {
+                    quote!((!arrow_array::Array::is_null(#array, index)).then(|| #list_read))
+                } else {
+                    list_read
+                }
+            }
+            (data_type, true) => match data_type {
                 DataType::Null => quote!(None),
                 DataType::Boolean => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_bool_from_arrow_array_nullable(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_bool_from_arrow_array_nullable(#array, index))
                 }
                 DataType::Int8 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Int8Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Int8Type>(#array, index))
                 }
                 DataType::Int16 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Int16Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Int16Type>(#array, index))
                 }
                 DataType::Int32 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Int32Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Int32Type>(#array, index))
                 }
                 DataType::Int64 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Int64Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Int64Type>(#array, index))
                 }
                 DataType::UInt8 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::UInt8Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::UInt8Type>(#array, index))
                 }
                 DataType::UInt16 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::UInt16Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::UInt16Type>(#array, index))
                 }
                 DataType::UInt32 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::UInt32Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::UInt32Type>(#array, index))
                 }
                 DataType::UInt64 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::UInt64Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::UInt64Type>(#array, index))
                 }
                 DataType::Float16 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Float16Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Float16Type>(#array, index))
                 }
                 DataType::Float32 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Float32Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Float32Type>(#array, index))
                 }
                 DataType::Float64 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Float64Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array_nullable::<arrow::datatypes::Float64Type>(#array, index))
                 }
                 DataType::Utf8 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_string_from_arrow_array_nullable(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_string_from_arrow_array_nullable(#array, index))
                 }
                 DataType::Timestamp(_, None) => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_timestamp_from_arrow_array_nullable(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_timestamp_from_arrow_array_nullable(#array, index))
                 }
                 _ => unimplemented!("parquet_read_assigmment for {:?}", data_type),
             },
-            TypeDef::DataType(data_type, false) => match data_type {
+            (data_type, false) => match data_type {
                 DataType::Null => quote!(None),
                 DataType::Boolean => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_bool_from_arrow_array(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_bool_from_arrow_array(#array, index))
                 }
                 DataType::Int8 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Int8Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Int8Type>(#array, index))
                 }
                 DataType::Int16 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Int16Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Int16Type>(#array, index))
                 }
                 DataType::Int32 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Int32Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Int32Type>(#array, index))
                 }
                 DataType::Int64 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Int64Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Int64Type>(#array, index))
                 }
                 DataType::UInt8 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::UInt8Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::UInt8Type>(#array, index))
                 }
                 DataType::UInt16 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::UInt16Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::UInt16Type>(#array, index))
                 }
                 DataType::UInt32 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::UInt32Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::UInt32Type>(#array, index))
                 }
                 DataType::UInt64 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::UInt64Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::UInt64Type>(#array, index))
                 }
                 DataType::Float16 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Float16Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Float16Type>(#array, index))
                 }
                 DataType::Float32 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Float32Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Float32Type>(#array, index))
                 }
                 DataType::Float64 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Float64Type>(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_native_value_from_arrow_array::<arrow::datatypes::Float64Type>(#array, index))
                 }
                 DataType::Utf8 => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_string_from_arrow_array(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_string_from_arrow_array(#array, index))
                 }
                 DataType::Timestamp(_, None) => {
-                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_timestamp_from_arrow_array(&self.#field_name, index))
+                    quote!(arroyo_worker::connectors::filesystem::arrow::extract_timestamp_from_arrow_array(#array, index))
                 }
                 _ => unimplemented!("parquet_read_assigmment for {:?}", data_type),
             },
@@ -782,7 +851,7 @@ impl From<SourceField> for StructField {
             ),
         };
 
-        StructField::new(f.field_name, None, t)
+        StructField::new(f.field_name, None, t).with_metadata_key(f.metadata_key)
     }
 }
 
@@ -1023,7 +1092,12 @@ impl StructField {
             });
         }
 
-        if let TypeDef::DataType(DataType::Timestamp(_, _), nullable) = self.data_type {
+        if self.metadata_key.is_some() {
+            // metadata fields aren't part of the payload, and are set by the source afterwards
+            attributes.push(quote! {
+                #[serde(skip_deserializing, default = "arroyo_formats::FromMetadata::placeholder")]
+            });
+        } else if let TypeDef::DataType(DataType::Timestamp(_, _), nullable) = self.data_type {
             match format.as_ref().map(|t| &*t) {
                 Some(Format::Json(JsonFormat {
                     timestamp_format: TimestampFormat::UnixMillis,
@@ -1141,7 +1215,13 @@ impl StructField {
             DataType::LargeBinary => todo!(),
             DataType::Utf8 => quote!(arrow::datatypes::DataType::Utf8),
             DataType::LargeUtf8 => todo!(),
-            DataType::List(_) => todo!(),
+            DataType::List(field) => {
+                // list builders always produce nullable items
+                let item_type = Self::get_data_type_literal(field.data_type(), parent_nullable);
+                quote!(arrow::datatypes::DataType::List(std::sync::Arc::new(
+                    arrow::datatypes::Field::new("item", #item_type, true)
+                )))
+            }
             DataType::FixedSizeList(_, _) => todo!(),
             DataType::LargeList(_) => todo!(),
             DataType::Struct(struct_fields) => {
@@ -1213,6 +1293,21 @@ impl StructField {
             ) => {
                 quote!(self.#field_array_name.append_option(data.#field_name.map(|time| arroyo_types::to_nanos(time) as i64)))
             }
+            TypeDef::DataType(DataType::List(field), nullable) => {
+                let items = if field.is_nullable() {
+                    quote!(list)
+                } else {
+                    quote!(list.into_iter().map(Some))
+                };
+                if *nullable {
+                    quote!(self.#field_array_name.append_option(data.#field_name.map(|list| #items)))
+                } else {
+                    quote!({
+                        let list = data.#field_name;
+                        self.#field_array_name.append_value(#items)
+                    })
+                }
+            }
             TypeDef::DataType(_, true) => {
                 quote!(self.#field_array_name.append_option(data.#field_name))
             }
@@ -1268,8 +1363,8 @@ impl StructField {
         }
     }
 
-    fn create_array_builder(&self) -> TokenStream {
-        match &self.data_type {
+    fn create_array_builder(&self) -> Result<TokenStream> {
+        Ok(match &self.data_type {
             TypeDef::StructDef(struct_type, false) => {
                 let builder_name = format!("{}RecordBatchBuilder", struct_type.struct_name_ident());
                 let builder_ident: Ident = parse_str(&builder_name).expect(&builder_name);
@@ -1295,7 +1390,7 @@ impl StructField {
                 | DataType::Float32
                 | DataType::Float64
                 | DataType::Timestamp(_, None) => {
-                    let builder_type = self.to_array_builder_type();
+                    let builder_type = self.to_array_builder_type()?;
                     quote!(#builder_type::with_capacity(1024))
                 }
                 DataType::Timestamp(_, _) => todo!(),
@@ -1312,7 +1407,10 @@ impl StructField {
                     arrow_array::types::GenericStringType<i32>,
                 >::new()),
                 DataType::LargeUtf8 => todo!(),
-                DataType::List(_) => todo!(),
+                DataType::List(field) => {
+                    let values_builder = Self::list_item_field(field)?.create_array_builder()?;
+                    quote!(arrow_array::builder::GenericListBuilder::<i32, _>::new(#values_builder))
+                }
                 DataType::FixedSizeList(_, _) => todo!(),
                 DataType::LargeList(_) => todo!(),
                 DataType::Struct(_) => todo!(),
@@ -1323,10 +1421,10 @@ impl StructField {
                 DataType::Map(_, _) => todo!(),
                 DataType::RunEndEncoded(_, _) => todo!(),
             },
-        }
+        })
     }
 
-    fn to_array_builder_type(&self) -> Type {
+    fn to_array_builder_type(&self) -> Result<Type> {
         let tokens = match &self.data_type {
             TypeDef::StructDef(struct_type, _) => {
                 let builder_name = format!("{}RecordBatchBuilder", struct_type.struct_name_ident());
@@ -1406,7 +1504,10 @@ impl StructField {
                     )
                 }
                 DataType::LargeUtf8 => todo!(),
-                DataType::List(_) => todo!(),
+                DataType::List(field) => {
+                    let values_builder = Self::list_item_field(field)?.to_array_builder_type()?;
+                    quote!(arrow_array::builder::GenericListBuilder<i32, #values_builder>)
+                }
                 DataType::FixedSizeList(_, _) => todo!(),
                 DataType::LargeList(_) => todo!(),
                 DataType::Struct(_) => todo!(),
@@ -1417,7 +1518,18 @@ impl StructField {
                 _ => todo!("{:?}", self),
             },
         };
-        parse_str(&tokens.to_string()).unwrap()
+        Ok(parse_str(&tokens.to_string()).unwrap())
+    }
+
+    fn list_item_field(field: &Field) -> Result<StructField> {
+        if matches!(field.data_type(), DataType::Timestamp(_, _)) {
+            bail!("lists of timestamps are not yet supported in connector tables");
+        }
+        Ok(StructField::new(
+            "item".to_string(),
+            None,
+            TypeDef::DataType(field.data_type().clone(), field.is_nullable()),
+        ))
     }
 
     pub fn get_return_expression(&self, parent_ident: TokenStream) -> TokenStream {
@@ -1603,6 +1715,7 @@ impl TryFrom<StructField> for SourceField {
     fn try_from(f: StructField) -> Result<Self, Self::Error> {
         let field_name = f.name();
         let nullable = f.nullable();
+        let metadata_key = f.metadata_key.clone();
         let (field_type, sql_name) = match f.data_type {
             TypeDef::StructDef(StructDef { name, fields, .. }, _) => {
                 let fields: Result<_, String> = fields.into_iter().map(|f| f.try_into()).collect();
@@ -1645,6 +1758,7 @@ impl TryFrom<StructField> for SourceField {
                 sql_name,
             },
             nullable,
+            metadata_key,
         })
     }
 }
@@ -0,0 +1,363 @@
+//! The state backend that operators use, which is chosen when a process starts by the
+//! `STATE_BACKEND` environment variable rather than when the pipeline is compiled. Checkpoints
+//! record the backend that wrote them, since neither backend can restore the state of the other.
+
+#[cfg(feature = "disk-state")]
+use crate::disk::DiskBackend;
+use crate::parquet::ParquetBackend;
+use crate::tables::{CacheBudget, DataTuple};
+use crate::BackingStore;
+use anyhow::{anyhow, bail, Result};
+use arroyo_rpc::grpc::{
+    CheckpointMetadata, OperatorCheckpointMetadata, TableDescriptor, TableType,
+};
+use arroyo_rpc::{CompactionResult, ControlResp};
+use arroyo_types::{CheckpointBarrier, Data, Key, TaskInfo, STATE_BACKEND_ENV};
+use async_trait::async_trait;
+use std::env;
+use std::ops::Range;
+use std::time::SystemTime;
+use tokio::sync::mpsc::Sender;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendType {
+    Parquet,
+    #[cfg(feature = "disk-state")]
+    Disk,
+}
+
+impl BackendType {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "parquet" => Ok(BackendType::Parquet),
+            #[cfg(feature = "disk-state")]
+            "disk" => Ok(BackendType::Disk),
+            #[cfg(not(feature = "disk-state"))]
+            "disk" => bail!("the disk state backend requires building with the disk-state feature"),
+            name => bail!(
+                "unknown state backend '{}'; expected 'parquet' or 'disk'",
+                name
+            ),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BackendType::Parquet => ParquetBackend::name(),
+            #[cfg(feature = "disk-state")]
+            BackendType::Disk => DiskBackend::name(),
+        }
+    }
+
+    /// The backend that wrote a checkpoint
+    pub fn of_checkpoint(metadata: &CheckpointMetadata) -> Result<Self> {
+        if metadata.backend.is_empty() {
+            // checkpoints written before the backend was recorded
+            return Ok(BackendType::Parquet);
+        }
+        Self::from_name(&metadata.backend)
+    }
+}
+
+/// The backend configured for this process. Servers call this when they start, so that a bad
+/// configuration stops them rather than failing each task.
+pub fn backend_type() -> Result<BackendType> {
+    match env::var(STATE_BACKEND_ENV) {
+        Ok(backend) => BackendType::from_name(&backend)
+            .map_err(|e| anyhow!("invalid {}: {}", STATE_BACKEND_ENV, e)),
+        Err(_) => Ok(BackendType::Parquet),
+    }
+}
+
+/// Checks that a checkpoint can be restored by the configured backend, which can't read the
+/// state written by the other one.
+pub fn check_checkpoint_backend(metadata: &CheckpointMetadata) -> Result<BackendType> {
+    let configured = backend_type()?;
+    let written = BackendType::of_checkpoint(metadata)?;
+    if written != configured {
+        bail!(
+            "checkpoint {} of job {} was written by the {} state backend, but this cluster is \
+            configured to use the {} backend; set {} to '{}' to restore it",
+            metadata.epoch,
+            metadata.job_id,
+            written.name(),
+            configured.name(),
+            STATE_BACKEND_ENV,
+            written.name()
+        );
+    }
+    Ok(configured)
+}
+
+pub enum StateBackend {
+    Parquet(ParquetBackend),
+    #[cfg(feature = "disk-state")]
+    Disk(DiskBackend),
+}
+
+/// Calls a method of the backend of an instance
+macro_rules! dispatch {
+    ($self:expr, $backend:ident => $e:expr) => {
+        match $self {
+            StateBackend::Parquet($backend) => $e,
+            #[cfg(feature = "disk-state")]
+            StateBackend::Disk($backend) => $e,
+        }
+    };
+}
+
+/// Calls an associated function of the configured backend, which must return a `Result`
+macro_rules! dispatch_static {
+    ($backend:ident => $e:expr) => {
+        match backend_type()? {
+            BackendType::Parquet => {
+                type $backend = ParquetBackend;
+                $e
+            }
+            #[cfg(feature = "disk-state")]
+            BackendType::Disk => {
+                type $backend = DiskBackend;
+                $e
+            }
+        }
+    };
+}
+
+#[async_trait]
+impl BackingStore for StateBackend {
+    async fn prepare_checkpoint_load(metadata: &CheckpointMetadata) -> Result<()> {
+        check_checkpoint_backend(metadata)?;
+        dispatch_static!(B => B::prepare_checkpoint_load(metadata).await)
+    }
+
+    // both backends store their metadata, and clean up and copy their checkpoints, the same way
+
+    async fn load_latest_checkpoint_metadata(job_id: &str) -> Option<CheckpointMetadata> {
+        ParquetBackend::load_latest_checkpoint_metadata(job_id).await
+    }
+
+    async fn load_checkpoint_metadata(job_id: &str, epoch: u32) -> Option<CheckpointMetadata> {
+        ParquetBackend::load_checkpoint_metadata(job_id, epoch).await
+    }
+
+    async fn load_operator_metadata(
+        job_id: &str,
+        operator_id: &str,
+        epoch: u32,
+    ) -> Option<OperatorCheckpointMetadata> {
+        ParquetBackend::load_operator_metadata(job_id, operator_id, epoch).await
+    }
+
+    async fn new(
+        task_info: &TaskInfo,
+        tables: Vec<TableDescriptor>,
+        control_tx: Sender<ControlResp>,
+    ) -> Result<Self> {
+        Ok(match backend_type()? {
+            BackendType::Parquet => {
+                StateBackend::Parquet(ParquetBackend::new(task_info, tables, control_tx).await?)
+            }
+            #[cfg(feature = "disk-state")]
+            BackendType::Disk => {
+                StateBackend::Disk(DiskBackend::new(task_info, tables, control_tx).await?)
+            }
+        })
+    }
+
+    async fn from_checkpoint(
+        task_info: &TaskInfo,
+        metadata: CheckpointMetadata,
+        tables: Vec<TableDescriptor>,
+        control_tx: Sender<ControlResp>,
+    ) -> Result<Self> {
+        Ok(match check_checkpoint_backend(&metadata)? {
+            BackendType::Parquet => StateBackend::Parquet(
+                ParquetBackend::from_checkpoint(task_info, metadata, tables, control_tx).await?,
+            ),
+            #[cfg(feature = "disk-state")]
+            BackendType::Disk => StateBackend::Disk(
+                DiskBackend::from_checkpoint(task_info, metadata, tables, control_tx).await?,
+            ),
+        })
+    }
+
+    fn name() -> &'static str {
+        // the servers check the configuration when they start
+        backend_type().map_or("unknown", |backend| backend.name())
+    }
+
+    fn task_info(&self) -> &TaskInfo {
+        dispatch!(self, b => b.task_info())
+    }
+
+    async fn write_operator_checkpoint_metadata(metadata: OperatorCheckpointMetadata) {
+        ParquetBackend::write_operator_checkpoint_metadata(metadata).await
+    }
+
+    async fn write_checkpoint_metadata(metadata: CheckpointMetadata) {
+        ParquetBackend::write_checkpoint_metadata(metadata).await
+    }
+
+    async fn cleanup_checkpoint(
+        metadata: CheckpointMetadata,
+        old_min_epoch: u32,
+        new_min_epoch: u32,
+    ) -> Result<()> {
+        ParquetBackend::cleanup_checkpoint(metadata, old_min_epoch, new_min_epoch).await
+    }
+
+    async fn write_savepoint(job_id: &str, epoch: u32, path: &str) -> Result<CheckpointMetadata> {
+        ParquetBackend::write_savepoint(job_id, epoch, path).await
+    }
+
+    async fn restore_savepoint(path: &str, job_id: &str) -> Result<CheckpointMetadata> {
+        ParquetBackend::restore_savepoint(path, job_id).await
+    }
+
+    async fn checkpoint(
+        &mut self,
+        barrier: CheckpointBarrier,
+        watermark: Option<SystemTime>,
+    ) -> Result<u32> {
+        dispatch!(self, b => b.checkpoint(barrier, watermark).await)
+    }
+
+    async fn get_data_tuples<K: Key, V: Data>(&self, table: char) -> Vec<DataTuple<K, V>> {
+        dispatch!(self, b => b.get_data_tuples(table).await)
+    }
+
+    async fn write_data_tuple<K: Key, V: Data>(
+        &mut self,
+        table: char,
+        table_type: TableType,
+        timestamp: SystemTime,
+        key: &mut K,
+        value: &mut V,
+    ) {
+        dispatch!(self, b => b.write_data_tuple(table, table_type, timestamp, key, value).await)
+    }
+
+    async fn delete_time_key<K: Key>(
+        &mut self,
+        table: char,
+        table_type: TableType,
+        timestamp: SystemTime,
+        key: &mut K,
+    ) {
+        dispatch!(self, b => b.delete_time_key(table, table_type, timestamp, key).await)
+    }
+
+    async fn delete_key<K: Key>(&mut self, table: char, key: &mut K) {
+        dispatch!(self, b => b.delete_key(table, key).await)
+    }
+
+    async fn delete_data_value<K: Key, V: Data>(
+        &mut self,
+        table: char,
+        timestamp: SystemTime,
+        key: &mut K,
+        value: &mut V,
+    ) {
+        dispatch!(self, b => b.delete_data_value(table, timestamp, key, value).await)
+    }
+
+    async fn delete_time_range<K: Key>(
+        &mut self,
+        table: char,
+        key: &mut K,
+        range: Range<SystemTime>,
+    ) {
+        dispatch!(self, b => b.delete_time_range(table, key, range).await)
+    }
+
+    async fn write_key_value<K: Key, V: Data>(&mut self, table: char, key: &mut K, value: &mut V) {
+        dispatch!(self, b => b.write_key_value(table, key, value).await)
+    }
+
+    async fn get_global_key_values<K: Key, V: Data>(&self, table: char) -> Vec<(K, V)> {
+        dispatch!(self, b => b.get_global_key_values(table).await)
+    }
+
+    async fn get_key_values<K: Key, V: Data>(&self, table: char) -> Vec<(K, V)> {
+        dispatch!(self, b => b.get_key_values(table).await)
+    }
+
+    async fn write_keyed_value<K: Key, V: Data>(
+        &mut self,
+        table: char,
+        timestamp: SystemTime,
+        key: &mut K,
+        value: &mut V,
+    ) {
+        dispatch!(self, b => b.write_keyed_value(table, timestamp, key, value).await)
+    }
+
+    async fn delete_keyed_value<K: Key>(&mut self, table: char, key: &mut K) {
+        dispatch!(self, b => b.delete_keyed_value(table, key).await)
+    }
+
+    fn cache_budget(&self) -> Option<CacheBudget> {
+        dispatch!(self, b => b.cache_budget())
+    }
+
+    async fn get_keyed_value<K: Key, V: Data>(&self, table: char, key: &mut K) -> Option<V> {
+        dispatch!(self, b => b.get_keyed_value(table, key).await)
+    }
+
+    async fn filter_keyed_values<K: Key, V: Data, F: FnMut(&K, &V) -> bool + Send>(
+        &self,
+        table: char,
+        filter: F,
+    ) -> Vec<(K, V)> {
+        dispatch!(self, b => b.filter_keyed_values(table, filter).await)
+    }
+
+    async fn get_key_time_values<K: Key, V: Data>(
+        &self,
+        table: char,
+        key: &mut K,
+    ) -> Vec<(SystemTime, V)> {
+        dispatch!(self, b => b.get_key_time_values(table, key).await)
+    }
+
+    async fn get_earliest_key_times<K: Key>(&self, table: char) -> Vec<(K, SystemTime)> {
+        dispatch!(self, b => b.get_earliest_key_times(table).await)
+    }
+
+    async fn get_time_key_values<K: Key, V: Data>(
+        &self,
+        table: char,
+        timestamp: SystemTime,
+    ) -> Vec<(K, V)> {
+        dispatch!(self, b => b.get_time_key_values(table, timestamp).await)
+    }
+
+    async fn get_time_key_times(&self, table: char) -> Vec<SystemTime> {
+        dispatch!(self, b => b.get_time_key_times(table).await)
+    }
+
+    async fn delete_time_key_values(&mut self, table: char, timestamp: SystemTime) {
+        dispatch!(self, b => b.delete_time_key_values(table, timestamp).await)
+    }
+
+    async fn get_global_value<K: Key, V: Data>(&self, table: char, key: &mut K) -> Option<V> {
+        dispatch!(self, b => b.get_global_value(table, key).await)
+    }
+
+    async fn compact_operator(
+        parallelism: usize,
+        job_id: String,
+        operator_id: String,
+        epoch: u32,
+    ) -> Result<Option<CompactionResult>> {
+        dispatch_static!(B => B::compact_operator(parallelism, job_id, operator_id, epoch).await)
+    }
+
+    async fn load_compacted(&mut self, compaction: CompactionResult) {
+        dispatch!(self, b => b.load_compacted(compaction).await)
+    }
+
+    async fn insert_committing_data(&mut self, epoch: u32, table: char, committing_data: Vec<u8>) {
+        dispatch!(self, b => b.insert_committing_data(epoch, table, committing_data).await)
+    }
+}
@@ -330,6 +330,10 @@ impl CheckpointState {
             backend_data::BackendData::ParquetStore(data) => {
                 Some(((data.epoch, data.file.clone()), backend_data))
             }
+            backend_data::BackendData::DiskStore(data) => Some((
+                (data.epoch, format!("disk-{:0>3}", data.subtask_index)),
+                backend_data,
+            )),
         }
     }
 
@@ -354,6 +358,7 @@ impl CheckpointState {
             finish_time: to_micros(finish_time),
             min_epoch: self.min_epoch,
             operator_ids: self.completed_operators.iter().cloned().collect(),
+            backend: StateBackend::name().to_string(),
         })
         .await;
         Ok(())
@@ -0,0 +1,1159 @@
+//! A state backend that keeps the state of each subtask in an embedded RocksDB database on local
+//! disk, so that operators can hold more state than fits in memory. The caches of keyed tables
+//! only hold part of a table, and read evicted entries back from the database.
+//!
+//! Checkpoints are incremental: the immutable table files of a database are uploaded once, to a
+//! location shared by all epochs, so each checkpoint only uploads the files that were written
+//! since the previous one, along with the small files that describe the database.
+
+use crate::parquet::{checkpoints_path, get_storage_provider, operator_path, ParquetBackend};
+use crate::tables::{CacheBudget, DataTuple, EvictionPolicy};
+use crate::{check_restored_tables, hash_key, BackingStore, DataOperation, BINCODE_CONFIG};
+use anyhow::{anyhow, bail, Context, Result};
+use arroyo_rpc::grpc::backend_data::BackendData;
+use arroyo_rpc::grpc::{
+    CheckpointMetadata, DiskStoreData, DiskStoreFile, OperatorCheckpointMetadata,
+    SubtaskCheckpointMetadata, TableDeleteBehavior, TableDescriptor, TableType,
+};
+use arroyo_rpc::{grpc, CheckpointCompleted, CompactionResult, ControlResp};
+use arroyo_storage::StorageProvider;
+use arroyo_types::{
+    from_nanos, to_micros, to_nanos, CheckpointBarrier, Data, Key, TaskInfo,
+    DISK_STATE_CACHE_BYTES_ENV, DISK_STATE_CACHE_EVICTION_ENV, DISK_STATE_DIR_ENV,
+};
+use bincode::Decode;
+use rocksdb::checkpoint::Checkpoint;
+use rocksdb::{Direction, IteratorMode, Options, ReadOptions, WriteBatch, WriteOptions, DB};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::sync::oneshot;
+use tracing::{debug, error, info, warn};
+
+const DEFAULT_STATE_DIR: &str = "/tmp/arroyo/state";
+const DEFAULT_CACHE_BYTES: usize = 64 * 1024 * 1024;
+const MAX_UPLOAD_ATTEMPTS: u32 = 5;
+
+// Every key starts with the table, followed by a byte that determines how the rest of the key is
+// laid out. Integers are big-endian so that keys sort by them.
+
+// Tables accessed through `KeyedState`: [key hash][key length][key] -> value
+const KEYED: u8 = b'k';
+// TimeKeyMaps: [timestamp][key hash][key length][key] -> value
+const TIME_KEYED: u8 = b't';
+// KeyTimeMultiMaps: [key hash][key length][key][timestamp][sequence number] -> value
+const MULTI_MAP: u8 = b'm';
+// Global tables: [key hash][key length][key] -> value
+const GLOBAL: u8 = b'g';
+// The last sequence number given to a KeyTimeMultiMap value. Tables are named by letters, so this
+// can't collide with their keys.
+const SEQUENCE_KEY: &[u8] = b"\0sequence";
+
+pub struct DiskBackend {
+    epoch: u32,
+    task_info: TaskInfo,
+    tables: HashMap<char, TableDescriptor>,
+    db: Arc<DB>,
+    dir: PathBuf,
+    sequence: u64,
+    cache_budget: CacheBudget,
+    uploader: Sender<DiskQueueItem>,
+    finish_rx: Option<oneshot::Receiver<()>>,
+}
+
+fn state_dir() -> PathBuf {
+    PathBuf::from(env::var(DISK_STATE_DIR_ENV).unwrap_or_else(|_| DEFAULT_STATE_DIR.to_string()))
+}
+
+/// The budget for the cache of each table of an operator with `tables` tables, which share the
+/// operator's budget evenly
+fn cache_budget(tables: usize) -> Result<CacheBudget> {
+    let bytes: usize = match env::var(DISK_STATE_CACHE_BYTES_ENV) {
+        Ok(bytes) => bytes.parse().map_err(|_| {
+            anyhow!(
+                "{} must be a number of bytes, not '{}'",
+                DISK_STATE_CACHE_BYTES_ENV,
+                bytes
+            )
+        })?,
+        Err(_) => DEFAULT_CACHE_BYTES,
+    };
+    let eviction = match env::var(DISK_STATE_CACHE_EVICTION_ENV).as_deref() {
+        Ok("lru") | Err(_) => EvictionPolicy::Lru,
+        Ok("fifo") => EvictionPolicy::Fifo,
+        Ok(eviction) => bail!(
+            "{} must be 'lru' or 'fifo', not '{}'",
+            DISK_STATE_CACHE_EVICTION_ENV,
+            eviction
+        ),
+    };
+    Ok(CacheBudget {
+        bytes: bytes / tables.max(1),
+        eviction,
+    })
+}
+
+fn subtask_dir(task_info: &TaskInfo) -> PathBuf {
+    state_dir().join(&task_info.job_id).join(format!(
+        "{}-{:0>3}",
+        task_info.operator_id, task_info.task_index
+    ))
+}
+
+fn shared_file_path(task_info: &TaskInfo, instance: u64, name: &str) -> String {
+    format!(
+        "{}shared/operator-{}/{:016x}-{}",
+        checkpoints_path(&task_info.job_id),
+        task_info.operator_id,
+        instance,
+        name
+    )
+}
+
+fn snapshot_path(task_info: &TaskInfo, epoch: u32) -> String {
+    format!(
+        "{}/disk-{:0>3}",
+        operator_path(&task_info.job_id, epoch, &task_info.operator_id),
+        task_info.task_index
+    )
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap()
+}
+
+fn is_table_file(name: &str) -> bool {
+    name.ends_with(".sst")
+}
+
+fn remove_dir(dir: &Path) -> Result<()> {
+    if dir.exists() {
+        std::fs::remove_dir_all(dir)
+            .with_context(|| format!("failed to remove {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+fn open_db(dir: &Path) -> Result<DB> {
+    let mut options = Options::default();
+    options.create_if_missing(true);
+    DB::open(&options, dir)
+        .with_context(|| format!("failed to open state database {}", dir.display()))
+}
+
+/// Operators can't handle errors from reads and writes of their state, so a failure of the
+/// database fails the task
+fn check_db<T>(result: Result<T, rocksdb::Error>) -> T {
+    result.unwrap_or_else(|e| panic!("state database failed: {}", e))
+}
+
+fn write_options() -> WriteOptions {
+    // checkpoints are what make the state durable, so there's no need for a write-ahead log
+    let mut options = WriteOptions::default();
+    options.disable_wal(true);
+    options
+}
+
+fn read_sequence(db: &DB) -> Result<u64> {
+    Ok(db
+        .get(SEQUENCE_KEY)?
+        .map_or(0, |bytes| u64::from_be_bytes(bytes[..].try_into().unwrap())))
+}
+
+fn encode_key<K: Key>(key: &K) -> (u64, Vec<u8>) {
+    (
+        hash_key(key),
+        bincode::encode_to_vec(key, BINCODE_CONFIG).unwrap(),
+    )
+}
+
+fn decode<T: Decode>(bytes: &[u8]) -> T {
+    bincode::decode_from_slice(bytes, BINCODE_CONFIG).unwrap().0
+}
+
+fn encode_time(time: SystemTime) -> [u8; 8] {
+    (to_nanos(time) as u64).to_be_bytes()
+}
+
+fn decode_time(bytes: &[u8]) -> SystemTime {
+    from_nanos(u64::from_be_bytes(bytes.try_into().unwrap()) as u128)
+}
+
+fn key_prefix(table: char, layout: u8, key_hash: u64, key: &[u8]) -> Vec<u8> {
+    let mut db_key = Vec::with_capacity(14 + key.len() + 16);
+    db_key.push(table as u8);
+    db_key.push(layout);
+    db_key.extend_from_slice(&key_hash.to_be_bytes());
+    db_key.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    db_key.extend_from_slice(key);
+    db_key
+}
+
+fn time_keyed_key(table: char, timestamp: SystemTime, key_hash: u64, key: &[u8]) -> Vec<u8> {
+    let mut db_key = vec![table as u8, TIME_KEYED];
+    db_key.extend_from_slice(&encode_time(timestamp));
+    db_key.extend_from_slice(&key_prefix(table, TIME_KEYED, key_hash, key)[2..]);
+    db_key
+}
+
+/// The first key after all of the keys that start with `prefix`
+fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return end;
+        }
+    }
+    unreachable!("keys start with the name of their table")
+}
+
+struct EntryKey<'a> {
+    layout: u8,
+    key_hash: u64,
+    key: &'a [u8],
+    timestamp: SystemTime,
+}
+
+fn parse_key(db_key: &[u8]) -> EntryKey<'_> {
+    let layout = db_key[1];
+    let (mut timestamp, rest) = if layout == TIME_KEYED {
+        (decode_time(&db_key[2..10]), &db_key[10..])
+    } else {
+        (SystemTime::UNIX_EPOCH, &db_key[2..])
+    };
+    let key_hash = u64::from_be_bytes(rest[0..8].try_into().unwrap());
+    let length = u32::from_be_bytes(rest[8..12].try_into().unwrap()) as usize;
+    let key = &rest[12..12 + length];
+    if layout == MULTI_MAP {
+        timestamp = decode_time(&rest[12 + length..20 + length]);
+    }
+    EntryKey {
+        layout,
+        key_hash,
+        key,
+        timestamp,
+    }
+}
+
+/// Downloads the files of a snapshot into `dir`, taking the table files from `cache_dir` if they
+/// were already uploaded or downloaded on this machine.
+async fn download_snapshot(
+    storage: &StorageProvider,
+    snapshot: &DiskStoreData,
+    dir: &Path,
+    cache_dir: &Path,
+) -> Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    tokio::fs::create_dir_all(cache_dir).await?;
+    for file in &snapshot.files {
+        let target = dir.join(&file.name);
+        if is_table_file(&file.name) {
+            let cached = cache_dir.join(file_name(&file.path));
+            if !cached.exists() {
+                let data = storage.get(file.path.as_str()).await?;
+                // so that an interrupted download is never mistaken for a cached file
+                let partial = cached.with_extension("partial");
+                tokio::fs::write(&partial, &data).await?;
+                tokio::fs::rename(&partial, &cached).await?;
+            }
+            // table files are immutable, so the database and the cache can share them
+            tokio::fs::hard_link(&cached, &target).await?;
+        } else {
+            let data = storage.get(file.path.as_str()).await?;
+            tokio::fs::write(&target, &data).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies the entries of the database in `source` whose keys are in `key_range` into `target`,
+/// except for those of global tables, which are restored separately.
+fn copy_key_range(source: &Path, target: &DB, key_range: &RangeInclusive<u64>) -> Result<()> {
+    let source = DB::open_for_read_only(&Options::default(), source, false)
+        .with_context(|| format!("failed to open snapshot {}", source.display()))?;
+    let mut batch = WriteBatch::default();
+    for entry in source.iterator(IteratorMode::Start) {
+        let (db_key, value) = entry?;
+        if db_key.as_ref() == SEQUENCE_KEY {
+            // keep the sequence numbers unique for the keys copied from every snapshot
+            if read_sequence(&source)? > read_sequence(target)? {
+                target.put_opt(SEQUENCE_KEY, value, &write_options())?;
+            }
+            continue;
+        }
+        let entry_key = parse_key(&db_key);
+        if entry_key.layout == GLOBAL || !key_range.contains(&entry_key.key_hash) {
+            continue;
+        }
+        batch.put(db_key, value);
+        if batch.len() >= 10_000 {
+            target.write_opt(std::mem::take(&mut batch), &write_options())?;
+        }
+    }
+    target.write_opt(batch, &write_options())?;
+    Ok(())
+}
+
+impl DiskBackend {
+    async fn start(
+        task_info: &TaskInfo,
+        tables: HashMap<char, TableDescriptor>,
+        control_tx: Sender<ControlResp>,
+        db: DB,
+        epoch: u32,
+        uploaded: HashMap<String, DiskStoreFile>,
+    ) -> Result<Self> {
+        let dir = subtask_dir(task_info);
+        let cache_budget = cache_budget(tables.len())?;
+        let sequence = read_sequence(&db)?;
+        let (tx, rx) = mpsc::channel(1024);
+        let (finish_tx, finish_rx) = oneshot::channel();
+
+        (DiskUploader {
+            queue: rx,
+            storage: get_storage_provider().await?,
+            control_tx,
+            finish_tx: Some(finish_tx),
+            task_info: task_info.clone(),
+            tables: tables.values().cloned().collect(),
+            instance: rand::random(),
+            uploaded,
+            cache_dir: dir.join("files"),
+            commit_data: HashMap::new(),
+            commit_epoch: None,
+        })
+        .start();
+
+        Ok(Self {
+            cache_budget,
+            epoch,
+            task_info: task_info.clone(),
+            tables,
+            sequence,
+            db: Arc::new(db),
+            dir,
+            uploader: tx,
+            finish_rx: Some(finish_rx),
+        })
+    }
+
+    fn put(&self, db_key: Vec<u8>, value: Vec<u8>) {
+        check_db(self.db.put_opt(db_key, value, &write_options()));
+    }
+
+    fn delete(&self, db_key: Vec<u8>) {
+        check_db(self.db.delete_opt(db_key, &write_options()));
+    }
+
+    fn delete_range(&self, from: Vec<u8>, to: Vec<u8>) {
+        let mut batch = WriteBatch::default();
+        batch.delete_range(from, to);
+        check_db(self.db.write_opt(batch, &write_options()));
+    }
+
+    /// Calls `f` with every entry whose key starts with `prefix`
+    fn scan(&self, prefix: &[u8], mut f: impl FnMut(&[u8], &[u8])) {
+        let mut options = ReadOptions::default();
+        options.set_iterate_upper_bound(prefix_end(prefix));
+        for entry in self
+            .db
+            .iterator_opt(IteratorMode::From(prefix, Direction::Forward), options)
+        {
+            let (db_key, value) = check_db(entry);
+            f(&db_key, &value);
+        }
+    }
+
+    fn scan_key_values<K: Key, V: Data>(&self, table: char, layout: u8) -> Vec<(K, V)> {
+        let mut result = vec![];
+        self.scan(&[table as u8, layout], |db_key, value| {
+            result.push((decode(parse_key(db_key).key), decode(value)));
+        });
+        result
+    }
+
+    /// Drops the entries of TimeKeyMaps that can no longer be read, as the parquet backend drops
+    /// their files
+    fn expire_before_watermark(&self, watermark: SystemTime) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        for (table, descriptor) in &self.tables {
+            if descriptor.table_type() != TableType::TimeKeyMap
+                || descriptor.delete_behavior() != TableDeleteBehavior::NoReadsBeforeWatermark
+            {
+                continue;
+            }
+            let cutoff = watermark
+                .checked_sub(Duration::from_micros(descriptor.retention_micros))
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            let start = vec![*table as u8, TIME_KEYED];
+            let mut end = start.clone();
+            end.extend_from_slice(&encode_time(cutoff));
+            batch.delete_range(start, end);
+        }
+        self.db.write_opt(batch, &write_options())?;
+        Ok(())
+    }
+
+    fn global_entries(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut entries = vec![];
+        for (table, descriptor) in &self.tables {
+            if descriptor.table_type() == TableType::Global {
+                self.scan(&[*table as u8, GLOBAL], |db_key, value| {
+                    entries.push((db_key.to_vec(), value.to_vec()));
+                });
+            }
+        }
+        entries
+    }
+}
+
+#[async_trait::async_trait]
+impl BackingStore for DiskBackend {
+    fn name() -> &'static str {
+        "disk"
+    }
+
+    fn task_info(&self) -> &TaskInfo {
+        &self.task_info
+    }
+
+    // checkpoints share their metadata, and the way their files are cleaned up and copied into
+    // savepoints, with the parquet backend
+
+    async fn load_latest_checkpoint_metadata(job_id: &str) -> Option<CheckpointMetadata> {
+        ParquetBackend::load_latest_checkpoint_metadata(job_id).await
+    }
+
+    async fn load_checkpoint_metadata(job_id: &str, epoch: u32) -> Option<CheckpointMetadata> {
+        ParquetBackend::load_checkpoint_metadata(job_id, epoch).await
+    }
+
+    async fn load_operator_metadata(
+        job_id: &str,
+        operator_id: &str,
+        epoch: u32,
+    ) -> Option<OperatorCheckpointMetadata> {
+        ParquetBackend::load_operator_metadata(job_id, operator_id, epoch).await
+    }
+
+    async fn write_operator_checkpoint_metadata(metadata: OperatorCheckpointMetadata) {
+        ParquetBackend::write_operator_checkpoint_metadata(metadata).await
+    }
+
+    async fn write_checkpoint_metadata(metadata: CheckpointMetadata) {
+        ParquetBackend::write_checkpoint_metadata(metadata).await
+    }
+
+    async fn cleanup_checkpoint(
+        metadata: CheckpointMetadata,
+        old_min_epoch: u32,
+        new_min_epoch: u32,
+    ) -> Result<()> {
+        ParquetBackend::cleanup_checkpoint(metadata, old_min_epoch, new_min_epoch).await
+    }
+
+    async fn write_savepoint(job_id: &str, epoch: u32, path: &str) -> Result<CheckpointMetadata> {
+        ParquetBackend::write_savepoint(job_id, epoch, path).await
+    }
+
+    async fn restore_savepoint(path: &str, job_id: &str) -> Result<CheckpointMetadata> {
+        ParquetBackend::restore_savepoint(path, job_id).await
+    }
+
+    async fn new(
+        task_info: &TaskInfo,
+        tables: Vec<TableDescriptor>,
+        control_tx: Sender<ControlResp>,
+    ) -> Result<Self> {
+        let db_dir = subtask_dir(task_info).join("db");
+        remove_dir(&db_dir)?;
+        let db = open_db(&db_dir)?;
+        let tables = tables
+            .into_iter()
+            .map(|table| (table.name.chars().next().unwrap(), table))
+            .collect();
+        Self::start(task_info, tables, control_tx, db, 1, HashMap::new()).await
+    }
+
+    async fn from_checkpoint(
+        task_info: &TaskInfo,
+        metadata: CheckpointMetadata,
+        tables: Vec<TableDescriptor>,
+        control_tx: Sender<ControlResp>,
+    ) -> Result<Self> {
+        let operator_metadata =
+            Self::load_operator_metadata(&task_info.job_id, &task_info.operator_id, metadata.epoch)
+                .await
+                .ok_or_else(|| {
+                    anyhow!(
+                        "missing metadata for operator {}, epoch {}",
+                        task_info.operator_id,
+                        metadata.epoch
+                    )
+                })?;
+        let tables: HashMap<char, TableDescriptor> = tables
+            .into_iter()
+            .map(|table| (table.name.chars().next().unwrap(), table))
+            .collect();
+        check_restored_tables(task_info, &operator_metadata.tables, &tables);
+        let snapshots: Vec<DiskStoreData> = operator_metadata
+            .backend_data
+            .into_iter()
+            .map(|backend_data| match backend_data.backend_data {
+                Some(BackendData::DiskStore(snapshot)) => Ok(snapshot),
+                _ => Err(anyhow!(
+                    "checkpoint {} of operator {} has no disk data",
+                    metadata.epoch,
+                    task_info.operator_id
+                )),
+            })
+            .collect::<Result<_>>()?;
+
+        let storage = get_storage_provider().await?;
+        let dir = subtask_dir(task_info);
+        let db_dir = dir.join("db");
+        let cache_dir = dir.join("files");
+        remove_dir(&db_dir)?;
+
+        let key_range = &task_info.key_range;
+        let mut uploaded = HashMap::new();
+        let db = match snapshots.iter().find(|snapshot| {
+            snapshot.min_routing_key == *key_range.start()
+                && snapshot.max_routing_key == *key_range.end()
+        }) {
+            Some(snapshot) => {
+                // the subtask has the same keys as when the checkpoint was taken, so it can use
+                // the snapshot's database as is, without uploading its table files again
+                download_snapshot(&storage, snapshot, &db_dir, &cache_dir)
+                    .await
+                    .context("failed to download state snapshot")?;
+                uploaded.extend(
+                    snapshot
+                        .files
+                        .iter()
+                        .filter(|file| is_table_file(&file.name))
+                        .map(|file| (file.name.clone(), file.clone())),
+                );
+                open_db(&db_dir)?
+            }
+            None => {
+                info!(
+                    message = "Restoring disk state for new key range",
+                    operator_id = task_info.operator_id,
+                    task_index = task_info.task_index,
+                );
+                let db = open_db(&db_dir)?;
+                for snapshot in snapshots.iter().filter(|snapshot| {
+                    snapshot.min_routing_key <= *key_range.end()
+                        && *key_range.start() <= snapshot.max_routing_key
+                }) {
+                    let restore_dir = dir.join(format!("restore-{:0>3}", snapshot.subtask_index));
+                    remove_dir(&restore_dir)?;
+                    download_snapshot(&storage, snapshot, &restore_dir, &cache_dir)
+                        .await
+                        .context("failed to download state snapshot")?;
+                    copy_key_range(&restore_dir, &db, key_range)?;
+                    remove_dir(&restore_dir)?;
+                }
+                db
+            }
+        };
+
+        // every subtask restores all of the global state, like with the parquet backend
+        for snapshot in &snapshots {
+            if snapshot.global_file.is_empty() {
+                continue;
+            }
+            let data = storage
+                .get(snapshot.global_file.as_str())
+                .await
+                .with_context(|| {
+                    format!("unable to find file {} in checkpoint", snapshot.global_file)
+                })?;
+            let entries: Vec<(Vec<u8>, Vec<u8>)> = decode(&data);
+            let mut batch = WriteBatch::default();
+            for (db_key, value) in entries {
+                batch.put(db_key, value);
+            }
+            db.write_opt(batch, &write_options())?;
+        }
+
+        Self::start(
+            task_info,
+            tables,
+            control_tx,
+            db,
+            metadata.epoch + 1,
+            uploaded,
+        )
+        .await
+    }
+
+    async fn prepare_checkpoint_load(_metadata: &CheckpointMetadata) -> Result<()> {
+        Ok(())
+    }
+
+    async fn checkpoint(
+        &mut self,
+        barrier: CheckpointBarrier,
+        watermark: Option<SystemTime>,
+    ) -> Result<u32> {
+        assert_eq!(barrier.epoch, self.epoch);
+        let snapshot = if self.tables.is_empty() {
+            None
+        } else {
+            if let Some(watermark) = watermark {
+                self.expire_before_watermark(watermark)?;
+            }
+
+            // the snapshot hard-links the table files of the database, so it's cheap to take
+            // while processing is paused, and is uploaded in the background. Flushing still
+            // writes the latest changes to disk, so it runs off of the async threads.
+            let snapshots_dir = self.dir.join("snapshots");
+            let snapshot_dir = snapshots_dir.join(format!("epoch-{}", self.epoch));
+            let db = self.db.clone();
+            let sequence = self.sequence;
+            let dir = snapshot_dir.clone();
+            tokio::task::spawn_blocking(move || -> Result<()> {
+                db.put_opt(SEQUENCE_KEY, sequence.to_be_bytes(), &write_options())?;
+                db.flush()?;
+                remove_dir(&dir)?;
+                std::fs::create_dir_all(&snapshots_dir)?;
+                Checkpoint::new(&*db)?.create_checkpoint(&dir)?;
+                Ok(())
+            })
+            .await?
+            .with_context(|| format!("failed to snapshot state for epoch {}", self.epoch))?;
+            Some((snapshot_dir, self.global_entries()))
+        };
+
+        self.uploader
+            .send(DiskQueueItem::Checkpoint(DiskCheckpoint {
+                epoch: self.epoch,
+                time: barrier.timestamp,
+                watermark,
+                then_stop: barrier.then_stop,
+                snapshot,
+            }))
+            .await
+            // the uploader only stops after failing the task
+            .map_err(|_| anyhow!("disk state uploader has failed"))?;
+        if barrier.then_stop {
+            match self.finish_rx.take().unwrap().await {
+                Ok(_) => info!("finished stopping checkpoint"),
+                Err(err) => warn!("error waiting for stopping checkpoint {:?}", err),
+            }
+        }
+        self.epoch += 1;
+        Ok(self.epoch - 1)
+    }
+
+    async fn get_data_tuples<K: Key, V: Data>(&self, table: char) -> Vec<DataTuple<K, V>> {
+        let table_type = self.tables.get(&table).unwrap().table_type();
+        let layout = match table_type {
+            TableType::Global => GLOBAL,
+            TableType::TimeKeyMap => TIME_KEYED,
+            TableType::KeyTimeMultiMap => MULTI_MAP,
+        };
+        let mut result = vec![];
+        self.scan(&[table as u8, layout], |db_key, value| {
+            let entry_key = parse_key(db_key);
+            if table_type != TableType::Global
+                && !self.task_info.key_range.contains(&entry_key.key_hash)
+            {
+                return;
+            }
+            result.push(DataTuple {
+                timestamp: entry_key.timestamp,
+                key: decode(entry_key.key),
+                value: Some(decode(value)),
+                operation: DataOperation::Insert,
+            });
+        });
+        result
+    }
+
+    async fn write_data_tuple<K: Key, V: Data>(
+        &mut self,
+        table: char,
+        table_type: TableType,
+        timestamp: SystemTime,
+        key: &mut K,
+        value: &mut V,
+    ) {
+        let (key_hash, key_bytes) = encode_key(key);
+        let db_key = match table_type {
+            TableType::Global => key_prefix(table, GLOBAL, key_hash, &key_bytes),
+            TableType::TimeKeyMap => time_keyed_key(table, timestamp, key_hash, &key_bytes),
+            TableType::KeyTimeMultiMap => {
+                self.sequence += 1;
+                let mut db_key = key_prefix(table, MULTI_MAP, key_hash, &key_bytes);
+                db_key.extend_from_slice(&encode_time(timestamp));
+                db_key.extend_from_slice(&self.sequence.to_be_bytes());
+                db_key
+            }
+        };
+        self.put(
+            db_key,
+            bincode::encode_to_vec(&*value, BINCODE_CONFIG).unwrap(),
+        );
+    }
+
+    async fn delete_time_key<K: Key>(
+        &mut self,
+        table: char,
+        _table_type: TableType,
+        timestamp: SystemTime,
+        key: &mut K,
+    ) {
+        let (key_hash, key_bytes) = encode_key(key);
+        self.delete(time_keyed_key(table, timestamp, key_hash, &key_bytes));
+    }
+
+    async fn delete_key<K: Key>(&mut self, table: char, key: &mut K) {
+        let (key_hash, key_bytes) = encode_key(key);
+        let prefix = key_prefix(table, MULTI_MAP, key_hash, &key_bytes);
+        let end = prefix_end(&prefix);
+        self.delete_range(prefix, end);
+    }
+
+    async fn delete_data_value<K: Key, V: Data>(
+        &mut self,
+        table: char,
+        timestamp: SystemTime,
+        key: &mut K,
+        value: &mut V,
+    ) {
+        let (key_hash, key_bytes) = encode_key(key);
+        let value_bytes = bincode::encode_to_vec(&*value, BINCODE_CONFIG).unwrap();
+        let mut prefix = key_prefix(table, MULTI_MAP, key_hash, &key_bytes);
+        prefix.extend_from_slice(&encode_time(timestamp));
+        let mut batch = WriteBatch::default();
+        self.scan(&prefix, |db_key, stored_value| {
+            if stored_value == value_bytes {
+                batch.delete(db_key);
+            }
+        });
+        check_db(self.db.write_opt(batch, &write_options()));
+    }
+
+    async fn delete_time_range<K: Key>(
+        &mut self,
+        table: char,
+        key: &mut K,
+        range: Range<SystemTime>,
+    ) {
+        let (key_hash, key_bytes) = encode_key(key);
+        let prefix = key_prefix(table, MULTI_MAP, key_hash, &key_bytes);
+        let mut start = prefix.clone();
+        start.extend_from_slice(&encode_time(range.start));
+        let mut end = prefix;
+        end.extend_from_slice(&encode_time(range.end));
+        self.delete_range(start, end);
+    }
+
+    async fn write_key_value<K: Key, V: Data>(&mut self, table: char, key: &mut K, value: &mut V) {
+        self.write_data_tuple(table, TableType::Global, SystemTime::UNIX_EPOCH, key, value)
+            .await
+    }
+
+    async fn get_global_key_values<K: Key, V: Data>(&self, table: char) -> Vec<(K, V)> {
+        self.scan_key_values(table, GLOBAL)
+    }
+
+    async fn get_key_values<K: Key, V: Data>(&self, table: char) -> Vec<(K, V)> {
+        self.scan_key_values(table, KEYED)
+    }
+
+    async fn write_keyed_value<K: Key, V: Data>(
+        &mut self,
+        table: char,
+        _timestamp: SystemTime,
+        key: &mut K,
+        value: &mut V,
+    ) {
+        let (key_hash, key_bytes) = encode_key(key);
+        self.put(
+            key_prefix(table, KEYED, key_hash, &key_bytes),
+            bincode::encode_to_vec(&*value, BINCODE_CONFIG).unwrap(),
+        );
+    }
+
+    async fn delete_keyed_value<K: Key>(&mut self, table: char, key: &mut K) {
+        let (key_hash, key_bytes) = encode_key(key);
+        self.delete(key_prefix(table, KEYED, key_hash, &key_bytes));
+    }
+
+    fn cache_budget(&self) -> Option<CacheBudget> {
+        Some(self.cache_budget)
+    }
+
+    async fn get_keyed_value<K: Key, V: Data>(&self, table: char, key: &mut K) -> Option<V> {
+        let (key_hash, key_bytes) = encode_key(key);
+        check_db(self.db.get(key_prefix(table, KEYED, key_hash, &key_bytes)))
+            .map(|value| decode(&value))
+    }
+
+    async fn filter_keyed_values<K: Key, V: Data, F: FnMut(&K, &V) -> bool + Send>(
+        &self,
+        table: char,
+        mut filter: F,
+    ) -> Vec<(K, V)> {
+        let mut result = vec![];
+        self.scan(&[table as u8, KEYED], |db_key, value| {
+            let key = decode(parse_key(db_key).key);
+            let value = decode(value);
+            if filter(&key, &value) {
+                result.push((key, value));
+            }
+        });
+        result
+    }
+
+    async fn get_key_time_values<K: Key, V: Data>(
+        &self,
+        table: char,
+        key: &mut K,
+    ) -> Vec<(SystemTime, V)> {
+        let (key_hash, key_bytes) = encode_key(key);
+        let mut result = vec![];
+        self.scan(
+            &key_prefix(table, MULTI_MAP, key_hash, &key_bytes),
+            |db_key, value| {
+                result.push((parse_key(db_key).timestamp, decode(value)));
+            },
+        );
+        result
+    }
+
+    async fn get_earliest_key_times<K: Key>(&self, table: char) -> Vec<(K, SystemTime)> {
+        let prefix = [table as u8, MULTI_MAP];
+        let mut result = vec![];
+        let mut iterator = self.db.raw_iterator();
+        iterator.seek(prefix);
+        while let Some(db_key) = iterator.key() {
+            if !db_key.starts_with(&prefix) {
+                break;
+            }
+            // the first entry of a key has its earliest timestamp, so skip over the rest
+            let entry_key = parse_key(db_key);
+            result.push((decode(entry_key.key), entry_key.timestamp));
+            let next = prefix_end(&key_prefix(
+                table,
+                MULTI_MAP,
+                entry_key.key_hash,
+                entry_key.key,
+            ));
+            iterator.seek(next);
+        }
+        check_db(iterator.status());
+        result
+    }
+
+    async fn get_time_key_values<K: Key, V: Data>(
+        &self,
+        table: char,
+        timestamp: SystemTime,
+    ) -> Vec<(K, V)> {
+        let mut prefix = vec![table as u8, TIME_KEYED];
+        prefix.extend_from_slice(&encode_time(timestamp));
+        let mut result = vec![];
+        self.scan(&prefix, |db_key, value| {
+            result.push((decode(parse_key(db_key).key), decode(value)));
+        });
+        result
+    }
+
+    async fn get_time_key_times(&self, table: char) -> Vec<SystemTime> {
+        let prefix = [table as u8, TIME_KEYED];
+        let mut result = vec![];
+        let mut iterator = self.db.raw_iterator();
+        iterator.seek(prefix);
+        while let Some(db_key) = iterator.key() {
+            if !db_key.starts_with(&prefix) {
+                break;
+            }
+            // skip over the rest of the entries at the same time
+            let time_prefix = db_key[..10].to_vec();
+            result.push(decode_time(&time_prefix[2..]));
+            iterator.seek(prefix_end(&time_prefix));
+        }
+        check_db(iterator.status());
+        result
+    }
+
+    async fn delete_time_key_values(&mut self, table: char, timestamp: SystemTime) {
+        let mut prefix = vec![table as u8, TIME_KEYED];
+        prefix.extend_from_slice(&encode_time(timestamp));
+        let end = prefix_end(&prefix);
+        self.delete_range(prefix, end);
+    }
+
+    async fn get_global_value<K: Key, V: Data>(&self, table: char, key: &mut K) -> Option<V> {
+        let (key_hash, key_bytes) = encode_key(key);
+        check_db(self.db.get(key_prefix(table, GLOBAL, key_hash, &key_bytes)))
+            .map(|value| decode(&value))
+    }
+
+    async fn compact_operator(
+        _parallelism: usize,
+        _job_id: String,
+        _operator_id: String,
+        _epoch: u32,
+    ) -> Result<Option<CompactionResult>> {
+        // the databases compact their own files
+        Ok(None)
+    }
+
+    async fn load_compacted(&mut self, _compaction: CompactionResult) {}
+
+    async fn insert_committing_data(&mut self, epoch: u32, table: char, committing_data: Vec<u8>) {
+        self.uploader
+            .send(DiskQueueItem::CommitData {
+                epoch,
+                table,
+                data: committing_data,
+            })
+            .await
+            .expect("disk state uploader has failed");
+    }
+}
+
+#[derive(Debug)]
+enum DiskQueueItem {
+    Checkpoint(DiskCheckpoint),
+    CommitData {
+        epoch: u32,
+        table: char,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug)]
+struct DiskCheckpoint {
+    epoch: u32,
+    time: SystemTime,
+    watermark: Option<SystemTime>,
+    then_stop: bool,
+    // the directory of the database snapshot and the entries of the global tables, unless the
+    // operator has no tables
+    snapshot: Option<(PathBuf, Vec<(Vec<u8>, Vec<u8>)>)>,
+}
+
+/// Uploads the snapshots of a subtask's database in the order they were taken, and reports the
+/// finished checkpoints to the controller.
+struct DiskUploader {
+    queue: Receiver<DiskQueueItem>,
+    storage: StorageProvider,
+    control_tx: Sender<ControlResp>,
+    finish_tx: Option<oneshot::Sender<()>>,
+    task_info: TaskInfo,
+    tables: Vec<TableDescriptor>,
+    // distinguishes the table files of this database from those of earlier runs of the subtask,
+    // which may have had the same names
+    instance: u64,
+    // the table files that are already in checkpoint storage, by their name in the database
+    uploaded: HashMap<String, DiskStoreFile>,
+    cache_dir: PathBuf,
+    commit_data: HashMap<char, Vec<u8>>,
+    commit_epoch: Option<u32>,
+}
+
+impl DiskUploader {
+    fn start(mut self) {
+        tokio::spawn(async move {
+            while let Some(item) = self.queue.recv().await {
+                match item {
+                    DiskQueueItem::CommitData { epoch, table, data } => {
+                        self.commit_epoch = Some(epoch);
+                        self.commit_data.insert(table, data);
+                    }
+                    DiskQueueItem::Checkpoint(checkpoint) => {
+                        let epoch = checkpoint.epoch;
+                        let then_stop = checkpoint.then_stop;
+                        if let Err(e) = self.upload(checkpoint).await {
+                            // the checkpoint can't complete, so fail the task to have the job
+                            // recover from the previous one
+                            error!(
+                                message = "failed to upload disk state",
+                                operator_id = self.task_info.operator_id,
+                                task_index = self.task_info.task_index,
+                                epoch,
+                                error = format!("{:?}", e),
+                            );
+                            self.control_tx
+                                .send(ControlResp::TaskFailed {
+                                    operator_id: self.task_info.operator_id.clone(),
+                                    task_index: self.task_info.task_index,
+                                    error: format!(
+                                        "failed to upload state for checkpoint {}: {:?}",
+                                        epoch, e
+                                    ),
+                                })
+                                .await
+                                .ok();
+                            return;
+                        }
+                        if then_stop {
+                            // the subtask may have stopped waiting for the checkpoint
+                            self.finish_tx.take().unwrap().send(()).ok();
+                            return;
+                        }
+                    }
+                }
+            }
+            debug!("Disk state uploader closed");
+        });
+    }
+
+    async fn upload(&mut self, checkpoint: DiskCheckpoint) -> Result<()> {
+        if let Some(commit_epoch) = self.commit_epoch.take() {
+            if commit_epoch != checkpoint.epoch {
+                bail!(
+                    "commit epoch {} does not match checkpoint epoch {}",
+                    commit_epoch,
+                    checkpoint.epoch
+                );
+            }
+        }
+
+        let mut bytes = 0;
+        let mut backend_data = vec![];
+        if let Some((dir, global_entries)) = checkpoint.snapshot {
+            let path = snapshot_path(&self.task_info, checkpoint.epoch);
+            let mut files = vec![];
+            let mut entries = tokio::fs::read_dir(&dir).await?;
+            tokio::fs::create_dir_all(&self.cache_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if let Some(file) = self.uploaded.get(&name) {
+                    files.push(file.clone());
+                    continue;
+                }
+                let file_path = if is_table_file(&name) {
+                    shared_file_path(&self.task_info, self.instance, &name)
+                } else {
+                    format!("{}/{}", path, name)
+                };
+                let data = tokio::fs::read(entry.path()).await?;
+                bytes += data.len();
+                self.put(&file_path, data).await?;
+
+                let file = DiskStoreFile {
+                    name: name.clone(),
+                    path: file_path,
+                };
+                if is_table_file(&name) {
+                    // keep the file, so that restoring on this machine doesn't download it again
+                    let cached = self.cache_dir.join(file_name(&file.path));
+                    if let Err(e) = tokio::fs::hard_link(entry.path(), &cached).await {
+                        warn!("failed to cache state file {}: {:?}", name, e);
+                    }
+                    self.uploaded.insert(name, file.clone());
+                }
+                files.push(file);
+            }
+
+            // table files that were compacted away won't be part of any later snapshot
+            let current: HashSet<&str> = files.iter().map(|file| file.name.as_str()).collect();
+            self.uploaded
+                .retain(|name, _| current.contains(name.as_str()));
+            self.prune_cache().await?;
+
+            let global_file = if global_entries.is_empty() {
+                String::new()
+            } else {
+                let global_file = format!("{}/global", path);
+                let data = bincode::encode_to_vec(&global_entries, BINCODE_CONFIG)?;
+                bytes += data.len();
+                self.put(&global_file, data).await?;
+                global_file
+            };
+            tokio::fs::remove_dir_all(&dir).await?;
+
+            backend_data.push(grpc::BackendData {
+                backend_data: Some(BackendData::DiskStore(DiskStoreData {
+                    epoch: checkpoint.epoch,
+                    subtask_index: self.task_info.task_index as u32,
+                    min_routing_key: *self.task_info.key_range.start(),
+                    max_routing_key: *self.task_info.key_range.end(),
+                    files,
+                    global_file,
+                })),
+            });
+        }
+
+        let subtask_metadata = SubtaskCheckpointMetadata {
+            subtask_index: self.task_info.task_index as u32,
+            start_time: to_micros(checkpoint.time),
+            finish_time: to_micros(SystemTime::now()),
+            has_state: !backend_data.is_empty(),
+            tables: self.tables.clone(),
+            watermark: checkpoint.watermark.map(to_micros),
+            backend_data,
+            bytes: bytes as u64,
+            committing_data: self
+                .commit_data
+                .drain()
+                .map(|(table, data)| (table.to_string(), data))
+                .collect(),
+        };
+        self.control_tx
+            .send(ControlResp::CheckpointCompleted(CheckpointCompleted {
+                checkpoint_epoch: checkpoint.epoch,
+                operator_id: self.task_info.operator_id.clone(),
+                subtask_metadata,
+            }))
+            .await
+            .unwrap();
+        Ok(())
+    }
+
+    /// Writes a file to checkpoint storage, retrying failures with an exponential backoff
+    async fn put(&self, path: &str, data: Vec<u8>) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            match self.storage.put(path, data.clone()).await {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt < MAX_UPLOAD_ATTEMPTS => {
+                    warn!(
+                        "failed to upload {}, retrying (attempt {}): {:?}",
+                        path, attempt, e
+                    );
+                    tokio::time::sleep(Duration::from_millis((100 * (1 << attempt)).min(5_000)))
+                        .await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    bail!(
+                        "failed to upload {} after {} attempts: {:?}",
+                        path,
+                        attempt,
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    /// Removes the cached table files that are no longer part of the latest snapshot
+    async fn prune_cache(&self) -> Result<()> {
+        let keep: HashSet<&str> = self
+            .uploaded
+            .values()
+            .map(|file| file_name(&file.path))
+            .collect();
+        let mut entries = tokio::fs::read_dir(&self.cache_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if !keep.contains(entry.file_name().to_string_lossy().as_ref()) {
+                tokio::fs::remove_file(entry.path()).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+//! Reads the tables of a checkpoint outside of a running job, so that the state of a job can be
+//! inspected when it misbehaves.
+
+use crate::parquet::{get_storage_provider, ParquetBackend};
+use crate::tables::{BlindDataTuple, DataTuple};
+use crate::{BackingStore, StateBackend, BINCODE_CONFIG};
+use anyhow::{anyhow, bail, Result};
+use arroyo_rpc::api_types::checkpoints::StateValueType;
+use arroyo_rpc::grpc::backend_data::BackendData;
+use arroyo_rpc::grpc::{OperatorCheckpointMetadata, ParquetStoreData, TableDescriptor};
+use arroyo_types::{Data, Key};
+use bytes::Bytes;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde_json::Value;
+
+/// The size of one of an operator's tables in a checkpoint
+#[derive(Debug, Clone)]
+pub struct TableStats {
+    pub table: TableDescriptor,
+    /// the number of state files that hold the table
+    pub files: usize,
+    /// unknown for backends that store all of a subtask's tables together
+    pub bytes: Option<u64>,
+    /// the number of rows in the state files, which include the deletions since the table
+    /// was last compacted; unknown for backends that store all of a subtask's tables together
+    pub rows: Option<u64>,
+}
+
+async fn load_operator_metadata(
+    job_id: &str,
+    operator_id: &str,
+    epoch: u32,
+) -> Result<OperatorCheckpointMetadata> {
+    StateBackend::load_operator_metadata(job_id, operator_id, epoch)
+        .await
+        .ok_or_else(|| {
+            anyhow!(
+                "no checkpoint of operator {} at epoch {} for job {}",
+                operator_id,
+                epoch,
+                job_id
+            )
+        })
+}
+
+fn table_files(metadata: &OperatorCheckpointMetadata, table: char) -> Vec<&ParquetStoreData> {
+    metadata
+        .backend_data
+        .iter()
+        .filter_map(|backend_data| match &backend_data.backend_data {
+            Some(BackendData::ParquetStore(data)) if data.table.starts_with(table) => Some(data),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether the operator was checkpointed by the disk state backend, whose tables can't be read
+/// outside of the job
+pub fn has_disk_state(metadata: &OperatorCheckpointMetadata) -> bool {
+    metadata
+        .backend_data
+        .iter()
+        .any(|backend_data| matches!(backend_data.backend_data, Some(BackendData::DiskStore(_))))
+}
+
+/// Returns the sizes of the tables of an operator in the checkpoint for `epoch`
+pub async fn operator_table_stats(
+    job_id: &str,
+    operator_id: &str,
+    epoch: u32,
+) -> Result<Vec<TableStats>> {
+    let metadata = load_operator_metadata(job_id, operator_id, epoch).await?;
+    let storage = get_storage_provider().await?;
+    let disk_state = has_disk_state(&metadata);
+
+    let mut stats = vec![];
+    for table in &metadata.tables {
+        let files = table_files(&metadata, table.name.chars().next().unwrap());
+        if disk_state {
+            stats.push(TableStats {
+                table: table.clone(),
+                files: 0,
+                bytes: None,
+                rows: None,
+            });
+            continue;
+        }
+
+        let mut bytes = 0;
+        let mut rows = 0;
+        for file in &files {
+            let data = storage.get(file.file.as_str()).await?;
+            bytes += data.len() as u64;
+            rows += ParquetRecordBatchReaderBuilder::try_new(data)?
+                .metadata()
+                .file_metadata()
+                .num_rows() as u64;
+        }
+        stats.push(TableStats {
+            table: table.clone(),
+            files: files.len(),
+            bytes: Some(bytes),
+            rows: Some(rows),
+        });
+    }
+    Ok(stats)
+}
+
+async fn read_table_files(
+    job_id: &str,
+    operator_id: &str,
+    epoch: u32,
+    table: char,
+) -> Result<Vec<Bytes>> {
+    let metadata = load_operator_metadata(job_id, operator_id, epoch).await?;
+    if !metadata.tables.iter().any(|t| t.name.starts_with(table)) {
+        bail!("operator {} has no table '{}'", operator_id, table);
+    }
+    if has_disk_state(&metadata) {
+        bail!(
+            "operator {} was checkpointed by the disk state backend, whose tables can only be listed",
+            operator_id
+        );
+    }
+
+    let storage = get_storage_provider().await?;
+    let mut result = vec![];
+    for file in table_files(&metadata, table) {
+        result.push(storage.get(file.file.as_str()).await?);
+    }
+    Ok(result)
+}
+
+/// Returns the rows of a table in the checkpoint for `epoch`, without decoding their keys and
+/// values, optionally only those for the key encoded as `key`
+pub async fn read_blind_table(
+    job_id: &str,
+    operator_id: &str,
+    epoch: u32,
+    table: char,
+    key: Option<&[u8]>,
+) -> Result<Vec<BlindDataTuple>> {
+    let mut result = vec![];
+    for bytes in read_table_files(job_id, operator_id, epoch, table).await? {
+        match key {
+            Some(key) => result.extend(ParquetBackend::blind_tuples_for_key_from_parquet_bytes(
+                bytes, key,
+            )?),
+            None => result.extend(ParquetBackend::blind_tuples_from_parquet_bytes(
+                bytes.into(),
+                &(0..=u64::MAX),
+            )),
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the rows of a table in the checkpoint for `epoch`, decoded as the table's key and
+/// value types
+pub async fn read_table<K: Key, V: Data>(
+    job_id: &str,
+    operator_id: &str,
+    epoch: u32,
+    table: char,
+) -> Result<Vec<DataTuple<K, V>>> {
+    let mut result = vec![];
+    for bytes in read_table_files(job_id, operator_id, epoch, table).await? {
+        result.extend(ParquetBackend::tuples_from_parquet_bytes(
+            bytes.into(),
+            &(0..=u64::MAX),
+        ));
+    }
+    Ok(result)
+}
+
+pub fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Decodes a key or value, as it is stored in a table, as `value_type`
+pub fn decode(bytes: &[u8], value_type: StateValueType) -> Result<Value> {
+    fn decode<T: bincode::Decode>(bytes: &[u8]) -> Result<T> {
+        let (value, read) = bincode::decode_from_slice(bytes, BINCODE_CONFIG)?;
+        if read != bytes.len() {
+            bail!("{} bytes were left over", bytes.len() - read);
+        }
+        Ok(value)
+    }
+
+    Ok(match value_type {
+        StateValueType::Bool => decode::<bool>(bytes)?.into(),
+        StateValueType::I32 => decode::<i32>(bytes)?.into(),
+        StateValueType::I64 => decode::<i64>(bytes)?.into(),
+        StateValueType::U32 => decode::<u32>(bytes)?.into(),
+        StateValueType::U64 => decode::<u64>(bytes)?.into(),
+        StateValueType::F32 => decode::<f32>(bytes)?.into(),
+        StateValueType::F64 => decode::<f64>(bytes)?.into(),
+        StateValueType::String => decode::<String>(bytes)?.into(),
+        StateValueType::Bytes => hex_encode(&decode::<Vec<u8>>(bytes)?).into(),
+    })
+}
+
+/// Encodes a key written as text, like `42` or `us-east`, the way keys of `key_type` are
+/// stored. Byte keys are written hex-encoded.
+pub fn encode_key(key: &str, key_type: StateValueType) -> Result<Vec<u8>> {
+    fn encode<T: bincode::Encode>(value: T) -> Result<Vec<u8>> {
+        Ok(bincode::encode_to_vec(value, BINCODE_CONFIG)?)
+    }
+
+    match key_type {
+        StateValueType::Bool => encode(key.parse::<bool>()?),
+        StateValueType::I32 => encode(key.parse::<i32>()?),
+        StateValueType::I64 => encode(key.parse::<i64>()?),
+        StateValueType::U32 => encode(key.parse::<u32>()?),
+        StateValueType::U64 => encode(key.parse::<u64>()?),
+        StateValueType::F32 => encode(key.parse::<f32>()?),
+        StateValueType::F64 => encode(key.parse::<f64>()?),
+        StateValueType::String => encode(key.to_string()),
+        StateValueType::Bytes => {
+            encode(hex_decode(key).ok_or_else(|| anyhow!("'{}' is not hex-encoded", key))?)
+        }
+    }
+}
@@ -19,11 +19,15 @@ use tables::global_keyed_map::{GlobalKeyedState, GlobalKeyedStateCache};
 use tables::key_time_multi_map::{KeyTimeMultiMap, KeyTimeMultiMapCache};
 use tables::keyed_map::{KeyedState, KeyedStateCache};
 use tables::time_key_map::{TimeKeyMap, TimeKeyMapCache};
-use tables::{global_keyed_map, key_time_multi_map, keyed_map, time_key_map};
+use tables::CacheBudget;
 use tokio::sync::mpsc::Sender;
 
+mod backend;
 pub mod checkpoint_state;
 pub mod committing_state;
+#[cfg(feature = "disk-state")]
+pub mod disk;
+pub mod inspect;
 mod metrics;
 pub mod parquet;
 mod subtask_state;
@@ -32,7 +36,7 @@ pub mod tables;
 pub const BINCODE_CONFIG: Configuration = bincode::config::standard();
 pub const FULL_KEY_RANGE: RangeInclusive<u64> = 0..=u64::MAX;
 
-pub type StateBackend = parquet::ParquetBackend;
+pub use backend::{backend_type, check_checkpoint_backend, BackendType, StateBackend};
 
 pub fn global_table(name: impl Into<String>, description: impl Into<String>) -> TableDescriptor {
     TableDescriptor {
@@ -113,7 +117,7 @@ pub enum DataOperation {
     DeleteTimeRange(DeleteTimeRangeOperation), // delete all values for key in range (only for KeyTimeMultiMap)
 }
 #[async_trait]
-pub trait BackingStore {
+pub trait BackingStore: Send + Sync + Sized {
     /// prepares a checkpoint to be loaded, e.g., by deleting future data
     async fn prepare_checkpoint_load(metadata: &CheckpointMetadata) -> Result<()>;
 
@@ -135,7 +139,7 @@ pub trait BackingStore {
         task_info: &TaskInfo,
         tables: Vec<TableDescriptor>,
         control_tx: Sender<ControlResp>,
-    ) -> Self;
+    ) -> Result<Self>;
 
     /// creates a new instance of the BackingStore from a checkpoint
     async fn from_checkpoint(
@@ -143,7 +147,7 @@ pub trait BackingStore {
         metadata: CheckpointMetadata,
         tables: Vec<TableDescriptor>,
         control_tx: Sender<ControlResp>,
-    ) -> Self;
+    ) -> Result<Self>;
 
     /// returns the name of the BackingStore implementation
     fn name() -> &'static str;
@@ -164,13 +168,21 @@ pub trait BackingStore {
         new_min_epoch: u32,
     ) -> Result<()>;
 
+    /// copies the checkpoint for the given job and epoch into a self-contained savepoint at
+    /// `path`, which is not affected by the cleanup of the job's checkpoints
+    async fn write_savepoint(job_id: &str, epoch: u32, path: &str) -> Result<CheckpointMetadata>;
+
+    /// copies the savepoint at `path` into the checkpoints of the given job, so that the job can
+    /// be restored from it like from its own checkpoint
+    async fn restore_savepoint(path: &str, job_id: &str) -> Result<CheckpointMetadata>;
+
     /// creates a checkpoint of the current state of the BackingStore instance,
     /// returning the checkpoint epoch
     async fn checkpoint(
         &mut self,
         barrier: CheckpointBarrier,
         watermark: Option<SystemTime>,
-    ) -> u32;
+    ) -> Result<u32>;
 
     /// gets the data tuples for a given table
     async fn get_data_tuples<K: Key, V: Data>(&self, table: char) -> Vec<DataTuple<K, V>>;
@@ -223,6 +235,92 @@ pub trait BackingStore {
     /// gets the key-value pairs for a given table
     async fn get_key_values<K: Key, V: Data>(&self, table: char) -> Vec<(K, V)>;
 
+    /// writes the value of a key of a table accessed through `KeyedState`, replacing the previous
+    /// value of the key
+    async fn write_keyed_value<K: Key, V: Data>(
+        &mut self,
+        table: char,
+        timestamp: SystemTime,
+        key: &mut K,
+        value: &mut V,
+    ) {
+        self.write_data_tuple(table, TableType::TimeKeyMap, timestamp, key, value)
+            .await
+    }
+
+    /// deletes a key of a table accessed through `KeyedState`
+    async fn delete_keyed_value<K: Key>(&mut self, table: char, key: &mut K) {
+        self.delete_time_key(table, TableType::Global, SystemTime::UNIX_EPOCH, key)
+            .await
+    }
+
+    /// the budget for the in-memory cache of each keyed table, if the backend can read entries
+    /// that were evicted from the cache back through the methods below. Otherwise the caches
+    /// hold whole tables.
+    fn cache_budget(&self) -> Option<CacheBudget> {
+        None
+    }
+
+    /// gets the value of a key of a table written through `write_keyed_value`
+    async fn get_keyed_value<K: Key, V: Data>(&self, _table: char, _key: &mut K) -> Option<V> {
+        None
+    }
+
+    /// gets the entries of a table written through `write_keyed_value` that match `filter`
+    async fn filter_keyed_values<K: Key, V: Data, F: FnMut(&K, &V) -> bool + Send>(
+        &self,
+        _table: char,
+        _filter: F,
+    ) -> Vec<(K, V)> {
+        vec![]
+    }
+
+    /// gets the values of a key of a KeyTimeMultiMap, with their timestamps
+    async fn get_key_time_values<K: Key, V: Data>(
+        &self,
+        _table: char,
+        _key: &mut K,
+    ) -> Vec<(SystemTime, V)> {
+        vec![]
+    }
+
+    /// gets the earliest timestamp of every key of a KeyTimeMultiMap
+    async fn get_earliest_key_times<K: Key>(&self, _table: char) -> Vec<(K, SystemTime)> {
+        vec![]
+    }
+
+    /// gets the entries of a TimeKeyMap at `timestamp`
+    async fn get_time_key_values<K: Key, V: Data>(
+        &self,
+        _table: char,
+        _timestamp: SystemTime,
+    ) -> Vec<(K, V)> {
+        vec![]
+    }
+
+    /// gets the timestamps that a TimeKeyMap has entries at
+    async fn get_time_key_times(&self, _table: char) -> Vec<SystemTime> {
+        vec![]
+    }
+
+    /// deletes the entries of a TimeKeyMap at `timestamp`, once they have been evicted by the
+    /// operator. Backends that don't spill drop them once the watermark passes instead.
+    async fn delete_time_key_values(&mut self, _table: char, _timestamp: SystemTime) {}
+
+    /// gets the value of a key of a global table
+    async fn get_global_value<K: Key, V: Data>(&self, _table: char, _key: &mut K) -> Option<V> {
+        None
+    }
+
+    /// compacts the state of an operator, returning the data that the subtasks should swap for
+    /// the compacted data, if there was anything to compact
+    async fn compact_operator(
+        parallelism: usize,
+        job_id: String,
+        operator_id: String,
+        epoch: u32,
+    ) -> Result<Option<CompactionResult>>;
+
     /// loads a compacted state into the BackingStore instance
     async fn load_compacted(&mut self, compaction: CompactionResult);
 
@@ -245,15 +343,40 @@ pub fn hash_key<K: Hash>(key: &K) -> u64 {
     hasher.finish()
 }
 
+// the operator may have changed since the checkpoint was taken if the pipeline was upgraded, so
+// make sure that it can still read the restored tables
+pub(crate) fn check_restored_tables(
+    task_info: &TaskInfo,
+    restored: &[TableDescriptor],
+    tables: &HashMap<char, TableDescriptor>,
+) {
+    for restored in restored {
+        match tables.get(&restored.name.chars().next().unwrap()) {
+            Some(table) if table.table_type == restored.table_type => {}
+            Some(table) => panic!(
+                "table {} of operator {} was checkpointed as {:?} but is now {:?}",
+                restored.name,
+                task_info.operator_id,
+                restored.table_type(),
+                table.table_type()
+            ),
+            None => panic!(
+                "operator {} no longer has table {} ({}) to restore its state into",
+                task_info.operator_id, restored.name, restored.description
+            ),
+        }
+    }
+}
+
 impl<S: BackingStore> StateStore<S> {
     pub async fn new(
         task_info: &TaskInfo,
         tables: Vec<TableDescriptor>,
         control_tx: Sender<ControlResp>,
-    ) -> Self {
-        let backend = S::new(task_info, tables.clone(), control_tx).await;
+    ) -> Result<Self> {
+        let backend = S::new(task_info, tables.clone(), control_tx).await?;
 
-        StateStore {
+        Ok(StateStore {
             backend,
             task_info: task_info.clone(),
             table_descriptors: tables
@@ -262,7 +385,7 @@ impl<S: BackingStore> StateStore<S> {
                 .collect(),
             restore_from: None,
             caches: HashMap::new(),
-        }
+        })
     }
 
     pub async fn from_checkpoint(
@@ -270,11 +393,11 @@ impl<S: BackingStore> StateStore<S> {
         checkpoint_metadata: CheckpointMetadata,
         tables: Vec<TableDescriptor>,
         tx: Sender<ControlResp>,
-    ) -> Self {
+    ) -> Result<Self> {
         let backend =
-            S::from_checkpoint(task_info, checkpoint_metadata.clone(), tables.clone(), tx).await;
+            S::from_checkpoint(task_info, checkpoint_metadata.clone(), tables.clone(), tx).await?;
 
-        StateStore {
+        Ok(StateStore {
             backend,
             task_info: task_info.clone(),
             table_descriptors: tables
@@ -283,7 +406,7 @@ impl<S: BackingStore> StateStore<S> {
                 .collect(),
             restore_from: Some(checkpoint_metadata),
             caches: HashMap::new(),
-        }
+        })
     }
 
     // We now handle this in the individual tables. Don't love it, but they have different behaviors.
@@ -313,7 +436,7 @@ impl<S: BackingStore> StateStore<S> {
                     .await;
                     Box::new(cache)
                 }
-                None => Box::<time_key_map::TimeKeyMapCache<K, V>>::default(),
+                None => Box::new(TimeKeyMapCache::<K, V>::new(self.backend.cache_budget())),
             };
             e.insert(cache);
         }
@@ -355,7 +478,9 @@ impl<S: BackingStore> StateStore<S> {
                     .await;
                     Box::new(cache)
                 }
-                None => Box::<key_time_multi_map::KeyTimeMultiMapCache<K, V>>::default(),
+                None => Box::new(KeyTimeMultiMapCache::<K, V>::new(
+                    self.backend.cache_budget(),
+                )),
             };
             e.insert(cache);
         }
@@ -389,7 +514,9 @@ impl<S: BackingStore> StateStore<S> {
                         GlobalKeyedStateCache::<K, V>::from_checkpoint(&self.backend, table).await;
                     Box::new(cache)
                 }
-                None => Box::<global_keyed_map::GlobalKeyedStateCache<K, V>>::default(),
+                None => Box::new(GlobalKeyedStateCache::<K, V>::new(
+                    self.backend.cache_budget(),
+                )),
             };
             e.insert(cache);
         }
@@ -419,7 +546,7 @@ impl<S: BackingStore> StateStore<S> {
                         KeyedStateCache::<K, V>::from_checkpoint(&self.backend, table).await;
                     Box::new(cache)
                 }
-                None => Box::<keyed_map::KeyedStateCache<K, V>>::default(),
+                None => Box::new(KeyedStateCache::<K, V>::new(self.backend.cache_budget())),
             };
             e.insert(cache);
         }
@@ -436,8 +563,13 @@ impl<S: BackingStore> StateStore<S> {
         KeyedState::new(table, &mut self.backend, cache)
     }
 
-    pub async fn checkpoint(&mut self, barrier: CheckpointBarrier, watermark: Option<SystemTime>) {
-        self.backend.checkpoint(barrier, watermark).await;
+    pub async fn checkpoint(
+        &mut self,
+        barrier: CheckpointBarrier,
+        watermark: Option<SystemTime>,
+    ) -> Result<()> {
+        self.backend.checkpoint(barrier, watermark).await?;
+        Ok(())
     }
 
     pub async fn load_compacted(&mut self, compaction: CompactionResult) {
@@ -466,17 +598,21 @@ mod test {
     use test_case::test_case;
     use tokio::sync::mpsc::Receiver;
 
+    use arroyo_rpc::api_types::checkpoints::StateValueType;
     use arroyo_rpc::{CompactionResult, ControlResp};
     use rand::RngCore;
     use std::time::{Duration, SystemTime};
     use tokio::sync::mpsc::channel;
 
+    #[cfg(feature = "disk-state")]
+    use crate::disk::DiskBackend;
     use crate::parquet::ParquetBackend;
     use crate::tables::key_time_multi_map::KeyTimeMultiMap;
     use crate::tables::keyed_map::KeyedState;
     use crate::tables::time_key_map::TimeKeyMap;
     use crate::{
-        global_table, key_time_multi_map_table, timestamp_table, BackingStore, StateStore,
+        global_table, key_time_multi_map_table, timestamp_table, BackendType, BackingStore,
+        StateStore,
     };
     use arroyo_types::{to_micros, CheckpointBarrier, TaskInfo};
 
@@ -513,7 +649,8 @@ mod test {
                 default_tables(),
                 tx,
             )
-            .await,
+            .await
+            .unwrap(),
             rx,
         )
     }
@@ -533,7 +670,47 @@ mod test {
                 default_tables(),
                 tx,
             )
-            .await,
+            .await
+            .unwrap(),
+            rx,
+        )
+    }
+
+    #[cfg(feature = "disk-state")]
+    async fn disk_for_test() -> (StateStore<DiskBackend>, Receiver<ControlResp>) {
+        let job_id = rand::thread_rng().next_u64();
+        let operator_id = rand::thread_rng().next_u64();
+        let (tx, rx) = channel(10);
+        (
+            StateStore::<DiskBackend>::new(
+                &TaskInfo::for_test(
+                    &format!("test_job_{}", job_id),
+                    &format!("test_op_{}", operator_id),
+                ),
+                default_tables(),
+                tx,
+            )
+            .await
+            .unwrap(),
+            rx,
+        )
+    }
+
+    #[cfg(feature = "disk-state")]
+    async fn disk_for_test_from_checkpoint(
+        task_info: &TaskInfo,
+        checkpoint_metadata: &CheckpointMetadata,
+    ) -> (StateStore<DiskBackend>, Receiver<ControlResp>) {
+        let (tx, rx) = channel(10);
+        (
+            StateStore::<DiskBackend>::from_checkpoint(
+                task_info,
+                checkpoint_metadata.clone(),
+                default_tables(),
+                tx,
+            )
+            .await
+            .unwrap(),
             rx,
         )
     }
@@ -559,8 +736,8 @@ mod test {
         result
     }
 
-    async fn do_checkpoint(
-        ss: &mut StateStore<impl BackingStore>,
+    async fn do_checkpoint<S: BackingStore>(
+        ss: &mut StateStore<S>,
         job_id: &str,
         operator_id: &str,
         epoch: u32,
@@ -576,7 +753,8 @@ mod test {
                 },
                 Some(SystemTime::UNIX_EPOCH),
             )
-            .await;
+            .await
+            .unwrap();
         // wait until we get confirmation on the queue
 
         let message = match rx.recv().await {
@@ -607,6 +785,7 @@ mod test {
             start_time: 0,
             finish_time: 0,
             operator_ids: vec![operator_id.to_string()],
+            backend: S::name().to_string(),
         };
 
         ParquetBackend::write_checkpoint_metadata(checkpoint_metadata.clone()).await;
@@ -614,7 +793,27 @@ mod test {
         checkpoint_metadata
     }
 
+    #[test]
+    fn test_checkpoint_backend() {
+        let metadata = |backend: &str| CheckpointMetadata {
+            backend: backend.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            BackendType::of_checkpoint(&metadata("")).unwrap(),
+            BackendType::Parquet
+        );
+        assert_eq!(
+            BackendType::of_checkpoint(&metadata("parquet")).unwrap(),
+            BackendType::Parquet
+        );
+        assert!(BackendType::of_checkpoint(&metadata("rocks")).is_err());
+        #[cfg(not(feature = "disk-state"))]
+        assert!(BackendType::of_checkpoint(&metadata("disk")).is_err());
+    }
+
     #[test_case(parquet_for_test().await; "parquet store")]
+    #[cfg_attr(feature = "disk-state", test_case(disk_for_test().await; "disk store"))]
     #[tokio::test]
     async fn test_global(p: (StateStore<impl BackingStore>, Receiver<ControlResp>)) {
         let (mut ss, _rx) = p;
@@ -623,20 +822,21 @@ mod test {
 
         gs.insert("k1".into(), 1).await;
 
-        assert_eq!(*gs.get(&"k1".into()).unwrap(), 1);
+        assert_eq!(*gs.get(&"k1".into()).await.unwrap(), 1);
 
         let mut gs = ss.get_global_keyed_state::<String, i64>('g').await;
-        assert_eq!(*gs.get(&"k1".into()).unwrap(), 1);
+        assert_eq!(*gs.get(&"k1".into()).await.unwrap(), 1);
 
         gs.insert("k2".into(), 2).await;
 
-        let mut entries = gs.get_all();
+        let mut entries = gs.get_all().await;
         entries.sort();
 
         assert_eq!(entries, vec![&1i64, &2]);
     }
 
     #[test_case(parquet_for_test().await; "parquet store")]
+    #[cfg_attr(feature = "disk-state", test_case(disk_for_test().await; "disk store"))]
     #[tokio::test]
     async fn test_key_time_multi_map(p: (StateStore<impl BackingStore>, Receiver<ControlResp>)) {
         let (mut ss, mut rx) = p;
@@ -771,6 +971,7 @@ mod test {
     }
 
     #[test_case(parquet_for_test().await; "parquet store")]
+    #[cfg_attr(feature = "disk-state", test_case(disk_for_test().await; "disk store"))]
     #[tokio::test]
     async fn test_time_key_map(p: (StateStore<impl BackingStore>, Receiver<ControlResp>)) {
         let (mut ss, mut rx) = p;
@@ -791,7 +992,7 @@ mod test {
         ks.insert(t3, 1, 4);
         ks.insert(t4, 1, 5);
 
-        assert_eq!(ks.get_all_for_time(t1), vec![(&1, &2)]);
+        assert_eq!(ks.get_all_for_time(t1).await, vec![(&1, &2)]);
         assert_eq!(
             ks.get_all().await,
             vec![(t1, &1, &2), (t2, &1, &3), (t3, &1, &4), (t4, &1, &5)]
@@ -801,7 +1002,7 @@ mod test {
 
         let mut ks = ss.get_time_key_map::<usize, i32>('t', None).await;
 
-        assert_eq!(ks.get_all_for_time(t1), vec![(&1, &2)]);
+        assert_eq!(ks.get_all_for_time(t1).await, vec![(&1, &2)]);
         assert_eq!(
             ks.get_all().await,
             vec![(t1, &1, &2), (t2, &1, &3), (t3, &1, &4), (t4, &1, &5)]
@@ -820,7 +1021,7 @@ mod test {
         let mut ks: KeyedState<usize, i32, _> = ss.get_key_state('t').await;
         let t1 = SystemTime::UNIX_EPOCH;
         ks.insert(t1, 1, 1).await;
-        assert_eq!(Some(&1), ks.get(&mut 1));
+        assert_eq!(Some(&1), ks.get(&mut 1).await);
 
         // checkpoint 1
 
@@ -879,7 +1080,118 @@ mod test {
 
         // check that the key is gone
 
-        let ks: KeyedState<usize, i32, _> = restored.get_key_state('t').await;
-        assert_eq!(None, ks.get(&mut 1));
+        let mut ks: KeyedState<usize, i32, _> = restored.get_key_state('t').await;
+        assert_eq!(None, ks.get(&mut 1).await);
+    }
+
+    #[tokio::test]
+    async fn test_inspect_checkpoint() {
+        let (mut ss, mut rx) = parquet_for_test().await;
+        let job_id = ss.task_info.job_id.clone();
+        let operator_id = ss.task_info.operator_id.clone();
+
+        let mut ks: KeyedState<usize, i32, _> = ss.get_key_state('t').await;
+        ks.insert(SystemTime::UNIX_EPOCH, 1, 1).await;
+        ks.insert(SystemTime::UNIX_EPOCH, 2, 2).await;
+
+        do_checkpoint(&mut ss, &job_id, &operator_id, 1, &mut rx).await;
+
+        let stats = crate::inspect::operator_table_stats(&job_id, &operator_id, 1)
+            .await
+            .unwrap();
+        assert_eq!(3, stats.len());
+        let t = stats.iter().find(|s| s.table.name == "t").unwrap();
+        assert_eq!(1, t.files);
+        assert_eq!(Some(2), t.rows);
+        assert!(t.bytes.unwrap() > 0);
+
+        let mut values: Vec<_> =
+            crate::inspect::read_table::<usize, i32>(&job_id, &operator_id, 1, 't')
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|tuple| (tuple.key, tuple.value.unwrap()))
+                .collect();
+        values.sort();
+        assert_eq!(vec![(1, 1), (2, 2)], values);
+
+        let key = bincode::encode_to_vec(2usize, crate::BINCODE_CONFIG).unwrap();
+        assert_eq!(
+            key,
+            crate::inspect::encode_key("2", StateValueType::U64).unwrap()
+        );
+        let tuples = crate::inspect::read_blind_table(&job_id, &operator_id, 1, 't', Some(&key))
+            .await
+            .unwrap();
+        assert_eq!(1, tuples.len());
+        assert_eq!(
+            serde_json::Value::from(2),
+            crate::inspect::decode(&tuples[0].value, StateValueType::I32).unwrap()
+        );
+        assert!(crate::inspect::decode(&tuples[0].value, StateValueType::String).is_err());
+
+        assert!(
+            crate::inspect::read_blind_table(&job_id, &operator_id, 1, 'x', None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[cfg(feature = "disk-state")]
+    #[tokio::test]
+    async fn test_disk_restore_with_new_key_ranges() {
+        // evict from the caches, so that reads go to the database
+        env::set_var(arroyo_types::DISK_STATE_CACHE_BYTES_ENV, "64");
+        let (mut ss, mut rx) = disk_for_test().await;
+        let job_id = ss.task_info.job_id.clone();
+        let operator_id = ss.task_info.operator_id.clone();
+
+        let mut ks: KeyedState<usize, i32, _> = ss.get_key_state('t').await;
+        for key in 0..100 {
+            ks.insert(SystemTime::UNIX_EPOCH, key, key as i32).await;
+        }
+        for key in 0..100 {
+            assert_eq!(Some(&(key as i32)), ks.get(&mut key.clone()).await);
+        }
+        assert_eq!(10, ks.filter_all(|_, value| *value < 10).await.len());
+
+        let mut gs = ss.get_global_keyed_state::<String, i64>('g').await;
+        gs.insert("k1".into(), 1).await;
+
+        let checkpoint = do_checkpoint(&mut ss, &job_id, &operator_id, 1, &mut rx).await;
+        // restored subtasks open their databases in the same directories
+        let task_info = ss.task_info.clone();
+        drop(ss);
+
+        // restoring into the same key range reuses the snapshot
+        let (mut restored, _) = disk_for_test_from_checkpoint(&task_info, &checkpoint).await;
+        let mut ks: KeyedState<usize, i32, _> = restored.get_key_state('t').await;
+        assert_eq!(Some(&42), ks.get(&mut 42).await);
+        drop(restored);
+
+        // restoring into two subtasks splits the keys between them
+        let mut restored_keys = 0;
+        for task_index in 0..2 {
+            let mut task_info = task_info.clone();
+            task_info.task_index = task_index;
+            task_info.parallelism = 2;
+            task_info.key_range = arroyo_types::range_for_server(task_index, 2);
+
+            let (mut restored, _) = disk_for_test_from_checkpoint(&task_info, &checkpoint).await;
+            let mut ks: KeyedState<usize, i32, _> = restored.get_key_state('t').await;
+            for key in 0..100usize {
+                let value = ks.get(&mut key.clone()).await.cloned();
+                if task_info.key_range.contains(&crate::hash_key(&key)) {
+                    assert_eq!(Some(key as i32), value);
+                    restored_keys += 1;
+                } else {
+                    assert_eq!(None, value);
+                }
+            }
+
+            let mut gs = restored.get_global_keyed_state::<String, i64>('g').await;
+            assert_eq!(Some(&1), gs.get(&"k1".into()).await);
+        }
+        assert_eq!(100, restored_keys);
     }
 }
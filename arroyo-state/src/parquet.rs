@@ -1,11 +1,13 @@
 use crate::metrics::CURRENT_FILES_GAUGE;
 use crate::tables::{BlindDataTuple, Compactor, DataTuple};
 use crate::{
-    hash_key, BackingStore, DataOperation, DeleteKeyOperation, DeleteTimeKeyOperation,
-    DeleteTimeRangeOperation, DeleteValueOperation, StateStore, BINCODE_CONFIG,
+    check_restored_tables, hash_key, BackingStore, DataOperation, DeleteKeyOperation,
+    DeleteTimeKeyOperation, DeleteTimeRangeOperation, DeleteValueOperation, StateStore,
+    BINCODE_CONFIG,
 };
-use anyhow::{bail, Context, Result};
-use arrow_array::RecordBatch;
+use anyhow::{anyhow, bail, Context, Result};
+use arrow::compute::kernels::cmp::eq;
+use arrow_array::{BinaryArray, RecordBatch};
 use arroyo_rpc::grpc::backend_data::BackendData;
 use arroyo_rpc::grpc::{
     backend_data, CheckpointMetadata, OperatorCheckpointMetadata, ParquetStoreData,
@@ -15,14 +17,15 @@ use arroyo_rpc::{grpc, CheckpointCompleted, CompactionResult, ControlResp};
 use arroyo_storage::StorageProvider;
 use arroyo_types::{
     from_nanos, range_for_server, to_micros, to_nanos, CheckpointBarrier, Data, Key, TaskInfo,
-    CHECKPOINT_URL_ENV, S3_ENDPOINT_ENV, S3_REGION_ENV,
+    CHECKPOINT_URL_ENV, DISK_STATE_CACHE_BYTES_ENV, DISK_STATE_CACHE_EVICTION_ENV,
+    DISK_STATE_DIR_ENV, S3_ENDPOINT_ENV, S3_REGION_ENV,
 };
 use bincode::config;
 use bytes::Bytes;
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
-use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
-use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::{ArrowPredicateFn, ParquetRecordBatchReaderBuilder, RowFilter};
+use parquet::arrow::{ArrowWriter, ProjectionMask};
 use parquet::basic::ZstdLevel;
 use parquet::file::properties::{EnabledStatistics, WriterProperties};
 use prost::Message;
@@ -38,7 +41,7 @@ use tracing::{debug, info, warn};
 pub const FULL_KEY_RANGE: RangeInclusive<u64> = 0..=u64::MAX;
 pub const GENERATIONS_TO_COMPACT: u32 = 1; // only compact generation 0 files
 
-async fn get_storage_provider() -> anyhow::Result<StorageProvider> {
+pub(crate) async fn get_storage_provider() -> anyhow::Result<StorageProvider> {
     // TODO: this should be encoded in the config so that the controller doesn't need
     // to be synchronized with the workers
     let storage_url =
@@ -64,17 +67,21 @@ pub struct ParquetBackend {
 }
 
 fn base_path(job_id: &str, epoch: u32) -> String {
-    format!("{}/checkpoints/checkpoint-{:0>7}", job_id, epoch)
+    format!("{}checkpoint-{:0>7}", checkpoints_path(job_id), epoch)
 }
 
 fn metadata_path(path: &str) -> String {
     format!("{}/metadata", path)
 }
 
-fn operator_path(job_id: &str, epoch: u32, operator: &str) -> String {
+pub(crate) fn operator_path(job_id: &str, epoch: u32, operator: &str) -> String {
     format!("{}/operator-{}", base_path(job_id, epoch), operator)
 }
 
+pub(crate) fn checkpoints_path(job_id: &str) -> String {
+    format!("{}/checkpoints/", job_id)
+}
+
 fn table_checkpoint_path(task_info: &TaskInfo, table: char, epoch: u32, compacted: bool) -> String {
     format!(
         "{}/table-{}-{:0>3}{}",
@@ -85,6 +92,29 @@ fn table_checkpoint_path(task_info: &TaskInfo, table: char, epoch: u32, compacte
     )
 }
 
+fn savepoint_operator_path(path: &str, operator: &str) -> String {
+    format!("{}/operator-{}", path, operator)
+}
+
+/// The paths of the files in checkpoint storage that hold the given backend data. Checkpoint
+/// cleanup and savepoints only move these files around, so they are shared with the disk backend.
+fn state_files(backend_data: &mut grpc::BackendData) -> Vec<&mut String> {
+    match &mut backend_data.backend_data {
+        Some(BackendData::ParquetStore(parquet_store)) => vec![&mut parquet_store.file],
+        Some(BackendData::DiskStore(disk_store)) => {
+            let global_file =
+                (!disk_store.global_file.is_empty()).then_some(&mut disk_store.global_file);
+            disk_store
+                .files
+                .iter_mut()
+                .map(|file| &mut file.path)
+                .chain(global_file)
+                .collect()
+        }
+        None => vec![],
+    }
+}
+
 #[async_trait::async_trait]
 impl BackingStore for ParquetBackend {
     fn name() -> &'static str {
@@ -152,9 +182,9 @@ impl BackingStore for ParquetBackend {
         task_info: &TaskInfo,
         tables: Vec<TableDescriptor>,
         tx: Sender<ControlResp>,
-    ) -> Self {
-        let storage = get_storage_provider().await.unwrap();
-        Self {
+    ) -> Result<Self> {
+        let storage = get_storage_provider().await?;
+        Ok(Self {
             epoch: 1,
             min_epoch: 1,
             current_files: HashMap::new(),
@@ -171,7 +201,7 @@ impl BackingStore for ParquetBackend {
                 .map(|table| (table.name.clone().chars().next().unwrap(), table))
                 .collect(),
             storage,
-        }
+        })
     }
 
     async fn from_checkpoint(
@@ -179,26 +209,32 @@ impl BackingStore for ParquetBackend {
         metadata: CheckpointMetadata,
         tables: Vec<TableDescriptor>,
         control_tx: Sender<ControlResp>,
-    ) -> Self {
+    ) -> Result<Self> {
         let operator_metadata =
             Self::load_operator_metadata(&task_info.job_id, &task_info.operator_id, metadata.epoch)
                 .await
-                .unwrap_or_else(|| {
-                    panic!(
+                .ok_or_else(|| {
+                    anyhow!(
                         "missing metadata for operator {}, epoch {}",
-                        task_info.operator_id, metadata.epoch
+                        task_info.operator_id,
+                        metadata.epoch
                     )
-                });
+                })?;
         let mut current_files: HashMap<char, BTreeMap<u32, Vec<ParquetStoreData>>> = HashMap::new();
         let tables: HashMap<char, TableDescriptor> = tables
             .into_iter()
             .map(|table| (table.name.clone().chars().next().unwrap(), table))
             .collect();
+        check_restored_tables(task_info, &operator_metadata.tables, &tables);
         for backend_data in operator_metadata.backend_data {
             let Some(backend_data::BackendData::ParquetStore(parquet_data)) =
                 backend_data.backend_data
             else {
-                panic!("expect parquet data")
+                bail!(
+                    "checkpoint {} of operator {} has no parquet data",
+                    metadata.epoch,
+                    task_info.operator_id
+                );
             };
             let table_descriptor = tables
                 .get(&parquet_data.table.chars().next().unwrap())
@@ -222,8 +258,8 @@ impl BackingStore for ParquetBackend {
 
         let writer_current_files = current_files.clone();
 
-        let storage = get_storage_provider().await.unwrap();
-        Self {
+        let storage = get_storage_provider().await?;
+        Ok(Self {
             epoch: metadata.epoch + 1,
             min_epoch: metadata.min_epoch,
             current_files,
@@ -237,7 +273,7 @@ impl BackingStore for ParquetBackend {
             task_info: task_info.clone(),
             tables,
             storage,
-        }
+        })
     }
 
     async fn prepare_checkpoint_load(_metadata: &CheckpointMetadata) -> anyhow::Result<()> {
@@ -302,18 +338,87 @@ impl BackingStore for ParquetBackend {
         Ok(())
     }
 
+    async fn write_savepoint(job_id: &str, epoch: u32, path: &str) -> Result<CheckpointMetadata> {
+        let metadata = Self::load_checkpoint_metadata(job_id, epoch)
+            .await
+            .ok_or_else(|| anyhow!("checkpoint {} not found for job {}", epoch, job_id))?;
+        let storage_client = get_storage_provider().await?;
+
+        // files keep their path relative to the job's checkpoint directory, so that the layout
+        // can be recreated when the savepoint is restored
+        for operator_id in &metadata.operator_ids {
+            let mut operator_metadata = Self::load_operator_metadata(job_id, operator_id, epoch)
+                .await
+                .ok_or_else(|| {
+                    anyhow!(
+                        "missing metadata for operator {}, epoch {}",
+                        operator_id,
+                        epoch
+                    )
+                })?;
+            Self::copy_files(
+                &storage_client,
+                &mut operator_metadata,
+                &checkpoints_path(job_id),
+                &format!("{}/", path),
+            )
+            .await?;
+            storage_client
+                .put(
+                    metadata_path(&savepoint_operator_path(path, operator_id)),
+                    operator_metadata.encode_to_vec(),
+                )
+                .await?;
+        }
+
+        storage_client
+            .put(metadata_path(path), metadata.encode_to_vec())
+            .await?;
+        Ok(metadata)
+    }
+
+    async fn restore_savepoint(path: &str, job_id: &str) -> Result<CheckpointMetadata> {
+        let storage_client = get_storage_provider().await?;
+        let data = storage_client
+            .get(metadata_path(path))
+            .await
+            .context(format!("savepoint {} not found", path))?;
+        let mut metadata = CheckpointMetadata::decode(&data[..])?;
+
+        for operator_id in &metadata.operator_ids {
+            let data = storage_client
+                .get(metadata_path(&savepoint_operator_path(path, operator_id)))
+                .await?;
+            let mut operator_metadata = OperatorCheckpointMetadata::decode(&data[..])?;
+            Self::copy_files(
+                &storage_client,
+                &mut operator_metadata,
+                &format!("{}/", path),
+                &checkpoints_path(job_id),
+            )
+            .await?;
+            operator_metadata.job_id = job_id.to_string();
+            Self::write_operator_checkpoint_metadata(operator_metadata).await;
+        }
+
+        metadata.job_id = job_id.to_string();
+        metadata.min_epoch = metadata.epoch;
+        Self::write_checkpoint_metadata(metadata.clone()).await;
+        Ok(metadata)
+    }
+
     async fn checkpoint(
         &mut self,
         barrier: CheckpointBarrier,
         watermark: Option<SystemTime>,
-    ) -> u32 {
+    ) -> Result<u32> {
         assert_eq!(barrier.epoch, self.epoch);
         self.writer
             .checkpoint(self.epoch, barrier.timestamp, watermark, barrier.then_stop)
             .await;
         self.epoch += 1;
         self.min_epoch = barrier.min_epoch;
-        self.epoch - 1
+        Ok(self.epoch - 1)
     }
 
     async fn get_data_tuples<K: Key, V: Data>(&self, table: char) -> Vec<DataTuple<K, V>> {
@@ -330,10 +435,10 @@ impl BackingStore for ParquetBackend {
                     let bytes = self.storage.get(&file.file).await.unwrap_or_else(|_| {
                         panic!("unable to find file {} in checkpoint", file.file)
                     });
-                    result.append(
-                        &mut self
-                            .tuples_from_parquet_bytes(bytes.into(), &self.task_info.key_range),
-                    );
+                    result.append(&mut Self::tuples_from_parquet_bytes(
+                        bytes.into(),
+                        &self.task_info.key_range,
+                    ));
                 }
             }
         }
@@ -480,6 +585,126 @@ impl BackingStore for ParquetBackend {
             .await
     }
 
+    /// Called after a checkpoint is committed
+    async fn compact_operator(
+        parallelism: usize,
+        job_id: String,
+        operator_id: String,
+        epoch: u32,
+    ) -> Result<Option<CompactionResult>> {
+        let min_files_to_compact = env::var("MIN_FILES_TO_COMPACT")
+            .unwrap_or_else(|_| "4".to_string())
+            .parse()
+            .unwrap();
+
+        let checkpoint_metadata = Self::load_checkpoint_metadata(&job_id, epoch)
+            .await
+            .unwrap_or_else(|| {
+                panic!(
+                    "missing checkpoint metadata for job {}, epoch {}",
+                    job_id, epoch
+                )
+            });
+
+        let operator_checkpoint_metadata =
+            Self::load_operator_metadata(&job_id, &operator_id, epoch)
+                .await
+                .expect("expect operator metadata to still be present");
+
+        let mut backend_data_to_drop = HashMap::new();
+        let mut backend_data_to_load = vec![]; // one file per partition per table
+
+        // we reduce the range of epochs to a set of compacted files
+        for index in 0..parallelism {
+            let key_range = range_for_server(index, parallelism);
+
+            // construct a theoretical TaskInfo for the purpose of partitioning the data
+            let task = TaskInfo {
+                job_id: job_id.clone(),
+                operator_name: "".to_string(), // TODO: this is not used
+                operator_id: operator_id.clone(),
+                task_index: index,
+                parallelism,
+                key_range: key_range.clone(),
+            };
+            let (tx, _) = channel(10);
+
+            // we must access the data through the state store because
+            // it keeps track of the table -> file relation
+            let mut state_store = StateStore::<ParquetBackend>::from_checkpoint(
+                &task,
+                checkpoint_metadata.clone(),
+                operator_checkpoint_metadata.tables.clone(),
+                tx.clone(),
+            )
+            .await?;
+
+            // for each table this operator has, generate this partition's compacted file
+            for (table_char, epoch_files) in state_store.backend.current_files.drain() {
+                for generation in 0..GENERATIONS_TO_COMPACT {
+                    // get just the files for this table
+                    let generation_files: Vec<ParquetStoreData> = epoch_files
+                        .values()
+                        .flatten()
+                        .filter(|file| file.generation == generation)
+                        .cloned()
+                        .collect();
+
+                    if generation_files.len() < min_files_to_compact {
+                        continue;
+                    }
+
+                    info!(
+                        message = "Compacting table partition",
+                        job_id,
+                        operator_id,
+                        table = table_char.to_string(),
+                        epoch,
+                        index,
+                        files = generation_files.len(),
+                    );
+
+                    for file in &generation_files {
+                        backend_data_to_drop.insert(
+                            file.file.clone(),
+                            grpc::BackendData {
+                                backend_data: Some(BackendData::ParquetStore(file.clone())),
+                            },
+                        );
+                    }
+
+                    let compact_parquet_store_data = ParquetBackend::compact_table_partition(
+                        table_char,
+                        task.clone(),
+                        generation,
+                        generation_files,
+                        get_storage_provider().await?,
+                        epoch,
+                        state_store.table_descriptors.get(&table_char).unwrap(),
+                    )
+                    .await;
+
+                    if let Some(p) = compact_parquet_store_data {
+                        backend_data_to_load.push(grpc::BackendData {
+                            backend_data: Some(BackendData::ParquetStore(p)),
+                        });
+                    }
+                }
+            }
+        }
+
+        if !backend_data_to_drop.is_empty() {
+            // CompactionResult is only sent if there is data to drop
+            Ok(Some(CompactionResult {
+                operator_id,
+                backend_data_to_drop: backend_data_to_drop.values().cloned().collect(),
+                backend_data_to_load,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
     async fn load_compacted(&mut self, compaction: CompactionResult) {
         self.writer.load_compacted_data(compaction).await;
     }
@@ -516,7 +741,7 @@ impl ParquetBackend {
                 .await
                 .unwrap_or_else(|_| panic!("unable to find file {} in checkpoint", file.file))
                 .into();
-            for tuple in self.tuples_from_parquet_bytes(bytes, key_range) {
+            for tuple in Self::tuples_from_parquet_bytes(bytes, key_range) {
                 match tuple.operation {
                     DataOperation::Insert => {
                         state_map.insert(tuple.key, tuple.value.unwrap());
@@ -611,126 +836,6 @@ impl ParquetBackend {
         p
     }
 
-    /// Called after a checkpoint is committed
-    pub async fn compact_operator(
-        parallelism: usize,
-        job_id: String,
-        operator_id: String,
-        epoch: u32,
-    ) -> Result<Option<CompactionResult>> {
-        let min_files_to_compact = env::var("MIN_FILES_TO_COMPACT")
-            .unwrap_or_else(|_| "4".to_string())
-            .parse()
-            .unwrap();
-
-        let checkpoint_metadata = Self::load_checkpoint_metadata(&job_id, epoch)
-            .await
-            .unwrap_or_else(|| {
-                panic!(
-                    "missing checkpoint metadata for job {}, epoch {}",
-                    job_id, epoch
-                )
-            });
-
-        let operator_checkpoint_metadata =
-            Self::load_operator_metadata(&job_id, &operator_id, epoch)
-                .await
-                .expect("expect operator metadata to still be present");
-
-        let mut backend_data_to_drop = HashMap::new();
-        let mut backend_data_to_load = vec![]; // one file per partition per table
-
-        // we reduce the range of epochs to a set of compacted files
-        for index in 0..parallelism {
-            let key_range = range_for_server(index, parallelism);
-
-            // construct a theoretical TaskInfo for the purpose of partitioning the data
-            let task = TaskInfo {
-                job_id: job_id.clone(),
-                operator_name: "".to_string(), // TODO: this is not used
-                operator_id: operator_id.clone(),
-                task_index: index,
-                parallelism,
-                key_range: key_range.clone(),
-            };
-            let (tx, _) = channel(10);
-
-            // we must access the data through the state store because
-            // it keeps track of the table -> file relation
-            let mut state_store = StateStore::<ParquetBackend>::from_checkpoint(
-                &task,
-                checkpoint_metadata.clone(),
-                operator_checkpoint_metadata.tables.clone(),
-                tx.clone(),
-            )
-            .await;
-
-            // for each table this operator has, generate this partition's compacted file
-            for (table_char, epoch_files) in state_store.backend.current_files.drain() {
-                for generation in 0..GENERATIONS_TO_COMPACT {
-                    // get just the files for this table
-                    let generation_files: Vec<ParquetStoreData> = epoch_files
-                        .values()
-                        .flatten()
-                        .filter(|file| file.generation == generation)
-                        .cloned()
-                        .collect();
-
-                    if generation_files.len() < min_files_to_compact {
-                        continue;
-                    }
-
-                    info!(
-                        message = "Compacting table partition",
-                        job_id,
-                        operator_id,
-                        table = table_char.to_string(),
-                        epoch,
-                        index,
-                        files = generation_files.len(),
-                    );
-
-                    for file in &generation_files {
-                        backend_data_to_drop.insert(
-                            file.file.clone(),
-                            grpc::BackendData {
-                                backend_data: Some(BackendData::ParquetStore(file.clone())),
-                            },
-                        );
-                    }
-
-                    let compact_parquet_store_data = ParquetBackend::compact_table_partition(
-                        table_char,
-                        task.clone(),
-                        generation,
-                        generation_files,
-                        get_storage_provider().await?,
-                        epoch,
-                        state_store.table_descriptors.get(&table_char).unwrap(),
-                    )
-                    .await;
-
-                    if let Some(p) = compact_parquet_store_data {
-                        backend_data_to_load.push(grpc::BackendData {
-                            backend_data: Some(BackendData::ParquetStore(p)),
-                        });
-                    }
-                }
-            }
-        }
-
-        if !backend_data_to_drop.is_empty() {
-            // CompactionResult is only sent if there is data to drop
-            Ok(Some(CompactionResult {
-                operator_id,
-                backend_data_to_drop: backend_data_to_drop.values().cloned().collect(),
-                backend_data_to_load,
-            }))
-        } else {
-            Ok(None)
-        }
-    }
-
     /// Delete files no longer referenced by the new min epoch
     pub async fn cleanup_operator(
         job_id: String,
@@ -743,13 +848,11 @@ impl ParquetBackend {
                 .await
                 .expect("expect new_min_epoch metadata to still be present")
                 .backend_data
-                .iter()
-                .map(|backend_data| {
-                    let Some(BackendData::ParquetStore(parquet_store)) = &backend_data.backend_data
-                    else {
-                        unreachable!("expect parquet backends")
-                    };
-                    parquet_store.file.clone()
+                .iter_mut()
+                .flat_map(|backend_data| {
+                    state_files(backend_data)
+                        .into_iter()
+                        .map(|file| file.clone())
                 })
                 .collect();
 
@@ -764,15 +867,14 @@ impl ParquetBackend {
             };
 
             // delete any files that are not in the new min epoch
-            for backend_data in metadata.backend_data {
-                let Some(BackendData::ParquetStore(parquet_store)) = &backend_data.backend_data
-                else {
-                    unreachable!("expect parquet backends")
-                };
-                let file = parquet_store.file.clone();
-                if !paths_to_keep.contains(&file) && !deleted_paths.contains(&file) {
-                    deleted_paths.insert(file.clone());
-                    storage_client.delete_if_present(file).await?;
+            for mut backend_data in metadata.backend_data {
+                for file in state_files(&mut backend_data) {
+                    if !paths_to_keep.contains(file.as_str())
+                        && !deleted_paths.contains(file.as_str())
+                    {
+                        deleted_paths.insert(file.clone());
+                        storage_client.delete_if_present(file.as_str()).await?;
+                    }
                 }
             }
         }
@@ -780,9 +882,37 @@ impl ParquetBackend {
         Ok(operator_id)
     }
 
+    /// Copies the files of an operator from under the prefix `from` to the same relative path
+    /// under `to`, updating the metadata to point at the copies
+    async fn copy_files(
+        storage_client: &StorageProvider,
+        operator_metadata: &mut OperatorCheckpointMetadata,
+        from: &str,
+        to: &str,
+    ) -> Result<()> {
+        for backend_data in &mut operator_metadata.backend_data {
+            for file in state_files(backend_data) {
+                let Some(relative_path) = file.strip_prefix(from) else {
+                    bail!(
+                        "state file {} of operator {} is not under {}",
+                        file,
+                        operator_metadata.operator_id,
+                        from
+                    );
+                };
+                let destination = format!("{}{}", to, relative_path);
+                let data = storage_client.get(file.as_str()).await?;
+                storage_client
+                    .put(destination.as_str(), data.to_vec())
+                    .await?;
+                *file = destination;
+            }
+        }
+        Ok(())
+    }
+
     /// Return rows from the given bytes that are in the given key range
-    fn tuples_from_parquet_bytes<K: Key, V: Data>(
-        &self,
+    pub(crate) fn tuples_from_parquet_bytes<K: Key, V: Data>(
         bytes: Vec<u8>,
         range: &RangeInclusive<u64>,
     ) -> Vec<DataTuple<K, V>> {
@@ -858,7 +988,7 @@ impl ParquetBackend {
 
     /// Return rows from the given bytes that are in the given key range,
     /// but without deserializing the key and value.
-    fn blind_tuples_from_parquet_bytes(
+    pub(crate) fn blind_tuples_from_parquet_bytes(
         bytes: Vec<u8>,
         range: &RangeInclusive<u64>,
     ) -> Vec<BlindDataTuple> {
@@ -867,9 +997,34 @@ impl ParquetBackend {
             .build()
             .unwrap();
 
-        let mut result = vec![];
-
         let batches: Vec<RecordBatch> = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        Self::blind_tuples_from_batches(batches, range)
+    }
+
+    /// Return the rows from the given bytes for the key encoded as `key`, without deserializing
+    /// the key and value. The key is compared as the file is read, so the other columns are
+    /// only decoded for the rows of the key.
+    pub(crate) fn blind_tuples_for_key_from_parquet_bytes(
+        bytes: Bytes,
+        key: &[u8],
+    ) -> Result<Vec<BlindDataTuple>> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(bytes)?;
+        let key_column = ProjectionMask::roots(builder.parquet_schema(), [2]);
+        let key = BinaryArray::new_scalar(key);
+        let predicate = ArrowPredicateFn::new(key_column, move |batch| eq(batch.column(0), &key));
+        let reader = builder
+            .with_row_filter(RowFilter::new(vec![Box::new(predicate)]))
+            .build()?;
+
+        let batches: Vec<RecordBatch> = reader.collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::blind_tuples_from_batches(batches, &(0..=u64::MAX)))
+    }
+
+    fn blind_tuples_from_batches(
+        batches: Vec<RecordBatch>,
+        range: &RangeInclusive<u64>,
+    ) -> Vec<BlindDataTuple> {
+        let mut result = vec![];
         for batch in batches {
             let num_rows = batch.num_rows();
             let key_hash_array = batch
@@ -1505,8 +1660,15 @@ impl ParquetFlusher {
 }
 
 pub fn get_storage_env_vars() -> HashMap<String, String> {
-    [S3_REGION_ENV, S3_ENDPOINT_ENV, CHECKPOINT_URL_ENV]
-        .iter()
-        .filter_map(|&var| env::var(var).ok().map(|v| (var.to_string(), v)))
-        .collect()
+    [
+        S3_REGION_ENV,
+        S3_ENDPOINT_ENV,
+        CHECKPOINT_URL_ENV,
+        DISK_STATE_DIR_ENV,
+        DISK_STATE_CACHE_BYTES_ENV,
+        DISK_STATE_CACHE_EVICTION_ENV,
+    ]
+    .iter()
+    .filter_map(|&var| env::var(var).ok().map(|v| (var.to_string(), v)))
+    .collect()
 }
@@ -1,4 +1,5 @@
 use crate::metrics::TABLE_SIZE_GAUGE;
+use crate::tables::{CacheBudget, CacheTracker};
 use crate::BackingStore;
 use arroyo_types::{Data, Key};
 use std::collections::HashMap;
@@ -25,7 +26,7 @@ impl<'a, K: Key, V: Data, S: BackingStore> GlobalKeyedState<'a, K, V, S> {
         self.parquet
             .write_key_value(self.table, &mut key, &mut value)
             .await;
-        self.cache.values.insert(key, value);
+        self.cache.insert(key, value);
 
         TABLE_SIZE_GAUGE
             .with_label_values(&[
@@ -36,33 +37,70 @@ impl<'a, K: Key, V: Data, S: BackingStore> GlobalKeyedState<'a, K, V, S> {
             .set(self.cache.values.len() as f64);
     }
 
-    pub fn get_all(&mut self) -> Vec<&V> {
+    pub async fn get_all(&mut self) -> Vec<&V> {
+        if self.cache.tracker.spilling() {
+            // this reads the whole table into the cache, which is trimmed back to its budget by
+            // the next write or load
+            for (key, value) in self.parquet.get_global_key_values::<K, V>(self.table).await {
+                if !self.cache.values.contains_key(&key) {
+                    self.cache.tracker.insert(&key, &value);
+                    self.cache.values.insert(key, value);
+                }
+            }
+        }
         self.cache.values.values().collect()
     }
 
-    pub fn get(&self, key: &K) -> Option<&V> {
+    pub async fn get(&mut self, key: &K) -> Option<&V> {
+        if self.cache.values.contains_key(key) {
+            self.cache.tracker.touch(key);
+        } else if self.cache.tracker.spilling() {
+            // the entry may have been evicted, so read it back from the backend
+            let value = self
+                .parquet
+                .get_global_value(self.table, &mut key.clone())
+                .await?;
+            self.cache.insert(key.clone(), value);
+        }
         self.cache.values.get(key)
     }
 }
 
 pub struct GlobalKeyedStateCache<K: Key, V: Data> {
     values: HashMap<K, V>,
+    tracker: CacheTracker<K>,
 }
 
 impl<K: Key, V: Data> GlobalKeyedStateCache<K, V> {
+    pub fn new(budget: Option<CacheBudget>) -> Self {
+        Self {
+            values: HashMap::new(),
+            tracker: CacheTracker::new(budget),
+        }
+    }
+
     pub async fn from_checkpoint<S: BackingStore>(backing_store: &S, table: char) -> Self {
-        let mut values = HashMap::new();
-        for (key, value) in backing_store.get_global_key_values(table).await {
-            values.insert(key, value);
+        let mut cache = Self::new(backing_store.cache_budget());
+        // a spilling cache starts out empty and reads entries from the backend as they're needed
+        if !cache.tracker.spilling() {
+            for (key, value) in backing_store.get_global_key_values(table).await {
+                cache.values.insert(key, value);
+            }
+        }
+        cache
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        self.tracker.insert(&key, &value);
+        self.values.insert(key, value);
+        for key in self.tracker.evict() {
+            self.values.remove(&key);
         }
-        Self { values }
     }
 }
 
 impl<K: Key, V: Data> Default for GlobalKeyedStateCache<K, V> {
     fn default() -> Self {
-        Self {
-            values: Default::default(),
-        }
+        Self::new(None)
     }
 }
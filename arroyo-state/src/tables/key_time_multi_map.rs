@@ -1,4 +1,5 @@
 use crate::metrics::TABLE_SIZE_GAUGE;
+use crate::tables::{CacheBudget, CacheTracker};
 use crate::{BackingStore, DataOperation, StateBackend, BINCODE_CONFIG};
 use arroyo_rpc::grpc::{CheckpointMetadata, TableDescriptor, TableType};
 use arroyo_types::{from_micros, Data, Key, TaskInfo};
@@ -24,6 +25,7 @@ impl<'a, K: Key, V: Data, S: BackingStore> KeyTimeMultiMap<'a, K, V, S> {
         }
     }
     pub async fn insert(&mut self, timestamp: SystemTime, mut key: K, mut value: V) {
+        self.load(&mut key).await;
         self.backing_store
             .write_data_tuple(
                 self.table,
@@ -53,7 +55,7 @@ impl<'a, K: Key, V: Data, S: BackingStore> KeyTimeMultiMap<'a, K, V, S> {
         self.backing_store
             .delete_data_value(self.table, timestamp, &mut key, &mut value)
             .await;
-        self.cache.remove_value(&timestamp, &mut key, &mut value);
+        self.cache.remove_value(&timestamp, &key, &value);
     }
 
     pub async fn get_time_range(
@@ -62,6 +64,7 @@ impl<'a, K: Key, V: Data, S: BackingStore> KeyTimeMultiMap<'a, K, V, S> {
         start: SystemTime,
         end: SystemTime,
     ) -> Vec<&V> {
+        self.load(key).await;
         let Some(key_map) = self.cache.values.get(key) else {
             return vec![];
         };
@@ -71,9 +74,26 @@ impl<'a, K: Key, V: Data, S: BackingStore> KeyTimeMultiMap<'a, K, V, S> {
             .collect()
     }
 
+    pub async fn get_time_range_with_timestamps(
+        &mut self,
+        key: &mut K,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Vec<(SystemTime, &V)> {
+        self.load(key).await;
+        let Some(key_map) = self.cache.values.get(key) else {
+            return vec![];
+        };
+        key_map
+            .range(start..end)
+            .flat_map(|(time, values)| values.iter().map(|value| (*time, value)))
+            .collect()
+    }
+
     pub async fn clear_time_range(&mut self, key: &mut K, start: SystemTime, end: SystemTime) {
         if let Some(key_map) = self.cache.values.get_mut(key) {
             key_map.retain(|time, _values| !(start..end).contains(time));
+            self.cache.tracker.insert(key, &*key_map);
         };
         self.backing_store
             .delete_time_range(self.table, key, start..end)
@@ -81,8 +101,29 @@ impl<'a, K: Key, V: Data, S: BackingStore> KeyTimeMultiMap<'a, K, V, S> {
     }
 
     pub async fn expire_entries_before(&mut self, expiration_time: SystemTime) {
-        let keys = self.cache.expire_entries_before(expiration_time);
+        let keys = self.cache.expiring_keys(expiration_time);
         for mut key in keys {
+            if self.cache.values.contains_key(&key) || !self.cache.tracker.spilling() {
+                self.cache.expire_key(&key, expiration_time);
+            } else {
+                // the key has been evicted, so find the values it keeps in the backend rather
+                // than loading it back into the cache
+                let retained = self
+                    .backing_store
+                    .get_key_time_values::<K, V>(self.table, &mut key)
+                    .await
+                    .into_iter()
+                    .map(|(time, _)| time)
+                    .filter(|time| *time >= expiration_time)
+                    .min();
+                if let Some(earliest) = retained {
+                    self.cache
+                        .expirations
+                        .entry(earliest)
+                        .or_default()
+                        .insert(key.clone());
+                }
+            }
             self.backing_store
                 .delete_time_range(
                     self.table,
@@ -97,16 +138,53 @@ impl<'a, K: Key, V: Data, S: BackingStore> KeyTimeMultiMap<'a, K, V, S> {
         &mut self,
         key: &mut K,
     ) -> Option<impl Iterator<Item = (SystemTime, &V)>> {
+        self.load(key).await;
         self.cache.get_all_values_with_timestamps(key)
     }
+
+    /// Makes sure that all of the values of `key` are in the cache, reading them back from the
+    /// backend if the key has been evicted.
+    async fn load(&mut self, key: &mut K) {
+        if self.cache.values.contains_key(key) {
+            self.cache.tracker.touch(key);
+            return;
+        }
+        if !self.cache.tracker.spilling() {
+            return;
+        }
+        let mut key_map: BTreeMap<SystemTime, Vec<V>> = BTreeMap::new();
+        for (time, value) in self
+            .backing_store
+            .get_key_time_values(self.table, key)
+            .await
+        {
+            key_map.entry(time).or_default().push(value);
+        }
+        if key_map.is_empty() {
+            return;
+        }
+        self.cache.tracker.insert(key, &key_map);
+        self.cache.values.insert(key.clone(), key_map);
+        self.cache.evict();
+    }
 }
 
 pub struct KeyTimeMultiMapCache<K: Key, V: Data> {
     pub(crate) values: HashMap<K, BTreeMap<SystemTime, Vec<V>>>,
+    // the earliest time of every key, including those that have been evicted from `values`
     pub(crate) expirations: BTreeMap<SystemTime, HashSet<K>>,
+    tracker: CacheTracker<K>,
 }
 
 impl<K: Key, V: Data> KeyTimeMultiMapCache<K, V> {
+    pub fn new(budget: Option<CacheBudget>) -> Self {
+        Self {
+            values: HashMap::new(),
+            expirations: BTreeMap::new(),
+            tracker: CacheTracker::new(budget),
+        }
+    }
+
     pub async fn from_checkpoint<S: BackingStore>(
         backing_store: &S,
         task_info: &TaskInfo,
@@ -114,6 +192,17 @@ impl<K: Key, V: Data> KeyTimeMultiMapCache<K, V> {
         table_descriptor: &TableDescriptor,
         checkpoint_metadata: &CheckpointMetadata,
     ) -> Self {
+        let budget = backing_store.cache_budget();
+        if budget.is_some() {
+            // a spilling cache starts out empty and reads keys from the backend as they're
+            // needed, but has to know when each of them expires
+            let mut cache = Self::new(budget);
+            for (key, time) in backing_store.get_earliest_key_times::<K>(table).await {
+                cache.expirations.entry(time).or_default().insert(key);
+            }
+            return cache;
+        }
+
         let mut values: HashMap<K, BTreeMap<SystemTime, Vec<V>>> = HashMap::new();
         // TODO: there may be a race here, as the initial checkpoint_metadata might get stale.
         // This is unlikely as this method is only called on start, but should probably be the domain of the backing store.
@@ -191,6 +280,7 @@ impl<K: Key, V: Data> KeyTimeMultiMapCache<K, V> {
         Self {
             values,
             expirations,
+            tracker: CacheTracker::new(None),
         }
     }
 
@@ -208,32 +298,48 @@ impl<K: Key, V: Data> KeyTimeMultiMapCache<K, V> {
         }
     }
 
-    fn expire_entries_before(&mut self, time: SystemTime) -> HashSet<K> {
-        let keys_to_remove: HashSet<_> = self
-            .expirations
-            .range(..time)
-            .flat_map(|(_time, keys)| keys.clone())
-            .collect();
-        for key in keys_to_remove.clone() {
-            let key_data = self.values.get_mut(&key).unwrap();
-            if *key_data.last_key_value().unwrap().0 <= time {
-                self.values.remove(&key);
-            } else {
-                let retained_data = key_data.split_off(&time);
-                let earliest_key = retained_data.first_key_value().unwrap().0;
+    /// Removes and returns the keys with values before `time`, which have to be passed to
+    /// `expire_key` (or have their expiration updated) once their values are available.
+    fn expiring_keys(&mut self, time: SystemTime) -> HashSet<K> {
+        let retained = self.expirations.split_off(&time);
+        std::mem::replace(&mut self.expirations, retained)
+            .into_values()
+            .flatten()
+            .collect()
+    }
+
+    /// Drops the values of `key` from before `time`, and registers when the rest expire
+    fn expire_key(&mut self, key: &K, time: SystemTime) {
+        let Some(key_data) = self.values.get_mut(key) else {
+            return;
+        };
+        let retained_data = key_data.split_off(&time);
+        match retained_data.first_key_value() {
+            Some((earliest, _)) => {
                 self.expirations
-                    .entry(*earliest_key)
+                    .entry(*earliest)
                     .or_default()
-                    .insert(key);
+                    .insert(key.clone());
+                self.tracker.insert(key, &retained_data);
                 *key_data = retained_data;
             }
+            None => {
+                self.values.remove(key);
+                self.tracker.remove(key);
+            }
+        }
+    }
+
+    fn evict(&mut self) {
+        for key in self.tracker.evict() {
+            self.values.remove(&key);
         }
-        keys_to_remove
     }
 
     // Insert a new value for a key at a given timestamp.
     // This potentially updates the earliest timestamp for the key.
     fn insert(&mut self, timestamp: SystemTime, key: K, value: V) {
+        self.tracker.grow(&key, &(timestamp, &value));
         let current_entries = self.values.entry(key.clone()).or_default();
         // If there are no entries for this key, insert the new value.
         // the expiration is the timestamp of the new value.
@@ -257,10 +363,12 @@ impl<K: Key, V: Data> KeyTimeMultiMapCache<K, V> {
                 current_entries.entry(timestamp).or_default().push(value);
             }
         }
+        self.evict();
     }
 
     fn remove_key(&mut self, key: &K) {
         self.values.remove(key);
+        self.tracker.remove(key);
         self.expirations.values_mut().for_each(|keys| {
             keys.remove(key);
         });
@@ -271,15 +379,13 @@ impl<K: Key, V: Data> KeyTimeMultiMapCache<K, V> {
             key_map.entry(*timestamp).and_modify(|values| {
                 values.retain(|stored_value| stored_value != value);
             });
+            self.tracker.insert(key, &*key_map);
         }
     }
 }
 
 impl<K: Key, V: Data> Default for KeyTimeMultiMapCache<K, V> {
     fn default() -> Self {
-        Self {
-            values: Default::default(),
-            expirations: Default::default(),
-        }
+        Self::new(None)
     }
 }
@@ -1,6 +1,6 @@
 use crate::metrics::TABLE_SIZE_GAUGE;
+use crate::tables::{CacheBudget, CacheTracker};
 use crate::BackingStore;
-use arroyo_rpc::grpc::TableType;
 use arroyo_types::{Data, Key};
 use std::collections::HashMap;
 use std::time::SystemTime;
@@ -26,13 +26,7 @@ impl<'a, K: Key, V: Data, S: BackingStore> KeyedState<'a, K, V, S> {
 
     pub async fn insert(&mut self, timestamp: SystemTime, mut key: K, mut value: V) {
         self.backing_state
-            .write_data_tuple(
-                self.table,
-                TableType::TimeKeyMap,
-                timestamp,
-                &mut key,
-                &mut value,
-            )
+            .write_keyed_value(self.table, timestamp, &mut key, &mut value)
             .await;
         self.cache.insert(key, value);
 
@@ -46,42 +40,83 @@ impl<'a, K: Key, V: Data, S: BackingStore> KeyedState<'a, K, V, S> {
     }
 
     pub async fn remove(&mut self, key: &mut K) {
-        self.cache.remove(&key);
-        self.backing_state
-            .delete_time_key(self.table, TableType::Global, SystemTime::UNIX_EPOCH, key)
-            .await;
+        self.cache.remove(key);
+        self.backing_state.delete_keyed_value(self.table, key).await;
     }
 
-    pub fn get(&self, key: &K) -> Option<&V> {
+    pub async fn get(&mut self, key: &mut K) -> Option<&V> {
+        if self.cache.values.contains_key(key) {
+            self.cache.tracker.touch(key);
+        } else if self.cache.tracker.spilling() {
+            // the entry may have been evicted, so read it back from the backend
+            let value = self.backing_state.get_keyed_value(self.table, key).await?;
+            self.cache.insert(key.clone(), value);
+        }
         self.cache.values.get(key)
     }
+
+    /// Returns the entries of the table that match `filter`, including those that have been
+    /// evicted from the cache.
+    pub async fn filter_all<F: FnMut(&K, &V) -> bool + Send>(
+        &mut self,
+        mut filter: F,
+    ) -> Vec<(K, V)> {
+        if !self.cache.tracker.spilling() {
+            return self
+                .cache
+                .values
+                .iter()
+                .filter(|(key, value)| filter(key, value))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+        }
+        // every write goes through to the backend, so it holds the whole table
+        self.backing_state
+            .filter_keyed_values(self.table, filter)
+            .await
+    }
 }
 
 pub struct KeyedStateCache<K: Key, V: Data> {
     values: HashMap<K, V>,
+    tracker: CacheTracker<K>,
 }
 
 impl<K: Key, V: Data> KeyedStateCache<K, V> {
+    pub fn new(budget: Option<CacheBudget>) -> Self {
+        Self {
+            values: HashMap::new(),
+            tracker: CacheTracker::new(budget),
+        }
+    }
+
     pub async fn from_checkpoint<S: BackingStore>(backing_store: &S, table: char) -> Self {
-        let mut values = HashMap::new();
-        for (key, value) in backing_store.get_key_values(table).await {
-            values.insert(key, value);
+        let mut cache = Self::new(backing_store.cache_budget());
+        // a spilling cache starts out empty and reads entries from the backend as they're needed
+        if !cache.tracker.spilling() {
+            for (key, value) in backing_store.get_key_values(table).await {
+                cache.values.insert(key, value);
+            }
         }
-        Self { values }
+        cache
     }
 
     pub fn insert(&mut self, key: K, value: V) {
+        self.tracker.insert(&key, &value);
         self.values.insert(key, value);
+        for key in self.tracker.evict() {
+            self.values.remove(&key);
+        }
     }
+
     pub fn remove(&mut self, key: &K) {
+        self.tracker.remove(key);
         self.values.remove(key);
     }
 }
 
 impl<K: Key, V: Data> Default for KeyedStateCache<K, V> {
     fn default() -> Self {
-        Self {
-            values: Default::default(),
-        }
+        Self::new(None)
     }
 }
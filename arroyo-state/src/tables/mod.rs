@@ -1,5 +1,7 @@
-use crate::DataOperation;
+use crate::{DataOperation, BINCODE_CONFIG};
 use arroyo_rpc::grpc::TableType;
+use arroyo_types::Key;
+use bincode::Encode;
 use std::collections::{BTreeMap, HashMap};
 use std::time::SystemTime;
 
@@ -30,6 +32,136 @@ pub struct BlindDataTuple {
     pub operation: DataOperation,
 }
 
+/// How much of a keyed table a backend that can read evicted entries back lets its cache hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheBudget {
+    pub bytes: usize,
+    pub eviction: EvictionPolicy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// evict the entry that was least recently read or written
+    Lru,
+    /// evict the entry that was loaded into the cache first
+    Fifo,
+}
+
+/// Tracks the approximate size and the eviction order of the entries of a table cache. Without a
+/// budget the cache holds the whole table, and nothing is tracked.
+pub(crate) struct CacheTracker<K: Key> {
+    budget: Option<CacheBudget>,
+    tick: u64,
+    entries: HashMap<K, (u64, usize)>,
+    order: BTreeMap<u64, K>,
+    bytes: usize,
+}
+
+impl<K: Key> CacheTracker<K> {
+    pub(crate) fn new(budget: Option<CacheBudget>) -> Self {
+        Self {
+            budget,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            bytes: 0,
+        }
+    }
+
+    /// whether entries may be evicted, so that a miss has to be read from the backend
+    pub(crate) fn spilling(&self) -> bool {
+        self.budget.is_some()
+    }
+
+    /// records that the entry for `key` now encodes to roughly the size of `data`
+    pub(crate) fn insert<T: Encode>(&mut self, key: &K, data: &T) {
+        if !self.spilling() {
+            return;
+        }
+        let size = encoded_size(key) + encoded_size(data);
+        self.update(key, |bytes| *bytes = size);
+    }
+
+    /// records that `data` was added to the entry for `key`
+    pub(crate) fn grow<T: Encode>(&mut self, key: &K, data: &T) {
+        if !self.spilling() {
+            return;
+        }
+        let size = encoded_size(data);
+        if !self.entries.contains_key(key) {
+            let key_size = encoded_size(key);
+            self.update(key, |bytes| *bytes = key_size + size);
+        } else {
+            self.update(key, |bytes| *bytes += size);
+        }
+    }
+
+    fn update(&mut self, key: &K, f: impl FnOnce(&mut usize)) {
+        match self.entries.get_mut(key) {
+            Some((_, bytes)) => {
+                self.bytes -= *bytes;
+                f(bytes);
+                self.bytes += *bytes;
+                self.touch(key);
+            }
+            None => {
+                let mut size = 0;
+                f(&mut size);
+                self.tick += 1;
+                self.entries.insert(key.clone(), (self.tick, size));
+                self.order.insert(self.tick, key.clone());
+                self.bytes += size;
+            }
+        }
+    }
+
+    pub(crate) fn touch(&mut self, key: &K) {
+        let Some(budget) = self.budget else {
+            return;
+        };
+        if budget.eviction != EvictionPolicy::Lru {
+            return;
+        }
+        if let Some((tick, _)) = self.entries.get_mut(key) {
+            self.order.remove(tick);
+            self.tick += 1;
+            *tick = self.tick;
+            self.order.insert(self.tick, key.clone());
+        }
+    }
+
+    pub(crate) fn remove(&mut self, key: &K) {
+        if let Some((tick, bytes)) = self.entries.remove(key) {
+            self.order.remove(&tick);
+            self.bytes -= bytes;
+        }
+    }
+
+    /// returns the keys to evict to get back under the budget. The most recently inserted entry
+    /// is never evicted, so that it can still be returned to the reader that loaded it.
+    pub(crate) fn evict(&mut self) -> Vec<K> {
+        let Some(budget) = self.budget else {
+            return vec![];
+        };
+        let mut evicted = vec![];
+        while self.bytes > budget.bytes && self.order.len() > 1 {
+            let (_, key) = self.order.pop_first().unwrap();
+            let (_, bytes) = self.entries.remove(&key).unwrap();
+            self.bytes -= bytes;
+            evicted.push(key);
+        }
+        evicted
+    }
+
+    pub(crate) fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+fn encoded_size<T: Encode>(data: &T) -> usize {
+    bincode::encode_to_vec(data, BINCODE_CONFIG).unwrap().len()
+}
+
 impl Compactor {
     pub(crate) fn for_table_type(table_type: TableType) -> Self {
         match table_type {
@@ -133,7 +265,7 @@ impl Compactor {
 
 #[cfg(test)]
 mod test {
-    use crate::tables::{BlindDataTuple, Compactor};
+    use crate::tables::{BlindDataTuple, CacheBudget, CacheTracker, Compactor, EvictionPolicy};
     use crate::{DataOperation, DeleteTimeKeyOperation, DeleteTimeRangeOperation};
     use std::time::{Duration, SystemTime};
 
@@ -246,4 +378,43 @@ mod test {
             Compactor::KeyTimeMultiMap.compact_tuples(tuples_out),
         );
     }
+
+    #[test]
+    fn test_cache_tracker_eviction() {
+        let budget = |eviction| {
+            Some(CacheBudget {
+                // each entry is a one-byte key and a one-byte value
+                bytes: 4,
+                eviction,
+            })
+        };
+
+        let mut lru = CacheTracker::new(budget(EvictionPolicy::Lru));
+        lru.insert(&1u8, &1u8);
+        lru.insert(&2u8, &2u8);
+        lru.touch(&1);
+        assert!(lru.evict().is_empty());
+        lru.insert(&3u8, &3u8);
+        assert_eq!(vec![2], lru.evict());
+        assert_eq!(4, lru.bytes());
+
+        let mut fifo = CacheTracker::new(budget(EvictionPolicy::Fifo));
+        fifo.insert(&1u8, &1u8);
+        fifo.insert(&2u8, &2u8);
+        fifo.touch(&1);
+        fifo.insert(&3u8, &3u8);
+        assert_eq!(vec![1], fifo.evict());
+
+        // an entry larger than the budget stays until another one is loaded
+        let mut tracker = CacheTracker::new(budget(EvictionPolicy::Lru));
+        tracker.insert(&1u8, &vec![0u8; 16]);
+        assert!(tracker.evict().is_empty());
+        tracker.insert(&2u8, &2u8);
+        assert_eq!(vec![1], tracker.evict());
+
+        let mut unbounded = CacheTracker::new(None);
+        unbounded.insert(&1u8, &vec![0u8; 16]);
+        assert!(unbounded.evict().is_empty());
+        assert_eq!(0, unbounded.bytes());
+    }
 }
@@ -1,8 +1,9 @@
 use crate::metrics::TABLE_SIZE_GAUGE;
+use crate::tables::{CacheBudget, CacheTracker};
 use crate::{BackingStore, DataOperation, BINCODE_CONFIG};
 use arroyo_rpc::grpc::{TableDescriptor, TableType};
 use arroyo_types::{Data, Key, TaskInfo};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::time::{Duration, SystemTime};
 
 pub struct TimeKeyMap<'a, K: Key, V: Data, S: BackingStore> {
@@ -19,7 +20,8 @@ impl<'a, K: Key, V: Data + PartialEq, S: BackingStore> TimeKeyMap<'a, K, V, S> {
             cache,
         }
     }
-    pub fn get(&self, timestamp: SystemTime, key: &mut K) -> Option<&V> {
+    pub async fn get(&mut self, timestamp: SystemTime, key: &mut K) -> Option<&V> {
+        self.load(timestamp).await;
         let buffered_value = self
             .cache
             .buffered_values
@@ -58,7 +60,8 @@ impl<'a, K: Key, V: Data + PartialEq, S: BackingStore> TimeKeyMap<'a, K, V, S> {
             .set(self.cache.buffered_values.len() as f64);
     }
 
-    pub fn get_all_for_time(&self, timestamp: SystemTime) -> Vec<(&K, &V)> {
+    pub async fn get_all_for_time(&mut self, timestamp: SystemTime) -> Vec<(&K, &V)> {
+        self.load(timestamp).await;
         match (
             self.cache.buffered_values.get(&timestamp),
             self.cache.persisted_values.get(&timestamp),
@@ -79,6 +82,11 @@ impl<'a, K: Key, V: Data + PartialEq, S: BackingStore> TimeKeyMap<'a, K, V, S> {
         if !self.cache.buffered_values.is_empty() {
             self.flush().await;
         }
+        // this reads the whole table into the cache, which is trimmed back to its budget by the
+        // next load
+        for timestamp in self.cache.persisted_times.clone() {
+            self.fetch(timestamp).await;
+        }
         self.cache
             .persisted_values
             .iter()
@@ -97,18 +105,23 @@ impl<'a, K: Key, V: Data + PartialEq, S: BackingStore> TimeKeyMap<'a, K, V, S> {
             m.remove(k);
         }
 
+        self.load(event_time).await;
         if let Some(m) = self.cache.persisted_values.get_mut(&event_time) {
             self.store
                 .delete_time_key(self.table, TableType::TimeKeyMap, event_time, k)
                 .await;
-            m.remove(k)
+            let removed = m.remove(k);
+            if removed.is_some() {
+                self.cache.tracker.insert(&event_time, &*m);
+            }
+            removed
         } else {
             None
         }
     }
 
     pub fn get_min_time(&self) -> Option<SystemTime> {
-        let persisted_time = self.cache.persisted_values.keys().min();
+        let persisted_time = self.cache.min_persisted_time();
         let buffered_time = self.cache.buffered_values.keys().min();
         match (persisted_time, buffered_time) {
             (None, None) => None,
@@ -117,18 +130,11 @@ impl<'a, K: Key, V: Data + PartialEq, S: BackingStore> TimeKeyMap<'a, K, V, S> {
         }
     }
 
-    pub fn evict_all_before_watermark(&mut self, watermark: SystemTime) -> Vec<(K, V)> {
+    pub async fn evict_all_before_watermark(&mut self, watermark: SystemTime) -> Vec<(K, V)> {
         let mut result = vec![];
-        loop {
-            let persisted_time = self.cache.persisted_values.keys().min();
-            let buffered_time = self.cache.buffered_values.keys().min();
-            let min_time = match (persisted_time, buffered_time) {
-                (None, None) => break,
-                (None, Some(time)) | (Some(time), None) => time,
-                (Some(persisted_time), Some(buffered_time)) => persisted_time.min(buffered_time),
-            };
-            if *min_time <= watermark {
-                result.append(&mut self.evict_for_timestamp(*min_time))
+        while let Some(min_time) = self.get_min_time() {
+            if min_time <= watermark {
+                result.append(&mut self.evict_for_timestamp(min_time).await)
             } else {
                 break;
             }
@@ -136,7 +142,16 @@ impl<'a, K: Key, V: Data + PartialEq, S: BackingStore> TimeKeyMap<'a, K, V, S> {
         result
     }
 
-    pub fn evict_for_timestamp(&mut self, timestamp: SystemTime) -> Vec<(K, V)> {
+    pub async fn evict_for_timestamp(&mut self, timestamp: SystemTime) -> Vec<(K, V)> {
+        self.load(timestamp).await;
+        if self.cache.persisted_times.remove(&timestamp) {
+            // the values are handed to the operator, so they must not be read back from the
+            // backend if the time is loaded again
+            self.cache.tracker.remove(&timestamp);
+            self.store
+                .delete_time_key_values(self.table, timestamp)
+                .await;
+        }
         match (
             self.cache.persisted_values.remove(&timestamp),
             self.cache.buffered_values.remove(&timestamp),
@@ -165,6 +180,9 @@ impl<'a, K: Key, V: Data + PartialEq, S: BackingStore> TimeKeyMap<'a, K, V, S> {
                 break;
             }
             let (time, mut values) = self.cache.buffered_values.pop_first().unwrap();
+            // the persisted values at this time are merged with the buffered ones, so they must
+            // be in the cache
+            self.load(time).await;
             let persisted_map = self.cache.persisted_values.entry(time).or_default();
             let drained = values.drain();
             for (mut key, mut value) in drained {
@@ -179,6 +197,11 @@ impl<'a, K: Key, V: Data + PartialEq, S: BackingStore> TimeKeyMap<'a, K, V, S> {
                     .await;
                 persisted_map.insert(key, value);
             }
+            if self.cache.tracker.spilling() {
+                self.cache.persisted_times.insert(time);
+                self.cache.tracker.insert(&time, &*persisted_map);
+                self.cache.evict();
+            }
         }
     }
 
@@ -188,14 +211,60 @@ impl<'a, K: Key, V: Data + PartialEq, S: BackingStore> TimeKeyMap<'a, K, V, S> {
         };
         self.flush_at_watermark(*timestamp).await;
     }
+
+    /// Makes sure that the persisted values at `timestamp` are in the cache, reading them back
+    /// from the backend if they have been evicted.
+    async fn load(&mut self, timestamp: SystemTime) {
+        if self.fetch(timestamp).await {
+            self.cache.evict();
+        }
+    }
+
+    /// Reads the persisted values at `timestamp` back into the cache if they have been evicted,
+    /// returning whether it did
+    async fn fetch(&mut self, timestamp: SystemTime) -> bool {
+        if self.cache.persisted_values.contains_key(&timestamp) {
+            self.cache.tracker.touch(&timestamp);
+            return false;
+        }
+        if !self.cache.persisted_times.contains(&timestamp) {
+            return false;
+        }
+        let values: HashMap<K, V> = self
+            .store
+            .get_time_key_values(self.table, timestamp)
+            .await
+            .into_iter()
+            .collect();
+        self.cache.tracker.insert(&timestamp, &values);
+        self.cache.persisted_values.insert(timestamp, values);
+        true
+    }
 }
 
+/// Caches the values of a TimeKeyMap by their time. If the backend lets the cache spill, the
+/// values of the times that were least recently used are evicted once it exceeds its budget, and
+/// read back as a whole when they are needed again. Values that haven't been flushed are always
+/// kept in memory.
 pub struct TimeKeyMapCache<K: Key, V: Data> {
     persisted_values: BTreeMap<SystemTime, HashMap<K, V>>,
     buffered_values: BTreeMap<SystemTime, HashMap<K, V>>,
+    // the times with persisted values, including those that have been evicted from
+    // `persisted_values`; only tracked when spilling
+    persisted_times: BTreeSet<SystemTime>,
+    tracker: CacheTracker<SystemTime>,
 }
 
 impl<K: Key, V: Data> TimeKeyMapCache<K, V> {
+    pub fn new(budget: Option<CacheBudget>) -> Self {
+        Self {
+            persisted_values: BTreeMap::new(),
+            buffered_values: BTreeMap::new(),
+            persisted_times: BTreeSet::new(),
+            tracker: CacheTracker::new(budget),
+        }
+    }
+
     pub async fn from_checkpoint<S: BackingStore>(
         backing_store: &S,
         _task_info: &TaskInfo,
@@ -203,10 +272,25 @@ impl<K: Key, V: Data> TimeKeyMapCache<K, V> {
         table_descriptor: &TableDescriptor,
         watermark: Option<SystemTime>,
     ) -> Self {
-        let mut persisted_values: BTreeMap<SystemTime, HashMap<K, V>> = BTreeMap::new();
         let min_valid_time = watermark.map_or(SystemTime::UNIX_EPOCH, |watermark| {
             watermark - Duration::from_micros(table_descriptor.retention_micros)
         });
+
+        let budget = backing_store.cache_budget();
+        if budget.is_some() {
+            // a spilling cache starts out empty and reads times from the backend as they're
+            // needed
+            let mut cache = Self::new(budget);
+            cache.persisted_times = backing_store
+                .get_time_key_times(table)
+                .await
+                .into_iter()
+                .filter(|time| *time >= min_valid_time)
+                .collect();
+            return cache;
+        }
+
+        let mut persisted_values: BTreeMap<SystemTime, HashMap<K, V>> = BTreeMap::new();
         for tuple in backing_store.get_data_tuples(table).await {
             if tuple.timestamp < min_valid_time {
                 continue;
@@ -241,16 +325,27 @@ impl<K: Key, V: Data> TimeKeyMapCache<K, V> {
 
         Self {
             persisted_values,
-            buffered_values: BTreeMap::default(),
+            ..Self::new(None)
+        }
+    }
+
+    fn min_persisted_time(&self) -> Option<&SystemTime> {
+        if self.tracker.spilling() {
+            self.persisted_times.first()
+        } else {
+            self.persisted_values.keys().next()
+        }
+    }
+
+    fn evict(&mut self) {
+        for time in self.tracker.evict() {
+            self.persisted_values.remove(&time);
         }
     }
 }
 
 impl<K: Key, V: Data> Default for TimeKeyMapCache<K, V> {
     fn default() -> Self {
-        Self {
-            persisted_values: BTreeMap::default(),
-            buffered_values: BTreeMap::default(),
-        }
+        Self::new(None)
     }
 }
@@ -259,7 +259,7 @@ impl BackendConfig {
         }))
     }
 
-    fn key(&self) -> Option<&String> {
+    pub fn key(&self) -> Option<&String> {
         match self {
             BackendConfig::S3(s3) => s3.key.as_ref(),
             BackendConfig::GCS(gcs) => gcs.key.as_ref(),
@@ -526,7 +526,9 @@ impl StorageProvider {
         Ok(format!("{}/{}", self.canonical_url, path))
     }
 
-    fn qualify_path(&self, path: &Path) -> Path {
+    /// Prefixes a path relative to this provider's URL with the key of the URL, giving the
+    /// path in the backing store.
+    pub fn qualify_path(&self, path: &Path) -> Path {
         match self.config.key() {
             Some(prefix) => {
                 let prefix_path: Path = prefix.to_string().into();
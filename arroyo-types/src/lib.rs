@@ -108,6 +108,18 @@ pub const S3_ENDPOINT_ENV: &str = "S3_ENDPOINT";
 pub const S3_REGION_ENV: &str = "S3_REGION";
 pub const CHECKPOINT_URL_ENV: &str = "CHECKPOINT_URL";
 
+// state backend configuration
+// Which backend operators keep their state in, either "parquet" (the default) or "disk"; the disk
+// backend requires workers built with the disk-state feature
+pub const STATE_BACKEND_ENV: &str = "STATE_BACKEND";
+// Local directory where the disk state backend keeps the databases of its subtasks
+pub const DISK_STATE_DIR_ENV: &str = "DISK_STATE_DIR";
+// Maximum number of bytes the in-memory table caches of each subtask of an operator may hold in
+// total before entries are evicted to disk; the budget is split evenly between its tables
+pub const DISK_STATE_CACHE_BYTES_ENV: &str = "DISK_STATE_CACHE_BYTES";
+// Which cache entries are evicted first once the budget is exceeded, either "lru" or "fifo"
+pub const DISK_STATE_CACHE_EVICTION_ENV: &str = "DISK_STATE_CACHE_EVICTION";
+
 // compiler service
 pub const ARTIFACT_URL_ENV: &str = "ARTIFACT_URL";
 
@@ -0,0 +1,223 @@
+use std::{
+    fs::File,
+    io::Write,
+    marker::PhantomData,
+    time::{Instant, SystemTime},
+};
+
+use arrow::datatypes::Fields;
+use arroyo_formats::SchemaData;
+use arroyo_rpc::formats::CsvFormat;
+use arroyo_types::Data;
+
+use super::{
+    local::{CurrentFileRecovery, LocalWriter},
+    BatchBufferingWriter, FileSettings, FileSystemTable, FormatSettings, MultiPartWriterStats,
+    TableType,
+};
+
+fn csv_format(config: &FileSystemTable) -> CsvFormat {
+    let TableType::Sink {
+        format_settings:
+            Some(FormatSettings::Csv {
+                delimiter,
+                quote,
+                escape,
+                header,
+                null_literal,
+                ..
+            }),
+        ..
+    } = &config.table_type
+    else {
+        unreachable!("CSV writers require CSV format settings");
+    };
+
+    let default = CsvFormat::default();
+    let char_setting = |s: &Option<String>| s.as_ref().and_then(|s| s.chars().next());
+    CsvFormat {
+        delimiter: char_setting(delimiter).unwrap_or(default.delimiter),
+        quote: char_setting(quote).unwrap_or(default.quote),
+        escape: char_setting(escape),
+        header: header.unwrap_or(default.header),
+        null_literal: null_literal.clone().unwrap_or(default.null_literal),
+    }
+}
+
+// the header row of a new file, if the format has one
+fn header_row(format: &CsvFormat, fields: &Fields) -> Vec<u8> {
+    if !format.header {
+        return vec![];
+    }
+    let mut header = arroyo_formats::csv::header(format, fields);
+    header.extend(b"\n");
+    header
+}
+
+pub struct CsvWriter<D: Data + SchemaData> {
+    format: CsvFormat,
+    fields: Fields,
+    current_buffer: Vec<u8>,
+    target_part_size: usize,
+    phantom: PhantomData<D>,
+}
+
+impl<D: Data + SchemaData> BatchBufferingWriter for CsvWriter<D> {
+    type BatchData = D;
+
+    fn new(config: &FileSystemTable) -> Self {
+        let target_part_size = if let TableType::Sink {
+            file_settings:
+                Some(FileSettings {
+                    target_part_size: Some(target_part_size),
+                    ..
+                }),
+            ..
+        } = config.table_type
+        {
+            target_part_size as usize
+        } else {
+            5 * 1024 * 1024
+        };
+        let format = csv_format(config);
+        let fields = D::schema().fields().clone();
+        Self {
+            current_buffer: header_row(&format, &fields),
+            format,
+            fields,
+            target_part_size,
+            phantom: PhantomData,
+        }
+    }
+
+    fn suffix() -> String {
+        "csv".to_string()
+    }
+
+    fn add_batch_data(&mut self, data: Self::BatchData) -> Option<Vec<u8>> {
+        self.current_buffer.extend(arroyo_formats::csv::to_vec(
+            &self.format,
+            &self.fields,
+            &data,
+        ));
+        self.current_buffer.extend(b"\n");
+        if self.buffer_length() > self.target_part_size {
+            Some(self.evict_current_buffer())
+        } else {
+            None
+        }
+    }
+
+    fn buffer_length(&self) -> usize {
+        self.current_buffer.len()
+    }
+
+    fn evict_current_buffer(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.current_buffer)
+    }
+
+    fn get_trailing_bytes_for_checkpoint(&mut self) -> Option<Vec<u8>> {
+        if self.current_buffer.is_empty() {
+            None
+        } else {
+            Some(self.current_buffer.clone())
+        }
+    }
+
+    fn close(&mut self, final_batch: Option<Self::BatchData>) -> Option<Vec<u8>> {
+        if let Some(final_batch) = final_batch {
+            if let Some(final_batch) = self.add_batch_data(final_batch) {
+                return Some(final_batch);
+            }
+        }
+        if self.current_buffer.is_empty() {
+            None
+        } else {
+            Some(self.evict_current_buffer())
+        }
+    }
+}
+
+pub struct CsvLocalWriter {
+    tmp_path: String,
+    final_path: String,
+    file: File,
+    format: CsvFormat,
+    fields: Fields,
+    stats: Option<MultiPartWriterStats>,
+}
+
+impl<D: Data + SchemaData> LocalWriter<D> for CsvLocalWriter {
+    fn new(tmp_path: String, final_path: String, table_properties: &FileSystemTable) -> Self {
+        let format = csv_format(table_properties);
+        let fields = D::schema().fields().clone();
+        let mut file = File::create(&tmp_path).unwrap();
+        file.write_all(&header_row(&format, &fields)).unwrap();
+        CsvLocalWriter {
+            tmp_path,
+            final_path,
+            file,
+            format,
+            fields,
+            stats: None,
+        }
+    }
+
+    fn file_suffix() -> &'static str {
+        "csv"
+    }
+
+    fn write(&mut self, value: D, timestamp: SystemTime) -> anyhow::Result<()> {
+        if self.stats.is_none() {
+            self.stats = Some(MultiPartWriterStats {
+                bytes_written: 0,
+                parts_written: 0,
+                first_write_at: Instant::now(),
+                last_write_at: Instant::now(),
+                representative_timestamp: timestamp,
+            });
+        } else {
+            self.stats.as_mut().unwrap().last_write_at = Instant::now();
+        }
+        self.file.write_all(&arroyo_formats::csv::to_vec(
+            &self.format,
+            &self.fields,
+            &value,
+        ))?;
+        self.file.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> anyhow::Result<usize> {
+        self.file.flush()?;
+        let size = self.file.metadata()?.len() as usize;
+        self.stats.as_mut().unwrap().bytes_written = size;
+        Ok(size)
+    }
+
+    fn close(&mut self) -> anyhow::Result<super::local::FilePreCommit> {
+        LocalWriter::<D>::sync(self)?;
+        Ok(super::local::FilePreCommit {
+            tmp_file: self.tmp_path.clone(),
+            destination: self.final_path.clone(),
+        })
+    }
+
+    fn checkpoint(&mut self) -> anyhow::Result<Option<super::local::CurrentFileRecovery>> {
+        let bytes_written = LocalWriter::<D>::sync(self)?;
+        if bytes_written > 0 {
+            Ok(Some(CurrentFileRecovery {
+                tmp_file: self.tmp_path.clone(),
+                bytes_written,
+                suffix: None,
+                destination: self.final_path.clone(),
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn stats(&self) -> MultiPartWriterStats {
+        self.stats.clone().unwrap()
+    }
+}
@@ -48,6 +48,74 @@ pub(crate) async fn commit_files_to_delta(
     Ok(Some(new_version))
 }
 
+/// Loads the Delta table at the URL of the storage provider, for reading, or returns None if
+/// there's no table there yet.
+pub(crate) async fn load_table(
+    storage_provider: Arc<StorageProvider>,
+) -> Result<Option<deltalake::DeltaTable>> {
+    let table_path = match storage_provider.config().key() {
+        Some(key) => format!("{}/{}", storage_provider.object_store_base_url(), key),
+        None => storage_provider.object_store_base_url().to_string(),
+    };
+    let storage_options = configure_storage_options(&table_path, storage_provider).await?;
+    match DeltaTableBuilder::from_uri(&table_path)
+        .with_storage_options(storage_options)
+        .load()
+        .await
+    {
+        Ok(table) => Ok(Some(table)),
+        Err(deltalake::DeltaTableError::NotATable(_)) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Returns the current version of the table and the paths of all of its data files in the
+/// storage provider's backing store.
+pub(crate) fn table_files(
+    table: &deltalake::DeltaTable,
+    storage_provider: &StorageProvider,
+) -> Result<(i64, Vec<Path>)> {
+    let files = table
+        .get_state()
+        .files()
+        .iter()
+        .map(|add| data_file_path(storage_provider, &add.path))
+        .collect::<Result<_>>()?;
+    Ok((table.version(), files))
+}
+
+/// Returns the versions of the table after `version`, along with the paths of the data files
+/// that each of them appended. Files that were removed or rewritten without changing the data,
+/// as by compaction, are not included.
+pub(crate) async fn versions_after(
+    table: &deltalake::DeltaTable,
+    storage_provider: &StorageProvider,
+    version: i64,
+) -> Result<Vec<(i64, Vec<Path>)>> {
+    let mut versions = vec![];
+    let mut current = version;
+    while let PeekCommit::New(next, actions) = table.peek_next_commit(current).await? {
+        let files = actions
+            .into_iter()
+            .filter_map(|action| match action {
+                Action::add(add) if add.data_change => {
+                    Some(data_file_path(storage_provider, &add.path))
+                }
+                _ => None,
+            })
+            .collect::<Result<_>>()?;
+        versions.push((next, files));
+        current = next;
+    }
+    Ok(versions)
+}
+
+// the paths of add actions are URL-encoded and relative to the root of the table
+fn data_file_path(storage_provider: &StorageProvider, path: &str) -> Result<Path> {
+    let path = percent_encoding::percent_decode_str(path).decode_utf8()?;
+    Ok(storage_provider.qualify_path(&Path::from(path.trim_start_matches('/'))))
+}
+
 async fn load_or_create_table(
     table_path: &str,
     storage_options: HashMap<String, String>,
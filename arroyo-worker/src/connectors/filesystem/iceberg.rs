@@ -0,0 +1,1864 @@
+use super::{FinishedFile, IcebergCatalog, IcebergSettings, Partitioning};
+use anyhow::{anyhow, bail, Result};
+use apache_avro::types::Value as AvroValue;
+use arrow::datatypes::{DataType, Fields, Schema};
+use arroyo_storage::{BackendConfig, StorageProvider};
+use arroyo_types::{to_millis, Data, Key, Record};
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+use object_store::{path::Path, ObjectStore};
+use parquet::file::footer::{decode_footer, decode_metadata};
+use reqwest::{Method, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tracing::{info, warn};
+use url::Url;
+use uuid::Uuid;
+
+// the number of times a commit that conflicts with another writer is retried
+const MAX_COMMIT_ATTEMPTS: u64 = 10;
+// snapshot summary property that records the last file of a commit, so that a commit that is
+// replayed on recovery can be recognized
+const LAST_FILE_PROPERTY: &str = "arroyo.last-file";
+const NAME_MAPPING_PROPERTY: &str = "schema.name-mapping.default";
+const NULL_PARTITION: &str = "null";
+// Iceberg assigns partition field ids starting after this one
+const PARTITION_FIELD_ID_BASE: i64 = 999;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeTransform {
+    Year,
+    Month,
+    Day,
+    Hour,
+}
+
+impl TimeTransform {
+    /// The transform for the finest date or time field of a time partition pattern; patterns
+    /// finer than an hour are partitioned by hour, as that's the finest Iceberg transform.
+    fn from_pattern(pattern: &str) -> Result<Self> {
+        let specifiers: Vec<char> = pattern
+            .split('%')
+            .skip(1)
+            .filter_map(|s| s.chars().next())
+            .collect();
+        let has = |chars: &[char]| specifiers.iter().any(|c| chars.contains(c));
+
+        if has(&['H', 'k', 'I', 'l', 'M', 'S', 'R', 'T', 'X', 'c', 's']) {
+            Ok(TimeTransform::Hour)
+        } else if has(&['d', 'e', 'j', 'F', 'D', 'x']) {
+            Ok(TimeTransform::Day)
+        } else if has(&['m', 'b', 'B', 'h']) {
+            Ok(TimeTransform::Month)
+        } else if has(&['Y', 'y', 'C', 'G']) {
+            Ok(TimeTransform::Year)
+        } else {
+            bail!(
+                "time partition pattern '{}' does not contain a date or time field",
+                pattern
+            )
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            TimeTransform::Year => "year",
+            TimeTransform::Month => "month",
+            TimeTransform::Day => "day",
+            TimeTransform::Hour => "hour",
+        }
+    }
+
+    /// The partition value, in transform units since the epoch.
+    fn apply(&self, time: DateTime<Utc>) -> i32 {
+        match self {
+            TimeTransform::Year => time.year() - 1970,
+            TimeTransform::Month => (time.year() - 1970) * 12 + time.month0() as i32,
+            TimeTransform::Day => time.timestamp().div_euclid(86400) as i32,
+            TimeTransform::Hour => time.timestamp().div_euclid(3600) as i32,
+        }
+    }
+
+    /// Formats a partition value the way Iceberg does in partition paths.
+    fn to_human(&self, value: i32) -> String {
+        match self {
+            TimeTransform::Year => format!("{:04}", 1970 + value),
+            TimeTransform::Month => format!(
+                "{:04}-{:02}",
+                1970 + value.div_euclid(12),
+                value.rem_euclid(12) + 1
+            ),
+            TimeTransform::Day => (epoch() + chrono::Duration::days(value as i64))
+                .format("%Y-%m-%d")
+                .to_string(),
+            TimeTransform::Hour => format!(
+                "{}-{:02}",
+                TimeTransform::Day.to_human(value.div_euclid(24)),
+                value.rem_euclid(24)
+            ),
+        }
+    }
+
+    fn parse_human(&self, s: &str) -> Result<i32> {
+        Ok(match self {
+            TimeTransform::Year => s.parse::<i32>()? - 1970,
+            TimeTransform::Month => {
+                let (year, month) = s
+                    .split_once('-')
+                    .ok_or_else(|| anyhow!("invalid month partition '{}'", s))?;
+                (year.parse::<i32>()? - 1970) * 12 + month.parse::<i32>()? - 1
+            }
+            TimeTransform::Day => {
+                (NaiveDate::parse_from_str(s, "%Y-%m-%d")? - epoch()).num_days() as i32
+            }
+            TimeTransform::Hour => {
+                let (day, hour) = s
+                    .rsplit_once('-')
+                    .ok_or_else(|| anyhow!("invalid hour partition '{}'", s))?;
+                TimeTransform::Day.parse_human(day)? * 24 + hour.parse::<i32>()?
+            }
+        })
+    }
+}
+
+fn epoch() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct PartitionField {
+    source: String,
+    name: String,
+    transform: Option<TimeTransform>,
+}
+
+impl PartitionField {
+    fn transform_name(&self) -> &'static str {
+        self.transform.map(|t| t.name()).unwrap_or("identity")
+    }
+}
+
+/// The partition fields of the table: the time partition, if there's a time partition
+/// pattern, followed by an identity partition for each of the partition fields.
+fn partition_fields(
+    partitioning: Option<&Partitioning>,
+    settings: &IcebergSettings,
+) -> Result<Vec<PartitionField>> {
+    let Some(partitioning) = partitioning else {
+        return Ok(vec![]);
+    };
+
+    let mut fields = vec![];
+    if let Some(pattern) = &partitioning.time_partition_pattern {
+        let source = settings.time_partition_field.clone().ok_or_else(|| {
+            anyhow!("a time partition field is required to partition Iceberg tables by time")
+        })?;
+        let transform = TimeTransform::from_pattern(pattern)?;
+        fields.push(PartitionField {
+            name: format!("{}_{}", source, transform.name()),
+            source,
+            transform: Some(transform),
+        });
+    }
+    fields.extend(
+        partitioning
+            .partition_fields
+            .iter()
+            .map(|source| PartitionField {
+                source: source.clone(),
+                name: source.clone(),
+                transform: None,
+            }),
+    );
+    Ok(fields)
+}
+
+/// Returns a partitioner that lays out files in Iceberg-style partition directories, like
+/// `created_day=2023-10-01/region=us`.
+pub(crate) fn get_partitioner<K: Key, T: Data + Serialize>(
+    partitioning: Option<Partitioning>,
+    settings: &IcebergSettings,
+) -> Option<Box<dyn Fn(&Record<K, T>) -> Result<String> + Send>> {
+    let fields = partition_fields(partitioning.as_ref(), settings)
+        .expect("invalid partitioning for Iceberg sink");
+    if fields.is_empty() {
+        return None;
+    }
+
+    Some(Box::new(move |record: &Record<K, T>| {
+        partition_path(&fields, &serde_json::to_value(&record.value)?)
+    }))
+}
+
+fn partition_path(fields: &[PartitionField], value: &Value) -> Result<String> {
+    let parts = fields
+        .iter()
+        .map(|field| {
+            let field_value = value
+                .get(&field.source)
+                .ok_or_else(|| anyhow!("field {} not found in value {:?}", field.source, value))?;
+            let partition = match (field.transform, field_value) {
+                (_, Value::Null) => NULL_PARTITION.to_string(),
+                (Some(transform), v) => transform.to_human(transform.apply(json_timestamp(v)?)),
+                (None, Value::String(s)) => escape(s),
+                (None, v) => v.to_string(),
+            };
+            Ok(format!("{}={}", field.name, partition))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(parts.join("/"))
+}
+
+/// Reads a timestamp in any of the ways they are serialized: as epoch millis, as an RFC 3339
+/// string, or as a serde SystemTime.
+fn json_timestamp(value: &Value) -> Result<DateTime<Utc>> {
+    let timestamp = match value {
+        Value::Number(n) => n
+            .as_i64()
+            .and_then(|millis| Utc.timestamp_millis_opt(millis).single()),
+        Value::String(s) => Some(DateTime::parse_from_rfc3339(s)?.with_timezone(&Utc)),
+        Value::Object(o) => o
+            .get("secs_since_epoch")
+            .and_then(|s| s.as_i64())
+            .zip(o.get("nanos_since_epoch").and_then(|n| n.as_u64()))
+            .and_then(|(secs, nanos)| Utc.timestamp_opt(secs, nanos as u32).single()),
+        _ => None,
+    };
+    timestamp.ok_or_else(|| anyhow!("invalid timestamp for time partition: {}", value))
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+            escaped.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                escaped.push_str(&format!("%{:02X}", b));
+            }
+        }
+    }
+    escaped
+}
+
+fn unescape(s: &str) -> Result<String> {
+    let mut bytes = vec![];
+    let mut i = 0;
+    while i < s.len() {
+        if s.as_bytes()[i] == b'%' {
+            let hex = s
+                .get(i + 1..i + 3)
+                .ok_or_else(|| anyhow!("invalid escape in partition '{}'", s))?;
+            bytes.push(u8::from_str_radix(hex, 16)?);
+            i += 3;
+        } else {
+            bytes.push(s.as_bytes()[i]);
+            i += 1;
+        }
+    }
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// Converts an Arrow schema to an Iceberg schema, returning it along with the last assigned
+/// field id.
+fn iceberg_schema(schema: &Schema) -> Result<(Value, i64)> {
+    let mut next_id = 1;
+    let fields = struct_fields(schema.fields(), &mut next_id)?;
+    Ok((
+        json!({
+            "type": "struct",
+            "schema-id": 0,
+            "fields": fields,
+        }),
+        next_id - 1,
+    ))
+}
+
+fn struct_fields(fields: &Fields, next_id: &mut i64) -> Result<Vec<Value>> {
+    // the fields of a struct are numbered before any of their children
+    let ids: Vec<i64> = fields
+        .iter()
+        .map(|_| {
+            *next_id += 1;
+            *next_id - 1
+        })
+        .collect();
+
+    fields
+        .iter()
+        .zip(ids)
+        .map(|(field, id)| {
+            Ok(json!({
+                "id": id,
+                "name": field.name(),
+                "required": !field.is_nullable(),
+                "type": iceberg_type(field.data_type(), next_id)?,
+            }))
+        })
+        .collect()
+}
+
+fn iceberg_type(data_type: &DataType, next_id: &mut i64) -> Result<Value> {
+    Ok(match data_type {
+        DataType::Boolean => json!("boolean"),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 => {
+            json!("int")
+        }
+        DataType::Int64 | DataType::UInt32 | DataType::UInt64 => json!("long"),
+        DataType::Float16 | DataType::Float32 => json!("float"),
+        DataType::Float64 => json!("double"),
+        DataType::Utf8 | DataType::LargeUtf8 => json!("string"),
+        DataType::Binary | DataType::LargeBinary => json!("binary"),
+        DataType::Date32 | DataType::Date64 => json!("date"),
+        DataType::Timestamp(_, None) => json!("timestamp"),
+        DataType::Timestamp(_, Some(_)) => json!("timestamptz"),
+        DataType::Decimal128(precision, scale) => {
+            json!(format!("decimal({}, {})", precision, scale))
+        }
+        DataType::Struct(fields) => json!({
+            "type": "struct",
+            "fields": struct_fields(fields, next_id)?,
+        }),
+        DataType::List(field) | DataType::LargeList(field) => {
+            let element_id = *next_id;
+            *next_id += 1;
+            json!({
+                "type": "list",
+                "element-id": element_id,
+                "element": iceberg_type(field.data_type(), next_id)?,
+                "element-required": !field.is_nullable(),
+            })
+        }
+        other => bail!("{:?} columns are not supported by the Iceberg sink", other),
+    })
+}
+
+/// Maps the field ids of a schema to column names, which lets readers resolve the columns of
+/// the parquet files, as they are written without field ids.
+fn name_mapping(fields: &Value) -> Vec<Value> {
+    fields
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|field| {
+            let mut mapping = json!({
+                "field-id": field["id"],
+                "names": [field["name"]],
+            });
+            if let Some(nested) = nested_name_mapping(&field["type"]) {
+                mapping["fields"] = Value::Array(nested);
+            }
+            mapping
+        })
+        .collect()
+}
+
+fn nested_name_mapping(iceberg_type: &Value) -> Option<Vec<Value>> {
+    match iceberg_type["type"].as_str() {
+        Some("struct") => Some(name_mapping(&iceberg_type["fields"])),
+        Some("list") => {
+            let mut element = json!({
+                "field-id": iceberg_type["element-id"],
+                "names": ["element", "item"],
+            });
+            if let Some(nested) = nested_name_mapping(&iceberg_type["element"]) {
+                element["fields"] = Value::Array(nested);
+            }
+            Some(vec![element])
+        }
+        _ => None,
+    }
+}
+
+fn current_schema(metadata: &Value) -> Result<&Value> {
+    let id = &metadata["current-schema-id"];
+    metadata["schemas"]
+        .as_array()
+        .and_then(|schemas| schemas.iter().find(|s| &s["schema-id"] == id))
+        .ok_or_else(|| anyhow!("Iceberg table metadata has no current schema"))
+}
+
+fn default_spec(metadata: &Value) -> Result<&Value> {
+    let id = &metadata["default-spec-id"];
+    metadata["partition-specs"]
+        .as_array()
+        .and_then(|specs| specs.iter().find(|s| &s["spec-id"] == id))
+        .ok_or_else(|| anyhow!("Iceberg table metadata has no default partition spec"))
+}
+
+fn schema_field<'a>(schema: &'a Value, name: &str) -> Result<&'a Value> {
+    schema["fields"]
+        .as_array()
+        .and_then(|fields| fields.iter().find(|f| f["name"] == name))
+        .ok_or_else(|| anyhow!("column '{}' is not in the Iceberg table's schema", name))
+}
+
+fn current_snapshot(metadata: &Value) -> Option<&Value> {
+    let id = metadata["current-snapshot-id"]
+        .as_i64()
+        .filter(|id| *id >= 0)?;
+    metadata["snapshots"]
+        .as_array()?
+        .iter()
+        .find(|s| s["snapshot-id"].as_i64() == Some(id))
+}
+
+/// Finds the snapshot that committed the files ending in `last_file`, if there is one.
+fn committed_snapshot(metadata: &Value, last_file: &str) -> Option<i64> {
+    metadata["snapshots"]
+        .as_array()?
+        .iter()
+        .find(|s| s["summary"][LAST_FILE_PROPERTY] == last_file)
+        .and_then(|s| s["snapshot-id"].as_i64())
+}
+
+fn push(metadata: &mut Value, key: &str, item: Value) {
+    if !metadata[key].is_array() {
+        metadata[key] = json!([]);
+    }
+    metadata[key].as_array_mut().unwrap().push(item);
+}
+
+/// Applies a new snapshot to the table metadata, making it the current snapshot of the main
+/// branch.
+fn add_snapshot(metadata: &mut Value, snapshot: Value, previous_metadata_file: Option<String>) {
+    let timestamp = snapshot["timestamp-ms"].clone();
+    let id = snapshot["snapshot-id"].clone();
+
+    metadata["last-sequence-number"] = snapshot["sequence-number"].clone();
+    metadata["last-updated-ms"] = timestamp.clone();
+    metadata["current-snapshot-id"] = id.clone();
+    metadata["refs"]["main"] = json!({"snapshot-id": id, "type": "branch"});
+    push(metadata, "snapshots", snapshot);
+    push(
+        metadata,
+        "snapshot-log",
+        json!({"timestamp-ms": timestamp, "snapshot-id": id}),
+    );
+    if let Some(file) = previous_metadata_file {
+        push(
+            metadata,
+            "metadata-log",
+            json!({"timestamp-ms": timestamp, "metadata-file": file}),
+        );
+    }
+}
+
+fn write_long(buf: &mut Vec<u8>, n: i64) {
+    let mut z = ((n << 1) ^ (n >> 63)) as u64;
+    loop {
+        let b = (z & 0x7f) as u8;
+        z >>= 7;
+        if z == 0 {
+            buf.push(b);
+            return;
+        }
+        buf.push(b | 0x80);
+    }
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_long(buf, bytes.len() as i64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Writes an Avro object container file. This is done by hand rather than with the avro
+/// writer so that the schema in the header keeps its `field-id` attributes, which Iceberg
+/// readers depend on.
+fn write_avro(
+    schema: &Value,
+    metadata: &[(&str, String)],
+    records: Vec<AvroValue>,
+) -> Result<Vec<u8>> {
+    let schema = schema.to_string();
+    let parsed = apache_avro::Schema::parse_str(&schema)?;
+
+    let mut out = b"Obj\x01".to_vec();
+    write_long(&mut out, metadata.len() as i64 + 2);
+    write_bytes(&mut out, b"avro.schema");
+    write_bytes(&mut out, schema.as_bytes());
+    write_bytes(&mut out, b"avro.codec");
+    write_bytes(&mut out, b"null");
+    for (key, value) in metadata {
+        write_bytes(&mut out, key.as_bytes());
+        write_bytes(&mut out, value.as_bytes());
+    }
+    write_long(&mut out, 0);
+
+    let sync = *Uuid::new_v4().as_bytes();
+    out.extend_from_slice(&sync);
+
+    if !records.is_empty() {
+        let count = records.len();
+        let mut block = vec![];
+        for record in records {
+            block.extend(apache_avro::to_avro_datum(&parsed, record)?);
+        }
+        write_long(&mut out, count as i64);
+        write_long(&mut out, block.len() as i64);
+        out.extend(block);
+        out.extend_from_slice(&sync);
+    }
+
+    Ok(out)
+}
+
+fn optional(value: Option<AvroValue>) -> AvroValue {
+    match value {
+        Some(value) => AvroValue::Union(1, Box::new(value)),
+        None => AvroValue::Union(0, Box::new(AvroValue::Null)),
+    }
+}
+
+fn manifest_entry_schema(partition_fields: Vec<Value>) -> Value {
+    let optional_long = |name: &str, id: i64| json!({"name": name, "type": ["null", "long"], "default": null, "field-id": id});
+    json!({
+        "type": "record",
+        "name": "manifest_entry",
+        "fields": [
+            {"name": "status", "type": "int", "field-id": 0},
+            optional_long("snapshot_id", 1),
+            optional_long("sequence_number", 3),
+            optional_long("file_sequence_number", 4),
+            {"name": "data_file", "field-id": 2, "type": {
+                "type": "record",
+                "name": "r2",
+                "fields": [
+                    {"name": "content", "type": "int", "field-id": 134},
+                    {"name": "file_path", "type": "string", "field-id": 100},
+                    {"name": "file_format", "type": "string", "field-id": 101},
+                    {"name": "partition", "field-id": 102, "type": {
+                        "type": "record",
+                        "name": "r102",
+                        "fields": partition_fields,
+                    }},
+                    {"name": "record_count", "type": "long", "field-id": 103},
+                    {"name": "file_size_in_bytes", "type": "long", "field-id": 104},
+                ],
+            }},
+        ],
+    })
+}
+
+// the fields of a v2 manifest list entry, with their types and field ids
+const MANIFEST_FILE_FIELDS: &[(&str, &str, i64)] = &[
+    ("manifest_path", "string", 500),
+    ("manifest_length", "long", 501),
+    ("partition_spec_id", "int", 502),
+    ("content", "int", 517),
+    ("sequence_number", "long", 515),
+    ("min_sequence_number", "long", 516),
+    ("added_snapshot_id", "long", 503),
+    ("added_files_count", "int", 504),
+    ("existing_files_count", "int", 505),
+    ("deleted_files_count", "int", 506),
+    ("added_rows_count", "long", 512),
+    ("existing_rows_count", "long", 513),
+    ("deleted_rows_count", "long", 514),
+];
+
+fn manifest_list_schema() -> Value {
+    let fields: Vec<Value> = MANIFEST_FILE_FIELDS
+        .iter()
+        .map(|(name, avro_type, id)| json!({"name": name, "type": avro_type, "field-id": id}))
+        .collect();
+    json!({
+        "type": "record",
+        "name": "manifest_file",
+        "fields": fields,
+    })
+}
+
+/// Carries an entry of the parent snapshot's manifest list forward, keeping the fields that
+/// are written by this sink.
+fn manifest_file_record(value: AvroValue) -> Result<AvroValue> {
+    let AvroValue::Record(fields) = value else {
+        bail!("invalid manifest list entry: {:?}", value);
+    };
+    let mut fields: HashMap<String, AvroValue> = fields.into_iter().collect();
+
+    let fields = MANIFEST_FILE_FIELDS
+        .iter()
+        .map(|(name, _, _)| {
+            let value = match fields.remove(*name) {
+                Some(AvroValue::Union(_, value)) => *value,
+                Some(value) => value,
+                None => bail!("manifest list entry is missing {}", name),
+            };
+            Ok((name.to_string(), value))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(AvroValue::Record(fields))
+}
+
+struct DataFile {
+    path: String,
+    partition: Option<String>,
+    record_count: i64,
+    size: i64,
+}
+
+struct ManifestFile {
+    path: String,
+    length: i64,
+    spec_id: i64,
+    files: i64,
+    rows: i64,
+}
+
+struct LoadedTable {
+    metadata: Value,
+    // the metadata version of hadoop tables, where 0 is a table that doesn't exist yet
+    version: i64,
+}
+
+struct RestCatalog {
+    client: reqwest::Client,
+    token: Option<String>,
+    namespace: Vec<String>,
+    table: String,
+    namespaces_url: Url,
+    tables_url: Url,
+    table_url: Url,
+}
+
+async fn rest_request(
+    client: &reqwest::Client,
+    token: Option<&String>,
+    method: Method,
+    url: Url,
+    body: Option<&Value>,
+) -> Result<(StatusCode, Value)> {
+    let mut request = client
+        .request(method, url)
+        .header("Content-Type", "application/json");
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    if let Some(body) = body {
+        request = request.body(serde_json::to_vec(body)?);
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+    let bytes = response.bytes().await?;
+    let body = if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&bytes)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).to_string()))
+    };
+    Ok((status, body))
+}
+
+impl RestCatalog {
+    async fn new(settings: &IcebergSettings) -> Result<Self> {
+        let uri = settings
+            .catalog_uri
+            .as_ref()
+            .ok_or_else(|| anyhow!("no uri for Iceberg REST catalog"))?
+            .trim_end_matches('/');
+        let namespace: Vec<String> = settings
+            .namespace
+            .as_ref()
+            .ok_or_else(|| anyhow!("no namespace for Iceberg REST catalog"))?
+            .split('.')
+            .map(|s| s.to_string())
+            .collect();
+        let table = settings
+            .table_name
+            .clone()
+            .ok_or_else(|| anyhow!("no table for Iceberg REST catalog"))?;
+        let client = reqwest::Client::new();
+
+        let mut config_url = Url::parse(&format!("{}/v1/config", uri))?;
+        if let Some(warehouse) = &settings.warehouse {
+            config_url
+                .query_pairs_mut()
+                .append_pair("warehouse", warehouse);
+        }
+        let (status, config) = rest_request(
+            &client,
+            settings.token.as_ref(),
+            Method::GET,
+            config_url,
+            None,
+        )
+        .await?;
+        if !status.is_success() {
+            bail!(
+                "failed to get config from Iceberg REST catalog ({}): {}",
+                status,
+                config
+            );
+        }
+
+        // the catalog may route requests for the warehouse through a path prefix
+        let mut base = Url::parse(&format!("{}/v1", uri))?;
+        if let Some(prefix) = config["overrides"]["prefix"]
+            .as_str()
+            .or(config["defaults"]["prefix"].as_str())
+        {
+            base.path_segments_mut()
+                .map_err(|_| anyhow!("invalid Iceberg REST catalog uri '{}'", uri))?
+                .extend(prefix.split('/'));
+        }
+
+        let mut namespaces_url = base.clone();
+        namespaces_url
+            .path_segments_mut()
+            .map_err(|_| anyhow!("invalid Iceberg REST catalog uri '{}'", uri))?
+            .push("namespaces");
+
+        let mut tables_url = namespaces_url.clone();
+        tables_url
+            .path_segments_mut()
+            .map_err(|_| anyhow!("invalid Iceberg REST catalog uri '{}'", uri))?
+            .push(&namespace.join("\u{1f}"))
+            .push("tables");
+
+        let mut table_url = tables_url.clone();
+        table_url
+            .path_segments_mut()
+            .map_err(|_| anyhow!("invalid Iceberg REST catalog uri '{}'", uri))?
+            .push(&table);
+
+        Ok(Self {
+            client,
+            token: settings.token.clone(),
+            namespace,
+            table,
+            namespaces_url,
+            tables_url,
+            table_url,
+        })
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        url: &Url,
+        body: Option<&Value>,
+    ) -> Result<(StatusCode, Value)> {
+        rest_request(&self.client, self.token.as_ref(), method, url.clone(), body).await
+    }
+
+    async fn load(&self) -> Result<Option<Value>> {
+        let (status, body) = self.request(Method::GET, &self.table_url, None).await?;
+        match status {
+            StatusCode::OK => Ok(Some(body["metadata"].clone())),
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => bail!(
+                "failed to load Iceberg table {} ({}): {}",
+                self.table,
+                status,
+                body
+            ),
+        }
+    }
+
+    async fn create(&self, create_request: Value) -> Result<Value> {
+        let (status, body) = self
+            .request(
+                Method::POST,
+                &self.namespaces_url,
+                Some(&json!({"namespace": self.namespace})),
+            )
+            .await?;
+        if !status.is_success() && status != StatusCode::CONFLICT {
+            bail!(
+                "failed to create Iceberg namespace {} ({}): {}",
+                self.namespace.join("."),
+                status,
+                body
+            );
+        }
+
+        let (status, body) = self
+            .request(Method::POST, &self.tables_url, Some(&create_request))
+            .await?;
+        match status {
+            StatusCode::OK => Ok(body["metadata"].clone()),
+            // another subtask or writer created it first
+            StatusCode::CONFLICT => self
+                .load()
+                .await?
+                .ok_or_else(|| anyhow!("Iceberg table {} was not found", self.table)),
+            _ => bail!(
+                "failed to create Iceberg table {} ({}): {}",
+                self.table,
+                status,
+                body
+            ),
+        }
+    }
+
+    async fn commit(&self, metadata: &Value, snapshot: Value) -> Result<bool> {
+        let parent = current_snapshot(metadata)
+            .map(|s| s["snapshot-id"].clone())
+            .unwrap_or(Value::Null);
+        let snapshot_id = snapshot["snapshot-id"].clone();
+
+        let mut updates = vec![];
+        if metadata["properties"][NAME_MAPPING_PROPERTY].is_null() {
+            let mapping = name_mapping(&current_schema(metadata)?["fields"]);
+            updates.push(json!({
+                "action": "set-properties",
+                "updates": {NAME_MAPPING_PROPERTY: Value::Array(mapping).to_string()},
+            }));
+        }
+        updates.push(json!({"action": "add-snapshot", "snapshot": snapshot}));
+        updates.push(json!({
+            "action": "set-snapshot-ref",
+            "ref-name": "main",
+            "type": "branch",
+            "snapshot-id": snapshot_id,
+        }));
+
+        let request = json!({
+            "identifier": {"namespace": self.namespace, "name": self.table},
+            "requirements": [
+                {"type": "assert-table-uuid", "uuid": metadata["table-uuid"]},
+                {"type": "assert-ref-snapshot-id", "ref": "main", "snapshot-id": parent},
+            ],
+            "updates": updates,
+        });
+
+        let (status, body) = self
+            .request(Method::POST, &self.table_url, Some(&request))
+            .await?;
+        match status {
+            StatusCode::OK => Ok(true),
+            StatusCode::CONFLICT => Ok(false),
+            _ => bail!(
+                "failed to commit to Iceberg table {} ({}): {}",
+                self.table,
+                status,
+                body
+            ),
+        }
+    }
+}
+
+enum Catalog {
+    Hadoop,
+    Rest(RestCatalog),
+}
+
+/// The files of an Iceberg table in the storage of the filesystem connector.
+struct TableStorage {
+    store: Arc<dyn ObjectStore>,
+    table_path: Path,
+    // the URI of the root of the object store, which paths are made absolute against
+    base_uri: String,
+}
+
+impl TableStorage {
+    fn new(table_path: Path, storage_provider: &StorageProvider) -> Self {
+        let base_uri = match storage_provider.config() {
+            BackendConfig::S3(_) => storage_provider.object_store_base_url().to_string(),
+            BackendConfig::GCS(_) => format!(
+                "gs://{}",
+                storage_provider
+                    .object_store_base_url()
+                    .trim_start_matches("https://")
+                    .trim_end_matches(".storage.googleapis.com")
+            ),
+            BackendConfig::Local(local) => format!("file://{}", local.path.trim_end_matches('/')),
+        };
+
+        Self {
+            store: storage_provider.get_backing_store(),
+            table_path,
+            base_uri,
+        }
+    }
+
+    fn uri(&self, path: &str) -> String {
+        format!("{}/{}", self.base_uri, path)
+    }
+
+    fn path_from_uri(&self, uri: &str) -> Result<Path> {
+        // other writers may use the hadoop forms of these schemes
+        let uri = if let Some(path) = uri.strip_prefix("s3a://") {
+            format!("s3://{}", path)
+        } else if uri.starts_with("file:/") && !uri.starts_with("file://") {
+            format!("file://{}", uri.trim_start_matches("file:"))
+        } else {
+            uri.to_string()
+        };
+
+        let path = uri
+            .strip_prefix(&format!("{}/", self.base_uri))
+            .ok_or_else(|| anyhow!("{} is not in the storage of the Iceberg table", uri))?;
+        Ok(Path::parse(path)?)
+    }
+
+    fn metadata_path(&self, name: &str) -> Path {
+        Path::from(format!("{}/metadata/{}", self.table_path, name))
+    }
+
+    fn version_path(&self, version: i64) -> Path {
+        self.metadata_path(&format!("v{}.metadata.json", version))
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        match self.store.head(path).await {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The latest metadata version of a table in a hadoop catalog, or 0 if there is no table.
+    async fn hadoop_version(&self) -> Result<i64> {
+        let mut version = match self
+            .store
+            .get(&self.metadata_path("version-hint.text"))
+            .await
+        {
+            Ok(hint) => String::from_utf8(hint.bytes().await?.to_vec())?
+                .trim()
+                .parse()?,
+            Err(object_store::Error::NotFound { .. }) => 0,
+            Err(e) => return Err(e.into()),
+        };
+        // the hint is written after the metadata, so it may be behind
+        while self.exists(&self.version_path(version + 1)).await? {
+            version += 1;
+        }
+        Ok(version)
+    }
+
+    async fn read_metadata(&self, version: i64) -> Result<Value> {
+        let bytes = self
+            .store
+            .get(&self.version_path(version))
+            .await?
+            .bytes()
+            .await?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    async fn read_avro(&self, uri: &str) -> Result<Vec<AvroValue>> {
+        let bytes = self
+            .store
+            .get(&self.path_from_uri(uri)?)
+            .await?
+            .bytes()
+            .await?;
+        apache_avro::Reader::new(&bytes[..])?
+            .map(|value| Ok(value?))
+            .collect()
+    }
+}
+
+struct IcebergTable {
+    storage: TableStorage,
+    catalog: Catalog,
+    partition_fields: Vec<PartitionField>,
+    schema: Schema,
+}
+
+impl IcebergTable {
+    async fn new(
+        table_path: Path,
+        storage_provider: &StorageProvider,
+        settings: &IcebergSettings,
+        partitioning: Option<&Partitioning>,
+        schema: Schema,
+    ) -> Result<Self> {
+        let catalog = match settings.catalog {
+            IcebergCatalog::Hadoop => Catalog::Hadoop,
+            IcebergCatalog::Rest => Catalog::Rest(RestCatalog::new(settings).await?),
+        };
+
+        Ok(Self {
+            storage: TableStorage::new(table_path, storage_provider),
+            catalog,
+            partition_fields: partition_fields(partitioning, settings)?,
+            schema,
+        })
+    }
+
+    fn partition_spec(&self, schema: &Value) -> Result<Value> {
+        let fields = self
+            .partition_fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                Ok(json!({
+                    "source-id": schema_field(schema, &field.source)?["id"],
+                    "field-id": PARTITION_FIELD_ID_BASE + 1 + i as i64,
+                    "name": field.name,
+                    "transform": field.transform_name(),
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(json!({"spec-id": 0, "fields": fields}))
+    }
+
+    fn new_table_metadata(&self) -> Result<Value> {
+        let (schema, last_column_id) = iceberg_schema(&self.schema)?;
+        let spec = self.partition_spec(&schema)?;
+        let mapping = Value::Array(name_mapping(&schema["fields"])).to_string();
+
+        Ok(json!({
+            "format-version": 2,
+            "table-uuid": Uuid::new_v4().to_string(),
+            "location": self.storage.uri(self.storage.table_path.as_ref()),
+            "last-sequence-number": 0,
+            "last-updated-ms": to_millis(SystemTime::now()),
+            "last-column-id": last_column_id,
+            "current-schema-id": 0,
+            "schemas": [schema],
+            "default-spec-id": 0,
+            "partition-specs": [spec],
+            "last-partition-id": PARTITION_FIELD_ID_BASE + self.partition_fields.len() as i64,
+            "default-sort-order-id": 0,
+            "sort-orders": [{"order-id": 0, "fields": []}],
+            "properties": {
+                NAME_MAPPING_PROPERTY: mapping,
+                "write.format.default": "parquet",
+            },
+            "current-snapshot-id": -1,
+            "refs": {},
+            "snapshots": [],
+            "snapshot-log": [],
+            "metadata-log": [],
+        }))
+    }
+
+    async fn load(&self) -> Result<LoadedTable> {
+        match &self.catalog {
+            Catalog::Hadoop => {
+                let version = self.storage.hadoop_version().await?;
+                let metadata = if version == 0 {
+                    self.new_table_metadata()?
+                } else {
+                    self.storage.read_metadata(version).await?
+                };
+                Ok(LoadedTable { metadata, version })
+            }
+            Catalog::Rest(catalog) => {
+                let metadata = match catalog.load().await? {
+                    Some(metadata) => metadata,
+                    None => {
+                        let metadata = self.new_table_metadata()?;
+                        catalog
+                            .create(json!({
+                                "name": catalog.table,
+                                "location": metadata["location"],
+                                "schema": metadata["schemas"][0],
+                                "partition-spec": metadata["partition-specs"][0],
+                                "write-order": metadata["sort-orders"][0],
+                                "stage-create": false,
+                                "properties": metadata["properties"],
+                            }))
+                            .await?
+                    }
+                };
+                Ok(LoadedTable {
+                    metadata,
+                    version: 0,
+                })
+            }
+        }
+    }
+
+    /// Checks that the files written by this sink can be committed to the table.
+    fn validate(&self, metadata: &Value) -> Result<()> {
+        if metadata["format-version"].as_i64() != Some(2) {
+            bail!(
+                "the Iceberg sink only supports v2 tables, but the table has format version {}",
+                metadata["format-version"]
+            );
+        }
+
+        let schema = current_schema(metadata)?;
+        for field in self.schema.fields() {
+            schema_field(schema, field.name())?;
+        }
+
+        let table_fields: Vec<(&str, &str)> = default_spec(metadata)?["fields"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|f| {
+                (
+                    f["name"].as_str().unwrap_or_default(),
+                    f["transform"].as_str().unwrap_or_default(),
+                )
+            })
+            .collect();
+        let fields: Vec<(&str, &str)> = self
+            .partition_fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.transform_name()))
+            .collect();
+        if table_fields != fields {
+            bail!(
+                "the Iceberg table is partitioned by {:?}, but the sink is partitioned by {:?}",
+                table_fields,
+                fields
+            );
+        }
+        Ok(())
+    }
+
+    async fn data_file(&self, file: &FinishedFile) -> Result<DataFile> {
+        let path = Path::parse(&file.filename)?;
+        if file.size < 8 {
+            bail!("{} is too small to be a parquet file", file.filename);
+        }
+
+        let footer = self
+            .storage
+            .store
+            .get_range(&path, file.size - 8..file.size)
+            .await?;
+        let footer: &[u8; 8] = footer.as_ref().try_into()?;
+        let metadata_len = decode_footer(footer)?;
+        let metadata = self
+            .storage
+            .store
+            .get_range(&path, file.size - 8 - metadata_len..file.size - 8)
+            .await?;
+        let record_count = decode_metadata(&metadata)?.file_metadata().num_rows();
+
+        // files written by the local sink don't record their partition, so it's taken from
+        // their directory within the table
+        let partition = file.partition.clone().or_else(|| {
+            file.filename
+                .strip_prefix(&format!("{}/", self.storage.table_path))
+                .and_then(|relative| relative.rsplit_once('/'))
+                .map(|(dir, _)| dir.to_string())
+        });
+
+        Ok(DataFile {
+            path: file.filename.clone(),
+            partition,
+            record_count,
+            size: file.size as i64,
+        })
+    }
+
+    fn partition_values(
+        &self,
+        file: &DataFile,
+        types: &[&str],
+    ) -> Result<Vec<(String, AvroValue)>> {
+        let raw: HashMap<&str, &str> = file
+            .partition
+            .iter()
+            .flat_map(|p| p.split('/'))
+            .filter_map(|part| part.split_once('='))
+            .collect();
+
+        self.partition_fields
+            .iter()
+            .zip(types)
+            .map(|(field, iceberg_type)| {
+                let raw = raw.get(field.name.as_str()).ok_or_else(|| {
+                    anyhow!("{} has no value for partition {}", file.path, field.name)
+                })?;
+                let value = if *raw == NULL_PARTITION {
+                    None
+                } else {
+                    Some(match (field.transform, *iceberg_type) {
+                        (Some(transform), _) => AvroValue::Int(transform.parse_human(raw)?),
+                        (None, "int") => AvroValue::Int(raw.parse()?),
+                        (None, "long") => AvroValue::Long(raw.parse()?),
+                        (None, "float") => AvroValue::Float(raw.parse()?),
+                        (None, "double") => AvroValue::Double(raw.parse()?),
+                        (None, "boolean") => AvroValue::Boolean(raw.parse()?),
+                        (None, _) => AvroValue::String(unescape(raw)?),
+                    })
+                };
+                Ok((field.name.clone(), optional(value)))
+            })
+            .collect()
+    }
+
+    async fn write_manifest(&self, metadata: &Value, files: &[DataFile]) -> Result<ManifestFile> {
+        let schema = current_schema(metadata)?;
+        let spec = default_spec(metadata)?;
+
+        let mut partition_fields = vec![];
+        let mut types = vec![];
+        for (field, spec_field) in self
+            .partition_fields
+            .iter()
+            .zip(spec["fields"].as_array().into_iter().flatten())
+        {
+            let iceberg_type = match field.transform {
+                Some(_) => "int",
+                None => schema_field(schema, &field.source)?["type"]
+                    .as_str()
+                    .filter(|t| {
+                        matches!(
+                            *t,
+                            "int" | "long" | "float" | "double" | "boolean" | "string"
+                        )
+                    })
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "partition field '{}' must be a number, boolean or string",
+                            field.source
+                        )
+                    })?,
+            };
+            partition_fields.push(json!({
+                "name": field.name,
+                "type": ["null", iceberg_type],
+                "default": null,
+                "field-id": spec_field["field-id"],
+            }));
+            types.push(iceberg_type);
+        }
+
+        // the snapshot id and sequence numbers of added files are inherited from the
+        // manifest list, so the manifest can be reused if the commit has to be retried
+        let entries = files
+            .iter()
+            .map(|file| {
+                Ok(AvroValue::Record(vec![
+                    ("status".to_string(), AvroValue::Int(1)),
+                    ("snapshot_id".to_string(), optional(None)),
+                    ("sequence_number".to_string(), optional(None)),
+                    ("file_sequence_number".to_string(), optional(None)),
+                    (
+                        "data_file".to_string(),
+                        AvroValue::Record(vec![
+                            ("content".to_string(), AvroValue::Int(0)),
+                            (
+                                "file_path".to_string(),
+                                AvroValue::String(self.storage.uri(&file.path)),
+                            ),
+                            (
+                                "file_format".to_string(),
+                                AvroValue::String("PARQUET".to_string()),
+                            ),
+                            (
+                                "partition".to_string(),
+                                AvroValue::Record(self.partition_values(file, &types)?),
+                            ),
+                            (
+                                "record_count".to_string(),
+                                AvroValue::Long(file.record_count),
+                            ),
+                            ("file_size_in_bytes".to_string(), AvroValue::Long(file.size)),
+                        ]),
+                    ),
+                ]))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let bytes = write_avro(
+            &manifest_entry_schema(partition_fields),
+            &[
+                ("schema", schema.to_string()),
+                ("schema-id", schema["schema-id"].to_string()),
+                ("partition-spec", spec["fields"].to_string()),
+                ("partition-spec-id", spec["spec-id"].to_string()),
+                ("format-version", "2".to_string()),
+                ("content", "data".to_string()),
+            ],
+            entries,
+        )?;
+
+        let path = self
+            .storage
+            .metadata_path(&format!("{}-m0.avro", Uuid::new_v4()));
+        let length = bytes.len() as i64;
+        self.storage.store.put(&path, bytes.into()).await?;
+
+        Ok(ManifestFile {
+            path: self.storage.uri(path.as_ref()),
+            length,
+            spec_id: spec["spec-id"].as_i64().unwrap_or_default(),
+            files: files.len() as i64,
+            rows: files.iter().map(|f| f.record_count).sum(),
+        })
+    }
+
+    /// Writes the manifest list for a snapshot that adds the manifest to the current snapshot,
+    /// returning the snapshot.
+    async fn write_snapshot(
+        &self,
+        metadata: &Value,
+        manifest: &ManifestFile,
+        snapshot_id: i64,
+        last_file: &str,
+    ) -> Result<Value> {
+        let sequence_number = metadata["last-sequence-number"].as_i64().unwrap_or(0) + 1;
+        let parent = current_snapshot(metadata);
+
+        let mut manifests = vec![];
+        if let Some(list) = parent.and_then(|p| p["manifest-list"].as_str()) {
+            for value in self.storage.read_avro(list).await? {
+                manifests.push(manifest_file_record(value)?);
+            }
+        }
+        manifests.push(AvroValue::Record(vec![
+            (
+                "manifest_path".to_string(),
+                AvroValue::String(manifest.path.clone()),
+            ),
+            (
+                "manifest_length".to_string(),
+                AvroValue::Long(manifest.length),
+            ),
+            (
+                "partition_spec_id".to_string(),
+                AvroValue::Int(manifest.spec_id as i32),
+            ),
+            ("content".to_string(), AvroValue::Int(0)),
+            (
+                "sequence_number".to_string(),
+                AvroValue::Long(sequence_number),
+            ),
+            (
+                "min_sequence_number".to_string(),
+                AvroValue::Long(sequence_number),
+            ),
+            (
+                "added_snapshot_id".to_string(),
+                AvroValue::Long(snapshot_id),
+            ),
+            (
+                "added_files_count".to_string(),
+                AvroValue::Int(manifest.files as i32),
+            ),
+            ("existing_files_count".to_string(), AvroValue::Int(0)),
+            ("deleted_files_count".to_string(), AvroValue::Int(0)),
+            (
+                "added_rows_count".to_string(),
+                AvroValue::Long(manifest.rows),
+            ),
+            ("existing_rows_count".to_string(), AvroValue::Long(0)),
+            ("deleted_rows_count".to_string(), AvroValue::Long(0)),
+        ]));
+
+        let parent_id = parent.map(|p| p["snapshot-id"].clone());
+        let bytes = write_avro(
+            &manifest_list_schema(),
+            &[
+                ("snapshot-id", snapshot_id.to_string()),
+                (
+                    "parent-snapshot-id",
+                    parent_id
+                        .as_ref()
+                        .map(|id| id.to_string())
+                        .unwrap_or_else(|| "null".to_string()),
+                ),
+                ("sequence-number", sequence_number.to_string()),
+                ("format-version", "2".to_string()),
+            ],
+            manifests,
+        )?;
+
+        let path =
+            self.storage
+                .metadata_path(&format!("snap-{}-{}.avro", snapshot_id, Uuid::new_v4()));
+        self.storage.store.put(&path, bytes.into()).await?;
+
+        let mut snapshot = json!({
+            "snapshot-id": snapshot_id,
+            "sequence-number": sequence_number,
+            "timestamp-ms": to_millis(SystemTime::now()),
+            "manifest-list": self.storage.uri(path.as_ref()),
+            "schema-id": metadata["current-schema-id"],
+            "summary": {
+                "operation": "append",
+                "added-data-files": manifest.files.to_string(),
+                "added-records": manifest.rows.to_string(),
+                LAST_FILE_PROPERTY: last_file,
+            },
+        });
+        if let Some(parent_id) = parent_id {
+            snapshot["parent-snapshot-id"] = parent_id;
+        }
+        Ok(snapshot)
+    }
+
+    /// Commits the snapshot, returning false if another writer committed to the table first.
+    async fn commit(&self, table: &LoadedTable, snapshot: Value) -> Result<bool> {
+        match &self.catalog {
+            Catalog::Hadoop => {
+                let mut metadata = table.metadata.clone();
+                if metadata["properties"][NAME_MAPPING_PROPERTY].is_null() {
+                    let mapping = name_mapping(&current_schema(&metadata)?["fields"]);
+                    metadata["properties"][NAME_MAPPING_PROPERTY] =
+                        Value::String(Value::Array(mapping).to_string());
+                }
+                let previous = (table.version > 0).then(|| {
+                    self.storage
+                        .uri(self.storage.version_path(table.version).as_ref())
+                });
+                add_snapshot(&mut metadata, snapshot, previous);
+
+                // the new version is written to a temporary file and then copied into place
+                // only if no other writer has created that version
+                let version_path = self.storage.version_path(table.version + 1);
+                let tmp = self
+                    .storage
+                    .metadata_path(&format!("{}.metadata.json.tmp", Uuid::new_v4()));
+                self.storage
+                    .store
+                    .put(&tmp, serde_json::to_vec_pretty(&metadata)?.into())
+                    .await?;
+                let result = self
+                    .storage
+                    .store
+                    .copy_if_not_exists(&tmp, &version_path)
+                    .await;
+                let result: Result<bool> = match result {
+                    // stores without conditional copies, like S3, rely on the commit being
+                    // made by a single subtask
+                    Err(object_store::Error::NotSupported { .. }) => {
+                        if self.storage.exists(&version_path).await? {
+                            Ok(false)
+                        } else {
+                            self.storage.store.copy(&tmp, &version_path).await?;
+                            Ok(true)
+                        }
+                    }
+                    Ok(()) => Ok(true),
+                    Err(object_store::Error::AlreadyExists { .. }) => Ok(false),
+                    Err(e) => Err(e.into()),
+                };
+                self.storage.store.delete(&tmp).await?;
+                if !result? {
+                    return Ok(false);
+                }
+
+                self.storage
+                    .store
+                    .put(
+                        &self.storage.metadata_path("version-hint.text"),
+                        (table.version + 1).to_string().into_bytes().into(),
+                    )
+                    .await?;
+                Ok(true)
+            }
+            Catalog::Rest(catalog) => catalog.commit(&table.metadata, snapshot).await,
+        }
+    }
+}
+
+fn avro_field<'a>(record: &'a AvroValue, name: &str) -> Option<&'a AvroValue> {
+    let AvroValue::Record(fields) = record else {
+        return None;
+    };
+    fields
+        .iter()
+        .find(|(field, _)| field == name)
+        .map(|(_, value)| match value {
+            AvroValue::Union(_, value) => value.as_ref(),
+            value => value,
+        })
+}
+
+fn avro_long(record: &AvroValue, name: &str) -> Option<i64> {
+    match avro_field(record, name)? {
+        AvroValue::Int(i) => Some(*i as i64),
+        AvroValue::Long(l) => Some(*l),
+        _ => None,
+    }
+}
+
+/// Reads the data files of an Iceberg table, for the filesystem source. Versions of the table
+/// are identified by their snapshot ids.
+pub(crate) struct IcebergReader {
+    storage: TableStorage,
+    catalog: Catalog,
+}
+
+impl IcebergReader {
+    /// Without settings, the table is read from a hadoop catalog at the path of the storage
+    /// provider.
+    pub(crate) async fn new(
+        storage_provider: &StorageProvider,
+        settings: Option<&IcebergSettings>,
+    ) -> Result<Self> {
+        let table_path = Path::from(storage_provider.config().key().cloned().unwrap_or_default());
+        let catalog = match settings {
+            Some(settings) if settings.catalog == IcebergCatalog::Rest => {
+                Catalog::Rest(RestCatalog::new(settings).await?)
+            }
+            _ => Catalog::Hadoop,
+        };
+        Ok(Self {
+            storage: TableStorage::new(table_path, storage_provider),
+            catalog,
+        })
+    }
+
+    async fn metadata(&self) -> Result<Option<Value>> {
+        match &self.catalog {
+            Catalog::Hadoop => match self.storage.hadoop_version().await? {
+                0 => Ok(None),
+                version => Ok(Some(self.storage.read_metadata(version).await?)),
+            },
+            Catalog::Rest(catalog) => catalog.load().await,
+        }
+    }
+
+    /// Returns the current snapshot of the table and the paths of all of its data files, or
+    /// None if the table doesn't have any snapshots yet.
+    pub(crate) async fn table_files(&self) -> Result<Option<(i64, Vec<Path>)>> {
+        let Some(metadata) = self.metadata().await? else {
+            return Ok(None);
+        };
+        let Some(snapshot) = current_snapshot(&metadata) else {
+            return Ok(None);
+        };
+        let snapshot_id = snapshot["snapshot-id"]
+            .as_i64()
+            .ok_or_else(|| anyhow!("invalid Iceberg snapshot: {}", snapshot))?;
+
+        Ok(Some((snapshot_id, self.data_files(snapshot, false).await?)))
+    }
+
+    /// Returns the snapshots of the table after `snapshot_id`, oldest first, along with the
+    /// paths of the data files that each of them appended. Snapshots that aren't appends, like
+    /// overwrites and compactions, don't add any files.
+    pub(crate) async fn versions_after(&self, snapshot_id: i64) -> Result<Vec<(i64, Vec<Path>)>> {
+        let metadata = self
+            .metadata()
+            .await?
+            .ok_or_else(|| anyhow!("the Iceberg table no longer exists"))?;
+        let snapshots: HashMap<i64, &Value> = metadata["snapshots"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|s| Some((s["snapshot-id"].as_i64()?, s)))
+            .collect();
+
+        // walk back from the current snapshot to the last one that was read
+        let mut new_snapshots = vec![];
+        let mut current = current_snapshot(&metadata);
+        loop {
+            let Some(snapshot) = current else {
+                bail!(
+                    "snapshot {} is no longer in the history of the Iceberg table",
+                    snapshot_id
+                );
+            };
+            if snapshot["snapshot-id"].as_i64() == Some(snapshot_id) {
+                break;
+            }
+            new_snapshots.push(snapshot);
+            current = snapshot["parent-snapshot-id"]
+                .as_i64()
+                .and_then(|parent| snapshots.get(&parent).copied());
+        }
+
+        let mut versions = vec![];
+        for snapshot in new_snapshots.into_iter().rev() {
+            let id = snapshot["snapshot-id"].as_i64().unwrap_or_default();
+            let files = if snapshot["summary"]["operation"] == "append" {
+                self.data_files(snapshot, true).await?
+            } else {
+                info!(
+                    "skipping Iceberg snapshot {} with operation {}",
+                    id, snapshot["summary"]["operation"]
+                );
+                vec![]
+            };
+            versions.push((id, files));
+        }
+        Ok(versions)
+    }
+
+    /// The data files of the snapshot, or only those that it added if `added_only` is set.
+    async fn data_files(&self, snapshot: &Value, added_only: bool) -> Result<Vec<Path>> {
+        let snapshot_id = snapshot["snapshot-id"].as_i64();
+        let list = snapshot["manifest-list"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Iceberg snapshot has no manifest list: {}", snapshot))?;
+
+        let mut files = vec![];
+        for manifest in self.storage.read_avro(list).await? {
+            // manifests of v1 tables only contain data files
+            if avro_long(&manifest, "content").unwrap_or(0) != 0 {
+                warn!(
+                    "ignoring delete files in Iceberg snapshot {}",
+                    snapshot["snapshot-id"]
+                );
+                continue;
+            }
+            if added_only && avro_long(&manifest, "added_snapshot_id") != snapshot_id {
+                continue;
+            }
+            let Some(AvroValue::String(manifest_path)) = avro_field(&manifest, "manifest_path")
+            else {
+                bail!("invalid Iceberg manifest list entry: {:?}", manifest);
+            };
+
+            for entry in self.storage.read_avro(manifest_path).await? {
+                // entries are existing (0), added (1) or deleted (2)
+                match avro_long(&entry, "status") {
+                    Some(2) => continue,
+                    Some(1) => {}
+                    _ if added_only => continue,
+                    _ => {}
+                }
+                let Some(data_file) = avro_field(&entry, "data_file") else {
+                    bail!("invalid Iceberg manifest entry: {:?}", entry);
+                };
+                if avro_long(data_file, "content").unwrap_or(0) != 0 {
+                    continue;
+                }
+                let Some(AvroValue::String(path)) = avro_field(data_file, "file_path") else {
+                    bail!("invalid Iceberg data file: {:?}", data_file);
+                };
+                files.push(self.storage.path_from_uri(path)?);
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// Commits the finished files to the Iceberg table at the table path, creating the table if
+/// it doesn't exist. Files that were already committed by an earlier attempt are skipped.
+pub(crate) async fn commit_files_to_iceberg(
+    finished_files: Vec<FinishedFile>,
+    relative_table_path: Path,
+    storage_provider: Arc<StorageProvider>,
+    settings: &IcebergSettings,
+    partitioning: Option<&Partitioning>,
+    schema: Schema,
+) -> Result<()> {
+    let Some(last_file) = finished_files.iter().map(|f| f.filename.clone()).max() else {
+        return Ok(());
+    };
+
+    let table = IcebergTable::new(
+        relative_table_path,
+        &storage_provider,
+        settings,
+        partitioning,
+        schema,
+    )
+    .await?;
+
+    let mut loaded = table.load().await?;
+    if let Some(snapshot_id) = committed_snapshot(&loaded.metadata, &last_file) {
+        info!(
+            "files were already committed in Iceberg snapshot {}",
+            snapshot_id
+        );
+        return Ok(());
+    }
+    table.validate(&loaded.metadata)?;
+
+    let mut data_files = vec![];
+    for file in &finished_files {
+        data_files.push(table.data_file(file).await?);
+    }
+    let manifest = table.write_manifest(&loaded.metadata, &data_files).await?;
+    let snapshot_id = (Uuid::new_v4().as_u64_pair().0 & i64::MAX as u64) as i64;
+
+    for attempt in 1..=MAX_COMMIT_ATTEMPTS {
+        let snapshot = table
+            .write_snapshot(&loaded.metadata, &manifest, snapshot_id, &last_file)
+            .await?;
+        if table.commit(&loaded, snapshot).await? {
+            info!(
+                "committed {} files to Iceberg snapshot {}",
+                data_files.len(),
+                snapshot_id
+            );
+            return Ok(());
+        }
+
+        warn!(
+            "Iceberg commit conflicted with another writer, retrying (attempt {})",
+            attempt
+        );
+        tokio::time::sleep(Duration::from_millis(100 * attempt)).await;
+        loaded = table.load().await?;
+        if committed_snapshot(&loaded.metadata, &last_file).is_some() {
+            return Ok(());
+        }
+        table.validate(&loaded.metadata)?;
+    }
+
+    bail!(
+        "failed to commit to Iceberg table after {} attempts",
+        MAX_COMMIT_ATTEMPTS
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::{Field, TimeUnit};
+
+    fn settings() -> IcebergSettings {
+        IcebergSettings {
+            catalog: IcebergCatalog::Hadoop,
+            catalog_uri: None,
+            warehouse: None,
+            namespace: None,
+            table_name: None,
+            token: None,
+            time_partition_field: Some("created".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_time_transforms() {
+        assert_eq!(
+            TimeTransform::from_pattern("year=%Y/month=%m").unwrap(),
+            TimeTransform::Month
+        );
+        assert_eq!(
+            TimeTransform::from_pattern("%Y-%m-%d/%H").unwrap(),
+            TimeTransform::Hour
+        );
+        assert_eq!(
+            TimeTransform::from_pattern("%Y/%j").unwrap(),
+            TimeTransform::Day
+        );
+        assert!(TimeTransform::from_pattern("static").is_err());
+
+        let time = Utc.with_ymd_and_hms(2023, 10, 5, 13, 30, 0).unwrap();
+        for (transform, human) in [
+            (TimeTransform::Year, "2023"),
+            (TimeTransform::Month, "2023-10"),
+            (TimeTransform::Day, "2023-10-05"),
+            (TimeTransform::Hour, "2023-10-05-13"),
+        ] {
+            let value = transform.apply(time);
+            assert_eq!(transform.to_human(value), human);
+            assert_eq!(transform.parse_human(human).unwrap(), value);
+        }
+        assert_eq!(TimeTransform::Day.apply(time), 19635);
+    }
+
+    #[test]
+    fn test_partition_path() {
+        let fields = partition_fields(
+            Some(&Partitioning {
+                time_partition_pattern: Some("%Y/%m/%d".to_string()),
+                partition_fields: vec!["region".to_string(), "shard".to_string()],
+            }),
+            &settings(),
+        )
+        .unwrap();
+
+        let path = partition_path(
+            &fields,
+            &json!({"created": 1696512600000i64, "region": "us/east", "shard": 3}),
+        )
+        .unwrap();
+        assert_eq!(path, "created_day=2023-10-05/region=us%2Feast/shard=3");
+
+        let path = partition_path(
+            &fields,
+            &json!({"created": "2023-10-05T13:30:00Z", "region": null, "shard": 3}),
+        )
+        .unwrap();
+        assert_eq!(path, "created_day=2023-10-05/region=null/shard=3");
+        assert_eq!(unescape("us%2Feast").unwrap(), "us/east");
+
+        assert!(partition_path(&fields, &json!({"created": "yesterday", "shard": 3})).is_err());
+    }
+
+    #[test]
+    fn test_iceberg_schema() {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(
+                "created",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new(
+                "tags",
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+                true,
+            ),
+            Field::new("region", DataType::Utf8, true),
+        ]);
+
+        let (schema, last_column_id) = iceberg_schema(&schema).unwrap();
+        assert_eq!(last_column_id, 5);
+        assert_eq!(schema["fields"][2]["id"], 3);
+        assert_eq!(schema["fields"][2]["type"]["element-id"], 5);
+        assert_eq!(schema["fields"][3]["id"], 4);
+        assert_eq!(schema["fields"][1]["type"], "timestamp");
+
+        let mapping = name_mapping(&schema["fields"]);
+        assert_eq!(mapping[2]["fields"][0]["field-id"], 5);
+        assert_eq!(mapping[3]["names"][0], "region");
+    }
+
+    #[test]
+    fn test_write_avro() {
+        let records = vec![AvroValue::Record(
+            MANIFEST_FILE_FIELDS
+                .iter()
+                .map(|(name, avro_type, _)| {
+                    let value = match *avro_type {
+                        "string" => AvroValue::String("s3://bucket/manifest.avro".to_string()),
+                        "int" => AvroValue::Int(1),
+                        _ => AvroValue::Long(2),
+                    };
+                    (name.to_string(), value)
+                })
+                .collect(),
+        )];
+
+        let bytes = write_avro(
+            &manifest_list_schema(),
+            &[("format-version", "2".to_string())],
+            records.clone(),
+        )
+        .unwrap();
+
+        let reader = apache_avro::Reader::new(&bytes[..]).unwrap();
+        assert_eq!(
+            reader.user_metadata().get("format-version"),
+            Some(&b"2".to_vec())
+        );
+        assert!(String::from_utf8_lossy(&bytes).contains("\"field-id\":500"));
+
+        let read: Vec<AvroValue> = reader
+            .map(|v| manifest_file_record(v.unwrap()).unwrap())
+            .collect();
+        assert_eq!(read, records);
+    }
+
+    #[test]
+    fn test_add_snapshot() {
+        let mut metadata = json!({"snapshots": [], "current-snapshot-id": -1});
+        assert!(current_snapshot(&metadata).is_none());
+
+        add_snapshot(
+            &mut metadata,
+            json!({
+                "snapshot-id": 7,
+                "sequence-number": 1,
+                "timestamp-ms": 1000,
+                "summary": {"operation": "append", LAST_FILE_PROPERTY: "a/b.parquet"},
+            }),
+            Some("file:///t/metadata/v1.metadata.json".to_string()),
+        );
+
+        assert_eq!(current_snapshot(&metadata).unwrap()["snapshot-id"], 7);
+        assert_eq!(metadata["refs"]["main"]["snapshot-id"], 7);
+        assert_eq!(metadata["last-sequence-number"], 1);
+        assert_eq!(metadata["metadata-log"].as_array().unwrap().len(), 1);
+        assert_eq!(committed_snapshot(&metadata, "a/b.parquet"), Some(7));
+        assert_eq!(committed_snapshot(&metadata, "a/c.parquet"), None);
+    }
+}
@@ -18,8 +18,8 @@ use anyhow::{bail, Result};
 use arroyo_formats::SchemaData;
 
 use super::{
-    add_suffix_prefix, delta, get_partitioner_from_file_settings, CommitState, CommitStyle,
-    FileNaming, FileSystemTable, FilenameStrategy, MultiPartWriterStats, RollingPolicy, TableType,
+    add_suffix_prefix, delta, get_partitioner, iceberg, CommitState, CommitStyle, FileNaming,
+    FileSystemTable, FilenameStrategy, MultiPartWriterStats, RollingPolicy, TableType,
 };
 
 pub struct LocalFileSystemWriter<K: Key, D: Data + Sync, V: LocalWriter<D>> {
@@ -29,7 +29,7 @@ pub struct LocalFileSystemWriter<K: Key, D: Data + Sync, V: LocalWriter<D>> {
     final_dir: String,
     next_file_index: usize,
     subtask_id: usize,
-    partitioner: Option<Box<dyn Fn(&Record<K, D>) -> String + Send>>,
+    partitioner: Option<Box<dyn Fn(&Record<K, D>) -> Result<String> + Send>>,
     finished_files: Vec<FilePreCommit>,
     rolling_policy: RollingPolicy,
     table_properties: FileSystemTable,
@@ -46,13 +46,16 @@ impl<K: Key, D: Data + Sync + Serialize, V: LocalWriter<D>> LocalFileSystemWrite
         create_dir_all(&tmp_dir).unwrap();
 
         let TableType::Sink {
-            ref file_settings, ..
+            ref file_settings,
+            ref iceberg_settings,
+            ..
         } = table_properties.table_type
         else {
             unreachable!("LocalFileSystemWriter can only be used as a sink")
         };
         let commit_state = match file_settings.as_ref().unwrap().commit_style.unwrap() {
             CommitStyle::DeltaLake => CommitState::DeltaLake { last_version: -1 },
+            CommitStyle::Iceberg => CommitState::Iceberg,
             CommitStyle::Direct => CommitState::VanillaParquet,
         };
 
@@ -76,8 +79,9 @@ impl<K: Key, D: Data + Sync + Serialize, V: LocalWriter<D>> LocalFileSystemWrite
             final_dir,
             next_file_index: 0,
             subtask_id: 0,
-            partitioner: get_partitioner_from_file_settings(
+            partitioner: get_partitioner(
                 file_settings.as_ref().unwrap().clone(),
+                iceberg_settings.as_ref(),
             ),
             finished_files: Vec::new(),
             rolling_policy: RollingPolicy::from_file_settings(file_settings.as_ref().unwrap()),
@@ -222,7 +226,7 @@ impl<K: Key, D: Data + Sync + SchemaData + Serialize, V: LocalWriter<D> + Send +
     }
 
     async fn insert_record(&mut self, record: &Record<K, D>) -> Result<()> {
-        let partition = self.partitioner.as_ref().map(|f| f(record));
+        let partition = self.partitioner.as_ref().map(|f| f(record)).transpose()?;
         let writer = self.get_or_insert_writer(&partition);
         writer
             .write(record.value.clone(), record.timestamp)
@@ -282,6 +286,27 @@ impl<K: Key, D: Data + Sync + SchemaData + Serialize, V: LocalWriter<D> + Send +
                     last_version: version,
                 };
             }
+        } else if let CommitState::Iceberg = self.commit_state {
+            let TableType::Sink {
+                file_settings,
+                iceberg_settings,
+                ..
+            } = &self.table_properties.table_type
+            else {
+                unreachable!("LocalFileSystemWriter can only be used as a sink")
+            };
+            let storage_provider = Arc::new(StorageProvider::for_url("/").await?);
+            iceberg::commit_files_to_iceberg(
+                finished_files,
+                object_store::path::Path::parse(&self.final_dir)?,
+                storage_provider,
+                iceberg_settings
+                    .as_ref()
+                    .expect("Iceberg sink requires iceberg_settings"),
+                file_settings.as_ref().unwrap().partitioning.as_ref(),
+                D::schema(),
+            )
+            .await?;
         }
         Ok(())
     }
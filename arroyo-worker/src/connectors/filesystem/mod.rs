@@ -26,7 +26,9 @@ import_types!(schema = "../connector-schemas/filesystem/table.json");
 
 use arroyo_types::*;
 pub mod arrow;
+pub mod csv;
 mod delta;
+mod iceberg;
 pub mod json;
 pub mod local;
 pub mod parquet;
@@ -36,6 +38,7 @@ pub mod source;
 use arroyo_formats::SchemaData;
 
 use self::{
+    csv::{CsvLocalWriter, CsvWriter},
     json::{JsonLocalWriter, JsonWriter, PassThrough},
     local::{LocalFileSystemWriter, LocalWriter},
     parquet::{FixedSizeRecordBatchBuilder, ParquetLocalWriter, RecordBatchBufferingWriter},
@@ -49,7 +52,7 @@ pub struct FileSystemSink<
     R: MultiPartWriter<InputType = T> + Send + 'static,
 > {
     sender: Sender<FileSystemMessages<T>>,
-    partitioner: Option<Box<dyn Fn(&Record<K, T>) -> String + Send>>,
+    partitioner: Option<Box<dyn Fn(&Record<K, T>) -> Result<String> + Send>>,
     checkpoint_receiver: Receiver<CheckpointData<T>>,
     commit_strategy: CommitStrategy,
     _ts: PhantomData<(K, R)>,
@@ -64,10 +67,15 @@ pub type ParquetFileSystemSink<K, T, R> = FileSystemSink<
 pub type JsonFileSystemSink<K, T> =
     FileSystemSink<K, T, BatchMultipartWriter<PassThrough<T>, JsonWriter<T>>>;
 
+pub type CsvFileSystemSink<K, T> =
+    FileSystemSink<K, T, BatchMultipartWriter<PassThrough<T>, CsvWriter<T>>>;
+
 pub type LocalParquetFileSystemSink<K, T, R> = LocalFileSystemWriter<K, T, ParquetLocalWriter<R>>;
 
 pub type LocalJsonFileSystemSink<K, T> = LocalFileSystemWriter<K, T, JsonLocalWriter>;
 
+pub type LocalCsvFileSystemSink<K, T> = LocalFileSystemWriter<K, T, CsvLocalWriter>;
+
 impl<K: Key, T: Data + Sync + SchemaData + Serialize, V: LocalWriter<T>>
     LocalFileSystemWriter<K, T, V>
 {
@@ -99,6 +107,7 @@ impl<
             file_settings,
             format_settings: _,
             storage_options,
+            iceberg_settings,
         } = table.clone().table_type
         else {
             unreachable!("multi-part writer can only be used as sink");
@@ -107,9 +116,9 @@ impl<
         let (checkpoint_sender, checkpoint_receiver) = tokio::sync::mpsc::channel(10000);
         let commit_strategy = match file_settings.as_ref().unwrap().commit_style.unwrap() {
             CommitStyle::Direct => CommitStrategy::PerSubtask,
-            CommitStyle::DeltaLake => CommitStrategy::PerOperator,
+            CommitStyle::DeltaLake | CommitStyle::Iceberg => CommitStrategy::PerOperator,
         };
-        let partition_func = get_partitioner_from_file_settings(file_settings.unwrap());
+        let partition_func = get_partitioner(file_settings.unwrap(), iceberg_settings.as_ref());
         tokio::spawn(async move {
             let storage_path: Path = StorageProvider::get_key(&write_path).unwrap().into();
             let provider =
@@ -144,9 +153,21 @@ impl<
     }
 }
 
+fn get_partitioner<K: Key, T: Data + Serialize>(
+    file_settings: FileSettings,
+    iceberg_settings: Option<&IcebergSettings>,
+) -> Option<Box<dyn Fn(&Record<K, T>) -> Result<String> + Send>> {
+    match (file_settings.commit_style, iceberg_settings) {
+        (Some(CommitStyle::Iceberg), Some(settings)) => {
+            iceberg::get_partitioner(file_settings.partitioning, settings)
+        }
+        _ => get_partitioner_from_file_settings(file_settings),
+    }
+}
+
 fn get_partitioner_from_file_settings<K: Key, T: Data + Serialize>(
     file_settings: FileSettings,
-) -> Option<Box<dyn Fn(&Record<K, T>) -> String + Send>> {
+) -> Option<Box<dyn Fn(&Record<K, T>) -> Result<String> + Send>> {
     let Some(partitions) = file_settings.partitioning else {
         return None;
     };
@@ -155,17 +176,17 @@ fn get_partitioner_from_file_settings<K: Key, T: Data + Serialize>(
         partitions.partition_fields.is_empty(),
     ) {
         (None, false) => Some(Box::new(move |record: &Record<K, T>| {
-            partition_string_for_fields(&record.value, &partitions.partition_fields).unwrap()
+            partition_string_for_fields(&record.value, &partitions.partition_fields)
         })),
         (None, true) => None,
         (Some(pattern), false) => Some(Box::new(move |record: &Record<K, T>| {
             let time_partition = formatted_time_from_timestamp(record.timestamp, &pattern);
             let field_partition =
-                partition_string_for_fields(&record.value, &partitions.partition_fields).unwrap();
-            format!("{}/{}", time_partition, field_partition)
+                partition_string_for_fields(&record.value, &partitions.partition_fields)?;
+            Ok(format!("{}/{}", time_partition, field_partition))
         })),
         (Some(pattern), true) => Some(Box::new(move |record: &Record<K, T>| {
-            formatted_time_from_timestamp(record.timestamp, &pattern)
+            Ok(formatted_time_from_timestamp(record.timestamp, &pattern))
         })),
     }
 }
@@ -374,6 +395,7 @@ struct AsyncMultipartFileSystemWriter<T: Data + SchemaData + Sync, R: MultiPartW
 #[derive(Debug, Clone, Encode, Decode, PartialEq, Eq)]
 pub enum CommitState {
     DeltaLake { last_version: i64 },
+    Iceberg,
     VanillaParquet,
 }
 
@@ -652,6 +674,7 @@ where
 
         let commit_state = match file_settings.commit_style.unwrap() {
             CommitStyle::DeltaLake => CommitState::DeltaLake { last_version: -1 },
+            CommitStyle::Iceberg => CommitState::Iceberg,
             CommitStyle::Direct => CommitState::VanillaParquet,
         };
         let mut file_naming = file_settings
@@ -874,6 +897,26 @@ where
                     last_version: new_version,
                 };
             }
+        } else if let CommitState::Iceberg = self.commit_state {
+            let TableType::Sink {
+                file_settings,
+                iceberg_settings,
+                ..
+            } = &self.properties.table_type
+            else {
+                unreachable!("AsyncMultipartFileSystemWriter can only be used as a sink");
+            };
+            iceberg::commit_files_to_iceberg(
+                finished_files,
+                self.path.clone(),
+                self.object_store.clone(),
+                iceberg_settings
+                    .as_ref()
+                    .expect("Iceberg sink requires iceberg_settings"),
+                file_settings.as_ref().unwrap().partitioning.as_ref(),
+                T::schema(),
+            )
+            .await?;
         }
         let finished_message = CheckpointData::Finished {
             max_file_index: self.max_file_index,
@@ -886,7 +929,7 @@ where
     fn delta_version(&mut self) -> i64 {
         match self.commit_state {
             CommitState::DeltaLake { last_version } => last_version,
-            CommitState::VanillaParquet => 0,
+            CommitState::Iceberg | CommitState::VanillaParquet => 0,
         }
     }
 
@@ -1553,7 +1596,8 @@ impl<K: Key, T: Data + Sync, R: MultiPartWriter<InputType = T> + Send + 'static>
         let partition = self
             .partitioner
             .as_ref()
-            .map(|partition_fn| partition_fn(record));
+            .map(|partition_fn| partition_fn(record))
+            .transpose()?;
         let value = record.value.clone();
 
         self.sender
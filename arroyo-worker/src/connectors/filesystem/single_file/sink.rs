@@ -56,6 +56,7 @@ impl<K: Key, T: SchemaData + Serialize> FileSink<K, T> {
             .get_global_keyed_state('f')
             .await
             .get(&self.output_path)
+            .await
             .cloned()
             .unwrap_or_default();
         self.file = Some(
@@ -72,9 +73,14 @@ impl<K: Key, T: SchemaData + Serialize> FileSink<K, T> {
         }
     }
 
-    async fn process_element(&mut self, record: &Record<K, T>, _ctx: &mut Context<(), ()>) {
-        let Some(row) = self.serializer.to_vec(&record.value) else {
-            return;
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), ()>) {
+        let row = match self.serializer.to_vec(&record.value) {
+            Ok(Some(row)) => row,
+            Ok(None) => return,
+            Err(e) => {
+                ctx.report_user_error(e).await;
+                return;
+            }
         };
         // row as a line
         let file = self.file.as_mut().unwrap();
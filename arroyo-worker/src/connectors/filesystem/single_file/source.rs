@@ -51,6 +51,7 @@ impl<K: Data, T: DeserializeOwned + Data> FileSourceFunc<K, T> {
             .get_global_keyed_state('f')
             .await
             .get(&self.input_file)
+            .await
             .map(|v| *v)
             .unwrap_or_default();
 
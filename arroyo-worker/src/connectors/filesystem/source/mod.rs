@@ -1,7 +1,8 @@
 use core::panic;
 use std::future::ready;
 use std::pin::Pin;
-use std::time::SystemTime;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use std::{collections::HashMap, marker::PhantomData};
 
 use anyhow::Result;
@@ -18,8 +19,9 @@ use tokio::{
     io::{AsyncBufReadExt, BufReader},
     select,
 };
-use tokio_stream::wrappers::LinesStream;
+use tokio_stream::wrappers::{LinesStream, ReceiverStream};
 use tokio_stream::Stream;
+use tokio_util::io::SyncIoBridge;
 use tracing::{info, warn};
 
 use arroyo_formats::{DataDeserializer, SchemaData};
@@ -31,13 +33,23 @@ use typify::import_types;
 
 use crate::{engine::Context, SourceFinishType};
 
+use self::table::TableReader;
+
+mod table;
+
 import_types!(schema = "../connector-schemas/filesystem/table.json");
 
+// key of the last fully read table version in the 'v' table
+const TABLE_VERSION_KEY: &str = "version";
+// records decoded ahead of the stream when reading avro and CSV files
+const RECORD_BUFFER: usize = 1024;
+
 #[derive(StreamNode)]
 pub struct FileSystemSourceFunc<K: Data, T: SchemaData + Data> {
     table: TableType,
     deserializer: DataDeserializer<T>,
     file_states: HashMap<String, FileReadState>,
+    table_version: Option<i64>,
     _t: PhantomData<(K, T)>,
 }
 
@@ -62,12 +74,16 @@ impl<K: Data, T: SchemaData> FileSystemSourceFunc<K, T> {
             table: table.table_type,
             deserializer: DataDeserializer::new(format, config.framing),
             file_states: HashMap::new(),
+            table_version: None,
             _t: PhantomData,
         }
     }
 
     pub fn tables(&self) -> Vec<arroyo_rpc::grpc::TableDescriptor> {
-        vec![arroyo_state::global_table('a', "fs")]
+        vec![
+            arroyo_state::global_table('a', "fs"),
+            arroyo_state::global_table('v', "table version"),
+        ]
     }
 
     fn name(&self) -> String {
@@ -107,38 +123,72 @@ impl<K: Data, T: SchemaData> FileSystemSourceFunc<K, T> {
             return Ok(SourceFinishType::Final);
         }
 
-        let (storage_provider, regex_pattern) = match &self.table {
-            TableType::Source {
-                path,
-                storage_options,
-                compression_format: _,
-                regex_pattern,
-            } => {
-                let storage_provider =
-                    StorageProvider::for_url_with_options(&path, storage_options.clone())
-                        .await
+        let (storage_provider, regex_pattern, table_format, iceberg_settings, poll_interval) =
+            match &self.table {
+                TableType::Source {
+                    path,
+                    storage_options,
+                    compression_format: _,
+                    regex_pattern,
+                    table_format,
+                    poll_interval_seconds,
+                    iceberg_settings,
+                } => {
+                    let storage_provider =
+                        StorageProvider::for_url_with_options(&path, storage_options.clone())
+                            .await
+                            .map_err(|err| {
+                                UserError::new("failed to create storage provider", err.to_string())
+                            })?;
+                    let matcher = regex_pattern
+                        .as_ref()
+                        .map(|pattern| Regex::new(&pattern))
+                        .transpose()
                         .map_err(|err| {
-                            UserError::new("failed to create storage provider", err.to_string())
+                            UserError::new(
+                                format!(
+                                    "invalid regex pattern {}",
+                                    regex_pattern.as_ref().unwrap()
+                                ),
+                                err.to_string(),
+                            )
                         })?;
-                let matcher = regex_pattern
-                    .as_ref()
-                    .map(|pattern| Regex::new(&pattern))
-                    .transpose()
-                    .map_err(|err| {
-                        UserError::new(
-                            format!("invalid regex pattern {}", regex_pattern.as_ref().unwrap()),
-                            err.to_string(),
-                        )
-                    })?;
-                (storage_provider, matcher)
-            }
-            TableType::Sink { .. } => {
-                return Err(UserError::new(
-                    "invalid table config",
-                    "filesystem source cannot be used as a sink".to_string(),
-                ))
-            }
-        };
+                    (
+                        storage_provider,
+                        matcher,
+                        table_format.clone(),
+                        iceberg_settings.clone(),
+                        poll_interval_seconds.map(|s| Duration::from_secs(s as u64)),
+                    )
+                }
+                TableType::Sink { .. } => {
+                    return Err(UserError::new(
+                        "invalid table config",
+                        "filesystem source cannot be used as a sink".to_string(),
+                    ))
+                }
+            };
+
+        let mut state: GlobalKeyedState<String, (String, FileReadState), _> =
+            ctx.state.get_global_keyed_state('a').await;
+        self.file_states = state
+            .get_all()
+            .await
+            .into_iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+
+        if let Some(table_format) = table_format {
+            return self
+                .read_table(
+                    ctx,
+                    storage_provider,
+                    table_format,
+                    iceberg_settings,
+                    poll_interval,
+                )
+                .await;
+        }
 
         // TODO: sort by creation time
         let mut file_paths = storage_provider
@@ -156,14 +206,6 @@ impl<K: Data, T: SchemaData> FileSystemSourceFunc<K, T> {
                 }
             });
 
-        let mut state: GlobalKeyedState<String, (String, FileReadState), _> =
-            ctx.state.get_global_keyed_state('a').await;
-        self.file_states = state
-            .get_all()
-            .into_iter()
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect();
-
         while let Some(path) = file_paths.next().await {
             let obj_key = path
                 .map_err(|err| UserError::new("could not get next path", err.to_string()))?
@@ -183,6 +225,107 @@ impl<K: Data, T: SchemaData> FileSystemSourceFunc<K, T> {
         Ok(SourceFinishType::Final)
     }
 
+    /// Reads a Delta Lake or Iceberg table version by version, starting from a snapshot of the
+    /// table and then following the versions committed after it. Without a poll interval the
+    /// source finishes once it has read the current version.
+    async fn read_table(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        storage_provider: StorageProvider,
+        table_format: TableFormat,
+        iceberg_settings: Option<IcebergSourceSettings>,
+        poll_interval: Option<Duration>,
+    ) -> Result<SourceFinishType, UserError> {
+        let storage_provider = Arc::new(storage_provider);
+        let mut state: GlobalKeyedState<String, i64, _> =
+            ctx.state.get_global_keyed_state('v').await;
+        self.table_version = state.get(&TABLE_VERSION_KEY.to_string()).await.copied();
+
+        let mut reader = None;
+        loop {
+            if reader.is_none() {
+                reader = TableReader::load(
+                    &table_format,
+                    iceberg_settings.as_ref(),
+                    storage_provider.clone(),
+                )
+                .await
+                .map_err(|err| UserError::new("failed to load table", err.to_string()))?;
+            }
+
+            if let Some(reader) = &reader {
+                let versions = match self.table_version {
+                    Some(version) => reader.versions_after(&storage_provider, version).await,
+                    None => reader
+                        .snapshot(&storage_provider)
+                        .await
+                        .map(|snapshot| snapshot.into_iter().collect()),
+                }
+                .map_err(|err| UserError::new("failed to read table log", err.to_string()))?;
+
+                for (version, files) in versions {
+                    for path in files {
+                        let obj_key = path.to_string();
+                        if let Some(FileReadState::Finished) = self.file_states.get(&obj_key) {
+                            continue;
+                        }
+                        if let Some(finish_type) =
+                            self.read_file(ctx, &storage_provider, &obj_key).await?
+                        {
+                            return Ok(finish_type);
+                        }
+                    }
+                    info!("finished reading table version {}", version);
+                    // data files are never rewritten, so only the files of the version being
+                    // read need to be tracked
+                    self.table_version = Some(version);
+                    self.file_states.clear();
+                }
+            }
+
+            let Some(poll_interval) = poll_interval else {
+                info!("FileSystem source finished");
+                return Ok(SourceFinishType::Final);
+            };
+
+            let sleep = tokio::time::sleep(poll_interval);
+            tokio::pin!(sleep);
+            loop {
+                select! {
+                    _ = &mut sleep => break,
+                    msg_res = ctx.control_rx.recv() => {
+                        if let Some(control_message) = msg_res {
+                            if let Some(finish_type) =
+                                self.process_control_message(ctx, control_message).await
+                            {
+                                return Ok(finish_type);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn get_file_reader(
+        &mut self,
+        storage_provider: &StorageProvider,
+        path: String,
+    ) -> Result<Box<dyn AsyncRead + Unpin + Send>, UserError> {
+        let stream_reader = storage_provider
+            .get_as_stream(path.clone())
+            .await
+            .map_err(|err| {
+                UserError::new("could not read file", format!("path:{}, err:{}", path, err))
+            })?;
+
+        Ok(match self.get_compression_format() {
+            CompressionFormat::Zstd => Box::new(ZstdDecoder::new(BufReader::new(stream_reader))),
+            CompressionFormat::Gzip => Box::new(GzipDecoder::new(BufReader::new(stream_reader))),
+            CompressionFormat::None => Box::new(BufReader::new(stream_reader)),
+        })
+    }
+
     async fn get_item_stream(
         &mut self,
         storage_provider: &StorageProvider,
@@ -190,20 +333,9 @@ impl<K: Data, T: SchemaData> FileSystemSourceFunc<K, T> {
     ) -> Result<Box<dyn Stream<Item = Result<T, UserError>> + Unpin + Send>, UserError> {
         let format = self.deserializer.get_format().clone();
         match *format {
-            arroyo_rpc::formats::Format::Json(_) => {
+            arroyo_rpc::formats::Format::Json(_) | arroyo_rpc::formats::Format::RawString(_) => {
                 let deserializer = self.deserializer.clone();
-                let stream_reader = storage_provider.get_as_stream(path).await.unwrap();
-
-                let compression_reader: Box<dyn AsyncRead + Unpin + Send> =
-                    match self.get_compression_format() {
-                        CompressionFormat::Zstd => {
-                            Box::new(ZstdDecoder::new(BufReader::new(stream_reader)))
-                        }
-                        CompressionFormat::Gzip => {
-                            Box::new(GzipDecoder::new(BufReader::new(stream_reader)))
-                        }
-                        CompressionFormat::None => Box::new(BufReader::new(stream_reader)),
-                    };
+                let compression_reader = self.get_file_reader(storage_provider, path).await?;
                 // use line iterators
                 let lines = LinesStream::new(BufReader::new(compression_reader).lines());
                 let x = Box::new(lines.map(move |res| match res {
@@ -216,7 +348,90 @@ impl<K: Data, T: SchemaData> FileSystemSourceFunc<K, T> {
                     as Box<dyn Stream<Item = Result<T, UserError>> + Unpin + Send>;
                 Ok(x as Box<dyn Stream<Item = Result<T, UserError>> + Unpin + Send>)
             }
-            arroyo_rpc::formats::Format::Avro(_) => todo!(),
+            arroyo_rpc::formats::Format::Avro(ref avro) => {
+                if avro.raw_datums || avro.confluent_schema_registry {
+                    return Err(UserError::new(
+                        "unsupported format",
+                        "the filesystem source only supports Avro object container files",
+                    ));
+                }
+                let into_json = avro.into_unstructured_json;
+
+                // the avro reader is synchronous, so it runs on a blocking thread that pulls
+                // blocks from the file as records are consumed, rather than buffering the file
+                let file_reader = self.get_file_reader(storage_provider, path.clone()).await?;
+                let (tx, rx) = tokio::sync::mpsc::channel(RECORD_BUFFER);
+                tokio::task::spawn_blocking(move || {
+                    let reader = match apache_avro::Reader::new(SyncIoBridge::new(file_reader)) {
+                        Ok(reader) => reader,
+                        Err(err) => {
+                            let _ = tx.blocking_send(Err(UserError::new(
+                                "invalid avro object container file",
+                                format!("path:{}, err:{}", path, err),
+                            )));
+                            return;
+                        }
+                    };
+                    for record in reader {
+                        let record = arroyo_formats::avro::from_avro_value(record, into_json);
+                        if tx.blocking_send(record).is_err() {
+                            // the stream was dropped
+                            return;
+                        }
+                    }
+                });
+
+                Ok(Box::new(ReceiverStream::new(rx)))
+            }
+            arroyo_rpc::formats::Format::Csv(ref csv) => {
+                // quoted fields may span lines, so records are parsed by the (synchronous) CSV
+                // reader, which runs on a blocking thread like the avro reader
+                let file_reader = self.get_file_reader(storage_provider, path.clone()).await?;
+                let format = csv.clone();
+                let fields = T::schema().fields().clone();
+                let (tx, rx) = tokio::sync::mpsc::channel(RECORD_BUFFER);
+                tokio::task::spawn_blocking(move || {
+                    let mut reader = arroyo_formats::csv::reader_builder(&format)
+                        .has_headers(format.header)
+                        .from_reader(SyncIoBridge::new(file_reader));
+                    let headers = if format.header {
+                        match reader.headers() {
+                            Ok(headers) => Some(headers.clone()),
+                            Err(err) => {
+                                let _ = tx.blocking_send(Err(UserError::new(
+                                    "could not read CSV header",
+                                    format!("path:{}, err:{}", path, err),
+                                )));
+                                return;
+                            }
+                        }
+                    } else {
+                        None
+                    };
+
+                    for record in reader.into_records() {
+                        let record = record
+                            .map_err(|err| {
+                                UserError::new("could not read CSV record", err.to_string())
+                            })
+                            .and_then(|record| {
+                                arroyo_formats::csv::deserialize_record(
+                                    &format,
+                                    &fields,
+                                    headers.as_ref(),
+                                    &record,
+                                )
+                                .map_err(|err| UserError::new("Deserialization failed", err))
+                            });
+                        if tx.blocking_send(record).is_err() {
+                            // the stream was dropped
+                            return;
+                        }
+                    }
+                });
+
+                Ok(Box::new(ReceiverStream::new(rx)))
+            }
             arroyo_rpc::formats::Format::Parquet(_) => {
                 let object_meta = storage_provider
                     .get_backing_store()
@@ -266,7 +481,10 @@ impl<K: Data, T: SchemaData> FileSystemSourceFunc<K, T> {
                     as Box<dyn Stream<Item = Result<T, UserError>> + Send + Unpin>;
                 Ok(result)
             }
-            arroyo_rpc::formats::Format::RawString(_) => todo!(),
+            arroyo_rpc::formats::Format::Protobuf(_) => Err(UserError::new(
+                "unsupported format",
+                "the filesystem source does not support protobuf",
+            )),
         }
     }
 
@@ -355,6 +573,13 @@ impl<K: Data, T: SchemaData> FileSystemSourceFunc<K, T> {
                         .insert(file.clone(), (file.clone(), read_state.clone()))
                         .await;
                 }
+                if let Some(version) = self.table_version {
+                    ctx.state
+                        .get_global_keyed_state('v')
+                        .await
+                        .insert(TABLE_VERSION_KEY.to_string(), version)
+                        .await;
+                }
                 // checkpoint our state
                 if self.checkpoint(c, ctx).await {
                     Some(SourceFinishType::Immediate)
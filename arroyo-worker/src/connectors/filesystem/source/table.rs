@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use arroyo_storage::StorageProvider;
+use deltalake::DeltaTable;
+use object_store::path::Path;
+
+use super::{IcebergSourceSettings, TableFormat};
+use crate::connectors::filesystem::{delta, iceberg::IcebergReader, IcebergSettings};
+
+/// Discovers the data files of a Delta Lake or Iceberg table, version by version. Versions are
+/// Delta table versions or Iceberg snapshot ids.
+pub(crate) enum TableReader {
+    Delta(DeltaTable),
+    Iceberg(IcebergReader),
+}
+
+impl TableReader {
+    /// Returns None if the table doesn't exist yet.
+    pub(crate) async fn load(
+        format: &TableFormat,
+        iceberg_settings: Option<&IcebergSourceSettings>,
+        storage_provider: Arc<StorageProvider>,
+    ) -> Result<Option<Self>> {
+        Ok(match format {
+            TableFormat::Delta => delta::load_table(storage_provider)
+                .await?
+                .map(TableReader::Delta),
+            TableFormat::Iceberg => {
+                // the sink's settings are a superset of the source's, so the catalog is shared
+                let settings: Option<IcebergSettings> = iceberg_settings
+                    .map(|s| serde_json::from_value(serde_json::to_value(s)?))
+                    .transpose()?;
+                Some(TableReader::Iceberg(
+                    IcebergReader::new(&storage_provider, settings.as_ref()).await?,
+                ))
+            }
+        })
+    }
+
+    /// The current version of the table and all of its data files.
+    pub(crate) async fn snapshot(
+        &self,
+        storage_provider: &StorageProvider,
+    ) -> Result<Option<(i64, Vec<Path>)>> {
+        match self {
+            TableReader::Delta(table) => Ok(Some(delta::table_files(table, storage_provider)?)),
+            TableReader::Iceberg(reader) => reader.table_files().await,
+        }
+    }
+
+    /// The versions committed after `version`, oldest first, with the data files they added.
+    pub(crate) async fn versions_after(
+        &self,
+        storage_provider: &StorageProvider,
+        version: i64,
+    ) -> Result<Vec<(i64, Vec<Path>)>> {
+        match self {
+            TableReader::Delta(table) => {
+                delta::versions_after(table, storage_provider, version).await
+            }
+            TableReader::Iceberg(reader) => reader.versions_after(version).await,
+        }
+    }
+}
@@ -133,7 +133,7 @@ where
 
         let mut s: GlobalKeyedState<u32, FluvioState, _> =
             ctx.state.get_global_keyed_state('f').await;
-        let state: Vec<&FluvioState> = s.get_all();
+        let state: Vec<&FluvioState> = s.get_all().await;
 
         // did we restore any partitions?
         let has_state = !state.is_empty();
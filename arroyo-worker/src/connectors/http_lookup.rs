@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use arroyo_rpc::OperatorConfig;
+use arroyo_types::string_to_map;
+use async_trait::async_trait;
+use futures::{stream, StreamExt, TryStreamExt};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use typify::import_types;
+
+use crate::operators::lookup_join::LookupConnector;
+
+import_types!(schema = "../connector-schemas/http_lookup/table.json");
+
+const DEFAULT_MAX_CONCURRENCY: usize = 16;
+
+pub struct HttpLookup {
+    endpoint: String,
+    client: reqwest::Client,
+    max_concurrency: usize,
+}
+
+async fn get(client: reqwest::Client, url: String) -> anyhow::Result<Option<Vec<u8>>> {
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| anyhow!("HTTP request to {} failed: {}", url, e))?;
+
+    match response.status() {
+        StatusCode::NOT_FOUND => Ok(None),
+        status if status.is_success() => Ok(Some(
+            response
+                .bytes()
+                .await
+                .map_err(|e| anyhow!("failed to read response from {}: {}", url, e))?
+                .to_vec(),
+        )),
+        status => bail!("HTTP request to {} failed with status {}", url, status),
+    }
+}
+
+#[async_trait]
+impl LookupConnector for HttpLookup {
+    fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for HttpLookup");
+        let table: HttpLookupTable =
+            serde_json::from_value(config.table).expect("Invalid table config for HttpLookup");
+
+        let headers = string_to_map(table.headers.as_ref().map(|t| t.0.as_str()).unwrap_or(""))
+            .expect("Invalid header map")
+            .into_iter()
+            .map(|(k, v)| {
+                (
+                    (&k).try_into()
+                        .expect(&format!("invalid header name {}", k)),
+                    (&v).try_into()
+                        .expect(&format!("invalid header value {}", v)),
+                )
+            })
+            .collect();
+
+        Self {
+            endpoint: table.endpoint,
+            client: reqwest::ClientBuilder::new()
+                .default_headers(headers)
+                .timeout(Duration::from_secs(5))
+                .build()
+                .expect("could not construct reqwest client"),
+            max_concurrency: table
+                .max_concurrency
+                .map(|c| c.get() as usize)
+                .unwrap_or(DEFAULT_MAX_CONCURRENCY),
+        }
+    }
+
+    async fn lookup(&mut self, keys: &[String]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        let requests: Vec<_> = keys
+            .iter()
+            .map(|key| {
+                let url = self.endpoint.replace(
+                    "{key}",
+                    &url::form_urlencoded::byte_serialize(key.as_bytes()).collect::<String>(),
+                );
+                get(self.client.clone(), url)
+            })
+            .collect();
+
+        stream::iter(requests)
+            .buffered(self.max_concurrency)
+            .try_collect()
+            .await
+    }
+}
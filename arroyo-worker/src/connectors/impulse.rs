@@ -101,12 +101,12 @@ impl<K: Data, T: Data> ImpulseSourceFunc<K, T> {
     }
 
     async fn on_start(&mut self, ctx: &mut Context<(), ImpulseEvent>) {
-        let s = ctx
+        let mut s = ctx
             .state
             .get_global_keyed_state::<usize, ImpulseSourceState>('i')
             .await;
 
-        if let Some(state) = s.get(&ctx.task_info.task_index) {
+        if let Some(state) = s.get(&ctx.task_info.task_index).await {
             self.state = *state;
         }
     }
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, bail, Result};
+use arroyo_rpc::OperatorConfig;
+use arroyo_types::{Data, Key, Record, TaskInfo};
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use mysql_async::prelude::Queryable;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::{info, warn};
+use typify::import_types;
+
+use self::statements::{Dialect, Param, Statement};
+use super::two_phase_committer::{TwoPhaseCommitter, TwoPhaseCommitterOperator};
+
+pub mod statements;
+
+import_types!(schema = "../connector-schemas/jdbc/connection.json");
+import_types!(schema = "../connector-schemas/jdbc/table.json");
+
+// the maximum number of rows written by a single statement
+const BATCH_SIZE: usize = 500;
+
+enum DatabaseClient {
+    Postgres(tokio_postgres::Client),
+    MySql(mysql_async::Conn),
+}
+
+impl DatabaseClient {
+    async fn connect(config: &JdbcConfig) -> Result<Self> {
+        match dialect(&config.url)? {
+            Dialect::Postgres => {
+                let mut pg_config = tokio_postgres::Config::from_str(&config.url)?;
+                if let Some(username) = &config.username {
+                    pg_config.user(username);
+                }
+                if let Some(password) = &config.password {
+                    pg_config.password(password);
+                }
+
+                let (client, connection) = pg_config.connect(tokio_postgres::NoTls).await?;
+                tokio::spawn(async move {
+                    if let Err(e) = connection.await {
+                        warn!("postgres connection error: {:?}", e);
+                    }
+                });
+                Ok(DatabaseClient::Postgres(client))
+            }
+            Dialect::MySql => {
+                let mut builder =
+                    mysql_async::OptsBuilder::from_opts(mysql_async::Opts::from_url(&config.url)?);
+                if let Some(username) = &config.username {
+                    builder = builder.user(Some(username));
+                }
+                if let Some(password) = &config.password {
+                    builder = builder.pass(Some(password));
+                }
+                Ok(DatabaseClient::MySql(
+                    mysql_async::Conn::new(builder).await?,
+                ))
+            }
+        }
+    }
+
+    async fn execute_transaction(&mut self, statements: &[Statement]) -> Result<()> {
+        match self {
+            DatabaseClient::Postgres(client) => {
+                let transaction = client.transaction().await?;
+                for statement in statements {
+                    let prepared = transaction.prepare(&statement.sql).await?;
+                    for params in &statement.params {
+                        let params: Vec<_> = params.iter().map(Param::to_postgres).collect();
+                        transaction.execute(&prepared, &params).await?;
+                    }
+                }
+                transaction.commit().await?;
+            }
+            DatabaseClient::MySql(conn) => {
+                let mut transaction = conn
+                    .start_transaction(mysql_async::TxOpts::default())
+                    .await?;
+                for statement in statements {
+                    transaction
+                        .exec_batch(
+                            statement.sql.as_str(),
+                            statement.params.iter().map(|params| {
+                                params.iter().map(Param::to_mysql).collect::<Vec<_>>()
+                            }),
+                        )
+                        .await?;
+                }
+                transaction.commit().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn dialect(url: &str) -> Result<Dialect> {
+    match url.split_once("://").map(|(scheme, _)| scheme) {
+        Some("postgres") | Some("postgresql") => Ok(Dialect::Postgres),
+        Some("mysql") => Ok(Dialect::MySql),
+        _ => bail!("unsupported database url '{}'", url),
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode, PartialEq)]
+pub struct JdbcPreCommit {
+    statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Change {
+    Upsert(Map<String, Value>),
+    Delete(Vec<Value>),
+}
+
+/// Applies an updating stream to a database table as upserts and deletes keyed on its primary
+/// key. The changes in each checkpoint are collapsed to the last change per key, and are written
+/// in a single transaction once the checkpoint has completed. As applying the same batch of
+/// upserts and deletes again leaves the table in the same state, re-committing the last batch on
+/// recovery gives exactly-once results.
+pub struct JdbcSink<K: Key, T: Data + Sync> {
+    config: JdbcConfig,
+    table: String,
+    dialect: Dialect,
+    primary_keys: Vec<String>,
+    client: Option<DatabaseClient>,
+    // pending changes by the JSON of their primary key
+    pending: HashMap<String, Change>,
+    _t: PhantomData<(K, T)>,
+}
+
+impl<K: Key, T: Data + Sync + Serialize> JdbcSink<K, T> {
+    pub fn from_config(config: &str) -> TwoPhaseCommitterOperator<K, T, Self> {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for JdbcSink");
+        let connection: JdbcConfig = serde_json::from_value(config.connection)
+            .expect("Invalid connection config for JdbcSink");
+        let table: JdbcTable =
+            serde_json::from_value(config.table).expect("Invalid table config for JdbcSink");
+
+        TwoPhaseCommitterOperator::new(Self::new(connection, table))
+    }
+
+    fn new(config: JdbcConfig, table: JdbcTable) -> Self {
+        Self {
+            dialect: dialect(&config.url).expect("Invalid url for JdbcSink"),
+            config,
+            table: table.table,
+            primary_keys: table.primary_keys,
+            client: None,
+            pending: HashMap::new(),
+            _t: PhantomData,
+        }
+    }
+
+    fn key(&self, row: &Map<String, Value>) -> Result<(String, Vec<Value>)> {
+        let key = self
+            .primary_keys
+            .iter()
+            .map(|k| {
+                row.get(k)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("row is missing primary key '{}'", k))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((serde_json::to_string(&key)?, key))
+    }
+
+    fn statements(&self, changes: Vec<Change>) -> Vec<Statement> {
+        let mut upserts = vec![];
+        let mut deletes = vec![];
+        for change in changes {
+            match change {
+                Change::Upsert(row) => upserts.push(row),
+                Change::Delete(key) => deletes.push(key),
+            }
+        }
+
+        let mut statements: Vec<_> = deletes
+            .chunks(BATCH_SIZE)
+            .map(|chunk| self.dialect.delete(&self.table, &self.primary_keys, chunk))
+            .collect();
+
+        // rows of a table have the same fields, so any of them gives the columns
+        let Some(first) = upserts.first() else {
+            return statements;
+        };
+        let columns: Vec<String> = first.keys().cloned().collect();
+
+        for chunk in upserts.chunks(BATCH_SIZE) {
+            statements.push(
+                self.dialect
+                    .upsert(&self.table, &columns, &self.primary_keys, chunk),
+            );
+        }
+
+        statements
+    }
+}
+
+fn row(value: &Value, field: &str) -> Result<Map<String, Value>> {
+    value
+        .get(field)
+        .and_then(|v| v.as_object())
+        .cloned()
+        .ok_or_else(|| anyhow!("change is missing '{}'", field))
+}
+
+#[async_trait]
+impl<K: Key, T: Data + Sync + Serialize> TwoPhaseCommitter<K, T> for JdbcSink<K, T> {
+    type DataRecovery = ();
+    type PreCommit = JdbcPreCommit;
+
+    fn name(&self) -> String {
+        "jdbc_sink".to_string()
+    }
+
+    async fn init(&mut self, _: &TaskInfo, _: Vec<Self::DataRecovery>) -> Result<()> {
+        self.client = Some(DatabaseClient::connect(&self.config).await?);
+        info!("Connected to database for table {}", self.table);
+        Ok(())
+    }
+
+    async fn insert_record(&mut self, record: &Record<K, T>) -> Result<()> {
+        let value = serde_json::to_value(&record.value)?;
+
+        match value.get("op").and_then(|op| op.as_str()) {
+            Some("c") | Some("u") => {
+                if let Some(before) = value.get("before").filter(|b| !b.is_null()) {
+                    let before = before
+                        .as_object()
+                        .ok_or_else(|| anyhow!("invalid 'before' in change"))?;
+                    let (before_key, key) = self.key(before)?;
+                    let (after_key, _) = self.key(&row(&value, "after")?)?;
+                    // an update that changes the primary key moves the row
+                    if before_key != after_key {
+                        self.pending.insert(before_key, Change::Delete(key));
+                    }
+                }
+
+                let after = row(&value, "after")?;
+                let (key, _) = self.key(&after)?;
+                self.pending.insert(key, Change::Upsert(after));
+            }
+            Some("d") => {
+                let (key, values) = self.key(&row(&value, "before")?)?;
+                self.pending.insert(key, Change::Delete(values));
+            }
+            _ => bail!("record is not a change: {}", value),
+        }
+
+        Ok(())
+    }
+
+    async fn commit(&mut self, _: &TaskInfo, pre_commit: Vec<Self::PreCommit>) -> Result<()> {
+        let client = self
+            .client
+            .as_mut()
+            .ok_or_else(|| anyhow!("JDBC sink is not connected"))?;
+
+        for batch in pre_commit {
+            client.execute_transaction(&batch.statements).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn checkpoint(
+        &mut self,
+        task_info: &TaskInfo,
+        _watermark: Option<SystemTime>,
+        _stopping: bool,
+    ) -> Result<(Self::DataRecovery, HashMap<String, Self::PreCommit>)> {
+        if self.pending.is_empty() {
+            return Ok(((), HashMap::new()));
+        }
+
+        let changes = std::mem::take(&mut self.pending).into_values().collect();
+        let statements = self.statements(changes);
+
+        Ok((
+            (),
+            HashMap::from([(
+                format!("jdbc-{}", task_info.task_index),
+                JdbcPreCommit { statements },
+            )]),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arroyo_types::{Debezium, DebeziumOp};
+    use serde_json::json;
+
+    #[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize, PartialEq)]
+    struct Total {
+        id: i64,
+        total: f64,
+    }
+
+    fn change(
+        before: Option<Total>,
+        after: Option<Total>,
+        op: DebeziumOp,
+    ) -> Record<(), Debezium<Total>> {
+        Record {
+            timestamp: SystemTime::now(),
+            key: None,
+            value: Debezium { before, after, op },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_collapsed() {
+        let mut sink: JdbcSink<(), Debezium<Total>> = JdbcSink::new(
+            JdbcConfig {
+                url: "postgres://localhost/test".to_string(),
+                username: None,
+                password: None,
+            },
+            JdbcTable {
+                table: "totals".to_string(),
+                primary_keys: vec!["id".to_string()],
+            },
+        );
+
+        let t = |id, total| Total { id, total };
+        for record in [
+            change(None, Some(t(1, 1.0)), DebeziumOp::Create),
+            change(Some(t(1, 1.0)), Some(t(1, 2.0)), DebeziumOp::Update),
+            change(None, Some(t(2, 5.0)), DebeziumOp::Create),
+            change(Some(t(2, 5.0)), None, DebeziumOp::Delete),
+            change(Some(t(3, 1.0)), Some(t(4, 1.0)), DebeziumOp::Update),
+        ] {
+            sink.insert_record(&record).await.unwrap();
+        }
+
+        let ((), pre_commits) = sink
+            .checkpoint(&arroyo_types::get_test_task_info(), None, false)
+            .await
+            .unwrap();
+
+        let statements = pre_commits.into_values().next().unwrap().statements;
+        let [delete, upsert] = statements.as_slice() else {
+            panic!("expected a delete and an upsert, found {:?}", statements);
+        };
+
+        let json = |statement: &Statement| -> Vec<Value> {
+            let [params] = statement.params.as_slice() else {
+                panic!("expected one row of parameters");
+            };
+            let [Param::Text(json)] = params.as_slice() else {
+                panic!("expected a JSON parameter");
+            };
+            let mut rows: Vec<Value> = serde_json::from_str(json).unwrap();
+            rows.sort_by_key(|row| row["id"].as_i64());
+            rows
+        };
+
+        assert!(delete.sql.starts_with("DELETE FROM \"totals\""));
+        assert_eq!(json(delete), vec![json!({"id": 2}), json!({"id": 3})]);
+        assert!(upsert
+            .sql
+            .starts_with("INSERT INTO \"totals\" (\"id\", \"total\")"));
+        assert_eq!(
+            json(upsert),
+            vec![
+                json!({"id": 1, "total": 2.0}),
+                json!({"id": 4, "total": 1.0})
+            ]
+        );
+        assert!(sink.pending.is_empty());
+    }
+}
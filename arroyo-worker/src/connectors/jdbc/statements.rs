@@ -0,0 +1,354 @@
+use bincode::{Decode, Encode};
+use serde_json::{Map, Value};
+
+/// The SQL dialects of the databases we can write to. Values are bound as parameters rather than
+/// written into statements. MySQL converts parameters to the types of their columns, while
+/// Postgres checks the types of parameters strictly, so rows are instead passed to it as a single
+/// JSON parameter that it converts to the row type of the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Postgres,
+    MySql,
+}
+
+/// A parameter of a statement.
+#[derive(Debug, Clone, Encode, Decode, PartialEq)]
+pub enum Param {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl From<&Value> for Param {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Null => Param::Null,
+            Value::Bool(b) => Param::Bool(*b),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => Param::Int(i),
+                None => Param::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            Value::String(s) => Param::Text(s.clone()),
+            v => Param::Text(v.to_string()),
+        }
+    }
+}
+
+impl Param {
+    pub fn to_postgres(&self) -> &(dyn tokio_postgres::types::ToSql + Sync) {
+        match self {
+            Param::Null => &None::<&str>,
+            Param::Bool(b) => b,
+            Param::Int(i) => i,
+            Param::Float(f) => f,
+            Param::Text(s) => s,
+        }
+    }
+
+    pub fn to_mysql(&self) -> mysql_async::Value {
+        match self {
+            Param::Null => mysql_async::Value::NULL,
+            Param::Bool(b) => mysql_async::Value::Int(*b as i64),
+            Param::Int(i) => mysql_async::Value::Int(*i),
+            Param::Float(f) => mysql_async::Value::Double(*f),
+            Param::Text(s) => mysql_async::Value::Bytes(s.as_bytes().to_vec()),
+        }
+    }
+}
+
+/// A statement, which is executed once for each of its rows of parameters.
+#[derive(Debug, Clone, Encode, Decode, PartialEq)]
+pub struct Statement {
+    pub sql: String,
+    pub params: Vec<Vec<Param>>,
+}
+
+impl Dialect {
+    pub fn quote_ident(&self, ident: &str) -> String {
+        match self {
+            Dialect::Postgres => format!("\"{}\"", ident.replace('"', "\"\"")),
+            Dialect::MySql => format!("`{}`", ident.replace('`', "``")),
+        }
+    }
+
+    /// Quotes a table name, which may be qualified by its schema (or database in MySQL).
+    pub fn quote_table(&self, table: &str) -> String {
+        table
+            .split('.')
+            .map(|part| self.quote_ident(part))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    fn placeholders(&self, n: usize) -> String {
+        vec!["?"; n].join(", ")
+    }
+
+    // the rows as a JSON array of objects, which Postgres converts to the row type of the table
+    fn json_rows(&self, columns: &[String], rows: &[Map<String, Value>]) -> Vec<Vec<Param>> {
+        let rows: Vec<Map<String, Value>> = rows
+            .iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .map(|c| (c.clone(), row.get(c).cloned().unwrap_or(Value::Null)))
+                    .collect()
+            })
+            .collect();
+        vec![vec![Param::Text(Value::from(rows).to_string())]]
+    }
+
+    fn positional_rows(&self, columns: &[String], rows: &[Map<String, Value>]) -> Vec<Vec<Param>> {
+        rows.iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .map(|c| Param::from(row.get(c).unwrap_or(&Value::Null)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// An insert of the rows that updates any existing rows with the same primary key.
+    pub fn upsert(
+        &self,
+        table: &str,
+        columns: &[String],
+        primary_keys: &[String],
+        rows: &[Map<String, Value>],
+    ) -> Statement {
+        let table = self.quote_table(table);
+        let column_list = columns
+            .iter()
+            .map(|c| self.quote_ident(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let updated: Vec<_> = columns
+            .iter()
+            .filter(|c| !primary_keys.contains(c))
+            .map(|c| self.quote_ident(c))
+            .collect();
+
+        match self {
+            Dialect::Postgres => {
+                let keys = primary_keys
+                    .iter()
+                    .map(|k| self.quote_ident(k))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                let on_conflict = if updated.is_empty() {
+                    format!("ON CONFLICT ({}) DO NOTHING", keys)
+                } else {
+                    format!(
+                        "ON CONFLICT ({}) DO UPDATE SET {}",
+                        keys,
+                        updated
+                            .iter()
+                            .map(|c| format!("{} = EXCLUDED.{}", c, c))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                };
+
+                Statement {
+                    sql: format!(
+                        "INSERT INTO {} ({}) SELECT {} FROM json_populate_recordset(NULL::{}, $1::text::json) {}",
+                        table, column_list, column_list, table, on_conflict
+                    ),
+                    params: self.json_rows(columns, rows),
+                }
+            }
+            Dialect::MySql => {
+                let updated = if updated.is_empty() {
+                    // MySQL has no way to do nothing on conflict, so we set a key to itself
+                    vec![self.quote_ident(&primary_keys[0])]
+                } else {
+                    updated
+                };
+
+                Statement {
+                    sql: format!(
+                        "INSERT INTO {} ({}) VALUES ({}) ON DUPLICATE KEY UPDATE {}",
+                        table,
+                        column_list,
+                        self.placeholders(columns.len()),
+                        updated
+                            .iter()
+                            .map(|c| format!("{} = VALUES({})", c, c))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    params: self.positional_rows(columns, rows),
+                }
+            }
+        }
+    }
+
+    /// A delete of the rows with the given primary keys.
+    pub fn delete(&self, table: &str, primary_keys: &[String], keys: &[Vec<Value>]) -> Statement {
+        let table = self.quote_table(table);
+        let rows: Vec<Map<String, Value>> = keys
+            .iter()
+            .map(|key| {
+                primary_keys
+                    .iter()
+                    .cloned()
+                    .zip(key.iter().cloned())
+                    .collect()
+            })
+            .collect();
+
+        match self {
+            Dialect::Postgres => {
+                let condition = primary_keys
+                    .iter()
+                    .map(|k| {
+                        let k = self.quote_ident(k);
+                        format!("target.{} = deleted.{}", k, k)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" AND ");
+
+                Statement {
+                    sql: format!(
+                        "DELETE FROM {} AS target USING json_populate_recordset(NULL::{}, $1::text::json) AS deleted WHERE {}",
+                        table, table, condition
+                    ),
+                    params: self.json_rows(primary_keys, &rows),
+                }
+            }
+            Dialect::MySql => {
+                let condition = primary_keys
+                    .iter()
+                    .map(|k| format!("{} = ?", self.quote_ident(k)))
+                    .collect::<Vec<_>>()
+                    .join(" AND ");
+
+                Statement {
+                    sql: format!("DELETE FROM {} WHERE {}", table, condition),
+                    params: self.positional_rows(primary_keys, &rows),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dialect, Param};
+    use serde_json::{json, Map, Value};
+
+    fn row(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(Dialect::Postgres.quote_ident("a\"b"), "\"a\"\"b\"");
+        assert_eq!(Dialect::MySql.quote_ident("a`b"), "`a``b`");
+        assert_eq!(
+            Dialect::Postgres.quote_table("public.totals"),
+            "\"public\".\"totals\""
+        );
+        assert_eq!(Dialect::MySql.quote_table("totals"), "`totals`");
+    }
+
+    #[test]
+    fn test_params() {
+        assert_eq!(Param::from(&json!(null)), Param::Null);
+        assert_eq!(Param::from(&json!(true)), Param::Bool(true));
+        assert_eq!(Param::from(&json!(3)), Param::Int(3));
+        assert_eq!(Param::from(&json!(1.5)), Param::Float(1.5));
+        assert_eq!(Param::from(&json!("it's")), Param::Text("it's".to_string()));
+        assert_eq!(
+            Param::from(&json!({"a": 1})),
+            Param::Text("{\"a\":1}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_upsert() {
+        let columns = vec!["id".to_string(), "total".to_string()];
+        let keys = vec!["id".to_string()];
+        let rows = vec![
+            row(json!({"id": 1, "total": 10})),
+            row(json!({"id": 2, "total": "it's"})),
+        ];
+
+        let upsert = Dialect::Postgres.upsert("totals", &columns, &keys, &rows);
+        assert_eq!(
+            upsert.sql,
+            "INSERT INTO \"totals\" (\"id\", \"total\") SELECT \"id\", \"total\" \
+            FROM json_populate_recordset(NULL::\"totals\", $1::text::json) \
+            ON CONFLICT (\"id\") DO UPDATE SET \"total\" = EXCLUDED.\"total\""
+        );
+        assert_eq!(
+            upsert.params,
+            vec![vec![Param::Text(
+                "[{\"id\":1,\"total\":10},{\"id\":2,\"total\":\"it's\"}]".to_string()
+            )]]
+        );
+
+        let upsert = Dialect::MySql.upsert("totals", &columns, &keys, &rows);
+        assert_eq!(
+            upsert.sql,
+            "INSERT INTO `totals` (`id`, `total`) VALUES (?, ?) \
+            ON DUPLICATE KEY UPDATE `total` = VALUES(`total`)"
+        );
+        assert_eq!(
+            upsert.params,
+            vec![
+                vec![Param::Int(1), Param::Int(10)],
+                vec![Param::Int(2), Param::Text("it's".to_string())]
+            ]
+        );
+
+        assert_eq!(
+            Dialect::Postgres
+                .upsert("ids", &keys, &keys, &[row(json!({"id": 1}))])
+                .sql,
+            "INSERT INTO \"ids\" (\"id\") SELECT \"id\" \
+            FROM json_populate_recordset(NULL::\"ids\", $1::text::json) \
+            ON CONFLICT (\"id\") DO NOTHING"
+        );
+    }
+
+    #[test]
+    fn test_delete() {
+        let keys = vec!["id".to_string(), "region".to_string()];
+        let delete = Dialect::Postgres.delete("totals", &keys, &[vec![json!(1), json!("us")]]);
+        assert_eq!(
+            delete.sql,
+            "DELETE FROM \"totals\" AS target \
+            USING json_populate_recordset(NULL::\"totals\", $1::text::json) AS deleted \
+            WHERE target.\"id\" = deleted.\"id\" AND target.\"region\" = deleted.\"region\""
+        );
+        assert_eq!(
+            delete.params,
+            vec![vec![Param::Text(
+                "[{\"id\":1,\"region\":\"us\"}]".to_string()
+            )]]
+        );
+
+        let delete = Dialect::MySql.delete(
+            "totals",
+            &keys,
+            &[vec![json!(1), json!("us")], vec![json!(2), json!("eu")]],
+        );
+        assert_eq!(
+            delete.sql,
+            "DELETE FROM `totals` WHERE `id` = ? AND `region` = ?"
+        );
+        assert_eq!(
+            delete.params,
+            vec![
+                vec![Param::Int(1), Param::Text("us".to_string())],
+                vec![Param::Int(2), Param::Text("eu".to_string())]
+            ]
+        );
+    }
+}
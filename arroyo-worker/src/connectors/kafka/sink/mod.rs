@@ -1,5 +1,6 @@
 use crate::engine::{Context, StreamNode};
 use anyhow::Result;
+use arrow::datatypes::DataType;
 use arroyo_formats::DataSerializer;
 use arroyo_formats::SchemaData;
 use arroyo_macro::process_fn;
@@ -12,6 +13,7 @@ use std::marker::PhantomData;
 
 use tracing::{error, warn};
 
+use rdkafka::message::{Header, OwnedHeaders};
 use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord, Producer};
 use rdkafka::util::Timeout;
 
@@ -37,6 +39,9 @@ pub struct KafkaSinkFunc<K: Key + Serialize, T: SchemaData> {
     write_futures: Vec<DeliveryFuture>,
     client_config: HashMap<String, String>,
     serializer: DataSerializer<T>,
+    key_field: Option<String>,
+    key_is_bytes: bool,
+    headers_field: Option<String>,
     _t: PhantomData<K>,
 }
 
@@ -60,6 +65,63 @@ impl From<SinkCommitMode> for ConsistencyMode {
     }
 }
 
+// the key of a message written from a field: text and bytes are written as-is, null leaves the
+// message without a key, and anything else is written as JSON
+fn message_key(value: &serde_json::Value, is_bytes: bool) -> Result<Option<Vec<u8>>, String> {
+    match value {
+        serde_json::Value::Null => Ok(None),
+        serde_json::Value::String(s) => Ok(Some(s.as_bytes().to_vec())),
+        // bytes are serialized as arrays of numbers
+        serde_json::Value::Array(values) if is_bytes => values
+            .iter()
+            .map(|v| {
+                v.as_u64()
+                    .and_then(|b| u8::try_from(b).ok())
+                    .ok_or_else(|| format!("{} is not a byte", v))
+            })
+            .collect::<Result<_, _>>()
+            .map(Some),
+        value => Ok(Some(value.to_string().into_bytes())),
+    }
+}
+
+// whether the field holds bytes, rather than a value to be written as JSON
+fn is_bytes_field<T: SchemaData>(field: &str) -> bool {
+    T::schema().field_with_name(field).map_or(false, |f| {
+        matches!(f.data_type(), DataType::Binary | DataType::LargeBinary)
+    })
+}
+
+// the headers of a message written from a field holding a JSON object from header names to their
+// values; text values are written as-is, and anything else as JSON
+fn message_headers(value: &serde_json::Value) -> Result<Option<OwnedHeaders>, String> {
+    let serde_json::Value::String(json) = value else {
+        return if value.is_null() {
+            Ok(None)
+        } else {
+            Err(format!("expected a JSON string, found {}", value))
+        };
+    };
+
+    let fields: serde_json::Map<String, serde_json::Value> = serde_json::from_str(json)
+        .map_err(|e| format!("'{}' is not a JSON object: {}", json, e))?;
+
+    let mut headers = OwnedHeaders::new_with_capacity(fields.len());
+    for (key, value) in &fields {
+        let value = match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(s) => Some(s.clone()),
+            value => Some(value.to_string()),
+        };
+        headers = headers.insert(Header {
+            key,
+            value: value.as_ref(),
+        });
+    }
+
+    Ok(Some(headers))
+}
+
 impl<K: Key + Serialize, T: SchemaData> KafkaSinkFunc<K, T> {
     pub fn new(
         servers: &str,
@@ -78,6 +140,9 @@ impl<K: Key + Serialize, T: SchemaData> KafkaSinkFunc<K, T> {
                 .map(|(key, value)| (key.to_string(), value.to_string()))
                 .collect(),
             serializer: DataSerializer::new(format),
+            key_field: None,
+            key_is_bytes: false,
+            headers_field: None,
             _t: PhantomData,
         }
     }
@@ -89,7 +154,12 @@ impl<K: Key + Serialize, T: SchemaData> KafkaSinkFunc<K, T> {
             .expect("Invalid connection config for KafkaSink");
         let table: KafkaTable =
             serde_json::from_value(config.table).expect("Invalid table config for KafkaSource");
-        let TableType::Sink { commit_mode } = table.type_ else {
+        let TableType::Sink {
+            commit_mode,
+            key_field,
+            headers_field,
+        } = table.type_
+        else {
             panic!("found non-sink kafka config in sink operator");
         };
 
@@ -103,6 +173,11 @@ impl<K: Key + Serialize, T: SchemaData> KafkaSinkFunc<K, T> {
             serializer: DataSerializer::new(
                 config.format.expect("Format must be defined for KafkaSink"),
             ),
+            key_is_bytes: key_field
+                .as_ref()
+                .map_or(false, |field| is_bytes_field::<T>(field)),
+            key_field,
+            headers_field,
             _t: PhantomData,
         }
     }
@@ -211,14 +286,14 @@ impl<K: Key + Serialize, T: SchemaData + Serialize> KafkaSinkFunc<K, T> {
         }
     }
 
-    async fn publish(&mut self, k: Option<String>, v: Vec<u8>) {
-        let mut rec = {
-            if let Some(k) = k.as_ref() {
-                FutureRecord::to(&self.topic).key(k).payload(&v)
-            } else {
-                FutureRecord::to(&self.topic).payload(&v)
-            }
-        };
+    async fn publish(&mut self, k: Option<Vec<u8>>, v: Vec<u8>, headers: Option<OwnedHeaders>) {
+        let mut rec = FutureRecord::to(&self.topic).payload(&v);
+        if let Some(k) = k.as_ref() {
+            rec = rec.key(k);
+        }
+        if let Some(headers) = headers {
+            rec = rec.headers(headers);
+        }
 
         loop {
             match self.producer.as_mut().unwrap().send_result(rec) {
@@ -239,15 +314,48 @@ impl<K: Key + Serialize, T: SchemaData + Serialize> KafkaSinkFunc<K, T> {
         }
     }
 
-    async fn process_element(&mut self, record: &Record<K, T>, _ctx: &mut Context<(), ()>) {
-        let k = record
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), ()>) {
+        let mut k = record
             .key
             .as_ref()
-            .map(|k| serde_json::to_string(k).unwrap());
-        let v = self.serializer.to_vec(&record.value);
+            .map(|k| serde_json::to_string(k).unwrap().into_bytes());
+        let mut headers = None;
+
+        if self.key_field.is_some() || self.headers_field.is_some() {
+            let value = serde_json::to_value(&record.value).unwrap();
+            if let Some(key_field) = &self.key_field {
+                k = match value
+                    .get(key_field)
+                    .map(|v| message_key(v, self.key_is_bytes))
+                {
+                    Some(Ok(k)) => k,
+                    Some(Err(e)) => {
+                        ctx.report_user_error(UserError::new(
+                            "Could not write Kafka message key",
+                            format!("invalid key in field '{}': {}", key_field, e),
+                        ))
+                        .await;
+                        return;
+                    }
+                    None => None,
+                };
+            }
+            if let Some(headers_field) = &self.headers_field {
+                headers = match value.get(headers_field).map(message_headers) {
+                    Some(Ok(headers)) => headers,
+                    Some(Err(e)) => {
+                        warn!("not writing headers from field '{}': {}", headers_field, e);
+                        None
+                    }
+                    None => None,
+                };
+            }
+        }
 
-        if let Some(v) = v {
-            self.publish(k, v).await;
+        match self.serializer.to_vec(&record.value) {
+            Ok(Some(v)) => self.publish(k, v, headers).await,
+            Ok(None) => {}
+            Err(e) => ctx.report_user_error(e).await,
         }
     }
 
@@ -10,11 +10,12 @@ use arroyo_types::CheckpointBarrier;
 use arroyo_types::*;
 use rdkafka::admin::{AdminClient, AdminOptions, NewTopic};
 use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::message::Headers;
 use rdkafka::producer::Producer;
 use rdkafka::{ClientConfig, Message};
 use tokio::sync::mpsc::channel;
 
-use super::KafkaSinkFunc;
+use super::{message_headers, message_key, KafkaSinkFunc};
 
 pub struct KafkaTopicTester {
     topic: String,
@@ -231,3 +232,37 @@ async fn test_kafka() {
         assert_eq!(record.value, result);
     }
 }
+
+#[test]
+fn test_message_key_and_headers() {
+    assert_eq!(
+        Ok(Some(b"user-1".to_vec())),
+        message_key(&serde_json::json!("user-1"), false)
+    );
+    assert_eq!(
+        Ok(Some(vec![1, 2])),
+        message_key(&serde_json::json!([1, 2]), true)
+    );
+    assert!(message_key(&serde_json::json!([1, 256]), true).is_err());
+    // arrays of numbers are only bytes if the field is
+    assert_eq!(
+        Ok(Some(b"[1,256]".to_vec())),
+        message_key(&serde_json::json!([1, 256]), false)
+    );
+    assert_eq!(
+        Ok(Some(b"5".to_vec())),
+        message_key(&serde_json::json!(5), false)
+    );
+    assert_eq!(Ok(None), message_key(&serde_json::Value::Null, false));
+
+    let headers = message_headers(&serde_json::json!("{\"a\": \"x\", \"b\": 1}"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(2, headers.count());
+    assert_eq!("a", headers.get(0).key);
+    assert_eq!(Some(&b"x"[..]), headers.get(0).value);
+    assert_eq!(Some(&b"1"[..]), headers.get(1).value);
+
+    assert!(message_headers(&serde_json::Value::Null).unwrap().is_none());
+    assert!(message_headers(&serde_json::json!("[1]")).is_err());
+}
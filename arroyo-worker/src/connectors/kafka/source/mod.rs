@@ -1,24 +1,25 @@
 use crate::engine::{Context, StreamNode};
 use crate::SourceFinishType;
-use arroyo_formats::{DataDeserializer, SchemaData};
+use arroyo_formats::{DataDeserializer, Metadata, SchemaData};
 use arroyo_macro::source_fn;
 use arroyo_rpc::formats::{Format, Framing};
 use arroyo_rpc::grpc::TableDescriptor;
 use arroyo_rpc::schema_resolver::{ConfluentSchemaRegistry, FailingSchemaResolver, SchemaResolver};
-use arroyo_rpc::OperatorConfig;
 use arroyo_rpc::{grpc::StopMode, ControlMessage, ControlResp};
+use arroyo_rpc::{MetadataField, OperatorConfig};
 use arroyo_state::tables::global_keyed_map::GlobalKeyedState;
 use arroyo_types::*;
 use bincode::{Decode, Encode};
 use governor::{Quota, RateLimiter};
 use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::message::{BorrowedMessage, Headers};
 use rdkafka::{ClientConfig, Message as KMessage, Offset, TopicPartitionList};
 use serde::de::DeserializeOwned;
 use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::num::NonZeroU32;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use tokio::select;
 use tracing::{debug, error, info, warn};
 
@@ -40,6 +41,7 @@ where
     deserializer: DataDeserializer<T>,
     client_configs: HashMap<String, String>,
     messages_per_second: NonZeroU32,
+    metadata_fields: Vec<MetadataField>,
     _t: PhantomData<K>,
 }
 
@@ -80,6 +82,7 @@ where
                 .map(|(key, value)| (key.to_string(), value.to_string()))
                 .collect(),
             messages_per_second: NonZeroU32::new(messages_per_second).unwrap(),
+            metadata_fields: vec![],
             _t: PhantomData,
         }
     }
@@ -142,6 +145,7 @@ where
                     .unwrap_or(u32::MAX),
             )
             .unwrap(),
+            metadata_fields: config.metadata_fields,
             _t: PhantomData,
         }
     }
@@ -178,7 +182,7 @@ where
 
         let mut s: GlobalKeyedState<i32, KafkaState, _> =
             ctx.state.get_global_keyed_state('k').await;
-        let state: Vec<&KafkaState> = s.get_all();
+        let state: Vec<&KafkaState> = s.get_all().await;
 
         // did we restore any partitions?
         let has_state = !state.is_empty();
@@ -272,10 +276,17 @@ where
                                 let iter = self.deserializer.deserialize_slice(v).await;
 
                                 for value in iter {
+                                    let mut value = value?;
+                                    for field in &self.metadata_fields {
+                                        message_metadata(&msg, &field.key).and_then(|m| value.set_metadata(&field.field_name, m))
+                                            .map_err(|e| UserError::new("Failed to read metadata from Kafka record",
+                                                format!("could not set field '{}' from '{}': {}", field.field_name, field.key, e)))?;
+                                    }
+
                                     ctx.collector.collect(Record {
                                         timestamp: from_millis(timestamp as u64),
                                         key: None,
-                                        value: value?,
+                                        value,
                                     }).await;
                                 }
 
@@ -341,3 +352,44 @@ where
         }
     }
 }
+
+fn message_metadata(msg: &BorrowedMessage, key: &str) -> Result<Metadata, String> {
+    Ok(match key {
+        "key" => Metadata::Bytes(msg.key().map(|k| k.to_vec())),
+        "headers" => Metadata::Text(Some(headers_json(msg.headers()))),
+        "topic" => Metadata::Text(Some(msg.topic().to_string())),
+        "partition" => Metadata::Int(msg.partition() as i64),
+        "offset" => Metadata::Int(msg.offset()),
+        "timestamp" => Metadata::Timestamp(
+            msg.timestamp()
+                .to_millis()
+                .map(|t| from_millis(t as u64))
+                .unwrap_or(SystemTime::UNIX_EPOCH),
+        ),
+        _ => return Err(format!("unsupported Kafka metadata '{}'", key)),
+    })
+}
+
+// headers are exposed as a JSON object from their names to their values, which are read as
+// (lossy) UTF-8 or null if they're missing
+fn headers_json<H: Headers>(headers: Option<&H>) -> String {
+    let headers: serde_json::Map<String, serde_json::Value> = headers
+        .map(|headers| {
+            headers
+                .iter()
+                .map(|h| {
+                    (
+                        h.key.to_string(),
+                        h.value
+                            .map(|v| {
+                                serde_json::Value::String(String::from_utf8_lossy(v).to_string())
+                            })
+                            .unwrap_or(serde_json::Value::Null),
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    serde_json::Value::Object(headers).to_string()
+}
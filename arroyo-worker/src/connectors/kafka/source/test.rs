@@ -103,6 +103,7 @@ impl KafkaTopicTester {
             start_time: to_micros(SystemTime::now()),
             finish_time: to_micros(SystemTime::now()),
             operator_ids: vec![task_info.operator_id.clone()],
+            backend: StateBackend::name().to_string(),
         });
 
         let mut ctx: Context<(), TestData> = Context::new(
@@ -268,6 +269,7 @@ async fn test_kafka() {
         start_time: 0,
         finish_time: 0,
         operator_ids: vec![task_info.operator_id.clone()],
+        backend: StateBackend::name().to_string(),
     })
     .await;
 
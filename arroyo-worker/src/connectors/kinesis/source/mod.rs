@@ -234,6 +234,7 @@ impl<K: Data, T: SchemaData> KinesisSourceFunc<K, T> {
             ctx.state.get_global_keyed_state('k').await;
         for (shard_id, shard_state) in s
             .get_all()
+            .await
             .into_iter()
             .map(|shard_state| (shard_state.shard_id.clone(), shard_state.clone()))
             .filter(|(shard_id, _shard_state)| {
@@ -1,11 +1,16 @@
 pub mod blackhole;
 pub mod filesystem;
 pub mod fluvio;
+pub mod http_lookup;
 pub mod impulse;
+pub mod jdbc;
 pub mod kafka;
 pub mod kinesis;
+pub mod mqtt;
+pub mod nats;
 pub mod nexmark;
 pub mod polling_http;
+pub mod postgres_cdc;
 pub mod redis;
 pub mod sse;
 pub mod two_phase_committer;
@@ -0,0 +1,185 @@
+use crate::engine::{Context, StreamNode};
+use arroyo_formats::{DataSerializer, SchemaData};
+use arroyo_macro::process_fn;
+use arroyo_rpc::formats::Format;
+use arroyo_rpc::OperatorConfig;
+use arroyo_types::*;
+use rumqttc::{AsyncClient, ConnectionError, Event, Outgoing, Packet, QoS};
+use serde::Serialize;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::{create_connection, MqttConfig, MqttTable, TableType};
+
+/// Publishes to an MQTT topic. Checkpoints wait until every message published before them has
+/// been written to the broker and, for QoS 1 and 2, acknowledged by it.
+#[derive(StreamNode)]
+pub struct MqttSinkFunc<K: Key + Serialize, T: SchemaData + Serialize> {
+    config: MqttConfig,
+    topic: String,
+    qos: QoS,
+    retain: bool,
+    serializer: DataSerializer<T>,
+    client: Option<AsyncClient>,
+    event_loop: Option<JoinHandle<()>>,
+    // messages that have been published but not yet sent (QoS 0) or acknowledged (QoS 1 and 2)
+    outstanding: Arc<AtomicUsize>,
+    completed: Arc<Notify>,
+    _t: PhantomData<K>,
+}
+
+impl<K: Key + Serialize, T: SchemaData + Serialize> MqttSinkFunc<K, T> {
+    pub fn new(config: MqttConfig, topic: &str, qos: QoS, retain: bool, format: Format) -> Self {
+        Self {
+            config,
+            topic: topic.to_string(),
+            qos,
+            retain,
+            serializer: DataSerializer::new(format),
+            client: None,
+            event_loop: None,
+            outstanding: Arc::new(AtomicUsize::new(0)),
+            completed: Arc::new(Notify::new()),
+            _t: PhantomData,
+        }
+    }
+
+    pub fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for MqttSink");
+        let connection: MqttConfig = serde_json::from_value(config.connection)
+            .expect("Invalid connection config for MqttSink");
+        let table: MqttTable =
+            serde_json::from_value(config.table).expect("Invalid table config for MqttSink");
+        let TableType::Sink { retain } = table.type_ else {
+            panic!("found non-sink mqtt config in sink operator");
+        };
+
+        Self::new(
+            connection,
+            &table.topic,
+            table.qos(),
+            retain,
+            config.format.expect("Format must be defined for MqttSink"),
+        )
+    }
+}
+
+#[process_fn(in_k = K, in_t = T)]
+impl<K: Key + Serialize, T: SchemaData + Serialize> MqttSinkFunc<K, T> {
+    fn name(&self) -> String {
+        format!("mqtt-producer-{}", self.topic)
+    }
+
+    async fn on_start(&mut self, ctx: &mut Context<(), ()>) {
+        let id = format!(
+            "{}-{}-{}",
+            ctx.task_info.job_id, ctx.task_info.operator_id, ctx.task_info.task_index
+        );
+
+        let (client, mut eventloop) = match create_connection(&self.config, &id, true, false) {
+            Ok(c) => c,
+            Err(e) => {
+                ctx.report_error("Failed to construct MQTT client".to_string(), e.to_string())
+                    .await;
+                panic!("Failed to construct MQTT client: {:?}", e);
+            }
+        };
+
+        // wait until we're connected so that bad credentials or addresses fail the pipeline
+        loop {
+            match eventloop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    ctx.report_error(
+                        "Failed to connect to MQTT broker".to_string(),
+                        format!("{:?}", e),
+                    )
+                    .await;
+                    panic!("Failed to connect to MQTT broker: {:?}", e);
+                }
+            }
+        }
+        info!("Connected to MQTT broker at {}", self.config.url);
+
+        // the event loop has to be polled for publishes to make progress
+        let qos = self.qos;
+        let outstanding = self.outstanding.clone();
+        let completed = self.completed.clone();
+        self.event_loop = Some(tokio::spawn(async move {
+            loop {
+                let done = match eventloop.poll().await {
+                    Ok(Event::Outgoing(Outgoing::Publish(_))) => qos == QoS::AtMostOnce,
+                    Ok(Event::Incoming(Packet::PubAck(_))) => qos == QoS::AtLeastOnce,
+                    Ok(Event::Incoming(Packet::PubComp(_))) => qos == QoS::ExactlyOnce,
+                    Ok(_) => false,
+                    Err(ConnectionError::RequestsDone) => return,
+                    Err(e) => {
+                        // the client reconnects on the next poll, resending unacknowledged messages
+                        warn!("error while writing to MQTT broker: {:?}", e);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        false
+                    }
+                };
+                if done {
+                    outstanding.fetch_sub(1, Ordering::SeqCst);
+                    completed.notify_waiters();
+                }
+            }
+        }));
+        self.client = Some(client);
+    }
+
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), ()>) {
+        let v = match self.serializer.to_vec(&record.value) {
+            Ok(Some(v)) => v,
+            Ok(None) => return,
+            Err(e) => {
+                ctx.report_user_error(e).await;
+                return;
+            }
+        };
+
+        self.outstanding.fetch_add(1, Ordering::SeqCst);
+        self.client
+            .as_ref()
+            .unwrap()
+            .publish(&self.topic, self.qos, self.retain, v)
+            .await
+            .expect("MQTT event loop has stopped");
+    }
+
+    async fn handle_checkpoint(&mut self, _: &CheckpointBarrier, _: &mut Context<(), ()>) {
+        loop {
+            // the notification is registered before checking, so that none are missed
+            let completed = self.completed.notified();
+            if self.outstanding.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            completed.await;
+        }
+    }
+
+    async fn on_close(&mut self, _ctx: &mut Context<(), ()>) {
+        if let Some(client) = self.client.take() {
+            if let Err(e) = client.disconnect().await {
+                warn!("failed to disconnect from MQTT broker: {:?}", e);
+            }
+        }
+        // give the event loop a chance to send the disconnect before stopping it
+        if let Some(mut event_loop) = self.event_loop.take() {
+            if tokio::time::timeout(Duration::from_secs(5), &mut event_loop)
+                .await
+                .is_err()
+            {
+                event_loop.abort();
+            }
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use crate::engine::{Context, StreamNode};
+use crate::SourceFinishType;
+use arroyo_formats::{DataDeserializer, SchemaData};
+use arroyo_macro::source_fn;
+use arroyo_rpc::formats::{Format, Framing};
+use arroyo_rpc::grpc::{StopMode, TableDescriptor, TableWriteBehavior, TaskCheckpointEventType};
+use arroyo_rpc::{CheckpointEvent, ControlMessage, ControlResp, OperatorConfig};
+use arroyo_state::tables::global_keyed_map::GlobalKeyedState;
+use arroyo_types::*;
+use bincode::{Decode, Encode};
+use rumqttc::{AsyncClient, ConnectionError, Event, Packet, Publish, QoS};
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, VecDeque};
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime};
+use tokio::select;
+use tracing::{debug, info, warn};
+
+use super::{create_connection, MqttConfig, MqttTable, TableType};
+
+#[cfg(test)]
+mod test;
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq, PartialOrd)]
+pub struct MqttSourceState {
+    topic: String,
+}
+
+pub fn tables() -> Vec<TableDescriptor> {
+    vec![
+        arroyo_state::global_table("m", "mqtt source state"),
+        // holds no data, but has the controller tell us when each checkpoint has committed
+        TableDescriptor {
+            write_behavior: TableWriteBehavior::CommitWrites as i32,
+            ..arroyo_state::global_table("a", "mqtt acknowledgements")
+        },
+    ]
+}
+
+/// Reads from an MQTT subscription. As a subscription can't be partitioned, only the first subtask
+/// reads from it. The session is kept by the broker across restarts, so messages published while
+/// the pipeline is down are delivered (for QoS 1 and 2) once it recovers.
+///
+/// Messages are only acknowledged once a checkpoint that includes them has committed, so the
+/// broker redelivers any that were read after the checkpoint the pipeline recovers from. Brokers
+/// limit how many unacknowledged messages a client may have in flight, which bounds how many QoS 1
+/// and 2 messages can be read between checkpoints.
+#[derive(StreamNode)]
+pub struct MqttSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: SchemaData,
+{
+    config: MqttConfig,
+    topic: String,
+    qos: QoS,
+    deserializer: DataDeserializer<T>,
+    subscribed: Option<String>,
+    client: Option<AsyncClient>,
+    // messages read since the last checkpoint
+    unacked: Vec<Publish>,
+    // messages covered by a checkpoint that hasn't committed yet, by epoch
+    awaiting_commit: BTreeMap<u32, Vec<Publish>>,
+    // committed messages that haven't been handed to the client yet
+    to_ack: VecDeque<Publish>,
+    _t: PhantomData<K>,
+}
+
+#[source_fn(out_k = (), out_t = T)]
+impl<K, T> MqttSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: SchemaData,
+{
+    pub fn new(
+        config: MqttConfig,
+        topic: &str,
+        qos: QoS,
+        format: Format,
+        framing: Option<Framing>,
+    ) -> Self {
+        Self {
+            config,
+            topic: topic.to_string(),
+            qos,
+            deserializer: DataDeserializer::new(format, framing),
+            subscribed: None,
+            client: None,
+            unacked: vec![],
+            awaiting_commit: BTreeMap::new(),
+            to_ack: VecDeque::new(),
+            _t: PhantomData,
+        }
+    }
+
+    pub fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for MqttSource");
+        let connection: MqttConfig = serde_json::from_value(config.connection)
+            .expect("Invalid connection config for MqttSource");
+        let table: MqttTable =
+            serde_json::from_value(config.table).expect("Invalid table config for MqttSource");
+        let TableType::Source { .. } = &table.type_ else {
+            panic!("found non-source mqtt config in source operator");
+        };
+
+        Self {
+            config: connection,
+            qos: table.qos(),
+            topic: table.topic,
+            deserializer: DataDeserializer::new(
+                config.format.expect("Format must be set for MQTT source"),
+                config.framing,
+            ),
+            subscribed: None,
+            client: None,
+            unacked: vec![],
+            awaiting_commit: BTreeMap::new(),
+            to_ack: VecDeque::new(),
+            _t: PhantomData,
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("mqtt-{}", self.topic)
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        tables()
+    }
+
+    async fn run(&mut self, ctx: &mut Context<(), T>) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(r) => r,
+            Err(e) => {
+                ctx.report_error(e.name.clone(), e.details.clone()).await;
+
+                panic!("{}: {}", e.name, e.details);
+            }
+        }
+    }
+
+    async fn our_handle_control_message(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        msg: Option<ControlMessage>,
+    ) -> Option<SourceFinishType> {
+        match msg? {
+            ControlMessage::Checkpoint(c) => {
+                debug!("starting checkpointing {}", ctx.task_info.task_index);
+                if let Some(topic) = &self.subscribed {
+                    let mut s: GlobalKeyedState<(), MqttSourceState, _> =
+                        ctx.state.get_global_keyed_state('m').await;
+                    s.insert(
+                        (),
+                        MqttSourceState {
+                            topic: topic.clone(),
+                        },
+                    )
+                    .await;
+                }
+
+                self.awaiting_commit
+                    .insert(c.epoch, std::mem::take(&mut self.unacked));
+
+                if self.checkpoint(c, ctx).await {
+                    return Some(SourceFinishType::Immediate);
+                }
+            }
+            ControlMessage::Stop { mode } => {
+                info!("Stopping mqtt source: {:?}", mode);
+
+                match mode {
+                    StopMode::Graceful => {
+                        return Some(SourceFinishType::Graceful);
+                    }
+                    StopMode::Immediate => {
+                        return Some(SourceFinishType::Immediate);
+                    }
+                }
+            }
+            ControlMessage::Commit { epoch, .. } => {
+                // checkpoints commit in order, so this covers all earlier epochs as well
+                let uncommitted = self.awaiting_commit.split_off(&(epoch + 1));
+                let committed = std::mem::replace(&mut self.awaiting_commit, uncommitted);
+                self.to_ack.extend(committed.into_values().flatten());
+                self.send_acks();
+
+                ctx.control_tx
+                    .send(ControlResp::CheckpointEvent(CheckpointEvent {
+                        checkpoint_epoch: epoch,
+                        operator_id: ctx.task_info.operator_id.clone(),
+                        subtask_index: ctx.task_info.task_index as u32,
+                        time: SystemTime::now(),
+                        event_type: TaskCheckpointEventType::FinishedCommit,
+                    }))
+                    .await
+                    .expect("sent commit event");
+            }
+            ControlMessage::LoadCompacted { compacted } => {
+                ctx.load_compacted(compacted).await;
+            }
+            ControlMessage::NoOp => {}
+        }
+        None
+    }
+
+    // hands committed acks to the client, as long as its request queue has room; the rest are
+    // sent once the event loop has made progress
+    fn send_acks(&mut self) {
+        let Some(client) = &self.client else {
+            return;
+        };
+        while let Some(publish) = self.to_ack.front() {
+            if client.try_ack(publish).is_err() {
+                return;
+            }
+            self.to_ack.pop_front();
+        }
+    }
+
+    async fn run_int(&mut self, ctx: &mut Context<(), T>) -> Result<SourceFinishType, UserError> {
+        if ctx.task_info.task_index != 0 {
+            ctx.broadcast(Message::Watermark(Watermark::Idle)).await;
+
+            loop {
+                let msg = ctx.control_rx.recv().await;
+                if let Some(r) = self.our_handle_control_message(ctx, msg).await {
+                    return Ok(r);
+                }
+            }
+        }
+
+        // the client id is stable across restarts so that the broker resumes our session
+        let id = format!("{}-{}", ctx.task_info.job_id, ctx.task_info.operator_id);
+        let (client, mut eventloop) = create_connection(&self.config, &id, false, true)
+            .map_err(|e| UserError::new("Could not create MQTT client", format!("{:?}", e)))?;
+
+        // the restored session may still be subscribed to a topic the source no longer reads
+        let previous = {
+            let mut s: GlobalKeyedState<(), MqttSourceState, _> =
+                ctx.state.get_global_keyed_state('m').await;
+            s.get(&()).await.cloned()
+        };
+        if let Some(state) = previous {
+            if state.topic != self.topic {
+                info!("unsubscribing from previous topic {}", state.topic);
+                client.unsubscribe(&state.topic).await.map_err(|e| {
+                    UserError::new("Failed to unsubscribe from MQTT topic", format!("{:?}", e))
+                })?;
+            }
+        }
+
+        client
+            .subscribe(&self.topic, self.qos)
+            .await
+            .map_err(|e| UserError::new("Failed to subscribe to MQTT topic", format!("{:?}", e)))?;
+        self.subscribed = Some(self.topic.clone());
+        self.client = Some(client.clone());
+
+        loop {
+            select! {
+                event = eventloop.poll() => {
+                    self.send_acks();
+                    match event {
+                        Ok(Event::Incoming(Packet::Publish(p))) => {
+                            let iter = self.deserializer.deserialize_slice(&p.payload).await;
+                            for value in iter {
+                                ctx.collector.collect(Record {
+                                    timestamp: SystemTime::now(),
+                                    key: None,
+                                    value: value?,
+                                }).await;
+                            }
+                            self.unacked.push(p);
+                        }
+                        Ok(Event::Incoming(Packet::ConnAck(ack))) => {
+                            // subscriptions are lost if the broker didn't keep our session
+                            if !ack.session_present {
+                                if let Err(e) = client.try_subscribe(&self.topic, self.qos) {
+                                    warn!("failed to resubscribe to {}: {:?}", self.topic, e);
+                                }
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(ConnectionError::ConnectionRefused(code)) => {
+                            return Err(UserError::new("MQTT broker refused connection", format!("{:?}", code)));
+                        }
+                        Err(e) => {
+                            // the client reconnects on the next poll
+                            warn!("error while reading from MQTT broker: {:?}", e);
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                        }
+                    }
+                }
+                control_message = ctx.control_rx.recv() => {
+                    if let Some(r) = self.our_handle_control_message(ctx, control_message).await {
+                        return Ok(r);
+                    }
+                }
+            }
+        }
+    }
+}
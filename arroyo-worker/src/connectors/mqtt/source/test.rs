@@ -0,0 +1,311 @@
+use arrow::datatypes::{DataType, Field, Schema};
+use arroyo_state::{BackingStore, StateBackend};
+use rand::Rng;
+use std::net::TcpListener;
+use std::time::{Duration, SystemTime};
+
+use crate::connectors::mqtt::source::{self, MqttSourceFunc};
+use crate::connectors::mqtt::MqttConfig;
+use crate::engine::{Context, OutQueue, QueueItem};
+use arroyo_formats::SchemaData;
+use arroyo_rpc::formats::{Format, JsonFormat};
+use arroyo_rpc::grpc::{
+    CheckpointMetadata, OperatorCheckpointMetadata, StopMode, TaskCheckpointEventType,
+};
+use arroyo_rpc::{CheckpointCompleted, ControlMessage, ControlResp};
+use arroyo_types::{to_micros, CheckpointBarrier, Message, TaskInfo};
+use rumqttc::{AsyncClient, MqttOptions, QoS};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode, Serialize, Deserialize, PartialEq)]
+struct TestData {
+    i: u64,
+}
+
+impl SchemaData for TestData {
+    fn name() -> &'static str {
+        "test"
+    }
+
+    fn schema() -> Schema {
+        Schema::new(vec![Field::new("i", DataType::UInt64, false)])
+    }
+
+    fn to_raw_string(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn to_avro(&self, _schema: &apache_avro::Schema) -> apache_avro::types::Value {
+        todo!()
+    }
+}
+
+// starts an embedded broker on a free port, returning its port
+fn start_broker() -> u16 {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let config: rumqttd::Config = serde_json::from_value(serde_json::json!({
+        "id": 0,
+        "router": {
+            "max_connections": 100,
+            "max_outgoing_packet_count": 200,
+            "max_segment_size": 104857600,
+            "max_segment_count": 10
+        },
+        "v4": {
+            "1": {
+                "name": "v4-1",
+                "listen": format!("127.0.0.1:{}", port),
+                "next_connection_delay_ms": 1,
+                "connections": {
+                    "connection_timeout_ms": 60000,
+                    "max_payload_size": 20480,
+                    "max_inflight_count": 100,
+                    "dynamic_filters": true
+                }
+            }
+        }
+    }))
+    .unwrap();
+
+    std::thread::spawn(move || {
+        rumqttd::Broker::new(config).start().unwrap();
+    });
+
+    port
+}
+
+struct MqttSourceWithReads {
+    to_control_tx: Sender<ControlMessage>,
+    from_control_rx: Receiver<ControlResp>,
+    data_recv: Receiver<QueueItem>,
+}
+
+impl MqttSourceWithReads {
+    async fn assert_next_message_record_value(&mut self, expected_value: u64) {
+        loop {
+            let msg: Message<(), TestData> = self
+                .data_recv
+                .recv()
+                .await
+                .expect("option shouldn't be missing")
+                .into();
+            match msg {
+                Message::Record(record) => {
+                    assert_eq!(expected_value, record.value.i);
+                    return;
+                }
+                // the idle watermark and barriers may be interleaved with records
+                Message::Watermark(_) | Message::Barrier(_) => {}
+                msg => unreachable!("expected a record, got {:?}", msg),
+            }
+        }
+    }
+
+    async fn assert_control_checkpoint(&mut self, expected_epoch: u32) -> CheckpointCompleted {
+        loop {
+            let control_response = self
+                .from_control_rx
+                .recv()
+                .await
+                .expect("should be a valid message");
+
+            if let ControlResp::CheckpointCompleted(checkpoint) = control_response {
+                assert_eq!(expected_epoch, checkpoint.checkpoint_epoch);
+                return checkpoint;
+            }
+        }
+    }
+
+    async fn commit(&mut self, epoch: u32) {
+        self.to_control_tx
+            .send(ControlMessage::Commit {
+                epoch,
+                commit_data: HashMap::new(),
+            })
+            .await
+            .unwrap();
+
+        loop {
+            let control_response = self
+                .from_control_rx
+                .recv()
+                .await
+                .expect("should be a valid message");
+
+            if let ControlResp::CheckpointEvent(event) = control_response {
+                if event.event_type == TaskCheckpointEventType::FinishedCommit {
+                    assert_eq!(epoch, event.checkpoint_epoch);
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn stop(&mut self) {
+        self.to_control_tx
+            .send(ControlMessage::Stop {
+                mode: StopMode::Immediate,
+            })
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+async fn get_source_with_reader(
+    port: u16,
+    task_info: TaskInfo,
+    restore_from: Option<u32>,
+) -> MqttSourceWithReads {
+    let mut mqtt: MqttSourceFunc<(), TestData> = MqttSourceFunc::new(
+        MqttConfig {
+            url: format!("tcp://127.0.0.1:{}", port),
+            client_prefix: Some("test".to_string()),
+            username: None,
+            password: None,
+            tls: None,
+            max_packet_size: None,
+        },
+        "arroyo/+/test",
+        QoS::AtLeastOnce,
+        Format::Json(JsonFormat::default()),
+        None,
+    );
+    let (to_control_tx, control_rx) = channel(128);
+    let (command_tx, from_control_rx) = channel(128);
+    let (data_tx, recv) = channel(128);
+
+    let checkpoint_metadata = restore_from.map(|epoch| CheckpointMetadata {
+        job_id: task_info.job_id.to_string(),
+        epoch,
+        min_epoch: 1,
+        start_time: to_micros(SystemTime::now()),
+        finish_time: to_micros(SystemTime::now()),
+        operator_ids: vec![task_info.operator_id.clone()],
+        backend: StateBackend::name().to_string(),
+    });
+
+    let mut ctx: Context<(), TestData> = Context::new(
+        task_info,
+        checkpoint_metadata,
+        control_rx,
+        command_tx,
+        1,
+        vec![vec![OutQueue::new(data_tx, false)]],
+        source::tables(),
+    )
+    .await;
+
+    tokio::spawn(async move {
+        mqtt.on_start(&mut ctx).await;
+        mqtt.run(&mut ctx).await;
+    });
+
+    // give the source time to subscribe
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    MqttSourceWithReads {
+        to_control_tx,
+        from_control_rx,
+        data_recv: recv,
+    }
+}
+
+async fn publisher(port: u16) -> AsyncClient {
+    let (client, mut eventloop) =
+        AsyncClient::new(MqttOptions::new("test-publisher", "127.0.0.1", port), 10);
+    tokio::spawn(async move { while eventloop.poll().await.is_ok() {} });
+    client
+}
+
+async fn publish(client: &AsyncClient, i: u64) {
+    client
+        .publish(
+            "arroyo/device-1/test",
+            QoS::AtLeastOnce,
+            false,
+            serde_json::to_vec(&TestData { i }).unwrap(),
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_mqtt() {
+    let port = start_broker();
+
+    let mut task_info = arroyo_types::get_test_task_info();
+    task_info.job_id = format!("mqtt-job-{}", rand::thread_rng().gen::<u64>());
+
+    let mut reader = get_source_with_reader(port, task_info.clone(), None).await;
+    let client = publisher(port).await;
+
+    for message in 1u64..20 {
+        publish(&client, message).await;
+        reader.assert_next_message_record_value(message).await;
+    }
+
+    reader
+        .to_control_tx
+        .send(ControlMessage::Checkpoint(CheckpointBarrier {
+            epoch: 1,
+            min_epoch: 0,
+            timestamp: SystemTime::now(),
+            then_stop: false,
+        }))
+        .await
+        .unwrap();
+    let checkpoint_completed = reader.assert_control_checkpoint(1).await;
+    // messages are acknowledged once the checkpoint commits
+    reader.commit(1).await;
+
+    StateBackend::write_operator_checkpoint_metadata(OperatorCheckpointMetadata {
+        job_id: task_info.job_id.clone(),
+        operator_id: task_info.operator_id.clone(),
+        epoch: 1,
+        start_time: 0,
+        finish_time: 0,
+        min_watermark: Some(0),
+        max_watermark: Some(0),
+        has_state: true,
+        tables: source::tables(),
+        backend_data: checkpoint_completed.subtask_metadata.backend_data,
+        bytes: checkpoint_completed.subtask_metadata.bytes,
+        commit_data: None,
+    })
+    .await;
+
+    StateBackend::write_checkpoint_metadata(CheckpointMetadata {
+        job_id: task_info.job_id.clone(),
+        epoch: 1,
+        min_epoch: 1,
+        start_time: 0,
+        finish_time: 0,
+        operator_ids: vec![task_info.operator_id.clone()],
+        backend: StateBackend::name().to_string(),
+    })
+    .await;
+
+    reader.stop().await;
+
+    // messages published while the source is down are kept in its session by the broker
+    publish(&client, 20).await;
+
+    let mut reader = get_source_with_reader(port, task_info.clone(), Some(1)).await;
+    reader.assert_next_message_record_value(20).await;
+    publish(&client, 21).await;
+    reader.assert_next_message_record_value(21).await;
+    reader.stop().await;
+
+    // messages that weren't covered by a committed checkpoint are delivered again on recovery
+    let mut reader = get_source_with_reader(port, task_info, Some(1)).await;
+    reader.assert_next_message_record_value(20).await;
+    reader.assert_next_message_record_value(21).await;
+}
@@ -0,0 +1,32 @@
+use anyhow::anyhow;
+use async_nats::{ConnectOptions, ServerAddr};
+use serde::{Deserialize, Serialize};
+use typify::import_types;
+
+pub mod sink;
+pub mod source;
+
+import_types!(schema = "../connector-schemas/nats/connection.json");
+import_types!(schema = "../connector-schemas/nats/table.json");
+
+pub async fn connect(config: &NatsConfig) -> anyhow::Result<async_nats::Client> {
+    let servers = config
+        .servers
+        .split(',')
+        .map(|s| {
+            s.trim()
+                .parse::<ServerAddr>()
+                .map_err(|e| anyhow!("invalid NATS server '{}': {:?}", s, e))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let options = match &config.authentication {
+        NatsConfigAuthentication::None {} => ConnectOptions::new(),
+        NatsConfigAuthentication::Credentials { username, password } => {
+            ConnectOptions::with_user_and_password(username.clone(), password.clone())
+        }
+        NatsConfigAuthentication::Token { token } => ConnectOptions::with_token(token.clone()),
+    };
+
+    Ok(options.connect(servers).await?)
+}
@@ -0,0 +1,256 @@
+use crate::engine::{Context, StreamNode};
+use arroyo_formats::{DataSerializer, SchemaData};
+use arroyo_macro::process_fn;
+use arroyo_rpc::grpc::TableDescriptor;
+use arroyo_rpc::OperatorConfig;
+use arroyo_state::tables::global_keyed_map::GlobalKeyedState;
+use arroyo_types::*;
+use async_nats::header::NATS_MESSAGE_ID;
+use async_nats::jetstream::context::PublishAckFuture;
+use async_nats::HeaderMap;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::time::SystemTime;
+use tracing::info;
+
+use super::{connect, NatsConfig, NatsTable, TableType};
+
+/// Publishes to a JetStream subject. Every message carries a deduplication ID derived from its
+/// timestamp and payload, numbered among the identical messages the subtask has published, so
+/// messages replayed after recovery from a checkpoint get the IDs they were first published with
+/// and are dropped by the stream (within its duplicate window).
+#[derive(StreamNode)]
+pub struct NatsSinkFunc<K: Key + Serialize, T: SchemaData + Serialize> {
+    config: NatsConfig,
+    subject: String,
+    serializer: DataSerializer<T>,
+    jetstream: Option<async_nats::jetstream::Context>,
+    publish_futures: Vec<PublishAckFuture>,
+    message_ids: MessageIds,
+    _t: PhantomData<K>,
+}
+
+impl<K: Key + Serialize, T: SchemaData + Serialize> NatsSinkFunc<K, T> {
+    pub fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for NatsSink");
+        let connection: NatsConfig = serde_json::from_value(config.connection)
+            .expect("Invalid connection config for NatsSink");
+        let table: NatsTable =
+            serde_json::from_value(config.table).expect("Invalid table config for NatsSink");
+        let TableType::Sink { subject } = table.type_ else {
+            panic!("found non-sink nats config in sink operator");
+        };
+
+        Self {
+            config: connection,
+            subject,
+            serializer: DataSerializer::new(
+                config.format.expect("Format must be defined for NatsSink"),
+            ),
+            jetstream: None,
+            publish_futures: vec![],
+            message_ids: MessageIds::default(),
+            _t: PhantomData,
+        }
+    }
+}
+
+/// Assigns deduplication IDs to messages. The same messages get the same IDs however they're
+/// ordered, as long as the counts are restored from the checkpoint the messages are replayed from.
+#[derive(Debug, Default)]
+struct MessageIds {
+    // how many messages have been published for each timestamp and payload hash
+    counts: HashMap<(SystemTime, u64), u64>,
+}
+
+impl MessageIds {
+    fn restore(counts: &[(SystemTime, u64, u64)]) -> Self {
+        Self {
+            counts: counts
+                .iter()
+                .map(|(timestamp, hash, count)| ((*timestamp, *hash), *count))
+                .collect(),
+        }
+    }
+
+    fn next(&mut self, task_info: &TaskInfo, timestamp: SystemTime, payload: &[u8]) -> String {
+        let mut hasher = DefaultHasher::new();
+        payload.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let count = self.counts.entry((timestamp, hash)).or_default();
+        *count += 1;
+        format!(
+            "{}-{}-{}-{}-{:x}-{}",
+            task_info.job_id,
+            task_info.operator_id,
+            task_info.task_index,
+            to_micros(timestamp),
+            hash,
+            count
+        )
+    }
+
+    // the counts to checkpoint. Messages from before the watermark are rarely followed by identical
+    // ones, so their counts are dropped; a late message identical to one of them gets the same ID
+    // and is dropped if the stream still remembers it
+    fn checkpoint(&mut self, watermark: Option<SystemTime>) -> Vec<(SystemTime, u64, u64)> {
+        if let Some(watermark) = watermark {
+            self.counts
+                .retain(|(timestamp, _), _| *timestamp >= watermark);
+        }
+        self.counts
+            .iter()
+            .map(|((timestamp, hash), count)| (*timestamp, *hash, *count))
+            .collect()
+    }
+}
+
+#[process_fn(in_k = K, in_t = T)]
+impl<K: Key + Serialize, T: SchemaData + Serialize> NatsSinkFunc<K, T> {
+    fn name(&self) -> String {
+        format!("nats-producer-{}", self.subject)
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        vec![arroyo_state::global_table("m", "nats sink message counts")]
+    }
+
+    async fn on_start(&mut self, ctx: &mut Context<(), ()>) {
+        let mut s: GlobalKeyedState<usize, Vec<(SystemTime, u64, u64)>, _> =
+            ctx.state.get_global_keyed_state('m').await;
+        if let Some(counts) = s.get(&ctx.task_info.task_index).await {
+            self.message_ids = MessageIds::restore(counts);
+        }
+
+        match connect(&self.config).await {
+            Ok(client) => {
+                info!("Connected to NATS at {}", self.config.servers);
+                self.jetstream = Some(async_nats::jetstream::new(client));
+            }
+            Err(e) => {
+                ctx.report_error("Failed to connect to NATS".to_string(), e.to_string())
+                    .await;
+                panic!("Failed to connect to NATS: {:?}", e);
+            }
+        }
+    }
+
+    async fn flush(&mut self, ctx: &mut Context<(), ()>) {
+        for future in self.publish_futures.drain(..) {
+            if let Err(e) = future.await {
+                ctx.report_error("Failed to publish to NATS".to_string(), format!("{:?}", e))
+                    .await;
+                panic!("Failed to publish to NATS: {:?}", e);
+            }
+        }
+    }
+
+    async fn handle_checkpoint(&mut self, _: &CheckpointBarrier, ctx: &mut Context<(), ()>) {
+        // ensure all messages were acknowledged by the stream before finishing the checkpoint
+        self.flush(ctx).await;
+
+        let counts = self.message_ids.checkpoint(ctx.last_present_watermark());
+        ctx.state
+            .get_global_keyed_state('m')
+            .await
+            .insert(ctx.task_info.task_index, counts)
+            .await;
+    }
+
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), ()>) {
+        let v = match self.serializer.to_vec(&record.value) {
+            Ok(Some(v)) => v,
+            Ok(None) => return,
+            Err(e) => {
+                ctx.report_user_error(e).await;
+                return;
+            }
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            NATS_MESSAGE_ID,
+            self.message_ids
+                .next(&ctx.task_info, record.timestamp, &v)
+                .as_str(),
+        );
+
+        let result = self
+            .jetstream
+            .as_ref()
+            .unwrap()
+            .publish_with_headers(self.subject.clone(), headers, v.into())
+            .await;
+
+        match result {
+            Ok(future) => self.publish_futures.push(future),
+            Err(e) => {
+                ctx.report_error("Failed to publish to NATS".to_string(), format!("{:?}", e))
+                    .await;
+                panic!("Failed to publish to NATS: {:?}", e);
+            }
+        }
+    }
+
+    async fn on_close(&mut self, ctx: &mut Context<(), ()>) {
+        self.flush(ctx).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::MessageIds;
+
+    #[test]
+    fn test_replayed_message_ids() {
+        let task_info = arroyo_types::get_test_task_info();
+        let time = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let messages = [
+            (time(1), "a"),
+            (time(1), "b"),
+            (time(2), "a"),
+            (time(2), "a"),
+            (time(2), "c"),
+            (time(3), "a"),
+        ];
+
+        let mut ids = MessageIds::default();
+        let mut published = vec![];
+        let mut checkpoint = vec![];
+        for (i, (timestamp, payload)) in messages.iter().enumerate() {
+            if i == 3 {
+                checkpoint = ids.checkpoint(Some(time(2)));
+            }
+            published.push(ids.next(&task_info, *timestamp, payload.as_bytes()));
+        }
+        assert_eq!(
+            published.len(),
+            published
+                .iter()
+                .collect::<std::collections::HashSet<_>>()
+                .len(),
+            "identical messages must get distinct ids"
+        );
+
+        // after recovery, the messages since the checkpoint are replayed, possibly in another order
+        let mut ids = MessageIds::restore(&checkpoint);
+        let mut replayed: Vec<_> = [5, 4, 3]
+            .iter()
+            .map(|i| {
+                let (timestamp, payload) = messages[*i];
+                ids.next(&task_info, timestamp, payload.as_bytes())
+            })
+            .collect();
+        replayed.sort();
+        let mut expected = published[3..].to_vec();
+        expected.sort();
+        assert_eq!(expected, replayed);
+    }
+}
@@ -0,0 +1,238 @@
+use crate::engine::{Context, StreamNode};
+use crate::SourceFinishType;
+use arroyo_formats::{DataDeserializer, SchemaData};
+use arroyo_macro::source_fn;
+use arroyo_rpc::grpc::{StopMode, TableDescriptor};
+use arroyo_rpc::{ControlMessage, OperatorConfig};
+use arroyo_state::tables::global_keyed_map::GlobalKeyedState;
+use arroyo_types::*;
+use async_nats::jetstream::consumer::{pull, AckPolicy, DeliverPolicy};
+use bincode::{Decode, Encode};
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+use std::time::SystemTime;
+use tokio::select;
+use tracing::{debug, info, warn};
+
+use super::{connect, NatsConfig, NatsTable, SourceOffset, TableType};
+
+#[derive(Copy, Clone, Debug, Encode, Decode, PartialEq, PartialOrd)]
+pub struct NatsState {
+    stream_sequence: u64,
+}
+
+pub fn tables() -> Vec<TableDescriptor> {
+    vec![arroyo_state::global_table("n", "nats source state")]
+}
+
+/// Reads from a JetStream stream through an ephemeral consumer that starts after the last
+/// sequence number stored in state, so that messages are replayed exactly from the last
+/// checkpoint on recovery. As a consumer's messages can't be partitioned by sequence, only the
+/// first subtask reads from it.
+#[derive(StreamNode)]
+pub struct NatsSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: SchemaData,
+{
+    config: NatsConfig,
+    stream: String,
+    subject: Option<String>,
+    offset_mode: SourceOffset,
+    deserializer: DataDeserializer<T>,
+    _t: PhantomData<K>,
+}
+
+#[source_fn(out_k = (), out_t = T)]
+impl<K, T> NatsSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: SchemaData,
+{
+    pub fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for NatsSource");
+        let connection: NatsConfig = serde_json::from_value(config.connection)
+            .expect("Invalid connection config for NatsSource");
+        let table: NatsTable =
+            serde_json::from_value(config.table).expect("Invalid table config for NatsSource");
+        let TableType::Source {
+            stream,
+            subject,
+            offset,
+        } = table.type_
+        else {
+            panic!("found non-source nats config in source operator");
+        };
+
+        Self {
+            config: connection,
+            stream,
+            subject,
+            offset_mode: offset,
+            deserializer: DataDeserializer::new(
+                config.format.expect("Format must be set for NATS source"),
+                config.framing,
+            ),
+            _t: PhantomData,
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("nats-{}", self.stream)
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        tables()
+    }
+
+    async fn run(&mut self, ctx: &mut Context<(), T>) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(r) => r,
+            Err(e) => {
+                ctx.report_error(e.name.clone(), e.details.clone()).await;
+
+                panic!("{}: {}", e.name, e.details);
+            }
+        }
+    }
+
+    async fn get_consumer(&mut self, ctx: &mut Context<(), T>) -> anyhow::Result<pull::Stream> {
+        info!("Creating NATS consumer for {}", self.stream);
+        let client = connect(&self.config).await?;
+        let stream = async_nats::jetstream::new(client)
+            .get_stream(&self.stream)
+            .await?;
+
+        let mut s: GlobalKeyedState<String, NatsState, _> =
+            ctx.state.get_global_keyed_state('n').await;
+
+        let deliver_policy = match s.get(&self.stream).await {
+            Some(state) => DeliverPolicy::ByStartSequence {
+                start_sequence: state.stream_sequence + 1,
+            },
+            None => match self.offset_mode {
+                SourceOffset::Earliest => DeliverPolicy::All,
+                SourceOffset::Latest => DeliverPolicy::New,
+            },
+        };
+
+        info!(
+            "Consuming from stream {} with {:?}",
+            self.stream, deliver_policy
+        );
+
+        let consumer = stream
+            .create_consumer(pull::Config {
+                deliver_policy,
+                // progress is tracked through the sequence numbers in state
+                ack_policy: AckPolicy::None,
+                filter_subject: self.subject.clone().unwrap_or_default(),
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(consumer.messages().await?)
+    }
+
+    async fn our_handle_control_message(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        last_sequence: Option<u64>,
+        msg: Option<ControlMessage>,
+    ) -> Option<SourceFinishType> {
+        match msg? {
+            ControlMessage::Checkpoint(c) => {
+                debug!("starting checkpointing {}", ctx.task_info.task_index);
+                if let Some(stream_sequence) = last_sequence {
+                    let mut s = ctx.state.get_global_keyed_state('n').await;
+                    s.insert(self.stream.clone(), NatsState { stream_sequence })
+                        .await;
+                }
+
+                if self.checkpoint(c, ctx).await {
+                    return Some(SourceFinishType::Immediate);
+                }
+            }
+            ControlMessage::Stop { mode } => {
+                info!("Stopping NATS source: {:?}", mode);
+
+                match mode {
+                    StopMode::Graceful => {
+                        return Some(SourceFinishType::Graceful);
+                    }
+                    StopMode::Immediate => {
+                        return Some(SourceFinishType::Immediate);
+                    }
+                }
+            }
+            ControlMessage::Commit { .. } => {
+                unreachable!("sources shouldn't receive commit messages");
+            }
+            ControlMessage::LoadCompacted { compacted } => {
+                ctx.load_compacted(compacted).await;
+            }
+            ControlMessage::NoOp => {}
+        }
+        None
+    }
+
+    async fn run_int(&mut self, ctx: &mut Context<(), T>) -> Result<SourceFinishType, UserError> {
+        if ctx.task_info.task_index != 0 {
+            ctx.broadcast(Message::Watermark(Watermark::Idle)).await;
+
+            loop {
+                let msg = ctx.control_rx.recv().await;
+                if let Some(r) = self.our_handle_control_message(ctx, None, msg).await {
+                    return Ok(r);
+                }
+            }
+        }
+
+        let mut messages = self
+            .get_consumer(ctx)
+            .await
+            .map_err(|e| UserError::new("Could not create NATS consumer", format!("{:?}", e)))?;
+
+        let mut last_sequence = None;
+        loop {
+            select! {
+                message = messages.next() => {
+                    match message {
+                        Some(Ok(msg)) => {
+                            let info = msg.info().map_err(|e| {
+                                UserError::new("Failed to read NATS message metadata", format!("{:?}", e))
+                            })?;
+                            let stream_sequence = info.stream_sequence;
+                            let timestamp = u128::try_from(info.published.unix_timestamp_nanos())
+                                .map(from_nanos)
+                                .unwrap_or_else(|_| SystemTime::now());
+
+                            let iter = self.deserializer.deserialize_slice(&msg.payload).await;
+                            for value in iter {
+                                ctx.collector.collect(Record {
+                                    timestamp,
+                                    key: None,
+                                    value: value?,
+                                }).await;
+                            }
+                            last_sequence = Some(stream_sequence);
+                        }
+                        Some(Err(e)) => {
+                            warn!("error while reading from NATS: {:?}", e);
+                        }
+                        None => {
+                            return Err(UserError::new("NATS consumer closed", format!("the consumer for stream {} stopped delivering messages", self.stream)));
+                        }
+                    }
+                }
+                control_message = ctx.control_rx.recv() => {
+                    if let Some(r) = self.our_handle_control_message(ctx, last_sequence, control_message).await {
+                        return Ok(r);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -124,7 +124,7 @@ impl<K: Data, T: Data> NexmarkSourceFunc<K, T> {
                 .state
                 .get_global_keyed_state::<usize, NexmarkSourceState>('s')
                 .await;
-            let saved_states = ss.get_all().len();
+            let saved_states = ss.get_all().await.len();
             if saved_states != ctx.task_info.parallelism {
                 let config = GeneratorConfig::new(
                     NexmarkConfig::new(
@@ -143,7 +143,7 @@ impl<K: Data, T: Data> NexmarkSourceFunc<K, T> {
                     event_count: 0,
                 }
             } else {
-                ss.get(&ctx.task_info.task_index).unwrap().clone()
+                ss.get(&ctx.task_info.task_index).await.unwrap().clone()
             }
         });
     }
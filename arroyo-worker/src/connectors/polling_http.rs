@@ -120,10 +120,10 @@ where
     }
 
     async fn on_start(&mut self, ctx: &mut Context<(), T>) {
-        let s: GlobalKeyedState<(), PollingHttpSourceState, _> =
+        let mut s: GlobalKeyedState<(), PollingHttpSourceState, _> =
             ctx.state.get_global_keyed_state('s').await;
 
-        if let Some(state) = s.get(&()) {
+        if let Some(state) = s.get(&()).await {
             self.state = state.clone();
         }
     }
@@ -0,0 +1,52 @@
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::{Client, NoTls};
+use tracing::warn;
+use typify::import_types;
+
+pub mod pgoutput;
+pub mod source;
+
+import_types!(schema = "../connector-schemas/postgres_cdc/connection.json");
+import_types!(schema = "../connector-schemas/postgres_cdc/table.json");
+
+pub async fn connect(config: &PostgresCdcConfig) -> anyhow::Result<Client> {
+    let port = u16::try_from(config.port.unwrap_or(5432))
+        .map_err(|_| anyhow!("invalid port {}", config.port.unwrap_or_default()))?;
+
+    let mut pg_config = tokio_postgres::Config::new();
+    pg_config
+        .host(&config.host)
+        .port(port)
+        .dbname(&config.database)
+        .user(&config.user)
+        .application_name("arroyo");
+
+    if let Some(password) = &config.password {
+        pg_config.password(password);
+    }
+
+    let (client, connection) = pg_config.connect(NoTls).await?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            warn!("postgres connection error: {:?}", e);
+        }
+    });
+
+    Ok(client)
+}
+
+/// Splits a possibly schema-qualified table name into its schema and name.
+pub fn split_table_name(table: &str) -> anyhow::Result<(String, String)> {
+    match table.split('.').collect::<Vec<_>>().as_slice() {
+        [name] if !name.is_empty() => Ok(("public".to_string(), name.to_string())),
+        [schema, name] if !schema.is_empty() && !name.is_empty() => {
+            Ok((schema.to_string(), name.to_string()))
+        }
+        _ => bail!("invalid table '{}'", table),
+    }
+}
+
+pub fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
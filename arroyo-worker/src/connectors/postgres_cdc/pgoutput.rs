@@ -0,0 +1,512 @@
+//! Decoding of the messages produced by Postgres' built-in `pgoutput` logical decoding plugin
+//! (protocol version 1), as described in
+//! https://www.postgresql.org/docs/current/protocol-logicalrep-message-formats.html
+
+use std::time::{Duration, SystemTime};
+
+use bincode::{Decode, Encode};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde_json::{Map, Value};
+use tokio_postgres::types::Type;
+
+// microseconds between the unix epoch and the postgres epoch (2000-01-01)
+const POSTGRES_EPOCH_MICROS: u64 = 946_684_800_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub type_oid: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relation {
+    pub id: u32,
+    pub namespace: String,
+    pub name: String,
+    pub columns: Vec<Column>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TupleValue {
+    Null,
+    // a TOASTed value that was not changed, and so is not included in the message
+    Unchanged,
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogicalMessage {
+    Begin {
+        final_lsn: u64,
+        timestamp: SystemTime,
+        xid: u32,
+    },
+    Commit {
+        end_lsn: u64,
+    },
+    Relation(Relation),
+    Insert {
+        relation: u32,
+        new: Vec<TupleValue>,
+    },
+    Update {
+        relation: u32,
+        old: Option<Vec<TupleValue>>,
+        new: Vec<TupleValue>,
+    },
+    Delete {
+        relation: u32,
+        old: Vec<TupleValue>,
+    },
+    Truncate {
+        relations: Vec<u32>,
+    },
+    // origin, type and generic messages, which carry nothing we need
+    Other,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < n {
+            return Err(format!(
+                "unexpected end of message; needed {} bytes but only {} remain",
+                n,
+                self.data.len()
+            ));
+        }
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let end = self
+            .data
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| "unterminated string in message".to_string())?;
+        let s = String::from_utf8_lossy(&self.data[..end]).to_string();
+        self.data = &self.data[end + 1..];
+        Ok(s)
+    }
+
+    fn tuple(&mut self) -> Result<Vec<TupleValue>, String> {
+        (0..self.u16()?)
+            .map(|_| match self.u8()? {
+                b'n' => Ok(TupleValue::Null),
+                b'u' => Ok(TupleValue::Unchanged),
+                b't' => {
+                    let len = self.u32()? as usize;
+                    Ok(TupleValue::Text(
+                        String::from_utf8_lossy(self.take(len)?).to_string(),
+                    ))
+                }
+                other => Err(format!("unsupported tuple value kind '{}'", other as char)),
+            })
+            .collect()
+    }
+
+    fn expect(&mut self, tag: u8) -> Result<(), String> {
+        match self.u8()? {
+            t if t == tag => Ok(()),
+            t => Err(format!(
+                "expected '{}' but found '{}' in message",
+                tag as char, t as char
+            )),
+        }
+    }
+}
+
+pub fn parse(data: &[u8]) -> Result<LogicalMessage, String> {
+    let mut r = Reader { data };
+
+    Ok(match r.u8()? {
+        b'B' => LogicalMessage::Begin {
+            final_lsn: r.u64()?,
+            timestamp: SystemTime::UNIX_EPOCH
+                + Duration::from_micros(
+                    POSTGRES_EPOCH_MICROS.saturating_add_signed(r.u64()? as i64),
+                ),
+            xid: r.u32()?,
+        },
+        b'C' => {
+            let _flags = r.u8()?;
+            let _commit_lsn = r.u64()?;
+            LogicalMessage::Commit { end_lsn: r.u64()? }
+        }
+        b'R' => {
+            let id = r.u32()?;
+            let namespace = r.string()?;
+            let name = r.string()?;
+            let _replica_identity = r.u8()?;
+            let columns = (0..r.u16()?)
+                .map(|_| {
+                    let _flags = r.u8()?;
+                    let name = r.string()?;
+                    let type_oid = r.u32()?;
+                    let _type_modifier = r.u32()?;
+                    Ok(Column { name, type_oid })
+                })
+                .collect::<Result<_, String>>()?;
+
+            LogicalMessage::Relation(Relation {
+                id,
+                // pgoutput sends an empty namespace for pg_catalog
+                namespace: if namespace.is_empty() {
+                    "pg_catalog".to_string()
+                } else {
+                    namespace
+                },
+                name,
+                columns,
+            })
+        }
+        b'I' => {
+            let relation = r.u32()?;
+            r.expect(b'N')?;
+            LogicalMessage::Insert {
+                relation,
+                new: r.tuple()?,
+            }
+        }
+        b'U' => {
+            let relation = r.u32()?;
+            let old = match r.u8()? {
+                b'K' | b'O' => {
+                    let old = r.tuple()?;
+                    r.expect(b'N')?;
+                    Some(old)
+                }
+                b'N' => None,
+                t => return Err(format!("unexpected tuple type '{}' in update", t as char)),
+            };
+            LogicalMessage::Update {
+                relation,
+                old,
+                new: r.tuple()?,
+            }
+        }
+        b'D' => {
+            let relation = r.u32()?;
+            match r.u8()? {
+                b'K' | b'O' => {}
+                t => return Err(format!("unexpected tuple type '{}' in delete", t as char)),
+            }
+            LogicalMessage::Delete {
+                relation,
+                old: r.tuple()?,
+            }
+        }
+        b'T' => {
+            let count = r.u32()?;
+            let _options = r.u8()?;
+            LogicalMessage::Truncate {
+                relations: (0..count).map(|_| r.u32()).collect::<Result<_, _>>()?,
+            }
+        }
+        b'O' | b'Y' | b'M' => LogicalMessage::Other,
+        t => return Err(format!("unknown pgoutput message type '{}'", t as char)),
+    })
+}
+
+/// Converts a value in Postgres' text representation into the JSON value our deserializers
+/// expect for the corresponding SQL type.
+pub fn value_json(type_oid: u32, text: &str) -> Value {
+    let Some(ty) = Type::from_oid(type_oid) else {
+        return Value::String(text.to_string());
+    };
+
+    let converted = match ty {
+        Type::BOOL => Some(Value::Bool(text == "t")),
+        Type::INT2 | Type::INT4 | Type::INT8 | Type::OID => {
+            text.parse::<i64>().ok().map(Value::from)
+        }
+        Type::FLOAT4 | Type::FLOAT8 | Type::NUMERIC => text
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number),
+        Type::JSON | Type::JSONB => serde_json::from_str(text).ok(),
+        Type::TIMESTAMP => NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+            .ok()
+            .map(|t| Value::String(Utc.from_utc_datetime(&t).to_rfc3339())),
+        Type::TIMESTAMPTZ => DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z")
+            .ok()
+            .map(|t| Value::String(t.with_timezone(&Utc).to_rfc3339())),
+        _ => None,
+    };
+
+    converted.unwrap_or_else(|| Value::String(text.to_string()))
+}
+
+/// Builds a JSON object for a row from its decoded tuple. Unchanged TOASTed values are taken from
+/// the old version of the row, if there is one.
+pub fn row_json(columns: &[Column], tuple: &[TupleValue], old: Option<&Value>) -> Value {
+    let mut row = Map::new();
+    for (column, value) in columns.iter().zip(tuple) {
+        let value = match value {
+            TupleValue::Null => Value::Null,
+            TupleValue::Unchanged => old
+                .and_then(|o| o.get(&column.name))
+                .cloned()
+                .unwrap_or(Value::Null),
+            TupleValue::Text(text) => value_json(column.type_oid, text),
+        };
+        row.insert(column.name.clone(), value);
+    }
+    Value::Object(row)
+}
+
+pub fn parse_lsn(lsn: &str) -> Option<u64> {
+    let (high, low) = lsn.split_once('/')?;
+    Some((u64::from_str_radix(high, 16).ok()? << 32) | u64::from_str_radix(low, 16).ok()?)
+}
+
+pub fn format_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xFFFF_FFFF)
+}
+
+/// A transaction snapshot as returned by `txid_current_snapshot()`, used to tell which
+/// replicated transactions were already included in the initial snapshot of the table.
+#[derive(Debug, Clone, Encode, Decode, PartialEq)]
+pub struct TxSnapshot {
+    xmin: u64,
+    xmax: u64,
+    in_progress: Vec<u64>,
+}
+
+impl TxSnapshot {
+    pub fn parse(snapshot: &str) -> Option<Self> {
+        let mut parts = snapshot.split(':');
+        let xmin = parts.next()?.parse().ok()?;
+        let xmax = parts.next()?.parse().ok()?;
+        let in_progress = match parts.next()? {
+            "" => vec![],
+            xip => xip
+                .split(',')
+                .map(|x| x.parse().ok())
+                .collect::<Option<_>>()?,
+        };
+
+        Some(Self {
+            xmin,
+            xmax,
+            in_progress,
+        })
+    }
+
+    /// Whether the effects of the (committed) transaction are visible in the snapshot. Logical
+    /// replication only reports the low 32 bits of transaction ids, so they're extended with the
+    /// epoch of the snapshot.
+    pub fn is_visible(&self, xid: u32) -> bool {
+        let mut full = (self.xmax & !0xFFFF_FFFF) | xid as u64;
+        if full > self.xmax && full >= 1 << 32 {
+            full -= 1 << 32;
+        }
+
+        full < self.xmin || (full < self.xmax && !self.in_progress.contains(&full))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tuple(values: &[Option<&str>]) -> Vec<u8> {
+        let mut data = (values.len() as u16).to_be_bytes().to_vec();
+        for v in values {
+            match v {
+                Some(v) => {
+                    data.push(b't');
+                    data.extend((v.len() as u32).to_be_bytes());
+                    data.extend(v.as_bytes());
+                }
+                None => data.push(b'n'),
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_relation() {
+        let mut data = vec![b'R'];
+        data.extend(16384u32.to_be_bytes());
+        data.extend(b"public\0orders\0");
+        data.push(b'f');
+        data.extend(2u16.to_be_bytes());
+        for (name, oid) in [("id", 23u32), ("created", 1184)] {
+            data.push(1);
+            data.extend(name.as_bytes());
+            data.push(0);
+            data.extend(oid.to_be_bytes());
+            data.extend((-1i32).to_be_bytes());
+        }
+
+        assert_eq!(
+            parse(&data).unwrap(),
+            LogicalMessage::Relation(Relation {
+                id: 16384,
+                namespace: "public".to_string(),
+                name: "orders".to_string(),
+                columns: vec![
+                    Column {
+                        name: "id".to_string(),
+                        type_oid: 23
+                    },
+                    Column {
+                        name: "created".to_string(),
+                        type_oid: 1184
+                    }
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_changes() {
+        let mut insert = vec![b'I'];
+        insert.extend(16384u32.to_be_bytes());
+        insert.push(b'N');
+        insert.extend(tuple(&[Some("1"), None]));
+        assert_eq!(
+            parse(&insert).unwrap(),
+            LogicalMessage::Insert {
+                relation: 16384,
+                new: vec![TupleValue::Text("1".to_string()), TupleValue::Null],
+            }
+        );
+
+        let mut update = vec![b'U'];
+        update.extend(16384u32.to_be_bytes());
+        update.push(b'O');
+        update.extend(tuple(&[Some("1")]));
+        update.push(b'N');
+        update.extend(tuple(&[Some("2")]));
+        assert_eq!(
+            parse(&update).unwrap(),
+            LogicalMessage::Update {
+                relation: 16384,
+                old: Some(vec![TupleValue::Text("1".to_string())]),
+                new: vec![TupleValue::Text("2".to_string())],
+            }
+        );
+
+        let mut delete = vec![b'D'];
+        delete.extend(16384u32.to_be_bytes());
+        delete.push(b'O');
+        delete.extend(tuple(&[Some("2")]));
+        assert_eq!(
+            parse(&delete).unwrap(),
+            LogicalMessage::Delete {
+                relation: 16384,
+                old: vec![TupleValue::Text("2".to_string())],
+            }
+        );
+
+        assert!(parse(&[b'I', 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_parse_begin() {
+        let mut begin = vec![b'B'];
+        begin.extend(0x16B374D848u64.to_be_bytes());
+        // one second after the postgres epoch
+        begin.extend(1_000_000u64.to_be_bytes());
+        begin.extend(42u32.to_be_bytes());
+
+        assert_eq!(
+            parse(&begin).unwrap(),
+            LogicalMessage::Begin {
+                final_lsn: 0x16B374D848,
+                timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs(946_684_801),
+                xid: 42,
+            }
+        );
+    }
+
+    #[test]
+    fn test_value_json() {
+        assert_eq!(value_json(16, "t"), Value::Bool(true));
+        assert_eq!(value_json(20, "-12"), Value::from(-12));
+        assert_eq!(value_json(1700, "1.5"), Value::from(1.5));
+        assert_eq!(value_json(25, "hello"), Value::from("hello"));
+        assert_eq!(value_json(3802, r#"{"a": 1}"#), serde_json::json!({"a": 1}));
+        assert_eq!(
+            value_json(1114, "2023-10-01 12:30:00.5"),
+            Value::from("2023-10-01T12:30:00.500+00:00")
+        );
+        assert_eq!(
+            value_json(1184, "2023-10-01 14:30:00+02"),
+            Value::from("2023-10-01T12:30:00+00:00")
+        );
+        // values we can't convert are passed through as strings
+        assert_eq!(value_json(701, "NaN"), Value::from("NaN"));
+    }
+
+    #[test]
+    fn test_row_json_unchanged() {
+        let columns = vec![
+            Column {
+                name: "id".to_string(),
+                type_oid: 23,
+            },
+            Column {
+                name: "body".to_string(),
+                type_oid: 25,
+            },
+        ];
+        let old = serde_json::json!({"id": 1, "body": "large"});
+
+        assert_eq!(
+            row_json(
+                &columns,
+                &[TupleValue::Text("2".to_string()), TupleValue::Unchanged],
+                Some(&old)
+            ),
+            serde_json::json!({"id": 2, "body": "large"})
+        );
+    }
+
+    #[test]
+    fn test_lsn() {
+        assert_eq!(parse_lsn("16/B374D848"), Some(0x16B374D848));
+        assert_eq!(format_lsn(0x16B374D848), "16/B374D848");
+        assert_eq!(parse_lsn("invalid"), None);
+    }
+
+    #[test]
+    fn test_snapshot_visibility() {
+        let snapshot = TxSnapshot::parse("100:105:102,103").unwrap();
+        assert!(snapshot.is_visible(99));
+        assert!(snapshot.is_visible(101));
+        assert!(!snapshot.is_visible(102));
+        assert!(snapshot.is_visible(104));
+        assert!(!snapshot.is_visible(105));
+
+        // a snapshot in the second xid epoch
+        let snapshot = TxSnapshot::parse("4294967396:4294967400:").unwrap();
+        assert!(snapshot.is_visible(99));
+        assert!(!snapshot.is_visible(104));
+        assert!(snapshot.is_visible(u32::MAX));
+    }
+}
@@ -0,0 +1,609 @@
+use crate::engine::{Context, StreamNode};
+use crate::SourceFinishType;
+use anyhow::{anyhow, bail};
+use arroyo_formats::SchemaData;
+use arroyo_macro::source_fn;
+use arroyo_rpc::grpc::{StopMode, TableDescriptor, TableWriteBehavior, TaskCheckpointEventType};
+use arroyo_rpc::{CheckpointEvent, ControlMessage, ControlResp, OperatorConfig};
+use arroyo_state::tables::global_keyed_map::GlobalKeyedState;
+use arroyo_types::*;
+use bincode::{Decode, Encode};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime};
+use tokio::select;
+use tokio::time::MissedTickBehavior;
+use tokio_postgres::{Client, SimpleQueryMessage};
+use tracing::{debug, info, warn};
+
+use super::pgoutput::{self, format_lsn, Column, LogicalMessage, TupleValue, TxSnapshot};
+use super::{connect, quote_ident, split_table_name, PostgresCdcConfig, PostgresCdcTable};
+
+// the number of new changes to read from the slot in each poll
+const BATCH_SIZE: i32 = 1024;
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const SNAPSHOT_FETCH_SIZE: usize = 1024;
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+pub struct PostgresCdcState {
+    // the commit LSN of the last transaction that was emitted
+    lsn: u64,
+    snapshot: Option<TxSnapshot>,
+}
+
+pub fn tables() -> Vec<TableDescriptor> {
+    vec![
+        arroyo_state::global_table("p", "postgres cdc source state"),
+        // holds no data, but has the controller tell us when each checkpoint has committed
+        TableDescriptor {
+            write_behavior: TableWriteBehavior::CommitWrites as i32,
+            ..arroyo_state::global_table("c", "postgres cdc slot commits")
+        },
+    ]
+}
+
+/// Captures the changes to a table from a logical replication slot using the `pgoutput` plugin,
+/// and emits them as Debezium changes. Changes are read through the SQL replication functions
+/// from a temporary copy of the slot, which is consumed as it is read. The slot itself is only
+/// advanced to the LSN stored in a checkpoint once that checkpoint has committed, so that changes
+/// are replayed from the last checkpoint on recovery.
+///
+/// When the pipeline starts without a checkpoint the slot is created, and unless disabled the
+/// existing rows of the table are emitted from a snapshot taken after it. Transactions that were
+/// already visible in that snapshot are skipped when they're replicated. A slot that already
+/// exists may belong to another consumer, so it's never taken over or dropped; instead the slot
+/// is dropped if the run that created it ends before taking a checkpoint.
+///
+/// As a slot can only be read by a single consumer, only the first subtask reads from it.
+#[derive(StreamNode)]
+pub struct PostgresCdcSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: SchemaData,
+{
+    config: PostgresCdcConfig,
+    table: PostgresCdcTable,
+    schema_name: String,
+    table_name: String,
+    // the commit LSN of the last transaction that was read
+    lsn: u64,
+    // the LSNs stored in checkpoints that haven't committed yet, by epoch
+    awaiting_commit: BTreeMap<u32, u64>,
+    snapshot: Option<TxSnapshot>,
+    // the temporary copy of the slot that changes are consumed from
+    read_slot: String,
+    // whether this run created the slot and hasn't stored it in a checkpoint yet
+    created_slot: bool,
+    relation_id: Option<u32>,
+    columns: Vec<Column>,
+    _t: PhantomData<(K, T)>,
+}
+
+async fn collect_change<T: SchemaData>(
+    ctx: &mut Context<(), T>,
+    timestamp: SystemTime,
+    before: Value,
+    after: Value,
+    op: &str,
+) -> anyhow::Result<()> {
+    let value: T = serde_json::from_value(json!({
+        "before": before,
+        "after": after,
+        "op": op,
+    }))
+    .map_err(|e| anyhow!("failed to convert change to the table schema: {}", e))?;
+
+    ctx.collector
+        .collect(Record {
+            timestamp,
+            key: None,
+            value,
+        })
+        .await;
+
+    Ok(())
+}
+
+#[source_fn(out_k = (), out_t = T)]
+impl<K, T> PostgresCdcSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: SchemaData,
+{
+    pub fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for PostgresCdcSource");
+        let connection: PostgresCdcConfig = serde_json::from_value(config.connection)
+            .expect("Invalid connection config for PostgresCdcSource");
+        let table: PostgresCdcTable = serde_json::from_value(config.table)
+            .expect("Invalid table config for PostgresCdcSource");
+        let (schema_name, table_name) =
+            split_table_name(&table.table).expect("Invalid table for PostgresCdcSource");
+
+        Self {
+            config: connection,
+            table,
+            schema_name,
+            table_name,
+            lsn: 0,
+            awaiting_commit: BTreeMap::new(),
+            snapshot: None,
+            read_slot: String::new(),
+            created_slot: false,
+            relation_id: None,
+            columns: vec![],
+            _t: PhantomData,
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("postgres-cdc-{}", self.table.table)
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        tables()
+    }
+
+    async fn run(&mut self, ctx: &mut Context<(), T>) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(r) => r,
+            Err(e) => {
+                ctx.report_error(e.name.clone(), e.details.clone()).await;
+
+                panic!("{}: {}", e.name, e.details);
+            }
+        }
+    }
+
+    fn qualified_table(&self) -> String {
+        format!(
+            "{}.{}",
+            quote_ident(&self.schema_name),
+            quote_ident(&self.table_name)
+        )
+    }
+
+    async fn start(&mut self, client: &Client, ctx: &mut Context<(), T>) -> anyhow::Result<()> {
+        let restored = {
+            let mut s: GlobalKeyedState<String, PostgresCdcState, _> =
+                ctx.state.get_global_keyed_state('p').await;
+            s.get(&self.table.slot).await.cloned()
+        };
+
+        let identity: String = client
+            .query_opt(
+                "SELECT c.relreplident::text FROM pg_class c \
+                JOIN pg_namespace n ON n.oid = c.relnamespace \
+                WHERE n.nspname = $1 AND c.relname = $2",
+                &[&self.schema_name, &self.table_name],
+            )
+            .await?
+            .ok_or_else(|| anyhow!("table '{}' does not exist", self.table.table))?
+            .get(0);
+
+        if identity != "f" {
+            bail!(
+                "table '{}' must have a full replica identity so that updates and deletes include \
+                the previous values of rows; set it with `ALTER TABLE {} REPLICA IDENTITY FULL`",
+                self.table.table,
+                self.qualified_table()
+            );
+        }
+
+        let publication_exists = client
+            .query_opt(
+                "SELECT 1 FROM pg_publication WHERE pubname = $1",
+                &[&self.table.publication],
+            )
+            .await?
+            .is_some();
+
+        if !publication_exists {
+            info!(
+                "Creating publication {} for {}",
+                self.table.publication, self.table.table
+            );
+            client
+                .batch_execute(&format!(
+                    "CREATE PUBLICATION {} FOR TABLE {}",
+                    quote_ident(&self.table.publication),
+                    self.qualified_table()
+                ))
+                .await?;
+        }
+
+        let slot_exists = client
+            .query_opt(
+                "SELECT 1 FROM pg_replication_slots WHERE slot_name = $1",
+                &[&self.table.slot],
+            )
+            .await?
+            .is_some();
+
+        if slot_exists && restored.is_none() {
+            bail!(
+                "replication slot '{}' already exists, but the pipeline has no checkpoint for it, \
+                so it may be in use by another consumer; choose a different slot, or drop it with \
+                `SELECT pg_drop_replication_slot('{}')` if it's no longer used",
+                self.table.slot,
+                self.table.slot.replace('\'', "''")
+            );
+        }
+
+        if !slot_exists {
+            info!("Creating replication slot {}", self.table.slot);
+            if restored.is_some() {
+                warn!(
+                    "replication slot {} no longer exists; changes since the last checkpoint may be lost",
+                    self.table.slot
+                );
+            }
+
+            client
+                .execute(
+                    "SELECT pg_create_logical_replication_slot($1, 'pgoutput')",
+                    &[&self.table.slot],
+                )
+                .await?;
+            self.created_slot = restored.is_none();
+        }
+
+        match restored {
+            Some(state) => {
+                info!(
+                    "Restoring replication from slot {} at {}",
+                    self.table.slot,
+                    format_lsn(state.lsn)
+                );
+                self.lsn = state.lsn;
+                self.snapshot = state.snapshot;
+                // the restored checkpoint has completed, so the slot can be advanced to it
+                self.advance_slot(client, state.lsn).await?;
+            }
+            None if self.table.snapshot.unwrap_or(true) => {
+                self.take_snapshot(client, ctx).await?;
+            }
+            None => {}
+        }
+
+        // the copy starts where the slot was confirmed up to, and is dropped when the session ends
+        self.read_slot = format!("arroyo_cdc_{:016x}", rand::random::<u64>());
+        client
+            .execute(
+                "SELECT pg_copy_logical_replication_slot($1, $2, true)",
+                &[&self.table.slot, &self.read_slot],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn take_snapshot(
+        &mut self,
+        client: &Client,
+        ctx: &mut Context<(), T>,
+    ) -> anyhow::Result<()> {
+        info!("Taking initial snapshot of {}", self.table.table);
+
+        client
+            .batch_execute("BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .await?;
+
+        // the first query of the transaction fixes its snapshot
+        let snapshot: String = client
+            .query_one("SELECT txid_current_snapshot()::text", &[])
+            .await?
+            .get(0);
+        let snapshot = TxSnapshot::parse(&snapshot)
+            .ok_or_else(|| anyhow!("invalid transaction snapshot '{}'", snapshot))?;
+
+        let query = format!("SELECT * FROM {}", self.qualified_table());
+        let columns: Vec<Column> = client
+            .prepare(&query)
+            .await?
+            .columns()
+            .iter()
+            .map(|c| Column {
+                name: c.name().to_string(),
+                type_oid: c.type_().oid(),
+            })
+            .collect();
+
+        client
+            .batch_execute(&format!("DECLARE arroyo_snapshot CURSOR FOR {}", query))
+            .await?;
+
+        let mut count = 0;
+        loop {
+            let messages = client
+                .simple_query(&format!(
+                    "FETCH {} FROM arroyo_snapshot",
+                    SNAPSHOT_FETCH_SIZE
+                ))
+                .await?;
+
+            let mut fetched = 0;
+            for message in messages {
+                let SimpleQueryMessage::Row(row) = message else {
+                    continue;
+                };
+
+                let tuple: Vec<_> = (0..row.len())
+                    .map(|i| match row.get(i) {
+                        Some(v) => TupleValue::Text(v.to_string()),
+                        None => TupleValue::Null,
+                    })
+                    .collect();
+
+                let after = pgoutput::row_json(&columns, &tuple, None);
+                collect_change(ctx, SystemTime::now(), Value::Null, after, "c").await?;
+                fetched += 1;
+            }
+
+            if fetched == 0 {
+                break;
+            }
+            count += fetched;
+        }
+
+        client.batch_execute("COMMIT").await?;
+
+        info!(
+            "Finished snapshot of {} with {} rows",
+            self.table.table, count
+        );
+        self.snapshot = Some(snapshot);
+        Ok(())
+    }
+
+    async fn advance_slot(&mut self, client: &Client, lsn: u64) -> anyhow::Result<()> {
+        if lsn == 0 {
+            return Ok(());
+        }
+
+        debug!("Advancing slot {} to {}", self.table.slot, format_lsn(lsn));
+        client
+            .execute(
+                "SELECT pg_replication_slot_advance(slot_name, $2::text::pg_lsn) \
+                FROM pg_replication_slots \
+                WHERE slot_name = $1 AND confirmed_flush_lsn < $2::text::pg_lsn",
+                &[&self.table.slot, &format_lsn(lsn)],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn poll(&mut self, client: &Client, ctx: &mut Context<(), T>) -> anyhow::Result<()> {
+        let rows = client
+            .query(
+                "SELECT data FROM pg_logical_slot_get_binary_changes($1, NULL, $2, \
+                'proto_version', '1', 'publication_names', $3)",
+                &[&self.read_slot, &BATCH_SIZE, &self.table.publication],
+            )
+            .await?;
+
+        let mut transaction_lsn = None;
+        let mut skip = true;
+        let mut timestamp = SystemTime::now();
+
+        for row in rows {
+            let data: &[u8] = row.get(0);
+            let message = pgoutput::parse(data)
+                .map_err(|e| anyhow!("failed to decode replication message: {}", e))?;
+
+            match message {
+                LogicalMessage::Begin {
+                    final_lsn,
+                    timestamp: commit_time,
+                    xid,
+                } => {
+                    let processed = final_lsn <= self.lsn;
+                    let in_snapshot = self
+                        .snapshot
+                        .as_ref()
+                        .map(|s| s.is_visible(xid))
+                        .unwrap_or(false);
+
+                    skip = processed || in_snapshot;
+                    transaction_lsn = (!processed).then_some(final_lsn);
+                    timestamp = commit_time;
+                }
+                LogicalMessage::Commit { .. } => {
+                    if let Some(lsn) = transaction_lsn.take() {
+                        self.lsn = lsn;
+                    }
+                    skip = true;
+                }
+                LogicalMessage::Relation(relation) => {
+                    if relation.namespace == self.schema_name && relation.name == self.table_name {
+                        self.relation_id = Some(relation.id);
+                        self.columns = relation.columns;
+                    }
+                }
+                LogicalMessage::Insert { relation, new } => {
+                    if skip || self.relation_id != Some(relation) {
+                        continue;
+                    }
+
+                    let after = pgoutput::row_json(&self.columns, &new, None);
+                    collect_change(ctx, timestamp, Value::Null, after, "c").await?;
+                }
+                LogicalMessage::Update { relation, old, new } => {
+                    if skip || self.relation_id != Some(relation) {
+                        continue;
+                    }
+
+                    let Some(old) = old else {
+                        bail!(
+                            "received an update without the previous values of the row; \
+                            table '{}' must have a full replica identity",
+                            self.table.table
+                        );
+                    };
+
+                    let before = pgoutput::row_json(&self.columns, &old, None);
+                    let after = pgoutput::row_json(&self.columns, &new, Some(&before));
+                    collect_change(ctx, timestamp, before, after, "u").await?;
+                }
+                LogicalMessage::Delete { relation, old } => {
+                    if skip || self.relation_id != Some(relation) {
+                        continue;
+                    }
+
+                    let before = pgoutput::row_json(&self.columns, &old, None);
+                    collect_change(ctx, timestamp, before, Value::Null, "d").await?;
+                }
+                LogicalMessage::Truncate { relations } => {
+                    if !skip && self.relation_id.is_some_and(|id| relations.contains(&id)) {
+                        warn!(
+                            "table {} was truncated, which can't be represented as changes",
+                            self.table.table
+                        );
+                    }
+                }
+                LogicalMessage::Other => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn our_handle_control_message(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        client: Option<&Client>,
+        msg: Option<ControlMessage>,
+    ) -> Option<SourceFinishType> {
+        match msg? {
+            ControlMessage::Checkpoint(c) => {
+                debug!("starting checkpointing {}", ctx.task_info.task_index);
+                if client.is_some() {
+                    let mut s = ctx.state.get_global_keyed_state('p').await;
+                    s.insert(
+                        self.table.slot.clone(),
+                        PostgresCdcState {
+                            lsn: self.lsn,
+                            snapshot: self.snapshot.clone(),
+                        },
+                    )
+                    .await;
+                    self.awaiting_commit.insert(c.epoch, self.lsn);
+                    self.created_slot = false;
+                }
+
+                if self.checkpoint(c, ctx).await {
+                    return Some(SourceFinishType::Immediate);
+                }
+            }
+            ControlMessage::Stop { mode } => {
+                info!("Stopping Postgres CDC source: {:?}", mode);
+
+                match mode {
+                    StopMode::Graceful => {
+                        return Some(SourceFinishType::Graceful);
+                    }
+                    StopMode::Immediate => {
+                        return Some(SourceFinishType::Immediate);
+                    }
+                }
+            }
+            ControlMessage::Commit { epoch, .. } => {
+                // checkpoints commit in order, so this covers all earlier epochs as well
+                let uncommitted = self.awaiting_commit.split_off(&(epoch + 1));
+                let committed = std::mem::replace(&mut self.awaiting_commit, uncommitted);
+
+                if let (Some(client), Some(lsn)) = (client, committed.into_values().max()) {
+                    // if this fails the slot is advanced by a later commit, or on recovery
+                    if let Err(e) = self.advance_slot(client, lsn).await {
+                        warn!("failed to advance slot {}: {:?}", self.table.slot, e);
+                    }
+                }
+
+                ctx.control_tx
+                    .send(ControlResp::CheckpointEvent(CheckpointEvent {
+                        checkpoint_epoch: epoch,
+                        operator_id: ctx.task_info.operator_id.clone(),
+                        subtask_index: ctx.task_info.task_index as u32,
+                        time: SystemTime::now(),
+                        event_type: TaskCheckpointEventType::FinishedCommit,
+                    }))
+                    .await
+                    .expect("sent commit event");
+            }
+            ControlMessage::LoadCompacted { compacted } => {
+                ctx.load_compacted(compacted).await;
+            }
+            ControlMessage::NoOp => {}
+        }
+        None
+    }
+
+    async fn run_int(&mut self, ctx: &mut Context<(), T>) -> Result<SourceFinishType, UserError> {
+        if ctx.task_info.task_index != 0 {
+            ctx.broadcast(Message::Watermark(Watermark::Idle)).await;
+
+            loop {
+                let msg = ctx.control_rx.recv().await;
+                if let Some(r) = self.our_handle_control_message(ctx, None, msg).await {
+                    return Ok(r);
+                }
+            }
+        }
+
+        let client = connect(&self.config)
+            .await
+            .map_err(|e| UserError::new("Could not connect to Postgres", format!("{:?}", e)))?;
+
+        let result = self.replicate(&client, ctx).await;
+
+        // nothing refers to a slot that was never checkpointed, and the next run can't tell it
+        // apart from another consumer's
+        if self.created_slot {
+            info!(
+                "Dropping replication slot {} as it was never checkpointed",
+                self.table.slot
+            );
+            // the snapshot's transaction may have been left open
+            let _ = client.batch_execute("ROLLBACK").await;
+            if let Err(e) = client
+                .execute("SELECT pg_drop_replication_slot($1)", &[&self.table.slot])
+                .await
+            {
+                warn!("failed to drop slot {}: {:?}", self.table.slot, e);
+            }
+        }
+
+        result
+    }
+
+    async fn replicate(
+        &mut self,
+        client: &Client,
+        ctx: &mut Context<(), T>,
+    ) -> Result<SourceFinishType, UserError> {
+        self.start(client, ctx).await.map_err(|e| {
+            UserError::new("Failed to start Postgres replication", format!("{:?}", e))
+        })?;
+
+        let mut timer = tokio::time::interval(POLL_INTERVAL);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            select! {
+                _ = timer.tick() => {
+                    self.poll(client, ctx).await.map_err(|e| {
+                        UserError::new("Failed to read changes from Postgres", format!("{:?}", e))
+                    })?;
+                }
+                control_message = ctx.control_rx.recv() => {
+                    if let Some(r) = self.our_handle_control_message(ctx, Some(client), control_message).await {
+                        return Ok(r);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::connectors::redis::{Clients, GeneralConnection, RedisConfig, RedisTable, TableType};
+use crate::operators::lookup_join::LookupConnector;
+use anyhow::anyhow;
+use arroyo_rpc::OperatorConfig;
+use async_trait::async_trait;
+
+pub struct RedisLookup {
+    client: Clients,
+    connection: Option<GeneralConnection>,
+    key_prefix: String,
+}
+
+#[async_trait]
+impl LookupConnector for RedisLookup {
+    fn from_config(config: &str) -> Self {
+        let config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for RedisLookup");
+        let profile: RedisConfig = serde_json::from_value(config.connection)
+            .expect("Invalid connection profile for RedisLookup");
+        let table: RedisTable =
+            serde_json::from_value(config.table).expect("Invalid table config for Redis");
+
+        let TableType::Lookup { key_prefix } = table.connector_type else {
+            panic!("RedisLookup requires a lookup table");
+        };
+
+        Self {
+            client: Clients::new(&profile),
+            connection: None,
+            key_prefix: key_prefix.unwrap_or_default(),
+        }
+    }
+
+    async fn lookup(&mut self, keys: &[String]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        if self.connection.is_none() {
+            self.connection = Some(
+                self.client
+                    .get_connection()
+                    .await
+                    .map_err(|e| anyhow!("Failed to connect to Redis: {:?}", e))?,
+            );
+        }
+
+        // a pipeline of GETs rather than an MGET, as in a cluster the keys may be in different slots
+        let mut pipeline = redis::pipe();
+        for key in keys {
+            pipeline.get(format!("{}{}", self.key_prefix, key));
+        }
+
+        match pipeline
+            .query_async(self.connection.as_mut().unwrap())
+            .await
+        {
+            Ok(values) => Ok(values),
+            Err(e) => {
+                // reconnect on the next lookup
+                self.connection = None;
+                Err(anyhow!("Failed to read from Redis: {:?}", e))
+            }
+        }
+    }
+}
@@ -1,6 +1,93 @@
+use redis::aio::{ConnectionLike, ConnectionManager};
+use redis::cluster::{ClusterClient, ClusterClientBuilder};
+use redis::cluster_async::ClusterConnection;
+use redis::{Client, Cmd, IntoConnectionInfo, Pipeline, RedisFuture};
 use serde::{Deserialize, Serialize};
 use typify::import_types;
+pub mod lookup;
 pub mod sink;
+pub mod source;
 
 import_types!(schema = "../connector-schemas/redis/connection.json");
 import_types!(schema = "../connector-schemas/redis/table.json");
+
+pub(crate) fn standard_client(config: &RedisConfig, address: &str) -> Client {
+    let mut info = address.into_connection_info().expect("invalid address");
+    if let Some(username) = &config.username {
+        info.redis.username = Some(username.clone());
+    }
+    if let Some(password) = &config.password {
+        info.redis.password = Some(password.clone());
+    }
+
+    Client::open(info).expect("invalid address")
+}
+
+pub(crate) enum Clients {
+    Standard(Client),
+    Clustered(ClusterClient),
+}
+
+impl Clients {
+    pub(crate) fn new(config: &RedisConfig) -> Self {
+        match &config.connection {
+            RedisConfigConnection::Address(address) => {
+                Clients::Standard(standard_client(config, &address.0))
+            }
+            RedisConfigConnection::Addresses(addresses) => {
+                let mut builder =
+                    ClusterClientBuilder::new(addresses.iter().map(|e| e.0.clone()).collect());
+                if let Some(username) = &config.username {
+                    builder = builder.username(username.clone());
+                }
+                if let Some(password) = &config.password {
+                    builder = builder.password(password.clone());
+                }
+
+                Clients::Clustered(builder.build().expect("failed to construct cluster client"))
+            }
+        }
+    }
+
+    pub(crate) async fn get_connection(&self) -> Result<GeneralConnection, redis::RedisError> {
+        Ok(match self {
+            Clients::Standard(c) => {
+                GeneralConnection::Standard(ConnectionManager::new(c.clone()).await?)
+            }
+            Clients::Clustered(c) => GeneralConnection::Clustered(c.get_async_connection().await?),
+        })
+    }
+}
+
+pub enum GeneralConnection {
+    Standard(ConnectionManager),
+    Clustered(ClusterConnection),
+}
+
+impl ConnectionLike for GeneralConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, redis::Value> {
+        match self {
+            GeneralConnection::Standard(c) => c.req_packed_command(cmd),
+            GeneralConnection::Clustered(c) => c.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<redis::Value>> {
+        match self {
+            GeneralConnection::Standard(c) => c.req_packed_commands(cmd, offset, count),
+            GeneralConnection::Clustered(c) => c.req_packed_commands(cmd, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            GeneralConnection::Standard(c) => c.get_db(),
+            GeneralConnection::Clustered(c) => c.get_db(),
+        }
+    }
+}
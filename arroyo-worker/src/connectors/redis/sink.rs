@@ -1,14 +1,13 @@
-use crate::connectors::redis::{RedisConfig, RedisConfigConnection, RedisTable, TableType, Target};
+use crate::connectors::redis::{
+    Clients, GeneralConnection, RedisConfig, RedisTable, TableType, Target,
+};
 use crate::engine::{Context, ErrorReporter, StreamNode};
 use arroyo_formats::DataSerializer;
 use arroyo_formats::SchemaData;
 use arroyo_macro::process_fn;
 use arroyo_rpc::OperatorConfig;
 use arroyo_types::{CheckpointBarrier, Key, Record};
-use redis::aio::{ConnectionLike, ConnectionManager};
-use redis::cluster::ClusterClient;
-use redis::cluster_async::ClusterConnection;
-use redis::{Client, Cmd, Pipeline, RedisFuture};
+use redis::Pipeline;
 use serde::Serialize;
 use serde_json::Value;
 use std::collections::HashSet;
@@ -61,55 +60,6 @@ enum RedisCmd {
     Flush(u32),
 }
 
-enum Clients {
-    Standard(Client),
-    Clustered(ClusterClient),
-}
-
-impl Clients {
-    async fn get_connection(&self) -> Result<GeneralConnection, redis::RedisError> {
-        Ok(match self {
-            Clients::Standard(c) => {
-                GeneralConnection::Standard(ConnectionManager::new(c.clone()).await?)
-            }
-            Clients::Clustered(c) => GeneralConnection::Clustered(c.get_async_connection().await?),
-        })
-    }
-}
-
-pub enum GeneralConnection {
-    Standard(ConnectionManager),
-    Clustered(ClusterConnection),
-}
-
-impl ConnectionLike for GeneralConnection {
-    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, redis::Value> {
-        match self {
-            GeneralConnection::Standard(c) => c.req_packed_command(cmd),
-            GeneralConnection::Clustered(c) => c.req_packed_command(cmd),
-        }
-    }
-
-    fn req_packed_commands<'a>(
-        &'a mut self,
-        cmd: &'a Pipeline,
-        offset: usize,
-        count: usize,
-    ) -> RedisFuture<'a, Vec<redis::Value>> {
-        match self {
-            GeneralConnection::Standard(c) => c.req_packed_commands(cmd, offset, count),
-            GeneralConnection::Clustered(c) => c.req_packed_commands(cmd, offset, count),
-        }
-    }
-
-    fn get_db(&self) -> i64 {
-        match self {
-            GeneralConnection::Standard(c) => c.get_db(),
-            GeneralConnection::Clustered(c) => c.get_db(),
-        }
-    }
-}
-
 struct RedisWriter {
     rx: Receiver<RedisCmd>,
     tx: Sender<u32>,
@@ -257,15 +207,7 @@ where
         let table: RedisTable =
             serde_json::from_value(config.table).expect("Invalid table config for Redis");
 
-        let client = match profile.connection {
-            RedisConfigConnection::Address(address) => {
-                Clients::Standard(Client::open(address.0).expect("invalid address"))
-            }
-            RedisConfigConnection::Addresses(addresses) => Clients::Clustered(
-                ClusterClient::new(addresses.into_iter().map(|e| e.0).collect())
-                    .expect("failed to construct cluster client"),
-            ),
-        };
+        let client = Clients::new(&profile);
 
         let (tx, cmd_rx) = tokio::sync::mpsc::channel(128);
         let (cmd_tx, rx) = tokio::sync::mpsc::channel(128);
@@ -322,6 +264,9 @@ where
                                 }
                             }
                             TableType::Target(Target::HashTable { .. }) => RedisBehavior::Hash,
+                            TableType::Source(_) | TableType::Lookup { .. } => {
+                                unreachable!("only sink tables can be written to")
+                            }
                         },
                     }
                     .start();
@@ -354,9 +299,16 @@ where
         key
     }
 
-    async fn process_element(&mut self, record: &Record<K, T>, _ctx: &mut Context<(), ()>) {
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), ()>) {
         let value = serde_json::to_value(&record.value).unwrap();
-        let data = self.serializer.to_vec(&record.value).unwrap();
+        let data = match self.serializer.to_vec(&record.value) {
+            Ok(Some(data)) => data,
+            Ok(None) => return,
+            Err(e) => {
+                ctx.report_user_error(e).await;
+                return;
+            }
+        };
         match &self.table.connector_type {
             TableType::Target(target) => match &target {
                 Target::StringTable {
@@ -405,6 +357,9 @@ where
                         .expect("Redis writer panicked");
                 }
             },
+            TableType::Source(_) | TableType::Lookup { .. } => {
+                unreachable!("only sink tables can be written to")
+            }
         };
     }
 
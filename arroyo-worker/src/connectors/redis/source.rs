@@ -0,0 +1,541 @@
+use crate::engine::{Context, StreamNode};
+use crate::SourceFinishType;
+use arroyo_formats::{DataDeserializer, SchemaData};
+use arroyo_macro::source_fn;
+use arroyo_rpc::grpc::{StopMode, TableDescriptor};
+use arroyo_rpc::{ControlMessage, OperatorConfig};
+use arroyo_state::tables::global_keyed_map::GlobalKeyedState;
+use arroyo_types::*;
+use bincode::{Decode, Encode};
+use futures::StreamExt;
+use redis::streams::{StreamId, StreamPendingCountReply, StreamReadOptions, StreamReadReply};
+use redis::{AsyncCommands, RedisResult};
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+use std::time::SystemTime;
+use tokio::select;
+use tracing::{debug, info};
+
+use super::{
+    standard_client, Clients, GeneralConnection, RedisConfig, RedisConfigConnection, RedisTable,
+    Source, StreamOffset, TableType,
+};
+
+// the maximum number of entries read or acked by a single command
+const BATCH_SIZE: usize = 1000;
+// how long a read waits for new entries before control messages are checked
+const BLOCK_MS: usize = 500;
+const DEFAULT_CONSUMER: &str = "arroyo";
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq, PartialOrd)]
+pub struct RedisStreamState {
+    last_id: String,
+}
+
+pub fn tables() -> Vec<TableDescriptor> {
+    vec![arroyo_state::global_table("r", "redis stream source state")]
+}
+
+fn table_config<T>(config: &str) -> (RedisConfig, Source, DataDeserializer<T>)
+where
+    T: SchemaData,
+{
+    let config: OperatorConfig =
+        serde_json::from_str(config).expect("Invalid config for RedisSource");
+    let connection: RedisConfig = serde_json::from_value(config.connection)
+        .expect("Invalid connection profile for RedisSource");
+    let table: RedisTable =
+        serde_json::from_value(config.table).expect("Invalid table config for RedisSource");
+    let TableType::Source(source) = table.connector_type else {
+        panic!("found non-source redis config in source operator");
+    };
+
+    (
+        connection,
+        source,
+        DataDeserializer::new(
+            config.format.expect("Format must be set for Redis source"),
+            config.framing,
+        ),
+    )
+}
+
+/// Reads a Redis Stream through a consumer group. The id of the last entry read is stored in
+/// state on each checkpoint, and entries are only acked once the checkpoint that covers them has
+/// completed, so on recovery the entries delivered after the checkpoint are replayed from the
+/// consumer's pending list. As the pending list belongs to a single consumer, only the first
+/// subtask reads from the stream.
+#[derive(StreamNode)]
+pub struct RedisStreamSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: SchemaData,
+{
+    config: RedisConfig,
+    stream: String,
+    group: String,
+    consumer: String,
+    offset: StreamOffset,
+    field: Option<String>,
+    deserializer: DataDeserializer<T>,
+    last_id: Option<String>,
+    // entries read since the last checkpoint
+    unacked: Vec<String>,
+    // entries covered by the last checkpoint, which are acked once the next one starts
+    checkpointed: Vec<String>,
+    _t: PhantomData<K>,
+}
+
+#[source_fn(out_k = (), out_t = T)]
+impl<K, T> RedisStreamSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: SchemaData,
+{
+    pub fn from_config(config: &str) -> Self {
+        let (config, source, deserializer) = table_config(config);
+        let Source::Stream {
+            stream,
+            group,
+            consumer,
+            offset,
+            field,
+        } = source
+        else {
+            panic!("found non-stream redis config in stream source operator");
+        };
+
+        Self {
+            config,
+            stream,
+            group,
+            consumer: consumer.unwrap_or_else(|| DEFAULT_CONSUMER.to_string()),
+            offset,
+            field,
+            deserializer,
+            last_id: None,
+            unacked: vec![],
+            checkpointed: vec![],
+            _t: PhantomData,
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("redis-stream-{}", self.stream)
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        tables()
+    }
+
+    async fn run(&mut self, ctx: &mut Context<(), T>) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(r) => r,
+            Err(e) => {
+                ctx.report_error(e.name.clone(), e.details.clone()).await;
+
+                panic!("{}: {}", e.name, e.details);
+            }
+        }
+    }
+
+    async fn create_group(&mut self, connection: &mut GeneralConnection) -> RedisResult<()> {
+        let start = match self.offset {
+            StreamOffset::Earliest => "0",
+            StreamOffset::Latest => "$",
+        };
+
+        match connection
+            .xgroup_create_mkstream::<_, _, _, ()>(&self.stream, &self.group, start)
+            .await
+        {
+            Err(e) if e.code() == Some("BUSYGROUP") => Ok(()),
+            r => r,
+        }
+    }
+
+    async fn ack(&mut self, connection: &mut GeneralConnection, ids: &[String]) -> RedisResult<()> {
+        for chunk in ids.chunks(BATCH_SIZE) {
+            connection
+                .xack::<_, _, _, usize>(&self.stream, &self.group, chunk)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Acks the entries pending for our consumer up to `last_id`, which were processed before the
+    /// checkpoint we've restored from.
+    async fn ack_restored(
+        &mut self,
+        connection: &mut GeneralConnection,
+        last_id: &str,
+    ) -> RedisResult<()> {
+        loop {
+            let pending: StreamPendingCountReply = connection
+                .xpending_consumer_count(
+                    &self.stream,
+                    &self.group,
+                    "-",
+                    last_id,
+                    BATCH_SIZE,
+                    &self.consumer,
+                )
+                .await?;
+
+            if pending.ids.is_empty() {
+                return Ok(());
+            }
+
+            let ids: Vec<String> = pending.ids.into_iter().map(|p| p.id).collect();
+            self.ack(connection, &ids).await?;
+        }
+    }
+
+    fn payload(&self, entry: &StreamId) -> Result<Vec<u8>, UserError> {
+        match &self.field {
+            Some(field) => {
+                let value = entry.map.get(field).ok_or_else(|| {
+                    UserError::new(
+                        "Missing field in Redis Stream entry",
+                        format!("entry {} has no field '{}'", entry.id, field),
+                    )
+                })?;
+
+                redis::from_redis_value(value)
+                    .map_err(|e| UserError::new("Invalid Redis Stream entry", format!("{:?}", e)))
+            }
+            None => {
+                // fields whose values are valid JSON are read as JSON, and the rest as strings
+                let mut object = serde_json::Map::new();
+                for (k, v) in &entry.map {
+                    let s: String = redis::from_redis_value(v).map_err(|e| {
+                        UserError::new("Invalid Redis Stream entry", format!("{:?}", e))
+                    })?;
+                    let value = serde_json::from_str(&s).unwrap_or(serde_json::Value::String(s));
+                    object.insert(k.clone(), value);
+                }
+
+                Ok(serde_json::to_vec(&object).unwrap())
+            }
+        }
+    }
+
+    async fn our_handle_control_message(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        connection: Option<&mut GeneralConnection>,
+        msg: Option<ControlMessage>,
+    ) -> Result<Option<SourceFinishType>, UserError> {
+        let Some(msg) = msg else {
+            return Ok(None);
+        };
+
+        match msg {
+            ControlMessage::Checkpoint(c) => {
+                debug!("starting checkpointing {}", ctx.task_info.task_index);
+                if let Some(connection) = connection {
+                    // the previous checkpoint has completed, so its entries won't be replayed
+                    let checkpointed = std::mem::take(&mut self.checkpointed);
+                    self.ack(connection, &checkpointed).await.map_err(|e| {
+                        UserError::new("Failed to ack Redis Stream entries", format!("{:?}", e))
+                    })?;
+                }
+
+                if let Some(last_id) = &self.last_id {
+                    let mut s = ctx.state.get_global_keyed_state('r').await;
+                    s.insert(
+                        self.stream.clone(),
+                        RedisStreamState {
+                            last_id: last_id.clone(),
+                        },
+                    )
+                    .await;
+                }
+                self.checkpointed = std::mem::take(&mut self.unacked);
+
+                if self.checkpoint(c, ctx).await {
+                    return Ok(Some(SourceFinishType::Immediate));
+                }
+            }
+            ControlMessage::Stop { mode } => {
+                info!("Stopping Redis Stream source: {:?}", mode);
+
+                match mode {
+                    StopMode::Graceful => {
+                        return Ok(Some(SourceFinishType::Graceful));
+                    }
+                    StopMode::Immediate => {
+                        return Ok(Some(SourceFinishType::Immediate));
+                    }
+                }
+            }
+            ControlMessage::Commit { .. } => {
+                unreachable!("sources shouldn't receive commit messages");
+            }
+            ControlMessage::LoadCompacted { compacted } => {
+                ctx.load_compacted(compacted).await;
+            }
+            ControlMessage::NoOp => {}
+        }
+        Ok(None)
+    }
+
+    async fn run_int(&mut self, ctx: &mut Context<(), T>) -> Result<SourceFinishType, UserError> {
+        if ctx.task_info.task_index != 0 {
+            ctx.broadcast(Message::Watermark(Watermark::Idle)).await;
+
+            loop {
+                let msg = ctx.control_rx.recv().await;
+                if let Some(r) = self.our_handle_control_message(ctx, None, msg).await? {
+                    return Ok(r);
+                }
+            }
+        }
+
+        let mut connection = Clients::new(&self.config)
+            .get_connection()
+            .await
+            .map_err(|e| UserError::new("Failed to connect to Redis", format!("{:?}", e)))?;
+
+        self.create_group(&mut connection).await.map_err(|e| {
+            UserError::new("Failed to create Redis consumer group", format!("{:?}", e))
+        })?;
+
+        let restored = {
+            let mut s: GlobalKeyedState<String, RedisStreamState, _> =
+                ctx.state.get_global_keyed_state('r').await;
+            s.get(&self.stream).await.map(|s| s.last_id.clone())
+        };
+
+        if let Some(last_id) = &restored {
+            info!("Resuming stream {} after {}", self.stream, last_id);
+            self.ack_restored(&mut connection, last_id)
+                .await
+                .map_err(|e| {
+                    UserError::new("Failed to ack Redis Stream entries", format!("{:?}", e))
+                })?;
+        }
+
+        // entries that were delivered to our consumer but not acked are read before new ones
+        let mut history_id = Some(restored.clone().unwrap_or_else(|| "0".to_string()));
+        self.last_id = restored;
+
+        loop {
+            let mut options = StreamReadOptions::default()
+                .group(&self.group, &self.consumer)
+                .count(BATCH_SIZE);
+            if history_id.is_none() {
+                options = options.block(BLOCK_MS);
+            }
+            let id = history_id.as_deref().unwrap_or(">");
+
+            let reply: Option<StreamReadReply> = connection
+                .xread_options(&[&self.stream], &[id], &options)
+                .await
+                .map_err(|e| {
+                    UserError::new("Failed to read from Redis Stream", format!("{:?}", e))
+                })?;
+
+            let entries: Vec<StreamId> = reply
+                .map(|r| r.keys.into_iter().flat_map(|k| k.ids).collect())
+                .unwrap_or_default();
+
+            if history_id.is_some() {
+                history_id = entries.last().map(|e| e.id.clone());
+            }
+
+            for entry in entries {
+                // stream ids start with the time the entry was added, in millis
+                let timestamp = entry
+                    .id
+                    .split_once('-')
+                    .and_then(|(millis, _)| millis.parse().ok())
+                    .map(from_millis)
+                    .unwrap_or_else(SystemTime::now);
+
+                let payload = self.payload(&entry)?;
+                let iter = self.deserializer.deserialize_slice(&payload).await;
+                for value in iter {
+                    ctx.collector
+                        .collect(Record {
+                            timestamp,
+                            key: None,
+                            value: value?,
+                        })
+                        .await;
+                }
+
+                self.unacked.push(entry.id.clone());
+                self.last_id = Some(entry.id);
+            }
+
+            // reads aren't cancelled for control messages, as entries in a cancelled read would
+            // be delivered to our consumer without being processed
+            while let Ok(msg) = ctx.control_rx.try_recv() {
+                if let Some(r) = self
+                    .our_handle_control_message(ctx, Some(&mut connection), Some(msg))
+                    .await?
+                {
+                    return Ok(r);
+                }
+            }
+        }
+    }
+}
+
+/// Reads messages published to Redis Pub/Sub channels. Pub/Sub doesn't retain messages, so those
+/// published while the pipeline is stopped or recovering are lost. As every subscriber receives
+/// every message, only the first subtask subscribes.
+#[derive(StreamNode)]
+pub struct RedisPubSubSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: SchemaData,
+{
+    config: RedisConfig,
+    channels: Vec<String>,
+    pattern: bool,
+    deserializer: DataDeserializer<T>,
+    _t: PhantomData<K>,
+}
+
+#[source_fn(out_k = (), out_t = T)]
+impl<K, T> RedisPubSubSourceFunc<K, T>
+where
+    K: DeserializeOwned + Data,
+    T: SchemaData,
+{
+    pub fn from_config(config: &str) -> Self {
+        let (config, source, deserializer) = table_config(config);
+        let Source::PubSub { channels, pattern } = source else {
+            panic!("found non-pubsub redis config in pubsub source operator");
+        };
+
+        Self {
+            config,
+            channels: channels.split(',').map(|c| c.trim().to_string()).collect(),
+            pattern: pattern.unwrap_or(false),
+            deserializer,
+            _t: PhantomData,
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("redis-pubsub-{}", self.channels.join(","))
+    }
+
+    async fn run(&mut self, ctx: &mut Context<(), T>) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(r) => r,
+            Err(e) => {
+                ctx.report_error(e.name.clone(), e.details.clone()).await;
+
+                panic!("{}: {}", e.name, e.details);
+            }
+        }
+    }
+
+    async fn our_handle_control_message(
+        &mut self,
+        ctx: &mut Context<(), T>,
+        msg: Option<ControlMessage>,
+    ) -> Option<SourceFinishType> {
+        match msg? {
+            ControlMessage::Checkpoint(c) => {
+                debug!("starting checkpointing {}", ctx.task_info.task_index);
+                if self.checkpoint(c, ctx).await {
+                    return Some(SourceFinishType::Immediate);
+                }
+            }
+            ControlMessage::Stop { mode } => {
+                info!("Stopping Redis Pub/Sub source: {:?}", mode);
+
+                match mode {
+                    StopMode::Graceful => {
+                        return Some(SourceFinishType::Graceful);
+                    }
+                    StopMode::Immediate => {
+                        return Some(SourceFinishType::Immediate);
+                    }
+                }
+            }
+            ControlMessage::Commit { .. } => {
+                unreachable!("sources shouldn't receive commit messages");
+            }
+            ControlMessage::LoadCompacted { compacted } => {
+                ctx.load_compacted(compacted).await;
+            }
+            ControlMessage::NoOp => {}
+        }
+        None
+    }
+
+    async fn run_int(&mut self, ctx: &mut Context<(), T>) -> Result<SourceFinishType, UserError> {
+        if ctx.task_info.task_index != 0 {
+            ctx.broadcast(Message::Watermark(Watermark::Idle)).await;
+
+            loop {
+                let msg = ctx.control_rx.recv().await;
+                if let Some(r) = self.our_handle_control_message(ctx, msg).await {
+                    return Ok(r);
+                }
+            }
+        }
+
+        // published messages are broadcast to every node of a cluster, so any node will do
+        let address = match &self.config.connection {
+            RedisConfigConnection::Address(address) => address.0.clone(),
+            RedisConfigConnection::Addresses(addresses) => addresses
+                .first()
+                .expect("Redis cluster must have at least one address")
+                .0
+                .clone(),
+        };
+
+        let mut pubsub = standard_client(&self.config, &address)
+            .get_async_connection()
+            .await
+            .map_err(|e| UserError::new("Failed to connect to Redis", format!("{:?}", e)))?
+            .into_pubsub();
+
+        for channel in &self.channels {
+            let result = if self.pattern {
+                pubsub.psubscribe(channel).await
+            } else {
+                pubsub.subscribe(channel).await
+            };
+
+            result.map_err(|e| {
+                UserError::new(
+                    "Failed to subscribe to Redis channel",
+                    format!("{}: {:?}", channel, e),
+                )
+            })?;
+        }
+
+        let mut messages = pubsub.into_on_message();
+        loop {
+            select! {
+                message = messages.next() => {
+                    let Some(message) = message else {
+                        return Err(UserError::new("Redis subscription closed", "the connection to Redis was closed"));
+                    };
+
+                    let iter = self.deserializer.deserialize_slice(message.get_payload_bytes()).await;
+                    for value in iter {
+                        ctx.collector.collect(Record {
+                            timestamp: SystemTime::now(),
+                            key: None,
+                            value: value?,
+                        }).await;
+                    }
+                }
+                control_message = ctx.control_rx.recv() => {
+                    if let Some(r) = self.our_handle_control_message(ctx, control_message).await {
+                        return Ok(r);
+                    }
+                }
+            }
+        }
+    }
+}
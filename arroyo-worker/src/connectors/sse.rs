@@ -100,10 +100,10 @@ where
     }
 
     async fn on_start(&mut self, ctx: &mut Context<(), T>) {
-        let s: GlobalKeyedState<(), SSESourceState, _> =
+        let mut s: GlobalKeyedState<(), SSESourceState, _> =
             ctx.state.get_global_keyed_state('e').await;
 
-        if let Some(state) = s.get(&()) {
+        if let Some(state) = s.get(&()).await {
             self.state = state.clone();
         }
     }
@@ -110,6 +110,7 @@ impl<K: Key, T: Data + Sync, TPC: TwoPhaseCommitter<K, T>> TwoPhaseCommitterOper
         // take the max of all values
         let state_vec = tracking_key_state
             .get_all()
+            .await
             .into_iter()
             .map(|state| state.clone())
             .collect();
@@ -127,6 +128,7 @@ impl<K: Key, T: Data + Sync, TPC: TwoPhaseCommitter<K, T>> TwoPhaseCommitterOper
             > = ctx.state.get_global_keyed_state('p').await;
             self.pre_commits = pre_commit_state
                 .get_all()
+                .await
                 .into_iter()
                 .map(|state| state.clone())
                 .collect();
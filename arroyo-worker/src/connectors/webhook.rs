@@ -95,8 +95,13 @@ where
             .await
             .expect("websink semaphore closed");
 
-        let Some(body) = self.serializer.to_vec(&record.value) else {
-            return;
+        let body = match self.serializer.to_vec(&record.value) {
+            Ok(Some(body)) => body,
+            Ok(None) => return,
+            Err(e) => {
+                ctx.report_user_error(e).await;
+                return;
+            }
         };
 
         let body: bytes::Bytes = body.into();
@@ -257,6 +257,31 @@ impl<K: Key, T: Data> Collector<K, T> {
     }
 }
 
+/// Checks that the tables of a checkpoint (which may be a restored savepoint) can be read by the
+/// operator restoring it
+fn check_restored_tables(
+    restored: &[TableDescriptor],
+    tables: &[TableDescriptor],
+) -> anyhow::Result<()> {
+    for restored in restored {
+        let Some(table) = tables.iter().find(|t| t.name == restored.name) else {
+            anyhow::bail!("it has no table '{}'", restored.name);
+        };
+        if table.table_type != restored.table_type
+            || table.write_behavior != restored.write_behavior
+            || table.delete_behavior != restored.delete_behavior
+        {
+            anyhow::bail!(
+                "table '{}' was written as {:?} but is read as {:?}",
+                restored.name,
+                restored,
+                table
+            );
+        }
+    }
+    Ok(())
+}
+
 impl<K: Key, T: Data> Context<K, T> {
     pub async fn new(
         task_info: TaskInfo,
@@ -276,6 +301,10 @@ impl<K: Key, T: Data> Context<K, T> {
             retention_micros: 0,
         });
 
+        // operators added to the pipeline since the checkpoint was taken start without state
+        let restore_from =
+            restore_from.filter(|metadata| metadata.operator_ids.contains(&task_info.operator_id));
+
         let (state, watermark) = if let Some(metadata) = restore_from {
             let watermark = {
                 let metadata = StateBackend::load_operator_metadata(
@@ -283,11 +312,15 @@ impl<K: Key, T: Data> Context<K, T> {
                     &task_info.operator_id,
                     metadata.epoch,
                 )
-                .await;
-                metadata
-                    .expect("require metadata")
-                    .min_watermark
-                    .map(from_micros)
+                .await
+                .expect("require metadata");
+                if let Err(e) = check_restored_tables(&metadata.tables, &tables) {
+                    panic!(
+                        "cannot restore the state of operator {}: {}",
+                        task_info.operator_id, e
+                    );
+                }
+                metadata.min_watermark.map(from_micros)
             };
             let state = StateStore::<StateBackend>::from_checkpoint(
                 &task_info,
@@ -295,14 +328,25 @@ impl<K: Key, T: Data> Context<K, T> {
                 tables,
                 control_tx.clone(),
             )
-            .await;
+            .await
+            .unwrap_or_else(|e| {
+                panic!(
+                    "failed to restore the state of operator {}: {:?}",
+                    task_info.operator_id, e
+                )
+            });
 
             (state, watermark)
         } else {
-            (
-                StateStore::<StateBackend>::new(&task_info, tables, control_tx.clone()).await,
-                None,
-            )
+            let state = StateStore::<StateBackend>::new(&task_info, tables, control_tx.clone())
+                .await
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "failed to create the state of operator {}: {:?}",
+                        task_info.operator_id, e
+                    )
+                });
+            (state, None)
         };
 
         let (tx_queue_size_gauges, tx_queue_rem_gauges) =
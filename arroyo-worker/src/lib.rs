@@ -206,6 +206,9 @@ impl WorkerServer {
         let _guard =
             arroyo_server_common::init_logging(&format!("worker-{}-{}", self.id.0, self.job_id));
 
+        // fail now rather than in every task
+        arroyo_state::backend_type()?;
+
         let slots = std::env::var(arroyo_types::TASK_SLOTS_ENV)
             .map(|s| usize::from_str(&s).unwrap())
             .unwrap_or(8);
@@ -0,0 +1,269 @@
+//! Accumulators for the built-in aggregates that are computed incrementally by the two-phase
+//! aggregators. Each module exposes the same hooks that user-defined aggregates provide:
+//!
+//! * `accumulate` folds a single row into the state of a bin
+//! * `merge` combines the state of another bin into this one
+//! * `retract` removes a previously merged bin (only for aggregates usable in sliding windows)
+//! * `finish` produces the final value from the state
+//!
+//! States are plain tuples so that they can be stored in bins without any extra derives.
+
+/// Running sums of two variables, as `(count, sum_x, sum_y, sum_xy, sum_xx, sum_yy)`.
+pub type CovarianceState = (i64, f64, f64, f64, f64, f64);
+
+fn covariance_accumulate(state: &mut CovarianceState, x: f64, y: f64) {
+    state.0 += 1;
+    state.1 += x;
+    state.2 += y;
+    state.3 += x * y;
+    state.4 += x * x;
+    state.5 += y * y;
+}
+
+fn covariance_merge(state: &mut CovarianceState, other: &CovarianceState) {
+    state.0 += other.0;
+    state.1 += other.1;
+    state.2 += other.2;
+    state.3 += other.3;
+    state.4 += other.4;
+    state.5 += other.5;
+}
+
+fn covariance_retract(state: &mut CovarianceState, other: &CovarianceState) {
+    state.0 -= other.0;
+    state.1 -= other.1;
+    state.2 -= other.2;
+    state.3 -= other.3;
+    state.4 -= other.4;
+    state.5 -= other.5;
+}
+
+// the co-moment sum((x - mean_x) * (y - mean_y)), from which covariance and variance are derived
+fn co_moment(count: i64, sum_x: f64, sum_y: f64, sum_xy: f64) -> f64 {
+    sum_xy - sum_x * sum_y / count as f64
+}
+
+pub mod covar_samp {
+    use super::*;
+
+    pub fn accumulate(state: &mut CovarianceState, x: f64, y: f64) {
+        covariance_accumulate(state, x, y)
+    }
+
+    pub fn merge(state: &mut CovarianceState, other: &CovarianceState) {
+        covariance_merge(state, other)
+    }
+
+    pub fn retract(state: &mut CovarianceState, other: &CovarianceState) {
+        covariance_retract(state, other)
+    }
+
+    pub fn finish(state: &CovarianceState) -> Option<f64> {
+        if state.0 < 2 {
+            return None;
+        }
+        Some(co_moment(state.0, state.1, state.2, state.3) / (state.0 - 1) as f64)
+    }
+}
+
+pub mod covar_pop {
+    use super::*;
+
+    pub fn accumulate(state: &mut CovarianceState, x: f64, y: f64) {
+        covariance_accumulate(state, x, y)
+    }
+
+    pub fn merge(state: &mut CovarianceState, other: &CovarianceState) {
+        covariance_merge(state, other)
+    }
+
+    pub fn retract(state: &mut CovarianceState, other: &CovarianceState) {
+        covariance_retract(state, other)
+    }
+
+    pub fn finish(state: &CovarianceState) -> Option<f64> {
+        if state.0 < 1 {
+            return None;
+        }
+        Some(co_moment(state.0, state.1, state.2, state.3) / state.0 as f64)
+    }
+}
+
+pub mod corr {
+    use super::*;
+
+    pub fn accumulate(state: &mut CovarianceState, x: f64, y: f64) {
+        covariance_accumulate(state, x, y)
+    }
+
+    pub fn merge(state: &mut CovarianceState, other: &CovarianceState) {
+        covariance_merge(state, other)
+    }
+
+    pub fn retract(state: &mut CovarianceState, other: &CovarianceState) {
+        covariance_retract(state, other)
+    }
+
+    pub fn finish(state: &CovarianceState) -> Option<f64> {
+        if state.0 < 2 {
+            return None;
+        }
+        let covariance = co_moment(state.0, state.1, state.2, state.3);
+        let variance_x = co_moment(state.0, state.1, state.1, state.4);
+        let variance_y = co_moment(state.0, state.2, state.2, state.5);
+        if variance_x == 0.0 || variance_y == 0.0 {
+            return None;
+        }
+        Some(covariance / (variance_x * variance_y).sqrt())
+    }
+}
+
+/// ARG_MAX(value, by) keeps only the current best pair, so it can't be retracted and isn't
+/// computed incrementally for sliding windows.
+pub mod arg_max {
+    pub fn accumulate<V, B: PartialOrd>(state: &mut Option<(B, V)>, value: V, by: B) {
+        match state {
+            Some((current, _)) if *current >= by => {}
+            _ => *state = Some((by, value)),
+        }
+    }
+
+    pub fn merge<V: Clone, B: PartialOrd + Clone>(
+        state: &mut Option<(B, V)>,
+        other: &Option<(B, V)>,
+    ) {
+        if let Some((by, value)) = other {
+            accumulate(state, value.clone(), by.clone());
+        }
+    }
+
+    pub fn finish<V: Clone, B>(state: &Option<(B, V)>) -> Option<V> {
+        state.as_ref().map(|(_, value)| value.clone())
+    }
+}
+
+/// ARG_MIN(value, by), the counterpart of [`arg_max`].
+pub mod arg_min {
+    pub fn accumulate<V, B: PartialOrd>(state: &mut Option<(B, V)>, value: V, by: B) {
+        match state {
+            Some((current, _)) if *current <= by => {}
+            _ => *state = Some((by, value)),
+        }
+    }
+
+    pub fn merge<V: Clone, B: PartialOrd + Clone>(
+        state: &mut Option<(B, V)>,
+        other: &Option<(B, V)>,
+    ) {
+        if let Some((by, value)) = other {
+            accumulate(state, value.clone(), by.clone());
+        }
+    }
+
+    pub fn finish<V: Clone, B>(state: &Option<(B, V)>) -> Option<V> {
+        state.as_ref().map(|(_, value)| value.clone())
+    }
+}
+
+/// ARRAY_AGG(value ORDER BY ...) keeps every value with the key it's ordered by; the values are
+/// only sorted when the array is produced, so bins can be merged and retracted in any order.
+pub mod array_agg {
+    pub fn accumulate<K, V>(state: &mut Vec<(K, V)>, value: V, key: K) {
+        state.push((key, value));
+    }
+
+    pub fn merge<K: Clone, V: Clone>(state: &mut Vec<(K, V)>, other: &[(K, V)]) {
+        state.extend(other.iter().cloned());
+    }
+
+    pub fn retract<K: PartialEq, V: PartialEq>(state: &mut Vec<(K, V)>, other: &[(K, V)]) {
+        for entry in other {
+            if let Some(i) = state.iter().position(|e| e == entry) {
+                state.remove(i);
+            }
+        }
+    }
+
+    pub fn finish<K, V: Clone, O: Ord>(state: &[(K, V)], sort_key: impl Fn(&K) -> O) -> Vec<V> {
+        let mut entries: Vec<_> = state.iter().collect();
+        entries.sort_by_key(|(key, _)| sort_key(key));
+        entries
+            .into_iter()
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_covariance_merge_and_retract() {
+        let mut left = CovarianceState::default();
+        for (x, y) in [(1.0, 2.0), (2.0, 4.0), (3.0, 7.0)] {
+            covar_samp::accumulate(&mut left, x, y);
+        }
+        let mut right = CovarianceState::default();
+        for (x, y) in [(4.0, 8.0), (5.0, 9.0)] {
+            covar_samp::accumulate(&mut right, x, y);
+        }
+
+        let mut merged = left;
+        covar_samp::merge(&mut merged, &right);
+        assert_eq!(Some(4.5), covar_samp::finish(&merged));
+        assert_eq!(Some(3.6), covar_pop::finish(&merged));
+
+        covar_samp::retract(&mut merged, &right);
+        assert_eq!(covar_samp::finish(&left), covar_samp::finish(&merged));
+    }
+
+    #[test]
+    fn test_corr() {
+        let mut state = CovarianceState::default();
+        for (x, y) in [(1.0, 3.0), (2.0, 5.0), (3.0, 7.0)] {
+            corr::accumulate(&mut state, x, y);
+        }
+        assert!((corr::finish(&state).unwrap() - 1.0).abs() < 1e-9);
+
+        let mut constant = CovarianceState::default();
+        corr::accumulate(&mut constant, 1.0, 1.0);
+        corr::accumulate(&mut constant, 1.0, 2.0);
+        assert_eq!(None, corr::finish(&constant));
+    }
+
+    #[test]
+    fn test_arg_max_and_min() {
+        let mut max = None;
+        let mut min = None;
+        for (value, by) in [("a", 3), ("b", 7), ("c", 1)] {
+            arg_max::accumulate(&mut max, value, by);
+            arg_min::accumulate(&mut min, value, by);
+        }
+        let mut other = None;
+        arg_max::accumulate(&mut other, "d", 9);
+        arg_max::merge(&mut max, &other);
+
+        assert_eq!(Some("d"), arg_max::finish(&max));
+        assert_eq!(Some("c"), arg_min::finish(&min));
+    }
+
+    #[test]
+    fn test_array_agg() {
+        let mut state = vec![];
+        array_agg::accumulate(&mut state, "c", 3);
+        array_agg::accumulate(&mut state, "a", 1);
+
+        let mut other = vec![];
+        array_agg::accumulate(&mut other, "b", 2);
+        array_agg::merge(&mut state, &other);
+        assert_eq!(vec!["a", "b", "c"], array_agg::finish(&state, |k| *k));
+        assert_eq!(
+            vec!["c", "b", "a"],
+            array_agg::finish(&state, |k| std::cmp::Reverse(*k))
+        );
+
+        array_agg::retract(&mut state, &other);
+        assert_eq!(vec!["a", "c"], array_agg::finish(&state, |k| *k));
+    }
+}
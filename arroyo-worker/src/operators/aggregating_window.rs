@@ -100,14 +100,14 @@ impl<K: Key, T: Data, BinA: Data, MemA: Data, OutT: Data>
         };
         let mut aggregating_map = ctx.state.get_time_key_map('a', watermark).await;
         let mut key = record.key.clone().unwrap();
-        let bin_aggregate = aggregating_map.get(bin_start, &mut key);
+        let bin_aggregate = aggregating_map.get(bin_start, &mut key).await;
         let new_value = (self.bin_merger)(&record.value, bin_aggregate);
         aggregating_map.insert(bin_start, key, new_value);
     }
 
     async fn on_start(&mut self, ctx: &mut Context<K, OutT>) {
         let watermark = ctx.last_present_watermark();
-        let map = ctx.state.get_time_key_map::<K, BinA>('a', watermark).await;
+        let mut map = ctx.state.get_time_key_map::<K, BinA>('a', watermark).await;
 
         let Some(map_min_time) = map.get_min_time() else {
             self.state = SlidingWindowState::NoData;
@@ -129,7 +129,7 @@ impl<K: Key, T: Data, BinA: Data, MemA: Data, OutT: Data>
         }
         let mut bin = map_min_bin;
         while bin < watermark_bin {
-            for (key, bin_value) in map.get_all_for_time(bin) {
+            for (key, bin_value) in map.get_all_for_time(bin).await {
                 self.add_data(key, bin_value.clone());
             }
             bin += self.slide;
@@ -188,12 +188,15 @@ impl<K: Key, T: Data, BinA: Data, MemA: Data, OutT: Data>
         aggregating_map.flush_at_watermark(bin_end).await;
 
         // add the next bin data to the in memory store.
-        for (key, bin) in aggregating_map.get_all_for_time(bin_start) {
+        for (key, bin) in aggregating_map.get_all_for_time(bin_start).await {
             self.add_data(key, bin.clone());
         }
 
         // remove the leaving bin data from memory
-        for (key, bin) in aggregating_map.evict_for_timestamp(bin_start - self.width) {
+        for (key, bin) in aggregating_map
+            .evict_for_timestamp(bin_start - self.width)
+            .await
+        {
             self.remove_data(&key, bin);
         }
 
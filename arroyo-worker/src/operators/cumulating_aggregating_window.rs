@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use crate::engine::{Context, StreamNode};
+use arroyo_macro::process_fn;
+use arroyo_rpc::grpc::{TableDeleteBehavior, TableDescriptor, TableType, TableWriteBehavior};
+use arroyo_state::tables::time_key_map::TimeKeyMap;
+use arroyo_types::*;
+
+/// An aggregator for cumulating windows, which start every `max_size` and are emitted every
+/// `step` until they reach it, each time covering everything since their start.
+///
+/// Rows are aggregated into tumbling bins of width `step`. When the watermark passes the end of a
+/// bin, it's combined into the running aggregate of its window, which is emitted for every key
+/// that has had data in the window so far and stored at the start of the next bin.
+#[derive(StreamNode)]
+pub struct CumulatingAggregatingWindowFunc<K: Key, T: Data, BinA: Data, OutT: Data> {
+    step: Duration,
+    max_size: Duration,
+    aggregator: fn(&K, Window, &BinA) -> OutT,
+    bin_merger: fn(&T, Option<&BinA>) -> BinA,
+    bin_combiner: fn(&BinA, Option<&BinA>) -> BinA,
+}
+
+fn truncate(timestamp: SystemTime, width: Duration) -> SystemTime {
+    let nanos = to_nanos(timestamp);
+    from_nanos(nanos - nanos % width.as_nanos())
+}
+
+#[process_fn(in_k = K, in_t = T, out_k = K, out_t = OutT)]
+impl<K: Key, T: Data, BinA: Data, OutT: Data> CumulatingAggregatingWindowFunc<K, T, BinA, OutT> {
+    fn name(&self) -> String {
+        "CumulatingAggregatingWindow".to_string()
+    }
+
+    pub fn new(
+        step: Duration,
+        max_size: Duration,
+        aggregator: fn(&K, Window, &BinA) -> OutT,
+        bin_merger: fn(&T, Option<&BinA>) -> BinA,
+        bin_combiner: fn(&BinA, Option<&BinA>) -> BinA,
+    ) -> Self {
+        assert!(
+            !step.is_zero() && max_size.as_nanos() % step.as_nanos() == 0,
+            "the maximum size of a cumulating window must be a multiple of its step"
+        );
+        CumulatingAggregatingWindowFunc {
+            step,
+            max_size,
+            aggregator,
+            bin_merger,
+            bin_combiner,
+        }
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        vec![
+            TableDescriptor {
+                name: "a".to_string(),
+                description: "window bins".to_string(),
+                table_type: TableType::TimeKeyMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: self.step.as_micros() as u64,
+            },
+            TableDescriptor {
+                name: "c".to_string(),
+                description: "cumulative window aggregates".to_string(),
+                table_type: TableType::TimeKeyMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: self.step.as_micros() as u64,
+            },
+        ]
+    }
+
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<K, OutT>) {
+        let bin_start = truncate(record.timestamp, self.step);
+        if let Some(watermark) = ctx.last_present_watermark() {
+            if bin_start < truncate(watermark, self.step) {
+                return;
+            }
+        }
+
+        let mut bins: TimeKeyMap<K, BinA, _> = ctx
+            .state
+            .get_time_key_map('a', ctx.last_present_watermark())
+            .await;
+        let mut key = record.key.clone().unwrap();
+        let bin_aggregate = bins.get(bin_start, &mut key).await;
+        let bin = (self.bin_merger)(&record.value, bin_aggregate);
+        bins.insert(bin_start, key, bin);
+    }
+
+    // the start of the earliest bin that hasn't been emitted
+    async fn next_bin(&mut self, ctx: &mut Context<K, OutT>) -> Option<SystemTime> {
+        let watermark = ctx.last_present_watermark();
+        let bins: TimeKeyMap<K, BinA, _> = ctx.state.get_time_key_map('a', watermark).await;
+        let bin = bins.get_min_time();
+        let cumulative: TimeKeyMap<K, BinA, _> = ctx.state.get_time_key_map('c', watermark).await;
+        match (bin, cumulative.get_min_time()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    async fn advance(&mut self, bin_start: SystemTime, ctx: &mut Context<K, OutT>) {
+        let watermark = ctx.last_present_watermark();
+        let window_start = truncate(bin_start, self.max_size);
+        let bin_end = bin_start + self.step;
+
+        let mut aggregates: HashMap<K, BinA> = {
+            let mut cumulative: TimeKeyMap<K, BinA, _> =
+                ctx.state.get_time_key_map('c', watermark).await;
+            cumulative
+                .evict_for_timestamp(bin_start)
+                .await
+                .into_iter()
+                .collect()
+        };
+        {
+            let mut bins: TimeKeyMap<K, BinA, _> = ctx.state.get_time_key_map('a', watermark).await;
+            for (key, bin) in bins.evict_for_timestamp(bin_start).await {
+                let aggregate = (self.bin_combiner)(&bin, aggregates.get(&key));
+                aggregates.insert(key, aggregate);
+            }
+        }
+
+        let window = Window::new(window_start, bin_end);
+        let mut records = vec![];
+        for (key, aggregate) in &aggregates {
+            records.push(Record {
+                timestamp: bin_end - Duration::from_nanos(1),
+                key: Some(key.clone()),
+                value: (self.aggregator)(key, window, aggregate),
+            });
+        }
+
+        // the window keeps accumulating until it reaches its maximum size
+        if bin_end < window_start + self.max_size {
+            let mut cumulative: TimeKeyMap<K, BinA, _> =
+                ctx.state.get_time_key_map('c', watermark).await;
+            for (key, aggregate) in aggregates {
+                cumulative.insert(bin_end, key, aggregate);
+            }
+        }
+
+        for record in records {
+            ctx.collect(record).await;
+        }
+    }
+
+    async fn handle_watermark(&mut self, watermark: Watermark, ctx: &mut Context<K, OutT>) {
+        if let Watermark::EventTime(t) = watermark {
+            while let Some(bin_start) = self.next_bin(ctx).await {
+                if bin_start + self.step > t {
+                    break;
+                }
+                self.advance(bin_start, ctx).await;
+            }
+        }
+
+        ctx.broadcast(arroyo_types::Message::Watermark(watermark))
+            .await;
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _checkpoint_barrier: &arroyo_types::CheckpointBarrier,
+        ctx: &mut Context<K, OutT>,
+    ) {
+        let watermark = ctx.last_present_watermark();
+        let mut bins: TimeKeyMap<K, BinA, _> = ctx.state.get_time_key_map('a', watermark).await;
+        bins.flush().await;
+        let mut cumulative: TimeKeyMap<K, BinA, _> =
+            ctx.state.get_time_key_map('c', watermark).await;
+        cumulative.flush().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(
+            from_millis(4_000),
+            truncate(from_millis(5_500), Duration::from_secs(4))
+        );
+        assert_eq!(
+            from_millis(5_000),
+            truncate(from_millis(5_500), Duration::from_secs(1))
+        );
+    }
+}
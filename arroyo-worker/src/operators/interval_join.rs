@@ -0,0 +1,209 @@
+use std::{
+    marker::PhantomData,
+    time::{Duration, SystemTime},
+};
+
+use arroyo_macro::{co_process_fn, StreamNode};
+use arroyo_rpc::grpc::{TableDeleteBehavior, TableDescriptor, TableType, TableWriteBehavior};
+use arroyo_state::tables::key_time_multi_map::KeyTimeMultiMap;
+use arroyo_types::*;
+
+use crate::engine::Context;
+
+/// An inner join between two streams whose rows match when their keys are equal and the left
+/// record's timestamp minus the right record's lies within `[lower_bound, upper_bound]` (in
+/// microseconds).
+///
+/// Rows from each side are stored by their timestamp in a `KeyTimeMultiMap` and evicted once the
+/// watermark guarantees no future row of the other side can fall within their interval, so the
+/// output is append-only.
+#[derive(StreamNode)]
+pub struct IntervalJoin<K: Key, T1: Data, T2: Data> {
+    lower_bound: i64,
+    upper_bound: i64,
+    _t: PhantomData<(K, T1, T2)>,
+}
+
+fn shift(time: SystemTime, micros: i64) -> SystemTime {
+    if micros >= 0 {
+        time + Duration::from_micros(micros as u64)
+    } else {
+        time.checked_sub(Duration::from_micros(micros.unsigned_abs()))
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+}
+
+#[co_process_fn(in_k1=K, in_t1=T1, in_k2=K, in_t2=T2, out_k=K, out_t=UpdatingData<(T1, T2)>)]
+impl<K: Key, T1: Data, T2: Data> IntervalJoin<K, T1, T2> {
+    fn name(&self) -> String {
+        "IntervalJoin".to_string()
+    }
+
+    pub fn new(lower_bound: i64, upper_bound: i64) -> Self {
+        assert!(
+            lower_bound <= upper_bound,
+            "interval join lower bound must not be after its upper bound"
+        );
+        Self {
+            lower_bound,
+            upper_bound,
+            _t: PhantomData,
+        }
+    }
+
+    // left rows can match right rows down to (left_time - upper_bound), so they are only needed
+    // while left_time >= watermark + lower_bound
+    fn left_retention(&self) -> Duration {
+        Duration::from_micros((-self.lower_bound).max(0) as u64)
+    }
+
+    // right rows are needed while right_time >= watermark - upper_bound
+    fn right_retention(&self) -> Duration {
+        Duration::from_micros(self.upper_bound.max(0) as u64)
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        vec![
+            TableDescriptor {
+                name: "l".to_string(),
+                description: "interval join left state".to_string(),
+                table_type: TableType::KeyTimeMultiMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: self.left_retention().as_micros() as u64,
+            },
+            TableDescriptor {
+                name: "r".to_string(),
+                description: "interval join right state".to_string(),
+                table_type: TableType::KeyTimeMultiMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: self.right_retention().as_micros() as u64,
+            },
+        ]
+    }
+
+    async fn process_left(
+        &mut self,
+        left_record: &Record<K, T1>,
+        ctx: &mut Context<K, UpdatingData<(T1, T2)>>,
+    ) {
+        if let Some(watermark) = ctx.last_present_watermark() {
+            if left_record.timestamp < watermark {
+                return;
+            }
+        };
+        let left_time = left_record.timestamp;
+        let mut key = left_record.key.clone().unwrap();
+
+        let mut right_state: KeyTimeMultiMap<K, T2, _> =
+            ctx.state.get_key_time_multi_map('r').await;
+        let matches: Vec<(SystemTime, T2)> = right_state
+            .get_time_range_with_timestamps(
+                &mut key,
+                shift(left_time, -self.upper_bound),
+                shift(left_time, 1 - self.lower_bound),
+            )
+            .await
+            .into_iter()
+            .map(|(time, right)| (time, right.clone()))
+            .collect();
+
+        for (right_time, right) in matches {
+            ctx.collect(Record {
+                timestamp: left_time.max(right_time),
+                key: Some(key.clone()),
+                value: UpdatingData::Append((left_record.value.clone(), right)),
+            })
+            .await;
+        }
+
+        let mut left_state: KeyTimeMultiMap<K, T1, _> = ctx.state.get_key_time_multi_map('l').await;
+        left_state
+            .insert(left_time, key, left_record.value.clone())
+            .await;
+    }
+
+    async fn process_right(
+        &mut self,
+        right_record: &Record<K, T2>,
+        ctx: &mut Context<K, UpdatingData<(T1, T2)>>,
+    ) {
+        if let Some(watermark) = ctx.last_present_watermark() {
+            if right_record.timestamp < watermark {
+                return;
+            }
+        };
+        let right_time = right_record.timestamp;
+        let mut key = right_record.key.clone().unwrap();
+
+        let mut left_state: KeyTimeMultiMap<K, T1, _> = ctx.state.get_key_time_multi_map('l').await;
+        let matches: Vec<(SystemTime, T1)> = left_state
+            .get_time_range_with_timestamps(
+                &mut key,
+                shift(right_time, self.lower_bound),
+                shift(right_time, self.upper_bound + 1),
+            )
+            .await
+            .into_iter()
+            .map(|(time, left)| (time, left.clone()))
+            .collect();
+
+        for (left_time, left) in matches {
+            ctx.collect(Record {
+                timestamp: right_time.max(left_time),
+                key: Some(key.clone()),
+                value: UpdatingData::Append((left, right_record.value.clone())),
+            })
+            .await;
+        }
+
+        let mut right_state: KeyTimeMultiMap<K, T2, _> =
+            ctx.state.get_key_time_multi_map('r').await;
+        right_state
+            .insert(right_time, key, right_record.value.clone())
+            .await;
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut Context<K, UpdatingData<(T1, T2)>>,
+    ) {
+        if let Watermark::EventTime(watermark) = watermark {
+            let mut left_state: KeyTimeMultiMap<K, T1, _> =
+                ctx.state.get_key_time_multi_map('l').await;
+            left_state
+                .expire_entries_before(shift(watermark, self.lower_bound))
+                .await;
+
+            let mut right_state: KeyTimeMultiMap<K, T2, _> =
+                ctx.state.get_key_time_multi_map('r').await;
+            right_state
+                .expire_entries_before(shift(watermark, -self.upper_bound))
+                .await;
+        }
+
+        ctx.broadcast(arroyo_types::Message::Watermark(watermark))
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shift() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(10);
+        assert_eq!(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(15),
+            shift(time, 5_000_000)
+        );
+        assert_eq!(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(4),
+            shift(time, -6_000_000)
+        );
+        assert_eq!(SystemTime::UNIX_EPOCH, shift(time, -20_000_000));
+    }
+}
@@ -0,0 +1,526 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::marker::PhantomData;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::engine::{Context, StreamNode};
+use arroyo_formats::{DataDeserializer, SchemaData};
+use arroyo_macro::process_fn;
+use arroyo_rpc::OperatorConfig;
+use arroyo_types::{CheckpointBarrier, Data, Key, Message, Record, UserError, Watermark};
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// The number of times a batch is looked up before the failure is reported and the batch is
+/// queued to be looked up again
+const MAX_LOOKUP_ATTEMPTS: u32 = 5;
+
+/// A connector that can fetch values for a batch of keys from an external system, used as the
+/// right side of a lookup join. Values are returned in the table's format, with `None` for keys
+/// that don't exist.
+#[async_trait]
+pub trait LookupConnector: Send + Sized + 'static {
+    fn from_config(config: &str) -> Self;
+
+    async fn lookup(&mut self, keys: &[String]) -> anyhow::Result<Vec<Option<Vec<u8>>>>;
+}
+
+type LookupResult<C> = (C, Vec<String>, anyhow::Result<Vec<Option<Vec<u8>>>>);
+
+/// Looks up a batch of keys, retrying failures with an exponential backoff. A connector that
+/// returns the wrong number of values is treated as having failed.
+async fn lookup_with_retries<C: LookupConnector>(
+    connector: &mut C,
+    keys: &[String],
+) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+    let mut attempt = 1;
+    loop {
+        let result = connector.lookup(keys).await.and_then(|values| {
+            if values.len() == keys.len() {
+                Ok(values)
+            } else {
+                Err(anyhow::anyhow!(
+                    "lookup returned {} values for {} keys",
+                    values.len(),
+                    keys.len()
+                ))
+            }
+        });
+
+        match result {
+            Ok(values) => return Ok(values),
+            Err(e) if attempt < MAX_LOOKUP_ATTEMPTS => {
+                warn!(
+                    "lookup of {} keys failed, retrying (attempt {}): {:?}",
+                    keys.len(),
+                    attempt,
+                    e
+                );
+                tokio::time::sleep(Duration::from_millis((50 * (1 << attempt)).min(5_000))).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+enum Pending<T: Data> {
+    Record {
+        timestamp: SystemTime,
+        key: Option<String>,
+        value: T,
+    },
+    Watermark(Watermark),
+}
+
+/// A cache of looked-up values (including misses), which expires entries after a fixed TTL and
+/// evicts the oldest entries once it grows past `max_entries`.
+struct LookupCache<R: Data> {
+    ttl: Duration,
+    max_entries: usize,
+    entries: HashMap<String, (Instant, Option<R>)>,
+    insertion_order: VecDeque<(Instant, String)>,
+}
+
+impl<R: Data> LookupCache<R> {
+    fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries,
+            entries: HashMap::new(),
+            insertion_order: VecDeque::new(),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Option<R>> {
+        self.entries
+            .get(key)
+            .filter(|(inserted, _)| inserted.elapsed() < self.ttl)
+            .map(|(_, value)| value)
+    }
+
+    fn insert(&mut self, key: String, value: Option<R>) {
+        let now = Instant::now();
+        self.entries.insert(key.clone(), (now, value));
+        self.insertion_order.push_back((now, key));
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while let Some((inserted, key)) = self.insertion_order.front() {
+            if self.entries.len() <= self.max_entries && inserted.elapsed() < self.ttl {
+                return;
+            }
+
+            // only remove the entry if it hasn't been re-inserted since
+            if self.entries.get(key).map(|(t, _)| t == inserted) == Some(true) {
+                self.entries.remove(key);
+            }
+            self.insertion_order.pop_front();
+        }
+    }
+}
+
+/// Converts the (single-field) key struct for a record into the string that is looked up in the
+/// external system. Null keys never match.
+fn lookup_key<K: Serialize>(key: &K) -> Option<String> {
+    let value = match serde_json::to_value(key).unwrap() {
+        Value::Object(map) if map.len() == 1 => map.into_iter().next().unwrap().1,
+        value => value,
+    };
+
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s),
+        value => Some(value.to_string()),
+    }
+}
+
+#[derive(StreamNode)]
+pub struct LookupJoinFunc<
+    K: Key + Serialize,
+    T: Data,
+    R: SchemaData,
+    OutT: Data,
+    C: LookupConnector,
+> {
+    connector: Option<C>,
+    deserializer: DataDeserializer<R>,
+    merge: fn(&T, Option<&R>) -> Option<OutT>,
+    cache: Option<LookupCache<R>>,
+    max_batch_size: usize,
+    max_batch_wait: Duration,
+    // records and watermarks that are waiting on lookups, in the order they arrived
+    pending: VecDeque<Pending<T>>,
+    // values for the keys that pending records are waiting on; entries are removed once the last
+    // record waiting on them has been emitted
+    resolved: HashMap<String, Option<R>>,
+    // the number of pending records for each key
+    waiting: HashMap<String, usize>,
+    // keys that have been requested, either in the next batch or the in-flight batch
+    requested: HashSet<String>,
+    next_batch: Vec<String>,
+    next_batch_started: Option<Instant>,
+    in_flight: Option<JoinHandle<LookupResult<C>>>,
+    _t: PhantomData<K>,
+}
+
+#[process_fn(in_k = K, in_t = T, out_k = (), out_t = OutT, tick_ms = 10)]
+impl<K: Key + Serialize, T: Data, R: SchemaData, OutT: Data, C: LookupConnector>
+    LookupJoinFunc<K, T, R, OutT, C>
+{
+    fn name(&self) -> String {
+        "LookupJoin".to_string()
+    }
+
+    pub fn new(
+        config: &str,
+        merge: fn(&T, Option<&R>) -> Option<OutT>,
+        cache_ttl: Option<Duration>,
+        max_cache_entries: usize,
+        max_batch_size: usize,
+        max_batch_wait: Duration,
+    ) -> Self {
+        let operator_config: OperatorConfig =
+            serde_json::from_str(config).expect("Invalid config for LookupJoin");
+
+        Self {
+            connector: Some(C::from_config(config)),
+            deserializer: DataDeserializer::new(
+                operator_config
+                    .format
+                    .expect("lookup tables must have a format"),
+                operator_config.framing,
+            ),
+            merge,
+            cache: cache_ttl.map(|ttl| LookupCache::new(ttl, max_cache_entries)),
+            max_batch_size: max_batch_size.max(1),
+            max_batch_wait,
+            pending: VecDeque::new(),
+            resolved: HashMap::new(),
+            waiting: HashMap::new(),
+            requested: HashSet::new(),
+            next_batch: vec![],
+            next_batch_started: None,
+            in_flight: None,
+            _t: PhantomData,
+        }
+    }
+
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), OutT>) {
+        let key = lookup_key(
+            record
+                .key
+                .as_ref()
+                .expect("lookup join input must be keyed"),
+        );
+
+        if let Some(key) = &key {
+            *self.waiting.entry(key.clone()).or_default() += 1;
+            if !self.resolved.contains_key(key) && !self.requested.contains(key) {
+                match self.cache.as_ref().and_then(|c| c.get(key)) {
+                    Some(value) => {
+                        self.resolved.insert(key.clone(), value.clone());
+                    }
+                    None => {
+                        self.requested.insert(key.clone());
+                        self.next_batch.push(key.clone());
+                        self.next_batch_started.get_or_insert_with(Instant::now);
+                    }
+                }
+            }
+        }
+
+        self.pending.push_back(Pending::Record {
+            timestamp: record.timestamp,
+            key,
+            value: record.value.clone(),
+        });
+
+        if self.next_batch.len() >= self.max_batch_size {
+            // wait for the previous batch before sending another, which bounds the amount
+            // of buffered data
+            if self.in_flight.is_some() {
+                self.finish_batch(ctx).await;
+            }
+            self.start_batch();
+        }
+
+        self.emit_resolved(ctx).await;
+    }
+
+    fn start_batch(&mut self) {
+        let keys = std::mem::take(&mut self.next_batch);
+        self.next_batch_started = None;
+        let mut connector = self
+            .connector
+            .take()
+            .expect("lookup started while another was in flight");
+
+        self.in_flight = Some(tokio::spawn(async move {
+            let result = lookup_with_retries(&mut connector, &keys).await;
+            (connector, keys, result)
+        }));
+    }
+
+    async fn finish_batch(&mut self, ctx: &mut Context<(), OutT>) {
+        let Some(handle) = self.in_flight.take() else {
+            return;
+        };
+
+        let (connector, keys, result) = handle.await.expect("lookup task panicked");
+        self.connector = Some(connector);
+
+        let values = match result {
+            Ok(values) => values,
+            Err(e) => {
+                // the records waiting on these keys stay pending, and the keys are queued to be
+                // looked up again, so that failures don't drop or mis-join records
+                ctx.report_user_error(UserError::new(
+                    "Lookup failed",
+                    format!(
+                        "lookup of {} keys failed after {} attempts, retrying: {:?}",
+                        keys.len(),
+                        MAX_LOOKUP_ATTEMPTS,
+                        e
+                    ),
+                ))
+                .await;
+                self.next_batch.splice(0..0, keys);
+                self.next_batch_started.get_or_insert_with(Instant::now);
+                return;
+            }
+        };
+
+        for (key, value) in keys.into_iter().zip(values) {
+            let value = match value {
+                Some(bytes) => match self.deserializer.deserialize_slice(&bytes).await.next() {
+                    Some(Ok(value)) => Some(value),
+                    Some(Err(e)) => {
+                        ctx.report_user_error(e).await;
+                        None
+                    }
+                    None => None,
+                },
+                None => None,
+            };
+
+            self.requested.remove(&key);
+            if let Some(cache) = &mut self.cache {
+                cache.insert(key.clone(), value.clone());
+            }
+            self.resolved.insert(key, value);
+        }
+    }
+
+    async fn emit_resolved(&mut self, ctx: &mut Context<(), OutT>) {
+        while let Some(front) = self.pending.front() {
+            if let Pending::Record { key: Some(key), .. } = front {
+                if !self.resolved.contains_key(key) {
+                    break;
+                }
+            }
+
+            match self.pending.pop_front().unwrap() {
+                Pending::Record {
+                    timestamp,
+                    key,
+                    value,
+                } => {
+                    let right = key.as_ref().and_then(|key| self.resolved.get(key));
+                    let out = (self.merge)(&value, right.and_then(|v| v.as_ref()));
+
+                    if let Some(key) = key {
+                        self.release(key);
+                    }
+
+                    if let Some(value) = out {
+                        ctx.collect(Record {
+                            timestamp,
+                            key: None,
+                            value,
+                        })
+                        .await;
+                    }
+                }
+                Pending::Watermark(watermark) => {
+                    ctx.broadcast(Message::Watermark(watermark)).await;
+                }
+            }
+        }
+    }
+
+    /// Drops the resolved value for a key once no pending record is waiting on it, so that later
+    /// records go through the cache (and its TTL) again
+    fn release(&mut self, key: String) {
+        let count = self
+            .waiting
+            .get_mut(&key)
+            .expect("emitted a record that wasn't waiting");
+        *count -= 1;
+        if *count == 0 {
+            self.waiting.remove(&key);
+            self.resolved.remove(&key);
+        }
+    }
+
+    async fn flush(&mut self, ctx: &mut Context<(), OutT>) {
+        loop {
+            self.finish_batch(ctx).await;
+            if self.next_batch.is_empty() {
+                break;
+            }
+            self.start_batch();
+        }
+
+        self.emit_resolved(ctx).await;
+    }
+
+    async fn handle_tick(&mut self, _: u64, ctx: &mut Context<(), OutT>) {
+        if self
+            .in_flight
+            .as_ref()
+            .map(|h| h.is_finished())
+            .unwrap_or(false)
+        {
+            self.finish_batch(ctx).await;
+        }
+
+        if self.in_flight.is_none()
+            && self
+                .next_batch_started
+                .map(|t| t.elapsed() >= self.max_batch_wait)
+                .unwrap_or(false)
+        {
+            self.start_batch();
+        }
+
+        self.emit_resolved(ctx).await;
+    }
+
+    async fn handle_watermark(&mut self, watermark: Watermark, ctx: &mut Context<(), OutT>) {
+        // watermarks can't pass records that are still waiting on lookups
+        if self.pending.is_empty() {
+            ctx.broadcast(Message::Watermark(watermark)).await;
+        } else {
+            self.pending.push_back(Pending::Watermark(watermark));
+        }
+    }
+
+    async fn handle_checkpoint(&mut self, _: &CheckpointBarrier, ctx: &mut Context<(), OutT>) {
+        // lookups are not checkpointed, so everything before the barrier must be emitted
+        self.flush(ctx).await;
+    }
+
+    async fn on_close(&mut self, ctx: &mut Context<(), OutT>) {
+        self.flush(ctx).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lookup_key, lookup_with_retries, LookupCache, LookupConnector};
+    use async_trait::async_trait;
+    use serde::Serialize;
+    use std::time::Duration;
+
+    #[derive(Serialize)]
+    struct StringKey {
+        id: String,
+    }
+
+    #[derive(Serialize)]
+    struct IntKey {
+        id: Option<i64>,
+    }
+
+    #[test]
+    fn test_lookup_key() {
+        assert_eq!(
+            lookup_key(&StringKey {
+                id: "abc".to_string()
+            }),
+            Some("abc".to_string())
+        );
+        assert_eq!(lookup_key(&IntKey { id: Some(5) }), Some("5".to_string()));
+        assert_eq!(lookup_key(&IntKey { id: None }), None);
+    }
+
+    #[test]
+    fn test_lookup_cache() {
+        let mut cache: LookupCache<u64> = LookupCache::new(Duration::from_secs(60), 2);
+        cache.insert("a".to_string(), Some(1));
+        cache.insert("b".to_string(), None);
+        assert_eq!(cache.get("a"), Some(&Some(1)));
+        assert_eq!(cache.get("b"), Some(&None));
+        assert_eq!(cache.get("c"), None);
+
+        // re-inserting a key shouldn't let its old entry evict the new value
+        cache.insert("a".to_string(), Some(2));
+        assert_eq!(cache.get("a"), Some(&Some(2)));
+
+        cache.insert("c".to_string(), Some(3));
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(&Some(2)));
+        assert_eq!(cache.get("c"), Some(&Some(3)));
+
+        let mut expiring: LookupCache<u64> = LookupCache::new(Duration::ZERO, 10);
+        expiring.insert("a".to_string(), Some(1));
+        assert_eq!(expiring.get("a"), None);
+        assert!(expiring.entries.is_empty());
+    }
+
+    struct FlakyConnector {
+        failures: usize,
+    }
+
+    #[async_trait]
+    impl LookupConnector for FlakyConnector {
+        fn from_config(_: &str) -> Self {
+            unimplemented!()
+        }
+
+        async fn lookup(&mut self, keys: &[String]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                anyhow::bail!("unavailable");
+            }
+            Ok(keys.iter().map(|k| Some(k.as_bytes().to_vec())).collect())
+        }
+    }
+
+    struct MisbehavingConnector;
+
+    #[async_trait]
+    impl LookupConnector for MisbehavingConnector {
+        fn from_config(_: &str) -> Self {
+            unimplemented!()
+        }
+
+        async fn lookup(&mut self, _: &[String]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lookup_retries() {
+        let keys = vec!["a".to_string()];
+
+        let mut connector = FlakyConnector { failures: 2 };
+        assert_eq!(
+            lookup_with_retries(&mut connector, &keys).await.unwrap(),
+            vec![Some(b"a".to_vec())]
+        );
+
+        let mut connector = FlakyConnector { failures: 10 };
+        assert!(lookup_with_retries(&mut connector, &keys).await.is_err());
+
+        let mut connector = MisbehavingConnector;
+        let err = lookup_with_retries(&mut connector, &keys)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "lookup returned 0 values for 1 keys");
+    }
+}
@@ -0,0 +1,492 @@
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    time::{Duration, SystemTime},
+};
+
+use arroyo_macro::process_fn;
+use arroyo_rpc::grpc::{TableDeleteBehavior, TableDescriptor, TableType, TableWriteBehavior};
+use arroyo_state::tables::{keyed_map::KeyedState, time_key_map::TimeKeyMap};
+use arroyo_types::*;
+use bincode::{Decode, Encode};
+
+use crate::engine::{Context, StreamNode};
+
+/// A pattern variable repeated between `min` and `max` (unbounded if `None`) times.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatternElement {
+    pub variable: usize,
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+/// A partial match of the pattern, which has consumed consecutive rows of its key starting at
+/// row number `start`, and is currently in the `count`th repetition of `element`.
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+struct Run<T: Data> {
+    start: u64,
+    start_time: SystemTime,
+    end_time: SystemTime,
+    element: usize,
+    count: u64,
+    rows: Vec<(usize, T)>,
+}
+
+impl<T: Data> Run<T> {
+    fn extend(&self, element: usize, count: u64, time: SystemTime, row: (usize, T)) -> Self {
+        let mut rows = self.rows.clone();
+        rows.push(row);
+        Self {
+            start: self.start,
+            start_time: self.start_time,
+            end_time: time,
+            element,
+            count,
+            rows,
+        }
+    }
+
+    fn end(&self) -> u64 {
+        self.start + self.rows.len() as u64 - 1
+    }
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq)]
+struct Partial<T: Data> {
+    next_row: u64,
+    // partial matches that can still consume more rows
+    runs: Vec<Run<T>>,
+    // the longest complete match found so far for each start row
+    candidates: Vec<Run<T>>,
+}
+
+impl<T: Data> Default for Partial<T> {
+    fn default() -> Self {
+        Self {
+            next_row: 0,
+            runs: vec![],
+            candidates: vec![],
+        }
+    }
+}
+
+/// Implements `MATCH_RECOGNIZE` by running a non-deterministic finite automaton over the rows of
+/// each key in event-time order.
+///
+/// Rows are buffered until the watermark passes them, and then fed to the automaton, which keeps
+/// every partial match in keyed state. Quantifiers are greedy, and after a match the search
+/// resumes at the row following it (`AFTER MATCH SKIP PAST LAST ROW`), so a match is only emitted
+/// once no partial match that starts at or before it can still succeed. A pattern ending in an
+/// unbounded quantifier is therefore only emitted once a row breaks it or `within` expires.
+#[derive(StreamNode)]
+pub struct MatchRecognize<K: Key, T: Data, OutT: Data> {
+    pattern: Vec<PatternElement>,
+    within: Option<Duration>,
+    // whether the row satisfies the DEFINE condition of the variable
+    define: fn(&T, usize) -> bool,
+    measures: fn(&K, &[(usize, T)]) -> OutT,
+    _t: PhantomData<K>,
+}
+
+#[process_fn(in_k = K, in_t = T, out_k = (), out_t = OutT)]
+impl<K: Key, T: Data, OutT: Data> MatchRecognize<K, T, OutT> {
+    fn name(&self) -> String {
+        "MatchRecognize".to_string()
+    }
+
+    pub fn new(
+        pattern: Vec<PatternElement>,
+        within: Option<Duration>,
+        define: fn(&T, usize) -> bool,
+        measures: fn(&K, &[(usize, T)]) -> OutT,
+    ) -> Self {
+        assert!(
+            !pattern.is_empty(),
+            "MATCH_RECOGNIZE pattern can't be empty"
+        );
+        Self {
+            pattern,
+            within,
+            define,
+            measures,
+            _t: PhantomData,
+        }
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        vec![
+            TableDescriptor {
+                name: "b".to_string(),
+                description: "match_recognize buffered rows".to_string(),
+                table_type: TableType::TimeKeyMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: 0,
+            },
+            TableDescriptor {
+                name: "p".to_string(),
+                description: "match_recognize partial matches".to_string(),
+                table_type: TableType::TimeKeyMap as i32,
+                // partial matches stay alive until a row breaks them, however old they are
+                delete_behavior: TableDeleteBehavior::None as i32,
+                write_behavior: TableWriteBehavior::DefaultWrites as i32,
+                retention_micros: 0,
+            },
+            TableDescriptor {
+                name: "e".to_string(),
+                description: "match_recognize partial match deadlines".to_string(),
+                table_type: TableType::TimeKeyMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: 0,
+            },
+        ]
+    }
+
+    fn is_complete(&self, run: &Run<T>) -> bool {
+        run.count >= self.pattern[run.element].min
+            && self.pattern[run.element + 1..].iter().all(|e| e.min == 0)
+    }
+
+    fn is_extendable(&self, run: &Run<T>) -> bool {
+        self.pattern[run.element]
+            .max
+            .map_or(true, |max| run.count < max)
+            || (run.count >= self.pattern[run.element].min && run.element + 1 < self.pattern.len())
+    }
+
+    fn is_expired(&self, run: &Run<T>, time: SystemTime) -> bool {
+        self.within
+            .map_or(false, |within| run.start_time + within < time)
+    }
+
+    // the partial matches that result from feeding the row to the run
+    fn advance(&self, run: &Run<T>, time: SystemTime, row: &T) -> Vec<Run<T>> {
+        let mut successors = vec![];
+        let current = &self.pattern[run.element];
+        if current.max.map_or(true, |max| run.count < max) && (self.define)(row, current.variable) {
+            successors.push(run.extend(
+                run.element,
+                run.count + 1,
+                time,
+                (current.variable, row.clone()),
+            ));
+        }
+
+        if run.count >= current.min {
+            // later elements may be skipped as long as they're optional
+            for (element, next) in self.pattern.iter().enumerate().skip(run.element + 1) {
+                if (self.define)(row, next.variable) {
+                    successors.push(run.extend(element, 1, time, (next.variable, row.clone())));
+                }
+                if next.min > 0 {
+                    break;
+                }
+            }
+        }
+        successors
+    }
+
+    fn process_row(&self, partial: &mut Partial<T>, time: SystemTime, row: &T) {
+        let fresh = Run {
+            start: partial.next_row,
+            start_time: time,
+            end_time: time,
+            element: 0,
+            count: 0,
+            rows: vec![],
+        };
+        partial.next_row += 1;
+
+        let mut runs: Vec<Run<T>> = vec![];
+        for run in partial.runs.drain(..).chain(std::iter::once(fresh)) {
+            for successor in self.advance(&run, time, row) {
+                if self.is_expired(&successor, time) {
+                    continue;
+                }
+                // runs in the same state have the same future, so only the first (greediest) is kept
+                if runs.iter().any(|r| {
+                    r.start == successor.start
+                        && r.element == successor.element
+                        && r.count == successor.count
+                }) {
+                    continue;
+                }
+                if self.is_complete(&successor) {
+                    match partial
+                        .candidates
+                        .iter_mut()
+                        .find(|c| c.start == successor.start)
+                    {
+                        Some(candidate) => {
+                            if candidate.rows.len() < successor.rows.len() {
+                                *candidate = successor.clone();
+                            }
+                        }
+                        None => partial.candidates.push(successor.clone()),
+                    }
+                }
+                if self.is_extendable(&successor) {
+                    runs.push(successor);
+                }
+            }
+        }
+        partial.runs = runs;
+    }
+
+    // removes the matches that can no longer be replaced by a longer or earlier one
+    fn take_matches(&self, partial: &mut Partial<T>) -> Vec<Run<T>> {
+        let mut matches = vec![];
+        loop {
+            let Some(earliest) = partial
+                .runs
+                .iter()
+                .chain(partial.candidates.iter())
+                .map(|run| run.start)
+                .min()
+            else {
+                break;
+            };
+            if partial.runs.iter().any(|run| run.start == earliest) {
+                break;
+            }
+            let index = partial
+                .candidates
+                .iter()
+                .position(|c| c.start == earliest)
+                .unwrap();
+            let matched = partial.candidates.remove(index);
+            let end = matched.end();
+            partial.runs.retain(|run| run.start > end);
+            partial.candidates.retain(|c| c.start > end);
+            matches.push(matched);
+        }
+        matches
+    }
+
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), OutT>) {
+        let watermark = ctx.last_present_watermark();
+        if let Some(watermark) = watermark {
+            if record.timestamp < watermark {
+                return;
+            }
+        };
+
+        // rows are matched in event-time order, so they wait for the watermark
+        let mut key = record.key.clone().unwrap();
+        let mut state: TimeKeyMap<K, Vec<T>, _> = ctx.state.get_time_key_map('b', watermark).await;
+        let mut rows = state
+            .get(record.timestamp, &mut key)
+            .await
+            .cloned()
+            .unwrap_or_default();
+        rows.push(record.value.clone());
+        state.insert(record.timestamp, key, rows);
+    }
+
+    async fn handle_watermark(&mut self, watermark: Watermark, ctx: &mut Context<(), OutT>) {
+        if let Watermark::EventTime(watermark) = watermark {
+            let mut ready = vec![];
+            {
+                let mut state: TimeKeyMap<K, Vec<T>, _> =
+                    ctx.state.get_time_key_map('b', Some(watermark)).await;
+                while let Some(time) = state.get_min_time() {
+                    if time >= watermark {
+                        break;
+                    }
+                    for (key, rows) in state.evict_for_timestamp(time).await {
+                        ready.push((time, key, rows));
+                    }
+                }
+            }
+
+            // the keys that had a partial match whose deadline has passed, which can't consume any
+            // future row
+            let mut expired = vec![];
+            if self.within.is_some() {
+                let mut state: TimeKeyMap<K, (), _> =
+                    ctx.state.get_time_key_map('e', Some(watermark)).await;
+                while let Some(time) = state.get_min_time() {
+                    if time >= watermark {
+                        break;
+                    }
+                    for (key, _) in state.evict_for_timestamp(time).await {
+                        expired.push(key);
+                    }
+                }
+            }
+
+            let mut records = vec![];
+            let mut deadlines = vec![];
+            {
+                let mut state: KeyedState<K, Partial<T>, _> = ctx.state.get_key_state('p').await;
+
+                let mut touched: HashMap<K, Partial<T>> = HashMap::new();
+                for (time, mut key, rows) in ready {
+                    if !touched.contains_key(&key) {
+                        let partial = state.get(&mut key).await.cloned().unwrap_or_default();
+                        touched.insert(key.clone(), partial);
+                    }
+                    let partial = touched.get_mut(&key).unwrap();
+                    for row in rows {
+                        self.process_row(partial, time, &row);
+                    }
+                }
+
+                // a key's deadline may be stale if it has been touched since, in which case this
+                // finds nothing to expire
+                for mut key in expired {
+                    if !touched.contains_key(&key) {
+                        if let Some(partial) = state.get(&mut key).await.cloned() {
+                            touched.insert(key, partial);
+                        }
+                    }
+                }
+
+                for (mut key, mut partial) in touched {
+                    partial.runs.retain(|run| !self.is_expired(run, watermark));
+                    for matched in self.take_matches(&mut partial) {
+                        records.push(Record {
+                            timestamp: matched.end_time,
+                            key: None,
+                            value: (self.measures)(&key, &matched.rows),
+                        });
+                    }
+
+                    if let Some(within) = self.within {
+                        if let Some(start_time) =
+                            partial.runs.iter().map(|run| run.start_time).min()
+                        {
+                            deadlines.push((start_time + within, key.clone()));
+                        }
+                    }
+
+                    if partial.runs.is_empty() && partial.candidates.is_empty() {
+                        state.remove(&mut key).await;
+                    } else {
+                        state.insert(watermark, key, partial).await;
+                    }
+                }
+            }
+
+            if !deadlines.is_empty() {
+                let mut state: TimeKeyMap<K, (), _> =
+                    ctx.state.get_time_key_map('e', Some(watermark)).await;
+                for (deadline, key) in deadlines {
+                    state.insert(deadline, key, ());
+                }
+            }
+
+            for record in records {
+                ctx.collect(record).await;
+            }
+        }
+
+        ctx.broadcast(arroyo_types::Message::Watermark(watermark))
+            .await;
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _checkpoint_barrier: &arroyo_types::CheckpointBarrier,
+        ctx: &mut Context<(), OutT>,
+    ) {
+        let mut state: TimeKeyMap<K, Vec<T>, _> = ctx
+            .state
+            .get_time_key_map('b', ctx.last_present_watermark())
+            .await;
+        state.flush().await;
+
+        let mut state: TimeKeyMap<K, (), _> = ctx
+            .state
+            .get_time_key_map('e', ctx.last_present_watermark())
+            .await;
+        state.flush().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    // rows are login attempts, and the variables are 0 for failures and 1 for successes
+    fn is_variable(row: &bool, variable: usize) -> bool {
+        *row == (variable == 1)
+    }
+
+    fn count(_: &(), rows: &[(usize, bool)]) -> usize {
+        rows.len()
+    }
+
+    fn run(operator: &MatchRecognize<(), bool, usize>, rows: &[bool]) -> Vec<Vec<usize>> {
+        let mut partial = Partial::default();
+        let mut matches = vec![];
+        for (i, row) in rows.iter().enumerate() {
+            operator.process_row(&mut partial, secs(i as u64), row);
+            matches.extend(
+                operator
+                    .take_matches(&mut partial)
+                    .into_iter()
+                    .map(|m| m.rows.iter().map(|(variable, _)| *variable).collect()),
+            );
+        }
+        matches
+    }
+
+    #[test]
+    fn test_fixed_repetitions() {
+        // PATTERN (F{3} S)
+        let operator = MatchRecognize::new(
+            vec![
+                PatternElement {
+                    variable: 0,
+                    min: 3,
+                    max: Some(3),
+                },
+                PatternElement {
+                    variable: 1,
+                    min: 1,
+                    max: Some(1),
+                },
+            ],
+            None,
+            is_variable,
+            count,
+        );
+
+        let matches = run(
+            &operator,
+            &[false, false, true, false, false, false, false, true],
+        );
+        assert_eq!(vec![vec![0, 0, 0, 1]], matches);
+    }
+
+    #[test]
+    fn test_greedy_quantifier() {
+        // PATTERN (F+ S)
+        let operator = MatchRecognize::new(
+            vec![
+                PatternElement {
+                    variable: 0,
+                    min: 1,
+                    max: None,
+                },
+                PatternElement {
+                    variable: 1,
+                    min: 1,
+                    max: Some(1),
+                },
+            ],
+            None,
+            is_variable,
+            count,
+        );
+
+        let matches = run(&operator, &[false, false, true, true, false, true]);
+        assert_eq!(vec![vec![0, 0, 1], vec![0, 1]], matches);
+    }
+}
@@ -19,13 +19,20 @@ use wasmtime::{
     Caller, Engine, InstanceAllocationStrategy, Linker, Module, PoolingAllocationConfig, Store,
     TypedFunc,
 };
+pub mod accumulators;
 pub mod aggregating_window;
+pub mod cumulating_aggregating_window;
 pub mod functions;
+pub mod interval_join;
 pub mod join_with_expiration;
 pub mod joiners;
 pub mod joins;
+pub mod lookup_join;
+pub mod match_recognize;
+pub mod session_aggregating_window;
 pub mod sinks;
 pub mod sliding_top_n_aggregating_window;
+pub mod temporal_join;
 pub mod tumbling_aggregating_window;
 pub mod tumbling_top_n_window;
 pub mod updating_aggregate;
@@ -38,7 +45,7 @@ mod test {
     use arroyo_types::{from_millis, to_millis, Message, Record};
     use std::time::{Duration, SystemTime};
 
-    use super::SlidingWindowAssigner;
+    use super::{CumulatingWindowAssigner, SlidingWindowAssigner};
 
     #[tokio::test]
     #[ignore]
@@ -93,6 +100,29 @@ mod test {
             <SlidingWindowAssigner as TimeWindowAssigner<(), ()>>::windows(&assigner, start).len()
         );
     }
+
+    #[test]
+    fn test_cumulating_window_assignment() {
+        let assigner = CumulatingWindowAssigner {
+            step: Duration::from_secs(1),
+            max_size: Duration::from_secs(4),
+        };
+        let windows = <CumulatingWindowAssigner as TimeWindowAssigner<(), ()>>::windows(
+            &assigner,
+            from_millis(5_500),
+        );
+        assert_eq!(
+            vec![(4_000, 6_000), (4_000, 7_000), (4_000, 8_000)],
+            windows
+                .iter()
+                .map(|w| (to_millis(w.start), to_millis(w.end)))
+                .collect::<Vec<_>>()
+        );
+
+        let last = windows[2];
+        let next = <CumulatingWindowAssigner as TimeWindowAssigner<(), ()>>::next(&assigner, last);
+        assert_eq!((8_000, 9_000), (to_millis(next.start), to_millis(next.end)));
+    }
 }
 
 #[derive(Encode, Decode, Copy, Clone, Debug, PartialEq)]
@@ -164,15 +194,15 @@ impl<K: Key, D: Data> PeriodicWatermarkGenerator<K, D> {
     }
 
     async fn on_start(&mut self, ctx: &mut Context<K, D>) {
-        let gs = ctx.state.get_global_keyed_state('s').await;
+        let mut gs = ctx.state.get_global_keyed_state('s').await;
         self.last_event = SystemTime::now();
 
-        let state =
-            *(gs.get(&ctx.task_info.task_index)
-                .unwrap_or(&PeriodicWatermarkGeneratorState {
-                    last_watermark_emitted_at: SystemTime::UNIX_EPOCH,
-                    max_watermark: SystemTime::UNIX_EPOCH,
-                }));
+        let state = *(gs.get(&ctx.task_info.task_index).await.unwrap_or(
+            &PeriodicWatermarkGeneratorState {
+                last_watermark_emitted_at: SystemTime::UNIX_EPOCH,
+                max_watermark: SystemTime::UNIX_EPOCH,
+            },
+        ));
 
         self.state_cache = state;
     }
@@ -345,6 +375,52 @@ impl<K: Key, T: Data> TimeWindowAssigner<K, T> for SlidingWindowAssigner {
     }
 }
 
+/// Assigns rows to the expanding windows that start every `max_size` and grow by `step` until
+/// they reach it, so a row is in every window of its period that ends after it.
+#[derive(Copy, Clone)]
+pub struct CumulatingWindowAssigner {
+    step: Duration,
+    max_size: Duration,
+}
+
+impl<K: Key, T: Data> TimeWindowAssigner<K, T> for CumulatingWindowAssigner {
+    fn windows(&self, ts: SystemTime) -> Vec<Window> {
+        let ts_millis = to_millis(ts);
+        let step = self.step.as_millis() as u64;
+        let start = ts_millis - ts_millis % (self.max_size.as_millis() as u64);
+        let last_end = start + self.max_size.as_millis() as u64;
+
+        let mut end = ts_millis - ts_millis % step + step;
+        let mut windows = vec![];
+        while end <= last_end {
+            windows.push(Window {
+                start: from_millis(start),
+                end: from_millis(end),
+            });
+            end += step;
+        }
+        windows
+    }
+
+    fn next(&self, window: Window) -> Window {
+        if window.size() >= self.max_size {
+            Window {
+                start: window.end,
+                end: window.end + self.step,
+            }
+        } else {
+            Window {
+                start: window.start,
+                end: window.end + self.step,
+            }
+        }
+    }
+
+    fn safe_retention_duration(&self) -> Option<Duration> {
+        Some(self.max_size)
+    }
+}
+
 struct WasmOperatorEnv<K: Key, T: Data> {
     //ctx: Arc<Mutex<Option<Context<K, T>>>>,
     ctx: Option<Collector<K, T>>,
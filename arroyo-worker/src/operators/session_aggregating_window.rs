@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime};
+
+use crate::engine::{Context, StreamNode};
+use arroyo_macro::process_fn;
+use arroyo_rpc::grpc::{TableDeleteBehavior, TableDescriptor, TableType, TableWriteBehavior};
+use arroyo_state::tables::keyed_map::KeyedState;
+use arroyo_types::*;
+
+/// The open sessions of a key, sorted by start and non-overlapping, each with the partial
+/// aggregate of its rows.
+type Sessions<BinA> = Vec<(Window, BinA)>;
+
+/// A session window aggregator that keeps a single partial aggregate per open session, rather
+/// than every row, so its state is proportional to the number of sessions.
+///
+/// Sessions are extended by `gap` past their latest row, and are merged when a row bridges two
+/// of them. If `max_length` is set, no session grows beyond it: rows after its limit start a new
+/// session. Each session is emitted by a timer at its end.
+#[derive(StreamNode)]
+pub struct SessionAggregatingWindowFunc<K: Key, T: Data, BinA: Data, OutT: Data> {
+    gap: Duration,
+    max_length: Option<Duration>,
+    aggregator: fn(&K, Window, &BinA) -> OutT,
+    bin_merger: fn(&T, Option<&BinA>) -> BinA,
+    bin_combiner: fn(&BinA, Option<&BinA>) -> BinA,
+}
+
+/// The timers that adding a row to a key's sessions requires, as session ends.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SessionTimers {
+    cancel: Vec<SystemTime>,
+    schedule: Vec<SystemTime>,
+}
+
+// adds a row at `timestamp`, whose partial aggregate is `bin`, to the sessions of its key
+fn add_to_sessions<BinA: Clone>(
+    sessions: &mut Sessions<BinA>,
+    timestamp: SystemTime,
+    gap: Duration,
+    max_length: Option<Duration>,
+    bin: BinA,
+    combine: impl Fn(&BinA, Option<&BinA>) -> BinA,
+) -> SessionTimers {
+    let too_long = |window: &Window| max_length.map_or(false, |max| window.size() > max);
+    let previous_ends: HashSet<SystemTime> = sessions.iter().map(|(w, _)| w.end).collect();
+
+    let i = match sessions.iter().position(|(w, _)| w.contains(timestamp)) {
+        Some(i) => {
+            let (window, current) = &mut sessions[i];
+            *current = combine(&bin, Some(&*current));
+            let mut end = timestamp + gap;
+            if let Some(max_length) = max_length {
+                end = end.min(window.start + max_length);
+            }
+            window.end = window.end.max(end);
+            i
+        }
+        None => {
+            let i = sessions.partition_point(|(w, _)| w.start < timestamp);
+            let length = max_length.map_or(gap, |max_length| gap.min(max_length));
+            sessions.insert(i, (Window::new(timestamp, timestamp + length), bin));
+            i
+        }
+    };
+
+    // the session may now reach the ones after it, which it absorbs unless that would make it
+    // too long, in which case it ends where the next one starts
+    while i + 1 < sessions.len() && sessions[i].0.end > sessions[i + 1].0.start {
+        let (next_window, next_bin) = sessions[i + 1].clone();
+        let merged = Window::new(sessions[i].0.start, sessions[i].0.end.max(next_window.end));
+        if !too_long(&merged) {
+            sessions[i].1 = combine(&next_bin, Some(&sessions[i].1));
+            sessions[i].0 = merged;
+            sessions.remove(i + 1);
+        } else {
+            sessions[i].0.end = next_window.start;
+            break;
+        }
+    }
+
+    let ends: HashSet<SystemTime> = sessions.iter().map(|(w, _)| w.end).collect();
+    let mut timers = SessionTimers {
+        cancel: previous_ends.difference(&ends).copied().collect(),
+        schedule: ends.difference(&previous_ends).copied().collect(),
+    };
+    timers.cancel.sort();
+    timers.schedule.sort();
+    timers
+}
+
+#[process_fn(in_k = K, in_t = T, out_k = K, out_t = OutT, timer_t = Window)]
+impl<K: Key, T: Data, BinA: Data, OutT: Data> SessionAggregatingWindowFunc<K, T, BinA, OutT> {
+    fn name(&self) -> String {
+        "SessionAggregatingWindow".to_string()
+    }
+
+    pub fn new(
+        gap: Duration,
+        max_length: Option<Duration>,
+        aggregator: fn(&K, Window, &BinA) -> OutT,
+        bin_merger: fn(&T, Option<&BinA>) -> BinA,
+        bin_combiner: fn(&BinA, Option<&BinA>) -> BinA,
+    ) -> Self {
+        SessionAggregatingWindowFunc {
+            gap,
+            max_length,
+            aggregator,
+            bin_merger,
+            bin_combiner,
+        }
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        vec![TableDescriptor {
+            name: "s".to_string(),
+            description: "session aggregates".to_string(),
+            table_type: TableType::TimeKeyMap as i32,
+            delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+            write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+            retention_micros: 0,
+        }]
+    }
+
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<K, OutT>) {
+        if let Some(watermark) = ctx.last_present_watermark() {
+            if record.timestamp <= watermark {
+                // drop late data
+                return;
+            }
+        }
+
+        let mut key = record.key.clone().unwrap();
+        let mut sessions: Sessions<BinA> = {
+            let mut state: KeyedState<'_, K, Sessions<BinA>, _> =
+                ctx.state.get_key_state('s').await;
+            state.get(&mut key).await.cloned().unwrap_or_default()
+        };
+
+        let timers = add_to_sessions(
+            &mut sessions,
+            record.timestamp,
+            self.gap,
+            self.max_length,
+            (self.bin_merger)(&record.value, None),
+            self.bin_combiner,
+        );
+
+        for end in timers.cancel {
+            let _: Option<Window> = ctx.cancel_timer(&mut key, end).await;
+        }
+        for end in timers.schedule {
+            // the timer's window only carries the end, as the start of a session can change
+            ctx.schedule_timer(&mut key, end, Window::new(SystemTime::UNIX_EPOCH, end))
+                .await;
+        }
+
+        let last_end = sessions.last().unwrap().0.end;
+        ctx.state
+            .get_key_state('s')
+            .await
+            .insert(last_end, key, sessions)
+            .await;
+    }
+
+    async fn handle_timer(&mut self, mut key: K, window: Window, ctx: &mut Context<K, OutT>) {
+        let (window, bin) = {
+            let mut state: KeyedState<'_, K, Sessions<BinA>, _> =
+                ctx.state.get_key_state('s').await;
+            let mut sessions = state
+                .get(&mut key)
+                .await
+                .cloned()
+                .expect("there must be a session for this key in state");
+            let i = sessions
+                .iter()
+                .position(|(w, _)| w.end == window.end)
+                .expect("this session must be in state");
+            let session = sessions.remove(i);
+
+            match sessions.last() {
+                Some((last, _)) => {
+                    let last_end = last.end;
+                    state.insert(last_end, key.clone(), sessions).await;
+                }
+                None => {
+                    state.remove(&mut key).await;
+                }
+            }
+            session
+        };
+
+        let value = (self.aggregator)(&key, window, &bin);
+        ctx.collect(Record {
+            timestamp: window.end - Duration::from_nanos(1),
+            key: Some(key),
+            value,
+        })
+        .await;
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _checkpoint_barrier: &arroyo_types::CheckpointBarrier,
+        ctx: &mut Context<K, OutT>,
+    ) {
+        ctx.flush_timers::<Window>().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arroyo_types::from_millis;
+
+    fn add(sessions: &mut Sessions<u64>, millis: u64, max_length: Option<u64>) -> SessionTimers {
+        add_to_sessions(
+            sessions,
+            from_millis(millis),
+            Duration::from_millis(100),
+            max_length.map(Duration::from_millis),
+            1,
+            |new, current| new + current.copied().unwrap_or_default(),
+        )
+    }
+
+    fn window(start: u64, end: u64) -> Window {
+        Window::new(from_millis(start), from_millis(end))
+    }
+
+    #[test]
+    fn test_extend_and_merge() {
+        let mut sessions = vec![];
+        assert_eq!(
+            SessionTimers {
+                cancel: vec![],
+                schedule: vec![from_millis(100)],
+            },
+            add(&mut sessions, 0, None)
+        );
+        add(&mut sessions, 150, None);
+        assert_eq!(vec![(window(0, 100), 1), (window(150, 250), 1)], sessions);
+
+        // extending the first session makes it reach the second, so the two are merged
+        assert_eq!(
+            SessionTimers {
+                cancel: vec![from_millis(100)],
+                schedule: vec![],
+            },
+            add(&mut sessions, 90, None)
+        );
+        assert_eq!(vec![(window(0, 250), 3)], sessions);
+    }
+
+    #[test]
+    fn test_max_length() {
+        let mut sessions = vec![];
+        add(&mut sessions, 0, Some(150));
+        add(&mut sessions, 90, Some(150));
+        assert_eq!(vec![(window(0, 150), 2)], sessions);
+
+        // the session can't grow past its maximum length, so this row starts a new one
+        add(&mut sessions, 160, Some(150));
+        assert_eq!(vec![(window(0, 150), 2), (window(160, 260), 1)], sessions);
+
+        // a session that would be too long if merged with the next one ends where it starts
+        let mut sessions = vec![];
+        add(&mut sessions, 0, Some(150));
+        add(&mut sessions, 200, Some(150));
+        add(&mut sessions, 120, Some(150));
+        assert_eq!(
+            vec![
+                (window(0, 100), 1),
+                (window(120, 200), 1),
+                (window(200, 300), 1)
+            ],
+            sessions
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::{marker::PhantomData, time::SystemTime};
+
+use arroyo_macro::{co_process_fn, StreamNode};
+use arroyo_rpc::grpc::{TableDeleteBehavior, TableDescriptor, TableType, TableWriteBehavior};
+use arroyo_state::tables::{keyed_map::KeyedState, time_key_map::TimeKeyMap};
+use arroyo_types::*;
+
+use crate::engine::Context;
+use crate::operators::join_with_expiration::{Coercer, IncomingDataProcessor, NoOpProcessor};
+
+/// An event-time temporal join, which joins each left row against the version of the right row
+/// with the same key that was valid at the left row's timestamp.
+///
+/// Left rows and right updates are buffered by their time until the watermark passes them, at
+/// which point every version they could be joined against has arrived. Right updates are then
+/// applied in time order, and only the latest version of each key is kept, no matter how old it
+/// is. The output is append-only, even when the right side is an updating stream.
+#[derive(StreamNode)]
+pub struct TemporalJoin<
+    K: Key,
+    T1: Data,
+    InT2: Data,
+    P2: IncomingDataProcessor<InT2, T2>,
+    T2: Data,
+    Output: Data,
+> {
+    emit: fn(T1, Option<T2>) -> Option<Output>,
+    // right updates that arrived behind the watermark since the last one was reported
+    late_updates: usize,
+    _t: PhantomData<(K, InT2, P2)>,
+}
+
+/// Joins the left rows that the watermark has passed, in time order, applying the right updates
+/// before the watermark as their times are reached. `current` holds the latest version of the
+/// keys of the left rows (`None` if there isn't one). Returns the joined rows and the version
+/// that each updated key ends up with, along with its time.
+fn join_ready<K: Key, T1, T2: Clone>(
+    ready: Vec<(SystemTime, K, Vec<T1>)>,
+    updates: Vec<(SystemTime, K, Option<T2>)>,
+    current: &mut HashMap<K, Option<T2>>,
+) -> (
+    Vec<(SystemTime, K, T1, Option<T2>)>,
+    HashMap<K, (SystemTime, Option<T2>)>,
+) {
+    let mut joined = vec![];
+    let mut updated = HashMap::new();
+    let mut updates = updates.into_iter().peekable();
+
+    for (time, key, rows) in ready {
+        while let Some((version_time, key, version)) =
+            updates.next_if(|(version_time, _, _)| *version_time <= time)
+        {
+            current.insert(key.clone(), version.clone());
+            updated.insert(key, (version_time, version));
+        }
+
+        let version = current.get(&key).cloned().flatten();
+        joined.extend(
+            rows.into_iter()
+                .map(|row| (time, key.clone(), row, version.clone())),
+        );
+    }
+
+    for (version_time, key, version) in updates {
+        updated.insert(key, (version_time, version));
+    }
+
+    (joined, updated)
+}
+
+#[co_process_fn(in_k1=K, in_t1=T1, in_k2=K, in_t2=InT2, out_k=K, out_t=Output)]
+impl<K: Key, T1: Data, InT2: Data, P2: IncomingDataProcessor<InT2, T2>, T2: Data, Output: Data>
+    TemporalJoin<K, T1, InT2, P2, T2, Output>
+{
+    fn name(&self) -> String {
+        "TemporalJoin".to_string()
+    }
+
+    pub fn new(emit: fn(T1, Option<T2>) -> Option<Output>) -> Self {
+        Self {
+            emit,
+            late_updates: 0,
+            _t: PhantomData,
+        }
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        vec![
+            TableDescriptor {
+                name: "l".to_string(),
+                description: "temporal join pending left rows".to_string(),
+                table_type: TableType::TimeKeyMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: 0,
+            },
+            TableDescriptor {
+                name: "u".to_string(),
+                description: "temporal join pending right updates".to_string(),
+                table_type: TableType::TimeKeyMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: 0,
+            },
+            TableDescriptor {
+                name: "r".to_string(),
+                description: "temporal join right versions".to_string(),
+                table_type: TableType::TimeKeyMap as i32,
+                // the latest version of a key must be kept no matter how old it is
+                delete_behavior: TableDeleteBehavior::None as i32,
+                write_behavior: TableWriteBehavior::DefaultWrites as i32,
+                retention_micros: 0,
+            },
+        ]
+    }
+
+    async fn process_left(&mut self, record: &Record<K, T1>, ctx: &mut Context<K, Output>) {
+        let watermark = ctx.last_present_watermark();
+        if let Some(watermark) = watermark {
+            if record.timestamp < watermark {
+                return;
+            }
+        };
+
+        let mut key = record.key.clone().unwrap();
+        let mut state: TimeKeyMap<K, Vec<T1>, _> = ctx.state.get_time_key_map('l', watermark).await;
+        let mut rows = state
+            .get(record.timestamp, &mut key)
+            .await
+            .cloned()
+            .unwrap_or_default();
+        rows.push(record.value.clone());
+        state.insert(record.timestamp, key, rows);
+    }
+
+    async fn process_right(&mut self, record: &Record<K, InT2>, ctx: &mut Context<K, Output>) {
+        let watermark = ctx.last_present_watermark();
+        if let Some(watermark) = watermark {
+            if record.timestamp < watermark {
+                // left rows after the watermark may already have been joined against the version
+                // this would have replaced
+                self.late_updates += 1;
+                return;
+            }
+        };
+
+        let version = match P2::ensure_updating(record.value.clone()) {
+            UpdatingData::Append(value) | UpdatingData::Update { new: value, .. } => Some(value),
+            UpdatingData::Retract(_) => None,
+        };
+
+        let key = record.key.clone().unwrap();
+        let mut state: TimeKeyMap<K, Option<T2>, _> =
+            ctx.state.get_time_key_map('u', watermark).await;
+        state.insert(record.timestamp, key, version);
+    }
+
+    async fn handle_watermark(&mut self, watermark: Watermark, ctx: &mut Context<K, Output>) {
+        if let Watermark::EventTime(watermark) = watermark {
+            if self.late_updates > 0 {
+                ctx.report_user_error(UserError::new(
+                    "Late temporal join updates",
+                    format!(
+                        "{} updates to the versioned side of a temporal join arrived behind the \
+                        watermark and were dropped",
+                        self.late_updates
+                    ),
+                ))
+                .await;
+                self.late_updates = 0;
+            }
+
+            // left rows and right updates before the watermark can no longer receive earlier
+            // versions
+            let mut ready = vec![];
+            {
+                let mut state: TimeKeyMap<K, Vec<T1>, _> =
+                    ctx.state.get_time_key_map('l', Some(watermark)).await;
+                while let Some(time) = state.get_min_time() {
+                    if time >= watermark {
+                        break;
+                    }
+                    for (key, rows) in state.evict_for_timestamp(time).await {
+                        ready.push((time, key, rows));
+                    }
+                }
+            }
+
+            let mut updates = vec![];
+            {
+                let mut state: TimeKeyMap<K, Option<T2>, _> =
+                    ctx.state.get_time_key_map('u', Some(watermark)).await;
+                while let Some(time) = state.get_min_time() {
+                    if time >= watermark {
+                        break;
+                    }
+                    for (key, version) in state.evict_for_timestamp(time).await {
+                        updates.push((time, key, version));
+                    }
+                }
+            }
+
+            let mut records = vec![];
+            {
+                let mut state: KeyedState<K, T2, _> = ctx.state.get_key_state('r').await;
+                let mut current = HashMap::new();
+                let keys: Vec<K> = ready.iter().map(|(_, key, _)| key.clone()).collect();
+                for mut key in keys {
+                    if !current.contains_key(&key) {
+                        let version = state.get(&mut key).await.cloned();
+                        current.insert(key, version);
+                    }
+                }
+
+                let (joined, updated) = join_ready(ready, updates, &mut current);
+                for (time, key, row, version) in joined {
+                    if let Some(value) = (self.emit)(row, version) {
+                        records.push(Record {
+                            timestamp: time,
+                            key: Some(key),
+                            value,
+                        });
+                    }
+                }
+
+                for (mut key, (time, version)) in updated {
+                    match version {
+                        Some(version) => state.insert(time, key, version).await,
+                        None => state.remove(&mut key).await,
+                    }
+                }
+            }
+
+            for record in records {
+                ctx.collect(record).await;
+            }
+        }
+
+        ctx.broadcast(arroyo_types::Message::Watermark(watermark))
+            .await;
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _checkpoint_barrier: &arroyo_types::CheckpointBarrier,
+        ctx: &mut Context<K, Output>,
+    ) {
+        let watermark = ctx.last_present_watermark();
+        let mut state: TimeKeyMap<K, Vec<T1>, _> = ctx.state.get_time_key_map('l', watermark).await;
+        state.flush().await;
+        let mut state: TimeKeyMap<K, Option<T2>, _> =
+            ctx.state.get_time_key_map('u', watermark).await;
+        state.flush().await;
+    }
+}
+
+fn inner<T1: Data, T2: Data>(left: T1, right: Option<T2>) -> Option<UpdatingData<(T1, T2)>> {
+    right.map(|right| UpdatingData::Append((left, right)))
+}
+
+fn left<T1: Data, T2: Data>(left: T1, right: Option<T2>) -> Option<UpdatingData<(T1, Option<T2>)>> {
+    Some(UpdatingData::Append((left, right)))
+}
+
+pub fn inner_join<K: Key, T1: Data, T2: Data>(
+) -> TemporalJoin<K, T1, T2, Coercer<T2>, T2, UpdatingData<(T1, T2)>> {
+    TemporalJoin::new(inner)
+}
+
+pub fn inner_join_right_updating<K: Key, T1: Data, T2: Data>(
+) -> TemporalJoin<K, T1, UpdatingData<T2>, NoOpProcessor<T2>, T2, UpdatingData<(T1, T2)>> {
+    TemporalJoin::new(inner)
+}
+
+pub fn left_join<K: Key, T1: Data, T2: Data>(
+) -> TemporalJoin<K, T1, T2, Coercer<T2>, T2, UpdatingData<(T1, Option<T2>)>> {
+    TemporalJoin::new(left)
+}
+
+pub fn left_join_right_updating<K: Key, T1: Data, T2: Data>(
+) -> TemporalJoin<K, T1, UpdatingData<T2>, NoOpProcessor<T2>, T2, UpdatingData<(T1, Option<T2>)>> {
+    TemporalJoin::new(left)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn time(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_join_ready() {
+        let ready = vec![
+            (time(2), 1, vec!["l1"]),
+            (time(4), 2, vec!["l2"]),
+            (time(5), 1, vec!["l3", "l4"]),
+            (time(6), 1, vec!["l5"]),
+        ];
+        let updates = vec![
+            (time(1), 2, Some("b")),
+            (time(3), 1, Some("c")),
+            (time(5), 1, None),
+            (time(7), 3, Some("d")),
+        ];
+        let mut current = HashMap::from([(1, Some("a")), (2, None)]);
+
+        let (joined, updated) = join_ready(ready, updates, &mut current);
+        assert_eq!(
+            vec![
+                (time(2), 1, "l1", Some("a")),
+                (time(4), 2, "l2", Some("b")),
+                (time(5), 1, "l3", None),
+                (time(5), 1, "l4", None),
+                (time(6), 1, "l5", None),
+            ],
+            joined
+        );
+        assert_eq!(
+            HashMap::from([
+                (1, (time(5), None)),
+                (2, (time(1), Some("b"))),
+                (3, (time(7), Some("d"))),
+            ]),
+            updated
+        );
+    }
+}
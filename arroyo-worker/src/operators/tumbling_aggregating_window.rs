@@ -91,7 +91,7 @@ impl<K: Key, T: Data, BinA: Data, OutT: Data> TumblingAggregatingWindowFunc<K, T
             .await;
 
         let mut key = record.key.clone().unwrap();
-        let bin_aggregate = aggregating_map.get(bin_start, &mut key);
+        let bin_aggregate = aggregating_map.get(bin_start, &mut key).await;
         let new_value = (self.bin_merger)(&record.value, bin_aggregate);
         aggregating_map.insert(bin_start, key, new_value);
     }
@@ -142,7 +142,7 @@ impl<K: Key, T: Data, BinA: Data, OutT: Data> TumblingAggregatingWindowFunc<K, T
 
         let window_end = self.window_end(bin_start);
         let mut records = vec![];
-        for (key, value) in aggregating_map.evict_for_timestamp(bin_start) {
+        for (key, value) in aggregating_map.evict_for_timestamp(bin_start).await {
             records.push(Record {
                 timestamp: window_end,
                 key: Some(key.clone()),
@@ -71,7 +71,7 @@ impl<K: Key, T: Data, BinA: Data, OutT: Data> UpdatingAggregateOperator<K, T, Bi
         let mut mut_key = record.key.clone().unwrap();
         let key = mut_key.clone();
         let (new_value, state_op) = {
-            let bin_aggregate = aggregating_map.get(&mut mut_key);
+            let bin_aggregate = aggregating_map.get(&mut mut_key).await;
             match bin_aggregate {
                 Some(bin_aggregate) => {
                     let old_aggregate = (self.aggregator)(&key, bin_aggregate);
@@ -9,11 +9,12 @@ use arroyo_types::*;
 use std::time::Duration;
 
 use super::{
-    InstantWindowAssigner, SlidingWindowAssigner, TimeWindowAssigner, TumblingWindowAssigner,
+    CumulatingWindowAssigner, InstantWindowAssigner, SlidingWindowAssigner, TimeWindowAssigner,
+    TumblingWindowAssigner,
 };
 
 // Enforce a maximum session size to prevent unbounded state growth until we are able to
-// delete from parquet state.
+// delete from parquet state, unless the query sets a maximum of its own.
 const MAX_SESSION_SIZE: Duration = Duration::from_secs(24 * 60 * 60); // 1 day
 
 pub mod aggregators {
@@ -132,6 +133,18 @@ impl<K: Key, T: Data, OutT: Data, W: TimeWindowAssigner<K, T>> KeyedWindowFunc<K
             _phantom: PhantomData,
         }
     }
+    pub fn cumulating_window(
+        step: Duration,
+        max_size: Duration,
+        operation: WindowOperation<K, T, OutT>,
+    ) -> KeyedWindowFunc<K, T, OutT, CumulatingWindowAssigner> {
+        KeyedWindowFunc {
+            assigner: CumulatingWindowAssigner { step, max_size },
+            operation,
+            _phantom: PhantomData,
+        }
+    }
+
     pub fn instant_window(
         operation: WindowOperation<K, T, OutT>,
     ) -> KeyedWindowFunc<K, T, OutT, InstantWindowAssigner> {
@@ -209,12 +222,14 @@ impl<K: Key, T: Data, OutT: Data, W: TimeWindowAssigner<K, T>> KeyedWindowFunc<K
 pub struct SessionWindowFunc<K: Key, T: Data, OutT: Data> {
     operation: WindowOperation<K, T, OutT>,
     gap_size: Duration,
+    max_size: Duration,
     _t: PhantomData<K>,
 }
 
 struct WindowGroup {
     windows: Vec<Window>,
     gap_size: Duration,
+    max_size: Duration,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -227,13 +242,13 @@ impl WindowGroup {
     fn handle_new_window(&mut self, mut new: Window) -> Option<SystemTime> {
         // look for an existing window to extend forward, unless it would be too long
         if let Some(w) = self.windows.iter_mut().find(|w| w.contains(new.end)) {
-            if Window::new(new.start, w.end).size() < MAX_SESSION_SIZE {
+            if Window::new(new.start, w.end).size() < self.max_size {
                 w.start = new.start;
                 None
             } else {
-                // if the merged window would be too long, we will extend the new window to MAX_SESSION_SIZE
+                // if the merged window would be too long, we will extend the new window to the max size
                 // and shorten the overlapping one
-                new.end = new.start + MAX_SESSION_SIZE;
+                new.end = new.start + self.max_size;
                 w.start = new.end;
                 self.windows.push(new);
                 Some(new.end)
@@ -255,7 +270,7 @@ impl WindowGroup {
                 .map(|(i, w)| (i, *w))
             {
                 // there's an existing window this record falls into
-                let new_window = window.extend(timestamp + self.gap_size, MAX_SESSION_SIZE);
+                let new_window = window.extend(timestamp + self.gap_size, self.max_size);
                 if window != new_window {
                     // we're extending an existing window
                     self.windows.remove(i);
@@ -301,11 +316,11 @@ impl WindowGroup {
 
     // ensures we've followed the two rules of session windows:
     //  1. Windows must not overlap
-    //  2. Windows must not be longer than MAX_SESSION_SIZE
+    //  2. Windows must not be longer than the max size
     fn is_valid(&self) -> bool {
         self.windows
             .windows(2)
-            .all(|w| w[0].end <= w[1].start && w[0].size() <= MAX_SESSION_SIZE)
+            .all(|w| w[0].end <= w[1].start && w[0].size() <= self.max_size)
     }
 }
 
@@ -315,10 +330,15 @@ impl<K: Key, T: Data, OutT: Data> SessionWindowFunc<K, T, OutT> {
         "SessionWindow".to_string()
     }
 
-    pub fn new(operation: WindowOperation<K, T, OutT>, gap_size: Duration) -> Self {
+    pub fn new(
+        operation: WindowOperation<K, T, OutT>,
+        gap_size: Duration,
+        max_size: Option<Duration>,
+    ) -> Self {
         Self {
             operation,
             gap_size,
+            max_size: max_size.unwrap_or(MAX_SESSION_SIZE),
             _t: PhantomData,
         }
     }
@@ -332,7 +352,7 @@ impl<K: Key, T: Data, OutT: Data> SessionWindowFunc<K, T, OutT> {
                 delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
                 write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
                 // we always write the largest end in the list of windows
-                retention_micros: MAX_SESSION_SIZE.as_micros() as u64,
+                retention_micros: self.max_size.as_micros() as u64,
             },
             TableDescriptor {
                 name: "s".to_string(),
@@ -361,11 +381,14 @@ impl<K: Key, T: Data, OutT: Data> SessionWindowFunc<K, T, OutT> {
 
         let mut windows = WindowGroup {
             windows: {
-                let t: KeyedState<'_, K, Vec<Window>, _> = ctx.state.get_key_state('s').await;
-                t.get(&key).map(|t| t.iter().map(|w| *w).collect())
+                let mut t: KeyedState<'_, K, Vec<Window>, _> = ctx.state.get_key_state('s').await;
+                t.get(&mut key)
+                    .await
+                    .map(|t| t.iter().map(|w| *w).collect())
             }
             .unwrap_or_default(),
             gap_size: self.gap_size,
+            max_size: self.max_size,
         };
 
         let result = windows.handle_event(timestamp);
@@ -401,7 +424,8 @@ impl<K: Key, T: Data, OutT: Data> SessionWindowFunc<K, T, OutT> {
             // get the actual window (as the timer one doesn't have the actual start time)
             let mut t: KeyedState<'_, K, Vec<Window>, _> = ctx.state.get_key_state('s').await;
             let mut windows: Vec<Window> = t
-                .get(&key)
+                .get(&mut key)
+                .await
                 .map(|t| t.iter().map(|w| *w).collect())
                 .expect("there must be a window for this key in state");
 
@@ -458,6 +482,7 @@ mod tests {
         let mut wg = WindowGroup {
             windows: vec![],
             gap_size: Duration::from_millis(100),
+            max_size: MAX_SESSION_SIZE,
         };
 
         // no existing windows, so we should create one
@@ -485,6 +510,7 @@ mod tests {
         let mut wg = WindowGroup {
             windows: vec![Window::session(from_millis(0), Duration::from_millis(100))],
             gap_size: Duration::from_millis(100),
+            max_size: MAX_SESSION_SIZE,
         };
 
         // we should extend the existing window which involves removing the timer and re-adding it
@@ -513,6 +539,7 @@ mod tests {
         let mut wg = WindowGroup {
             windows: vec![Window::session(from_millis(0), Duration::from_millis(100))],
             gap_size: Duration::from_millis(100),
+            max_size: MAX_SESSION_SIZE,
         };
 
         // this doesn't overlap with the existing window, so we should add a new one
@@ -555,6 +582,7 @@ mod tests {
                 },
             ],
             gap_size: Duration::from_millis(100),
+            max_size: MAX_SESSION_SIZE,
         };
 
         // this extends one window far enough that it merges with the next one
@@ -585,6 +613,7 @@ mod tests {
                 MAX_SESSION_SIZE - Duration::from_secs(3),
             )],
             gap_size: Duration::from_secs(10),
+            max_size: MAX_SESSION_SIZE,
         };
 
         // this would extend the window past the max size, so it should only be extended up to the max size
@@ -622,6 +651,7 @@ mod tests {
                 ),
             ],
             gap_size: Duration::from_secs(10),
+            max_size: MAX_SESSION_SIZE,
         };
 
         let start = from_millis(half_millis - 2000);
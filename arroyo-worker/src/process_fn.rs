@@ -20,7 +20,7 @@ impl ProcessFnUtils {
             .state
             .get_time_key_map(TIMER_TABLE, ctx.last_present_watermark())
             .await;
-        state.evict_all_before_watermark(watermark)
+        state.evict_all_before_watermark(watermark).await
     }
 
     pub async fn send_checkpoint_event<OutK: Key, OutT: Data>(
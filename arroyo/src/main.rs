@@ -3,7 +3,8 @@ use bollard::container::{CreateContainerOptions, LogOutput, LogsOptions, StartCo
 use bollard::image::CreateImageOptions;
 use bollard::models::{ContainerStateStatusEnum, HostConfig, PortBinding};
 use bollard::{container, Docker};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use serde_json::Value;
 use std::collections::HashMap;
 use std::io;
 use std::io::Write;
@@ -13,6 +14,11 @@ use tokio::signal::unix::{signal, SignalKind};
 use tokio_stream::StreamExt;
 
 const CONTAINER_NAME: &str = "arroyo-cli-single";
+const DEFAULT_API_ENDPOINT: &str = "http://localhost:8000/api";
+// the types that the API can decode the keys and values of state tables as
+const STATE_TYPES: [&str; 9] = [
+    "bool", "i32", "i64", "u32", "u64", "f32", "f64", "string", "bytes",
+];
 
 #[derive(Parser)]
 #[command(version, about)]
@@ -36,6 +42,62 @@ enum Commands {
 
     /// Stops a running Arroyo cluster
     Stop {},
+
+    /// Inspects the state stored in a job's checkpoint
+    Checkpoint {
+        #[command(subcommand)]
+        command: CheckpointCommands,
+    },
+}
+
+#[derive(Args)]
+struct CheckpointArgs {
+    /// The pipeline the job belongs to
+    pipeline_id: String,
+
+    /// The job that took the checkpoint
+    job_id: String,
+
+    /// The epoch of the checkpoint
+    epoch: u32,
+
+    /// The base URL of the Arroyo API
+    #[arg(long, default_value = DEFAULT_API_ENDPOINT)]
+    api_endpoint: String,
+}
+
+#[derive(Subcommand)]
+enum CheckpointCommands {
+    /// Lists the state tables of each operator, with their sizes
+    Tables {
+        #[command(flatten)]
+        checkpoint: CheckpointArgs,
+    },
+
+    /// Prints the rows of a table as JSON, one per line, with their keys and values hex-encoded
+    /// and, if their types are given, decoded
+    Dump {
+        #[command(flatten)]
+        checkpoint: CheckpointArgs,
+
+        /// The operator that owns the table
+        operator_id: String,
+
+        /// The name of the table
+        table: String,
+
+        /// Only print the rows for this key, which is hex-encoded unless --key-type is given
+        #[arg(short, long)]
+        key: Option<String>,
+
+        /// Decodes the keys of the rows as this type
+        #[arg(long, value_parser = STATE_TYPES)]
+        key_type: Option<String>,
+
+        /// Decodes the values of the rows as this type
+        #[arg(long, value_parser = STATE_TYPES)]
+        value_type: Option<String>,
+    },
 }
 
 #[tokio::main]
@@ -45,6 +107,7 @@ pub async fn main() {
     let result = match &cli.command {
         Commands::Start { tag, daemon } => start(tag.clone(), *daemon).await,
         Commands::Stop {} => stop().await,
+        Commands::Checkpoint { command } => inspect_checkpoint(command).await,
     };
 
     if let Err(e) = result {
@@ -314,3 +377,105 @@ async fn stop() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+async fn get_json(url: &str) -> Result<Value> {
+    let response = reqwest::get(url)
+        .await
+        .with_context(|| format!("Failed to reach the API at {}", url))?;
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        bail!("API request failed with status {}: {}", status, body);
+    }
+    serde_json::from_str(&body).context("API returned invalid JSON")
+}
+
+fn checkpoint_url(checkpoint: &CheckpointArgs) -> String {
+    format!(
+        "{}/v1/pipelines/{}/jobs/{}/checkpoints/{}",
+        checkpoint.api_endpoint.trim_end_matches('/'),
+        checkpoint.pipeline_id,
+        checkpoint.job_id,
+        checkpoint.epoch
+    )
+}
+
+fn format_optional(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        v => v.to_string(),
+    }
+}
+
+async fn inspect_checkpoint(command: &CheckpointCommands) -> Result<()> {
+    match command {
+        CheckpointCommands::Tables { checkpoint } => {
+            let operators = get_json(&format!("{}/tables", checkpoint_url(checkpoint))).await?;
+            let operators: Vec<_> = operators["data"].as_array().into_iter().flatten().collect();
+
+            println!(
+                "{:<40} {:<6} {:<16} {:>6} {:>12} {:>12}  {}",
+                "OPERATOR", "TABLE", "TYPE", "FILES", "BYTES", "ROWS", "DESCRIPTION"
+            );
+            for operator in &operators {
+                for table in operator["tables"].as_array().into_iter().flatten() {
+                    println!(
+                        "{:<40} {:<6} {:<16} {:>6} {:>12} {:>12}  {}",
+                        operator["operatorId"].as_str().unwrap_or_default(),
+                        table["name"].as_str().unwrap_or_default(),
+                        table["tableType"].as_str().unwrap_or_default(),
+                        format_optional(&table["files"]),
+                        format_optional(&table["bytes"]),
+                        format_optional(&table["rows"]),
+                        table["description"].as_str().unwrap_or_default(),
+                    );
+                }
+            }
+
+            let unreadable: Vec<_> = operators
+                .iter()
+                .filter(|operator| operator["readable"] == Value::Bool(false))
+                .filter_map(|operator| operator["operatorId"].as_str())
+                .collect();
+            if !unreadable.is_empty() {
+                println!(
+                    "\nThese operators were checkpointed by the disk state backend, so their \
+                    tables can't be dumped: {}",
+                    unreadable.join(", ")
+                );
+            }
+        }
+        CheckpointCommands::Dump {
+            checkpoint,
+            operator_id,
+            table,
+            key,
+            key_type,
+            value_type,
+        } => {
+            let mut url = reqwest::Url::parse(&format!(
+                "{}/operators/{}/tables/{}",
+                checkpoint_url(checkpoint),
+                operator_id,
+                table
+            ))
+            .context("Invalid API endpoint")?;
+            for (name, value) in [
+                ("key", key),
+                ("key_type", key_type),
+                ("value_type", value_type),
+            ] {
+                if let Some(value) = value {
+                    url.query_pairs_mut().append_pair(name, value);
+                }
+            }
+
+            let entries = get_json(url.as_str()).await?;
+            for entry in entries["data"].as_array().into_iter().flatten() {
+                println!("{}", entry);
+            }
+        }
+    }
+
+    Ok(())
+}
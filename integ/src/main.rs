@@ -194,6 +194,7 @@ pub async fn main() {
                 source_name
             ),
             udfs: None,
+            savepoint_id: None,
         },
     )
     .await
@@ -234,6 +235,8 @@ pub async fn main() {
             checkpoint_interval_micros: None,
             parallelism: None,
             stop: Some(Some(StopType::Checkpoint)),
+            query: None,
+            udfs: None,
         },
     )
     .await